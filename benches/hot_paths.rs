@@ -0,0 +1,149 @@
+//! `cargo bench` runs everything against the in-memory repositories; the
+//! database benchmarks also run when `DATABASE_URL` is set, against a
+//! `my_todo_bench` database created next to it and seeded on first use.
+
+use std::{env, str::FromStr};
+
+use axum::{body::Body, http::Request, Router};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use hyper::StatusCode;
+use my_todo::{
+    cli::seed_generated,
+    config::Config,
+    create_app, database,
+    repositories::{
+        label::{LabelRepository, LabelRepositoryForDb, LabelRepositoryForMemory},
+        todo::{TodoReader, TodoRepositoryForDb, TodoRepositoryForMemory, TodoWriter},
+        user::UserRepositoryForMemory,
+    },
+};
+use rand::{rngs::StdRng, SeedableRng};
+use sqlx::{postgres::PgConnectOptions, ConnectOptions, Executor, PgPool};
+use tokio::runtime::Runtime;
+use tower::ServiceExt;
+
+const TODOS: u32 = 10_000;
+const LABELS: u32 = 50;
+const BENCH_DATABASE: &str = "my_todo_bench";
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+async fn seed<T: TodoWriter, L: LabelRepository>(todos: &T, labels: &L) {
+    let mut rng = StdRng::seed_from_u64(42);
+    seed_generated(todos, labels, TODOS, LABELS, &mut rng)
+        .await
+        .expect("failed seed");
+}
+
+async fn memory_repositories() -> (TodoRepositoryForMemory, LabelRepositoryForMemory) {
+    let labels = LabelRepositoryForMemory::new();
+    let todos = TodoRepositoryForMemory::with_labels(labels.clone());
+    seed(&todos, &labels).await;
+    (todos, labels)
+}
+
+/// `None` without a `DATABASE_URL` to derive the bench database from.
+async fn bench_database() -> Option<PgPool> {
+    dotenv::dotenv().ok();
+    let url = env::var("DATABASE_URL").ok()?;
+    let mut conn = PgConnectOptions::from_str(&url)
+        .expect("invalid DATABASE_URL")
+        .connect()
+        .await
+        .expect("failed connect database");
+    let exists: Option<(i32,)> = sqlx::query_as("select 1 from pg_database where datname = $1")
+        .bind(BENCH_DATABASE)
+        .fetch_optional(&mut conn)
+        .await
+        .unwrap();
+    if exists.is_none() {
+        conn.execute(format!("create database {}", BENCH_DATABASE).as_str())
+            .await
+            .expect("failed create bench database");
+    }
+
+    let options = PgConnectOptions::from_str(&url)
+        .unwrap()
+        .database(BENCH_DATABASE);
+    let pool = PgPool::connect_with(options)
+        .await
+        .expect("failed connect database");
+    database::migrate(&pool).await.expect("failed migrate");
+    let todos = TodoRepositoryForDb::new(pool.clone());
+    if todos.count().await.unwrap() < TODOS as i64 {
+        sqlx::query("truncate todos, labels cascade")
+            .execute(&pool)
+            .await
+            .unwrap();
+        seed(&todos, &LabelRepositoryForDb::new(pool.clone())).await;
+    }
+    Some(pool)
+}
+
+fn repository_all(c: &mut Criterion) {
+    let rt = runtime();
+    let mut group = c.benchmark_group("all");
+    group.throughput(Throughput::Elements(TODOS as u64));
+
+    let (memory, _) = rt.block_on(memory_repositories());
+    group.bench_function(BenchmarkId::new("memory", TODOS), |b| {
+        b.to_async(&rt).iter(|| memory.all())
+    });
+    if let Some(pool) = rt.block_on(bench_database()) {
+        let db = TodoRepositoryForDb::new(pool);
+        group.bench_function(BenchmarkId::new("db", TODOS), |b| {
+            b.to_async(&rt).iter(|| db.all())
+        });
+    }
+    group.finish();
+}
+
+fn serialize_list(c: &mut Criterion) {
+    let rt = runtime();
+    let (memory, _) = rt.block_on(memory_repositories());
+    let todos = rt.block_on(memory.all()).unwrap();
+
+    let mut group = c.benchmark_group("serialize");
+    group.throughput(Throughput::Elements(todos.len() as u64));
+    group.bench_function(BenchmarkId::new("todos", todos.len()), |b| {
+        b.iter(|| serde_json::to_vec(&todos).unwrap())
+    });
+    group.finish();
+}
+
+async fn get(app: &Router, uri: &str) {
+    let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+    let res = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    hyper::body::to_bytes(res.into_body()).await.unwrap();
+}
+
+fn request_handling(c: &mut Criterion) {
+    let rt = runtime();
+    let (todos, labels) = rt.block_on(memory_repositories());
+    // building the app spawns the outbox relay
+    let app = rt.block_on(async {
+        create_app(
+            todos,
+            labels,
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        )
+    });
+
+    let mut group = c.benchmark_group("request");
+    for uri in ["/todos", "/todos?limit=50", "/todos/1"] {
+        group.bench_with_input(BenchmarkId::new("GET", uri), uri, |b, uri| {
+            b.to_async(&rt).iter(|| get(&app, uri))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, repository_all, serialize_list, request_handling);
+criterion_main!(benches);
@@ -0,0 +1,10 @@
+CREATE TABLE todo_revisions
+(
+    id         SERIAL PRIMARY KEY,
+    todo_id    INTEGER     NOT NULL REFERENCES todos (id) ON DELETE CASCADE,
+    rev        INTEGER     NOT NULL,
+    text       TEXT        NOT NULL,
+    completed  BOOLEAN     NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (todo_id, rev)
+);
@@ -0,0 +1,4 @@
+-- accounts created through OAuth are identified by their verified email and
+-- may have no password
+ALTER TABLE users ADD COLUMN email TEXT UNIQUE;
+ALTER TABLE users ALTER COLUMN password_hash DROP NOT NULL;
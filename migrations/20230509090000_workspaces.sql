@@ -0,0 +1,28 @@
+CREATE TABLE workspaces
+(
+    id         SERIAL PRIMARY KEY,
+    name       TEXT        NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- todos and labels from before workspaces live in the default one, which
+-- stays open to every client
+INSERT INTO workspaces (id, name) VALUES (1, 'default');
+SELECT setval(pg_get_serial_sequence('workspaces', 'id'), 1);
+
+CREATE TABLE workspace_members
+(
+    workspace_id INTEGER     NOT NULL REFERENCES workspaces (id) ON DELETE CASCADE,
+    user_id      INTEGER     NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    role         TEXT        NOT NULL,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (workspace_id, user_id)
+);
+
+ALTER TABLE todos ADD COLUMN workspace_id INTEGER NOT NULL DEFAULT 1 REFERENCES workspaces (id);
+ALTER TABLE todos ALTER COLUMN workspace_id DROP DEFAULT;
+CREATE INDEX todos_workspace_id_idx ON todos (workspace_id);
+
+ALTER TABLE labels ADD COLUMN workspace_id INTEGER NOT NULL DEFAULT 1 REFERENCES workspaces (id);
+ALTER TABLE labels ALTER COLUMN workspace_id DROP DEFAULT;
+CREATE INDEX labels_workspace_id_idx ON labels (workspace_id);
@@ -0,0 +1,6 @@
+ALTER TABLE todos ADD COLUMN owner_id INTEGER REFERENCES users (id) ON DELETE SET NULL;
+ALTER TABLE todos ADD COLUMN assignee_id INTEGER REFERENCES users (id) ON DELETE SET NULL;
+CREATE INDEX todos_assignee_id_idx ON todos (assignee_id);
+
+-- when set, only a todo's owner, its assignee and workspace admins change it
+ALTER TABLE workspaces ADD COLUMN assignees_only BOOLEAN NOT NULL DEFAULT false;
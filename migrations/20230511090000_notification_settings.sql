@@ -0,0 +1,2 @@
+-- NULL until the user changes them; the application supplies the defaults
+ALTER TABLE users ADD COLUMN notification_settings JSONB;
@@ -0,0 +1,4 @@
+-- 'admin' accounts manage every other account through /admin/users
+ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'user';
+-- disabled accounts can not log in; their sessions and refresh tokens are gone
+ALTER TABLE users ADD COLUMN disabled_at TIMESTAMPTZ;
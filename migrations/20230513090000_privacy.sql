@@ -0,0 +1,25 @@
+-- outlives the accounts it mentions, so it references none of them
+CREATE TABLE audit_log
+(
+    id         SERIAL PRIMARY KEY,
+    user_id    INTEGER     NOT NULL,
+    -- who acted, when not the user themselves; NULL for the system
+    actor_id   INTEGER,
+    action     TEXT        NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX audit_log_user_id_idx ON audit_log (user_id);
+
+CREATE TABLE data_exports
+(
+    id           SERIAL PRIMARY KEY,
+    user_id      INTEGER     NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    status       TEXT        NOT NULL,
+    archive      JSONB,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now(),
+    completed_at TIMESTAMPTZ
+);
+
+-- set while a requested deletion waits out its grace period
+ALTER TABLE users ADD COLUMN delete_after TIMESTAMPTZ;
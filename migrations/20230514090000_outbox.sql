@@ -0,0 +1,14 @@
+-- domain events written with the todo change they describe, relayed to the
+-- event bus afterwards
+CREATE TABLE outbox
+(
+    id            BIGSERIAL PRIMARY KEY,
+    workspace_id  INTEGER     NOT NULL,
+    event         JSONB       NOT NULL,
+    created_at    TIMESTAMPTZ NOT NULL DEFAULT now(),
+    -- a relay holds the entry until then; it is up for grabs again afterwards
+    claimed_until TIMESTAMPTZ,
+    sent_at       TIMESTAMPTZ
+);
+
+CREATE INDEX outbox_unsent_idx ON outbox (id) WHERE sent_at IS NULL;
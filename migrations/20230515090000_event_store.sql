@@ -0,0 +1,24 @@
+-- todos kept as append-only event streams, used with
+-- database.todo_storage = "event_store"
+CREATE TABLE todo_streams
+(
+    id           SERIAL PRIMARY KEY,
+    workspace_id INTEGER     NOT NULL REFERENCES workspaces (id),
+    -- the stream folded up to snapshot_seq, so reads only fold what follows
+    snapshot     JSONB,
+    snapshot_seq INTEGER     NOT NULL DEFAULT 0,
+    -- set together with the stream's deleted event, so reads can skip it
+    deleted      BOOLEAN     NOT NULL DEFAULT false,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX todo_streams_workspace_id_idx ON todo_streams (workspace_id) WHERE NOT deleted;
+
+CREATE TABLE todo_events
+(
+    todo_id    INTEGER     NOT NULL REFERENCES todo_streams (id),
+    seq        INTEGER     NOT NULL,
+    event      JSONB       NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (todo_id, seq)
+);
@@ -0,0 +1,2 @@
+-- translations of `text`, keyed by language tag
+ALTER TABLE todos ADD COLUMN text_i18n JSONB NOT NULL DEFAULT '{}';
@@ -0,0 +1,12 @@
+CREATE TABLE saved_views
+(
+    id           SERIAL PRIMARY KEY,
+    workspace_id INTEGER     NOT NULL REFERENCES workspaces (id) ON DELETE CASCADE,
+    user_id      INTEGER     NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name         TEXT        NOT NULL,
+    -- kept as written; parsed again whenever the view is listed
+    filter       TEXT        NOT NULL,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX saved_views_user_id_idx ON saved_views (user_id, workspace_id);
@@ -0,0 +1,2 @@
+-- strict_word_similarity for fuzzy search over todo text
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
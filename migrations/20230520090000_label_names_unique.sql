@@ -0,0 +1,17 @@
+-- labels sharing a name within a workspace are folded into the oldest one
+-- before names are made unique
+UPDATE todo_labels SET label_id = kept.id
+FROM labels AS duplicate,
+     (SELECT workspace_id, name, min(id) AS id FROM labels GROUP BY workspace_id, name) AS kept
+WHERE todo_labels.label_id = duplicate.id
+  AND duplicate.workspace_id = kept.workspace_id
+  AND duplicate.name = kept.name
+  AND duplicate.id <> kept.id;
+DELETE FROM todo_labels AS later USING todo_labels AS earlier
+WHERE later.todo_id = earlier.todo_id
+  AND later.label_id = earlier.label_id
+  AND later.id > earlier.id;
+DELETE FROM labels
+WHERE id NOT IN (SELECT min(id) FROM labels GROUP BY workspace_id, name);
+
+CREATE UNIQUE INDEX labels_workspace_id_name_idx ON labels (workspace_id, name);
@@ -0,0 +1,2 @@
+-- GET /labels/suggest matches names by lowercase prefix
+CREATE INDEX labels_workspace_id_lower_name_idx ON labels (workspace_id, lower(name) text_pattern_ops);
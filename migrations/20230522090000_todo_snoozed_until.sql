@@ -0,0 +1,5 @@
+-- Snoozed todos stay out of default listings until then; the partial
+-- index serves the scheduler waking the ones whose snooze is over.
+ALTER TABLE todos ADD COLUMN snoozed_until TIMESTAMPTZ;
+
+CREATE INDEX todos_snoozed_until_idx ON todos (snoozed_until) WHERE snoozed_until IS NOT NULL;
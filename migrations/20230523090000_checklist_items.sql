@@ -0,0 +1,11 @@
+-- the steps of a todo, kept in order; position counts from 0 without gaps
+CREATE TABLE checklist_items
+(
+    id        SERIAL PRIMARY KEY,
+    todo_id   INTEGER NOT NULL REFERENCES todos (id) ON DELETE CASCADE,
+    text      TEXT    NOT NULL,
+    completed BOOLEAN NOT NULL DEFAULT false,
+    position  INTEGER NOT NULL
+);
+
+CREATE INDEX checklist_items_todo_id_idx ON checklist_items (todo_id, position);
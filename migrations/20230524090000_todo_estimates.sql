@@ -0,0 +1,4 @@
+-- minutes a todo is expected to take, kept with each revision so past days
+-- can be charted
+ALTER TABLE todos ADD COLUMN estimate_minutes INTEGER;
+ALTER TABLE todo_revisions ADD COLUMN estimate_minutes INTEGER;
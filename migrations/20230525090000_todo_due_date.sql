@@ -0,0 +1,4 @@
+-- the day a todo should be done by; the index serves calendar ranges
+ALTER TABLE todos ADD COLUMN due_date DATE;
+
+CREATE INDEX todos_due_date_idx ON todos (workspace_id, due_date) WHERE due_date IS NOT NULL;
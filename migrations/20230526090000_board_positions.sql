@@ -0,0 +1,11 @@
+-- where a todo sits within its board column; no foreign key on todo_id, as
+-- the event store keeps its todos in todo_streams. Rows of deleted todos are
+-- never read back, since ids are not reused.
+CREATE TABLE board_positions
+(
+    todo_id      INTEGER PRIMARY KEY,
+    workspace_id INTEGER NOT NULL REFERENCES workspaces (id),
+    position     INTEGER NOT NULL
+);
+
+CREATE INDEX board_positions_workspace_id_idx ON board_positions (workspace_id);
@@ -0,0 +1,12 @@
+CREATE TABLE slack_links
+(
+    workspace_id  INTEGER     NOT NULL REFERENCES workspaces (id) ON DELETE CASCADE,
+    user_id       INTEGER     NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    webhook_url   TEXT        NOT NULL,
+    team_id       TEXT,
+    slack_user_id TEXT,
+    created_at    TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_id, workspace_id),
+    -- the `/todo` command finds its user and workspace by these
+    UNIQUE (team_id, slack_user_id)
+);
@@ -0,0 +1,16 @@
+CREATE TABLE telegram_link_codes
+(
+    -- SHA-256 of the code; the code itself is shown once
+    code_hash    TEXT PRIMARY KEY,
+    workspace_id INTEGER     NOT NULL REFERENCES workspaces (id) ON DELETE CASCADE,
+    user_id      INTEGER     NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    expires_at   TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE telegram_links
+(
+    chat_id      BIGINT PRIMARY KEY,
+    workspace_id INTEGER     NOT NULL REFERENCES workspaces (id) ON DELETE CASCADE,
+    user_id      INTEGER     NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,2 @@
+-- long-form Markdown notes next to the short text
+ALTER TABLE todos ADD COLUMN description TEXT;
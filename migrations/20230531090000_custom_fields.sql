@@ -0,0 +1,3 @@
+-- fields a workspace defines for its todos, and the todos' values for them
+ALTER TABLE workspaces ADD COLUMN custom_fields JSONB NOT NULL DEFAULT '[]';
+ALTER TABLE todos ADD COLUMN custom JSONB NOT NULL DEFAULT '{}';
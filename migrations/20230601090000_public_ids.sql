@@ -0,0 +1,5 @@
+-- ids that can not be guessed, for `features.uuid_ids`; the app hands out
+-- time-ordered v7 ones, rows from before get random ones
+ALTER TABLE todos ADD COLUMN uuid UUID NOT NULL UNIQUE DEFAULT gen_random_uuid();
+ALTER TABLE labels ADD COLUMN uuid UUID NOT NULL UNIQUE DEFAULT gen_random_uuid();
+ALTER TABLE todo_streams ADD COLUMN uuid UUID NOT NULL UNIQUE DEFAULT gen_random_uuid();
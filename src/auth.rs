@@ -0,0 +1,98 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+use crate::{
+    handlers::error::ApiError,
+    middleware::access_log::LogFields,
+    repositories::{label::LabelRepository, todo::TodoRepository, user::UserRepository},
+    state::AppState,
+};
+
+pub mod csrf;
+pub mod lockout;
+pub mod oauth;
+pub mod password;
+pub mod refresh;
+pub mod session;
+pub mod token;
+
+/// The caller identified by an `Authorization: Bearer <access token>` header
+/// or, failing that, a session cookie; handlers taking it answer 401 without
+/// either.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuthUser {
+    pub id: i32,
+}
+
+#[async_trait]
+impl<T, L, U> FromRequestParts<AppState<T, L, U>> for AuthUser
+where
+    T: TodoRepository,
+    L: LabelRepository,
+    U: UserRepository,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState<T, L, U>,
+    ) -> Result<Self, Self::Rejection> {
+        let user = Self::authenticate(parts, state).await?;
+        if let Some(fields) = parts.extensions.get::<LogFields>() {
+            fields.record_user(user.id);
+        }
+        Ok(user)
+    }
+}
+
+impl AuthUser {
+    async fn authenticate<T, L, U>(
+        parts: &Parts,
+        state: &AppState<T, L, U>,
+    ) -> Result<Self, ApiError>
+    where
+        T: TodoRepository,
+        L: LabelRepository,
+        U: UserRepository,
+    {
+        let bearer = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if let Some(token) = bearer {
+            return state
+                .tokens
+                .verify(token.trim())
+                .map(|id| AuthUser { id })
+                .ok_or_else(unauthorized);
+        }
+
+        match session::session_id(&parts.headers) {
+            Some(session_id) => state
+                .user_repository
+                .session_user(session_id)
+                .await?
+                .map(|id| AuthUser { id })
+                .ok_or_else(unauthorized),
+            None => Err(unauthorized()),
+        }
+    }
+}
+
+pub fn unauthorized() -> ApiError {
+    ApiError::new(StatusCode::UNAUTHORIZED, "missing or invalid access token")
+}
+
+/// 256 random bits, URL-safe so it needs no quoting in a cookie or JSON.
+pub fn random_token() -> String {
+    let mut bytes = [0; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
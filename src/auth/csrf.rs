@@ -0,0 +1,70 @@
+use std::convert::Infallible;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, HeaderMap, HeaderName},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use sha2::{Digest, Sha256};
+
+use super::session;
+
+/// Carries the token on requests from scripts.
+pub static CSRF_HEADER: HeaderName = HeaderName::from_static("x-csrf-token");
+/// Carries the token on plain form posts.
+pub const CSRF_FIELD: &str = "csrf_token";
+
+/// The token for a session, derived from its id so nothing needs storing.
+/// The session cookie is HttpOnly, so another site can neither read the id
+/// nor work out the token.
+pub fn token(session_id: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(b"csrf:");
+    hasher.update(session_id.as_bytes());
+    URL_SAFE_NO_PAD.encode(hasher.finalize())
+}
+
+/// The token for the session cookie the request carries, if any.
+pub fn request_token(headers: &HeaderMap) -> Option<String> {
+    session::session_id(headers).map(token)
+}
+
+/// The request's token for pages to embed in their forms; `None` without
+/// a session cookie, when forms need none.
+#[derive(Debug, Clone)]
+pub struct CsrfToken(pub Option<String>);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for CsrfToken {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(request_token(&parts.headers)))
+    }
+}
+
+/// Compares in constant time, so timing tells nothing about the token.
+pub fn matches(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_derive_token_per_session() {
+        let token = token("session-a");
+        assert_eq!(token, super::token("session-a"));
+        assert_ne!(token, super::token("session-b"));
+        assert!(matches(&token, &super::token("session-a")));
+        assert!(!matches(&token, &super::token("session-b")));
+        assert!(!matches(&token, ""));
+    }
+}
@@ -0,0 +1,376 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::async_trait;
+
+use crate::config::LockoutConfig;
+
+/// Failures are counted per key until `window` passes without one. From
+/// `threshold` failures on, each further failure locks the key for `base`,
+/// doubled per extra failure and capped at `max`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockoutPolicy {
+    pub base: Duration,
+    pub max: Duration,
+    pub window: Duration,
+}
+
+impl LockoutPolicy {
+    fn lockout(&self, failures: u32, threshold: u32) -> Option<Duration> {
+        let extra = failures.checked_sub(threshold)?;
+        let factor = 2u32.checked_pow(extra).unwrap_or(u32::MAX);
+        Some(self.base.saturating_mul(factor).min(self.max))
+    }
+}
+
+impl From<&LockoutConfig> for LockoutPolicy {
+    fn from(config: &LockoutConfig) -> Self {
+        Self {
+            base: Duration::from_secs(config.base_secs),
+            max: Duration::from_secs(config.max_secs),
+            window: Duration::from_secs(config.window_secs),
+        }
+    }
+}
+
+#[async_trait]
+pub trait LockoutStore: Send + Sync + 'static {
+    /// Time left on the key's lockout.
+    async fn locked_for(&self, key: &str) -> anyhow::Result<Option<Duration>>;
+    /// Counts a failure and returns the lockout it started, if any.
+    async fn record_failure(
+        &self,
+        key: &str,
+        threshold: u32,
+        policy: &LockoutPolicy,
+    ) -> anyhow::Result<Option<Duration>>;
+    async fn reset(&self, key: &str) -> anyhow::Result<()>;
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Attempts {
+    failures: u32,
+    last_failure: Instant,
+    locked_until: Option<Instant>,
+}
+
+const MAX_MEMORY_KEYS: usize = 10_000;
+
+#[derive(Debug, Default)]
+pub struct MemoryLockoutStore {
+    attempts: Mutex<HashMap<String, Attempts>>,
+}
+
+impl MemoryLockoutStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn locked_for_at(&self, key: &str, now: Instant) -> Option<Duration> {
+        let attempts = self.attempts.lock().unwrap();
+        attempts
+            .get(key)
+            .and_then(|attempts| attempts.locked_until)
+            .and_then(|until| until.checked_duration_since(now))
+            .filter(|left| !left.is_zero())
+    }
+
+    fn record_failure_at(
+        &self,
+        key: &str,
+        threshold: u32,
+        policy: &LockoutPolicy,
+        now: Instant,
+    ) -> Option<Duration> {
+        let mut attempts = self.attempts.lock().unwrap();
+        if attempts.len() >= MAX_MEMORY_KEYS {
+            attempts.retain(|_, attempts| {
+                now.duration_since(attempts.last_failure) < policy.window
+                    || attempts.locked_until.is_some_and(|until| until > now)
+            });
+        }
+
+        let entry = attempts.entry(key.to_string()).or_insert(Attempts {
+            failures: 0,
+            last_failure: now,
+            locked_until: None,
+        });
+        if now.duration_since(entry.last_failure) >= policy.window {
+            entry.failures = 0;
+        }
+        entry.failures += 1;
+        entry.last_failure = now;
+
+        let lockout = policy.lockout(entry.failures, threshold);
+        if let Some(lockout) = lockout {
+            entry.locked_until = Some(now + lockout);
+        }
+        lockout
+    }
+}
+
+#[async_trait]
+impl LockoutStore for MemoryLockoutStore {
+    async fn locked_for(&self, key: &str) -> anyhow::Result<Option<Duration>> {
+        Ok(self.locked_for_at(key, Instant::now()))
+    }
+    async fn record_failure(
+        &self,
+        key: &str,
+        threshold: u32,
+        policy: &LockoutPolicy,
+    ) -> anyhow::Result<Option<Duration>> {
+        Ok(self.record_failure_at(key, threshold, policy, Instant::now()))
+    }
+    async fn reset(&self, key: &str) -> anyhow::Result<()> {
+        self.attempts.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "redis")]
+pub use self::redis_store::RedisLockoutStore;
+
+#[cfg(feature = "redis")]
+mod redis_store {
+    use super::*;
+    use redis::{aio::ConnectionManager, AsyncCommands, Script};
+    use tokio::sync::OnceCell;
+
+    /// Same counting as the memory store, evaluated atomically in Redis so
+    /// every instance sees one count per key.
+    const RECORD_FAILURE: &str = r#"
+        local threshold = tonumber(ARGV[1])
+        local base = tonumber(ARGV[2])
+        local max = tonumber(ARGV[3])
+        local window = tonumber(ARGV[4])
+        local now = tonumber(ARGV[5])
+        local entry = redis.call('HMGET', KEYS[1], 'failures', 'last')
+        local failures = tonumber(entry[1]) or 0
+        local last = tonumber(entry[2]) or now
+        if now - last >= window then
+            failures = 0
+        end
+        failures = failures + 1
+        local lockout = 0
+        if failures >= threshold then
+            lockout = math.min(base * 2 ^ (failures - threshold), max)
+            redis.call('HSET', KEYS[1], 'locked_until', now + lockout)
+        end
+        redis.call('HSET', KEYS[1], 'failures', failures, 'last', now)
+        redis.call('PEXPIRE', KEYS[1], window + max)
+        return lockout
+    "#;
+
+    pub struct RedisLockoutStore {
+        client: redis::Client,
+        connection: OnceCell<ConnectionManager>,
+        script: Script,
+    }
+
+    impl RedisLockoutStore {
+        pub fn new(url: &str) -> anyhow::Result<Self> {
+            Ok(Self {
+                client: redis::Client::open(url)?,
+                connection: OnceCell::new(),
+                script: Script::new(RECORD_FAILURE),
+            })
+        }
+
+        async fn connection(&self) -> anyhow::Result<ConnectionManager> {
+            Ok(self
+                .connection
+                .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+                .await?
+                .clone())
+        }
+    }
+
+    fn now_ms() -> anyhow::Result<u64> {
+        Ok(std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_millis() as u64)
+    }
+
+    fn redis_key(key: &str) -> String {
+        format!("lockout:{}", key)
+    }
+
+    #[async_trait]
+    impl LockoutStore for RedisLockoutStore {
+        async fn locked_for(&self, key: &str) -> anyhow::Result<Option<Duration>> {
+            let mut connection = self.connection().await?;
+            let until: Option<u64> = connection.hget(redis_key(key), "locked_until").await?;
+            let now = now_ms()?;
+            Ok(until
+                .filter(|until| *until > now)
+                .map(|until| Duration::from_millis(until - now)))
+        }
+        async fn record_failure(
+            &self,
+            key: &str,
+            threshold: u32,
+            policy: &LockoutPolicy,
+        ) -> anyhow::Result<Option<Duration>> {
+            let mut connection = self.connection().await?;
+            let lockout_ms: u64 = self
+                .script
+                .key(redis_key(key))
+                .arg(threshold)
+                .arg(policy.base.as_millis() as u64)
+                .arg(policy.max.as_millis() as u64)
+                .arg(policy.window.as_millis() as u64)
+                .arg(now_ms()?)
+                .invoke_async(&mut connection)
+                .await?;
+
+            Ok((lockout_ms > 0).then(|| Duration::from_millis(lockout_ms)))
+        }
+        async fn reset(&self, key: &str) -> anyhow::Result<()> {
+            let mut connection = self.connection().await?;
+            connection.del::<_, ()>(redis_key(key)).await?;
+            Ok(())
+        }
+    }
+}
+
+/// Login failure tracking per account and per client address.
+#[derive(Clone)]
+pub struct Lockout {
+    store: Arc<dyn LockoutStore>,
+    policy: LockoutPolicy,
+    account_failures: u32,
+    ip_failures: u32,
+}
+
+/// What a login attempt is counted against.
+#[derive(Debug, Clone, Copy)]
+pub enum LockoutKey<'a> {
+    Account(&'a str),
+    Ip(std::net::IpAddr),
+}
+
+impl LockoutKey<'_> {
+    fn key(&self) -> String {
+        match self {
+            LockoutKey::Account(username) => format!("account:{}", username.to_lowercase()),
+            LockoutKey::Ip(ip) => format!("ip:{}", ip),
+        }
+    }
+}
+
+impl Lockout {
+    pub fn new(store: Arc<dyn LockoutStore>, config: &LockoutConfig) -> Self {
+        Self {
+            store,
+            policy: LockoutPolicy::from(config),
+            account_failures: config.account_failures,
+            ip_failures: config.ip_failures,
+        }
+    }
+
+    pub fn from_config(config: &LockoutConfig) -> anyhow::Result<Self> {
+        let store: Arc<dyn LockoutStore> = match &config.redis_url {
+            #[cfg(feature = "redis")]
+            Some(url) => Arc::new(RedisLockoutStore::new(url)?),
+            #[cfg(not(feature = "redis"))]
+            Some(_) => anyhow::bail!("auth.lockout.redis_url needs the `redis` feature"),
+            None => Arc::new(MemoryLockoutStore::new()),
+        };
+
+        Ok(Self::new(store, config))
+    }
+
+    fn threshold(&self, key: &LockoutKey) -> u32 {
+        match key {
+            LockoutKey::Account(_) => self.account_failures,
+            LockoutKey::Ip(_) => self.ip_failures,
+        }
+    }
+
+    /// The longest lockout left on any of the keys. An unavailable store
+    /// locks nobody out.
+    pub async fn locked_for(&self, keys: &[LockoutKey<'_>]) -> Option<Duration> {
+        let mut longest = None;
+        for key in keys {
+            match self.store.locked_for(&key.key()).await {
+                Ok(left) => longest = longest.max(left),
+                Err(e) => tracing::warn!("lockout store failed: {:?}", e),
+            }
+        }
+        longest
+    }
+
+    pub async fn record_failure(&self, keys: &[LockoutKey<'_>]) {
+        for key in keys {
+            let threshold = self.threshold(key);
+            match self
+                .store
+                .record_failure(&key.key(), threshold, &self.policy)
+                .await
+            {
+                Ok(Some(lockout)) => tracing::warn!(
+                    target: "security",
+                    key = %key.key(),
+                    lockout_secs = lockout.as_secs(),
+                    "login locked after repeated failures"
+                ),
+                Ok(None) => {}
+                Err(e) => tracing::warn!("lockout store failed: {:?}", e),
+            }
+        }
+    }
+
+    pub async fn reset(&self, key: LockoutKey<'_>) {
+        if let Err(e) = self.store.reset(&key.key()).await {
+            tracing::warn!("lockout store failed: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_lock_out_exponentially() {
+        let store = MemoryLockoutStore::new();
+        let policy = LockoutPolicy {
+            base: Duration::from_secs(30),
+            max: Duration::from_secs(100),
+            window: Duration::from_secs(900),
+        };
+        let start = Instant::now();
+
+        assert_eq!(store.record_failure_at("a", 3, &policy, start), None);
+        assert_eq!(store.record_failure_at("a", 3, &policy, start), None);
+        assert_eq!(
+            store.record_failure_at("a", 3, &policy, start),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            store.locked_for_at("a", start),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(store.locked_for_at("b", start), None);
+
+        let later = start + Duration::from_secs(30);
+        assert_eq!(store.locked_for_at("a", later), None);
+        assert_eq!(
+            store.record_failure_at("a", 3, &policy, later),
+            Some(Duration::from_secs(60))
+        );
+        // capped at `max`
+        assert_eq!(
+            store.record_failure_at("a", 3, &policy, later),
+            Some(Duration::from_secs(100))
+        );
+
+        // a quiet window starts the count over
+        let much_later = later + Duration::from_secs(1000);
+        assert_eq!(store.record_failure_at("a", 3, &policy, much_later), None);
+    }
+}
@@ -0,0 +1,241 @@
+use std::fmt;
+
+use axum::http::{HeaderMap, HeaderValue};
+use reqwest::{header::ACCEPT, Url};
+use serde::Deserialize;
+
+use crate::config::{AuthConfig, OAuthClientConfig};
+
+use super::session::cookie_value;
+
+pub const STATE_COOKIE_NAME: &str = "todo_oauth_state";
+/// Seconds a user has to finish the provider's consent screen.
+const STATE_MAX_AGE: u64 = 10 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    Github,
+    Google,
+}
+
+impl Provider {
+    fn authorize_url(self) -> &'static str {
+        match self {
+            Provider::Github => "https://github.com/login/oauth/authorize",
+            Provider::Google => "https://accounts.google.com/o/oauth2/v2/auth",
+        }
+    }
+
+    fn token_url(self) -> &'static str {
+        match self {
+            Provider::Github => "https://github.com/login/oauth/access_token",
+            Provider::Google => "https://oauth2.googleapis.com/token",
+        }
+    }
+
+    fn scope(self) -> &'static str {
+        match self {
+            Provider::Github => "user:email",
+            Provider::Google => "openid email",
+        }
+    }
+}
+
+impl fmt::Display for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Provider::Github => "github",
+            Provider::Google => "google",
+        })
+    }
+}
+
+/// Authorization-code flow against the configured providers.
+#[derive(Debug, Clone)]
+pub struct OAuthClients {
+    http: reqwest::Client,
+    public_url: String,
+    github: Option<OAuthClientConfig>,
+    google: Option<OAuthClientConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GithubEmail {
+    email: String,
+    primary: bool,
+    verified: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct GoogleUserInfo {
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: bool,
+}
+
+impl OAuthClients {
+    pub fn from_config(config: &AuthConfig) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            public_url: config.public_url.trim_end_matches('/').to_string(),
+            github: config.github.clone(),
+            google: config.google.clone(),
+        }
+    }
+
+    pub fn is_configured(&self, provider: Provider) -> bool {
+        self.client(provider).is_some()
+    }
+
+    fn client(&self, provider: Provider) -> Option<&OAuthClientConfig> {
+        match provider {
+            Provider::Github => self.github.as_ref(),
+            Provider::Google => self.google.as_ref(),
+        }
+    }
+
+    fn redirect_uri(&self, provider: Provider) -> String {
+        format!("{}/auth/oauth/{}/callback", self.public_url, provider)
+    }
+
+    /// Where to send the browser; `None` when the provider is not configured.
+    pub fn authorize_url(&self, provider: Provider, state: &str) -> Option<Url> {
+        let client = self.client(provider)?;
+        let url = Url::parse_with_params(
+            provider.authorize_url(),
+            [
+                ("response_type", "code"),
+                ("client_id", client.client_id.as_str()),
+                ("redirect_uri", self.redirect_uri(provider).as_str()),
+                ("scope", provider.scope()),
+                ("state", state),
+            ],
+        )
+        .expect("provider urls are valid");
+        Some(url)
+    }
+
+    /// Exchanges the callback's code and asks the provider for the user's
+    /// verified email, `None` when it has none.
+    pub async fn verified_email(
+        &self,
+        provider: Provider,
+        code: &str,
+    ) -> anyhow::Result<Option<String>> {
+        let client = self
+            .client(provider)
+            .ok_or_else(|| anyhow::anyhow!("oauth provider {} is not configured", provider))?;
+        let token: TokenResponse = self
+            .http
+            .post(provider.token_url())
+            .header(ACCEPT, "application/json")
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("client_id", client.client_id.as_str()),
+                ("client_secret", client.client_secret.as_str()),
+                ("redirect_uri", self.redirect_uri(provider).as_str()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        match provider {
+            Provider::Github => {
+                let emails: Vec<GithubEmail> = self
+                    .http
+                    .get("https://api.github.com/user/emails")
+                    .bearer_auth(&token.access_token)
+                    .header(ACCEPT, "application/vnd.github+json")
+                    // GitHub rejects requests without one
+                    .header(reqwest::header::USER_AGENT, "my-todo")
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                Ok(emails
+                    .into_iter()
+                    .find(|email| email.primary && email.verified)
+                    .map(|email| email.email))
+            }
+            Provider::Google => {
+                let info: GoogleUserInfo = self
+                    .http
+                    .get("https://openidconnect.googleapis.com/v1/userinfo")
+                    .bearer_auth(&token.access_token)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                Ok(info.email.filter(|_| info.email_verified))
+            }
+        }
+    }
+}
+
+/// Binds the callback to the browser that started the flow. SameSite=Lax
+/// because the provider's redirect back is a cross-site navigation.
+pub fn state_cookie(state: &str, config: &AuthConfig) -> HeaderValue {
+    let mut cookie = format!(
+        "{}={}; Path=/auth/oauth; Max-Age={}; HttpOnly; SameSite=Lax",
+        STATE_COOKIE_NAME, state, STATE_MAX_AGE
+    );
+    if config.cookie_secure {
+        cookie.push_str("; Secure");
+    }
+    HeaderValue::from_str(&cookie).expect("states are header safe")
+}
+
+pub fn clear_state_cookie(config: &AuthConfig) -> HeaderValue {
+    let mut cookie = format!(
+        "{}=; Path=/auth/oauth; Max-Age=0; HttpOnly; SameSite=Lax",
+        STATE_COOKIE_NAME
+    );
+    if config.cookie_secure {
+        cookie.push_str("; Secure");
+    }
+    HeaderValue::from_str(&cookie).expect("cookie is header safe")
+}
+
+pub fn state(headers: &HeaderMap) -> Option<&str> {
+    cookie_value(headers, STATE_COOKIE_NAME)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn should_build_authorize_url_for_configured_providers() {
+        let config = AuthConfig {
+            github: Some(OAuthClientConfig {
+                client_id: "client".to_string(),
+                client_secret: "secret".to_string(),
+            }),
+            ..Config::default().auth
+        };
+        let clients = OAuthClients::from_config(&config);
+
+        let url = clients.authorize_url(Provider::Github, "xyz").unwrap();
+        let params: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+        assert!(params.contains(&("client_id".to_string(), "client".to_string())));
+        assert!(params.contains(&("state".to_string(), "xyz".to_string())));
+        assert!(params.contains(&(
+            "redirect_uri".to_string(),
+            "http://localhost:3000/auth/oauth/github/callback".to_string()
+        )));
+        assert!(!url.as_str().contains("secret"));
+        assert!(clients.authorize_url(Provider::Google, "xyz").is_none());
+    }
+}
@@ -0,0 +1,57 @@
+use tokio::sync::OnceCell;
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+
+/// Hashes with argon2id and the crate's default cost, as a PHC string that
+/// carries its own salt and parameters.
+pub async fn hash(password: String) -> anyhow::Result<String> {
+    // hashing is deliberately slow, keep it off the async workers
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| anyhow::anyhow!("failed to hash password: {}", e))
+    })
+    .await?
+}
+
+pub async fn verify(password: String, hash: String) -> anyhow::Result<bool> {
+    tokio::task::spawn_blocking(move || {
+        let hash = PasswordHash::new(&hash)
+            .map_err(|e| anyhow::anyhow!("invalid password hash: {}", e))?;
+        Ok(Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok())
+    })
+    .await?
+}
+
+/// Costs as much as a failed `verify`, for logins naming an unknown user so
+/// response times do not reveal which usernames exist.
+pub async fn verify_nothing(password: String) -> anyhow::Result<()> {
+    static DUMMY_HASH: OnceCell<String> = OnceCell::const_new();
+    let hash = DUMMY_HASH
+        .get_or_try_init(|| hash("not a real password 0".to_string()))
+        .await?;
+    verify(password, hash.clone()).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn should_verify_only_the_hashed_password() {
+        let hashed = hash("correct horse 1".to_string()).await.unwrap();
+        assert!(hashed.starts_with("$argon2id$"));
+        assert!(verify("correct horse 1".to_string(), hashed.clone())
+            .await
+            .unwrap());
+        assert!(!verify("wrong horse 1".to_string(), hashed).await.unwrap());
+    }
+}
@@ -0,0 +1,14 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use sha2::{Digest, Sha256};
+
+use super::random_token;
+
+pub fn new_refresh_token() -> String {
+    random_token()
+}
+
+/// Refresh tokens are random enough that a fast unsalted hash suffices; it
+/// keeps a leaked table from being replayed.
+pub fn hash(token: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+}
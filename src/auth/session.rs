@@ -0,0 +1,67 @@
+use axum::http::{header::COOKIE, HeaderMap, HeaderValue};
+
+use crate::config::AuthConfig;
+
+use super::random_token;
+
+pub const COOKIE_NAME: &str = "todo_session";
+
+pub fn new_session_id() -> String {
+    random_token()
+}
+
+/// The session id from the request's `Cookie` headers.
+pub fn session_id(headers: &HeaderMap) -> Option<&str> {
+    cookie_value(headers, COOKIE_NAME)
+}
+
+pub fn cookie_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// HttpOnly and SameSite=Strict so scripts cannot read it and other sites
+/// cannot send it along with forged requests.
+pub fn set_cookie(session_id: &str, config: &AuthConfig) -> HeaderValue {
+    cookie(session_id, config.session_ttl_secs, config.cookie_secure)
+}
+
+pub fn clear_cookie(config: &AuthConfig) -> HeaderValue {
+    cookie("", 0, config.cookie_secure)
+}
+
+fn cookie(value: &str, max_age: u64, secure: bool) -> HeaderValue {
+    let mut cookie = format!(
+        "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Strict",
+        COOKIE_NAME, value, max_age
+    );
+    if secure {
+        cookie.push_str("; Secure");
+    }
+    HeaderValue::from_str(&cookie).expect("session ids are header safe")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_read_session_cookie() {
+        let id = new_session_id();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            COOKIE,
+            format!("theme=dark; {}={}", COOKIE_NAME, id)
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(session_id(&headers), Some(id.as_str()));
+        assert_eq!(session_id(&HeaderMap::new()), None);
+    }
+}
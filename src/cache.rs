@@ -0,0 +1,110 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::async_trait;
+
+use crate::config::CacheConfig;
+
+/// String key/value store with a fixed time to live per entry.
+#[async_trait]
+pub trait Cache: Send + Sync {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<String>>;
+    async fn set(&self, key: &str, value: String) -> anyhow::Result<()>;
+    async fn remove(&self, keys: &[String]) -> anyhow::Result<()>;
+}
+
+/// In-process cache for single-instance deployments.
+pub struct MemoryCache {
+    entries: moka::future::Cache<String, String>,
+}
+
+impl MemoryCache {
+    pub fn new(ttl: Duration, capacity: u64) -> Self {
+        Self {
+            entries: moka::future::Cache::builder()
+                .time_to_live(ttl)
+                .max_capacity(capacity)
+                .build(),
+        }
+    }
+}
+
+#[async_trait]
+impl Cache for MemoryCache {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<String>> {
+        Ok(self.entries.get(key).await)
+    }
+    async fn set(&self, key: &str, value: String) -> anyhow::Result<()> {
+        self.entries.insert(key.to_string(), value).await;
+        Ok(())
+    }
+    async fn remove(&self, keys: &[String]) -> anyhow::Result<()> {
+        for key in keys {
+            self.entries.invalidate(key).await;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "redis")]
+pub use self::redis_cache::RedisCache;
+
+#[cfg(feature = "redis")]
+mod redis_cache {
+    use super::*;
+    use redis::{aio::ConnectionManager, AsyncCommands};
+    use tokio::sync::OnceCell;
+
+    pub struct RedisCache {
+        client: redis::Client,
+        connection: OnceCell<ConnectionManager>,
+        ttl: Duration,
+    }
+
+    impl RedisCache {
+        pub fn new(url: &str, ttl: Duration) -> anyhow::Result<Self> {
+            Ok(Self {
+                client: redis::Client::open(url)?,
+                connection: OnceCell::new(),
+                ttl,
+            })
+        }
+
+        async fn connection(&self) -> anyhow::Result<ConnectionManager> {
+            Ok(self
+                .connection
+                .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+                .await?
+                .clone())
+        }
+    }
+
+    #[async_trait]
+    impl Cache for RedisCache {
+        async fn get(&self, key: &str) -> anyhow::Result<Option<String>> {
+            Ok(self.connection().await?.get(key).await?)
+        }
+        async fn set(&self, key: &str, value: String) -> anyhow::Result<()> {
+            let ttl = self.ttl.as_secs().max(1);
+            Ok(self.connection().await?.set_ex(key, value, ttl).await?)
+        }
+        async fn remove(&self, keys: &[String]) -> anyhow::Result<()> {
+            Ok(self.connection().await?.del(keys).await?)
+        }
+    }
+}
+
+/// The cache selected by `cache.url`, if any.
+pub fn from_config(config: &CacheConfig) -> anyhow::Result<Option<Arc<dyn Cache>>> {
+    let Some(url) = &config.url else {
+        return Ok(None);
+    };
+    let ttl = Duration::from_secs(config.ttl_secs);
+    let cache: Arc<dyn Cache> = match url.split_once("://") {
+        Some(("memory", _)) => Arc::new(MemoryCache::new(ttl, config.capacity)),
+        #[cfg(feature = "redis")]
+        Some(("redis" | "rediss", _)) => Arc::new(RedisCache::new(url, ttl)?),
+        _ => anyhow::bail!("unsupported cache.url {:?}", url),
+    };
+
+    Ok(Some(cache))
+}
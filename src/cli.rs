@@ -0,0 +1,883 @@
+use std::{
+    io::Write,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Context;
+use chrono::{NaiveDate, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::Serialize;
+use sqlx::PgPool;
+use tokio::signal::unix::{signal, SignalKind};
+
+#[cfg(feature = "mongodb")]
+use crate::repositories::mongo::{self, LabelRepositoryForMongo, TodoRepositoryForMongo};
+#[cfg(feature = "redis")]
+use crate::repositories::redis_store::TodoRepositoryForRedis;
+use crate::{
+    app_state, cache,
+    config::{Config, DatabaseConfig, EventBackend, TodoStorage},
+    create_app_with_state,
+    database::{self, SlowQueryLog},
+    events::{Events, PgEventBus},
+    notifications::{LogDelivery, Notification, NotificationKind, Notifier},
+    repositories::{
+        account::{AccountFilter, UpdateAccount, UserRole},
+        audit::{AuditAction, NewAuditEntry},
+        cached::CachedTodoRepository,
+        event_store::TodoRepositoryForEventStore,
+        label::{
+            CreateLabel, Label, LabelRepository, LabelRepositoryForDb, LabelRepositoryForMemory,
+        },
+        split::SplitTodoRepository,
+        todo::{
+            CreateTodo, OwnedTodos, Todo, TodoReader, TodoRepository, TodoRepositoryForDb,
+            TodoRepositoryForMemory, TodoWriter, UpdateTodo,
+        },
+        user::{UserRepository, UserRepositoryForDb},
+    },
+    server,
+    slack::SlackDelivery,
+};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "my-todo",
+    version,
+    about = "Todo API server and admin commands"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the HTTP server (default)
+    Serve {
+        /// Address to bind, e.g. `::` for IPv6 and IPv4 alike
+        #[arg(long)]
+        host: Option<IpAddr>,
+        #[arg(long)]
+        port: Option<u16>,
+        /// Listen on this address as well; repeat for several
+        #[arg(long, conflicts_with_all = ["host", "port"])]
+        listen: Vec<SocketAddr>,
+    },
+    /// Apply pending database migrations
+    Migrate,
+    /// Insert sample todos and labels, or generated ones with --todos
+    Seed {
+        /// Generate this many todos with made-up text, labels and state
+        #[arg(long)]
+        todos: Option<u32>,
+        /// How many labels to generate for them
+        #[arg(long, default_value_t = 10, requires = "todos")]
+        labels: u32,
+    },
+    /// Write all todos and labels to stdout
+    Export {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+    },
+    /// Give an account the site-wide admin role
+    GrantAdmin { username: String },
+    /// Delete the accounts whose deletion grace period is over
+    PurgeAccounts,
+    /// Remind every account of its open todos due today; meant to run daily
+    RemindDue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+}
+
+#[derive(Debug, Serialize)]
+struct Export {
+    todos: Vec<Todo>,
+    labels: Vec<Label>,
+}
+
+pub async fn run(cli: Cli, config: Config) -> anyhow::Result<()> {
+    tracing::debug!("start connect database...");
+    let pool = database::connect(&config.database).await?;
+    let replica = match &config.database.replica_url {
+        Some(url) => Some(database::connect_lazy(&DatabaseConfig {
+            url: url.clone(),
+            ..config.database.clone()
+        })?),
+        None => None,
+    };
+    match config.database.todo_storage {
+        TodoStorage::Tables => {
+            let slow_queries = SlowQueryLog::from_config(&config.database);
+            let list_cap = config.database.max_listed_todos;
+            let todos = move |pool| {
+                TodoRepositoryForDb::new(pool)
+                    .with_slow_query_log(slow_queries.clone())
+                    .with_list_cap(list_cap)
+            };
+            run_with_replica(cli, config, pool, replica, todos).await
+        }
+        TodoStorage::EventStore => {
+            run_with_replica(cli, config, pool, replica, TodoRepositoryForEventStore::new).await
+        }
+        TodoStorage::File => {
+            let label_repository = LabelRepositoryForMemory::new();
+            let todo_repository = TodoRepositoryForMemory::with_labels(label_repository.clone())
+                .persist_to(&config.database.todo_file)?;
+            todo_repository.flush_on_change();
+            let result = tokio::select! {
+                result = run_with(cli, config, pool, todo_repository.clone(), label_repository) => result,
+                result = shutdown_signal() => result,
+            };
+            todo_repository.flush().await?;
+            result
+        }
+        TodoStorage::Mongo => {
+            let url = config
+                .database
+                .mongodb_url
+                .clone()
+                .context("todo_storage = \"mongodb\" needs database.mongodb_url")?;
+            run_with_mongo(cli, config, pool, &url).await
+        }
+        TodoStorage::Redis => {
+            let url = config
+                .database
+                .redis_url
+                .clone()
+                .context("todo_storage = \"redis\" needs database.redis_url")?;
+            run_with_redis(cli, config, pool, &url).await
+        }
+    }
+}
+
+#[cfg(feature = "mongodb")]
+async fn run_with_mongo(cli: Cli, config: Config, pool: PgPool, url: &str) -> anyhow::Result<()> {
+    let db = mongo::connect(url).await?;
+    let todo_repository = TodoRepositoryForMongo::new(db.clone());
+    run_with(
+        cli,
+        config,
+        pool,
+        todo_repository,
+        LabelRepositoryForMongo::new(db),
+    )
+    .await
+}
+
+#[cfg(not(feature = "mongodb"))]
+async fn run_with_mongo(_: Cli, _: Config, _: PgPool, _: &str) -> anyhow::Result<()> {
+    anyhow::bail!("todo_storage = \"mongodb\" needs a build with --features mongodb")
+}
+
+#[cfg(feature = "redis")]
+async fn run_with_redis(cli: Cli, config: Config, pool: PgPool, url: &str) -> anyhow::Result<()> {
+    let label_repository = LabelRepositoryForDb::new(pool.clone());
+    let todo_repository = TodoRepositoryForRedis::new(url, label_repository.clone())?;
+    run_with(cli, config, pool, todo_repository, label_repository).await
+}
+
+#[cfg(not(feature = "redis"))]
+async fn run_with_redis(_: Cli, _: Config, _: PgPool, _: &str) -> anyhow::Result<()> {
+    anyhow::bail!("todo_storage = \"redis\" needs a build with --features redis")
+}
+
+/// Resolves on the first SIGINT or SIGTERM.
+async fn shutdown_signal() -> anyhow::Result<()> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        _ = terminate.recv() => {}
+    }
+    tracing::info!("shutting down");
+    Ok(())
+}
+
+/// Reads todos through `replica` when there is one.
+async fn run_with_replica<T: TodoRepository>(
+    cli: Cli,
+    config: Config,
+    pool: PgPool,
+    replica: Option<PgPool>,
+    todos: impl Fn(PgPool) -> T,
+) -> anyhow::Result<()> {
+    let label_repository = LabelRepositoryForDb::new(pool.clone());
+    match replica {
+        Some(replica) => {
+            let todo_repository = SplitTodoRepository::new(todos(replica), todos(pool.clone()));
+            run_with(cli, config, pool, todo_repository, label_repository).await
+        }
+        None => run_with(cli, config, pool.clone(), todos(pool), label_repository).await,
+    }
+}
+
+async fn run_with<T: TodoRepository, L: LabelRepository>(
+    cli: Cli,
+    config: Config,
+    pool: PgPool,
+    todo_repository: T,
+    label_repository: L,
+) -> anyhow::Result<()> {
+    let serve = Command::Serve {
+        host: None,
+        port: None,
+        listen: Vec::new(),
+    };
+    match cli.command.unwrap_or(serve) {
+        Command::Serve { host, port, listen } => {
+            let addrs = serve_addrs(&config, host, port, listen);
+            let tls = config.tls.clone();
+            let user_repository = UserRepositoryForDb::new(pool.clone());
+            let events = match config.events.backend {
+                EventBackend::Memory => Events::default(),
+                EventBackend::Postgres => {
+                    Events::new(Arc::new(PgEventBus::listen(pool.clone()).await?))
+                }
+            };
+            #[cfg(feature = "kafka")]
+            if config.events.kafka.brokers.is_some() {
+                events.attach(crate::kafka::KafkaPublisher::connect(&config.events.kafka).await?);
+            }
+            #[cfg(feature = "nats")]
+            let nats = crate::nats::connect(&config.events.nats).await?;
+            #[cfg(feature = "nats")]
+            if let Some(client) = &nats {
+                events.attach(crate::nats::NatsPublisher::new(
+                    client.clone(),
+                    &config.events.nats,
+                ));
+            }
+            let app = match cache::from_config(&config.cache)? {
+                Some(cache) => {
+                    let todo_repository = CachedTodoRepository::new(todo_repository, cache);
+                    spawn_purge(todo_repository.clone(), user_repository.clone());
+                    spawn_unsnooze(todo_repository.clone());
+                    let state = app_state(
+                        todo_repository,
+                        label_repository,
+                        user_repository,
+                        config,
+                        events,
+                    );
+                    #[cfg(feature = "nats")]
+                    if let Some(client) = nats {
+                        let config = state.config.events.nats.clone();
+                        crate::nats::serve_creates(client, &config, state.clone()).await?;
+                    }
+                    create_app_with_state(state)
+                }
+                None => {
+                    spawn_purge(todo_repository.clone(), user_repository.clone());
+                    spawn_unsnooze(todo_repository.clone());
+                    let state = app_state(
+                        todo_repository,
+                        label_repository,
+                        user_repository,
+                        config,
+                        events,
+                    );
+                    #[cfg(feature = "nats")]
+                    if let Some(client) = nats {
+                        let config = state.config.events.nats.clone();
+                        crate::nats::serve_creates(client, &config, state.clone()).await?;
+                    }
+                    create_app_with_state(state)
+                }
+            };
+            server::serve(app, &addrs, tls.as_ref()).await?;
+        }
+        Command::Migrate => {
+            database::migrate(&pool).await?;
+            tracing::info!("migrations applied");
+        }
+        Command::Seed { todos, labels } => {
+            match todos {
+                Some(todos) => {
+                    let mut rng = StdRng::from_entropy();
+                    seed_generated(&todo_repository, &label_repository, todos, labels, &mut rng)
+                        .await?
+                }
+                None => seed(&todo_repository, &label_repository).await?,
+            }
+            tracing::info!("seed data inserted");
+        }
+        Command::Export { format } => {
+            export(
+                &todo_repository,
+                &label_repository,
+                format,
+                std::io::stdout().lock(),
+            )
+            .await?;
+        }
+        Command::GrantAdmin { username } => {
+            grant_admin(&UserRepositoryForDb::new(pool.clone()), &username).await?;
+            tracing::info!("{} is now an admin", username);
+        }
+        Command::PurgeAccounts => {
+            let user_repository = UserRepositoryForDb::new(pool.clone());
+            let purged = purge_accounts(&todo_repository, &user_repository).await?;
+            tracing::info!("{} accounts deleted", purged);
+        }
+        Command::RemindDue => {
+            let user_repository = Arc::new(UserRepositoryForDb::new(pool.clone()));
+            let notifier = Notifier::new(Arc::new(SlackDelivery::new(
+                user_repository.clone(),
+                Arc::new(LogDelivery),
+            )));
+            let today = Utc::now().date_naive();
+            let reminded =
+                remind_due(&todo_repository, user_repository.as_ref(), &notifier, today).await?;
+            tracing::info!("{} due todos reminded of", reminded);
+        }
+    }
+
+    Ok(())
+}
+
+/// The addresses `serve` listens on; flags take precedence over the
+/// config, and `--host` or `--port` over its `listen` list.
+fn serve_addrs(
+    config: &Config,
+    host: Option<IpAddr>,
+    port: Option<u16>,
+    listen: Vec<SocketAddr>,
+) -> Vec<SocketAddr> {
+    if !listen.is_empty() {
+        return listen;
+    }
+    if host.is_none() && port.is_none() {
+        return config.listen_addrs();
+    }
+    vec![SocketAddr::new(
+        host.unwrap_or(config.host),
+        port.unwrap_or(config.port),
+    )]
+}
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Runs `purge_accounts` every hour for as long as the server does.
+fn spawn_purge<T: TodoWriter, U: UserRepository>(todo_repository: T, user_repository: U) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = purge_accounts(&todo_repository, &user_repository).await {
+                tracing::warn!("account purge failed: {:?}", e);
+            }
+        }
+    });
+}
+
+const UNSNOOZE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Wakes the todos whose snooze is over every minute, so subscribers hear
+/// of them coming back. Listings stop hiding them on time either way.
+fn spawn_unsnooze<T: TodoWriter>(todo_repository: T) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(UNSNOOZE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match todo_repository.unsnooze_due().await {
+                Ok(woken) if !woken.is_empty() => {
+                    tracing::info!("{} snoozed todos woken", woken.len())
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("waking snoozed todos failed: {:?}", e),
+            }
+        }
+    });
+}
+
+/// Deletes every account whose deletion grace period is over, along with the
+/// todos it created, and returns how many went.
+pub async fn purge_accounts<T: TodoWriter, U: UserRepository>(
+    todo_repository: &T,
+    user_repository: &U,
+) -> anyhow::Result<usize> {
+    let due = user_repository.deletions_due().await?;
+    for &id in &due {
+        todo_repository
+            .release_owned(id, OwnedTodos::Delete)
+            .await?;
+        user_repository.delete(id).await?;
+        user_repository
+            .record_audit(NewAuditEntry {
+                user_id: id,
+                actor_id: None,
+                action: AuditAction::AccountDeleted,
+            })
+            .await?;
+    }
+
+    Ok(due.len())
+}
+
+/// Accounts looked at per page by `remind_due`.
+const REMIND_PAGE_SIZE: u32 = 100;
+
+/// Sends a `DueSoon` notification for every open todo due `today` to its
+/// assignee, or its owner when unassigned, over the channels they chose;
+/// disabled accounts are skipped. Returns how many were sent.
+pub async fn remind_due<T: TodoReader, U: UserRepository>(
+    todo_repository: &T,
+    user_repository: &U,
+    notifier: &Notifier,
+    today: NaiveDate,
+) -> anyhow::Result<usize> {
+    let filter = AccountFilter {
+        disabled: Some(false),
+        ..AccountFilter::default()
+    };
+    let mut reminded = 0;
+    let mut after = None;
+    loop {
+        let page = user_repository
+            .accounts(&filter, after, REMIND_PAGE_SIZE)
+            .await?;
+        for account in &page.items {
+            let user_id = account.user.id;
+            let due: Vec<(i32, Todo)> = todo_repository
+                .due_by(user_id, today)
+                .await?
+                .into_iter()
+                .filter(|(_, todo)| todo.due_date == Some(today))
+                .collect();
+            if due.is_empty() {
+                continue;
+            }
+            let settings = user_repository.notification_settings(user_id).await?;
+            for (workspace_id, todo) in due {
+                let notification = Notification {
+                    kind: NotificationKind::DueSoon,
+                    user_id,
+                    workspace_id,
+                    todo,
+                };
+                notifier.dispatch(&settings, &notification).await;
+                reminded += 1;
+            }
+        }
+        match page.next {
+            Some(next) => after = Some(next),
+            None => break,
+        }
+    }
+
+    Ok(reminded)
+}
+
+/// Bootstraps the first admin, who can then promote others over HTTP.
+pub async fn grant_admin<U: UserRepository>(
+    user_repository: &U,
+    username: &str,
+) -> anyhow::Result<()> {
+    let credentials = user_repository
+        .credentials_by_username(username)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("no user named {:?}", username))?;
+    user_repository
+        .update_account(
+            credentials.id,
+            UpdateAccount {
+                role: Some(UserRole::Admin),
+                disabled: None,
+            },
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub async fn seed<T: TodoWriter, L: LabelRepository>(
+    todo_repository: &T,
+    label_repository: &L,
+) -> anyhow::Result<()> {
+    let mut labels = vec![];
+    for name in ["home", "work", "errand"] {
+        labels.push(
+            label_repository
+                .create(CreateLabel::new(name.to_string()))
+                .await?
+                .id,
+        );
+    }
+    let [home, work, errand] = labels[..] else {
+        unreachable!()
+    };
+    for (text, labels, completed) in [
+        ("buy milk", vec![home, errand], false),
+        ("write weekly report", vec![work], false),
+        ("renew passport", vec![errand], true),
+    ] {
+        let todo = todo_repository
+            .create(CreateTodo {
+                labels,
+                ..CreateTodo::new(text.to_string())
+            })
+            .await?;
+        if completed {
+            todo_repository
+                .update(
+                    todo.id,
+                    UpdateTodo {
+                        text: None,
+                        completed: Some(true),
+                        labels: None,
+                        assignee_id: None,
+                        text_i18n: None,
+                        estimate_minutes: None,
+                        due_date: None,
+                        description: None,
+                        custom: None,
+                    },
+                )
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+const SEED_TOPICS: [&str; 12] = [
+    "home", "work", "errand", "health", "finance", "travel", "family", "garden", "car", "study",
+    "shopping", "hobby",
+];
+const SEED_VERBS: [&str; 12] = [
+    "buy", "call", "fix", "write", "review", "clean", "book", "plan", "email", "renew", "pay",
+    "order",
+];
+const SEED_OBJECTS: [&str; 12] = [
+    "milk",
+    "the plumber",
+    "weekly report",
+    "kitchen sink",
+    "dentist appointment",
+    "quarterly budget",
+    "passport",
+    "library books",
+    "team offsite",
+    "insurance",
+    "birthday gift",
+    "car service",
+];
+const SEED_WHEN: [&str; 6] = [
+    "",
+    " today",
+    " before friday",
+    " this weekend",
+    " soon",
+    " again",
+];
+/// Generated todos get up to this many labels.
+const SEED_MAX_LABELS: usize = 3;
+
+/// Creates `labels` labels and `todos` todos with made-up text, a few of
+/// those labels each and about a third of them completed.
+pub async fn seed_generated<T: TodoWriter, L: LabelRepository>(
+    todo_repository: &T,
+    label_repository: &L,
+    todos: u32,
+    labels: u32,
+    rng: &mut (impl Rng + Send),
+) -> anyhow::Result<()> {
+    let mut label_ids = Vec::with_capacity(labels as usize);
+    for i in 0..labels as usize {
+        let topic = SEED_TOPICS[i % SEED_TOPICS.len()];
+        let name = match i / SEED_TOPICS.len() {
+            0 => topic.to_string(),
+            round => format!("{}-{}", topic, round + 1),
+        };
+        label_ids.push(label_repository.create(CreateLabel::new(name)).await?.id);
+    }
+
+    for i in 0..todos {
+        let text = format!(
+            "{} {}{}",
+            SEED_VERBS.choose(rng).unwrap(),
+            SEED_OBJECTS.choose(rng).unwrap(),
+            SEED_WHEN.choose(rng).unwrap()
+        );
+        let count = rng.gen_range(0..=SEED_MAX_LABELS.min(label_ids.len()));
+        let labels = label_ids.choose_multiple(rng, count).copied().collect();
+        let completed = rng.gen_bool(1.0 / 3.0);
+
+        let todo = todo_repository
+            .create(CreateTodo {
+                labels,
+                ..CreateTodo::new(text)
+            })
+            .await?;
+        if completed {
+            todo_repository
+                .update(
+                    todo.id,
+                    UpdateTodo {
+                        text: None,
+                        completed: Some(true),
+                        labels: None,
+                        assignee_id: None,
+                        text_i18n: None,
+                        estimate_minutes: None,
+                        due_date: None,
+                        description: None,
+                        custom: None,
+                    },
+                )
+                .await?;
+        }
+        if (i + 1) % 1000 == 0 {
+            tracing::info!("{} of {} todos inserted", i + 1, todos);
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn export<T: TodoReader, L: LabelRepository>(
+    todo_repository: &T,
+    label_repository: &L,
+    format: ExportFormat,
+    mut writer: impl Write,
+) -> anyhow::Result<()> {
+    let export = Export {
+        todos: todo_repository.all().await?,
+        labels: label_repository.all().await?,
+    };
+    match format {
+        ExportFormat::Json => serde_json::to_writer_pretty(&mut writer, &export)?,
+    }
+    writeln!(writer)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        label::LabelRepositoryForMemory, todo::TodoRepositoryForMemory,
+        user::UserRepositoryForMemory,
+    };
+
+    #[test]
+    fn should_default_to_serve() {
+        let cli = Cli::try_parse_from(["my-todo"]).unwrap();
+        assert!(cli.command.is_none());
+
+        let cli = Cli::try_parse_from(["my-todo", "export", "--format", "json"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Export {
+                format: ExportFormat::Json
+            })
+        ));
+        assert!(Cli::try_parse_from(["my-todo", "export", "--format", "xml"]).is_err());
+
+        let cli =
+            Cli::try_parse_from(["my-todo", "seed", "--todos", "10000", "--labels", "50"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Seed {
+                todos: Some(10000),
+                labels: 50
+            })
+        ));
+        assert!(Cli::try_parse_from(["my-todo", "seed", "--labels", "50"]).is_err());
+
+        let cli = Cli::try_parse_from(["my-todo", "grant-admin", "alice"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::GrantAdmin { username }) if username == "alice"
+        ));
+    }
+
+    #[test]
+    fn should_take_listen_addresses_from_flags() {
+        let config = Config {
+            listen: vec!["127.0.0.1:3000".parse().unwrap()],
+            ..Config::default()
+        };
+        let addrs = |args: &[&str]| {
+            let cli = Cli::try_parse_from(args).unwrap();
+            let Some(Command::Serve { host, port, listen }) = cli.command else {
+                panic!("not serve: {:?}", cli.command);
+            };
+            serve_addrs(&config, host, port, listen)
+        };
+
+        assert_eq!(
+            addrs(&["my-todo", "serve"]),
+            vec!["127.0.0.1:3000".parse().unwrap()]
+        );
+        assert_eq!(
+            addrs(&["my-todo", "serve", "--host", "::", "--port", "8080"]),
+            vec!["[::]:8080".parse().unwrap()]
+        );
+        assert_eq!(
+            addrs(&["my-todo", "serve", "--port", "8080"]),
+            vec!["0.0.0.0:8080".parse().unwrap()]
+        );
+        assert_eq!(
+            addrs(&[
+                "my-todo",
+                "serve",
+                "--listen",
+                "127.0.0.1:80",
+                "--listen",
+                "[::1]:80"
+            ]),
+            vec!["127.0.0.1:80".parse().unwrap(), "[::1]:80".parse().unwrap()]
+        );
+        assert!(
+            Cli::try_parse_from(["my-todo", "serve", "--port", "1", "--listen", "[::1]:80"])
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn should_purge_accounts_after_grace_period() {
+        let todo_repository = TodoRepositoryForMemory::new();
+        let user_repository = UserRepositoryForMemory::new();
+        let leaving = user_repository
+            .create("leaving".to_string(), "hash".to_string())
+            .await
+            .unwrap();
+        let staying = user_repository
+            .create("staying".to_string(), "hash".to_string())
+            .await
+            .unwrap();
+        let todo = todo_repository
+            .create(CreateTodo {
+                owner_id: Some(leaving.id),
+                ..CreateTodo::new("left behind".to_string())
+            })
+            .await
+            .unwrap();
+        let past = chrono::Utc::now() - chrono::Duration::seconds(1);
+        let future = chrono::Utc::now() + chrono::Duration::days(1);
+        user_repository
+            .schedule_deletion(leaving.id, Some(past))
+            .await
+            .unwrap();
+        user_repository
+            .schedule_deletion(staying.id, Some(future))
+            .await
+            .unwrap();
+
+        let purged = purge_accounts(&todo_repository, &user_repository)
+            .await
+            .unwrap();
+        assert_eq!(purged, 1);
+        assert!(user_repository.find(leaving.id).await.is_err());
+        assert!(user_repository.find(staying.id).await.is_ok());
+        assert!(!todo_repository.exists(todo.id).await.unwrap());
+        let log = user_repository.audit_log(leaving.id).await.unwrap();
+        assert_eq!(log[0].action, AuditAction::AccountDeleted);
+        assert_eq!(log[0].actor_id, None);
+    }
+
+    #[tokio::test]
+    async fn should_remind_of_todos_due_today() {
+        let todo_repository = TodoRepositoryForMemory::new();
+        let user_repository = UserRepositoryForMemory::new();
+        let owner = user_repository
+            .create("owner".to_string(), "hash".to_string())
+            .await
+            .unwrap();
+        let today = chrono::Utc::now().date_naive();
+        for (text, due_date) in [
+            ("today", today),
+            ("yesterday", today - chrono::Duration::days(1)),
+            ("tomorrow", today + chrono::Duration::days(1)),
+        ] {
+            todo_repository
+                .create(CreateTodo {
+                    owner_id: Some(owner.id),
+                    due_date: Some(due_date),
+                    ..CreateTodo::new(text.to_string())
+                })
+                .await
+                .unwrap();
+        }
+
+        let reminded = remind_due(
+            &todo_repository,
+            &user_repository,
+            &Notifier::default(),
+            today,
+        )
+        .await
+        .unwrap();
+        assert_eq!(reminded, 1);
+        user_repository
+            .update_account(
+                owner.id,
+                UpdateAccount {
+                    role: None,
+                    disabled: Some(true),
+                },
+            )
+            .await
+            .unwrap();
+        let reminded = remind_due(
+            &todo_repository,
+            &user_repository,
+            &Notifier::default(),
+            today,
+        )
+        .await
+        .unwrap();
+        assert_eq!(reminded, 0);
+    }
+
+    #[tokio::test]
+    async fn should_export_seeded_data() {
+        let label_repository = LabelRepositoryForMemory::new();
+        let todo_repository = TodoRepositoryForMemory::with_labels(label_repository.clone());
+        seed(&todo_repository, &label_repository).await.unwrap();
+
+        let mut out = Vec::new();
+        export(
+            &todo_repository,
+            &label_repository,
+            ExportFormat::Json,
+            &mut out,
+        )
+        .await
+        .unwrap();
+
+        let value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(value["todos"].as_array().unwrap().len(), 3);
+        assert_eq!(value["labels"].as_array().unwrap().len(), 3);
+        assert_eq!(value["todos"][0]["labels"][0]["name"], "errand");
+    }
+
+    #[tokio::test]
+    async fn should_seed_generated_data() {
+        let label_repository = LabelRepositoryForMemory::new();
+        let todo_repository = TodoRepositoryForMemory::with_labels(label_repository.clone());
+        let mut rng = StdRng::seed_from_u64(7);
+        seed_generated(&todo_repository, &label_repository, 200, 15, &mut rng)
+            .await
+            .unwrap();
+
+        let labels = label_repository.all().await.unwrap();
+        assert_eq!(labels.len(), 15);
+        assert!(labels.iter().any(|label| label.name == "home-2"));
+        let todos = todo_repository.all().await.unwrap();
+        assert_eq!(todos.len(), 200);
+        assert!(todos
+            .iter()
+            .all(|todo| todo.labels.len() <= SEED_MAX_LABELS));
+        let completed = todos.iter().filter(|todo| todo.completed).count();
+        assert!((30..120).contains(&completed), "{} completed", completed);
+    }
+}
@@ -0,0 +1,1997 @@
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    env,
+    fmt::Display,
+    fs,
+    net::{IpAddr, SocketAddr},
+    path::Path,
+    str::FromStr,
+};
+
+use thiserror::Error;
+
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+const MIN_JWT_SECRET_BYTES: usize = 32;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("missing required setting [{0}]")]
+    Missing(&'static str),
+    #[error("invalid value for [{key}]: {value:?} ({reason})")]
+    Invalid {
+        key: &'static str,
+        value: String,
+        reason: String,
+    },
+    #[error("cannot read config file [{path}]: {reason}")]
+    File { path: String, reason: String },
+    #[error("invalid config file: {0}")]
+    Parse(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub host: IpAddr,
+    pub port: u16,
+    /// Addresses to listen on instead of `host`:`port`, all at once.
+    pub listen: Vec<SocketAddr>,
+    /// Serve HTTPS instead of HTTP when set.
+    pub tls: Option<TlsConfig>,
+    pub log_level: String,
+    pub log_format: LogFormat,
+    pub allowed_origin: String,
+    pub http: HttpConfig,
+    pub security_headers: SecurityHeadersConfig,
+    pub cache_control: CacheControlConfig,
+    pub database: DatabaseConfig,
+    pub rate_limit: RateLimitConfig,
+    pub cache: CacheConfig,
+    pub auth: AuthConfig,
+    pub quota: QuotaConfig,
+    pub search: SearchConfig,
+    pub events: EventsConfig,
+    pub reporting: ReportingConfig,
+    pub telemetry: TelemetryConfig,
+    pub slack: SlackConfig,
+    pub telegram: TelegramConfig,
+    pub discord: DiscordConfig,
+    pub email: EmailConfig,
+    pub features: FeatureToggles,
+}
+
+/// How log lines are written to stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// Human-readable lines.
+    #[default]
+    Pretty,
+    /// One JSON object per line, with the request's fields at the top level.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err("expected pretty or json".to_string()),
+        }
+    }
+}
+
+/// PEM files for serving HTTPS without a proxy in front. They are read
+/// again on SIGHUP, so renewed certificates need no restart.
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    /// gzip/brotli responses for clients that accept them.
+    pub compression: bool,
+    /// Larger request bodies are rejected with 413.
+    pub max_body_bytes: usize,
+    /// Requests running longer are answered with 408.
+    pub request_timeout_secs: u64,
+    /// In-flight requests beyond this are shed with 503.
+    pub max_concurrent_requests: usize,
+    /// Reject JSON bodies carrying fields the endpoint does not know.
+    pub strict_json: bool,
+    /// Files served for paths no route matches, with `index.html` standing
+    /// in for missing ones that a browser navigates to.
+    pub static_dir: Option<String>,
+    /// Peers whose `X-Forwarded-For`/`X-Forwarded-Proto` are believed.
+    pub trusted_proxies: Vec<IpNet>,
+}
+
+/// An address or a CIDR block such as `10.0.0.0/8`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNet {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        fn masked(bits: u128, width: u8, prefix: u8) -> u128 {
+            match prefix {
+                0 => 0,
+                prefix => bits >> (width - prefix),
+            }
+        }
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                masked(u32::from(net).into(), 32, self.prefix)
+                    == masked(u32::from(ip).into(), 32, self.prefix)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                masked(net.into(), 128, self.prefix) == masked(ip.into(), 128, self.prefix)
+            }
+            // proxies reached over IPv6 may show IPv4 peers as mapped addresses
+            (IpAddr::V4(_), IpAddr::V6(ip)) => ip
+                .to_ipv4_mapped()
+                .is_some_and(|ip| self.contains(IpAddr::V4(ip))),
+            (IpAddr::V6(_), IpAddr::V4(_)) => false,
+        }
+    }
+}
+
+impl FromStr for IpNet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|e| format!("{}", e))?;
+        let width = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse()
+                .ok()
+                .filter(|prefix| *prefix <= width)
+                .ok_or_else(|| format!("prefix must be 0 to {}", width))?,
+            None => width,
+        };
+        Ok(Self { addr, prefix })
+    }
+}
+
+/// Headers added to every response that does not set them itself; an empty
+/// value leaves the header out.
+#[derive(Debug, Clone)]
+pub struct SecurityHeadersConfig {
+    pub enabled: bool,
+    pub content_type_options: String,
+    pub frame_options: String,
+    pub referrer_policy: String,
+    /// Only honoured by browsers over HTTPS.
+    pub strict_transport_security: String,
+    pub content_security_policy: String,
+}
+
+/// `Cache-Control` for API answers that do not set their own.
+#[derive(Debug, Clone)]
+pub struct CacheControlConfig {
+    pub enabled: bool,
+    /// Writes and failed requests.
+    pub mutations: String,
+    /// Successful reads of a collection, e.g. `GET /todos`.
+    pub lists: String,
+    /// Successful reads of one resource, e.g. `GET /todos/:id`.
+    pub items: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct DatabaseConfig {
+    pub url: String,
+    /// Todos are read from here when set and written to `url`; the replica
+    /// gets the same pool settings.
+    pub replica_url: Option<String>,
+    pub max_connections: u32,
+    /// Connections kept open even when idle.
+    pub min_connections: u32,
+    /// Waiting longer for a free connection fails the query.
+    pub acquire_timeout_secs: u64,
+    /// Server-side limit per statement; `0` disables it.
+    pub statement_timeout_ms: u64,
+    /// Todo queries taking longer are logged; `0` disables it.
+    pub slow_query_ms: u64,
+    /// Extra attempts at startup while the database is still unreachable.
+    pub connect_retries: u32,
+    /// Most todos one unpaged listing returns; `0` lifts the cap.
+    pub max_listed_todos: u32,
+    pub todo_storage: TodoStorage,
+    /// Where `TodoStorage::File` keeps todos and labels.
+    pub todo_file: String,
+    /// The database `TodoStorage::Mongo` keeps todos and labels in.
+    pub mongodb_url: Option<String>,
+    /// Where `TodoStorage::Redis` keeps todos.
+    pub redis_url: Option<String>,
+}
+
+/// How todos are persisted; switching does not migrate existing todos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TodoStorage {
+    /// One row per todo, updated in place.
+    #[default]
+    Tables,
+    /// An append-only event stream per todo, see `TodoRepositoryForEventStore`.
+    EventStore,
+    /// Todos and labels in memory, saved to a JSON file; accounts stay in
+    /// the database.
+    File,
+    /// Todos and labels in MongoDB, see `TodoRepositoryForMongo`; accounts
+    /// stay in the database.
+    Mongo,
+    /// Todos in Redis, see `TodoRepositoryForRedis`; labels and accounts
+    /// stay in the database.
+    Redis,
+}
+
+impl FromStr for TodoStorage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tables" => Ok(TodoStorage::Tables),
+            "event_store" => Ok(TodoStorage::EventStore),
+            "file" => Ok(TodoStorage::File),
+            "mongodb" => Ok(TodoStorage::Mongo),
+            "redis" => Ok(TodoStorage::Redis),
+            _ => Err("expected tables, event_store, file, mongodb or redis".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Requests refilled per window.
+    pub requests: u32,
+    pub window_secs: u64,
+    /// Requests a client may send at once before refills matter.
+    pub burst: u32,
+    /// Shares the limits between instances when set (needs the `redis` feature).
+    pub redis_url: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    /// `memory://` for an in-process cache, `redis://…` to share it between
+    /// instances (needs the `redis` feature); reads are not cached when unset.
+    pub url: Option<String>,
+    pub ttl_secs: u64,
+    /// Entries kept by the in-process cache.
+    pub capacity: u64,
+}
+
+#[derive(Clone)]
+pub struct AuthConfig {
+    /// HS256 key for access tokens; a random one is generated at startup when
+    /// unset, so tokens do not survive a restart.
+    pub jwt_secret: Option<String>,
+    pub access_token_ttl_secs: u64,
+    /// Refresh tokens are rotated on every use and expire after this long.
+    pub refresh_token_ttl_secs: u64,
+    /// Lifetime of cookie sessions started with `"session": true` at login.
+    pub session_ttl_secs: u64,
+    /// `DELETE /me` takes effect this long after the request, which the user
+    /// may cancel until then.
+    pub deletion_grace_secs: u64,
+    /// Mark the session cookie `Secure`; turn off only for plain-HTTP setups.
+    pub cookie_secure: bool,
+    /// Where clients reach this server, for OAuth callback URLs.
+    pub public_url: String,
+    /// Browsers land here once an OAuth login started their session.
+    pub oauth_success_url: String,
+    pub github: Option<OAuthClientConfig>,
+    pub google: Option<OAuthClientConfig>,
+    pub lockout: LockoutConfig,
+}
+
+#[derive(Debug, Clone)]
+pub struct LockoutConfig {
+    pub enabled: bool,
+    /// Failed logins for one account before it is locked.
+    pub account_failures: u32,
+    /// Failed logins from one address before it is locked.
+    pub ip_failures: u32,
+    /// First lockout, doubled on every further failure up to `max_secs`.
+    pub base_secs: u64,
+    pub max_secs: u64,
+    /// Failures are forgotten after this long without another one.
+    pub window_secs: u64,
+    /// Shares the counts between instances when set (needs the `redis` feature).
+    pub redis_url: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct OAuthClientConfig {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+impl std::fmt::Debug for OAuthClientConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OAuthClientConfig")
+            .field("client_id", &self.client_id)
+            .field("client_secret", &"***")
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for AuthConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthConfig")
+            .field("jwt_secret", &self.jwt_secret.as_ref().map(|_| "***"))
+            .field("access_token_ttl_secs", &self.access_token_ttl_secs)
+            .field("refresh_token_ttl_secs", &self.refresh_token_ttl_secs)
+            .field("session_ttl_secs", &self.session_ttl_secs)
+            .field("deletion_grace_secs", &self.deletion_grace_secs)
+            .field("cookie_secure", &self.cookie_secure)
+            .field("public_url", &self.public_url)
+            .field("oauth_success_url", &self.oauth_success_url)
+            .field("github", &self.github)
+            .field("google", &self.google)
+            .field("lockout", &self.lockout)
+            .finish()
+    }
+}
+
+/// Unset limits are unlimited.
+#[derive(Debug, Clone, Default)]
+pub struct QuotaConfig {
+    /// Incomplete todos a user may own, across all workspaces.
+    pub max_open_todos: Option<u64>,
+    /// Labels per workspace.
+    pub max_labels: Option<u64>,
+}
+
+/// Fuzzy `?q=` search over todo text.
+#[derive(Debug, Clone)]
+pub struct SearchConfig {
+    /// Todos whose best run of words is less similar to the query, from 0
+    /// to 1, are left out.
+    pub similarity_threshold: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct EventsConfig {
+    pub backend: EventBackend,
+    pub kafka: KafkaConfig,
+    pub nats: NatsConfig,
+}
+
+#[derive(Debug, Clone)]
+pub struct KafkaConfig {
+    /// Comma-separated `host:port` list; events go to Kafka only when set
+    /// (needs the `kafka` feature).
+    pub brokers: Option<String>,
+    pub topic: String,
+    pub format: EventFormat,
+}
+
+#[derive(Debug, Clone)]
+pub struct NatsConfig {
+    /// Events go to NATS, and todos can be created through it, only when set
+    /// (needs the `nats` feature).
+    pub url: Option<String>,
+    /// Events are published to `<prefix>.events.<type>`; todos are created
+    /// from `<prefix>.create`.
+    pub subject_prefix: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventFormat {
+    Json,
+    Avro,
+}
+
+impl FromStr for EventFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(EventFormat::Json),
+            "avro" => Ok(EventFormat::Avro),
+            _ => Err("expected json or avro".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EventBackend {
+    /// Events reach the subscribers of this instance only.
+    #[default]
+    Memory,
+    /// Events reach the subscribers of every instance sharing the database.
+    Postgres,
+}
+
+impl FromStr for EventBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "memory" => Ok(EventBackend::Memory),
+            "postgres" => Ok(EventBackend::Postgres),
+            _ => Err("expected memory or postgres".to_string()),
+        }
+    }
+}
+
+/// Where 5xx answers, unexpected errors and panics are reported.
+#[derive(Debug, Clone, Default)]
+pub struct ReportingConfig {
+    /// Sends reports to Sentry (needs the `sentry` feature).
+    pub sentry_dsn: Option<String>,
+    pub environment: Option<String>,
+}
+
+/// Trace export to an OpenTelemetry collector.
+#[derive(Debug, Clone)]
+pub struct TelemetryConfig {
+    /// OTLP/HTTP base URL, e.g. `http://localhost:4318` (needs the `otel`
+    /// feature); traces are not exported without one.
+    pub otlp_endpoint: Option<String>,
+    pub service_name: String,
+}
+
+/// The Slack app users link their workspaces to.
+#[derive(Clone, Default)]
+pub struct SlackConfig {
+    /// Verifies requests to `POST /slack/commands`, which is not served
+    /// without it.
+    pub signing_secret: Option<String>,
+}
+
+impl std::fmt::Debug for SlackConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SlackConfig")
+            .field(
+                "signing_secret",
+                &self.signing_secret.as_ref().map(|_| "***"),
+            )
+            .finish()
+    }
+}
+
+/// The Telegram bot users chat with to manage their todos.
+#[derive(Clone, Default)]
+pub struct TelegramConfig {
+    /// The `secret_token` the bot's webhook was set up with; Telegram sends
+    /// it along every update to `POST /telegram/webhook`, which is not
+    /// served without it.
+    pub webhook_secret: Option<String>,
+}
+
+impl std::fmt::Debug for TelegramConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TelegramConfig")
+            .field(
+                "webhook_secret",
+                &self.webhook_secret.as_ref().map(|_| "***"),
+            )
+            .finish()
+    }
+}
+
+/// Inbound mail that turns into todos, forwarded by a Mailgun route.
+#[derive(Clone, Default)]
+pub struct EmailConfig {
+    /// The address mail is sent to, such as `todo@in.example.com`; each
+    /// user gets a plus-address of it, `todo+<token>@in.example.com`.
+    pub address: Option<String>,
+    /// Verifies the mail Mailgun posts to `POST /email/inbound`, which is
+    /// not served without it or `address`.
+    pub mailgun_signing_key: Option<String>,
+}
+
+impl std::fmt::Debug for EmailConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EmailConfig")
+            .field("address", &self.address)
+            .field(
+                "mailgun_signing_key",
+                &self.mailgun_signing_key.as_ref().map(|_| "***"),
+            )
+            .finish()
+    }
+}
+
+/// A Discord channel that hears of todo changes in every workspace.
+#[derive(Clone)]
+pub struct DiscordConfig {
+    /// The channel's webhook; nothing is posted without one.
+    pub webhook_url: Option<String>,
+    pub events: DiscordEvents,
+}
+
+/// Which todo events are posted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiscordEvents {
+    pub todo_created: bool,
+    pub todo_updated: bool,
+    pub todo_completed: bool,
+    pub todo_deleted: bool,
+    pub todo_assigned: bool,
+}
+
+impl std::fmt::Debug for DiscordConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // the webhook URL holds its token
+        f.debug_struct("DiscordConfig")
+            .field("webhook_url", &self.webhook_url.as_ref().map(|_| "***"))
+            .field("events", &self.events)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FeatureToggles {
+    pub revisions: bool,
+    /// Serve the GraphQL playground on `GET /graphql` (meant for development).
+    pub graphql_playground: bool,
+    /// Whether `POST /todos` refuses a todo whose text an open one already
+    /// has, when the request leaves `?detect_duplicates=` out.
+    pub detect_duplicates: bool,
+    /// Paths and links name todos and labels by their `uuid`, so ids can not
+    /// be guessed; serial ids in paths answer 404.
+    pub uuid_ids: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            host: IpAddr::from([0, 0, 0, 0]),
+            port: 3000,
+            listen: Vec::new(),
+            tls: None,
+            log_level: "info".to_string(),
+            log_format: LogFormat::Pretty,
+            allowed_origin: "http://localhost:3001".to_string(),
+            http: HttpConfig {
+                compression: true,
+                max_body_bytes: 64 * 1024,
+                request_timeout_secs: 30,
+                max_concurrent_requests: 512,
+                strict_json: false,
+                static_dir: None,
+                trusted_proxies: Vec::new(),
+            },
+            security_headers: SecurityHeadersConfig {
+                enabled: true,
+                content_type_options: "nosniff".to_string(),
+                frame_options: "DENY".to_string(),
+                referrer_policy: "strict-origin-when-cross-origin".to_string(),
+                strict_transport_security: "max-age=31536000; includeSubDomains".to_string(),
+                content_security_policy:
+                    "default-src 'self'; frame-ancestors 'none'; object-src 'none'".to_string(),
+            },
+            // answers depend on who asks, so shared caches keep none of them
+            cache_control: CacheControlConfig {
+                enabled: true,
+                mutations: "no-store".to_string(),
+                lists: "private, max-age=5, must-revalidate".to_string(),
+                items: "private, max-age=5, must-revalidate".to_string(),
+            },
+            database: DatabaseConfig {
+                url: String::new(),
+                replica_url: None,
+                max_connections: 5,
+                min_connections: 0,
+                acquire_timeout_secs: 5,
+                statement_timeout_ms: 10_000,
+                slow_query_ms: 500,
+                connect_retries: 10,
+                max_listed_todos: 500,
+                todo_storage: TodoStorage::Tables,
+                todo_file: "todos.json".to_string(),
+                mongodb_url: None,
+                redis_url: None,
+            },
+            rate_limit: RateLimitConfig {
+                enabled: true,
+                requests: 120,
+                window_secs: 60,
+                burst: 30,
+                redis_url: None,
+            },
+            cache: CacheConfig {
+                url: None,
+                ttl_secs: 30,
+                capacity: 10_000,
+            },
+            auth: AuthConfig {
+                jwt_secret: None,
+                access_token_ttl_secs: 15 * 60,
+                refresh_token_ttl_secs: 30 * 24 * 60 * 60,
+                session_ttl_secs: 7 * 24 * 60 * 60,
+                deletion_grace_secs: 30 * 24 * 60 * 60,
+                cookie_secure: true,
+                public_url: "http://localhost:3000".to_string(),
+                oauth_success_url: "/".to_string(),
+                github: None,
+                google: None,
+                lockout: LockoutConfig {
+                    enabled: true,
+                    account_failures: 5,
+                    ip_failures: 20,
+                    base_secs: 30,
+                    max_secs: 15 * 60,
+                    window_secs: 15 * 60,
+                    redis_url: None,
+                },
+            },
+            quota: QuotaConfig::default(),
+            search: SearchConfig {
+                similarity_threshold: 0.5,
+            },
+            events: EventsConfig {
+                backend: EventBackend::Memory,
+                kafka: KafkaConfig {
+                    brokers: None,
+                    topic: "my-todo.events".to_string(),
+                    format: EventFormat::Json,
+                },
+                nats: NatsConfig {
+                    url: None,
+                    subject_prefix: "todo".to_string(),
+                },
+            },
+            reporting: ReportingConfig::default(),
+            telemetry: TelemetryConfig {
+                otlp_endpoint: None,
+                service_name: "my-todo".to_string(),
+            },
+            slack: SlackConfig::default(),
+            telegram: TelegramConfig::default(),
+            // every update is noisy, and completions follow one anyway
+            discord: DiscordConfig {
+                webhook_url: None,
+                events: DiscordEvents {
+                    todo_created: true,
+                    todo_updated: false,
+                    todo_completed: true,
+                    todo_deleted: true,
+                    todo_assigned: true,
+                },
+            },
+            email: EmailConfig::default(),
+            features: FeatureToggles {
+                revisions: true,
+                graphql_playground: false,
+                detect_duplicates: false,
+                uuid_ids: false,
+            },
+        }
+    }
+}
+
+impl Config {
+    /// Load settings from `CONFIG_FILE` (default `config.toml`, optional)
+    /// and the process environment, which takes precedence.
+    pub fn load() -> Result<Self, ConfigError> {
+        let path = env::var("CONFIG_FILE").ok();
+        let file = match &path {
+            Some(path) => Some(read_file(path)?),
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Some(read_file(DEFAULT_CONFIG_FILE)?)
+            }
+            None => None,
+        };
+
+        Self::from_sources(file.as_deref(), |key| env::var(key).ok())
+    }
+
+    /// Where the server listens: `listen` when given, else `host`:`port`.
+    pub fn listen_addrs(&self) -> Vec<SocketAddr> {
+        if self.listen.is_empty() {
+            vec![SocketAddr::new(self.host, self.port)]
+        } else {
+            self.listen.clone()
+        }
+    }
+
+    pub fn from_sources(
+        file: Option<&str>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let file = match file {
+            Some(contents) => {
+                toml::from_str(contents).map_err(|e| ConfigError::Parse(e.to_string()))?
+            }
+            None => toml::Table::new(),
+        };
+        let src = Sources {
+            file,
+            env,
+            used: RefCell::default(),
+        };
+        let defaults = Config::default();
+
+        let allowed_origin: String =
+            src.get("ALLOWED_ORIGIN", "allowed_origin", defaults.allowed_origin)?;
+        check("ALLOWED_ORIGIN", &allowed_origin, |origin| {
+            origin
+                .parse::<hyper::header::HeaderValue>()
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        })?;
+
+        let http = HttpConfig {
+            compression: src.get(
+                "HTTP_COMPRESSION",
+                "http.compression",
+                defaults.http.compression,
+            )?,
+            max_body_bytes: src.get(
+                "HTTP_MAX_BODY_BYTES",
+                "http.max_body_bytes",
+                defaults.http.max_body_bytes,
+            )?,
+            request_timeout_secs: src.get(
+                "HTTP_REQUEST_TIMEOUT_SECS",
+                "http.request_timeout_secs",
+                defaults.http.request_timeout_secs,
+            )?,
+            max_concurrent_requests: src.get(
+                "HTTP_MAX_CONCURRENT_REQUESTS",
+                "http.max_concurrent_requests",
+                defaults.http.max_concurrent_requests,
+            )?,
+            strict_json: src.get(
+                "HTTP_STRICT_JSON",
+                "http.strict_json",
+                defaults.http.strict_json,
+            )?,
+            static_dir: src
+                .get_opt("HTTP_STATIC_DIR", "http.static_dir")?
+                .filter(|dir: &String| !dir.is_empty()),
+            trusted_proxies: src
+                .get_opt::<String>("HTTP_TRUSTED_PROXIES", "http.trusted_proxies")?
+                .map(|list| parse_list("HTTP_TRUSTED_PROXIES", &list))
+                .transpose()?
+                .unwrap_or_default(),
+        };
+        check("HTTP_MAX_BODY_BYTES", &http.max_body_bytes, at_least_one)?;
+        check(
+            "HTTP_REQUEST_TIMEOUT_SECS",
+            &http.request_timeout_secs,
+            at_least_one,
+        )?;
+        check(
+            "HTTP_MAX_CONCURRENT_REQUESTS",
+            &http.max_concurrent_requests,
+            at_least_one,
+        )?;
+
+        let headers = &defaults.security_headers;
+        let security_headers = SecurityHeadersConfig {
+            enabled: src.get(
+                "SECURITY_HEADERS_ENABLED",
+                "security_headers.enabled",
+                headers.enabled,
+            )?,
+            content_type_options: src.get(
+                "SECURITY_HEADERS_CONTENT_TYPE_OPTIONS",
+                "security_headers.content_type_options",
+                headers.content_type_options.clone(),
+            )?,
+            frame_options: src.get(
+                "SECURITY_HEADERS_FRAME_OPTIONS",
+                "security_headers.frame_options",
+                headers.frame_options.clone(),
+            )?,
+            referrer_policy: src.get(
+                "SECURITY_HEADERS_REFERRER_POLICY",
+                "security_headers.referrer_policy",
+                headers.referrer_policy.clone(),
+            )?,
+            strict_transport_security: src.get(
+                "SECURITY_HEADERS_STRICT_TRANSPORT_SECURITY",
+                "security_headers.strict_transport_security",
+                headers.strict_transport_security.clone(),
+            )?,
+            content_security_policy: src.get(
+                "SECURITY_HEADERS_CONTENT_SECURITY_POLICY",
+                "security_headers.content_security_policy",
+                headers.content_security_policy.clone(),
+            )?,
+        };
+        for (key, value) in [
+            (
+                "SECURITY_HEADERS_CONTENT_TYPE_OPTIONS",
+                &security_headers.content_type_options,
+            ),
+            (
+                "SECURITY_HEADERS_FRAME_OPTIONS",
+                &security_headers.frame_options,
+            ),
+            (
+                "SECURITY_HEADERS_REFERRER_POLICY",
+                &security_headers.referrer_policy,
+            ),
+            (
+                "SECURITY_HEADERS_STRICT_TRANSPORT_SECURITY",
+                &security_headers.strict_transport_security,
+            ),
+            (
+                "SECURITY_HEADERS_CONTENT_SECURITY_POLICY",
+                &security_headers.content_security_policy,
+            ),
+        ] {
+            check(key, value, |value| {
+                value
+                    .parse::<hyper::header::HeaderValue>()
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            })?;
+        }
+
+        let cache_control = CacheControlConfig {
+            enabled: src.get(
+                "CACHE_CONTROL_ENABLED",
+                "cache_control.enabled",
+                defaults.cache_control.enabled,
+            )?,
+            mutations: src.get(
+                "CACHE_CONTROL_MUTATIONS",
+                "cache_control.mutations",
+                defaults.cache_control.mutations,
+            )?,
+            lists: src.get(
+                "CACHE_CONTROL_LISTS",
+                "cache_control.lists",
+                defaults.cache_control.lists,
+            )?,
+            items: src.get(
+                "CACHE_CONTROL_ITEMS",
+                "cache_control.items",
+                defaults.cache_control.items,
+            )?,
+        };
+        for (key, value) in [
+            ("CACHE_CONTROL_MUTATIONS", &cache_control.mutations),
+            ("CACHE_CONTROL_LISTS", &cache_control.lists),
+            ("CACHE_CONTROL_ITEMS", &cache_control.items),
+        ] {
+            check(key, value, |value| {
+                value
+                    .parse::<hyper::header::HeaderValue>()
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            })?;
+        }
+
+        let listen = src
+            .get_opt::<String>("LISTEN", "listen")?
+            .map(|list| parse_list("LISTEN", &list))
+            .transpose()?
+            .unwrap_or(defaults.listen);
+
+        let database = DatabaseConfig {
+            url: src
+                .get_opt("DATABASE_URL", "database.url")?
+                .filter(|url: &String| !url.is_empty())
+                .ok_or(ConfigError::Missing("DATABASE_URL"))?,
+            replica_url: src
+                .get_opt("DATABASE_REPLICA_URL", "database.replica_url")?
+                .filter(|url: &String| !url.is_empty()),
+            max_connections: src.get(
+                "DATABASE_MAX_CONNECTIONS",
+                "database.max_connections",
+                defaults.database.max_connections,
+            )?,
+            min_connections: src.get(
+                "DATABASE_MIN_CONNECTIONS",
+                "database.min_connections",
+                defaults.database.min_connections,
+            )?,
+            acquire_timeout_secs: src.get(
+                "DATABASE_ACQUIRE_TIMEOUT_SECS",
+                "database.acquire_timeout_secs",
+                defaults.database.acquire_timeout_secs,
+            )?,
+            statement_timeout_ms: src.get(
+                "DATABASE_STATEMENT_TIMEOUT_MS",
+                "database.statement_timeout_ms",
+                defaults.database.statement_timeout_ms,
+            )?,
+            slow_query_ms: src.get(
+                "DATABASE_SLOW_QUERY_MS",
+                "database.slow_query_ms",
+                defaults.database.slow_query_ms,
+            )?,
+            connect_retries: src.get(
+                "DATABASE_CONNECT_RETRIES",
+                "database.connect_retries",
+                defaults.database.connect_retries,
+            )?,
+            max_listed_todos: src.get(
+                "DATABASE_MAX_LISTED_TODOS",
+                "database.max_listed_todos",
+                defaults.database.max_listed_todos,
+            )?,
+            todo_storage: src.get(
+                "DATABASE_TODO_STORAGE",
+                "database.todo_storage",
+                defaults.database.todo_storage,
+            )?,
+            todo_file: src.get(
+                "DATABASE_TODO_FILE",
+                "database.todo_file",
+                defaults.database.todo_file,
+            )?,
+            mongodb_url: src
+                .get_opt("DATABASE_MONGODB_URL", "database.mongodb_url")?
+                .filter(|url: &String| !url.is_empty()),
+            redis_url: src
+                .get_opt("DATABASE_REDIS_URL", "database.redis_url")?
+                .filter(|url: &String| !url.is_empty()),
+        };
+        check(
+            "DATABASE_MAX_CONNECTIONS",
+            &database.max_connections,
+            at_least_one,
+        )?;
+        check(
+            "DATABASE_MIN_CONNECTIONS",
+            &database.min_connections,
+            |min| {
+                if *min > database.max_connections {
+                    Err("must not exceed DATABASE_MAX_CONNECTIONS".to_string())
+                } else {
+                    Ok(())
+                }
+            },
+        )?;
+        check(
+            "DATABASE_ACQUIRE_TIMEOUT_SECS",
+            &database.acquire_timeout_secs,
+            at_least_one,
+        )?;
+
+        let rate_limit = RateLimitConfig {
+            enabled: src.get(
+                "RATE_LIMIT_ENABLED",
+                "rate_limit.enabled",
+                defaults.rate_limit.enabled,
+            )?,
+            requests: src.get(
+                "RATE_LIMIT_REQUESTS",
+                "rate_limit.requests",
+                defaults.rate_limit.requests,
+            )?,
+            window_secs: src.get(
+                "RATE_LIMIT_WINDOW_SECS",
+                "rate_limit.window_secs",
+                defaults.rate_limit.window_secs,
+            )?,
+            burst: src.get(
+                "RATE_LIMIT_BURST",
+                "rate_limit.burst",
+                defaults.rate_limit.burst,
+            )?,
+            redis_url: src.get_opt("RATE_LIMIT_REDIS_URL", "rate_limit.redis_url")?,
+        };
+        check("RATE_LIMIT_REQUESTS", &rate_limit.requests, at_least_one)?;
+        check(
+            "RATE_LIMIT_WINDOW_SECS",
+            &rate_limit.window_secs,
+            at_least_one,
+        )?;
+        check("RATE_LIMIT_BURST", &rate_limit.burst, at_least_one)?;
+        if cfg!(not(feature = "redis")) && rate_limit.redis_url.is_some() {
+            return Err(ConfigError::Invalid {
+                key: "RATE_LIMIT_REDIS_URL",
+                value: rate_limit.redis_url.unwrap_or_default(),
+                reason: "built without the `redis` feature".to_string(),
+            });
+        }
+
+        let cache = CacheConfig {
+            url: src.get_opt("CACHE_URL", "cache.url")?,
+            ttl_secs: src.get("CACHE_TTL_SECS", "cache.ttl_secs", defaults.cache.ttl_secs)?,
+            capacity: src.get("CACHE_CAPACITY", "cache.capacity", defaults.cache.capacity)?,
+        };
+        check("CACHE_TTL_SECS", &cache.ttl_secs, at_least_one)?;
+        check("CACHE_CAPACITY", &cache.capacity, at_least_one)?;
+        if let Some(url) = &cache.url {
+            check("CACHE_URL", url, |url| match url.split_once("://") {
+                Some(("memory", _)) => Ok(()),
+                Some(("redis" | "rediss", _)) if cfg!(feature = "redis") => Ok(()),
+                Some(("redis" | "rediss", _)) => {
+                    Err("built without the `redis` feature".to_string())
+                }
+                _ => Err("expected memory:// or redis://".to_string()),
+            })?;
+        }
+
+        let auth = AuthConfig {
+            jwt_secret: src.get_opt("AUTH_JWT_SECRET", "auth.jwt_secret")?,
+            access_token_ttl_secs: src.get(
+                "AUTH_ACCESS_TOKEN_TTL_SECS",
+                "auth.access_token_ttl_secs",
+                defaults.auth.access_token_ttl_secs,
+            )?,
+            refresh_token_ttl_secs: src.get(
+                "AUTH_REFRESH_TOKEN_TTL_SECS",
+                "auth.refresh_token_ttl_secs",
+                defaults.auth.refresh_token_ttl_secs,
+            )?,
+            session_ttl_secs: src.get(
+                "AUTH_SESSION_TTL_SECS",
+                "auth.session_ttl_secs",
+                defaults.auth.session_ttl_secs,
+            )?,
+            deletion_grace_secs: src.get(
+                "AUTH_DELETION_GRACE_SECS",
+                "auth.deletion_grace_secs",
+                defaults.auth.deletion_grace_secs,
+            )?,
+            cookie_secure: src.get(
+                "AUTH_COOKIE_SECURE",
+                "auth.cookie_secure",
+                defaults.auth.cookie_secure,
+            )?,
+            public_url: src.get(
+                "AUTH_PUBLIC_URL",
+                "auth.public_url",
+                defaults.auth.public_url,
+            )?,
+            oauth_success_url: src.get(
+                "AUTH_OAUTH_SUCCESS_URL",
+                "auth.oauth_success_url",
+                defaults.auth.oauth_success_url,
+            )?,
+            github: src.oauth_client(
+                ("AUTH_GITHUB_CLIENT_ID", "auth.github.client_id"),
+                ("AUTH_GITHUB_CLIENT_SECRET", "auth.github.client_secret"),
+            )?,
+            google: src.oauth_client(
+                ("AUTH_GOOGLE_CLIENT_ID", "auth.google.client_id"),
+                ("AUTH_GOOGLE_CLIENT_SECRET", "auth.google.client_secret"),
+            )?,
+            lockout: LockoutConfig {
+                enabled: src.get(
+                    "AUTH_LOCKOUT_ENABLED",
+                    "auth.lockout.enabled",
+                    defaults.auth.lockout.enabled,
+                )?,
+                account_failures: src.get(
+                    "AUTH_LOCKOUT_ACCOUNT_FAILURES",
+                    "auth.lockout.account_failures",
+                    defaults.auth.lockout.account_failures,
+                )?,
+                ip_failures: src.get(
+                    "AUTH_LOCKOUT_IP_FAILURES",
+                    "auth.lockout.ip_failures",
+                    defaults.auth.lockout.ip_failures,
+                )?,
+                base_secs: src.get(
+                    "AUTH_LOCKOUT_BASE_SECS",
+                    "auth.lockout.base_secs",
+                    defaults.auth.lockout.base_secs,
+                )?,
+                max_secs: src.get(
+                    "AUTH_LOCKOUT_MAX_SECS",
+                    "auth.lockout.max_secs",
+                    defaults.auth.lockout.max_secs,
+                )?,
+                window_secs: src.get(
+                    "AUTH_LOCKOUT_WINDOW_SECS",
+                    "auth.lockout.window_secs",
+                    defaults.auth.lockout.window_secs,
+                )?,
+                redis_url: src.get_opt("AUTH_LOCKOUT_REDIS_URL", "auth.lockout.redis_url")?,
+            },
+        };
+        let lockout = &auth.lockout;
+        check(
+            "AUTH_LOCKOUT_ACCOUNT_FAILURES",
+            &lockout.account_failures,
+            at_least_one,
+        )?;
+        check(
+            "AUTH_LOCKOUT_IP_FAILURES",
+            &lockout.ip_failures,
+            at_least_one,
+        )?;
+        check("AUTH_LOCKOUT_BASE_SECS", &lockout.base_secs, at_least_one)?;
+        check("AUTH_LOCKOUT_MAX_SECS", &lockout.max_secs, |max| {
+            if *max < lockout.base_secs {
+                Err("must not be below AUTH_LOCKOUT_BASE_SECS".to_string())
+            } else {
+                Ok(())
+            }
+        })?;
+        check(
+            "AUTH_LOCKOUT_WINDOW_SECS",
+            &lockout.window_secs,
+            at_least_one,
+        )?;
+        if cfg!(not(feature = "redis")) && lockout.redis_url.is_some() {
+            return Err(ConfigError::Invalid {
+                key: "AUTH_LOCKOUT_REDIS_URL",
+                value: lockout.redis_url.clone().unwrap_or_default(),
+                reason: "built without the `redis` feature".to_string(),
+            });
+        }
+        check("AUTH_PUBLIC_URL", &auth.public_url, |url| {
+            if url.starts_with("http://") || url.starts_with("https://") {
+                Ok(())
+            } else {
+                Err("expected an http:// or https:// URL".to_string())
+            }
+        })?;
+        if let Some(secret) = &auth.jwt_secret {
+            if secret.len() < MIN_JWT_SECRET_BYTES {
+                return Err(ConfigError::Invalid {
+                    key: "AUTH_JWT_SECRET",
+                    value: "***".to_string(),
+                    reason: format!("must be at least {} bytes", MIN_JWT_SECRET_BYTES),
+                });
+            }
+        }
+        check(
+            "AUTH_ACCESS_TOKEN_TTL_SECS",
+            &auth.access_token_ttl_secs,
+            at_least_one,
+        )?;
+        check(
+            "AUTH_REFRESH_TOKEN_TTL_SECS",
+            &auth.refresh_token_ttl_secs,
+            at_least_one,
+        )?;
+        check(
+            "AUTH_SESSION_TTL_SECS",
+            &auth.session_ttl_secs,
+            at_least_one,
+        )?;
+
+        let kafka = KafkaConfig {
+            brokers: src.get_opt("EVENTS_KAFKA_BROKERS", "events.kafka.brokers")?,
+            topic: src.get(
+                "EVENTS_KAFKA_TOPIC",
+                "events.kafka.topic",
+                defaults.events.kafka.topic,
+            )?,
+            format: src.get(
+                "EVENTS_KAFKA_FORMAT",
+                "events.kafka.format",
+                defaults.events.kafka.format,
+            )?,
+        };
+        if cfg!(not(feature = "kafka")) && kafka.brokers.is_some() {
+            return Err(ConfigError::Invalid {
+                key: "EVENTS_KAFKA_BROKERS",
+                value: kafka.brokers.unwrap_or_default(),
+                reason: "built without the `kafka` feature".to_string(),
+            });
+        }
+        let nats = NatsConfig {
+            url: src.get_opt("EVENTS_NATS_URL", "events.nats.url")?,
+            subject_prefix: src.get(
+                "EVENTS_NATS_SUBJECT_PREFIX",
+                "events.nats.subject_prefix",
+                defaults.events.nats.subject_prefix,
+            )?,
+        };
+        if cfg!(not(feature = "nats")) && nats.url.is_some() {
+            return Err(ConfigError::Invalid {
+                key: "EVENTS_NATS_URL",
+                value: nats.url.unwrap_or_default(),
+                reason: "built without the `nats` feature".to_string(),
+            });
+        }
+
+        let search = SearchConfig {
+            similarity_threshold: src.get(
+                "SEARCH_SIMILARITY_THRESHOLD",
+                "search.similarity_threshold",
+                defaults.search.similarity_threshold,
+            )?,
+        };
+        check(
+            "SEARCH_SIMILARITY_THRESHOLD",
+            &search.similarity_threshold,
+            |threshold| {
+                if (0.0..=1.0).contains(threshold) {
+                    Ok(())
+                } else {
+                    Err("must be between 0 and 1".to_string())
+                }
+            },
+        )?;
+
+        let reporting = ReportingConfig {
+            sentry_dsn: src
+                .get_opt("SENTRY_DSN", "reporting.sentry_dsn")?
+                .filter(|dsn: &String| !dsn.is_empty()),
+            environment: src.get_opt("SENTRY_ENVIRONMENT", "reporting.environment")?,
+        };
+        if cfg!(not(feature = "sentry")) && reporting.sentry_dsn.is_some() {
+            return Err(ConfigError::Invalid {
+                key: "SENTRY_DSN",
+                value: reporting.sentry_dsn.unwrap_or_default(),
+                reason: "built without the `sentry` feature".to_string(),
+            });
+        }
+
+        let telemetry = TelemetryConfig {
+            otlp_endpoint: src
+                .get_opt("OTEL_EXPORTER_OTLP_ENDPOINT", "telemetry.otlp_endpoint")?
+                .filter(|url: &String| !url.is_empty()),
+            service_name: src.get(
+                "OTEL_SERVICE_NAME",
+                "telemetry.service_name",
+                defaults.telemetry.service_name,
+            )?,
+        };
+        if cfg!(not(feature = "otel")) && telemetry.otlp_endpoint.is_some() {
+            return Err(ConfigError::Invalid {
+                key: "OTEL_EXPORTER_OTLP_ENDPOINT",
+                value: telemetry.otlp_endpoint.unwrap_or_default(),
+                reason: "built without the `otel` feature".to_string(),
+            });
+        }
+
+        let discord = DiscordConfig {
+            webhook_url: src
+                .get_opt("DISCORD_WEBHOOK_URL", "discord.webhook_url")?
+                .filter(|url: &String| !url.is_empty()),
+            events: DiscordEvents {
+                todo_created: src.get(
+                    "DISCORD_TODO_CREATED",
+                    "discord.events.todo_created",
+                    defaults.discord.events.todo_created,
+                )?,
+                todo_updated: src.get(
+                    "DISCORD_TODO_UPDATED",
+                    "discord.events.todo_updated",
+                    defaults.discord.events.todo_updated,
+                )?,
+                todo_completed: src.get(
+                    "DISCORD_TODO_COMPLETED",
+                    "discord.events.todo_completed",
+                    defaults.discord.events.todo_completed,
+                )?,
+                todo_deleted: src.get(
+                    "DISCORD_TODO_DELETED",
+                    "discord.events.todo_deleted",
+                    defaults.discord.events.todo_deleted,
+                )?,
+                todo_assigned: src.get(
+                    "DISCORD_TODO_ASSIGNED",
+                    "discord.events.todo_assigned",
+                    defaults.discord.events.todo_assigned,
+                )?,
+            },
+        };
+        if let Some(url) = &discord.webhook_url {
+            check("DISCORD_WEBHOOK_URL", url, |url| {
+                if url.starts_with("https://") {
+                    Ok(())
+                } else {
+                    Err("expected an https:// URL".to_string())
+                }
+            })?;
+        }
+
+        let email = EmailConfig {
+            address: src
+                .get_opt("EMAIL_ADDRESS", "email.address")?
+                .filter(|address: &String| !address.is_empty()),
+            mailgun_signing_key: src
+                .get_opt("EMAIL_MAILGUN_SIGNING_KEY", "email.mailgun_signing_key")?
+                .filter(|key: &String| !key.is_empty()),
+        };
+        if let Some(address) = &email.address {
+            check("EMAIL_ADDRESS", address, |address| {
+                match address.split_once('@') {
+                    Some((local, domain))
+                        if !local.is_empty()
+                            && !local.contains('+')
+                            && !domain.is_empty()
+                            && !domain.contains('@') =>
+                    {
+                        Ok(())
+                    }
+                    _ => Err("expected an address like todo@in.example.com".to_string()),
+                }
+            })?;
+        }
+
+        let config = Self {
+            host: src.get("HOST", "host", defaults.host)?,
+            port: src.get("PORT", "port", defaults.port)?,
+            listen,
+            tls: src.tls()?,
+            log_level: src.get("RUST_LOG", "log_level", defaults.log_level)?,
+            log_format: src.get("LOG_FORMAT", "log_format", defaults.log_format)?,
+            allowed_origin,
+            http,
+            security_headers,
+            cache_control,
+            database,
+            rate_limit,
+            cache,
+            auth,
+            quota: QuotaConfig {
+                max_open_todos: src.get_opt("QUOTA_MAX_OPEN_TODOS", "quota.max_open_todos")?,
+                max_labels: src.get_opt("QUOTA_MAX_LABELS", "quota.max_labels")?,
+            },
+            search,
+            events: EventsConfig {
+                backend: src.get("EVENTS_BACKEND", "events.backend", defaults.events.backend)?,
+                kafka,
+                nats,
+            },
+            reporting,
+            telemetry,
+            slack: SlackConfig {
+                signing_secret: src
+                    .get_opt("SLACK_SIGNING_SECRET", "slack.signing_secret")?
+                    .filter(|secret: &String| !secret.is_empty()),
+            },
+            telegram: TelegramConfig {
+                webhook_secret: src
+                    .get_opt("TELEGRAM_WEBHOOK_SECRET", "telegram.webhook_secret")?
+                    .filter(|secret: &String| !secret.is_empty()),
+            },
+            discord,
+            email,
+            features: FeatureToggles {
+                revisions: src.get(
+                    "FEATURE_REVISIONS",
+                    "features.revisions",
+                    defaults.features.revisions,
+                )?,
+                graphql_playground: src.get(
+                    "FEATURE_GRAPHQL_PLAYGROUND",
+                    "features.graphql_playground",
+                    defaults.features.graphql_playground,
+                )?,
+                detect_duplicates: src.get(
+                    "FEATURE_DETECT_DUPLICATES",
+                    "features.detect_duplicates",
+                    defaults.features.detect_duplicates,
+                )?,
+                uuid_ids: src.get(
+                    "FEATURE_UUID_IDS",
+                    "features.uuid_ids",
+                    defaults.features.uuid_ids,
+                )?,
+            },
+        };
+        src.finish()?;
+
+        Ok(config)
+    }
+}
+
+/// A comma-separated list such as `0.0.0.0:3000, [::1]:3000`.
+fn parse_list<T>(key: &'static str, list: &str) -> Result<Vec<T>, ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    list.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            item.parse().map_err(|e: T::Err| ConfigError::Invalid {
+                key,
+                value: item.to_string(),
+                reason: e.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Looks settings up in the environment first and the TOML file second,
+/// remembering which file keys were read so typos can be reported.
+struct Sources<E> {
+    file: toml::Table,
+    env: E,
+    used: RefCell<HashSet<String>>,
+}
+
+impl<E: Fn(&str) -> Option<String>> Sources<E> {
+    fn get<T>(&self, env_key: &'static str, file_key: &str, default: T) -> Result<T, ConfigError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        Ok(self.get_opt(env_key, file_key)?.unwrap_or(default))
+    }
+
+    fn get_opt<T>(&self, env_key: &'static str, file_key: &str) -> Result<Option<T>, ConfigError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.used.borrow_mut().insert(file_key.to_string());
+        let value = match (self.env)(env_key) {
+            Some(value) => value,
+            None => match self.lookup(file_key) {
+                Some(toml::Value::String(value)) => value.clone(),
+                Some(value) => value.to_string(),
+                None => return Ok(None),
+            },
+        };
+
+        value
+            .parse()
+            .map(Some)
+            .map_err(|e: T::Err| ConfigError::Invalid {
+                key: env_key,
+                reason: e.to_string(),
+                value,
+            })
+    }
+
+    /// Both halves of an OAuth client or neither.
+    fn oauth_client(
+        &self,
+        (id_env, id_file): (&'static str, &str),
+        (secret_env, secret_file): (&'static str, &str),
+    ) -> Result<Option<OAuthClientConfig>, ConfigError> {
+        let client_id: Option<String> = self.get_opt(id_env, id_file)?;
+        let client_secret: Option<String> = self.get_opt(secret_env, secret_file)?;
+        match (client_id, client_secret) {
+            (Some(client_id), Some(client_secret)) => Ok(Some(OAuthClientConfig {
+                client_id,
+                client_secret,
+            })),
+            (None, None) => Ok(None),
+            (Some(_), None) => Err(ConfigError::Missing(secret_env)),
+            (None, Some(_)) => Err(ConfigError::Missing(id_env)),
+        }
+    }
+
+    /// Both TLS files or neither.
+    fn tls(&self) -> Result<Option<TlsConfig>, ConfigError> {
+        let cert_path: Option<String> = self.get_opt("TLS_CERT_PATH", "tls.cert_path")?;
+        let key_path: Option<String> = self.get_opt("TLS_KEY_PATH", "tls.key_path")?;
+        match (cert_path, key_path) {
+            (Some(cert_path), Some(key_path)) => Ok(Some(TlsConfig {
+                cert_path,
+                key_path,
+            })),
+            (None, None) => Ok(None),
+            (Some(_), None) => Err(ConfigError::Missing("TLS_KEY_PATH")),
+            (None, Some(_)) => Err(ConfigError::Missing("TLS_CERT_PATH")),
+        }
+    }
+
+    fn lookup(&self, file_key: &str) -> Option<&toml::Value> {
+        let mut keys = file_key.split('.');
+        let mut value = self.file.get(keys.next()?)?;
+        for key in keys {
+            value = value.as_table()?.get(key)?;
+        }
+        Some(value)
+    }
+
+    fn finish(self) -> Result<(), ConfigError> {
+        let used = self.used.into_inner();
+        let mut unknown = Vec::new();
+        collect_unknown(&self.file, "", &used, &mut unknown);
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Parse(format!(
+                "unknown keys [{}]",
+                unknown.join(", ")
+            )))
+        }
+    }
+}
+
+fn collect_unknown(
+    table: &toml::Table,
+    prefix: &str,
+    used: &HashSet<String>,
+    unknown: &mut Vec<String>,
+) {
+    for (key, value) in table {
+        let path = format!("{}{}", prefix, key);
+        match value {
+            toml::Value::Table(table) => {
+                collect_unknown(table, &format!("{}.", path), used, unknown)
+            }
+            _ if !used.contains(&path) => unknown.push(path),
+            _ => {}
+        }
+    }
+}
+
+fn check<T: Display>(
+    key: &'static str,
+    value: &T,
+    rule: impl Fn(&T) -> Result<(), String>,
+) -> Result<(), ConfigError> {
+    rule(value).map_err(|reason| ConfigError::Invalid {
+        key,
+        value: value.to_string(),
+        reason,
+    })
+}
+
+fn at_least_one<T: PartialOrd + From<u8>>(value: &T) -> Result<(), String> {
+    if *value >= T::from(1) {
+        Ok(())
+    } else {
+        Err("must be at least 1".to_string())
+    }
+}
+
+fn read_file(path: &str) -> Result<String, ConfigError> {
+    fs::read_to_string(path).map_err(|e| ConfigError::File {
+        path: path.to_string(),
+        reason: e.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env_of(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |key| vars.get(key).cloned()
+    }
+
+    #[test]
+    fn should_require_database_url() {
+        let result = Config::from_sources(None, env_of(&[]));
+        assert!(matches!(result, Err(ConfigError::Missing("DATABASE_URL"))));
+    }
+
+    #[test]
+    fn should_prefer_env_over_file() {
+        let file = r#"
+            port = 8080
+            log_level = "debug"
+
+            [http]
+            static_dir = "./dist"
+
+            [database]
+            url = "postgres://file"
+            max_connections = 10
+            todo_storage = "event_store"
+            todo_file = "/var/lib/my-todo/todos.json"
+
+            [rate_limit]
+            burst = 5
+
+            [features]
+            revisions = false
+        "#;
+        let config = Config::from_sources(
+            Some(file),
+            env_of(&[("PORT", "9000"), ("DATABASE_URL", "postgres://env")]),
+        )
+        .unwrap();
+
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.log_level, "debug");
+        assert_eq!(config.http.static_dir.as_deref(), Some("./dist"));
+        assert_eq!(config.database.url, "postgres://env");
+        assert_eq!(config.database.replica_url, None);
+        assert_eq!(config.database.max_connections, 10);
+        assert_eq!(config.database.todo_storage, TodoStorage::EventStore);
+        assert_eq!(config.database.todo_file, "/var/lib/my-todo/todos.json");
+        assert_eq!(config.database.mongodb_url, None);
+        assert_eq!(config.database.redis_url, None);
+        assert_eq!(config.rate_limit.burst, 5);
+        assert!(!config.features.revisions);
+    }
+
+    #[test]
+    fn should_listen_on_several_addresses() {
+        let config = Config::from_sources(
+            Some("host = \"::\"\nport = 8080"),
+            env_of(&[("DATABASE_URL", "postgres://env")]),
+        )
+        .unwrap();
+        assert_eq!(config.listen_addrs(), vec!["[::]:8080".parse().unwrap()]);
+
+        let config = Config::from_sources(
+            None,
+            env_of(&[
+                ("DATABASE_URL", "postgres://env"),
+                ("LISTEN", "127.0.0.1:3000, [::1]:3000"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            config.listen_addrs(),
+            vec![
+                "127.0.0.1:3000".parse().unwrap(),
+                "[::1]:3000".parse().unwrap()
+            ]
+        );
+
+        let result = Config::from_sources(
+            None,
+            env_of(&[("DATABASE_URL", "postgres://env"), ("LISTEN", "localhost")]),
+        );
+        assert!(matches!(
+            result,
+            Err(ConfigError::Invalid { key: "LISTEN", .. })
+        ));
+    }
+
+    #[test]
+    fn should_match_trusted_proxy_blocks() {
+        let config = Config::from_sources(
+            Some("[http]\ntrusted_proxies = \"10.0.0.0/8, 192.168.1.1, fd00::/8\""),
+            env_of(&[("DATABASE_URL", "postgres://env")]),
+        )
+        .unwrap();
+        let trusts = |ip: &str| {
+            let ip = ip.parse().unwrap();
+            config
+                .http
+                .trusted_proxies
+                .iter()
+                .any(|net| net.contains(ip))
+        };
+        assert!(trusts("10.1.2.3"));
+        assert!(trusts("192.168.1.1"));
+        assert!(trusts("::ffff:10.0.0.1"));
+        assert!(trusts("fd12::1"));
+        assert!(!trusts("192.168.1.2"));
+        assert!(!trusts("11.0.0.1"));
+        assert!(!trusts("fe80::1"));
+
+        let result = Config::from_sources(
+            None,
+            env_of(&[
+                ("DATABASE_URL", "postgres://env"),
+                ("HTTP_TRUSTED_PROXIES", "10.0.0.0/33"),
+            ]),
+        );
+        assert!(matches!(
+            result,
+            Err(ConfigError::Invalid {
+                key: "HTTP_TRUSTED_PROXIES",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn should_reject_invalid_values() {
+        let result = Config::from_sources(
+            None,
+            env_of(&[("DATABASE_URL", "postgres://env"), ("PORT", "http")]),
+        );
+        assert!(matches!(
+            result,
+            Err(ConfigError::Invalid { key: "PORT", .. })
+        ));
+
+        let result = Config::from_sources(
+            None,
+            env_of(&[
+                ("DATABASE_URL", "postgres://env"),
+                ("DATABASE_MAX_CONNECTIONS", "0"),
+            ]),
+        );
+        assert!(matches!(
+            result,
+            Err(ConfigError::Invalid {
+                key: "DATABASE_MAX_CONNECTIONS",
+                ..
+            })
+        ));
+
+        let result = Config::from_sources(
+            None,
+            env_of(&[
+                ("DATABASE_URL", "postgres://env"),
+                ("DATABASE_MIN_CONNECTIONS", "6"),
+            ]),
+        );
+        assert!(matches!(
+            result,
+            Err(ConfigError::Invalid {
+                key: "DATABASE_MIN_CONNECTIONS",
+                ..
+            })
+        ));
+
+        let result = Config::from_sources(
+            None,
+            env_of(&[
+                ("DATABASE_URL", "postgres://env"),
+                ("SEARCH_SIMILARITY_THRESHOLD", "1.5"),
+            ]),
+        );
+        assert!(matches!(
+            result,
+            Err(ConfigError::Invalid {
+                key: "SEARCH_SIMILARITY_THRESHOLD",
+                ..
+            })
+        ));
+
+        let result = Config::from_sources(
+            None,
+            env_of(&[
+                ("DATABASE_URL", "postgres://env"),
+                ("AUTH_GITHUB_CLIENT_ID", "client"),
+            ]),
+        );
+        assert!(matches!(
+            result,
+            Err(ConfigError::Missing("AUTH_GITHUB_CLIENT_SECRET"))
+        ));
+
+        let result = Config::from_sources(
+            None,
+            env_of(&[
+                ("DATABASE_URL", "postgres://env"),
+                ("AUTH_JWT_SECRET", "short"),
+            ]),
+        );
+        assert!(matches!(
+            result,
+            Err(ConfigError::Invalid {
+                key: "AUTH_JWT_SECRET",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn should_validate_cache_url() {
+        let config = Config::from_sources(
+            Some("[cache]\nurl = \"memory://\""),
+            env_of(&[("DATABASE_URL", "postgres://env")]),
+        )
+        .unwrap();
+        assert_eq!(config.cache.url.as_deref(), Some("memory://"));
+
+        let result = Config::from_sources(
+            None,
+            env_of(&[
+                ("DATABASE_URL", "postgres://env"),
+                ("CACHE_URL", "memcached://localhost"),
+            ]),
+        );
+        assert!(matches!(
+            result,
+            Err(ConfigError::Invalid {
+                key: "CACHE_URL",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn should_parse_log_format() {
+        let config = Config::from_sources(
+            Some("log_format = \"json\""),
+            env_of(&[("DATABASE_URL", "postgres://env")]),
+        )
+        .unwrap();
+        assert_eq!(config.log_format, LogFormat::Json);
+
+        let result = Config::from_sources(
+            None,
+            env_of(&[("DATABASE_URL", "postgres://env"), ("LOG_FORMAT", "xml")]),
+        );
+        assert!(matches!(
+            result,
+            Err(ConfigError::Invalid {
+                key: "LOG_FORMAT",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn should_parse_event_backend() {
+        let config = Config::from_sources(
+            Some("[events]\nbackend = \"postgres\""),
+            env_of(&[("DATABASE_URL", "postgres://env")]),
+        )
+        .unwrap();
+        assert_eq!(config.events.backend, EventBackend::Postgres);
+
+        let result = Config::from_sources(
+            None,
+            env_of(&[
+                ("DATABASE_URL", "postgres://env"),
+                ("EVENTS_BACKEND", "kafka"),
+            ]),
+        );
+        assert!(matches!(
+            result,
+            Err(ConfigError::Invalid {
+                key: "EVENTS_BACKEND",
+                ..
+            })
+        ));
+
+        let config = Config::from_sources(
+            Some("[events.kafka]\ntopic = \"todos\"\nformat = \"avro\""),
+            env_of(&[("DATABASE_URL", "postgres://env")]),
+        )
+        .unwrap();
+        assert_eq!(config.events.kafka.topic, "todos");
+        assert_eq!(config.events.kafka.format, EventFormat::Avro);
+        assert_eq!(config.events.kafka.brokers, None);
+
+        let result = Config::from_sources(
+            None,
+            env_of(&[
+                ("DATABASE_URL", "postgres://env"),
+                ("EVENTS_KAFKA_BROKERS", "localhost:9092"),
+            ]),
+        );
+        assert_eq!(result.is_ok(), cfg!(feature = "kafka"));
+
+        let result = Config::from_sources(
+            Some("[events.nats]\nurl = \"nats://localhost:4222\""),
+            env_of(&[("DATABASE_URL", "postgres://env")]),
+        );
+        assert_eq!(result.is_ok(), cfg!(feature = "nats"));
+    }
+
+    #[test]
+    fn should_require_both_tls_files() {
+        let config =
+            Config::from_sources(None, env_of(&[("DATABASE_URL", "postgres://env")])).unwrap();
+        assert!(config.tls.is_none());
+
+        let file = "[tls]\ncert_path = \"cert.pem\"\nkey_path = \"key.pem\"";
+        let config =
+            Config::from_sources(Some(file), env_of(&[("DATABASE_URL", "postgres://env")]))
+                .unwrap();
+        let tls = config.tls.unwrap();
+        assert_eq!(tls.cert_path, "cert.pem");
+        assert_eq!(tls.key_path, "key.pem");
+
+        let result = Config::from_sources(
+            None,
+            env_of(&[
+                ("DATABASE_URL", "postgres://env"),
+                ("TLS_CERT_PATH", "cert.pem"),
+            ]),
+        );
+        assert!(matches!(result, Err(ConfigError::Missing("TLS_KEY_PATH"))));
+    }
+
+    #[test]
+    fn should_override_security_headers() {
+        let file = r#"
+            [security_headers]
+            frame_options = ""
+            content_security_policy = "default-src 'self' https://cdn.example.com"
+        "#;
+        let config = Config::from_sources(
+            Some(file),
+            env_of(&[
+                ("DATABASE_URL", "postgres://env"),
+                ("SECURITY_HEADERS_REFERRER_POLICY", "no-referrer"),
+            ]),
+        )
+        .unwrap();
+        let headers = &config.security_headers;
+        assert_eq!(headers.content_type_options, "nosniff");
+        assert_eq!(headers.frame_options, "");
+        assert_eq!(headers.referrer_policy, "no-referrer");
+        assert_eq!(
+            headers.content_security_policy,
+            "default-src 'self' https://cdn.example.com"
+        );
+
+        let result = Config::from_sources(
+            None,
+            env_of(&[
+                ("DATABASE_URL", "postgres://env"),
+                ("SECURITY_HEADERS_FRAME_OPTIONS", "DENY\nX-Injected: 1"),
+            ]),
+        );
+        assert!(matches!(
+            result,
+            Err(ConfigError::Invalid {
+                key: "SECURITY_HEADERS_FRAME_OPTIONS",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn should_reject_unknown_file_keys() {
+        let result = Config::from_sources(
+            Some("prot = 1"),
+            env_of(&[("DATABASE_URL", "postgres://env")]),
+        );
+        assert!(matches!(result, Err(ConfigError::Parse(_))));
+    }
+
+    #[test]
+    fn should_read_discord_event_flags() {
+        let config = Config::from_sources(
+            Some(
+                r#"
+                [discord]
+                webhook_url = "https://discord.com/api/webhooks/1/token"
+                [discord.events]
+                todo_updated = true
+            "#,
+            ),
+            env_of(&[
+                ("DATABASE_URL", "postgres://env"),
+                ("DISCORD_TODO_CREATED", "false"),
+            ]),
+        )
+        .unwrap();
+        let events = config.discord.events;
+        assert!(!events.todo_created);
+        assert!(events.todo_updated);
+        assert!(events.todo_completed);
+        assert!(!format!("{:?}", config.discord).contains("token"));
+
+        let result = Config::from_sources(
+            None,
+            env_of(&[
+                ("DATABASE_URL", "postgres://env"),
+                (
+                    "DISCORD_WEBHOOK_URL",
+                    "http://discord.com/api/webhooks/1/token",
+                ),
+            ]),
+        );
+        assert!(matches!(
+            result,
+            Err(ConfigError::Invalid {
+                key: "DISCORD_WEBHOOK_URL",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn should_check_the_email_address() {
+        let env = |address| {
+            env_of(&[
+                ("DATABASE_URL", "postgres://env"),
+                ("EMAIL_ADDRESS", address),
+                ("EMAIL_MAILGUN_SIGNING_KEY", "key-secret"),
+            ])
+        };
+        let config = Config::from_sources(None, env("todo@in.example.com")).unwrap();
+        assert_eq!(config.email.address.as_deref(), Some("todo@in.example.com"));
+        assert!(!format!("{:?}", config.email).contains("key-secret"));
+
+        for address in ["todo", "todo+x@in.example.com", "@in.example.com"] {
+            assert!(matches!(
+                Config::from_sources(None, env(address)),
+                Err(ConfigError::Invalid {
+                    key: "EMAIL_ADDRESS",
+                    ..
+                })
+            ));
+        }
+    }
+}
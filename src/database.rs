@@ -0,0 +1,300 @@
+use std::{
+    future::Future,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::NaiveDate;
+use sqlx::{
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgConnectOptions, PgPoolOptions, PgTypeInfo, PgValueRef},
+    types::Json,
+    Decode, Encode, PgPool, Postgres, Type,
+};
+use uuid::Uuid;
+
+use crate::config::DatabaseConfig;
+
+/// Upper bound for the pause between startup connection attempts.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// Connect the pool, retrying with backoff while the database is not yet
+/// reachable (e.g. Postgres still booting next to the app).
+pub async fn connect(config: &DatabaseConfig) -> Result<PgPool, sqlx::Error> {
+    let options = connect_options(config)?;
+
+    let mut delay = Duration::from_millis(500);
+    let mut attempt = 0;
+    loop {
+        match pool_options(config).connect_with(options.clone()).await {
+            Ok(pool) => return Ok(pool),
+            Err(e) if attempt < config.connect_retries && is_unreachable(&e) => {
+                attempt += 1;
+                tracing::warn!(
+                    "database not reachable ({}), retry {}/{} in {:?}",
+                    e,
+                    attempt,
+                    config.connect_retries,
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_RETRY_DELAY);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Creates the pool without connecting, so a database that is down (e.g. a
+/// read replica) does not hold up startup.
+pub fn connect_lazy(config: &DatabaseConfig) -> Result<PgPool, sqlx::Error> {
+    Ok(pool_options(config).connect_lazy_with(connect_options(config)?))
+}
+
+fn connect_options(config: &DatabaseConfig) -> Result<PgConnectOptions, sqlx::Error> {
+    Ok(PgConnectOptions::from_str(&config.url)?
+        .options([("statement_timeout", config.statement_timeout_ms.to_string())]))
+}
+
+fn pool_options(config: &DatabaseConfig) -> PgPoolOptions {
+    PgPoolOptions::new()
+        .max_connections(config.max_connections)
+        .min_connections(config.min_connections)
+        .connect_timeout(Duration::from_secs(config.acquire_timeout_secs))
+}
+
+/// Errors that go away once the server is up; bad credentials do not.
+pub fn is_unreachable(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut => true,
+        // cannot_connect_now: the server is still starting up
+        sqlx::Error::Database(e) => e.code().as_deref() == Some("57P03"),
+        _ => false,
+    }
+}
+
+/// A bind value as it may appear in logs. Ids, counts and flags show as
+/// they are; anything that could hold user content does not.
+pub trait Redact: Sync {
+    fn redacted(&self) -> String;
+}
+
+macro_rules! shown {
+    ($($ty:ty),*) => {
+        $(impl Redact for $ty {
+            fn redacted(&self) -> String {
+                self.to_string()
+            }
+        })*
+    };
+}
+
+shown!(i32, i64, u32, f32, bool, NaiveDate);
+
+impl Redact for str {
+    fn redacted(&self) -> String {
+        "<redacted>".to_string()
+    }
+}
+
+impl Redact for String {
+    fn redacted(&self) -> String {
+        self.as_str().redacted()
+    }
+}
+
+impl<T: Redact> Redact for Option<T> {
+    fn redacted(&self) -> String {
+        self.as_ref()
+            .map_or_else(|| "null".to_string(), Redact::redacted)
+    }
+}
+
+impl<T: Redact> Redact for [T] {
+    fn redacted(&self) -> String {
+        let items: Vec<_> = self.iter().map(Redact::redacted).collect();
+        format!("[{}]", items.join(", "))
+    }
+}
+
+impl<T: Redact> Redact for Vec<T> {
+    fn redacted(&self) -> String {
+        self.as_slice().redacted()
+    }
+}
+
+impl<T: Sync> Redact for Json<T> {
+    fn redacted(&self) -> String {
+        "<redacted>".to_string()
+    }
+}
+
+impl<T: Redact + ?Sized> Redact for &T {
+    fn redacted(&self) -> String {
+        (**self).redacted()
+    }
+}
+
+impl Redact for DbUuid {
+    fn redacted(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+/// A `uuid` column. The sqlx we are on only speaks the `uuid` 0.8 crate, so
+/// this binds and reads the 1.x `Uuid` the models carry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DbUuid(pub Uuid);
+
+impl Type<Postgres> for DbUuid {
+    fn type_info() -> PgTypeInfo {
+        // the oid Postgres gives `uuid`
+        PgTypeInfo::with_oid(2950)
+    }
+}
+
+impl Encode<'_, Postgres> for DbUuid {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+        buf.extend_from_slice(self.0.as_bytes());
+        IsNull::No
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for DbUuid {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        let bytes = <&[u8] as Decode<Postgres>>::decode(value)?;
+        // binary results hold the 16 bytes, text ones the hyphenated form
+        let uuid = match Uuid::from_slice(bytes) {
+            Ok(uuid) => uuid,
+            Err(_) => std::str::from_utf8(bytes)?.parse()?,
+        };
+        Ok(Self(uuid))
+    }
+}
+
+/// Logs queries slower than `DatabaseConfig::slow_query_ms` at warn level
+/// under the `slow_query` target, and counts them.
+#[derive(Debug, Clone, Default)]
+pub struct SlowQueryLog {
+    /// `None` logs nothing.
+    threshold: Option<Duration>,
+    count: Arc<AtomicU64>,
+}
+
+impl SlowQueryLog {
+    pub fn from_config(config: &DatabaseConfig) -> Self {
+        Self {
+            threshold: (config.slow_query_ms > 0)
+                .then(|| Duration::from_millis(config.slow_query_ms)),
+            count: Arc::default(),
+        }
+    }
+
+    /// Slow queries seen so far, by this log and its clones.
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Runs `query`, the statement called `name` with `binds` bound, and
+    /// logs it when it took too long, failed or not.
+    pub async fn time<F: Future>(
+        &self,
+        name: &'static str,
+        binds: &[&dyn Redact],
+        query: F,
+    ) -> F::Output {
+        let start = Instant::now();
+        let output = query.await;
+        let elapsed = start.elapsed();
+        if self.threshold.is_some_and(|threshold| elapsed >= threshold) {
+            let total = self.count.fetch_add(1, Ordering::Relaxed) + 1;
+            let binds: Vec<_> = binds.iter().map(|bind| bind.redacted()).collect();
+            tracing::warn!(
+                target: "slow_query",
+                statement = name,
+                binds = %binds.join(", "),
+                elapsed_ms = elapsed.as_millis() as u64,
+                slow_queries_total = total,
+                "slow query"
+            );
+        }
+        output
+    }
+}
+
+pub async fn migrate(pool: &PgPool) -> Result<(), sqlx::migrate::MigrateError> {
+    sqlx::migrate!("./migrations").run(pool).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_db::TestDb;
+
+    #[tokio::test]
+    async fn should_apply_statement_timeout() {
+        let db = TestDb::new().await;
+        let config = DatabaseConfig {
+            url: db.url.clone(),
+            statement_timeout_ms: 1500,
+            ..crate::config::Config::default().database
+        };
+        let pool = connect(&config).await.expect("failed to connect");
+
+        let (timeout,): (String,) = sqlx::query_as("show statement_timeout")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(timeout, "1500ms");
+    }
+
+    #[tokio::test]
+    async fn should_count_only_slow_queries() {
+        let config = |slow_query_ms| DatabaseConfig {
+            slow_query_ms,
+            ..crate::config::Config::default().database
+        };
+        let log = SlowQueryLog::from_config(&config(5));
+        let text = "secret".to_string();
+        let binds: [&dyn Redact; 3] = [&1, &text, &None::<i32>];
+
+        assert_eq!(log.time("fast", &binds, async { 1 }).await, 1);
+        assert_eq!(log.count(), 0);
+        let slow = tokio::time::sleep(Duration::from_millis(10));
+        log.clone().time("slow", &binds, slow).await;
+        assert_eq!(log.count(), 1);
+
+        let disabled = SlowQueryLog::from_config(&config(0));
+        disabled
+            .time(
+                "slow",
+                &binds,
+                tokio::time::sleep(Duration::from_millis(10)),
+            )
+            .await;
+        assert_eq!(disabled.count(), 0);
+    }
+
+    #[test]
+    fn should_redact_user_content() {
+        let binds: Vec<String> = [&7 as &dyn Redact, &"buy milk", &Some(true), &vec![1, 2]]
+            .iter()
+            .map(|bind| bind.redacted())
+            .collect();
+        assert_eq!(binds, ["7", "<redacted>", "true", "[1, 2]"]);
+    }
+
+    #[tokio::test]
+    async fn should_give_up_on_bad_url_without_retrying() {
+        let config = DatabaseConfig {
+            url: "not a url".to_string(),
+            ..crate::config::Config::default().database
+        };
+        assert!(connect(&config).await.is_err());
+    }
+}
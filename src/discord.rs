@@ -0,0 +1,175 @@
+use axum::async_trait;
+use chrono::Utc;
+use serde_json::{json, Value};
+
+use crate::{
+    config::{DiscordConfig, DiscordEvents},
+    events::{DomainEvent, Subscriber, WorkspaceEvent},
+    repositories::todo::Todo,
+};
+
+const CREATED: u32 = 0x3498db;
+const UPDATED: u32 = 0x95a5a6;
+const COMPLETED: u32 = 0x2ecc71;
+const DELETED: u32 = 0xe74c3c;
+const ASSIGNED: u32 = 0xf1c40f;
+
+/// Posts the todo events `DiscordEvents` enables to a Discord webhook, one
+/// embed each; label events are not posted.
+pub struct DiscordNotifier {
+    webhook_url: String,
+    events: DiscordEvents,
+    http: reqwest::Client,
+}
+
+impl DiscordNotifier {
+    /// `None` without a webhook URL.
+    pub fn from_config(config: &DiscordConfig) -> Option<Self> {
+        Some(Self {
+            webhook_url: config.webhook_url.clone()?,
+            events: config.events,
+            http: reqwest::Client::new(),
+        })
+    }
+}
+
+#[async_trait]
+impl Subscriber for DiscordNotifier {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
+
+    async fn handle(&self, event: &WorkspaceEvent) -> anyhow::Result<()> {
+        let Some(embed) = embed(&self.events, event) else {
+            return Ok(());
+        };
+        self.http
+            .post(&self.webhook_url)
+            .json(&json!({ "embeds": [embed] }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// The embed for `event`, or `None` when it is not posted.
+pub fn embed(events: &DiscordEvents, event: &WorkspaceEvent) -> Option<Value> {
+    let (enabled, title, color, todo) = match &event.event {
+        DomainEvent::TodoCreated(todo) => (events.todo_created, "Todo created", CREATED, todo),
+        DomainEvent::TodoUpdated(todo) => (events.todo_updated, "Todo updated", UPDATED, todo),
+        DomainEvent::TodoCompleted(todo) => {
+            (events.todo_completed, "Todo completed", COMPLETED, todo)
+        }
+        DomainEvent::TodoAssigned { todo, .. } => {
+            (events.todo_assigned, "Todo assigned", ASSIGNED, todo)
+        }
+        DomainEvent::TodoDeleted(id) => {
+            return events.todo_deleted.then(|| {
+                json!({
+                    "title": "Todo deleted",
+                    "description": format!("#{}", id),
+                    "color": DELETED,
+                    "fields": [workspace_field(event.workspace_id)],
+                    "timestamp": Utc::now().to_rfc3339(),
+                })
+            });
+        }
+        DomainEvent::LabelCreated(_)
+        | DomainEvent::LabelUpdated(_)
+        | DomainEvent::LabelDeleted(_) => return None,
+    };
+    enabled.then(|| {
+        json!({
+            "title": title,
+            "description": format!("#{} {}", todo.id, todo.text),
+            "color": color,
+            "fields": todo_fields(event.workspace_id, todo),
+            "timestamp": Utc::now().to_rfc3339(),
+        })
+    })
+}
+
+fn workspace_field(workspace_id: i32) -> Value {
+    json!({ "name": "Workspace", "value": workspace_id.to_string(), "inline": true })
+}
+
+/// Only what the todo has: unassigned todos get no assignee field.
+fn todo_fields(workspace_id: i32, todo: &Todo) -> Vec<Value> {
+    let mut fields = vec![workspace_field(workspace_id)];
+    let mut field = |name: &str, value: String| {
+        fields.push(json!({ "name": name, "value": value, "inline": true }));
+    };
+    if let Some(assignee_id) = todo.assignee_id {
+        field("Assignee", format!("user {}", assignee_id));
+    }
+    if let Some(due) = todo.due_date {
+        field("Due", due.to_string());
+    }
+    if let Some(minutes) = todo.estimate_minutes {
+        field("Estimate", format!("{} min", minutes));
+    }
+    if !todo.labels.is_empty() {
+        let names: Vec<&str> = todo
+            .labels
+            .iter()
+            .map(|label| label.name.as_str())
+            .collect();
+        field("Labels", names.join(", "));
+    }
+    fields
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn should_embed_enabled_todo_events_only() {
+        let events = Config::default().discord.events;
+        let todo = Todo {
+            assignee_id: Some(2),
+            due_date: "2023-05-28".parse().ok(),
+            ..Todo::new(3, "ship it".to_string())
+        };
+        let event = |event| WorkspaceEvent {
+            workspace_id: 1,
+            event,
+        };
+
+        let completed = embed(&events, &event(DomainEvent::TodoCompleted(todo.clone()))).unwrap();
+        assert_eq!(completed["title"], "Todo completed");
+        assert_eq!(completed["description"], "#3 ship it");
+        assert_eq!(completed["color"], COMPLETED);
+        let fields: Vec<(&str, &str)> = completed["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|field| {
+                (
+                    field["name"].as_str().unwrap(),
+                    field["value"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("Workspace", "1"),
+                ("Assignee", "user 2"),
+                ("Due", "2023-05-28")
+            ]
+        );
+
+        assert!(embed(&events, &event(DomainEvent::TodoUpdated(todo.clone()))).is_none());
+        assert!(embed(&events, &event(DomainEvent::LabelDeleted(1))).is_none());
+        let deleted = embed(&events, &event(DomainEvent::TodoDeleted(3))).unwrap();
+        assert_eq!(deleted["description"], "#3");
+        let quiet = DiscordEvents {
+            todo_deleted: false,
+            ..events
+        };
+        assert!(embed(&quiet, &event(DomainEvent::TodoDeleted(3))).is_none());
+    }
+}
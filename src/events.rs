@@ -0,0 +1,346 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgListener, PgPool};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::repositories::{label::Label, todo::Todo};
+
+const CHANNEL_CAPACITY: usize = 256;
+/// `NOTIFY` channel shared by every `PgEventBus`.
+const PG_CHANNEL: &str = "domain_events";
+const LISTEN_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Something that happened, published once the repository call behind it
+/// succeeded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum DomainEvent {
+    TodoCreated(Todo),
+    TodoUpdated(Todo),
+    /// Follows the `TodoUpdated` that marked the todo completed.
+    TodoCompleted(Todo),
+    TodoDeleted(i32),
+    /// Follows the `TodoCreated` or `TodoUpdated` that gave the todo a new
+    /// assignee; `by` is whoever made the change.
+    TodoAssigned {
+        todo: Todo,
+        by: Option<i32>,
+    },
+    LabelCreated(Label),
+    /// A label was renamed, recolored or given another icon.
+    LabelUpdated(Label),
+    LabelDeleted(i32),
+}
+
+impl DomainEvent {
+    /// What creating `todo` amounts to; `by` is whoever created it.
+    pub fn created(todo: &Todo, by: Option<i32>) -> Vec<Self> {
+        let mut events = vec![DomainEvent::TodoCreated(todo.clone())];
+        if todo.assignee_id.is_some() {
+            events.push(DomainEvent::TodoAssigned {
+                todo: todo.clone(),
+                by,
+            });
+        }
+        events
+    }
+
+    /// What changing `before` into `after` amounts to; `by` is whoever made
+    /// the change.
+    pub fn changed(before: &Todo, after: &Todo, by: Option<i32>) -> Vec<Self> {
+        let mut events = vec![DomainEvent::TodoUpdated(after.clone())];
+        if after.completed && !before.completed {
+            events.push(DomainEvent::TodoCompleted(after.clone()));
+        }
+        if after.assignee_id.is_some() && after.assignee_id != before.assignee_id {
+            events.push(DomainEvent::TodoAssigned {
+                todo: after.clone(),
+                by,
+            });
+        }
+        events
+    }
+
+    /// The `type` the event serializes with.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DomainEvent::TodoCreated(_) => "todo_created",
+            DomainEvent::TodoUpdated(_) => "todo_updated",
+            DomainEvent::TodoCompleted(_) => "todo_completed",
+            DomainEvent::TodoDeleted(_) => "todo_deleted",
+            DomainEvent::TodoAssigned { .. } => "todo_assigned",
+            DomainEvent::LabelCreated(_) => "label_created",
+            DomainEvent::LabelUpdated(_) => "label_updated",
+            DomainEvent::LabelDeleted(_) => "label_deleted",
+        }
+    }
+
+    /// `None` for label events.
+    pub fn todo_id(&self) -> Option<i32> {
+        match self {
+            DomainEvent::TodoCreated(todo)
+            | DomainEvent::TodoUpdated(todo)
+            | DomainEvent::TodoCompleted(todo)
+            | DomainEvent::TodoAssigned { todo, .. } => Some(todo.id),
+            DomainEvent::TodoDeleted(id) => Some(*id),
+            DomainEvent::LabelCreated(_)
+            | DomainEvent::LabelUpdated(_)
+            | DomainEvent::LabelDeleted(_) => None,
+        }
+    }
+}
+
+/// An event together with the workspace it happened in, so subscribers only
+/// see the workspaces they may read.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkspaceEvent {
+    pub workspace_id: i32,
+    pub event: DomainEvent,
+}
+
+/// Carries events from publishers to every subscriber; subscribers that fall
+/// behind by more than the channel capacity miss the oldest events.
+#[async_trait]
+pub trait EventBus: Send + Sync + 'static {
+    async fn publish(&self, event: WorkspaceEvent) -> anyhow::Result<()>;
+    fn subscribe(&self) -> broadcast::Receiver<WorkspaceEvent>;
+}
+
+/// Reacts to events in a background task, see `Events::attach`.
+#[async_trait]
+pub trait Subscriber: Send + Sync + 'static {
+    /// Names the subscriber in logs.
+    fn name(&self) -> &'static str;
+    async fn handle(&self, event: &WorkspaceEvent) -> anyhow::Result<()>;
+}
+
+/// In-process fan-out; events stay within this instance.
+#[derive(Debug, Clone)]
+pub struct MemoryEventBus {
+    sender: broadcast::Sender<WorkspaceEvent>,
+}
+
+impl MemoryEventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { sender }
+    }
+
+    fn send(&self, event: WorkspaceEvent) {
+        // no subscribers is not an error
+        let _ = self.sender.send(event);
+    }
+}
+
+impl Default for MemoryEventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl EventBus for MemoryEventBus {
+    async fn publish(&self, event: WorkspaceEvent) -> anyhow::Result<()> {
+        self.send(event);
+        Ok(())
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<WorkspaceEvent> {
+        self.sender.subscribe()
+    }
+}
+
+/// Shares events between instances through Postgres `NOTIFY`. Each instance
+/// hands what it hears to its own subscribers, its own events included, so
+/// events published while the listener reconnects are lost and payloads over
+/// the 8000 byte `NOTIFY` limit fail to publish.
+#[derive(Debug, Clone)]
+pub struct PgEventBus {
+    pool: PgPool,
+    local: MemoryEventBus,
+}
+
+impl PgEventBus {
+    /// Starts listening before returning, so nothing published afterwards is
+    /// missed.
+    pub async fn listen(pool: PgPool) -> anyhow::Result<Self> {
+        let mut listener = PgListener::connect_with(&pool).await?;
+        listener.listen(PG_CHANNEL).await?;
+        let local = MemoryEventBus::new();
+        let bus = Self {
+            pool,
+            local: local.clone(),
+        };
+        tokio::spawn(async move {
+            loop {
+                match listener.recv().await {
+                    Ok(notification) => match serde_json::from_str(notification.payload()) {
+                        Ok(event) => local.send(event),
+                        Err(e) => tracing::warn!(
+                            "undecodable event {:?}: {:?}",
+                            notification.payload(),
+                            e
+                        ),
+                    },
+                    // the next `recv` reconnects
+                    Err(e) => {
+                        tracing::warn!("event listener failed: {:?}", e);
+                        tokio::time::sleep(LISTEN_RETRY_DELAY).await;
+                    }
+                }
+            }
+        });
+
+        Ok(bus)
+    }
+}
+
+#[async_trait]
+impl EventBus for PgEventBus {
+    async fn publish(&self, event: WorkspaceEvent) -> anyhow::Result<()> {
+        sqlx::query("select pg_notify($1, $2)")
+            .bind(PG_CHANNEL)
+            .bind(serde_json::to_string(&event)?)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<WorkspaceEvent> {
+        self.local.subscribe()
+    }
+}
+
+/// The bus handlers publish to. Publishing never fails the request that
+/// caused the event; failures are logged.
+#[derive(Clone)]
+pub struct Events {
+    bus: Arc<dyn EventBus>,
+}
+
+impl Events {
+    pub fn new(bus: Arc<dyn EventBus>) -> Self {
+        Self { bus }
+    }
+
+    pub async fn publish(&self, workspace_id: i32, event: DomainEvent) {
+        let event = WorkspaceEvent {
+            workspace_id,
+            event,
+        };
+        if let Err(e) = self.bus.publish(event.clone()).await {
+            tracing::warn!("publishing {:?} failed: {:?}", event, e);
+        }
+    }
+
+    /// Like `publish`, but leaves the failure to the caller; the outbox
+    /// relay retries on it.
+    pub async fn deliver(&self, event: WorkspaceEvent) -> anyhow::Result<()> {
+        self.bus.publish(event).await
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<WorkspaceEvent> {
+        self.bus.subscribe()
+    }
+
+    /// Hands every event published from now on to `subscriber`, one at a
+    /// time; failures are logged and the next event handled.
+    pub fn attach<S: Subscriber>(&self, subscriber: S) {
+        let mut receiver = self.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => {
+                        if let Err(e) = subscriber.handle(&event).await {
+                            tracing::warn!("{} failed on {:?}: {:?}", subscriber.name(), event, e);
+                        }
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        tracing::warn!("{} missed {} events", subscriber.name(), missed);
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Self::new(Arc::new(MemoryEventBus::new()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_db::TestDb;
+    use tokio::sync::mpsc;
+
+    struct Forward(mpsc::UnboundedSender<WorkspaceEvent>);
+
+    #[async_trait]
+    impl Subscriber for Forward {
+        fn name(&self) -> &'static str {
+            "forward"
+        }
+
+        async fn handle(&self, event: &WorkspaceEvent) -> anyhow::Result<()> {
+            Ok(self.0.send(event.clone())?)
+        }
+    }
+
+    #[tokio::test]
+    async fn should_hand_events_to_subscribers() {
+        let events = Events::default();
+        let (sender, mut received) = mpsc::unbounded_channel();
+        events.attach(Forward(sender));
+
+        events.publish(2, DomainEvent::TodoDeleted(1)).await;
+        events.publish(2, DomainEvent::LabelDeleted(3)).await;
+        assert_eq!(
+            received.recv().await.unwrap(),
+            WorkspaceEvent {
+                workspace_id: 2,
+                event: DomainEvent::TodoDeleted(1),
+            }
+        );
+        assert_eq!(
+            received.recv().await.unwrap().event.todo_id(),
+            None,
+            "label events carry no todo"
+        );
+    }
+
+    #[test]
+    fn should_name_events_by_their_type() {
+        let event = DomainEvent::TodoAssigned {
+            todo: Todo::new(1, "assigned".to_string()),
+            by: None,
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], event.kind());
+        assert_eq!(DomainEvent::LabelDeleted(1).kind(), "label_deleted");
+    }
+
+    #[tokio::test]
+    async fn should_share_events_through_postgres() {
+        let db = TestDb::new().await;
+        let pool = db.pool.clone();
+        let publisher = PgEventBus::listen(pool.clone()).await.unwrap();
+        let listener = PgEventBus::listen(pool).await.unwrap();
+        let mut received = listener.subscribe();
+
+        let event = WorkspaceEvent {
+            workspace_id: 1,
+            event: DomainEvent::TodoCompleted(Todo::new(7, "done".to_string())),
+        };
+        publisher.publish(event.clone()).await.unwrap();
+        let got = tokio::time::timeout(Duration::from_secs(5), received.recv())
+            .await
+            .expect("no event within 5s");
+        assert_eq!(got.unwrap(), event);
+    }
+}
@@ -0,0 +1,362 @@
+use std::marker::PhantomData;
+
+use async_graphql::{
+    http::{playground_source, GraphQLPlaygroundConfig},
+    Context, Enum, InputObject, Object, Schema, SimpleObject, Subscription,
+};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse, GraphQLSubscription};
+use axum::{
+    response::{Html, IntoResponse},
+    routing::get,
+    Extension, Router,
+};
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+use validator::Validate;
+
+use crate::{
+    events::{DomainEvent, WorkspaceEvent},
+    repositories::{
+        label::{CreateLabel, Label, LabelRepository},
+        todo::{CreateTodo, Todo, TodoRepository, UpdateTodo},
+        user::UserRepository,
+        workspace::DEFAULT_WORKSPACE_ID,
+    },
+    state::AppState,
+};
+
+/// The schema serves only the default workspace.
+pub type AppSchema<T, L, U> =
+    Schema<QueryRoot<T, L, U>, MutationRoot<T, L, U>, SubscriptionRoot<T, L, U>>;
+
+pub fn graphql_routes<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    state: AppState<T, L, U>,
+) -> Router<AppState<T, L, U>> {
+    let playground = state.config.features.graphql_playground;
+    let schema = build_schema(state);
+
+    let route = if playground {
+        get(graphql_playground).post(graphql_handler::<T, L, U>)
+    } else {
+        axum::routing::post(graphql_handler::<T, L, U>)
+    };
+    Router::new()
+        .route("/graphql", route)
+        .route_service("/graphql/ws", GraphQLSubscription::new(schema.clone()))
+        .layer(Extension(schema))
+}
+
+pub fn build_schema<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    state: AppState<T, L, U>,
+) -> AppSchema<T, L, U> {
+    Schema::build(
+        QueryRoot(PhantomData),
+        MutationRoot(PhantomData),
+        SubscriptionRoot(PhantomData),
+    )
+    .data(state)
+    .finish()
+}
+
+async fn graphql_handler<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    Extension(schema): Extension<AppSchema<T, L, U>>,
+    req: GraphQLRequest,
+) -> GraphQLResponse {
+    schema.execute(req.into_inner()).await.into()
+}
+
+async fn graphql_playground() -> impl IntoResponse {
+    Html(playground_source(
+        GraphQLPlaygroundConfig::new("/graphql").subscription_endpoint("/graphql/ws"),
+    ))
+}
+
+fn state<'a, T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    ctx: &Context<'a>,
+) -> async_graphql::Result<&'a AppState<T, L, U>> {
+    ctx.data::<AppState<T, L, U>>()
+}
+
+#[derive(Debug, Default, InputObject)]
+pub struct TodoFilter {
+    pub completed: Option<bool>,
+    /// Case-insensitive substring of the todo text.
+    pub text: Option<String>,
+}
+
+impl TodoFilter {
+    fn matches(&self, todo: &Todo) -> bool {
+        self.completed.is_none_or(|c| todo.completed == c)
+            && self
+                .text
+                .as_ref()
+                .is_none_or(|text| todo.text.to_lowercase().contains(&text.to_lowercase()))
+    }
+}
+
+pub struct QueryRoot<T, L, U>(PhantomData<(T, L, U)>);
+
+#[Object]
+impl<T: TodoRepository, L: LabelRepository, U: UserRepository> QueryRoot<T, L, U> {
+    async fn todos(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<TodoFilter>,
+    ) -> async_graphql::Result<Vec<Todo>> {
+        let filter = filter.unwrap_or_default();
+        let todos = state::<T, L, U>(ctx)?.todo_repository.all().await?;
+        Ok(todos
+            .into_iter()
+            .filter(|todo| filter.matches(todo))
+            .collect())
+    }
+
+    async fn todo(&self, ctx: &Context<'_>, id: i32) -> async_graphql::Result<Option<Todo>> {
+        Ok(state::<T, L, U>(ctx)?.todo_repository.find(id).await.ok())
+    }
+
+    async fn labels(
+        &self,
+        ctx: &Context<'_>,
+        name: Option<String>,
+    ) -> async_graphql::Result<Vec<Label>> {
+        let labels = state::<T, L, U>(ctx)?.label_repository.all().await?;
+        Ok(match name {
+            Some(name) => labels
+                .into_iter()
+                .filter(|label| label.name.to_lowercase().contains(&name.to_lowercase()))
+                .collect(),
+            None => labels,
+        })
+    }
+}
+
+pub struct MutationRoot<T, L, U>(PhantomData<(T, L, U)>);
+
+#[Object]
+impl<T: TodoRepository, L: LabelRepository, U: UserRepository> MutationRoot<T, L, U> {
+    async fn create_todo(
+        &self,
+        ctx: &Context<'_>,
+        text: String,
+        #[graphql(default)] labels: Vec<i32>,
+    ) -> async_graphql::Result<Todo> {
+        let payload = CreateTodo {
+            labels,
+            ..CreateTodo::new(text)
+        };
+        payload.validate()?;
+        let state = state::<T, L, U>(ctx)?;
+        let todo = state.todo_repository.create(payload).await?;
+        Ok(todo)
+    }
+
+    async fn update_todo(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        text: Option<String>,
+        completed: Option<bool>,
+        labels: Option<Vec<i32>>,
+    ) -> async_graphql::Result<Todo> {
+        let payload = UpdateTodo {
+            text,
+            completed,
+            labels,
+            assignee_id: None,
+            text_i18n: None,
+            estimate_minutes: None,
+            due_date: None,
+            description: None,
+            custom: None,
+        };
+        payload.validate()?;
+        let state = state::<T, L, U>(ctx)?;
+        let current = state.todo_repository.find(id).await?;
+        state
+            .quotas
+            .check_reopen(&*state.todo_repository, &current, payload.completed)
+            .await?;
+        let todo = state.todo_repository.update(id, payload).await?;
+        Ok(todo)
+    }
+
+    async fn delete_todo(&self, ctx: &Context<'_>, id: i32) -> async_graphql::Result<bool> {
+        let state = state::<T, L, U>(ctx)?;
+        state.todo_repository.delete(id).await?;
+        Ok(true)
+    }
+
+    async fn create_label(&self, ctx: &Context<'_>, name: String) -> async_graphql::Result<Label> {
+        let state = state::<T, L, U>(ctx)?;
+        state.quotas.check_labels(&*state.label_repository).await?;
+        let label = state
+            .label_repository
+            .create(CreateLabel::new(name))
+            .await?;
+        state
+            .events
+            .publish(
+                DEFAULT_WORKSPACE_ID,
+                DomainEvent::LabelCreated(label.clone()),
+            )
+            .await;
+        Ok(label)
+    }
+
+    async fn delete_label(&self, ctx: &Context<'_>, id: i32) -> async_graphql::Result<bool> {
+        let state = state::<T, L, U>(ctx)?;
+        state.label_repository.delete(id).await?;
+        state
+            .events
+            .publish(DEFAULT_WORKSPACE_ID, DomainEvent::LabelDeleted(id))
+            .await;
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum TodoChangeKind {
+    Created,
+    Updated,
+    /// Follows the `UPDATED` that marked the todo completed.
+    Completed,
+    Deleted,
+    Assigned,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct TodoChanged {
+    pub kind: TodoChangeKind,
+    pub id: i32,
+    /// Missing for deletions.
+    pub todo: Option<Todo>,
+}
+
+impl TodoChanged {
+    /// `None` for events that are not about a todo.
+    fn new(event: DomainEvent) -> Option<Self> {
+        let (kind, id, todo) = match event {
+            DomainEvent::TodoCreated(todo) => (TodoChangeKind::Created, todo.id, Some(todo)),
+            DomainEvent::TodoUpdated(todo) => (TodoChangeKind::Updated, todo.id, Some(todo)),
+            DomainEvent::TodoCompleted(todo) => (TodoChangeKind::Completed, todo.id, Some(todo)),
+            DomainEvent::TodoAssigned { todo, .. } => {
+                (TodoChangeKind::Assigned, todo.id, Some(todo))
+            }
+            DomainEvent::TodoDeleted(id) => (TodoChangeKind::Deleted, id, None),
+            DomainEvent::LabelCreated(_)
+            | DomainEvent::LabelUpdated(_)
+            | DomainEvent::LabelDeleted(_) => return None,
+        };
+        Some(Self { kind, id, todo })
+    }
+}
+
+pub struct SubscriptionRoot<T, L, U>(PhantomData<(T, L, U)>);
+
+#[Subscription]
+impl<T: TodoRepository, L: LabelRepository, U: UserRepository> SubscriptionRoot<T, L, U> {
+    /// Pushes every todo change, or only changes of `id` when given.
+    async fn todo_changed(
+        &self,
+        ctx: &Context<'_>,
+        id: Option<i32>,
+    ) -> async_graphql::Result<impl Stream<Item = TodoChanged>> {
+        let receiver = state::<T, L, U>(ctx)?.events.subscribe();
+        Ok(BroadcastStream::new(receiver).filter_map(move |event| {
+            // lagged receivers skip the dropped events and keep streaming
+            let WorkspaceEvent {
+                workspace_id,
+                event,
+            } = event.ok()?;
+            let changed = TodoChanged::new(event)?;
+            (workspace_id == DEFAULT_WORKSPACE_ID && id.is_none_or(|id| changed.id == id))
+                .then_some(changed)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        config::Config,
+        repositories::{
+            label::LabelRepositoryForMemory, todo::TodoRepositoryForMemory,
+            user::UserRepositoryForMemory,
+        },
+    };
+    use serde_json::json;
+
+    fn schema(
+    ) -> AppSchema<TodoRepositoryForMemory, LabelRepositoryForMemory, UserRepositoryForMemory> {
+        let state = AppState::new(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        state
+            .outbox
+            .relay(state.todo_repository.clone(), state.events.clone());
+        build_schema(state)
+    }
+
+    #[tokio::test]
+    async fn should_create_and_filter_todos() {
+        let schema = schema();
+        let res = schema
+            .execute(r#"mutation { a: createTodo(text: "Buy milk") { id } b: createTodo(text: "walk") { id } }"#)
+            .await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        let res = schema
+            .execute(r#"mutation { updateTodo(id: 2, completed: true) { completed } }"#)
+            .await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+
+        let res = schema
+            .execute(r#"{ todos(filter: { completed: false, text: "MILK" }) { id text } }"#)
+            .await;
+        assert_eq!(
+            res.data.into_json().unwrap(),
+            json!({ "todos": [{ "id": 1, "text": "Buy milk" }] })
+        );
+    }
+
+    #[tokio::test]
+    async fn should_reject_invalid_todo() {
+        let res = schema()
+            .execute(r#"mutation { createTodo(text: "") { id } }"#)
+            .await;
+        assert_eq!(res.errors.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn should_push_todo_changes() {
+        let schema = schema();
+        let mut stream =
+            schema.execute_stream("subscription { todoChanged(id: 1) { kind id todo { text } } }");
+        // the subscription is registered once the stream is first polled
+        let mutations = tokio::spawn({
+            let schema = schema.clone();
+            async move {
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                schema
+                    .execute(r#"mutation { a: createTodo(text: "one") { id } b: createTodo(text: "two") { id } }"#)
+                    .await;
+                schema.execute("mutation { deleteTodo(id: 1) }").await;
+            }
+        });
+
+        let created = stream.next().await.unwrap();
+        assert_eq!(
+            created.data.into_json().unwrap(),
+            json!({ "todoChanged": { "kind": "CREATED", "id": 1, "todo": { "text": "one" } } })
+        );
+        let deleted = stream.next().await.unwrap();
+        assert_eq!(
+            deleted.data.into_json().unwrap(),
+            json!({ "todoChanged": { "kind": "DELETED", "id": 1, "todo": null } })
+        );
+        mutations.await.unwrap();
+    }
+}
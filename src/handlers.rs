@@ -1,39 +1,165 @@
 use axum::{
     async_trait,
-    extract::{FromRequest, RequestParts},
-    http::StatusCode,
-    BoxError, Json,
+    body::Bytes,
+    extract::FromRequest,
+    http::{header::CONTENT_TYPE, HeaderMap, Request, StatusCode},
+    BoxError,
 };
 use serde::de::DeserializeOwned;
-use validator::Validate;
+use serde_json::json;
+use validator::{Validate, ValidationErrors};
+
+use self::error::ApiError;
+use crate::middleware::negotiate::{binary_to_json, BodyFormat};
+
+/// Request-wide JSON body options, installed as an extension by `create_app`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonOptions {
+    /// Reject members the target type does not know instead of ignoring them.
+    pub strict: bool,
+}
 
 #[derive(Debug)]
 pub struct ValidatedJson<T>(T);
 
 #[async_trait]
-impl<T, B> FromRequest<B> for ValidatedJson<T>
+impl<T, S, B> FromRequest<S, B> for ValidatedJson<T>
 where
     T: DeserializeOwned + Validate,
-    B: http_body::Body + Send,
+    S: Send + Sync,
+    B: http_body::Body + Send + 'static,
     B::Data: Send,
     B::Error: Into<BoxError>,
 {
-    type Rejection = (StatusCode, String);
+    type Rejection = ApiError;
 
-    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
-        let Json(value) = Json::<T>::from_request(req).await.map_err(|rejection| {
-            let message = format!("Json parse error: [{}]", rejection);
-            (StatusCode::BAD_REQUEST, message)
-        })?;
+    async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let value: T = parse_json(req, state).await?;
 
-        value.validate().map_err(|rejection| {
-            let message = format!("Validation error: [{}]", rejection).replace('\n', ",");
-            (StatusCode::BAD_REQUEST, message)
-        })?;
+        value.validate().map_err(validation_error)?;
 
         Ok(ValidatedJson(value))
     }
 }
 
+/// `application/json` or any `+json` type such as `application/merge-patch+json`.
+fn has_json_content_type(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<mime::Mime>().ok())
+        .is_some_and(|mime| {
+            mime.type_() == mime::APPLICATION
+                && (mime.subtype() == mime::JSON || mime.suffix() == Some(mime::JSON))
+        })
+}
+
+/// Reads the body as JSON, or as MessagePack or CBOR converted to JSON first.
+/// Malformed or mistyped bodies are a 422 naming the
+/// offending path, e.g. `labels[1]`.
+async fn parse_json<T, S, B>(req: Request<B>, state: &S) -> Result<T, ApiError>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+    B: http_body::Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    let options = req
+        .extensions()
+        .get::<JsonOptions>()
+        .copied()
+        .unwrap_or_default();
+    let binary = BodyFormat::binary_content_type(req.headers());
+    if binary.is_none() && !has_json_content_type(req.headers()) {
+        return Err(ApiError::new(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Content-Type must be application/json, application/msgpack or application/cbor",
+        ));
+    }
+    let bytes = Bytes::from_request(req, state).await.map_err(|rejection| {
+        ApiError::new(
+            rejection.status(),
+            format!("Json parse error: [{}]", rejection.body_text()),
+        )
+    })?;
+
+    match binary {
+        Some(format) => deserialize_json(&binary_to_json(format, &bytes)?, options),
+        None => deserialize_json(&bytes, options),
+    }
+}
+
+fn deserialize_json<T: DeserializeOwned>(
+    bytes: &[u8],
+    options: JsonOptions,
+) -> Result<T, ApiError> {
+    let mut unknown = None;
+    let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+    let mut record_unknown = |path: serde_ignored::Path| {
+        unknown.get_or_insert_with(|| path.to_string());
+    };
+    let value = serde_path_to_error::deserialize(serde_ignored::Deserializer::new(
+        &mut deserializer,
+        &mut record_unknown,
+    ))
+    .map_err(|e| unprocessable(&e.path().to_string(), e.into_inner()))?;
+    // trailing characters after the document
+    deserializer.end().map_err(|e| unprocessable(".", e))?;
+
+    match unknown {
+        Some(path) if options.strict => Err(ApiError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("Json parse error: [unknown field `{}`]", path),
+        )
+        .with_details(json!({ "path": path }))),
+        _ => Ok(value),
+    }
+}
+
+fn unprocessable(path: &str, error: serde_json::Error) -> ApiError {
+    // serde_path_to_error reports `?` when a syntax error cut the path short
+    let path = if path == "?" { "." } else { path };
+    let message = match path {
+        "." => format!("Json parse error: [{}]", error),
+        _ => format!("Json parse error: [{}: {}]", path, error),
+    };
+    ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, message).with_details(json!({
+        "path": path,
+        "line": error.line(),
+        "column": error.column(),
+    }))
+}
+
+pub(crate) fn validation_error(errors: ValidationErrors) -> ApiError {
+    let message = format!("Validation error: [{}]", errors).replace('\n', ",");
+    ApiError::bad_request(message)
+}
+
+pub mod admin;
+pub mod auth;
+pub mod board;
+pub mod burndown;
+pub mod calendar;
+pub mod chat;
+pub mod checklist;
+pub mod custom_field;
+pub mod email;
+pub mod error;
+pub mod fallback;
+pub mod fields;
+pub mod include;
+pub mod json_patch;
 pub mod label;
+pub mod links;
+pub mod merge_patch;
+pub mod oauth;
+pub mod pagination;
+pub mod path_id;
+pub mod render;
+pub mod slack;
+pub mod telegram;
 pub mod todo;
+pub mod user;
+pub mod view;
+pub mod workspace;
@@ -0,0 +1,169 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Path, Query, State},
+    http::{request::Parts, StatusCode},
+    response::IntoResponse,
+    routing::{get, patch},
+    Json, Router,
+};
+use serde::Deserialize;
+
+use crate::{
+    auth::AuthUser,
+    repositories::{
+        account::{AccountFilter, UpdateAccount, UserRole},
+        audit::{AuditAction, NewAuditEntry},
+        label::LabelRepository,
+        todo::{OwnedTodos, TodoRepository},
+        user::UserRepository,
+        RepositoryError,
+    },
+    state::AppState,
+};
+
+use super::{
+    error::ApiError,
+    pagination::{encode_cursor, Page, PageParams},
+    ValidatedJson,
+};
+
+pub fn admin_routes<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+) -> Router<AppState<T, L, U>> {
+    Router::new()
+        .route("/admin/users", get(all_users::<T, L, U>))
+        .route(
+            "/admin/users/:id",
+            patch(update_user::<T, L, U>).delete(delete_user::<T, L, U>),
+        )
+}
+
+/// A caller whose account holds the site-wide admin role; everyone else gets
+/// 403. The role is looked up on every request, so demotion takes effect at
+/// once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdminUser(pub AuthUser);
+
+#[async_trait]
+impl<T, L, U> FromRequestParts<AppState<T, L, U>> for AdminUser
+where
+    T: TodoRepository,
+    L: LabelRepository,
+    U: UserRepository,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState<T, L, U>,
+    ) -> Result<Self, Self::Rejection> {
+        let user = AuthUser::from_request_parts(parts, state).await?;
+        let account = state.user_repository.account(user.id).await?;
+        if account.role != UserRole::Admin || account.is_disabled() {
+            return Err(ApiError::new(
+                StatusCode::FORBIDDEN,
+                "only administrators may do this",
+            ));
+        }
+        Ok(Self(user))
+    }
+}
+
+/// Admins may not lock themselves out.
+fn check_not_self(admin: AdminUser, id: i32) -> Result<(), ApiError> {
+    if admin.0.id == id {
+        return Err(ApiError::bad_request(
+            "administrators can not change or delete their own account here",
+        ));
+    }
+    Ok(())
+}
+
+/// One page of accounts, newest first, narrowed by `q`, `role` and
+/// `disabled`.
+pub async fn all_users<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    _admin: AdminUser,
+    Query(params): Query<PageParams>,
+    Query(filter): Query<AccountFilter>,
+) -> Result<impl IntoResponse, ApiError> {
+    let page = state
+        .user_repository
+        .accounts(&filter, params.after()?, params.limit()?)
+        .await?;
+    let page = Page {
+        items: page.items,
+        next_cursor: page.next.map(encode_cursor),
+        prev_cursor: None,
+    };
+
+    Ok((StatusCode::OK, Json(page)))
+}
+
+pub async fn update_user<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    admin: AdminUser,
+    Path(id): Path<i32>,
+    ValidatedJson(payload): ValidatedJson<UpdateAccount>,
+) -> Result<impl IntoResponse, ApiError> {
+    check_not_self(admin, id)?;
+    let account = state.user_repository.update_account(id, payload).await?;
+    if payload.disabled == Some(true) {
+        tracing::info!(target: "security", user_id = id, admin_id = admin.0.id, "account disabled");
+    }
+
+    Ok((StatusCode::OK, Json(account)))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DeleteUserParams {
+    /// Hands the user's todos to this account instead of deleting them.
+    pub transfer_to: Option<i32>,
+}
+
+/// Deletes the account and every todo it created, or with `transfer_to`
+/// makes that account their owner. Todos assigned to the user are left
+/// unassigned.
+pub async fn delete_user<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    admin: AdminUser,
+    Path(id): Path<i32>,
+    Query(params): Query<DeleteUserParams>,
+) -> Result<StatusCode, ApiError> {
+    check_not_self(admin, id)?;
+    state.user_repository.find(id).await?;
+    let release = match params.transfer_to {
+        Some(to) if to == id => {
+            return Err(ApiError::bad_request(
+                "can not transfer todos to the deleted user",
+            ))
+        }
+        Some(to) => {
+            match state.user_repository.find(to).await {
+                Err(e) if matches!(e.downcast_ref(), Some(RepositoryError::NotFound(_))) => {
+                    return Err(ApiError::bad_request(format!("user {} not found", to)))
+                }
+                result => result?,
+            };
+            OwnedTodos::TransferTo(to)
+        }
+        None => OwnedTodos::Delete,
+    };
+
+    state
+        .todo_repository
+        .acting_as(Some(admin.0.id))
+        .release_owned(id, release)
+        .await?;
+    state.user_repository.delete(id).await?;
+    state
+        .user_repository
+        .record_audit(NewAuditEntry {
+            user_id: id,
+            actor_id: Some(admin.0.id),
+            action: AuditAction::AccountDeleted,
+        })
+        .await?;
+    tracing::info!(target: "security", user_id = id, admin_id = admin.0.id, "account deleted");
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,239 @@
+use axum::{
+    extract::State,
+    http::{
+        header::{RETRY_AFTER, SET_COOKIE},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::{
+    auth::{
+        csrf::CsrfToken, lockout::LockoutKey, password, random_token, refresh, session,
+        unauthorized,
+    },
+    middleware::forwarded::ClientInfo,
+    repositories::{
+        label::LabelRepository,
+        todo::TodoRepository,
+        user::{NewRefreshToken, RefreshRotation, UserRepository},
+    },
+    state::AppState,
+};
+
+use super::{error::ApiError, ValidatedJson};
+
+pub fn auth_routes<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+) -> Router<AppState<T, L, U>> {
+    Router::new()
+        .route("/auth/login", post(login::<T, L, U>))
+        .route("/auth/refresh", post(refresh_access_token::<T, L, U>))
+        .route("/auth/logout", post(logout::<T, L, U>))
+        .route("/auth/csrf", get(csrf_token))
+}
+
+/// Answers with an access and a refresh token, or with the user and a session
+/// cookie when the body asks for `"session": true`. Repeated failures lock the
+/// account or the client address out with 429.
+pub async fn login<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    client: Option<Extension<ClientInfo>>,
+    ValidatedJson(payload): ValidatedJson<Login>,
+) -> Result<Response, ApiError> {
+    let ip = client.map(|Extension(client)| client.ip);
+    let mut lockout_keys = vec![LockoutKey::Account(&payload.username)];
+    lockout_keys.extend(ip.map(LockoutKey::Ip));
+    if let Some(lockout) = &state.lockout {
+        if let Some(retry_after) = lockout.locked_for(&lockout_keys).await {
+            tracing::info!(
+                target: "security",
+                username = %payload.username,
+                ip = ?ip,
+                "login refused while locked out"
+            );
+            return Ok(too_many_attempts(retry_after));
+        }
+    }
+
+    let credentials = state
+        .user_repository
+        .credentials_by_username(&payload.username)
+        .await?;
+    let hash = credentials
+        .as_ref()
+        .and_then(|credentials| credentials.password_hash.clone());
+    let verified = match hash {
+        Some(hash) => password::verify(payload.password, hash).await?,
+        // unknown user or an OAuth-only account
+        None => {
+            password::verify_nothing(payload.password).await?;
+            false
+        }
+    };
+    let Some(credentials) = credentials.filter(|_| verified) else {
+        tracing::info!(
+            target: "security",
+            username = %payload.username,
+            ip = ?ip,
+            "login failed"
+        );
+        if let Some(lockout) = &state.lockout {
+            lockout.record_failure(&lockout_keys).await;
+        }
+        return Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "invalid username or password",
+        ));
+    };
+    // only told once the password proved who is asking
+    if credentials.disabled {
+        return Err(account_disabled());
+    }
+    let id = credentials.id;
+    if let Some(lockout) = &state.lockout {
+        lockout.reset(LockoutKey::Account(&payload.username)).await;
+    }
+
+    let auth = &state.config.auth;
+    if !payload.session {
+        let refresh_token = refresh::new_refresh_token();
+        state
+            .user_repository
+            .create_refresh_token(NewRefreshToken {
+                user_id: id,
+                family: random_token(),
+                token_hash: refresh::hash(&refresh_token),
+                expires_at: Utc::now() + Duration::seconds(auth.refresh_token_ttl_secs as i64),
+            })
+            .await?;
+        let mut token = state.tokens.issue(id)?;
+        token.refresh_token = Some(refresh_token);
+        return Ok((StatusCode::OK, Json(token)).into_response());
+    }
+
+    let cookie = start_session(&state, id).await?;
+    let user = state.user_repository.find(id).await?;
+
+    Ok((StatusCode::OK, [(SET_COOKIE, cookie)], Json(user)).into_response())
+}
+
+/// Stores a new session for the user and returns its `Set-Cookie` value.
+pub(super) async fn start_session<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    state: &AppState<T, L, U>,
+    user_id: i32,
+) -> Result<HeaderValue, ApiError> {
+    let auth = &state.config.auth;
+    let session_id = session::new_session_id();
+    let expires_at = Utc::now() + Duration::seconds(auth.session_ttl_secs as i64);
+    state
+        .user_repository
+        .create_session(session_id.clone(), user_id, expires_at)
+        .await?;
+
+    Ok(session::set_cookie(&session_id, auth))
+}
+
+/// Trades a refresh token for a new access token and its successor. Presenting
+/// a token a second time revokes every token descended from the same login.
+pub async fn refresh_access_token<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    ValidatedJson(payload): ValidatedJson<Refresh>,
+) -> Result<impl IntoResponse, ApiError> {
+    let next = refresh::new_refresh_token();
+    let expires_at =
+        Utc::now() + Duration::seconds(state.config.auth.refresh_token_ttl_secs as i64);
+    let rotation = state
+        .user_repository
+        .rotate_refresh_token(
+            &refresh::hash(&payload.refresh_token),
+            refresh::hash(&next),
+            expires_at,
+        )
+        .await?;
+
+    match rotation {
+        RefreshRotation::Rotated { user_id } => {
+            let mut token = state.tokens.issue(user_id)?;
+            token.refresh_token = Some(next);
+            Ok((StatusCode::OK, Json(token)))
+        }
+        RefreshRotation::Reused { user_id } => {
+            tracing::warn!(user_id, "refresh token reused, revoked its family");
+            Err(invalid_refresh_token())
+        }
+        RefreshRotation::Invalid => Err(invalid_refresh_token()),
+    }
+}
+
+/// Ends the cookie session and revokes the refresh token in the body, if
+/// any. Access tokens stay valid until they expire.
+pub async fn logout<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    headers: HeaderMap,
+    payload: Option<ValidatedJson<Refresh>>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let Some(session_id) = session::session_id(&headers) {
+        state.user_repository.delete_session(session_id).await?;
+    }
+    if let Some(ValidatedJson(payload)) = payload {
+        state
+            .user_repository
+            .revoke_refresh_token(&refresh::hash(&payload.refresh_token))
+            .await?;
+    }
+
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(SET_COOKIE, session::clear_cookie(&state.config.auth))],
+    ))
+}
+
+#[derive(Debug, Serialize)]
+pub struct CsrfTokenBody {
+    pub token: String,
+}
+
+/// The token scripts send in `X-CSRF-Token` with changes made through the
+/// session cookie; pages get it in their forms instead.
+pub async fn csrf_token(CsrfToken(token): CsrfToken) -> Result<Json<CsrfTokenBody>, ApiError> {
+    let token = token.ok_or_else(unauthorized)?;
+    Ok(Json(CsrfTokenBody { token }))
+}
+
+fn too_many_attempts(retry_after: std::time::Duration) -> Response {
+    let secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    let mut res = ApiError::new(
+        StatusCode::TOO_MANY_REQUESTS,
+        "too many failed logins, try again later",
+    )
+    .into_response();
+    res.headers_mut().insert(RETRY_AFTER, secs.into());
+    res
+}
+
+pub(super) fn account_disabled() -> ApiError {
+    ApiError::new(StatusCode::FORBIDDEN, "account is disabled")
+}
+
+fn invalid_refresh_token() -> ApiError {
+    ApiError::new(StatusCode::UNAUTHORIZED, "invalid refresh token")
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Validate)]
+pub struct Refresh {
+    refresh_token: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Validate)]
+pub struct Login {
+    username: String,
+    password: String,
+    /// Start a cookie session instead of issuing an access token.
+    #[serde(default)]
+    session: bool,
+}
@@ -0,0 +1,149 @@
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::{
+    repositories::{
+        board,
+        label::LabelRepository,
+        todo::{Todo, TodoFilter, TodoRepository},
+        user::UserRepository,
+    },
+    state::AppState,
+};
+
+use super::{
+    error::ApiError,
+    include::{Include, IncludeParams},
+    links::Linked,
+    todo::{todo_links, FilterParams, TodoView},
+    workspace::WorkspaceScope,
+    ValidatedJson,
+};
+
+/// The workspace's todos as a kanban board; moving a card takes the same
+/// rights as changing the todo.
+pub fn board_routes<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+) -> Router<AppState<T, L, U>> {
+    Router::new()
+        .route("/board", get(show_board::<T, L, U>))
+        .route("/board/move", post(move_on_board::<T, L, U>))
+}
+
+/// Todos have no status beyond `completed`, so it makes the columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BoardColumn {
+    Open,
+    Done,
+}
+
+impl BoardColumn {
+    const ALL: [BoardColumn; 2] = [BoardColumn::Open, BoardColumn::Done];
+
+    fn completed(self) -> bool {
+        self == BoardColumn::Done
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Board {
+    /// Every column, empty ones too.
+    pub columns: Vec<Column>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Column {
+    pub name: BoardColumn,
+    pub count: usize,
+    /// Top to bottom.
+    pub todos: Vec<Linked<TodoView>>,
+}
+
+/// Body of `POST /board/move`.
+#[derive(Debug, Deserialize, Validate)]
+pub struct MoveOnBoard {
+    pub todo_id: i32,
+    pub column: BoardColumn,
+    /// Counts from 0; last when past the end.
+    pub position: u32,
+}
+
+async fn load_board<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    state: &AppState<T, L, U>,
+    scope: &WorkspaceScope,
+    filter: TodoFilter,
+    include: Include,
+    headers: &HeaderMap,
+) -> Result<Board, ApiError> {
+    let todos = scope.todos(state);
+    let mut all = todos.with_filter(filter).all().await?;
+    board::sort(&mut all, &todos.board_positions().await?);
+    let view = |todo: Todo| {
+        let links = todo_links(scope, &todo);
+        Linked::new(TodoView::new(todo, include).in_language(headers), links)
+    };
+    let (done, open): (Vec<Todo>, Vec<Todo>) = all.into_iter().partition(|todo| todo.completed);
+    let columns = BoardColumn::ALL
+        .into_iter()
+        .zip([open, done])
+        .map(|(name, todos)| Column {
+            name,
+            count: todos.len(),
+            todos: todos.into_iter().map(view).collect(),
+        })
+        .collect();
+
+    Ok(Board { columns })
+}
+
+/// `FilterParams` narrow the cards as they do listings; positions hold
+/// within the whole column, so a narrowed column keeps their order.
+pub async fn show_board<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Query(include): Query<IncludeParams>,
+    Query(filter): Query<FilterParams>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let include = Include::from_params(&include)?;
+    let filter = filter.filter(&scope, &state.config.search)?;
+    let board = load_board(&state, &scope, filter, include, &headers).await?;
+
+    Ok((StatusCode::OK, Json(board)))
+}
+
+/// Puts the todo at `position` of `column`, completing or reopening it when
+/// it changes columns, and answers with the board as `GET /board` would for
+/// the same query.
+pub async fn move_on_board<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Query(include): Query<IncludeParams>,
+    Query(filter): Query<FilterParams>,
+    headers: HeaderMap,
+    ValidatedJson(payload): ValidatedJson<MoveOnBoard>,
+) -> Result<impl IntoResponse, ApiError> {
+    let include = Include::from_params(&include)?;
+    let filter = filter.filter(&scope, &state.config.search)?;
+    let todos = scope.todos(&state);
+    scope
+        .check_may_change(&state, &todos.find(payload.todo_id).await?)
+        .await?;
+    todos
+        .move_on_board(
+            payload.todo_id,
+            payload.column.completed(),
+            payload.position,
+        )
+        .await?;
+    let board = load_board(&state, &scope, filter, include, &headers).await?;
+
+    Ok((StatusCode::OK, Json(board)))
+}
@@ -0,0 +1,57 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use chrono::{Duration, NaiveDate, Utc};
+use serde::Deserialize;
+
+use crate::{
+    repositories::{label::LabelRepository, todo::TodoRepository, user::UserRepository},
+    state::AppState,
+};
+
+use super::{error::ApiError, workspace::WorkspaceScope};
+
+/// Days charted when `from` is not given, counting `to`.
+const DEFAULT_DAYS: i64 = 14;
+const MAX_DAYS: i64 = 366;
+
+/// The remaining effort of a workspace; under `/workspaces/:id/burndown`
+/// for a workspace other than the caller's default.
+pub fn burndown_routes<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+) -> Router<AppState<T, L, U>> {
+    Router::new().route("/burndown", get(burndown::<T, L, U>))
+}
+
+/// Days in UTC, both included; `to` defaults to today.
+#[derive(Debug, Deserialize)]
+pub struct BurndownParams {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+/// Estimated minutes left on open todos at the end of each day, for
+/// charting. Completed todos and todos without an estimate count as
+/// nothing left.
+pub async fn burndown<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Query(params): Query<BurndownParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let to = params.to.unwrap_or_else(|| Utc::now().date_naive());
+    let from = params.from.unwrap_or(to - Duration::days(DEFAULT_DAYS - 1));
+    if from > to {
+        return Err(ApiError::bad_request("from can not be after to"));
+    }
+    if (to - from).num_days() >= MAX_DAYS {
+        return Err(ApiError::bad_request(format!(
+            "can not chart over {} days",
+            MAX_DAYS
+        )));
+    }
+    let days = scope.todos(&state).burndown(from, to).await?;
+    Ok((StatusCode::OK, Json(days)))
+}
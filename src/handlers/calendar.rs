@@ -0,0 +1,151 @@
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use chrono::{Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    repositories::{
+        filter::{Comparison, FilterExpr, Term},
+        label::LabelRepository,
+        todo::{Todo, TodoFilter, TodoRepository},
+        user::UserRepository,
+    },
+    state::AppState,
+};
+
+use super::{
+    error::ApiError,
+    include::{Include, IncludeParams},
+    links::Linked,
+    todo::{todo_links, FilterParams, TodoView},
+    workspace::WorkspaceScope,
+};
+
+/// Days shown when `to` is not given, counting `from`.
+const DEFAULT_DAYS: i64 = 7;
+/// A month view padded to whole weeks.
+const MAX_DAYS: i64 = 42;
+
+pub fn calendar_routes<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+) -> Router<AppState<T, L, U>> {
+    Router::new().route("/todos/calendar", get(todo_calendar::<T, L, U>))
+}
+
+/// Days in UTC, both included; `from` defaults to today.
+#[derive(Debug, Deserialize)]
+pub struct CalendarParams {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Calendar {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// Every day of the range, empty ones too.
+    pub days: Vec<CalendarDay>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CalendarDay {
+    pub date: NaiveDate,
+    pub todos: Vec<Linked<TodoView>>,
+    /// Only on today: the open todos due before it.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub overdue: Vec<Linked<TodoView>>,
+}
+
+/// `filter` with `terms` added to its expression.
+fn narrowed(filter: &TodoFilter, terms: Vec<Term>) -> TodoFilter {
+    let exprs = filter
+        .expr
+        .clone()
+        .into_iter()
+        .chain(terms.into_iter().map(FilterExpr::Term))
+        .collect();
+    TodoFilter {
+        expr: Some(FilterExpr::All(exprs)),
+        ..filter.clone()
+    }
+}
+
+/// Todos with a due date in the range, by day, for a week or month view;
+/// `FilterParams` narrow them as they do listings. When the range holds
+/// today, open todos due before it are carried over to today's `overdue`,
+/// wherever they were due, and leave their own day.
+pub async fn todo_calendar<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Query(params): Query<CalendarParams>,
+    Query(include): Query<IncludeParams>,
+    Query(filter): Query<FilterParams>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let today = Utc::now().date_naive();
+    let from = params.from.unwrap_or(today);
+    let to = params.to.unwrap_or(from + Duration::days(DEFAULT_DAYS - 1));
+    if from > to {
+        return Err(ApiError::bad_request("from can not be after to"));
+    }
+    if (to - from).num_days() >= MAX_DAYS {
+        return Err(ApiError::bad_request(format!(
+            "can not show over {} days",
+            MAX_DAYS
+        )));
+    }
+    let include = Include::from_params(&include)?;
+    let filter = filter.filter(&scope, &state.config.search)?;
+    let todos = scope.todos(&state);
+    let view = |todo: Todo| {
+        let links = todo_links(&scope, &todo);
+        Linked::new(TodoView::new(todo, include).in_language(&headers), links)
+    };
+
+    let carries_over = from <= today && today <= to;
+    let overdue = |todo: &Todo| {
+        carries_over && !todo.completed && todo.due_date.is_some_and(|due| due < today)
+    };
+    let mut days: Vec<CalendarDay> = from
+        .iter_days()
+        .take_while(|date| *date <= to)
+        .map(|date| CalendarDay {
+            date,
+            todos: vec![],
+            overdue: vec![],
+        })
+        .collect();
+    let due = todos
+        .with_filter(narrowed(
+            &filter,
+            vec![
+                Term::Due(Comparison::OnOrAfter, from),
+                Term::Due(Comparison::OnOrBefore, to),
+            ],
+        ))
+        .all()
+        .await?;
+    for todo in due.into_iter().filter(|todo| !overdue(todo)) {
+        if let Some(due) = todo.due_date {
+            days[(due - from).num_days() as usize]
+                .todos
+                .push(view(todo));
+        }
+    }
+    if carries_over {
+        let overdue = todos
+            .with_filter(narrowed(
+                &filter,
+                vec![Term::Completed(false), Term::Due(Comparison::Before, today)],
+            ))
+            .all()
+            .await?;
+        days[(today - from).num_days() as usize].overdue = overdue.into_iter().map(view).collect();
+    }
+
+    Ok((StatusCode::OK, Json(Calendar { from, to, days })))
+}
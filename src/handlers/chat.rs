@@ -0,0 +1,137 @@
+use validator::Validate;
+
+use crate::{
+    auth::AuthUser,
+    quota::QuotaExceeded,
+    repositories::{
+        filter::{FilterExpr, Term},
+        label::LabelRepository,
+        todo::{CreateTodo, TodoFilter, TodoRepository, UpdateTodo},
+        user::UserRepository,
+        workspace::DEFAULT_WORKSPACE_ID,
+        RepositoryError,
+    },
+    state::AppState,
+};
+
+use super::{error::ApiError, workspace::WorkspaceScope};
+
+/// Open todos `/list` shows, newest first.
+const LIST_LIMIT: usize = 20;
+
+/// Chat commands act for the user a chat account is linked to, in the
+/// linked workspace, with the rights they would have over HTTP: `None` when
+/// the account is disabled or may not write there.
+pub async fn chat_scope<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    state: &AppState<T, L, U>,
+    workspace_id: i32,
+    user_id: i32,
+) -> Result<Option<WorkspaceScope>, ApiError> {
+    let users = &state.user_repository;
+    if users.credentials(user_id).await?.disabled {
+        return Ok(None);
+    }
+    let role = users.role(workspace_id, user_id).await?;
+    if workspace_id != DEFAULT_WORKSPACE_ID && !role.is_some_and(|role| role.can_write()) {
+        return Ok(None);
+    }
+    Ok(Some(WorkspaceScope {
+        id: workspace_id,
+        user: Some(AuthUser { id: user_id }),
+        role,
+        uuid_ids: state.config.features.uuid_ids,
+    }))
+}
+
+/// Creates a todo the caller owns, as `POST /todos` would; answers with
+/// the reply for the chat.
+pub async fn add_todo<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    state: &AppState<T, L, U>,
+    scope: &WorkspaceScope,
+    text: &str,
+) -> Result<String, ApiError> {
+    let payload = CreateTodo {
+        owner_id: scope.user.map(|user| user.id),
+        ..CreateTodo::new(text.to_string())
+    };
+    if payload.validate().is_err() {
+        return Ok("A todo takes 1 to 100 characters".to_string());
+    }
+    let todos = scope.todos(state);
+    match state
+        .quotas
+        .check_open_todos(&todos, payload.owner_id)
+        .await
+    {
+        Err(e) if e.is::<QuotaExceeded>() => return Ok(e.to_string()),
+        result => result?,
+    }
+    let todo = todos.create(payload).await?;
+
+    Ok(format!("Added #{}: {}", todo.id, todo.text))
+}
+
+/// The newest open todos of the workspace, one per line.
+pub async fn list_todos<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    state: &AppState<T, L, U>,
+    scope: &WorkspaceScope,
+) -> Result<String, ApiError> {
+    let open = scope
+        .todos(state)
+        .with_filter(TodoFilter {
+            expr: Some(FilterExpr::Term(Term::Completed(false))),
+            hide_snoozed: true,
+            ..TodoFilter::default()
+        })
+        .all()
+        .await?;
+    if open.is_empty() {
+        return Ok("Nothing left to do".to_string());
+    }
+    let mut lines: Vec<String> = open
+        .iter()
+        .take(LIST_LIMIT)
+        .map(|todo| format!("#{} {}", todo.id, todo.text))
+        .collect();
+    if open.len() > LIST_LIMIT {
+        lines.push(format!("and {} more", open.len() - LIST_LIMIT));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Completes todo `id` when the caller may change it.
+pub async fn complete_todo<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    state: &AppState<T, L, U>,
+    scope: &WorkspaceScope,
+    id: i32,
+) -> Result<String, ApiError> {
+    let todos = scope.todos(state);
+    let todo = match todos.find(id).await {
+        Ok(todo) => todo,
+        Err(e) if matches!(e.downcast_ref(), Some(RepositoryError::NotFound(_))) => {
+            return Ok(format!("There is no todo #{}", id));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if scope.check_may_change(state, &todo).await.is_err() {
+        return Ok(format!("You can not change #{}", id));
+    }
+    let todo = todos
+        .update(
+            id,
+            UpdateTodo {
+                text: None,
+                completed: Some(true),
+                labels: None,
+                assignee_id: None,
+                text_i18n: None,
+                estimate_minutes: None,
+                due_date: None,
+                description: None,
+                custom: None,
+            },
+        )
+        .await?;
+
+    Ok(format!("Completed #{}: {}", todo.id, todo.text))
+}
@@ -0,0 +1,92 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, patch},
+    Json, Router,
+};
+
+use crate::{
+    repositories::{
+        checklist::{CreateChecklistItem, UpdateChecklistItem},
+        label::LabelRepository,
+        todo::TodoRepository,
+        user::UserRepository,
+    },
+    state::AppState,
+};
+
+use super::{error::ApiError, path_id::PathId, workspace::WorkspaceScope, ValidatedJson};
+
+/// The ordered checklist of a todo; changing it takes the same rights as
+/// changing the todo.
+pub fn checklist_routes<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+) -> Router<AppState<T, L, U>> {
+    Router::new()
+        .route(
+            "/todos/:id/items",
+            get(all_items::<T, L, U>).post(create_item::<T, L, U>),
+        )
+        .route(
+            "/todos/:id/items/:item_id",
+            patch(update_item::<T, L, U>).delete(delete_item::<T, L, U>),
+        )
+}
+
+pub async fn all_items<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path(id): Path<PathId>,
+) -> Result<impl IntoResponse, ApiError> {
+    let id = scope.todo_id(&state, id).await?;
+    let items = scope.todos(&state).checklist(id).await?;
+
+    Ok((StatusCode::OK, Json(items)))
+}
+
+pub async fn create_item<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path(id): Path<PathId>,
+    ValidatedJson(payload): ValidatedJson<CreateChecklistItem>,
+) -> Result<impl IntoResponse, ApiError> {
+    let id = scope.todo_id(&state, id).await?;
+    let todos = scope.todos(&state);
+    scope
+        .check_may_change(&state, &todos.find(id).await?)
+        .await?;
+    let item = todos.add_item(id, payload).await?;
+
+    Ok((StatusCode::CREATED, Json(item)))
+}
+
+pub async fn update_item<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path((id, item_id)): Path<(PathId, i32)>,
+    ValidatedJson(payload): ValidatedJson<UpdateChecklistItem>,
+) -> Result<impl IntoResponse, ApiError> {
+    let id = scope.todo_id(&state, id).await?;
+    let todos = scope.todos(&state);
+    scope
+        .check_may_change(&state, &todos.find(id).await?)
+        .await?;
+    let item = todos.update_item(id, item_id, payload).await?;
+
+    Ok((StatusCode::OK, Json(item)))
+}
+
+pub async fn delete_item<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path((id, item_id)): Path<(PathId, i32)>,
+) -> Result<StatusCode, ApiError> {
+    let id = scope.todo_id(&state, id).await?;
+    let todos = scope.todos(&state);
+    scope
+        .check_may_change(&state, &todos.find(id).await?)
+        .await?;
+    todos.delete_item(id, item_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,54 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::get, Json, Router};
+
+use crate::{
+    repositories::{
+        custom_field::CustomFields,
+        label::LabelRepository,
+        todo::TodoRepository,
+        user::UserRepository,
+        workspace::{Role, DEFAULT_WORKSPACE_ID},
+    },
+    state::AppState,
+};
+
+use super::{error::ApiError, workspace::WorkspaceScope, ValidatedJson};
+
+/// The fields a workspace's todos carry under `custom`.
+pub fn custom_field_routes<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+) -> Router<AppState<T, L, U>> {
+    Router::new().route(
+        "/custom-fields",
+        get(all_custom_fields::<T, L, U>).put(set_custom_fields::<T, L, U>),
+    )
+}
+
+pub async fn all_custom_fields<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+) -> Result<impl IntoResponse, ApiError> {
+    let fields = state.user_repository.custom_fields(scope.id).await?;
+
+    Ok((StatusCode::OK, Json(fields)))
+}
+
+/// Replaces every definition; only admins may, outside the default
+/// workspace. Values todos already hold are kept, and are checked against
+/// the new definitions the next time their custom values change.
+pub async fn set_custom_fields<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    ValidatedJson(payload): ValidatedJson<CustomFields>,
+) -> Result<impl IntoResponse, ApiError> {
+    if scope.id != DEFAULT_WORKSPACE_ID && !scope.role.is_some_and(Role::can_manage) {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "only workspace admins may define custom fields",
+        ));
+    }
+    let fields = state
+        .user_repository
+        .set_custom_fields(scope.id, payload)
+        .await?;
+
+    Ok((StatusCode::OK, Json(fields)))
+}
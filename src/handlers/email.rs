@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{DefaultBodyLimit, FromRequest, Multipart, State},
+    http::{header::CONTENT_TYPE, Request, StatusCode},
+    response::IntoResponse,
+    routing::post,
+    BoxError, Json, Router,
+};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rand::{rngs::OsRng, RngCore};
+use serde::Serialize;
+use sha2::Sha256;
+use validator::Validate;
+
+use crate::{
+    auth::{csrf, refresh, unauthorized},
+    quota::QuotaExceeded,
+    repositories::{
+        email::NewEmailInbox,
+        label::LabelRepository,
+        todo::{CreateTodo, TodoRepository},
+        user::UserRepository,
+    },
+    state::AppState,
+};
+
+use super::{chat::chat_scope, error::ApiError, workspace::WorkspaceScope};
+
+/// Mail posted by Mailgun may carry attachments, which are read past and
+/// dropped; Mailgun takes no larger messages.
+const MAX_MAIL_BYTES: usize = 25 * 1024 * 1024;
+
+/// Mail signed longer ago than this is refused, so captured posts can not
+/// be replayed later.
+const MAX_MAIL_AGE_SECS: i64 = 5 * 60;
+
+/// A user's plus-address per workspace, and the endpoint a Mailgun route
+/// forwards mail for those addresses to.
+pub fn email_routes<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+) -> Router<AppState<T, L, U>> {
+    Router::new()
+        .route(
+            "/email/address",
+            post(create_address::<T, L, U>).delete(delete_address::<T, L, U>),
+        )
+        .route(
+            "/email/inbound",
+            post(inbound::<T, L, U>).layer(DefaultBodyLimit::max(MAX_MAIL_BYTES)),
+        )
+}
+
+#[derive(Debug, Serialize)]
+pub struct EmailAddress {
+    pub address: String,
+}
+
+/// A new plus-address that files mail as the caller's todos in this
+/// workspace, replacing the previous one; shown once.
+pub async fn create_address<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+) -> Result<impl IntoResponse, ApiError> {
+    let user = scope.user.ok_or_else(unauthorized)?;
+    let (local, domain) = state
+        .config
+        .email
+        .address
+        .as_deref()
+        .and_then(|address| address.split_once('@'))
+        .ok_or_else(|| ApiError::not_found("email is not configured"))?;
+    let token = address_token();
+    state
+        .user_repository
+        .set_email_inbox(NewEmailInbox {
+            token_hash: refresh::hash(&token),
+            workspace_id: scope.id,
+            user_id: user.id,
+        })
+        .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(EmailAddress {
+            address: format!("{}+{}@{}", local, token, domain),
+        }),
+    ))
+}
+
+pub async fn delete_address<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+) -> Result<StatusCode, ApiError> {
+    let user = scope.user.ok_or_else(unauthorized)?;
+    state
+        .user_repository
+        .delete_email_inbox(scope.id, user.id)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 128 random bits in lowercase hex, which survives mail servers that fold
+/// the case of addresses.
+fn address_token() -> String {
+    let mut bytes = [0; 16];
+    OsRng.fill_bytes(&mut bytes);
+    hex(&bytes)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The fields of a message Mailgun forwards that we use, from either the
+/// urlencoded or the multipart form it posts.
+#[derive(Debug)]
+pub struct InboundEmail {
+    pub recipient: String,
+    pub subject: String,
+    /// The text body without quoted replies and signature, when Mailgun
+    /// could tell them apart, or the whole text body.
+    pub body: Option<String>,
+    pub timestamp: String,
+    pub token: String,
+    pub signature: String,
+}
+
+#[async_trait]
+impl<S, B> FromRequest<S, B> for InboundEmail
+where
+    S: Send + Sync,
+    B: http_body::Body + Send + 'static,
+    B::Data: Into<Bytes> + Send,
+    B::Error: Into<BoxError>,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let invalid = |e: &dyn std::fmt::Display| {
+            ApiError::bad_request(format!("Invalid inbound email: {}", e))
+        };
+        let multipart = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("multipart/form-data"));
+
+        let mut fields = HashMap::new();
+        if multipart {
+            let mut form = Multipart::from_request(req, state)
+                .await
+                .map_err(|e| invalid(&e))?;
+            while let Some(field) = form.next_field().await.map_err(|e| invalid(&e))? {
+                let Some(name) = field.name().map(str::to_string) else {
+                    continue;
+                };
+                if field.file_name().is_some() {
+                    continue;
+                }
+                fields.insert(name, field.text().await.map_err(|e| invalid(&e))?);
+            }
+        } else {
+            let body = Bytes::from_request(req, state)
+                .await
+                .map_err(|e| invalid(&e))?;
+            fields = serde_urlencoded::from_bytes(&body).map_err(|e| invalid(&e))?;
+        }
+
+        let mut take = |name: &str| {
+            fields
+                .remove(name)
+                .ok_or_else(|| invalid(&format!("missing {}", name)))
+        };
+        Ok(Self {
+            recipient: take("recipient")?,
+            subject: take("subject").unwrap_or_default(),
+            body: take("stripped-text").or_else(|_| take("body-plain")).ok(),
+            timestamp: take("timestamp")?,
+            token: take("token")?,
+            signature: take("signature")?,
+        })
+    }
+}
+
+impl InboundEmail {
+    /// Whether Mailgun signed the post with `key` within the last five
+    /// minutes of `now`.
+    pub fn verify(&self, key: &str, now: DateTime<Utc>) -> bool {
+        let Ok(sent_at) = self.timestamp.parse::<i64>() else {
+            return false;
+        };
+        if (now.timestamp() - sent_at).abs() > MAX_MAIL_AGE_SECS {
+            return false;
+        }
+        csrf::matches(
+            &signature(key, &self.timestamp, &self.token),
+            &self.signature,
+        )
+    }
+}
+
+/// The hex HMAC-SHA256 of the timestamp followed by the token.
+fn signature(key: &str, timestamp: &str, token: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(timestamp.as_bytes());
+    mac.update(token.as_bytes());
+    hex(&mac.finalize().into_bytes())
+}
+
+/// The token of `recipient` when it is a plus-address of `address`; the
+/// recipient may come as `Name <todo+token@example.com>`.
+fn plus_token<'a>(address: &str, recipient: &'a str) -> Option<&'a str> {
+    let (local, domain) = address.split_once('@')?;
+    let recipient = recipient.trim();
+    let recipient = match recipient.rsplit_once('<') {
+        Some((_, rest)) => rest.strip_suffix('>')?,
+        None => recipient,
+    };
+    let (recipient_local, recipient_domain) = recipient.rsplit_once('@')?;
+    let (base, token) = recipient_local.split_once('+')?;
+    let matches = base.eq_ignore_ascii_case(local) && recipient_domain.eq_ignore_ascii_case(domain);
+    (matches && !token.is_empty()).then_some(token)
+}
+
+/// The body without surrounding blank lines, cut to what a description
+/// holds; `None` when there is nothing left.
+fn description(body: &str) -> Option<String> {
+    let body = body.trim();
+    (!body.is_empty()).then(|| body.chars().take(10000).collect())
+}
+
+/// The subject with runs of whitespace collapsed, cut to what a todo holds.
+fn todo_text(subject: &str) -> String {
+    subject
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(100)
+        .collect()
+}
+
+/// Files a mail Mailgun forwards as a todo of the plus-address's user, its
+/// subject becoming the text and its body the description. Mail that can
+/// not become a todo is answered with 406, which tells Mailgun not to
+/// retry; 404 when email is not configured.
+pub async fn inbound<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    email: InboundEmail,
+) -> Result<impl IntoResponse, ApiError> {
+    let config = &state.config.email;
+    let (Some(address), Some(key)) = (
+        config.address.as_deref(),
+        config.mailgun_signing_key.as_deref(),
+    ) else {
+        return Err(ApiError::not_found("email is not configured"));
+    };
+    if !email.verify(key, Utc::now()) {
+        return Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "invalid mailgun signature",
+        ));
+    }
+    let rejected = |message: &str| ApiError::new(StatusCode::NOT_ACCEPTABLE, message);
+
+    let token = plus_token(address, &email.recipient)
+        .ok_or_else(|| rejected("not a plus-address of this inbox"))?;
+    let inbox = state
+        .user_repository
+        .email_inbox(&refresh::hash(&token.to_ascii_lowercase()))
+        .await?
+        .ok_or_else(|| rejected("unknown address"))?;
+    let scope = chat_scope(&state, inbox.workspace_id, inbox.user_id)
+        .await?
+        .ok_or_else(|| rejected("the address's user can not add todos there"))?;
+
+    let payload = CreateTodo {
+        owner_id: Some(inbox.user_id),
+        description: email.body.as_deref().and_then(description),
+        ..CreateTodo::new(todo_text(&email.subject))
+    };
+    if payload.validate().is_err() {
+        return Err(rejected("mail without a subject"));
+    }
+    let todos = scope.todos(&state);
+    match state
+        .quotas
+        .check_open_todos(&todos, payload.owner_id)
+        .await
+    {
+        Err(e) if e.is::<QuotaExceeded>() => return Err(rejected(&e.to_string())),
+        result => result?,
+    }
+    let todo = todos.create(payload).await?;
+
+    Ok((StatusCode::OK, Json(todo)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_verify_mailgun_signatures() {
+        let now = Utc::now();
+        let timestamp = now.timestamp().to_string();
+        let email = |signature: String| InboundEmail {
+            recipient: "todo+abc@in.example.com".to_string(),
+            subject: "buy milk".to_string(),
+            body: None,
+            timestamp: timestamp.clone(),
+            token: "0123456789abcdef".to_string(),
+            signature,
+        };
+        let signed = email(signature("key", &timestamp, "0123456789abcdef"));
+        assert!(signed.verify("key", now));
+        assert!(!signed.verify("other", now));
+        assert!(!signed.verify("key", now + chrono::Duration::minutes(6)));
+        assert!(!email("forged".to_string()).verify("key", now));
+    }
+
+    #[test]
+    fn should_take_the_token_of_plus_addresses_only() {
+        let address = "todo@in.example.com";
+        assert_eq!(plus_token(address, "todo+abc@in.example.com"), Some("abc"));
+        assert_eq!(
+            plus_token(address, "Todo <TODO+abc@IN.example.com>"),
+            Some("abc")
+        );
+        assert_eq!(plus_token(address, "todo@in.example.com"), None);
+        assert_eq!(plus_token(address, "todo+@in.example.com"), None);
+        assert_eq!(plus_token(address, "other+abc@in.example.com"), None);
+        assert_eq!(plus_token(address, "todo+abc@example.com"), None);
+    }
+
+    #[test]
+    fn should_fit_subjects_into_todo_text() {
+        assert_eq!(todo_text("  buy\tmilk \n"), "buy milk");
+        assert_eq!(todo_text(&"x".repeat(120)).len(), 100);
+        assert_eq!(todo_text(" "), "");
+        assert_eq!(description("\n two\n lines \n\n").unwrap(), "two\n lines");
+        assert_eq!(description(" \n"), None);
+    }
+}
@@ -0,0 +1,127 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{
+    middleware::request_id::RequestId, quota::QuotaExceeded, repositories::RepositoryError,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ErrorBody {
+    pub status: u16,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+    /// The `X-Request-Id` of the request that failed, to quote in support
+    /// requests.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// What really went wrong behind a generic "Unexpected Error", kept out of
+/// the body but attached to the response for error reports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorCause(pub String);
+
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+    /// Boxed, as `Value` grows once a dependency turns on serde_json's
+    /// `preserve_order`.
+    details: Option<Box<Value>>,
+    cause: Option<String>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            details: None,
+            cause: None,
+        }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, message)
+    }
+
+    pub fn with_details(mut self, details: impl Serialize) -> Self {
+        self.details = serde_json::to_value(details).ok().map(Box::new);
+        self
+    }
+
+    /// Records what went wrong for error reports, keeping it out of the body.
+    pub fn with_cause(mut self, cause: impl Into<String>) -> Self {
+        self.cause = Some(cause.into());
+        self
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn body(&self) -> ErrorBody {
+        ErrorBody {
+            status: self.status.as_u16(),
+            message: self.message.clone(),
+            details: self.details.as_deref().cloned(),
+            request_id: RequestId::current().map(|RequestId(id)| id),
+        }
+    }
+}
+
+impl From<QuotaExceeded> for ApiError {
+    fn from(error: QuotaExceeded) -> Self {
+        Self::new(StatusCode::PAYMENT_REQUIRED, error.to_string()).with_details(error)
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(error: anyhow::Error) -> Self {
+        if let Some(exceeded) = error.downcast_ref::<QuotaExceeded>() {
+            return (*exceeded).into();
+        }
+        match error.downcast_ref::<RepositoryError>() {
+            Some(RepositoryError::NotFound(_)) => Self::not_found(error.to_string()),
+            Some(RepositoryError::Duplicate(_) | RepositoryError::Conflict(_)) => {
+                Self::new(StatusCode::CONFLICT, error.to_string())
+            }
+            Some(RepositoryError::MissingLabels(missing)) => {
+                Self::new(StatusCode::UNPROCESSABLE_ENTITY, error.to_string())
+                    .with_details(json!({ "missing_labels": missing }))
+            }
+            _ => {
+                tracing::error!("unexpected error: {:?}", error);
+                Self::internal("Unexpected Error").with_cause(format!("{:#}", error))
+            }
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    /// The body also rides along as an extension so `localize` can render
+    /// it again in the client's language.
+    fn into_response(self) -> Response {
+        let body = self.body();
+        let mut res = (self.status, Json(&body)).into_response();
+        res.extensions_mut().insert(body);
+        if let Some(cause) = self.cause {
+            res.extensions_mut().insert(ErrorCause(cause));
+        }
+        res
+    }
+}
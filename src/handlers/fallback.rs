@@ -0,0 +1,101 @@
+use std::path::Path;
+
+use axum::{
+    body::{boxed, Body},
+    http::{
+        header::{ACCEPT, ALLOW, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE},
+        Method, Request, StatusCode, Uri,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use tower::ServiceExt;
+use tower_http::services::{ServeDir, ServeFile};
+
+use super::error::ApiError;
+
+/// Router fallback for paths no route matches.
+pub async fn not_found(uri: Uri) -> ApiError {
+    ApiError::not_found(format!("no route for [{}]", uri.path()))
+}
+
+/// Router fallback serving a single-page app from `dir`. Files are served
+/// as they are; a GET for a missing one that accepts HTML, as a browser
+/// following a client-side route does, gets `index.html`. Anything else
+/// gets the usual 404.
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    files: ServeDir,
+    index: ServeFile,
+}
+
+impl StaticFiles {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            files: ServeDir::new(&dir),
+            index: ServeFile::new(dir.as_ref().join("index.html")),
+        }
+    }
+
+    pub async fn serve(self, req: Request<Body>) -> Response {
+        if !matches!(*req.method(), Method::GET | Method::HEAD) {
+            return not_found(req.uri().clone()).await.into_response();
+        }
+        let uri = req.uri().clone();
+        let mut index_req = Request::new(Body::empty());
+        *index_req.method_mut() = req.method().clone();
+        *index_req.headers_mut() = req.headers().clone();
+        let navigation = accepts_html(&req);
+
+        let Ok(res) = self.files.oneshot(req).await;
+        if res.status() != StatusCode::NOT_FOUND {
+            return res.map(boxed);
+        }
+        if !navigation {
+            return not_found(uri).await.into_response();
+        }
+        let Ok(res) = self.index.oneshot(index_req).await;
+        res.map(boxed)
+    }
+}
+
+fn accepts_html<B>(req: &Request<B>) -> bool {
+    req.headers()
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|range| range.split(';').next().map(str::trim) == Some("text/html"))
+}
+
+/// Replaces axum's empty 405 with the error body, listing the methods the
+/// route accepts. Must wrap the whole router: axum adds `Allow` after
+/// route-level layers ran.
+pub async fn method_not_allowed<B>(req: Request<B>, next: Next<B>) -> Response {
+    let method = req.method().clone();
+    let res = next.run(req).await;
+    if res.status() != StatusCode::METHOD_NOT_ALLOWED {
+        return res;
+    }
+
+    let (parts, _) = res.into_parts();
+    let allowed: Vec<&str> = parts
+        .headers
+        .get(ALLOW)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.split(',').map(str::trim).collect())
+        .unwrap_or_default();
+    let mut error = ApiError::new(
+        StatusCode::METHOD_NOT_ALLOWED,
+        format!("method [{}] not allowed", method),
+    )
+    .with_details(json!({ "allowed": allowed }))
+    .into_response();
+    for (name, value) in &parts.headers {
+        if ![CONTENT_TYPE, CONTENT_LENGTH, CONTENT_ENCODING].contains(name) {
+            error.headers_mut().insert(name, value.clone());
+        }
+    }
+    error
+}
@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::error::ApiError;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FieldsParams {
+    /// Comma separated, e.g. `fields=id,text`.
+    pub fields: Option<String>,
+}
+
+/// Top-level fields to keep when serializing a resource; keeps everything
+/// when the client did not ask for a selection.
+#[derive(Debug, Default)]
+pub struct Projection(Option<Vec<String>>);
+
+impl Projection {
+    pub fn from_params(params: &FieldsParams, allowed: &[&str]) -> Result<Self, ApiError> {
+        let Some(fields) = &params.fields else {
+            return Ok(Self(None));
+        };
+        let fields: Vec<String> = fields
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .map(str::to_string)
+            .collect();
+        if let Some(unknown) = fields
+            .iter()
+            .find(|field| !allowed.contains(&field.as_str()))
+        {
+            return Err(ApiError::bad_request(format!(
+                "unknown field [{}], expected one of [{}]",
+                unknown,
+                allowed.join(", ")
+            )));
+        }
+        if fields.is_empty() {
+            return Err(ApiError::bad_request("fields must not be empty"));
+        }
+
+        Ok(Self(Some(fields)))
+    }
+
+    /// Serialize `value`, trimming it (or each element of an array) to the
+    /// selected fields.
+    pub fn apply<T: Serialize>(&self, value: &T) -> Result<Value, ApiError> {
+        let value = serde_json::to_value(value).map_err(anyhow::Error::from)?;
+        Ok(match &self.0 {
+            Some(fields) => trim(value, fields),
+            None => value,
+        })
+    }
+}
+
+fn trim(value: Value, fields: &[String]) -> Value {
+    match value {
+        Value::Object(mut map) => {
+            map.retain(|key, _| fields.contains(key));
+            Value::Object(map)
+        }
+        Value::Array(items) => {
+            Value::Array(items.into_iter().map(|item| trim(item, fields)).collect())
+        }
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn should_trim_objects_and_arrays() {
+        let params = FieldsParams {
+            fields: Some("id, text".to_string()),
+        };
+        let projection = Projection::from_params(&params, &["id", "text", "completed"]).unwrap();
+        let value = json!([{ "id": 1, "text": "a", "completed": false }]);
+        assert_eq!(
+            projection.apply(&value).unwrap(),
+            json!([{ "id": 1, "text": "a" }])
+        );
+
+        let params = FieldsParams {
+            fields: Some("id,due".to_string()),
+        };
+        assert!(Projection::from_params(&params, &["id"]).is_err());
+    }
+}
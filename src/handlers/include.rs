@@ -0,0 +1,38 @@
+use serde::Deserialize;
+
+use super::error::ApiError;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct IncludeParams {
+    /// Comma separated associations to embed, e.g. `include=labels`.
+    pub include: Option<String>,
+}
+
+/// Associations a client asked to have embedded instead of referenced by id.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Include {
+    pub labels: bool,
+}
+
+impl Include {
+    const ASSOCIATIONS: &'static [&'static str] = &["labels"];
+
+    pub fn from_params(params: &IncludeParams) -> Result<Self, ApiError> {
+        let mut include = Self::default();
+        let names = params.include.as_deref().unwrap_or_default().split(',');
+        for name in names.map(str::trim).filter(|name| !name.is_empty()) {
+            match name {
+                "labels" => include.labels = true,
+                _ => {
+                    return Err(ApiError::bad_request(format!(
+                        "cannot include [{}], expected one of [{}]",
+                        name,
+                        Self::ASSOCIATIONS.join(", ")
+                    )))
+                }
+            }
+        }
+
+        Ok(include)
+    }
+}
@@ -0,0 +1,163 @@
+use axum::{
+    async_trait,
+    extract::FromRequest,
+    http::{header::CONTENT_TYPE, HeaderMap, Request, StatusCode},
+    BoxError,
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{error::ApiError, parse_json};
+
+pub const JSON_PATCH_JSON: &str = "application/json-patch+json";
+
+pub fn is_json_patch(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<mime::Mime>().ok())
+        .is_some_and(|mime| mime.essence_str() == JSON_PATCH_JSON)
+}
+
+/// One RFC 6902 operation; `move` and `copy` are not supported.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Test { path: String, value: Value },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPatch(pub Vec<PatchOperation>);
+
+impl JsonPatch {
+    /// Applies every operation to `document` or none of them. A failed
+    /// `test` is a 409 so clients can use it for optimistic concurrency.
+    pub fn apply(&self, document: &Value) -> Result<Value, ApiError> {
+        let mut patched = document.clone();
+        for operation in &self.0 {
+            match operation {
+                PatchOperation::Add { path, value } => add(&mut patched, path, value.clone())?,
+                PatchOperation::Remove { path } => {
+                    remove(&mut patched, path)?;
+                }
+                PatchOperation::Replace { path, value } => {
+                    remove(&mut patched, path)?;
+                    add(&mut patched, path, value.clone())?;
+                }
+                PatchOperation::Test { path, value } => {
+                    if patched.pointer(path) != Some(value) {
+                        return Err(ApiError::new(
+                            StatusCode::CONFLICT,
+                            format!("test failed at [{}]", path),
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(patched)
+    }
+}
+
+fn invalid_path(path: &str) -> ApiError {
+    ApiError::bad_request(format!("invalid patch path [{}]", path))
+}
+
+/// Parent pointer and the unescaped last token of `path`.
+fn split(path: &str) -> Result<(&str, String), ApiError> {
+    let (parent, token) = path.rsplit_once('/').ok_or_else(|| invalid_path(path))?;
+    Ok((parent, token.replace("~1", "/").replace("~0", "~")))
+}
+
+fn add(document: &mut Value, path: &str, value: Value) -> Result<(), ApiError> {
+    let (parent, token) = split(path)?;
+    match document.pointer_mut(parent) {
+        Some(Value::Object(map)) => {
+            map.insert(token, value);
+        }
+        Some(Value::Array(items)) if token == "-" => items.push(value),
+        Some(Value::Array(items)) => {
+            let index = token
+                .parse::<usize>()
+                .ok()
+                .filter(|index| *index <= items.len())
+                .ok_or_else(|| invalid_path(path))?;
+            items.insert(index, value);
+        }
+        _ => return Err(invalid_path(path)),
+    }
+    Ok(())
+}
+
+fn remove(document: &mut Value, path: &str) -> Result<Value, ApiError> {
+    let (parent, token) = split(path)?;
+    let removed = match document.pointer_mut(parent) {
+        Some(Value::Object(map)) => map.remove(&token),
+        Some(Value::Array(items)) => token
+            .parse::<usize>()
+            .ok()
+            .filter(|index| *index < items.len())
+            .map(|index| items.remove(index)),
+        _ => None,
+    };
+    removed.ok_or_else(|| invalid_path(path))
+}
+
+#[async_trait]
+impl<S, B> FromRequest<S, B> for JsonPatch
+where
+    S: Send + Sync,
+    B: http_body::Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let operations: Vec<PatchOperation> = parse_json(req, state).await?;
+
+        Ok(Self(operations))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn patch(operations: Value) -> JsonPatch {
+        JsonPatch(serde_json::from_value(operations).unwrap())
+    }
+
+    #[test]
+    fn should_apply_operations_in_order() {
+        let document = json!({ "text": "a", "labels": [1, 2] });
+        let patched = patch(json!([
+            { "op": "test", "path": "/text", "value": "a" },
+            { "op": "replace", "path": "/text", "value": "b" },
+            { "op": "add", "path": "/labels/-", "value": 3 },
+            { "op": "remove", "path": "/labels/0" },
+        ]))
+        .apply(&document)
+        .unwrap();
+        assert_eq!(patched, json!({ "text": "b", "labels": [2, 3] }));
+    }
+
+    #[test]
+    fn should_reject_failed_tests_and_bad_paths() {
+        let document = json!({ "text": "a", "labels": [] });
+        let result =
+            patch(json!([{ "op": "test", "path": "/text", "value": "b" }])).apply(&document);
+        assert_eq!(result.unwrap_err().status(), StatusCode::CONFLICT);
+
+        let result = patch(json!([{ "op": "remove", "path": "/labels/0" }])).apply(&document);
+        assert_eq!(result.unwrap_err().status(), StatusCode::BAD_REQUEST);
+
+        let result =
+            patch(json!([{ "op": "replace", "path": "/due", "value": 1 }])).apply(&document);
+        assert_eq!(result.unwrap_err().status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -1,49 +1,215 @@
-use std::sync::Arc;
-
 use axum::{
-    extract::{Extension, Path},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
-    Json,
+    routing::{delete, get, post},
+    Json, Router,
 };
 use serde::{Deserialize, Serialize};
-use validator::Validate;
 
-use crate::repositories::label::{LabelRepository, UpdateLabel};
+use crate::{
+    events::DomainEvent,
+    repositories::{
+        label::{CreateLabel, LabelRepository, MergeLabel, UpdateLabel},
+        search::Search,
+        todo::TodoRepository,
+        user::UserRepository,
+        RepositoryError,
+    },
+    state::AppState,
+};
+
+use super::{error::ApiError, path_id::PathId, workspace::WorkspaceScope, ValidatedJson};
 
-use super::ValidatedJson;
+pub fn label_routes<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+) -> Router<AppState<T, L, U>> {
+    Router::new()
+        .route(
+            "/labels",
+            post(create_label::<T, L, U>).get(all_label::<T, L, U>),
+        )
+        .route("/labels/stats", get(label_stats::<T, L, U>))
+        .route("/labels/cleanup", post(cleanup_labels::<T, L, U>))
+        .route("/labels/suggest", get(suggest_labels::<T, L, U>))
+        .route("/labels/:id/merge", post(merge_label::<T, L, U>))
+        .route(
+            "/labels/:id",
+            delete(delete_label::<T, L, U>).patch(update_label::<T, L, U>),
+        )
+}
 
-pub async fn create_label<T: LabelRepository>(
+pub async fn create_label<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
     ValidatedJson(payload): ValidatedJson<CreateLabel>,
-    Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let label = repository
-        .create(payload.name)
-        .await
-        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+) -> Result<impl IntoResponse, ApiError> {
+    let labels = scope.labels(&state);
+    state.quotas.check_labels(&labels).await?;
+    let label = match labels.create(payload).await {
+        Ok(label) => label,
+        Err(e) => return Err(with_existing(&labels, e).await),
+    };
+    state
+        .events
+        .publish(scope.id, DomainEvent::LabelCreated(label.clone()))
+        .await;
 
     Ok((StatusCode::CREATED, Json(label)))
 }
 
-pub async fn all_label<T: LabelRepository>(
-    Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let all = repository.all().await.unwrap();
+/// A `Duplicate` answers 409 with the label that has the name, so clients
+/// can use it instead.
+async fn with_existing<L: LabelRepository>(labels: &L, error: anyhow::Error) -> ApiError {
+    let Some(RepositoryError::Duplicate(id)) = error.downcast_ref::<RepositoryError>() else {
+        return error.into();
+    };
+    match labels.find(*id).await {
+        Ok(existing) => ApiError::from(error).with_details(existing),
+        Err(_) => error.into(),
+    }
+}
+
+pub async fn all_label<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+) -> Result<impl IntoResponse, ApiError> {
+    let all = scope.labels(&state).all().await?;
     Ok((StatusCode::OK, Json(all)))
 }
 
-pub async fn delete_label<T: LabelRepository>(
-    Path(id): Path<i32>,
-    Extension(repository): Extension<Arc<T>>,
-) -> StatusCode {
-    repository
-        .delete(id)
-        .await
-        .map(|_| StatusCode::NO_CONTENT)
-        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+/// Every label with how many todos carry it.
+/// Suggestions when `limit` is not given.
+const DEFAULT_SUGGESTIONS: u32 = 10;
+const MAX_SUGGESTIONS: u32 = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct SuggestParams {
+    #[serde(default)]
+    pub q: String,
+    pub limit: Option<u32>,
+}
+
+/// Labels for a typeahead: those starting with `q` or close to it, most used
+/// first. An empty `q` suggests the most used labels.
+pub async fn suggest_labels<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Query(params): Query<SuggestParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let limit = match params.limit.unwrap_or(DEFAULT_SUGGESTIONS) {
+        limit @ 1..=MAX_SUGGESTIONS => limit,
+        _ => {
+            return Err(ApiError::bad_request(format!(
+                "limit must be between 1 and {}",
+                MAX_SUGGESTIONS
+            )))
+        }
+    };
+    let search = Search {
+        text: params.q.trim().to_string(),
+        threshold: state.config.search.similarity_threshold,
+    };
+    let suggestions = scope.todos(&state).suggest_labels(&search, limit).await?;
+    Ok((StatusCode::OK, Json(suggestions)))
+}
+
+pub async fn label_stats<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+) -> Result<impl IntoResponse, ApiError> {
+    let stats = scope.todos(&state).label_stats().await?;
+    Ok((StatusCode::OK, Json(stats)))
+}
+
+#[derive(Debug, Serialize)]
+pub struct CleanedUp {
+    pub deleted: Vec<i32>,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Validate)]
-pub struct CreateLabel {
-    name: String,
+/// Deletes the labels no todo carries.
+pub async fn cleanup_labels<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+) -> Result<impl IntoResponse, ApiError> {
+    let deleted = scope.todos(&state).delete_unused_labels().await?;
+    for id in &deleted {
+        state
+            .events
+            .publish(scope.id, DomainEvent::LabelDeleted(*id))
+            .await;
+    }
+
+    Ok((StatusCode::OK, Json(CleanedUp { deleted })))
+}
+
+#[derive(Debug, Serialize)]
+pub struct Merged {
+    pub target_id: i32,
+    /// Ids of the todos moved over.
+    pub todos: Vec<i32>,
+}
+
+/// Moves the label's todos to another label and deletes it, say to fold
+/// `Work` into `work`.
+pub async fn merge_label<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path(id): Path<PathId>,
+    ValidatedJson(payload): ValidatedJson<MergeLabel>,
+) -> Result<impl IntoResponse, ApiError> {
+    let id = scope.label_id(&state, id).await?;
+    if payload.target_id == id {
+        return Err(ApiError::bad_request("can not merge a label into itself"));
+    }
+    let moved = scope
+        .todos(&state)
+        .merge_labels(id, payload.target_id)
+        .await?;
+    state
+        .events
+        .publish(scope.id, DomainEvent::LabelDeleted(id))
+        .await;
+
+    Ok((
+        StatusCode::OK,
+        Json(Merged {
+            target_id: payload.target_id,
+            todos: moved.iter().map(|todo| todo.id).collect(),
+        }),
+    ))
+}
+
+pub async fn update_label<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path(id): Path<PathId>,
+    ValidatedJson(payload): ValidatedJson<UpdateLabel>,
+) -> Result<impl IntoResponse, ApiError> {
+    let id = scope.label_id(&state, id).await?;
+    let labels = scope.labels(&state);
+    let label = match labels.update(id, payload).await {
+        Ok(label) => label,
+        Err(e) => return Err(with_existing(&labels, e).await),
+    };
+    state
+        .events
+        .publish(scope.id, DomainEvent::LabelUpdated(label.clone()))
+        .await;
+
+    Ok((StatusCode::OK, Json(label)))
+}
+
+pub async fn delete_label<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path(id): Path<PathId>,
+) -> Result<StatusCode, ApiError> {
+    let id = scope.label_id(&state, id).await?;
+    scope.labels(&state).delete(id).await?;
+    state
+        .events
+        .publish(scope.id, DomainEvent::LabelDeleted(id))
+        .await;
+
+    Ok(StatusCode::NO_CONTENT)
 }
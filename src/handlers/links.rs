@@ -0,0 +1,120 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use axum::http::Method;
+use serde::Serialize;
+
+use super::workspace::WorkspaceScope;
+use crate::repositories::workspace::DEFAULT_WORKSPACE_ID;
+
+/// Where a client can go next, and with which method.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Link {
+    pub href: String,
+    pub method: String,
+}
+
+impl Link {
+    pub fn new(method: Method, href: impl Into<String>) -> Self {
+        Self {
+            href: href.into(),
+            method: method.to_string(),
+        }
+    }
+}
+
+pub type Links = BTreeMap<&'static str, Link>;
+
+/// A response body with a `_links` section beside its own members.
+#[derive(Debug, Serialize)]
+pub struct Linked<T> {
+    #[serde(flatten)]
+    pub body: T,
+    #[serde(rename = "_links")]
+    pub links: Links,
+}
+
+impl<T> Linked<T> {
+    pub fn new(body: T, links: Links) -> Self {
+        Self { body, links }
+    }
+}
+
+/// The path of a route template such as `/todos/:id` in the scope's
+/// workspace; outside the default one it carries the `/workspaces/:id`
+/// prefix so the link works without the workspace header.
+pub fn expand(scope: &WorkspaceScope, template: &str, params: &[(&str, &dyn Display)]) -> String {
+    let path = template
+        .split('/')
+        .map(|segment| match segment.strip_prefix(':') {
+            Some(name) => params
+                .iter()
+                .find(|(param, _)| *param == name)
+                .map(|(_, value)| value.to_string())
+                .unwrap_or_else(|| segment.to_string()),
+            None => segment.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+    match scope.id {
+        DEFAULT_WORKSPACE_ID => path,
+        id => format!("/workspaces/{}{}", id, path),
+    }
+}
+
+/// `query` with `name` set to `value`, keeping every other parameter.
+pub fn with_param(query: Option<&str>, name: &str, value: &str) -> String {
+    query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty() && pair.split('=').next() != Some(name))
+        .chain([format!("{}={}", name, value).as_str()])
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// `query` without any of `names`.
+pub fn without_params(query: Option<&str>, names: &[&str]) -> String {
+    query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| {
+            !pair.is_empty() && !names.contains(&pair.split('=').next().unwrap_or_default())
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn scope(id: i32) -> WorkspaceScope {
+        WorkspaceScope {
+            id,
+            user: None,
+            role: None,
+            uuid_ids: false,
+        }
+    }
+
+    #[test]
+    fn should_expand_route_templates() {
+        assert_eq!(
+            expand(&scope(DEFAULT_WORKSPACE_ID), "/todos/:id", &[("id", &4)]),
+            "/todos/4"
+        );
+        assert_eq!(
+            expand(
+                &scope(2),
+                "/todos/:id/revisions/:rev",
+                &[("id", &4), ("rev", &1)]
+            ),
+            "/workspaces/2/todos/4/revisions/1"
+        );
+        assert_eq!(with_param(None, "after", "abc"), "after=abc");
+        assert_eq!(
+            with_param(Some("limit=5&after=old&assignee=me"), "after", "new"),
+            "limit=5&assignee=me&after=new"
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use axum::{
+    async_trait,
+    extract::FromRequest,
+    http::{header::CONTENT_TYPE, HeaderMap, Request, StatusCode},
+    BoxError,
+};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use super::{error::ApiError, parse_json, unprocessable};
+
+pub const MERGE_PATCH_JSON: &str = "application/merge-patch+json";
+
+pub fn is_merge_patch(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<mime::Mime>().ok())
+        .is_some_and(|mime| mime.essence_str() == MERGE_PATCH_JSON)
+}
+
+/// An RFC 7386 patch document: members set a field, `null` removes it and
+/// absent members leave it untouched.
+#[derive(Debug)]
+pub struct MergePatch(Map<String, Value>);
+
+impl MergePatch {
+    /// `None` when the member is absent, `Some(None)` when it is `null`.
+    pub fn take<T: DeserializeOwned>(&mut self, key: &str) -> Result<Option<Option<T>>, ApiError> {
+        match self.0.remove(key) {
+            None => Ok(None),
+            Some(Value::Null) => Ok(Some(None)),
+            Some(value) => serde_json::from_value(value)
+                .map(Some)
+                .map_err(|e| unprocessable(key, e)),
+        }
+    }
+
+    /// Rejects members no `take` asked for.
+    pub fn finish(self) -> Result<(), ApiError> {
+        match self.0.keys().next() {
+            Some(key) => Err(ApiError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("Json parse error: [unknown field `{}`]", key),
+            )
+            .with_details(serde_json::json!({ "path": key }))),
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl<S, B> FromRequest<S, B> for MergePatch
+where
+    S: Send + Sync,
+    B: http_body::Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let members: Map<String, Value> = parse_json(req, state).await?;
+
+        Ok(Self(members))
+    }
+}
@@ -0,0 +1,128 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{
+        header::{LOCATION, SET_COOKIE},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use serde::Deserialize;
+
+use crate::{
+    auth::{
+        oauth::{self, Provider},
+        random_token,
+    },
+    repositories::{label::LabelRepository, todo::TodoRepository, user::UserRepository},
+    state::AppState,
+};
+
+use super::{
+    auth::{account_disabled, start_session},
+    error::ApiError,
+};
+
+pub fn oauth_routes<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+) -> Router<AppState<T, L, U>> {
+    Router::new()
+        .route("/auth/oauth/:provider", get(authorize::<T, L, U>))
+        .route("/auth/oauth/:provider/callback", get(callback::<T, L, U>))
+}
+
+/// Sends the browser to the provider's consent screen.
+pub async fn authorize<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    Path(provider): Path<Provider>,
+) -> Result<impl IntoResponse, ApiError> {
+    let csrf_state = random_token();
+    let url = state
+        .oauth
+        .authorize_url(provider, &csrf_state)
+        .ok_or_else(|| not_configured(provider))?;
+    let location =
+        HeaderValue::from_str(url.as_str()).map_err(|_| ApiError::internal("Unexpected Error"))?;
+
+    Ok((
+        StatusCode::FOUND,
+        [
+            (LOCATION, location),
+            (
+                SET_COOKIE,
+                oauth::state_cookie(&csrf_state, &state.config.auth),
+            ),
+        ],
+    ))
+}
+
+/// Logs the user in with the provider's verified email, linking to or
+/// creating the account owning it, and lands on `oauth_success_url`.
+pub async fn callback<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    Path(provider): Path<Provider>,
+    Query(params): Query<CallbackParams>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    if !state.oauth.is_configured(provider) {
+        return Err(not_configured(provider));
+    }
+    if let Some(error) = params.error {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            format!("{} login was declined: {}", provider, error),
+        ));
+    }
+    let code = params
+        .code
+        .ok_or_else(|| ApiError::bad_request("missing authorization code"))?;
+    if params.state.is_none() || params.state.as_deref() != oauth::state(&headers) {
+        return Err(ApiError::bad_request("oauth state does not match"));
+    }
+
+    let email = state
+        .oauth
+        .verified_email(provider, &code)
+        .await
+        .map_err(|e| {
+            tracing::warn!("{} oauth exchange failed: {:?}", provider, e);
+            ApiError::new(
+                StatusCode::BAD_GATEWAY,
+                format!("{} did not complete the login", provider),
+            )
+        })?
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::FORBIDDEN,
+                format!("{} account has no verified email", provider),
+            )
+        })?;
+    let user = state
+        .user_repository
+        .find_or_create_by_email(&email)
+        .await?;
+    if state.user_repository.credentials(user.id).await?.disabled {
+        return Err(account_disabled());
+    }
+    let session_cookie = start_session(&state, user.id).await?;
+    let location = HeaderValue::from_str(&state.config.auth.oauth_success_url)
+        .map_err(|_| ApiError::internal("Unexpected Error"))?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(LOCATION, location);
+    headers.append(SET_COOKIE, session_cookie);
+    headers.append(SET_COOKIE, oauth::clear_state_cookie(&state.config.auth));
+    Ok((StatusCode::SEE_OTHER, headers))
+}
+
+fn not_configured(provider: Provider) -> ApiError {
+    ApiError::not_found(format!("oauth provider {} is not configured", provider))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackParams {
+    code: Option<String>,
+    state: Option<String>,
+    /// Set instead of `code` when the user declined.
+    error: Option<String>,
+}
@@ -0,0 +1,146 @@
+use axum::http::{HeaderName, HeaderValue};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+
+use super::error::ApiError;
+use crate::repositories::todo::PageCursor;
+
+pub const DEFAULT_LIMIT: u32 = 20;
+pub const MAX_LIMIT: u32 = 100;
+
+const CURSOR_PREFIX: &str = "id:";
+
+/// `before` value asking for the final page.
+pub const LAST_PAGE: &str = "last";
+
+/// How many items the whole listing holds, next to one page of it.
+pub static TOTAL_COUNT_HEADER: HeaderName = HeaderName::from_static("x-total-count");
+
+/// Set to `true` when an unpaged listing stopped at the server's cap.
+pub static TRUNCATED_HEADER: HeaderName = HeaderName::from_static("x-truncated");
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PageParams {
+    pub after: Option<String>,
+    pub before: Option<String>,
+    pub limit: Option<u32>,
+}
+
+impl PageParams {
+    pub fn is_requested(&self) -> bool {
+        self.after.is_some() || self.before.is_some() || self.limit.is_some()
+    }
+
+    pub fn limit(&self) -> Result<u32, ApiError> {
+        match self.limit.unwrap_or(DEFAULT_LIMIT) {
+            limit @ 1..=MAX_LIMIT => Ok(limit),
+            _ => Err(ApiError::bad_request(format!(
+                "limit must be between 1 and {}",
+                MAX_LIMIT
+            ))),
+        }
+    }
+
+    pub fn after(&self) -> Result<Option<i32>, ApiError> {
+        self.after.as_deref().map(decode_cursor).transpose()
+    }
+
+    /// `after` pages towards older items, `before` towards newer ones;
+    /// `before=last` is the final page.
+    pub fn cursor(&self) -> Result<PageCursor, ApiError> {
+        match (&self.after, self.before.as_deref()) {
+            (Some(_), Some(_)) => Err(ApiError::bad_request(
+                "after and before can not be combined",
+            )),
+            (_, None) => Ok(PageCursor::After(self.after()?)),
+            (None, Some(LAST_PAGE)) => Ok(PageCursor::Before(None)),
+            (None, Some(before)) => Ok(PageCursor::Before(Some(decode_cursor(before)?))),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    /// Only listings that page both ways set it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prev_cursor: Option<String>,
+}
+
+/// An RFC 8288 `Link` header value from `(rel, href)` pairs.
+pub fn link_header(links: &[(&str, String)]) -> HeaderValue {
+    let value = links
+        .iter()
+        .map(|(rel, href)| format!("<{}>; rel=\"{}\"", href, rel))
+        .collect::<Vec<_>>()
+        .join(", ");
+    HeaderValue::from_str(&value).expect("links are built from header safe parts")
+}
+
+/// Cursors are opaque to clients so the key they encode can change later.
+pub fn encode_cursor(id: i32) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{}{}", CURSOR_PREFIX, id))
+}
+
+pub fn decode_cursor(cursor: &str) -> Result<i32, ApiError> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .and_then(|value| value.strip_prefix(CURSOR_PREFIX)?.parse().ok())
+        .ok_or_else(|| ApiError::bad_request("invalid cursor"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_round_trip_cursor() {
+        let cursor = encode_cursor(42);
+        assert_eq!(decode_cursor(&cursor).unwrap(), 42);
+        assert!(decode_cursor("42").is_err());
+        assert!(decode_cursor(&URL_SAFE_NO_PAD.encode("name:42")).is_err());
+    }
+
+    #[test]
+    fn should_read_cursor_direction() {
+        let params = |after: Option<&str>, before: Option<&str>| PageParams {
+            after: after.map(str::to_string),
+            before: before.map(str::to_string),
+            limit: None,
+        };
+        let cursor = encode_cursor(7);
+
+        assert_eq!(
+            params(None, None).cursor().unwrap(),
+            PageCursor::After(None)
+        );
+        assert_eq!(
+            params(Some(&cursor), None).cursor().unwrap(),
+            PageCursor::After(Some(7))
+        );
+        assert_eq!(
+            params(None, Some(&cursor)).cursor().unwrap(),
+            PageCursor::Before(Some(7))
+        );
+        assert_eq!(
+            params(None, Some("last")).cursor().unwrap(),
+            PageCursor::Before(None)
+        );
+        assert!(params(Some(&cursor), Some(&cursor)).cursor().is_err());
+    }
+
+    #[test]
+    fn should_format_link_header() {
+        let header = link_header(&[
+            ("next", "/todos?after=abc".to_string()),
+            ("last", "/todos?before=last".to_string()),
+        ]);
+        assert_eq!(
+            header,
+            r#"</todos?after=abc>; rel="next", </todos?before=last>; rel="last""#
+        );
+    }
+}
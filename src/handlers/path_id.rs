@@ -0,0 +1,78 @@
+use std::{fmt, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer};
+use uuid::Uuid;
+
+/// A todo or label as a path names it: by its serial id or by its `uuid`.
+/// `WorkspaceScope::todo_id` and `label_id` turn it into the id the
+/// repositories take. Serial ids below 1 or past what an `integer` column
+/// holds are turned away here, so they never reach a repository.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathId {
+    Serial(i32),
+    Uuid(Uuid),
+}
+
+impl FromStr for PathId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(id) = s.parse::<i32>() {
+            if id < 1 {
+                return Err(format!("{} is not an id, ids start at 1", s));
+            }
+            return Ok(Self::Serial(id));
+        }
+        if let Ok(uuid) = s.parse() {
+            return Ok(Self::Uuid(uuid));
+        }
+        if s.bytes().all(|b| b.is_ascii_digit()) && !s.is_empty() {
+            return Err(format!("{} is too large for an id", s));
+        }
+        Err(format!("{} is neither an id nor a uuid", s))
+    }
+}
+
+impl<'de> Deserialize<'de> for PathId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl fmt::Display for PathId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Serial(id) => id.fmt(f),
+            Self::Uuid(uuid) => uuid.fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_parse_serial_ids_and_uuids() {
+        assert_eq!("12".parse::<PathId>(), Ok(PathId::Serial(12)));
+        let uuid = Uuid::now_v7();
+        assert_eq!(uuid.to_string().parse::<PathId>(), Ok(PathId::Uuid(uuid)));
+        assert!("twelve".parse::<PathId>().is_err());
+    }
+
+    #[test]
+    fn should_reject_ids_no_row_can_have() {
+        assert_eq!(
+            "0".parse::<PathId>(),
+            Err("0 is not an id, ids start at 1".to_string())
+        );
+        assert!("-3".parse::<PathId>().is_err());
+        assert_eq!(
+            "2147483648".parse::<PathId>(),
+            Err("2147483648 is too large for an id".to_string())
+        );
+        assert_eq!("2147483647".parse::<PathId>(), Ok(PathId::Serial(i32::MAX)));
+    }
+}
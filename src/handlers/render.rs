@@ -0,0 +1,77 @@
+use pulldown_cmark::{html, Options, Parser};
+use serde::Deserialize;
+
+use super::error::ApiError;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RenderParams {
+    /// `render=html` adds Markdown fields rendered to HTML.
+    pub render: Option<String>,
+}
+
+/// How Markdown fields are sent besides their source.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Render {
+    #[default]
+    Source,
+    Html,
+}
+
+impl Render {
+    const FORMATS: &'static [&'static str] = &["html"];
+
+    pub fn from_params(params: &RenderParams) -> Result<Self, ApiError> {
+        match params.render.as_deref().map(str::trim) {
+            None | Some("") => Ok(Self::Source),
+            Some("html") => Ok(Self::Html),
+            Some(format) => Err(ApiError::bad_request(format!(
+                "cannot render [{}], expected one of [{}]",
+                format,
+                Self::FORMATS.join(", ")
+            ))),
+        }
+    }
+}
+
+/// `markdown` as HTML that is safe to insert into a page: raw HTML in the
+/// source survives only as far as ammonia's allow-list goes, so scripts,
+/// event handlers and `javascript:` links are dropped. Task lists are left
+/// out, as their checkboxes would not survive that; todos have checklists.
+pub fn to_html(markdown: &str) -> String {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, Parser::new_ext(markdown, options));
+    ammonia::clean(&unsafe_html)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_render_sanitized_html() {
+        assert_eq!(
+            to_html("# Plan\n\n- **buy** ~~bread~~ milk"),
+            "<h1>Plan</h1>\n<ul>\n<li><strong>buy</strong> <del>bread</del> milk</li>\n</ul>\n"
+        );
+        assert_eq!(
+            to_html(
+                "<script>alert(1)</script>\n\n[x](javascript:alert(1)) <b onclick=\"x()\">b</b>"
+            ),
+            "\n<p><a rel=\"noopener noreferrer\">x</a> <b>b</b></p>\n"
+        );
+    }
+
+    #[test]
+    fn should_accept_html_only() {
+        let params = |render: &str| RenderParams {
+            render: Some(render.to_string()),
+        };
+        assert_eq!(
+            Render::from_params(&RenderParams::default()).unwrap(),
+            Render::Source
+        );
+        assert_eq!(Render::from_params(&params("html")).unwrap(), Render::Html);
+        assert!(Render::from_params(&params("pdf")).is_err());
+    }
+}
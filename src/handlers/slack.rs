@@ -0,0 +1,172 @@
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth::unauthorized,
+    repositories::{
+        label::LabelRepository, slack::SetSlackLink, todo::TodoRepository, user::UserRepository,
+    },
+    slack::verify_signature,
+    state::AppState,
+};
+
+use super::{
+    chat::{self, chat_scope},
+    error::ApiError,
+    workspace::WorkspaceScope,
+    ValidatedJson,
+};
+
+const USAGE: &str = "Usage: /todo add <text>";
+
+/// A user's Slack link per workspace, and the `/todo` slash command Slack
+/// posts to `/slack/commands` on behalf of linked Slack users.
+pub fn slack_routes<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+) -> Router<AppState<T, L, U>> {
+    Router::new()
+        .route(
+            "/slack/link",
+            get(find_link::<T, L, U>)
+                .put(set_link::<T, L, U>)
+                .delete(delete_link::<T, L, U>),
+        )
+        .route("/slack/commands", post(slash_command::<T, L, U>))
+}
+
+fn user_id(scope: &WorkspaceScope) -> Result<i32, ApiError> {
+    Ok(scope.user.ok_or_else(unauthorized)?.id)
+}
+
+pub async fn find_link<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+) -> Result<impl IntoResponse, ApiError> {
+    let link = state
+        .user_repository
+        .slack_link(scope.id, user_id(&scope)?)
+        .await?
+        .ok_or_else(|| ApiError::not_found("no slack link in this workspace"))?;
+
+    Ok((StatusCode::OK, Json(link)))
+}
+
+/// `team_id` and `slack_user_id` come together or not at all.
+pub async fn set_link<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    ValidatedJson(payload): ValidatedJson<SetSlackLink>,
+) -> Result<impl IntoResponse, ApiError> {
+    if payload.team_id.is_some() != payload.slack_user_id.is_some() {
+        return Err(ApiError::bad_request(
+            "team_id and slack_user_id go together",
+        ));
+    }
+    let link = state
+        .user_repository
+        .set_slack_link(scope.id, user_id(&scope)?, payload)
+        .await?;
+
+    Ok((StatusCode::OK, Json(link)))
+}
+
+pub async fn delete_link<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+) -> Result<StatusCode, ApiError> {
+    state
+        .user_repository
+        .delete_slack_link(scope.id, user_id(&scope)?)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The fields of a slash command request we use.
+#[derive(Debug, Deserialize)]
+pub struct SlashCommand {
+    pub team_id: String,
+    pub user_id: String,
+    #[serde(default)]
+    pub text: String,
+}
+
+/// What Slack shows the caller, and only them.
+#[derive(Debug, Serialize)]
+pub struct SlashReply {
+    pub response_type: &'static str,
+    pub text: String,
+}
+
+impl SlashReply {
+    fn ephemeral(text: impl Into<String>) -> Self {
+        Self {
+            response_type: "ephemeral",
+            text: text.into(),
+        }
+    }
+}
+
+/// Answers the `/todo` command when the request carries a valid signature
+/// of the configured signing secret. Problems with the command itself are
+/// answered as replies, which Slack shows the user; 404 when no secret is
+/// configured.
+pub async fn slash_command<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, ApiError> {
+    let secret = state
+        .config
+        .slack
+        .signing_secret
+        .as_deref()
+        .ok_or_else(|| ApiError::not_found("slack commands are not configured"))?;
+    if !verify_signature(secret, &headers, &body, Utc::now()) {
+        return Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "invalid slack signature",
+        ));
+    }
+    let command: SlashCommand = serde_urlencoded::from_bytes(&body)
+        .map_err(|e| ApiError::bad_request(format!("Invalid slash command: {}", e)))?;
+
+    let reply = match command.text.trim().split_once(char::is_whitespace) {
+        Some(("add", text)) => add_todo(&state, &command, text.trim()).await?,
+        _ => SlashReply::ephemeral(USAGE),
+    };
+    Ok((StatusCode::OK, Json(reply)))
+}
+
+/// Creates the todo as the linked user in the link's workspace.
+async fn add_todo<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    state: &AppState<T, L, U>,
+    command: &SlashCommand,
+    text: &str,
+) -> Result<SlashReply, ApiError> {
+    let Some(link) = state
+        .user_repository
+        .slack_link_by_slack_user(&command.team_id, &command.user_id)
+        .await?
+    else {
+        return Ok(SlashReply::ephemeral(
+            "Link your Slack account first: PUT /slack/link with your team_id and slack_user_id",
+        ));
+    };
+    let Some(scope) = chat_scope(state, link.workspace_id, link.user_id).await? else {
+        return Ok(SlashReply::ephemeral(
+            "You can not add todos to this workspace",
+        ));
+    };
+
+    Ok(SlashReply::ephemeral(
+        chat::add_todo(state, &scope, text).await?,
+    ))
+}
@@ -0,0 +1,210 @@
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth::{csrf, random_token, refresh, unauthorized},
+    repositories::{
+        label::LabelRepository, telegram::NewTelegramCode, todo::TodoRepository,
+        user::UserRepository,
+    },
+    state::AppState,
+};
+
+use super::{
+    chat::{self, chat_scope},
+    error::ApiError,
+    workspace::WorkspaceScope,
+};
+
+/// How long a link code can be redeemed.
+const CODE_TTL_MINUTES: i64 = 10;
+
+const SECRET_HEADER: &str = "x-telegram-bot-api-secret-token";
+
+const HELP: &str = "/add <text> adds a todo\n\
+    /list shows the open todos\n\
+    /done <id> completes one\n\
+    /unlink stops this chat acting for you";
+
+/// Link codes for Telegram chats, and the webhook of the bot they talk to.
+pub fn telegram_routes<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+) -> Router<AppState<T, L, U>> {
+    Router::new()
+        .route("/telegram/codes", post(create_code::<T, L, U>))
+        .route("/telegram/webhook", post(webhook::<T, L, U>))
+}
+
+#[derive(Debug, Serialize)]
+pub struct TelegramCode {
+    pub code: String,
+    /// What to send the bot, e.g. through a `t.me/<bot>?start=<code>` link.
+    pub command: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A code that links the first chat sending it to the caller in this
+/// workspace; shown once.
+pub async fn create_code<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+) -> Result<impl IntoResponse, ApiError> {
+    let user = scope.user.ok_or_else(unauthorized)?;
+    let code = random_token();
+    let expires_at = Utc::now() + Duration::minutes(CODE_TTL_MINUTES);
+    state
+        .user_repository
+        .create_telegram_code(NewTelegramCode {
+            code_hash: refresh::hash(&code),
+            workspace_id: scope.id,
+            user_id: user.id,
+            expires_at,
+        })
+        .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(TelegramCode {
+            command: format!("/start {}", code),
+            code,
+            expires_at,
+        }),
+    ))
+}
+
+/// The parts of a Telegram update we use; other kinds of update carry no
+/// `message`.
+#[derive(Debug, Deserialize)]
+pub struct Update {
+    pub message: Option<Message>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Message {
+    pub chat: Chat,
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Chat {
+    pub id: i64,
+}
+
+/// A `sendMessage` call, made by answering the webhook with it.
+#[derive(Debug, Serialize)]
+pub struct SendMessage {
+    pub method: &'static str,
+    pub chat_id: i64,
+    pub text: String,
+}
+
+/// Answers chat commands sent to the bot, for updates carrying the
+/// configured secret; 404 when none is configured.
+pub async fn webhook<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    let secret = state
+        .config
+        .telegram
+        .webhook_secret
+        .as_deref()
+        .ok_or_else(|| ApiError::not_found("telegram is not configured"))?;
+    let given = headers
+        .get(SECRET_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if !csrf::matches(secret, given) {
+        return Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "invalid telegram secret token",
+        ));
+    }
+    let update: Update = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::bad_request(format!("Invalid telegram update: {}", e)))?;
+
+    let Some(Message {
+        chat,
+        text: Some(text),
+    }) = update.message
+    else {
+        return Ok(StatusCode::OK.into_response());
+    };
+    let text = answer(&state, chat.id, &text).await?;
+    Ok(Json(SendMessage {
+        method: "sendMessage",
+        chat_id: chat.id,
+        text,
+    })
+    .into_response())
+}
+
+/// The command and its argument; commands sent in groups may name the bot,
+/// as in `/add@todo_bot`.
+fn parse(text: &str) -> (&str, &str) {
+    let text = text.trim();
+    let (command, argument) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let command = command.split('@').next().unwrap_or(command);
+    (command, argument.trim())
+}
+
+async fn answer<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    state: &AppState<T, L, U>,
+    chat_id: i64,
+    text: &str,
+) -> Result<String, ApiError> {
+    let users = &state.user_repository;
+    let (command, argument) = parse(text);
+    if matches!(command, "/start" | "/link") && !argument.is_empty() {
+        let linked = users
+            .redeem_telegram_code(&refresh::hash(argument), chat_id)
+            .await?;
+        return Ok(match linked {
+            Some(_) => format!("This chat now acts for you.\n{}", HELP),
+            None => "That code is unknown or expired; ask for a new one".to_string(),
+        });
+    }
+    let Some(link) = users.telegram_link(chat_id).await? else {
+        return Ok(
+            "Link this chat first: send /start with a code from POST /telegram/codes".to_string(),
+        );
+    };
+    if command == "/unlink" {
+        users.delete_telegram_link(chat_id).await?;
+        return Ok("This chat no longer acts for you".to_string());
+    }
+    let Some(scope) = chat_scope(state, link.workspace_id, link.user_id).await? else {
+        return Ok("You can not change todos in this workspace".to_string());
+    };
+
+    match (command, argument) {
+        ("/add", text) if !text.is_empty() => chat::add_todo(state, &scope, text).await,
+        ("/list", _) => chat::list_todos(state, &scope).await,
+        ("/done", id) => match id.trim_start_matches('#').parse() {
+            Ok(id) => chat::complete_todo(state, &scope, id).await,
+            Err(_) => Ok("Usage: /done <id>".to_string()),
+        },
+        _ => Ok(HELP.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_parse_commands() {
+        assert_eq!(parse("/add  buy milk "), ("/add", "buy milk"));
+        assert_eq!(parse("/list@todo_bot"), ("/list", ""));
+        assert_eq!(parse("/done@todo_bot #3"), ("/done", "#3"));
+        assert_eq!(parse("hello"), ("hello", ""));
+    }
+}
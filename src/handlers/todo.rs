@@ -1,69 +1,892 @@
-use std::sync::Arc;
+use std::collections::BTreeMap;
 
 use axum::{
-    extract::{Extension, Path},
-    http::StatusCode,
-    response::IntoResponse,
-    Json,
+    async_trait,
+    extract::{FromRequest, Path, Query, RawQuery, State},
+    handler::Handler,
+    http::{header::LINK, HeaderMap, HeaderValue, Method, Request, StatusCode},
+    middleware::from_fn,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    BoxError, Json, Router,
 };
 
-use crate::repositories::todo::{CreateTodo, TodoRepository, UpdateTodo};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
 
-use super::ValidatedJson;
+use crate::{
+    auth::unauthorized,
+    config::SearchConfig,
+    i18n::best_match,
+    middleware::etag::etag,
+    repositories::{
+        checklist::{CreateChecklistItem, Progress},
+        filter::{FilterError, FilterExpr, Term},
+        label::{Label, LabelRepository},
+        search::Search,
+        todo::{CreateTodo, Todo, TodoFilter, TodoRepository, UpdateTodo},
+        user::UserRepository,
+        RepositoryError,
+    },
+    state::AppState,
+};
+
+use super::{
+    error::ApiError,
+    fields::{FieldsParams, Projection},
+    include::{Include, IncludeParams},
+    json_patch::{is_json_patch, JsonPatch},
+    links::{expand, with_param, without_params, Link, Linked, Links},
+    merge_patch::{is_merge_patch, MergePatch},
+    pagination::{
+        encode_cursor, link_header, Page, PageParams, LAST_PAGE, TOTAL_COUNT_HEADER,
+        TRUNCATED_HEADER,
+    },
+    path_id::PathId,
+    render::{self, Render, RenderParams},
+    validation_error,
+    workspace::WorkspaceScope,
+    ValidatedJson,
+};
+
+/// Fields a client may pick with `?fields=`; `_links` is dropped unless
+/// picked too.
+const TODO_FIELDS: &[&str] = &[
+    "id",
+    "uuid",
+    "text",
+    "completed",
+    "labels",
+    "owner_id",
+    "assignee_id",
+    "text_i18n",
+    "snoozed_until",
+    "estimate_minutes",
+    "due_date",
+    "description",
+    "description_html",
+    "custom",
+    "progress",
+    "highlights",
+    "_links",
+];
+
+/// Read representation of a todo; associations not asked for with
+/// `?include=` are referenced by id only.
+#[derive(Debug, Serialize)]
+pub struct TodoView {
+    pub id: i32,
+    #[serde(skip_serializing_if = "Uuid::is_nil")]
+    pub uuid: Uuid,
+    pub text: String,
+    pub completed: bool,
+    pub labels: Association<Label>,
+    pub owner_id: Option<i32>,
+    pub assignee_id: Option<i32>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub text_i18n: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snoozed_until: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimate_minutes: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_date: Option<NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// `description` as sanitized HTML; only with `?render=html`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description_html: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub custom: BTreeMap<String, serde_json::Value>,
+    #[serde(skip_serializing_if = "Progress::is_empty")]
+    pub progress: Progress,
+    /// `text` with what matched `?q=` between `<mark>` and `</mark>`; only
+    /// in search results.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub highlights: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum Association<T> {
+    Ids(Vec<i32>),
+    Embedded(Vec<T>),
+}
+
+impl TodoView {
+    pub fn new(todo: Todo, include: Include) -> Self {
+        let labels = if include.labels {
+            Association::Embedded(todo.labels)
+        } else {
+            Association::Ids(todo.labels.iter().map(|label| label.id).collect())
+        };
+        Self {
+            id: todo.id,
+            uuid: todo.uuid,
+            text: todo.text,
+            completed: todo.completed,
+            labels,
+            owner_id: todo.owner_id,
+            assignee_id: todo.assignee_id,
+            text_i18n: todo.text_i18n,
+            snoozed_until: todo.snoozed_until,
+            estimate_minutes: todo.estimate_minutes,
+            due_date: todo.due_date,
+            description: todo.description,
+            description_html: None,
+            custom: todo.custom,
+            progress: todo.progress,
+            highlights: None,
+        }
+    }
+
+    /// Adds the rendered description when `render` asks for HTML.
+    pub fn rendered(mut self, render: Render) -> Self {
+        if render == Render::Html {
+            self.description_html = self.description.as_deref().map(render::to_html);
+        }
+        self
+    }
+
+    /// Marks what in the untranslated text matched `search`.
+    pub fn highlighted(mut self, search: Option<&Search>) -> Self {
+        self.highlights = search.map(|search| search.highlight(&self.text));
+        self
+    }
+
+    /// `text` in the translation `Accept-Language` ranks highest, if any.
+    pub fn in_language(mut self, headers: &HeaderMap) -> Self {
+        if let Some(tag) = best_match(headers, self.text_i18n.keys().map(String::as_str)) {
+            self.text = self.text_i18n[tag].clone();
+        }
+        self
+    }
+}
+
+/// Narrowing `GET /todos`: `?assignee=` takes `me` or a user id,
+/// `?filter=` a filter expression such as
+/// `completed:false AND (label:home OR assignee:me)`, and `?q=` words to
+/// search for, forgiving typos; the best matches come first. Snoozed todos
+/// are left out unless `?include_snoozed=true`. `?custom.<name>=` keeps
+/// todos whose custom field holds the value; see `custom_filter`.
+#[derive(Debug, Default, Deserialize)]
+pub struct FilterParams {
+    pub assignee: Option<String>,
+    pub filter: Option<String>,
+    pub q: Option<String>,
+    pub include_snoozed: Option<bool>,
+}
+
+impl FilterParams {
+    pub(super) fn filter(
+        &self,
+        scope: &WorkspaceScope,
+        config: &SearchConfig,
+    ) -> Result<TodoFilter, ApiError> {
+        let user_id = scope.user.map(|user| user.id);
+        let assignee_id = match self.assignee.as_deref() {
+            None => None,
+            Some("me") => Some(user_id.ok_or_else(unauthorized)?),
+            Some(id) => Some(id.parse().map_err(|_| {
+                ApiError::bad_request(format!("invalid assignee [{}]: use `me` or a user id", id))
+            })?),
+        };
+        let expr = self
+            .filter
+            .as_deref()
+            .map(|filter| filter.parse::<FilterExpr>()?.resolve(user_id))
+            .transpose()
+            .map_err(invalid_filter)?;
+        let search = self
+            .q
+            .as_deref()
+            .map(str::trim)
+            .filter(|text| !text.is_empty())
+            .map(|text| Search {
+                text: text.to_string(),
+                threshold: config.similarity_threshold,
+            });
+        Ok(TodoFilter {
+            assignee_id,
+            expr,
+            search,
+            hide_snoozed: !self.include_snoozed.unwrap_or(false),
+        })
+    }
+}
+
+/// The `?custom.<name>=` terms of `query`, their values read as the
+/// workspace's definition of the field says, e.g. `?custom.sprint=12` as a
+/// number. Fields nobody defined are refused.
+async fn custom_filter<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    state: &AppState<T, L, U>,
+    scope: &WorkspaceScope,
+    query: Option<&str>,
+) -> Result<Vec<FilterExpr>, ApiError> {
+    let params: Vec<(String, String)> = serde_urlencoded::from_str(query.unwrap_or_default())
+        .map_err(|e| ApiError::bad_request(format!("invalid query: {}", e)))?;
+    let wanted: Vec<(String, String)> = params
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.strip_prefix("custom.")?.to_string(), value)))
+        .collect();
+    if wanted.is_empty() {
+        return Ok(Vec::new());
+    }
+    let fields = state.user_repository.custom_fields(scope.id).await?;
+    wanted
+        .into_iter()
+        .map(|(name, text)| {
+            let field = fields
+                .get(&name)
+                .ok_or_else(|| format!("unknown custom field [{}]", name))?;
+            Ok(FilterExpr::Term(Term::Custom(name, field.parse(&text)?)))
+        })
+        .collect::<Result<_, String>>()
+        .map_err(|problem| ApiError::bad_request(format!("invalid filter: {}", problem)))
+}
+
+pub fn invalid_filter(error: FilterError) -> ApiError {
+    match error {
+        FilterError::SignInRequired => unauthorized(),
+        error => ApiError::bad_request(format!("invalid filter: {}", error)),
+    }
+}
+
+pub const TODOS_ROUTE: &str = "/todos";
+pub const TODO_ROUTE: &str = "/todos/:id";
+
+/// Where a client can take `todo` from here.
+pub(super) fn todo_links(scope: &WorkspaceScope, todo: &Todo) -> Links {
+    let href = expand(
+        scope,
+        TODO_ROUTE,
+        &[("id", &scope.path_id(todo.id, todo.uuid))],
+    );
+    let toggle = if todo.completed { "reopen" } else { "complete" };
+    Links::from([
+        ("self", Link::new(Method::GET, href.clone())),
+        ("update", Link::new(Method::PATCH, href.clone())),
+        ("delete", Link::new(Method::DELETE, href.clone())),
+        ("clone", Link::new(Method::POST, format!("{}/clone", href))),
+        (toggle, Link::new(Method::PATCH, href)),
+    ])
+}
+
+pub fn todo_routes<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+) -> Router<AppState<T, L, U>> {
+    Router::new()
+        .route(
+            TODOS_ROUTE,
+            post(create_todo::<T, L, U>).get(all_todo::<T, L, U>.layer(from_fn(etag))),
+        )
+        .route("/todos/count", get(count_todo::<T, L, U>))
+        .route(
+            TODO_ROUTE,
+            get(find_todo::<T, L, U>.layer(from_fn(etag)))
+                .head(todo_exists::<T, L, U>)
+                .delete(delete_todo::<T, L, U>)
+                .patch(update_todo::<T, L, U>),
+        )
+        .route("/todos/:id/clone", post(clone_todo::<T, L, U>))
+        .route(
+            "/todos/:id/snooze",
+            post(snooze_todo::<T, L, U>).delete(wake_todo::<T, L, U>),
+        )
+}
+
+pub fn todo_revision_routes<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+) -> Router<AppState<T, L, U>> {
+    Router::new()
+        .route("/todos/:id/revisions", get(all_todo_revisions::<T, L, U>))
+        .route(
+            "/todos/:id/revisions/:rev/revert",
+            post(revert_todo::<T, L, U>),
+        )
+}
+
+/// `?detect_duplicates=` on `POST /todos`; `features.detect_duplicates`
+/// when left out.
+#[derive(Debug, Default, Deserialize)]
+pub struct CreateParams {
+    pub detect_duplicates: Option<bool>,
+}
 
-pub async fn create_todo<T: TodoRepository>(
-    ValidatedJson(payload): ValidatedJson<CreateTodo>,
-    Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let todo = repository
-        .create(payload)
-        .await
-        .or(Err(StatusCode::NOT_FOUND))?;
+/// With duplicate detection on, a todo repeating the text of an open one,
+/// case and spacing aside, is refused with 409 and the open one in
+/// `details`, so a client retrying a lost answer does not add it twice.
+pub async fn create_todo<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Query(params): Query<CreateParams>,
+    ValidatedJson(mut payload): ValidatedJson<CreateTodo>,
+) -> Result<impl IntoResponse, ApiError> {
+    if let Some(assignee_id) = payload.assignee_id {
+        scope.check_assignee(&state, assignee_id).await?;
+    }
+    scope.check_custom(&state, &payload.custom).await?;
+    payload.owner_id = scope.user.map(|user| user.id);
+    let todos = scope.todos(&state);
+    let detect_duplicates = params
+        .detect_duplicates
+        .unwrap_or(state.config.features.detect_duplicates);
+    if detect_duplicates {
+        if let Some(existing) = todos.find_open_duplicate(&payload.text).await? {
+            let links = todo_links(&scope, &existing);
+            return Err(ApiError::new(
+                StatusCode::CONFLICT,
+                RepositoryError::Duplicate(existing.id).to_string(),
+            )
+            .with_details(Linked::new(existing, links)));
+        }
+    }
+    state
+        .quotas
+        .check_open_todos(&todos, payload.owner_id)
+        .await?;
+    let todo = todos.create(payload).await?;
+    let links = todo_links(&scope, &todo);
+
+    Ok((StatusCode::CREATED, Json(Linked::new(todo, links))))
+}
 
-    Ok((StatusCode::CREATED, Json(todo)))
+/// `?checklist=true` on `POST /todos/:id/clone` copies the checklist too.
+#[derive(Debug, Default, Deserialize)]
+pub struct CloneParams {
+    pub checklist: Option<bool>,
 }
 
-pub async fn find_todo<T: TodoRepository>(
-    Path(id): Path<i32>,
-    Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let todo = repository.find(id).await.or(Err(StatusCode::NOT_FOUND))?;
+/// Copies the text, description, translations, labels, estimate and custom
+/// values of a todo into a new open one the caller owns, and with
+/// `?checklist=true` its checklist with every item unchecked. A copy repeats the text on purpose, so duplicate
+/// detection does not apply.
+pub async fn clone_todo<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path(id): Path<PathId>,
+    Query(params): Query<CloneParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let id = scope.todo_id(&state, id).await?;
+    let todos = scope.todos(&state);
+    let original = todos.find(id).await?;
+    let payload = CreateTodo {
+        labels: original.labels.iter().map(|label| label.id).collect(),
+        text_i18n: original.text_i18n,
+        estimate_minutes: original.estimate_minutes,
+        description: original.description,
+        custom: original.custom,
+        owner_id: scope.user.map(|user| user.id),
+        ..CreateTodo::new(original.text)
+    };
+    scope.check_custom(&state, &payload.custom).await?;
+    state
+        .quotas
+        .check_open_todos(&todos, payload.owner_id)
+        .await?;
+    let mut todo = todos.create(payload).await?;
+    if params.checklist.unwrap_or(false) {
+        for item in todos.checklist(id).await? {
+            todos
+                .add_item(todo.id, CreateChecklistItem::new(item.text))
+                .await?;
+        }
+        todo = todos.find(todo.id).await?;
+    }
+    let links = todo_links(&scope, &todo);
 
-    Ok((StatusCode::OK, Json(todo)))
+    Ok((StatusCode::CREATED, Json(Linked::new(todo, links))))
 }
 
-pub async fn all_todo<T: TodoRepository>(
-    Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let todos = repository.all().await.or(Err(StatusCode::NOT_FOUND))?;
+pub async fn find_todo<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path(id): Path<PathId>,
+    Query(fields): Query<FieldsParams>,
+    Query(include): Query<IncludeParams>,
+    Query(render): Query<RenderParams>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let id = scope.todo_id(&state, id).await?;
+    let projection = Projection::from_params(&fields, TODO_FIELDS)?;
+    let include = Include::from_params(&include)?;
+    let render = Render::from_params(&render)?;
+    let todo = scope.todos(&state).find(id).await?;
+    let links = todo_links(&scope, &todo);
 
-    Ok((StatusCode::OK, Json(todos)))
+    Ok((
+        StatusCode::OK,
+        Json(
+            projection.apply(&Linked::new(
+                TodoView::new(todo, include)
+                    .rendered(render)
+                    .in_language(&headers),
+                links,
+            ))?,
+        ),
+    ))
 }
 
-pub async fn update_todo<T: TodoRepository>(
-    Path(id): Path<i32>,
-    ValidatedJson(payload): ValidatedJson<UpdateTodo>,
-    Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    // let todo = repository
-    //     .update(id, payload)
-    //     .map_err(|_| StatusCode::NOT_FOUND)?;
+/// Answers 200 or 404 without loading or sending the todo.
+pub async fn todo_exists<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path(id): Path<PathId>,
+) -> Result<StatusCode, ApiError> {
+    let id = scope.todo_id(&state, id).await?;
+    if scope.todos(&state).exists(id).await? {
+        Ok(StatusCode::OK)
+    } else {
+        Ok(StatusCode::NOT_FOUND)
+    }
+}
+
+pub async fn count_todo<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+) -> Result<impl IntoResponse, ApiError> {
+    let count = scope.todos(&state).count().await?;
 
-    let todo = repository
-        .update(id, payload)
-        .await
-        .or(Err(StatusCode::NOT_FOUND))?;
+    Ok((StatusCode::OK, Json(serde_json::json!({ "count": count }))))
+}
 
-    Ok((StatusCode::OK, Json(todo)))
+/// Lists every todo, or one page of them when `after`, `before` or `limit`
+/// is given; see `FilterParams` for narrowing the list. Pages carry `Link`
+/// headers to their neighbours, and both forms the total in
+/// `X-Total-Count`.
+#[allow(clippy::too_many_arguments)]
+pub async fn all_todo<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Query(params): Query<PageParams>,
+    Query(fields): Query<FieldsParams>,
+    Query(include): Query<IncludeParams>,
+    Query(render): Query<RenderParams>,
+    Query(filter): Query<FilterParams>,
+    RawQuery(query): RawQuery,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let mut filter = filter.filter(&scope, &state.config.search)?;
+    let custom = custom_filter(&state, &scope, query.as_deref()).await?;
+    if !custom.is_empty() {
+        filter.expr = Some(FilterExpr::All(
+            filter.expr.into_iter().chain(custom).collect(),
+        ));
+    }
+    let search = filter.search.clone();
+    let todos = scope.todos(&state).with_filter(filter);
+    let href = expand(&scope, TODOS_ROUTE, &[]);
+    list_todos(
+        todos,
+        &scope,
+        href,
+        state.config.database.max_listed_todos,
+        &params,
+        &fields,
+        &include,
+        &render,
+        search.as_ref(),
+        query,
+        &headers,
+    )
+    .await
 }
 
-pub async fn delete_todo<T: TodoRepository>(
-    Path(id): Path<i32>,
-    Extension(repositories): Extension<Arc<T>>,
-) -> StatusCode {
-    repositories
-        .delete(id)
-        .await
-        .map(|_| StatusCode::NO_CONTENT)
-        .unwrap_or(StatusCode::NOT_FOUND)
+/// The body of `GET /todos` for `todos`, listed at `href`, with highlights
+/// when they were narrowed by `search`. Without paging parameters at most
+/// `max_listed` todos are returned, `0` meaning all of them.
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn list_todos<T: TodoRepository>(
+    todos: T,
+    scope: &WorkspaceScope,
+    href: String,
+    max_listed: u32,
+    params: &PageParams,
+    fields: &FieldsParams,
+    include: &IncludeParams,
+    render: &RenderParams,
+    search: Option<&Search>,
+    query: Option<String>,
+    headers: &HeaderMap,
+) -> Result<Response, ApiError> {
+    let projection = Projection::from_params(fields, TODO_FIELDS)?;
+    let include = Include::from_params(include)?;
+    let render = Render::from_params(render)?;
+    let view = |todo: Todo| {
+        let links = todo_links(scope, &todo);
+        projection.apply(&Linked::new(
+            TodoView::new(todo, include)
+                .rendered(render)
+                .highlighted(search)
+                .in_language(headers),
+            links,
+        ))
+    };
+    if !params.is_requested() {
+        let mut listed = todos.all().await?;
+        let mut total = listed.len() as i64;
+        // the repository may have stopped at the cap already, only the
+        // count tells whether todos were left out
+        let cap = max_listed as usize;
+        if cap > 0 && listed.len() >= cap {
+            total = todos.count().await?;
+            listed.truncate(cap);
+        }
+        let truncated = total > listed.len() as i64;
+        let listed = listed
+            .into_iter()
+            .map(view)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut res = (
+            StatusCode::OK,
+            [(TOTAL_COUNT_HEADER.clone(), HeaderValue::from(total))],
+            Json(listed),
+        )
+            .into_response();
+        if truncated {
+            res.headers_mut()
+                .insert(TRUNCATED_HEADER.clone(), HeaderValue::from_static("true"));
+        }
+        return Ok(res);
+    }
+
+    let (cursor, limit) = (params.cursor()?, params.limit()?);
+    let total = todos.count().await?;
+    let page = todos.page(cursor, limit).await?;
+    let page = Page {
+        items: page.items.into_iter().map(view).collect::<Result<_, _>>()?,
+        next_cursor: page.next.map(encode_cursor),
+        prev_cursor: page.prev.map(encode_cursor),
+    };
+    let mut links = Links::from([(
+        "self",
+        Link::new(
+            Method::GET,
+            match &query {
+                Some(query) => format!("{}?{}", href, query),
+                None => href.clone(),
+            },
+        ),
+    )]);
+    // other pages keep every parameter but the cursor, and name the limit
+    // so that even the first one stays paged
+    let base = with_param(
+        Some(&without_params(query.as_deref(), &["after", "before"])),
+        "limit",
+        &limit.to_string(),
+    );
+    let page_href = |cursor: Option<(&str, &str)>| match cursor {
+        Some((name, value)) => format!("{}?{}", href, with_param(Some(&base), name, value)),
+        None => format!("{}?{}", href, base),
+    };
+    let mut rels = vec![
+        ("first", page_href(None)),
+        ("last", page_href(Some(("before", LAST_PAGE)))),
+    ];
+    if let Some(cursor) = &page.next_cursor {
+        rels.push(("next", page_href(Some(("after", cursor)))));
+    }
+    if let Some(cursor) = &page.prev_cursor {
+        rels.push(("prev", page_href(Some(("before", cursor)))));
+    }
+    for (rel, href) in &rels {
+        links.insert(rel, Link::new(Method::GET, href.clone()));
+    }
+
+    Ok((
+        StatusCode::OK,
+        [
+            (LINK, link_header(&rels)),
+            (TOTAL_COUNT_HEADER.clone(), HeaderValue::from(total)),
+        ],
+        Json(Linked::new(page, links)),
+    )
+        .into_response())
+}
+
+/// Body of `PATCH /todos/:id`. With `application/json`, `null` members are
+/// ignored like absent ones; with `application/merge-patch+json` they remove
+/// the field, which only labels allow. Either way a `null` under `custom`
+/// removes that custom field. `application/json-patch+json`
+/// operations run against the lean `TodoView` of the current todo.
+#[derive(Debug)]
+pub enum TodoPatch {
+    Update(UpdateTodo),
+    Json(JsonPatch),
+}
+
+/// A todo after JSON Patch operations ran; every field must survive them.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PatchedTodo {
+    id: i32,
+    #[serde(default)]
+    uuid: Uuid,
+    text: String,
+    completed: bool,
+    labels: Vec<i32>,
+    owner_id: Option<i32>,
+    assignee_id: Option<i32>,
+    #[serde(default)]
+    text_i18n: BTreeMap<String, String>,
+    #[serde(default)]
+    snoozed_until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    estimate_minutes: Option<i32>,
+    #[serde(default)]
+    due_date: Option<NaiveDate>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    custom: BTreeMap<String, serde_json::Value>,
+    #[serde(default)]
+    progress: Progress,
+}
+
+#[async_trait]
+impl<S, B> FromRequest<S, B> for TodoPatch
+where
+    S: Send + Sync,
+    B: http_body::Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<BoxError>,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        if is_json_patch(req.headers()) {
+            return Ok(Self::Json(JsonPatch::from_request(req, state).await?));
+        }
+        if !is_merge_patch(req.headers()) {
+            let ValidatedJson(payload) = ValidatedJson::from_request(req, state).await?;
+            return Ok(Self::Update(payload));
+        }
+
+        let mut patch = MergePatch::from_request(req, state).await?;
+        let required = |field: &str| {
+            ApiError::bad_request(format!("Validation error: [{}: can not be null]", field))
+        };
+        let payload = UpdateTodo {
+            text: patch
+                .take("text")?
+                .map(|text| text.ok_or_else(|| required("text")))
+                .transpose()?,
+            completed: patch
+                .take("completed")?
+                .map(|completed| completed.ok_or_else(|| required("completed")))
+                .transpose()?,
+            labels: patch.take("labels")?.map(Option::unwrap_or_default),
+            assignee_id: patch.take("assignee_id")?,
+            text_i18n: patch.take("text_i18n")?.map(Option::unwrap_or_default),
+            estimate_minutes: patch.take("estimate_minutes")?,
+            due_date: patch.take("due_date")?,
+            description: patch.take("description")?,
+            custom: patch
+                .take("custom")?
+                .map(|custom| custom.ok_or_else(|| required("custom")))
+                .transpose()?,
+        };
+        patch.finish()?;
+        payload.validate().map_err(validation_error)?;
+
+        Ok(Self::Update(payload))
+    }
+}
+
+fn patched_payload(current: &Todo, patched: serde_json::Value) -> Result<UpdateTodo, ApiError> {
+    let patched: PatchedTodo = serde_json::from_value(patched)
+        .map_err(|e| ApiError::bad_request(format!("invalid patch result: [{}]", e)))?;
+    if patched.id != current.id || patched.uuid != current.uuid {
+        return Err(ApiError::bad_request("id can not be changed"));
+    }
+    if patched.owner_id != current.owner_id {
+        return Err(ApiError::bad_request("owner_id can not be changed"));
+    }
+    if patched.progress != current.progress {
+        return Err(ApiError::bad_request(
+            "progress can not be changed; change the checklist instead",
+        ));
+    }
+    if patched.snoozed_until != current.snoozed_until {
+        return Err(ApiError::bad_request(
+            "snoozed_until can not be changed; snooze the todo instead",
+        ));
+    }
+    let payload = UpdateTodo {
+        text: Some(patched.text),
+        completed: Some(patched.completed),
+        labels: Some(patched.labels),
+        assignee_id: Some(patched.assignee_id),
+        text_i18n: Some(patched.text_i18n),
+        estimate_minutes: Some(patched.estimate_minutes),
+        due_date: Some(patched.due_date),
+        description: Some(patched.description),
+        custom: Some(custom_changes(&current.custom, patched.custom)),
+    };
+    payload.validate().map_err(validation_error)?;
+
+    Ok(payload)
+}
+
+/// What sets `current` to `patched`: every patched value, and `None` for
+/// the fields it left out.
+fn custom_changes(
+    current: &BTreeMap<String, serde_json::Value>,
+    patched: BTreeMap<String, serde_json::Value>,
+) -> BTreeMap<String, Option<serde_json::Value>> {
+    let removed = current
+        .keys()
+        .filter(|name| !patched.contains_key(*name))
+        .map(|name| (name.clone(), None))
+        .collect::<Vec<_>>();
+    patched
+        .into_iter()
+        .map(|(name, value)| (name, Some(value)))
+        .chain(removed)
+        .collect()
+}
+
+pub async fn update_todo<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path(id): Path<PathId>,
+    patch: TodoPatch,
+) -> Result<impl IntoResponse, ApiError> {
+    let id = scope.todo_id(&state, id).await?;
+    let todos = scope.todos(&state);
+    let current = todos.find(id).await?;
+    scope.check_may_change(&state, &current).await?;
+    let (payload, expected) = match patch {
+        TodoPatch::Update(payload) => (payload, None),
+        TodoPatch::Json(patch) => {
+            let document = serde_json::to_value(TodoView::new(current.clone(), Include::default()))
+                .map_err(anyhow::Error::from)?;
+            (
+                patched_payload(&current, patch.apply(&document)?)?,
+                Some(&current),
+            )
+        }
+    };
+    if let Some(Some(assignee_id)) = payload.assignee_id {
+        scope.check_assignee(&state, assignee_id).await?;
+    }
+    if payload.custom.is_some() {
+        scope
+            .check_custom(&state, &payload.merged_custom(&current.custom))
+            .await?;
+    }
+    state
+        .quotas
+        .check_reopen(&todos, &current, payload.completed)
+        .await?;
+    let todo = match expected {
+        Some(expected) => todos.update_if(id, expected, payload).await?,
+        None => todos.update(id, payload).await?,
+    };
+    let links = todo_links(&scope, &todo);
+
+    Ok((StatusCode::OK, Json(Linked::new(todo, links))))
+}
+
+pub async fn delete_todo<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path(id): Path<PathId>,
+) -> Result<StatusCode, ApiError> {
+    let id = scope.todo_id(&state, id).await?;
+    let todos = scope.todos(&state);
+    scope
+        .check_may_change(&state, &todos.find(id).await?)
+        .await?;
+    todos.delete(id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Body of `POST /todos/:id/snooze`: either `until` a point in time or
+/// `minutes` from now.
+#[derive(Debug, Deserialize, Validate)]
+pub struct SnoozeTodo {
+    pub until: Option<DateTime<Utc>>,
+    #[validate(range(min = 1, max = 527040, message = "must be between 1 and 527040"))]
+    pub minutes: Option<u32>,
+}
+
+/// Hides the todo from `GET /todos` until the snooze is over; the scheduler
+/// wakes it then. Snoozing again moves the time.
+pub async fn snooze_todo<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path(id): Path<PathId>,
+    ValidatedJson(payload): ValidatedJson<SnoozeTodo>,
+) -> Result<impl IntoResponse, ApiError> {
+    let id = scope.todo_id(&state, id).await?;
+    let now = Utc::now();
+    let until = match (payload.until, payload.minutes) {
+        (Some(until), None) if until > now => until,
+        (Some(_), None) => return Err(ApiError::bad_request("until must be in the future")),
+        (None, Some(minutes)) => now + Duration::minutes(minutes.into()),
+        _ => return Err(ApiError::bad_request("give either until or minutes")),
+    };
+    let todos = scope.todos(&state);
+    scope
+        .check_may_change(&state, &todos.find(id).await?)
+        .await?;
+    let todo = todos.snooze(id, Some(until)).await?;
+    let links = todo_links(&scope, &todo);
+
+    Ok((StatusCode::OK, Json(Linked::new(todo, links))))
+}
+
+/// Ends a snooze early.
+pub async fn wake_todo<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path(id): Path<PathId>,
+) -> Result<impl IntoResponse, ApiError> {
+    let id = scope.todo_id(&state, id).await?;
+    let todos = scope.todos(&state);
+    scope
+        .check_may_change(&state, &todos.find(id).await?)
+        .await?;
+    let todo = todos.snooze(id, None).await?;
+    let links = todo_links(&scope, &todo);
+
+    Ok((StatusCode::OK, Json(Linked::new(todo, links))))
+}
+
+pub async fn all_todo_revisions<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path(id): Path<PathId>,
+) -> Result<impl IntoResponse, ApiError> {
+    let id = scope.todo_id(&state, id).await?;
+    let revisions = scope.todos(&state).revisions(id).await?;
+
+    Ok((StatusCode::OK, Json(revisions)))
+}
+
+pub async fn revert_todo<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path((id, rev)): Path<(PathId, i32)>,
+) -> Result<impl IntoResponse, ApiError> {
+    let id = scope.todo_id(&state, id).await?;
+    let todos = scope.todos(&state);
+    let current = todos.find(id).await?;
+    scope.check_may_change(&state, &current).await?;
+    let completed = todos
+        .revisions(id)
+        .await?
+        .into_iter()
+        .find(|revision| revision.rev == rev)
+        .map(|revision| revision.completed);
+    state
+        .quotas
+        .check_reopen(&todos, &current, completed)
+        .await?;
+    let todo = todos.revert(id, rev).await?;
+    let links = todo_links(&scope, &todo);
+
+    Ok((StatusCode::OK, Json(Linked::new(todo, links))))
 }
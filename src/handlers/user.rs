@@ -0,0 +1,446 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Path, State},
+    http::{
+        header::{CONTENT_DISPOSITION, LOCATION},
+        HeaderMap, StatusCode,
+    },
+    response::IntoResponse,
+    routing::{get, patch, post},
+    Extension, Json, Router,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::{
+    auth::{password, AuthUser},
+    middleware::forwarded::ClientInfo,
+    quota::Usage,
+    repositories::{
+        account::Account,
+        audit::{AuditAction, AuditEntry, NewAuditEntry},
+        digest::{week_end, Digest},
+        label::{Label, LabelRepository},
+        notification::{NotificationSettings, UpdateNotificationSettings},
+        todo::{Todo, TodoRepository},
+        user::UserRepository,
+        workspace::{Membership, DEFAULT_WORKSPACE_ID},
+    },
+    state::AppState,
+};
+
+use super::{error::ApiError, ValidatedJson};
+
+const MIN_PASSWORD_LEN: usize = 8;
+const MAX_PASSWORD_LEN: usize = 128;
+
+pub fn user_routes<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+) -> Router<AppState<T, L, U>> {
+    Router::new()
+        .route("/users", post(register_user::<T, L, U>))
+        .route(
+            "/me",
+            get(find_me::<T, L, U>).delete(schedule_deletion::<T, L, U>),
+        )
+        .route(
+            "/me/deletion",
+            get(scheduled_deletion::<T, L, U>).delete(cancel_deletion::<T, L, U>),
+        )
+        .route("/me/export", post(request_export::<T, L, U>))
+        .route("/me/exports/:id", get(find_export::<T, L, U>))
+        .route("/me/exports/:id/archive", get(download_export::<T, L, U>))
+        .route("/me/usage", get(usage::<T, L, U>))
+        .route("/me/digest", get(digest::<T, L, U>))
+        .route("/me/password", patch(change_password::<T, L, U>))
+        .route(
+            "/me/notifications",
+            get(notification_settings::<T, L, U>).patch(update_notification_settings::<T, L, U>),
+        )
+}
+
+pub async fn register_user<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    ValidatedJson(payload): ValidatedJson<RegisterUser>,
+) -> Result<impl IntoResponse, ApiError> {
+    check_password_policy(&payload.username, &payload.password)?;
+    let password_hash = password::hash(payload.password).await?;
+    let user = state
+        .user_repository
+        .create(payload.username, password_hash)
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(user)))
+}
+
+pub async fn find_me<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let user = state.user_repository.find(user.id).await?;
+    Ok((StatusCode::OK, Json(user)))
+}
+
+pub async fn notification_settings<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let settings = state.user_repository.notification_settings(user.id).await?;
+    Ok((StatusCode::OK, Json(settings)))
+}
+
+pub async fn update_notification_settings<
+    T: TodoRepository,
+    L: LabelRepository,
+    U: UserRepository,
+>(
+    State(state): State<AppState<T, L, U>>,
+    user: AuthUser,
+    ValidatedJson(payload): ValidatedJson<UpdateNotificationSettings>,
+) -> Result<impl IntoResponse, ApiError> {
+    let current = state.user_repository.notification_settings(user.id).await?;
+    let settings = payload.apply(current);
+    state
+        .user_repository
+        .set_notification_settings(user.id, settings)
+        .await?;
+
+    Ok((StatusCode::OK, Json(settings)))
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserUsage {
+    /// Open todos the user created, in any workspace.
+    pub open_todos: Usage,
+    /// Labels of the default workspace and of every workspace the user is a
+    /// member of.
+    pub workspaces: Vec<WorkspaceUsage>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorkspaceUsage {
+    pub workspace_id: i32,
+    pub labels: Usage,
+}
+
+pub async fn usage<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let open_todos = state
+        .quotas
+        .open_todos(&*state.todo_repository, user.id)
+        .await?;
+    let mut workspace_ids = vec![DEFAULT_WORKSPACE_ID];
+    for membership in state.user_repository.memberships(user.id).await? {
+        if !workspace_ids.contains(&membership.workspace.id) {
+            workspace_ids.push(membership.workspace.id);
+        }
+    }
+    let mut workspaces = Vec::with_capacity(workspace_ids.len());
+    for workspace_id in workspace_ids {
+        let labels = state.label_repository.in_workspace(workspace_id);
+        workspaces.push(WorkspaceUsage {
+            workspace_id,
+            labels: state.quotas.labels(&labels).await?,
+        });
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(UserUsage {
+            open_todos,
+            workspaces,
+        }),
+    ))
+}
+
+/// The caller's open todos that are overdue, due today or due later this
+/// week, assigned to them or created by them for no one else; for daily
+/// summary emails and dashboard widgets.
+pub async fn digest<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let today = Utc::now().date_naive();
+    let todos = state
+        .todo_repository
+        .due_by(user.id, week_end(today))
+        .await?;
+
+    Ok((StatusCode::OK, Json(Digest::of(today, todos))))
+}
+
+pub async fn change_password<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    user: AuthUser,
+    ValidatedJson(payload): ValidatedJson<ChangePassword>,
+) -> Result<StatusCode, ApiError> {
+    let credentials = state.user_repository.credentials(user.id).await?;
+    let verified = match credentials.password_hash {
+        Some(hash) => password::verify(payload.current_password, hash).await?,
+        None => false,
+    };
+    if !verified {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "current password is incorrect",
+        ));
+    }
+    check_password_policy(&credentials.username, &payload.new_password)?;
+    let password_hash = password::hash(payload.new_password).await?;
+    state
+        .user_repository
+        .update_password(user.id, password_hash)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScheduledDeletion {
+    pub delete_after: DateTime<Utc>,
+}
+
+/// Schedules the caller's account for deletion once `auth.deletion_grace_secs`
+/// have passed; asking again keeps the first date.
+pub async fn schedule_deletion<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let account = state.user_repository.account(user.id).await?;
+    let delete_after = match account.delete_after {
+        Some(delete_after) => delete_after,
+        None => {
+            let delete_after =
+                Utc::now() + Duration::seconds(state.config.auth.deletion_grace_secs as i64);
+            state
+                .user_repository
+                .schedule_deletion(user.id, Some(delete_after))
+                .await?;
+            state
+                .user_repository
+                .record_audit(NewAuditEntry::by_user(
+                    user.id,
+                    AuditAction::DeletionScheduled,
+                ))
+                .await?;
+            delete_after
+        }
+    };
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(ScheduledDeletion { delete_after }),
+    ))
+}
+
+pub async fn scheduled_deletion<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let account = state.user_repository.account(user.id).await?;
+    let delete_after = account.delete_after.ok_or_else(no_deletion_scheduled)?;
+    Ok((StatusCode::OK, Json(ScheduledDeletion { delete_after })))
+}
+
+pub async fn cancel_deletion<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    user: AuthUser,
+) -> Result<StatusCode, ApiError> {
+    let account = state.user_repository.account(user.id).await?;
+    account.delete_after.ok_or_else(no_deletion_scheduled)?;
+    state
+        .user_repository
+        .schedule_deletion(user.id, None)
+        .await?;
+    state
+        .user_repository
+        .record_audit(NewAuditEntry::by_user(
+            user.id,
+            AuditAction::DeletionCancelled,
+        ))
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn no_deletion_scheduled() -> ApiError {
+    ApiError::not_found("no deletion is scheduled")
+}
+
+/// Starts collecting everything stored about the caller; poll the export
+/// named by `Location` until it is `ready`, then fetch its archive.
+pub async fn request_export<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    user: AuthUser,
+    client: Option<Extension<ClientInfo>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    let export = state.user_repository.create_export(user.id).await?;
+    state
+        .user_repository
+        .record_audit(NewAuditEntry::by_user(
+            user.id,
+            AuditAction::ExportRequested,
+        ))
+        .await?;
+
+    let (todos, users, id) = (
+        state.todo_repository.clone(),
+        state.user_repository.clone(),
+        export.id,
+    );
+    tokio::spawn(async move {
+        let archive = match build_archive(&*todos, &*users, user.id).await {
+            Ok(archive) => Some(archive),
+            Err(e) => {
+                tracing::warn!("data export {} failed: {:?}", id, e);
+                None
+            }
+        };
+        if let Err(e) = users.finish_export(id, archive).await {
+            tracing::warn!("data export {} could not be stored: {:?}", id, e);
+        }
+    });
+
+    let location = format!("/me/exports/{}", export.id);
+    let location = match client {
+        Some(Extension(client)) => client.url(&headers, &location),
+        None => location,
+    };
+    Ok((StatusCode::ACCEPTED, [(LOCATION, location)], Json(export)))
+}
+
+pub async fn find_export<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    user: AuthUser,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, ApiError> {
+    let export = state.user_repository.data_export(user.id, id).await?;
+    Ok((StatusCode::OK, Json(export)))
+}
+
+pub async fn download_export<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    user: AuthUser,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, ApiError> {
+    let archive = state
+        .user_repository
+        .export_archive(user.id, id)
+        .await?
+        .ok_or_else(|| ApiError::new(StatusCode::CONFLICT, "export is not ready"))?;
+    let disposition = format!("attachment; filename=\"my-todo-export-{}.json\"", id);
+
+    Ok((
+        StatusCode::OK,
+        [(CONTENT_DISPOSITION, disposition)],
+        Json(archive),
+    ))
+}
+
+/// Everything stored about one user, as handed out by a data export.
+#[derive(Debug, Serialize)]
+pub struct Archive {
+    pub exported_at: DateTime<Utc>,
+    pub account: Account,
+    pub notification_settings: NotificationSettings,
+    pub memberships: Vec<Membership>,
+    /// Todos the user created, in any workspace.
+    pub todos: Vec<ArchivedTodo>,
+    /// Labels attached to those todos.
+    pub labels: Vec<Label>,
+    /// The user's audit log.
+    pub activity: Vec<AuditEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArchivedTodo {
+    pub workspace_id: i32,
+    #[serde(flatten)]
+    pub todo: Todo,
+}
+
+pub async fn build_archive<T: TodoRepository, U: UserRepository>(
+    todo_repository: &T,
+    user_repository: &U,
+    user_id: i32,
+) -> anyhow::Result<serde_json::Value> {
+    let todos = todo_repository.owned(user_id).await?;
+    let labels: BTreeMap<i32, Label> = todos
+        .iter()
+        .flat_map(|(_, todo)| todo.labels.iter())
+        .map(|label| (label.id, label.clone()))
+        .collect();
+    let archive = Archive {
+        exported_at: Utc::now(),
+        account: user_repository.account(user_id).await?,
+        notification_settings: user_repository.notification_settings(user_id).await?,
+        memberships: user_repository.memberships(user_id).await?,
+        todos: todos
+            .into_iter()
+            .map(|(workspace_id, todo)| ArchivedTodo { workspace_id, todo })
+            .collect(),
+        labels: labels.into_values().collect(),
+        activity: user_repository.audit_log(user_id).await?,
+    };
+
+    Ok(serde_json::to_value(archive)?)
+}
+
+/// 8 to 128 characters with at least one letter and one digit, and not
+/// containing the username.
+fn check_password_policy(username: &str, password: &str) -> Result<(), ApiError> {
+    let len = password.chars().count();
+    let problem = if len < MIN_PASSWORD_LEN {
+        Some(format!("must be at least {} characters", MIN_PASSWORD_LEN))
+    } else if len > MAX_PASSWORD_LEN {
+        Some(format!("can not be over {} characters", MAX_PASSWORD_LEN))
+    } else if !password.chars().any(char::is_alphabetic) {
+        Some("must contain a letter".to_string())
+    } else if !password.chars().any(|c| c.is_ascii_digit()) {
+        Some("must contain a digit".to_string())
+    } else if password.to_lowercase().contains(&username.to_lowercase()) {
+        Some("must not contain the username".to_string())
+    } else {
+        None
+    };
+
+    match problem {
+        Some(problem) => Err(ApiError::bad_request(format!(
+            "Validation error: [password: {}]",
+            problem
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Validate)]
+pub struct RegisterUser {
+    #[validate(length(min = 3, message = "must be at least 3 characters"))]
+    #[validate(length(max = 32, message = "can not be over 32"))]
+    username: String,
+    password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Validate)]
+pub struct ChangePassword {
+    current_password: String,
+    new_password: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_enforce_password_policy() {
+        assert!(check_password_policy("alice", "s3cure-enough").is_ok());
+        for password in ["sh0rt", "no digits here", "12345678", "Alice123x"] {
+            let err = check_password_policy("alice", password).unwrap_err();
+            assert_eq!(err.status(), StatusCode::BAD_REQUEST, "{}", password);
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use axum::{
+    extract::{Path, Query, RawQuery, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+
+use crate::{
+    auth::unauthorized,
+    repositories::{
+        filter::FilterExpr, label::LabelRepository, todo::TodoRepository, user::UserRepository,
+        view::CreateView,
+    },
+    state::AppState,
+};
+
+use super::{
+    error::ApiError,
+    fields::FieldsParams,
+    include::IncludeParams,
+    links::expand,
+    pagination::PageParams,
+    render::RenderParams,
+    todo::{invalid_filter, list_todos},
+    workspace::WorkspaceScope,
+    ValidatedJson,
+};
+
+pub const VIEW_TODOS_ROUTE: &str = "/views/:id/todos";
+
+/// Saved searches: a user's named filter expressions, per workspace.
+pub fn view_routes<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+) -> Router<AppState<T, L, U>> {
+    Router::new()
+        .route(
+            "/views",
+            post(create_view::<T, L, U>).get(all_views::<T, L, U>),
+        )
+        .route(
+            "/views/:id",
+            get(find_view::<T, L, U>).delete(delete_view::<T, L, U>),
+        )
+        .route(VIEW_TODOS_ROUTE, get(view_todos::<T, L, U>))
+}
+
+fn user_id(scope: &WorkspaceScope) -> Result<i32, ApiError> {
+    Ok(scope.user.ok_or_else(unauthorized)?.id)
+}
+
+pub async fn create_view<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    ValidatedJson(payload): ValidatedJson<CreateView>,
+) -> Result<impl IntoResponse, ApiError> {
+    let view = state
+        .user_repository
+        .create_view(scope.id, user_id(&scope)?, payload)
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(view)))
+}
+
+pub async fn all_views<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+) -> Result<impl IntoResponse, ApiError> {
+    let views = state
+        .user_repository
+        .views(scope.id, user_id(&scope)?)
+        .await?;
+
+    Ok((StatusCode::OK, Json(views)))
+}
+
+pub async fn find_view<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, ApiError> {
+    let view = state
+        .user_repository
+        .view(scope.id, user_id(&scope)?, id)
+        .await?;
+
+    Ok((StatusCode::OK, Json(view)))
+}
+
+pub async fn delete_view<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, ApiError> {
+    state
+        .user_repository
+        .delete_view(scope.id, user_id(&scope)?, id)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The todos matching a view's filter, listed and paged like `GET /todos`.
+#[allow(clippy::too_many_arguments)]
+pub async fn view_todos<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path(id): Path<i32>,
+    Query(params): Query<PageParams>,
+    Query(fields): Query<FieldsParams>,
+    Query(include): Query<IncludeParams>,
+    Query(render): Query<RenderParams>,
+    RawQuery(query): RawQuery,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let user_id = user_id(&scope)?;
+    let view = state.user_repository.view(scope.id, user_id, id).await?;
+    let filter = view
+        .filter
+        .parse::<FilterExpr>()
+        .and_then(|expr| expr.to_filter(Some(user_id)))
+        .map_err(invalid_filter)?;
+    let todos = scope.todos(&state).with_filter(filter);
+    let href = expand(&scope, VIEW_TODOS_ROUTE, &[("id", &id)]);
+    list_todos(
+        todos,
+        &scope,
+        href,
+        state.config.database.max_listed_todos,
+        &params,
+        &fields,
+        &include,
+        &render,
+        None,
+        query,
+        &headers,
+    )
+    .await
+}
@@ -0,0 +1,371 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Path, State},
+    http::{request::Parts, Method, StatusCode},
+    response::IntoResponse,
+    routing::{delete, patch, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{
+    auth::AuthUser,
+    middleware::workspace::{WorkspacePath, WORKSPACE_HEADER},
+    repositories::{
+        label::LabelRepository,
+        todo::{Todo, TodoRepository},
+        user::UserRepository,
+        workspace::{Role, UpdateWorkspace, DEFAULT_WORKSPACE_ID},
+    },
+    state::AppState,
+};
+
+use super::{error::ApiError, path_id::PathId, ValidatedJson};
+
+pub fn workspace_routes<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+) -> Router<AppState<T, L, U>> {
+    Router::new()
+        .route(
+            "/workspaces",
+            post(create_workspace::<T, L, U>).get(all_workspaces::<T, L, U>),
+        )
+        .route("/workspaces/:id", patch(update_workspace::<T, L, U>))
+        .route(
+            "/workspaces/:id/members",
+            post(set_member::<T, L, U>).get(all_members::<T, L, U>),
+        )
+        .route(
+            "/workspaces/:id/members/:user_id",
+            delete(remove_member::<T, L, U>),
+        )
+}
+
+/// The workspace a todo or label request runs in: the `/workspaces/:id`
+/// prefix, else the `X-Workspace-Id` header, else the default workspace.
+/// Outside the default workspace the caller must be a member, and only
+/// members who may write get past it with anything but GET or HEAD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkspaceScope {
+    pub id: i32,
+    /// The caller, when they sent credentials; always set outside the
+    /// default workspace.
+    pub user: Option<AuthUser>,
+    /// The caller's role; the default workspace has no members.
+    pub role: Option<Role>,
+    /// Todos and labels go by their `uuid` in paths and links, as
+    /// `features.uuid_ids` asks.
+    pub uuid_ids: bool,
+}
+
+impl WorkspaceScope {
+    /// Todos of the workspace, changed on behalf of the caller.
+    pub fn todos<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+        &self,
+        state: &AppState<T, L, U>,
+    ) -> T {
+        state
+            .todo_repository
+            .in_workspace(self.id)
+            .acting_as(self.user.map(|user| user.id))
+    }
+
+    pub fn labels<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+        &self,
+        state: &AppState<T, L, U>,
+    ) -> L {
+        state.label_repository.in_workspace(self.id)
+    }
+
+    /// The id of the todo a path names. Uuids must belong to a todo of the
+    /// workspace, and under `uuid_ids` serial ids find nothing.
+    pub async fn todo_id<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+        &self,
+        state: &AppState<T, L, U>,
+        id: PathId,
+    ) -> Result<i32, ApiError> {
+        let found = match id {
+            PathId::Serial(serial) => (!self.uuid_ids).then_some(serial),
+            PathId::Uuid(uuid) => self.todos(state).id_of(uuid).await?,
+        };
+        found.ok_or_else(|| path_not_found(id))
+    }
+
+    /// The id of the label a path names, as `todo_id` finds todos.
+    pub async fn label_id<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+        &self,
+        state: &AppState<T, L, U>,
+        id: PathId,
+    ) -> Result<i32, ApiError> {
+        let found = match id {
+            PathId::Serial(serial) => (!self.uuid_ids).then_some(serial),
+            PathId::Uuid(uuid) => self.labels(state).id_of(uuid).await?,
+        };
+        found.ok_or_else(|| path_not_found(id))
+    }
+
+    /// How links name a todo or label: by `uuid` under `uuid_ids`.
+    pub fn path_id(&self, id: i32, uuid: Uuid) -> PathId {
+        if self.uuid_ids && !uuid.is_nil() {
+            PathId::Uuid(uuid)
+        } else {
+            PathId::Serial(id)
+        }
+    }
+
+    /// Fails unless `assignee_id` is a user who belongs to the workspace;
+    /// everyone belongs to the default one.
+    pub async fn check_assignee<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+        &self,
+        state: &AppState<T, L, U>,
+        assignee_id: i32,
+    ) -> Result<(), ApiError> {
+        let belongs = if self.id == DEFAULT_WORKSPACE_ID {
+            state.user_repository.find(assignee_id).await.is_ok()
+        } else {
+            state
+                .user_repository
+                .role(self.id, assignee_id)
+                .await?
+                .is_some()
+        };
+        if !belongs {
+            return Err(ApiError::bad_request(format!(
+                "user {} can not be assigned in this workspace",
+                assignee_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Fails unless `custom` fits the custom fields the workspace defines.
+    pub async fn check_custom<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+        &self,
+        state: &AppState<T, L, U>,
+        custom: &BTreeMap<String, Value>,
+    ) -> Result<(), ApiError> {
+        state
+            .user_repository
+            .custom_fields(self.id)
+            .await?
+            .check(custom)
+            .map_err(|problem| {
+                ApiError::bad_request(format!("Validation error: [custom: {}]", problem))
+            })
+    }
+
+    /// Under `assignees_only`, fails unless the caller owns the todo, is its
+    /// assignee or administers the workspace.
+    pub async fn check_may_change<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+        &self,
+        state: &AppState<T, L, U>,
+        todo: &Todo,
+    ) -> Result<(), ApiError> {
+        if !state
+            .user_repository
+            .workspace(self.id)
+            .await?
+            .assignees_only
+        {
+            return Ok(());
+        }
+        let involved = self
+            .user
+            .is_some_and(|user| [todo.owner_id, todo.assignee_id].contains(&Some(user.id)));
+        if !involved && !self.role.is_some_and(Role::can_manage) {
+            return Err(ApiError::new(
+                StatusCode::FORBIDDEN,
+                "only the owner or assignee may change this todo",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<T, L, U> FromRequestParts<AppState<T, L, U>> for WorkspaceScope
+where
+    T: TodoRepository,
+    L: LabelRepository,
+    U: UserRepository,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState<T, L, U>,
+    ) -> Result<Self, Self::Rejection> {
+        let id = match parts.extensions.get::<WorkspacePath>() {
+            Some(WorkspacePath(id)) => *id,
+            None => match parts.headers.get(&WORKSPACE_HEADER) {
+                Some(value) => value
+                    .to_str()
+                    .ok()
+                    .and_then(|value| value.trim().parse().ok())
+                    .ok_or_else(|| ApiError::bad_request("invalid X-Workspace-Id header"))?,
+                None => DEFAULT_WORKSPACE_ID,
+            },
+        };
+        let uuid_ids = state.config.features.uuid_ids;
+        if id == DEFAULT_WORKSPACE_ID {
+            return Ok(Self {
+                id,
+                user: AuthUser::from_request_parts(parts, state).await.ok(),
+                role: None,
+                uuid_ids,
+            });
+        }
+
+        let user = AuthUser::from_request_parts(parts, state).await?;
+        // non-members learn nothing, not even that the workspace exists
+        let role = state
+            .user_repository
+            .role(id, user.id)
+            .await?
+            .ok_or_else(|| workspace_not_found(id))?;
+        let read_only = [Method::GET, Method::HEAD].contains(&parts.method);
+        if !read_only && !role.can_write() {
+            return Err(forbidden(role));
+        }
+
+        Ok(Self {
+            id,
+            user: Some(user),
+            role: Some(role),
+            uuid_ids,
+        })
+    }
+}
+
+fn workspace_not_found(id: i32) -> ApiError {
+    ApiError::not_found(format!("workspace {} not found", id))
+}
+
+/// Worded as the repositories word a missing id.
+fn path_not_found(id: PathId) -> ApiError {
+    ApiError::not_found(format!("NotFound, id is {}", id))
+}
+
+fn forbidden(role: Role) -> ApiError {
+    ApiError::new(
+        StatusCode::FORBIDDEN,
+        format!("a workspace {} may not do this", role),
+    )
+}
+
+/// Resolves to the caller's role, failing unless they may manage members.
+async fn manager_role<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    state: &AppState<T, L, U>,
+    workspace_id: i32,
+    user: AuthUser,
+) -> Result<Role, ApiError> {
+    let role = state
+        .user_repository
+        .role(workspace_id, user.id)
+        .await?
+        .ok_or_else(|| workspace_not_found(workspace_id))?;
+    if !role.can_manage() {
+        return Err(forbidden(role));
+    }
+    Ok(role)
+}
+
+pub async fn create_workspace<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    user: AuthUser,
+    ValidatedJson(payload): ValidatedJson<CreateWorkspace>,
+) -> Result<impl IntoResponse, ApiError> {
+    let workspace = state
+        .user_repository
+        .create_workspace(payload.name, user.id)
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(workspace)))
+}
+
+pub async fn update_workspace<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    user: AuthUser,
+    Path(id): Path<i32>,
+    ValidatedJson(payload): ValidatedJson<UpdateWorkspace>,
+) -> Result<impl IntoResponse, ApiError> {
+    manager_role(&state, id, user).await?;
+    let workspace = state.user_repository.update_workspace(id, payload).await?;
+
+    Ok((StatusCode::OK, Json(workspace)))
+}
+
+pub async fn all_workspaces<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    user: AuthUser,
+) -> Result<impl IntoResponse, ApiError> {
+    let memberships = state.user_repository.memberships(user.id).await?;
+    Ok((StatusCode::OK, Json(memberships)))
+}
+
+pub async fn all_members<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    user: AuthUser,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, ApiError> {
+    state
+        .user_repository
+        .role(id, user.id)
+        .await?
+        .ok_or_else(|| workspace_not_found(id))?;
+    let members = state.user_repository.members(id).await?;
+    Ok((StatusCode::OK, Json(members)))
+}
+
+/// Adds a member or changes their role. Only owners hand out or take away
+/// ownership.
+pub async fn set_member<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    user: AuthUser,
+    Path(id): Path<i32>,
+    ValidatedJson(payload): ValidatedJson<SetMember>,
+) -> Result<StatusCode, ApiError> {
+    let role = manager_role(&state, id, user).await?;
+    let current = state.user_repository.role(id, payload.user_id).await?;
+    if role != Role::Owner && (payload.role == Role::Owner || current == Some(Role::Owner)) {
+        return Err(forbidden(role));
+    }
+    state
+        .user_repository
+        .set_member(id, payload.user_id, payload.role)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn remove_member<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    user: AuthUser,
+    Path((id, user_id)): Path<(i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    let role = manager_role(&state, id, user).await?;
+    let current = state.user_repository.role(id, user_id).await?;
+    if role != Role::Owner && current == Some(Role::Owner) {
+        return Err(forbidden(role));
+    }
+    state.user_repository.remove_member(id, user_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Validate)]
+pub struct CreateWorkspace {
+    #[validate(length(min = 1, message = "can not be empty"))]
+    #[validate(length(max = 100, message = "can not be over 100"))]
+    name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Validate)]
+pub struct SetMember {
+    user_id: i32,
+    role: Role,
+}
@@ -0,0 +1,240 @@
+use axum::http::{header::ACCEPT_LANGUAGE, HeaderMap};
+
+/// The languages error and validation messages are available in. Messages
+/// are written in English; other locales translate them through a catalog.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    Ja,
+}
+
+/// English fragments and their Japanese translation. `{n}` stands for a
+/// number carried over as it is; longer fragments come first so they win
+/// over the shorter ones they start with.
+const JA: &[(&str, &str)] = &[
+    ("Validation error", "入力エラー"),
+    ("Json parse error", "JSONの解析エラー"),
+    ("Body parse error", "本文の解析エラー"),
+    ("Unexpected Error", "予期しないエラーが発生しました"),
+    ("Too Many Requests", "リクエストが多すぎます"),
+    ("Service Unavailable", "サービスを利用できません"),
+    ("Request Timeout", "リクエストがタイムアウトしました"),
+    ("can not be empty", "空にできません"),
+    ("can not be null", "nullにできません"),
+    ("can not be over {n} characters", "{n}文字以内にしてください"),
+    ("can not be over {n}", "{n}文字以内にしてください"),
+    ("must be at least {n} characters", "{n}文字以上にしてください"),
+    ("must contain a letter", "英字を含めてください"),
+    ("must contain a digit", "数字を含めてください"),
+    ("must not contain the username", "ユーザー名を含めないでください"),
+    ("until must be in the future", "untilには未来の日時を指定してください"),
+    ("give either until or minutes", "untilかminutesのどちらか一方を指定してください"),
+    ("can not merge a label into itself", "ラベルをそれ自身に統合することはできません"),
+    (
+        "must be a hex color such as #1e90ff",
+        "#1e90ffのような16進数の色にしてください",
+    ),
+    ("NotFound, id is", "見つかりません。id:"),
+    ("Duplicate data, id is", "重複しています。id:"),
+    ("Conflict, id {n} was modified concurrently", "id {n} は同時に更新されました"),
+    ("id can not be changed", "idは変更できません"),
+    ("no route for", "ルートがありません:"),
+    ("invalid cursor", "カーソルが不正です"),
+    ("invalid username or password", "ユーザー名またはパスワードが違います"),
+    ("missing or invalid access token", "アクセストークンがないか不正です"),
+    ("missing or invalid CSRF token", "CSRFトークンがないか不正です"),
+    ("only administrators may do this", "管理者のみが実行できます"),
+    (
+        "Content-Type must be application/json, application/msgpack or application/cbor",
+        "Content-Typeはapplication/json、application/msgpack、application/cborのいずれかにしてください",
+    ),
+];
+
+impl Locale {
+    pub fn tag(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Ja => "ja",
+        }
+    }
+
+    fn from_tag(tag: &str) -> Option<Self> {
+        // `ja-JP` and `ja` alike; only the primary subtag matters
+        let primary = tag.split('-').next()?.to_ascii_lowercase();
+        match primary.as_str() {
+            "en" => Some(Locale::En),
+            "ja" => Some(Locale::Ja),
+            _ => None,
+        }
+    }
+
+    /// The locale `Accept-Language` ranks highest, earlier tags winning
+    /// ties. Wildcards and headers naming nothing we speak mean English.
+    pub fn preferred(headers: &HeaderMap) -> Self {
+        let mut best = (Locale::En, 0.0);
+        for (tag, q) in language_ranges(headers) {
+            if let Some(locale) = Self::from_tag(tag) {
+                if q > best.1 {
+                    best = (locale, q);
+                }
+            }
+        }
+        best.0
+    }
+
+    /// `message` with every fragment the catalog knows translated; the
+    /// rest, such as field names and ids, is left alone.
+    pub fn translate(self, message: &str) -> String {
+        let catalog = match self {
+            Locale::En => return message.to_string(),
+            Locale::Ja => JA,
+        };
+        catalog
+            .iter()
+            .fold(message.to_string(), |message, (en, translated)| {
+                replace_fragment(&message, en, translated)
+            })
+    }
+}
+
+/// The tags of `Accept-Language` with their quality, in header order.
+fn language_ranges(headers: &HeaderMap) -> impl Iterator<Item = (&str, f32)> {
+    headers
+        .get_all(ACCEPT_LANGUAGE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|range| {
+            let mut params = range.split(';');
+            let tag = params.next().unwrap_or_default().trim();
+            let q = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (tag, q)
+        })
+}
+
+/// Of the language tags in `available`, the one `Accept-Language` ranks
+/// highest. A tag matches a range naming it exactly or sharing its primary
+/// subtag, so `ja-JP` finds `ja` and `en` finds `en-US`; wildcards match
+/// nothing.
+pub fn best_match<'a>(
+    headers: &HeaderMap,
+    available: impl IntoIterator<Item = &'a str> + Clone,
+) -> Option<&'a str> {
+    let primary = |tag: &str| {
+        tag.split('-')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase()
+    };
+    let mut best = None;
+    let mut best_q = 0.0;
+    for (range, q) in language_ranges(headers) {
+        if q <= best_q {
+            continue;
+        }
+        let found = available
+            .clone()
+            .into_iter()
+            .find(|tag| tag.eq_ignore_ascii_case(range))
+            .or_else(|| {
+                available
+                    .clone()
+                    .into_iter()
+                    .find(|tag| primary(tag) == primary(range))
+            });
+        if let Some(tag) = found {
+            best = Some(tag);
+            best_q = q;
+        }
+    }
+    best
+}
+
+fn replace_fragment(message: &str, en: &str, translated: &str) -> String {
+    let (prefix, suffix, numbered) = match en.split_once("{n}") {
+        Some((prefix, suffix)) => (prefix, suffix, true),
+        None => (en, "", false),
+    };
+    let mut out = String::new();
+    let mut rest = message;
+    while let Some(at) = rest.find(prefix) {
+        let after = &rest[at + prefix.len()..];
+        let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if numbered && (digits == 0 || !after[digits..].starts_with(suffix)) {
+            out.push_str(&rest[..at + prefix.len()]);
+            rest = after;
+            continue;
+        }
+        let n = if numbered { &after[..digits] } else { "" };
+        out.push_str(&rest[..at]);
+        out.push_str(&translated.replace("{n}", n));
+        rest = &after[n.len() + suffix.len()..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn accept_language(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT_LANGUAGE, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn should_pick_preferred_locale() {
+        assert_eq!(Locale::preferred(&HeaderMap::new()), Locale::En);
+        assert_eq!(Locale::preferred(&accept_language("*")), Locale::En);
+        assert_eq!(Locale::preferred(&accept_language("fr, ja-JP")), Locale::Ja);
+        assert_eq!(
+            Locale::preferred(&accept_language("ja;q=0.5, en-US")),
+            Locale::En
+        );
+    }
+
+    #[test]
+    fn should_match_available_languages() {
+        let available = ["en-US", "ja"];
+        assert_eq!(best_match(&HeaderMap::new(), available), None);
+        assert_eq!(best_match(&accept_language("*"), available), None);
+        assert_eq!(
+            best_match(&accept_language("ja-JP, en;q=0.5"), available),
+            Some("ja")
+        );
+        assert_eq!(
+            best_match(&accept_language("fr, en;q=0.8, ja;q=0.3"), available),
+            Some("en-US")
+        );
+    }
+
+    #[test]
+    fn should_translate_known_fragments() {
+        assert_eq!(
+            Locale::Ja.translate("Validation error: [text: can not be empty]"),
+            "入力エラー: [text: 空にできません]"
+        );
+        assert_eq!(
+            Locale::Ja.translate("Validation error: [password: can not be over 128 characters]"),
+            "入力エラー: [password: 128文字以内にしてください]"
+        );
+        assert_eq!(
+            Locale::Ja.translate("text: can not be over 100"),
+            "text: 100文字以内にしてください"
+        );
+        assert_eq!(
+            Locale::En.translate("NotFound, id is 4"),
+            "NotFound, id is 4"
+        );
+        assert_eq!(
+            Locale::Ja.translate("NotFound, id is 4"),
+            "見つかりません。id: 4"
+        );
+    }
+}
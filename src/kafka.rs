@@ -0,0 +1,205 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use apache_avro::{types::Record as AvroRecord, Schema};
+use axum::async_trait;
+use chrono::Utc;
+use rskafka::{
+    client::{
+        partition::{Compression, PartitionClient, UnknownTopicHandling},
+        ClientBuilder,
+    },
+    record::Record,
+};
+use serde_json::Value;
+
+use crate::{
+    config::{EventFormat, KafkaConfig},
+    events::{Subscriber, WorkspaceEvent},
+};
+
+/// Every event goes to this partition so consumers see them in order.
+const PARTITION: i32 = 0;
+
+/// The Avro form of a `WorkspaceEvent`; `data` holds the event's JSON data,
+/// as it would appear under `event.data` in the JSON form.
+const AVRO_SCHEMA: &str = r#"{
+    "type": "record",
+    "name": "WorkspaceEvent",
+    "namespace": "my_todo.events",
+    "fields": [
+        {"name": "workspace_id", "type": "int"},
+        {"name": "type", "type": "string"},
+        {"name": "todo_id", "type": ["null", "int"]},
+        {"name": "data", "type": "string"}
+    ]
+}"#;
+
+/// Encodes events in the configured `EventFormat`.
+#[derive(Debug, Clone)]
+pub enum Encoder {
+    Json,
+    /// Bare Avro datums without the schema; consumers use `AVRO_SCHEMA`.
+    Avro(Schema),
+}
+
+impl Encoder {
+    pub fn new(format: EventFormat) -> Self {
+        match format {
+            EventFormat::Json => Encoder::Json,
+            EventFormat::Avro => {
+                Encoder::Avro(Schema::parse_str(AVRO_SCHEMA).expect("valid avro schema"))
+            }
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Encoder::Json => "application/json",
+            Encoder::Avro(_) => "avro/binary",
+        }
+    }
+
+    pub fn encode(&self, event: &WorkspaceEvent) -> anyhow::Result<Vec<u8>> {
+        let schema = match self {
+            Encoder::Json => return Ok(serde_json::to_vec(event)?),
+            Encoder::Avro(schema) => schema,
+        };
+        let mut tagged = match serde_json::to_value(&event.event)? {
+            Value::Object(tagged) => tagged,
+            _ => unreachable!("events serialize to objects"),
+        };
+        let kind = match tagged.remove("type") {
+            Some(Value::String(kind)) => kind,
+            _ => unreachable!("events are tagged with their type"),
+        };
+        let data = tagged.remove("data").unwrap_or(Value::Null);
+
+        let mut record = AvroRecord::new(schema).context("avro schema is not a record")?;
+        record.put("workspace_id", event.workspace_id);
+        record.put("type", kind);
+        record.put("todo_id", event.event.todo_id());
+        record.put("data", data.to_string());
+        Ok(apache_avro::to_avro_datum(schema, record)?)
+    }
+}
+
+/// Publishes every event it handles to the configured topic, keyed by
+/// workspace id. With the postgres event backend every instance hears every
+/// event, so enable it on one instance only.
+pub struct KafkaPublisher {
+    partition: PartitionClient,
+    encoder: Encoder,
+}
+
+impl KafkaPublisher {
+    pub async fn connect(config: &KafkaConfig) -> anyhow::Result<Self> {
+        let brokers = config
+            .brokers
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(|broker| broker.trim().to_string())
+            .filter(|broker| !broker.is_empty())
+            .collect::<Vec<_>>();
+        anyhow::ensure!(!brokers.is_empty(), "no kafka brokers configured");
+        let client = ClientBuilder::new(brokers).build().await?;
+        let partition = client
+            .partition_client(config.topic.clone(), PARTITION, UnknownTopicHandling::Retry)
+            .await?;
+
+        Ok(Self {
+            partition,
+            encoder: Encoder::new(config.format),
+        })
+    }
+}
+
+#[async_trait]
+impl Subscriber for KafkaPublisher {
+    fn name(&self) -> &'static str {
+        "kafka"
+    }
+
+    async fn handle(&self, event: &WorkspaceEvent) -> anyhow::Result<()> {
+        let record = Record {
+            key: Some(event.workspace_id.to_string().into_bytes()),
+            value: Some(self.encoder.encode(event)?),
+            headers: BTreeMap::from([(
+                "content-type".to_string(),
+                self.encoder.content_type().as_bytes().to_vec(),
+            )]),
+            timestamp: Utc::now(),
+        };
+        self.partition
+            .produce(vec![record], Compression::NoCompression)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{events::DomainEvent, repositories::todo::Todo};
+    use apache_avro::types::Value as AvroValue;
+
+    fn completed() -> WorkspaceEvent {
+        WorkspaceEvent {
+            workspace_id: 3,
+            event: DomainEvent::TodoCompleted(Todo::new(7, "done".to_string())),
+        }
+    }
+
+    #[test]
+    fn should_encode_json() {
+        let event = completed();
+        let encoded = Encoder::new(EventFormat::Json).encode(&event).unwrap();
+        let decoded: WorkspaceEvent = serde_json::from_slice(&encoded).unwrap();
+        assert_eq!(decoded, event);
+    }
+
+    #[test]
+    fn should_encode_avro() {
+        let encoder = Encoder::new(EventFormat::Avro);
+        let schema = match &encoder {
+            Encoder::Avro(schema) => schema.clone(),
+            Encoder::Json => unreachable!(),
+        };
+        let event = completed();
+        let encoded = encoder.encode(&event).unwrap();
+        let decoded = apache_avro::from_avro_datum(&schema, &mut &encoded[..], None).unwrap();
+
+        let fields = match decoded {
+            AvroValue::Record(fields) => fields,
+            other => panic!("decoded {:?}", other),
+        };
+        assert_eq!(fields[0], ("workspace_id".to_string(), AvroValue::Int(3)));
+        assert_eq!(
+            fields[1],
+            (
+                "type".to_string(),
+                AvroValue::String("todo_completed".to_string())
+            )
+        );
+        assert_eq!(
+            fields[2],
+            (
+                "todo_id".to_string(),
+                AvroValue::Union(1, Box::new(AvroValue::Int(7)))
+            )
+        );
+        let data = match &fields[3].1 {
+            AvroValue::String(data) => data,
+            other => panic!("data {:?}", other),
+        };
+        let todo: Todo = serde_json::from_str(data).unwrap();
+        assert_eq!(todo.id, 7);
+
+        let deleted = WorkspaceEvent {
+            workspace_id: 3,
+            event: DomainEvent::LabelDeleted(2),
+        };
+        encoder.encode(&deleted).unwrap();
+    }
+}
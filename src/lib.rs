@@ -0,0 +1,3916 @@
+pub mod auth;
+pub mod cache;
+pub mod cli;
+pub mod config;
+pub mod database;
+pub mod discord;
+pub mod events;
+pub mod graphql;
+pub mod handlers;
+pub mod i18n;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+pub mod middleware;
+#[cfg(feature = "test-util")]
+pub mod mocks;
+#[cfg(feature = "nats")]
+pub mod nats;
+pub mod notifications;
+pub mod outbox;
+pub mod quota;
+pub mod reporting;
+pub mod repositories;
+pub mod server;
+pub mod slack;
+pub mod state;
+pub mod telemetry;
+#[cfg(test)]
+mod test_db;
+pub mod web;
+
+pub use crate::repositories::{
+    label::LabelRepository,
+    todo::{TodoReader, TodoRepository, TodoScope, TodoWriter},
+    user::UserRepository,
+};
+use auth::csrf::CSRF_HEADER;
+use axum::{
+    error_handling::HandleErrorLayer,
+    extract::DefaultBodyLimit,
+    middleware::{from_fn, from_fn_with_state},
+    routing::get,
+    Extension, Router,
+};
+use config::Config;
+use discord::DiscordNotifier;
+use events::Events;
+use graphql::graphql_routes;
+use handlers::{
+    admin::admin_routes,
+    auth::auth_routes,
+    board::board_routes,
+    burndown::burndown_routes,
+    calendar::calendar_routes,
+    checklist::checklist_routes,
+    custom_field::custom_field_routes,
+    email::email_routes,
+    fallback::{method_not_allowed, not_found, StaticFiles},
+    label::label_routes,
+    oauth::oauth_routes,
+    pagination::TOTAL_COUNT_HEADER,
+    slack::slack_routes,
+    telegram::telegram_routes,
+    todo::{todo_revision_routes, todo_routes},
+    user::user_routes,
+    view::view_routes,
+    workspace::workspace_routes,
+    JsonOptions,
+};
+use middleware::{
+    access_log::{access_log, record_route},
+    cache_control::{cache_control, CacheControl},
+    csrf::csrf,
+    forwarded::{forwarded, TrustedProxies},
+    localize::localize,
+    negotiate::negotiate,
+    overload::handle_overload_error,
+    panic::handle_panic,
+    rate_limit::{rate_limit, RateLimiter},
+    request_id::{request_id, REQUEST_ID_HEADER},
+    security_headers::{security_headers, SecurityHeaders},
+    workspace::{workspace_prefix, WORKSPACE_HEADER},
+};
+#[cfg(feature = "test-util")]
+pub use mocks::{MockLabelRepository, MockTodoRepository};
+use notifications::{AssignmentNotifier, LogDelivery, Notifier};
+use outbox::Outbox;
+use reporting::report_errors;
+use repositories::evented::EventedRepository;
+use slack::SlackDelivery;
+use state::AppState;
+use std::{sync::Arc, time::Duration};
+use tower::{limit::GlobalConcurrencyLimitLayer, ServiceBuilder};
+use web::web_routes;
+
+use hyper::{
+    header::{AUTHORIZATION, CONTENT_TYPE, ETAG, IF_NONE_MATCH, LINK},
+    Method,
+};
+use tower_http::{
+    catch_panic::CatchPanicLayer,
+    compression::CompressionLayer,
+    cors::{AllowOrigin, CorsLayer},
+};
+
+pub fn create_app<Todo: TodoRepository, Label: LabelRepository, User: UserRepository>(
+    todo_repository: Todo,
+    label_repository: Label,
+    user_repository: User,
+    config: Config,
+) -> Router {
+    create_app_with_events(
+        todo_repository,
+        label_repository,
+        user_repository,
+        config,
+        Events::default(),
+    )
+}
+
+/// Like `create_app`, publishing on `events` instead of an in-process bus.
+pub fn create_app_with_events<
+    Todo: TodoRepository,
+    Label: LabelRepository,
+    User: UserRepository,
+>(
+    todo_repository: Todo,
+    label_repository: Label,
+    user_repository: User,
+    config: Config,
+    events: Events,
+) -> Router {
+    create_app_with_state(app_state(
+        todo_repository,
+        label_repository,
+        user_repository,
+        config,
+        events,
+    ))
+}
+
+/// The state `create_app_with_events` serves, its background tasks already
+/// running; lets other entry points such as NATS share it.
+pub fn app_state<Todo: TodoRepository, Label: LabelRepository, User: UserRepository>(
+    todo_repository: Todo,
+    label_repository: Label,
+    user_repository: User,
+    config: Config,
+    events: Events,
+) -> AppState<EventedRepository<Todo>, Label, User> {
+    let outbox = Outbox::default();
+    let mut state = AppState::new(
+        EventedRepository::new(todo_repository, outbox.clone()),
+        label_repository,
+        user_repository,
+        config,
+    );
+    state.outbox = outbox;
+    state.events = events;
+    state.notifier = Notifier::new(Arc::new(SlackDelivery::new(
+        state.user_repository.clone(),
+        Arc::new(LogDelivery),
+    )));
+    state.events.attach(AssignmentNotifier::new(
+        state.notifier.clone(),
+        state.user_repository.clone(),
+    ));
+    if let Some(discord) = DiscordNotifier::from_config(&state.config.discord) {
+        state.events.attach(discord);
+    }
+    state
+        .outbox
+        .relay(state.todo_repository.clone(), state.events.clone());
+    state
+}
+
+pub fn create_app_with_state<Todo: TodoRepository, Label: LabelRepository, User: UserRepository>(
+    state: AppState<Todo, Label, User>,
+) -> Router {
+    let config = &state.config;
+    let allowed_origin = config.allowed_origin.parse().unwrap();
+    let revisions = config.features.revisions;
+    let http = config.http.clone();
+    let rate_limiter = config.rate_limit.enabled.then(|| {
+        RateLimiter::from_config(&config.rate_limit).expect("invalid rate limit settings")
+    });
+
+    let static_files = http.static_dir.as_deref().map(StaticFiles::new);
+    let headers = SecurityHeaders::from_config(&config.security_headers)
+        .expect("invalid security header settings");
+    let cache_policies =
+        CacheControl::from_config(&config.cache_control).expect("invalid cache control settings");
+    let proxies = TrustedProxies::new(http.trusted_proxies.clone(), config.tls.is_some());
+    let reporter = state.reporter.clone();
+
+    let mut router = Router::new();
+    if static_files.is_none() {
+        router = router.route("/", get(root));
+    }
+    router = router
+        .merge(todo_routes::<Todo, Label, User>())
+        .merge(checklist_routes::<Todo, Label, User>())
+        .merge(burndown_routes::<Todo, Label, User>())
+        .merge(board_routes::<Todo, Label, User>())
+        .merge(calendar_routes::<Todo, Label, User>())
+        .merge(label_routes::<Todo, Label, User>())
+        .merge(user_routes::<Todo, Label, User>())
+        .merge(view_routes::<Todo, Label, User>())
+        .merge(custom_field_routes::<Todo, Label, User>())
+        .merge(slack_routes::<Todo, Label, User>())
+        .merge(telegram_routes::<Todo, Label, User>())
+        .merge(email_routes::<Todo, Label, User>())
+        .merge(auth_routes::<Todo, Label, User>())
+        .merge(oauth_routes::<Todo, Label, User>())
+        .merge(workspace_routes::<Todo, Label, User>())
+        .merge(admin_routes::<Todo, Label, User>())
+        .merge(web_routes::<Todo, Label, User>())
+        .merge(graphql_routes(state.clone()));
+    if revisions {
+        router = router.merge(todo_revision_routes::<Todo, Label, User>());
+    }
+
+    router = match static_files {
+        Some(files) => router.fallback(move |req| files.clone().serve(req)),
+        None => router.fallback(not_found),
+    };
+    router = router
+        .layer(CatchPanicLayer::custom(handle_panic))
+        .layer(from_fn_with_state(http.max_body_bytes, csrf))
+        .layer(Extension(JsonOptions {
+            strict: http.strict_json,
+        }))
+        .layer(DefaultBodyLimit::max(http.max_body_bytes));
+    router = router.layer(from_fn(localize)).layer(from_fn(negotiate));
+    if http.compression {
+        router = router.layer(CompressionLayer::new());
+    }
+    if let Some(rate_limiter) = rate_limiter {
+        router = router.layer(from_fn_with_state(rate_limiter, rate_limit));
+    }
+    router = router.layer(
+        ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(handle_overload_error))
+            .load_shed()
+            .layer(GlobalConcurrencyLimitLayer::new(
+                http.max_concurrent_requests,
+            ))
+            .timeout(Duration::from_secs(http.request_timeout_secs)),
+    );
+    router = router
+        .layer(from_fn_with_state(cache_policies, cache_control))
+        .layer(from_fn(record_route));
+
+    let router = router.layer(
+        CorsLayer::new()
+            .allow_origin(AllowOrigin::exact(allowed_origin))
+            // credentials carry the session cookie; they rule out `*` methods
+            .allow_credentials(true)
+            .allow_methods(vec![
+                Method::GET,
+                Method::HEAD,
+                Method::POST,
+                Method::PATCH,
+                Method::DELETE,
+            ])
+            .allow_headers(vec![
+                AUTHORIZATION,
+                CONTENT_TYPE,
+                WORKSPACE_HEADER.clone(),
+                CSRF_HEADER.clone(),
+                REQUEST_ID_HEADER.clone(),
+                IF_NONE_MATCH,
+            ])
+            .expose_headers(vec![
+                REQUEST_ID_HEADER.clone(),
+                ETAG,
+                LINK,
+                TOTAL_COUNT_HEADER.clone(),
+            ]),
+    );
+
+    // served as the fallback of an empty router so the middleware sees the
+    // finished 405 including its `Allow` header
+    let mut app = Router::new()
+        .fallback_service(router.with_state(state))
+        .layer(from_fn(method_not_allowed))
+        .layer(from_fn(workspace_prefix));
+    if let Some(reporter) = reporter {
+        app = app.layer(from_fn_with_state(reporter, report_errors));
+    }
+    app.layer(from_fn_with_state(proxies, forwarded))
+        .layer(from_fn(access_log))
+        .layer(from_fn(request_id))
+        .layer(from_fn_with_state(headers, security_headers))
+}
+
+async fn root() -> &'static str {
+    "Hello, world!!"
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::events::{DomainEvent, WorkspaceEvent};
+    use crate::handlers::pagination::Page;
+    use crate::repositories::{
+        burndown::BurndownDay,
+        checklist::{ChecklistItem, Progress},
+        label::{CreateLabel, Label, LabelRepositoryForMemory},
+        todo::{CreateTodo, Todo, TodoRepositoryForMemory, TodoWriter, UpdateTodo},
+        user::UserRepositoryForMemory,
+    };
+    use axum::{body::Body, http::Request};
+    use axum::{extract::ConnectInfo, response::Response};
+    use std::net::SocketAddr;
+
+    use hyper::{header, header::HeaderValue, Method, StatusCode};
+    use tokio::sync::broadcast::Receiver;
+    use tower::ServiceExt;
+
+    fn build_todo_req_with_json(path: &str, method: Method, json_body: String) -> Request<Body> {
+        Request::builder()
+            .uri(path)
+            .method(method)
+            .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(json_body))
+            .unwrap()
+    }
+
+    fn build_todo_req_with_empty(path: &str, method: Method) -> Request<Body> {
+        Request::builder()
+            .uri(path)
+            .method(method)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    async fn res_to_string(res: Response) -> String {
+        let b = res.into_body();
+        let bytes = hyper::body::to_bytes(b).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    async fn res_to_todo(res: Response) -> Todo {
+        let body = res_to_string(res).await;
+        let todo: Todo = serde_json::from_str(&body).unwrap_or_else(|_| panic!("body: {}", body));
+        todo
+    }
+
+    #[tokio::test]
+    async fn should_created_todo() {
+        let expected = Todo::new(1, "should_created_todo".to_string());
+
+        let repository = TodoRepositoryForMemory::new();
+        let req = build_todo_req_with_json(
+            "/todos",
+            Method::POST,
+            r#"{"text": "should_created_todo" }"#.to_string(),
+        );
+
+        let res = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        let todo = res_to_todo(res).await;
+        assert!(!todo.uuid.is_nil());
+        assert_eq!(
+            Todo {
+                uuid: todo.uuid,
+                ..expected
+            },
+            todo
+        );
+    }
+
+    #[tokio::test]
+    async fn should_refuse_todos_with_unknown_labels() {
+        let labels = LabelRepositoryForMemory::new();
+        let label = labels
+            .create(CreateLabel::new("work".to_string()))
+            .await
+            .unwrap();
+        let repository = TodoRepositoryForMemory::with_labels(labels.clone());
+        let req = build_todo_req_with_json(
+            "/todos",
+            Method::POST,
+            format!(r#"{{"text": "report", "labels": [{}, 8, 5, 8]}}"#, label.id),
+        );
+        let res = create_app(
+            repository.clone(),
+            labels,
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(body["message"], "Unknown labels, ids are [5, 8]");
+        assert_eq!(body["details"]["missing_labels"], serde_json::json!([5, 8]));
+        assert!(repository.all().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn should_refuse_duplicate_todos_when_asked() {
+        let repository = TodoRepositoryForMemory::new();
+        repository
+            .create(CreateTodo::new("Buy milk".to_string()))
+            .await
+            .expect("failed create todo");
+        let mut config = Config::default();
+        config.features.detect_duplicates = true;
+        let app = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            config,
+        );
+        let create = |uri: &str| {
+            build_todo_req_with_json(uri, Method::POST, r#"{"text": " buy  MILK"}"#.to_string())
+        };
+
+        let res = app.clone().oneshot(create("/todos")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        let body: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(body["message"], "Duplicate data, id is 1");
+        assert_eq!(body["details"]["text"], "Buy milk");
+        assert_eq!(body["details"]["_links"]["self"]["href"], "/todos/1");
+
+        let res = app
+            .oneshot(create("/todos?detect_duplicates=false"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn should_find_todo() {
+        // repo作成
+        let repository = TodoRepositoryForMemory::new();
+        // repoから、Todoを作成
+        let created = repository
+            .create(CreateTodo::new("should_find_todo".to_string()))
+            .await
+            .expect("failed create todo");
+        // 期待値作成
+        let expected = Todo {
+            uuid: created.uuid,
+            ..Todo::new(1, "should_find_todo".to_string())
+        };
+        // リクエストを作成
+        let req = build_todo_req_with_empty("/todos/1", Method::GET);
+        // レスポンスを作成
+        let res = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        // レスポンスから、todoを生成
+        let todo = res_to_todo(res).await;
+        // expected
+        assert_eq!(expected, todo);
+    }
+
+    #[tokio::test]
+    async fn should_refuse_ids_no_todo_can_have() {
+        for id in ["0", "-1", "99999999999"] {
+            let req = build_todo_req_with_empty(&format!("/todos/{}", id), Method::GET);
+            let res = create_app(
+                TodoRepositoryForMemory::new(),
+                LabelRepositoryForMemory::new(),
+                UserRepositoryForMemory::new(),
+                Config::default(),
+            )
+            .oneshot(req)
+            .await
+            .unwrap();
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST, "/todos/{}", id);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_get_all_todos() {
+        let repository = TodoRepositoryForMemory::new();
+        let expected = repository
+            .create(CreateTodo::new("should_get_all_todos".to_string()))
+            .await
+            .expect("failed create todo");
+        let req = build_todo_req_with_empty("/todos", Method::GET);
+        let res = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        let body = res_to_string(res).await;
+        let todo: Vec<Todo> = serde_json::from_str(&body)
+            .unwrap_or_else(|_| panic!("connot convert TOdo instance. boy: {}", body));
+        assert_eq!(vec![expected], todo)
+    }
+
+    #[tokio::test]
+    async fn should_answer_not_modified_for_matching_etag() {
+        let repository = TodoRepositoryForMemory::new();
+        repository
+            .create(CreateTodo::new("should_use_etag".to_string()))
+            .await
+            .expect("failed create todo");
+        let app = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let get = |path: &str, etag: Option<&HeaderValue>| {
+            let mut req = build_todo_req_with_empty(path, Method::GET);
+            if let Some(etag) = etag {
+                req.headers_mut()
+                    .insert(header::IF_NONE_MATCH, etag.clone());
+            }
+            req
+        };
+
+        for path in ["/todos", "/todos/1"] {
+            let res = app.clone().oneshot(get(path, None)).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+            let etag = res.headers()[header::ETAG].clone();
+
+            let res = app.clone().oneshot(get(path, Some(&etag))).await.unwrap();
+            assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+            assert_eq!(res.headers()[header::ETAG], etag);
+            assert_eq!(res_to_string(res).await, "");
+        }
+
+        let before = app.clone().oneshot(get("/todos/1", None)).await.unwrap();
+        let etag = before.headers()[header::ETAG].clone();
+        let req = build_todo_req_with_json(
+            "/todos/1",
+            Method::PATCH,
+            r#"{ "completed": true }"#.to_string(),
+        );
+        app.clone().oneshot(req).await.unwrap();
+        let res = app.oneshot(get("/todos/1", Some(&etag))).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_ne!(res.headers()[header::ETAG], etag);
+    }
+
+    #[tokio::test]
+    async fn should_page_todos_with_cursor() {
+        let repository = TodoRepositoryForMemory::new();
+        for i in 1..=3 {
+            repository
+                .create(CreateTodo::new(format!("todo {}", i)))
+                .await
+                .expect("failed create todo");
+        }
+        let app = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+
+        let req = build_todo_req_with_empty("/todos?limit=2", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let page: Page<Todo> = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(page.items.len(), 2);
+        let cursor = page.next_cursor.expect("expected a next cursor");
+
+        let uri = format!("/todos?limit=2&after={}", cursor);
+        let req = build_todo_req_with_empty(&uri, Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let page: Page<Todo> = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].text, "todo 1");
+        assert_eq!(page.next_cursor, None);
+
+        let req = build_todo_req_with_empty("/todos?after=bogus", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn should_send_link_and_total_count_headers() {
+        let repository = TodoRepositoryForMemory::new();
+        for i in 1..=5 {
+            repository
+                .create(CreateTodo::new(format!("todo {}", i)))
+                .await
+                .expect("failed create todo");
+        }
+        let app = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let rels = |res: &Response| -> Vec<String> {
+            let header = res.headers()[header::LINK].to_str().unwrap();
+            header
+                .split(", ")
+                .map(|link| link.split("rel=").nth(1).unwrap().replace('"', ""))
+                .collect()
+        };
+
+        let req = build_todo_req_with_empty("/todos", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.headers()["x-total-count"], "5");
+        assert!(!res.headers().contains_key(header::LINK));
+
+        let req = build_todo_req_with_empty("/todos?limit=2&before=last", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.headers()["x-total-count"], "5");
+        assert_eq!(rels(&res), vec!["first", "last", "prev"]);
+        let link = res.headers()[header::LINK].to_str().unwrap().to_string();
+        assert!(link.starts_with(
+            r#"</todos?limit=2>; rel="first", </todos?limit=2&before=last>; rel="last""#
+        ));
+        let page: Page<Todo> = serde_json::from_str(&res_to_string(res).await).unwrap();
+        let ids: Vec<i32> = page.items.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![2, 1]);
+
+        let uri = format!(
+            "/todos?limit=2&before={}",
+            page.prev_cursor.expect("expected a prev cursor")
+        );
+        let req = build_todo_req_with_empty(&uri, Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(rels(&res), vec!["first", "last", "next", "prev"]);
+        let page: Page<Todo> = serde_json::from_str(&res_to_string(res).await).unwrap();
+        let ids: Vec<i32> = page.items.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![4, 3]);
+
+        let uri = format!("/todos?after=x&before={}", page.next_cursor.unwrap());
+        let req = build_todo_req_with_empty(&uri, Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn should_cap_unpaged_listings() {
+        let repository = TodoRepositoryForMemory::new();
+        for i in 1..=5 {
+            repository
+                .create(CreateTodo::new(format!("todo {}", i)))
+                .await
+                .expect("failed create todo");
+        }
+        let app = |max_listed_todos| {
+            let mut config = Config::default();
+            config.database.max_listed_todos = max_listed_todos;
+            create_app(
+                repository.clone(),
+                LabelRepositoryForMemory::new(),
+                UserRepositoryForMemory::new(),
+                config,
+            )
+        };
+
+        let req = build_todo_req_with_empty("/todos", Method::GET);
+        let res = app(3).oneshot(req).await.unwrap();
+        assert_eq!(res.headers()["x-total-count"], "5");
+        assert_eq!(res.headers()["x-truncated"], "true");
+        let todos: Vec<Todo> = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(todos.len(), 3);
+
+        for max_listed_todos in [5, 0] {
+            let req = build_todo_req_with_empty("/todos", Method::GET);
+            let res = app(max_listed_todos).oneshot(req).await.unwrap();
+            assert_eq!(res.headers()["x-total-count"], "5");
+            assert!(!res.headers().contains_key("x-truncated"));
+        }
+    }
+
+    #[tokio::test]
+    async fn should_filter_todos_by_expression() {
+        let labels = LabelRepositoryForMemory::new();
+        let home = labels
+            .create(CreateLabel::new("home".to_string()))
+            .await
+            .unwrap();
+        let repository = TodoRepositoryForMemory::with_labels(labels.clone());
+        for (text, labels, completed) in [
+            ("laundry", vec![home.id], false),
+            ("taxes", vec![], false),
+            ("dishes", vec![home.id], true),
+        ] {
+            let todo = repository
+                .create(CreateTodo {
+                    labels,
+                    ..CreateTodo::new(text.to_string())
+                })
+                .await
+                .expect("failed create todo");
+            if completed {
+                repository
+                    .update(
+                        todo.id,
+                        UpdateTodo {
+                            text: None,
+                            completed: Some(true),
+                            labels: None,
+                            assignee_id: None,
+                            text_i18n: None,
+                            estimate_minutes: None,
+                            due_date: None,
+                            description: None,
+                            custom: None,
+                        },
+                    )
+                    .await
+                    .unwrap();
+            }
+        }
+        let app = create_app(
+            repository,
+            labels,
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let texts = |body: String| -> Vec<String> {
+            let todos: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+            todos
+                .iter()
+                .map(|todo| todo["text"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        let uri =
+            "/todos?filter=completed%3Afalse%20AND%20(label%3Ahome%20OR%20NOT%20label%3Ahome)";
+        let req = build_todo_req_with_empty(uri, Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(texts(res_to_string(res).await), vec!["taxes", "laundry"]);
+
+        let req = build_todo_req_with_empty("/todos?filter=label%3Ahome&limit=1", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.headers()["x-total-count"], "2");
+
+        let req = build_todo_req_with_empty("/todos?filter=(label%3Ahome", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(body["message"], "invalid filter: `(` at 0 is never closed");
+
+        let req = build_todo_req_with_empty("/todos?filter=assignee%3Ame", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn should_search_todos_forgiving_typos() {
+        let repository = TodoRepositoryForMemory::new();
+        for text in ["buy groceries", "taxes", "grocries"] {
+            repository
+                .create(CreateTodo::new(text.to_string()))
+                .await
+                .expect("failed create todo");
+        }
+        let app = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+
+        let req = build_todo_req_with_empty("/todos?q=grocries", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.headers()["x-total-count"], "2");
+        let todos: Vec<serde_json::Value> =
+            serde_json::from_str(&res_to_string(res).await).unwrap();
+        let texts: Vec<&str> = todos
+            .iter()
+            .map(|todo| todo["text"].as_str().unwrap())
+            .collect();
+        assert_eq!(texts, vec!["grocries", "buy groceries"]);
+        assert_eq!(todos[1]["highlights"], "buy <mark>groceries</mark>");
+
+        let req = build_todo_req_with_empty("/todos?q=%20", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.headers()["x-total-count"], "3");
+        let todos: Vec<serde_json::Value> =
+            serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert!(todos.iter().all(|todo| todo.get("highlights").is_none()));
+    }
+
+    #[tokio::test]
+    async fn should_link_todos_to_their_actions() {
+        let repository = TodoRepositoryForMemory::new();
+        for i in 1..=3 {
+            repository
+                .create(CreateTodo::new(format!("todo {}", i)))
+                .await
+                .expect("failed create todo");
+        }
+        let app = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let link = |value: &serde_json::Value, rel: &str| -> Option<String> {
+            let link = &value["_links"][rel];
+            Some(format!(
+                "{} {}",
+                link["method"].as_str()?,
+                link["href"].as_str()?
+            ))
+        };
+
+        let req = build_todo_req_with_empty("/todos/1", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let todo: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(link(&todo, "self"), Some("GET /todos/1".to_string()));
+        assert_eq!(link(&todo, "update"), Some("PATCH /todos/1".to_string()));
+        assert_eq!(link(&todo, "delete"), Some("DELETE /todos/1".to_string()));
+        assert_eq!(
+            link(&todo, "clone"),
+            Some("POST /todos/1/clone".to_string())
+        );
+        assert_eq!(link(&todo, "complete"), Some("PATCH /todos/1".to_string()));
+        assert_eq!(link(&todo, "reopen"), None);
+
+        let req = build_todo_req_with_empty("/todos?limit=2", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let page: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(link(&page, "self"), Some("GET /todos?limit=2".to_string()));
+        assert_eq!(
+            link(&page, "next"),
+            Some(format!(
+                "GET /todos?limit=2&after={}",
+                page["next_cursor"].as_str().unwrap()
+            ))
+        );
+        assert_eq!(
+            link(&page["items"][0], "self"),
+            Some("GET /todos/3".to_string())
+        );
+
+        let req = build_todo_req_with_json(
+            "/todos/2",
+            Method::PATCH,
+            r#"{"completed": true}"#.to_string(),
+        );
+        let res = app.oneshot(req).await.unwrap();
+        let todo: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(link(&todo, "reopen"), Some("PATCH /todos/2".to_string()));
+        assert_eq!(link(&todo, "complete"), None);
+    }
+
+    #[tokio::test]
+    async fn should_count_and_check_todos_without_body() {
+        let repository = TodoRepositoryForMemory::new();
+        let todo = repository
+            .create(CreateTodo::new("should_count_todos".to_string()))
+            .await
+            .expect("failed create todo");
+        let app = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+
+        let req = build_todo_req_with_empty("/todos/count", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res_to_string(res).await, r#"{"count":1}"#);
+
+        let uri = format!("/todos/{}", todo.id);
+        let res = app
+            .clone()
+            .oneshot(build_todo_req_with_empty(&uri, Method::HEAD))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res_to_string(res).await, "");
+
+        let res = app
+            .oneshot(build_todo_req_with_empty("/todos/99", Method::HEAD))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res_to_string(res).await, "");
+    }
+
+    #[tokio::test]
+    async fn should_select_todo_fields() {
+        let repository = TodoRepositoryForMemory::new();
+        repository
+            .create(CreateTodo::new("should_select_todo_fields".to_string()))
+            .await
+            .expect("failed create todo");
+        let app = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+
+        let req = build_todo_req_with_empty("/todos?fields=id,text", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(
+            res_to_string(res).await,
+            r#"[{"id":1,"text":"should_select_todo_fields"}]"#
+        );
+
+        let req = build_todo_req_with_empty("/todos/1?fields=completed", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res_to_string(res).await, r#"{"completed":false}"#);
+
+        let req = build_todo_req_with_empty("/todos?fields=id,password", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn should_embed_labels_only_when_included() {
+        let labels = LabelRepositoryForMemory::new();
+        let label = labels
+            .create(CreateLabel::new("home".to_string()))
+            .await
+            .unwrap();
+        let repository = TodoRepositoryForMemory::with_labels(labels.clone());
+        repository
+            .create(CreateTodo {
+                labels: vec![label.id],
+                ..CreateTodo::new("should_embed_labels".to_string())
+            })
+            .await
+            .expect("failed create todo");
+        let app = create_app(
+            repository,
+            labels,
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+
+        let req = build_todo_req_with_empty("/todos/1?fields=labels", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res_to_string(res).await, r#"{"labels":[1]}"#);
+
+        let req = build_todo_req_with_empty("/todos?include=labels&fields=labels", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(
+            res_to_string(res).await,
+            format!(
+                r##"[{{"labels":[{{"color":"#808080","id":1,"name":"home","uuid":"{}"}}]}}]"##,
+                label.uuid
+            )
+        );
+
+        let req = build_todo_req_with_empty("/todos/1?include=comments", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn should_update_todo() {
+        let repository = TodoRepositoryForMemory::new();
+        let created = repository
+            .create(CreateTodo::new("before_should_update_todo".to_string()))
+            .await
+            .expect("failed create todo");
+        let expected = Todo {
+            uuid: created.uuid,
+            ..Todo::new(1, "should_update_todo".to_string())
+        };
+
+        let req = build_todo_req_with_json(
+            "/todos/1",
+            Method::PATCH,
+            r#"{
+              "id": 1,
+              "text": "should_update_todo",
+              "completed": false
+            }"#
+            .to_string(),
+        );
+        let res = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        let todo = res_to_todo(res).await;
+
+        assert_eq!(expected, todo);
+    }
+
+    #[tokio::test]
+    async fn should_apply_merge_patch() {
+        let labels = LabelRepositoryForMemory::new();
+        let label = labels
+            .create(CreateLabel::new("home".to_string()))
+            .await
+            .unwrap();
+        let repository = TodoRepositoryForMemory::with_labels(labels.clone());
+        repository
+            .create(CreateTodo {
+                labels: vec![label.id],
+                ..CreateTodo::new("should_apply_merge_patch".to_string())
+            })
+            .await
+            .expect("failed create todo");
+        let app = create_app(
+            repository,
+            labels,
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let merge_patch = |body: &str| {
+            Request::builder()
+                .uri("/todos/1")
+                .method(Method::PATCH)
+                .header(header::CONTENT_TYPE, "application/merge-patch+json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        // plain JSON keeps treating null as "leave untouched"
+        let req =
+            build_todo_req_with_json("/todos/1", Method::PATCH, r#"{"labels": null}"#.to_string());
+        let todo = res_to_todo(app.clone().oneshot(req).await.unwrap()).await;
+        assert_eq!(todo.labels, vec![label]);
+
+        let req = merge_patch(r#"{"labels": null, "completed": true}"#);
+        let todo = res_to_todo(app.clone().oneshot(req).await.unwrap()).await;
+        assert!(todo.labels.is_empty());
+        assert!(todo.completed);
+        assert_eq!(todo.text, "should_apply_merge_patch");
+
+        let res = app
+            .clone()
+            .oneshot(merge_patch(r#"{"text": null}"#))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let res = app.oneshot(merge_patch(r#"{"due": 1}"#)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn should_apply_json_patch() {
+        let repository = TodoRepositoryForMemory::new();
+        repository
+            .create(CreateTodo::new("should_apply_json_patch".to_string()))
+            .await
+            .expect("failed create todo");
+        let app = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let json_patch = |body: &str| {
+            Request::builder()
+                .uri("/todos/1")
+                .method(Method::PATCH)
+                .header(header::CONTENT_TYPE, "application/json-patch+json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let req = json_patch(
+            r#"[
+                {"op": "test", "path": "/text", "value": "should_apply_json_patch"},
+                {"op": "replace", "path": "/completed", "value": true}
+            ]"#,
+        );
+        let todo = res_to_todo(app.clone().oneshot(req).await.unwrap()).await;
+        assert!(todo.completed);
+
+        // the todo changed, so a client holding the old text must not win
+        let req = json_patch(
+            r#"[
+                {"op": "test", "path": "/completed", "value": false},
+                {"op": "replace", "path": "/text", "value": "stale"}
+            ]"#,
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+
+        let req = json_patch(r#"[{"op": "remove", "path": "/text"}]"#);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn should_answer_unmatched_requests_with_error_body() {
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+
+        let req = build_todo_req_with_empty("/nothing-here", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let body: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(body["message"], "no route for [/nothing-here]");
+
+        let req = build_todo_req_with_empty("/todos", Method::PUT);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert!(res.headers().contains_key(header::ALLOW));
+        let body: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        let allowed = body["details"]["allowed"].as_array().unwrap();
+        assert!(allowed.contains(&"POST".into()));
+
+        let req = Request::builder()
+            .uri("/todos")
+            .method(Method::POST)
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(Body::from("buy milk"))
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let body: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(body["status"], 415);
+    }
+
+    #[tokio::test]
+    async fn should_serve_single_page_app() {
+        let dir = std::env::temp_dir().join(format!("my-todo-spa-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("assets")).unwrap();
+        std::fs::write(dir.join("index.html"), "<div id=app></div>").unwrap();
+        std::fs::write(dir.join("assets/app.js"), "mount()").unwrap();
+        let mut config = Config::default();
+        config.http.static_dir = Some(dir.to_string_lossy().into_owned());
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            config,
+        );
+        let navigate = |uri: &str| {
+            Request::builder()
+                .uri(uri)
+                .header(header::ACCEPT, "text/html,application/xhtml+xml;q=0.9")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let res = app.clone().oneshot(navigate("/")).await.unwrap();
+        assert_eq!(res_to_string(res).await, "<div id=app></div>");
+        let req = build_todo_req_with_empty("/assets/app.js", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res_to_string(res).await, "mount()");
+        let res = app
+            .clone()
+            .oneshot(navigate("/todos/1/edit"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res_to_string(res).await, "<div id=app></div>");
+
+        // API clients and missing assets still get the JSON 404
+        let req = build_todo_req_with_empty("/assets/missing.js", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let body: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(body["status"], 404);
+        let req = build_todo_req_with_empty("/todos", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res_to_string(res).await, "[]");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn should_negotiate_binary_formats() {
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let body = rmp_serde::to_vec_named(&serde_json::json!({"text": "packed"})).unwrap();
+        let req = Request::builder()
+            .uri("/todos")
+            .method(Method::POST)
+            .header(header::CONTENT_TYPE, "application/msgpack")
+            .header(header::ACCEPT, "application/cbor")
+            .body(Body::from(body))
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "application/cbor");
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        // uuids arrive as text, which `Uuid` only reads from JSON
+        let todo: serde_json::Value = ciborium::from_reader(&bytes[..]).unwrap();
+        assert_eq!(todo["id"], 1);
+        assert_eq!(todo["text"], "packed");
+
+        let req = Request::builder()
+            .uri("/todos/2")
+            .header(header::ACCEPT, "application/msgpack")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let body: serde_json::Value = rmp_serde::from_slice(&bytes).unwrap();
+        assert_eq!(body["status"], 404);
+    }
+
+    #[tokio::test]
+    async fn should_clone_todo_as_a_new_open_one() {
+        let labels = LabelRepositoryForMemory::new();
+        labels
+            .create(CreateLabel::new("weekly".to_string()))
+            .await
+            .expect("failed create label");
+        let repository = TodoRepositoryForMemory::with_labels(labels.clone());
+        repository
+            .create(CreateTodo {
+                labels: vec![1],
+                text_i18n: std::collections::BTreeMap::from([(
+                    "ja".to_string(),
+                    "ゴミ出し".to_string(),
+                )]),
+                ..CreateTodo::new("take out the trash".to_string())
+            })
+            .await
+            .expect("failed create todo");
+        repository
+            .update(
+                1,
+                UpdateTodo {
+                    text: None,
+                    completed: Some(true),
+                    labels: None,
+                    assignee_id: None,
+                    text_i18n: None,
+                    estimate_minutes: None,
+                    due_date: None,
+                    description: None,
+                    custom: None,
+                },
+            )
+            .await
+            .expect("failed update todo");
+        let mut config = Config::default();
+        config.features.detect_duplicates = true;
+        let app = create_app(
+            repository,
+            labels.clone(),
+            UserRepositoryForMemory::new(),
+            config,
+        );
+
+        let req = build_todo_req_with_empty("/todos/1/clone", Method::POST);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let copy = res_to_todo(res).await;
+        assert_eq!(copy.id, 2);
+        assert_eq!(copy.text, "take out the trash");
+        assert!(!copy.completed);
+        assert_eq!(copy.labels, vec![labels.find(1).await.unwrap()]);
+        assert_eq!(copy.text_i18n["ja"], "ゴミ出し");
+
+        // copies of open todos are not refused as duplicates either
+        let req = build_todo_req_with_empty("/todos/2/clone", Method::POST);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+
+        let req = build_todo_req_with_empty("/todos/9/clone", Method::POST);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn should_group_todos_by_due_day() {
+        let today = chrono::Utc::now().date_naive();
+        let day = |days: i64| today + chrono::Duration::days(days);
+        let repository = TodoRepositoryForMemory::new();
+        for (text, due) in [
+            ("late", Some(day(-1))),
+            ("done late", Some(day(-1))),
+            ("today", Some(day(0))),
+            ("soon", Some(day(2))),
+            ("later", Some(day(10))),
+            ("whenever", None),
+        ] {
+            repository
+                .create(CreateTodo {
+                    due_date: due,
+                    ..CreateTodo::new(text.to_string())
+                })
+                .await
+                .expect("failed create todo");
+        }
+        repository
+            .update(
+                2,
+                UpdateTodo {
+                    text: None,
+                    completed: Some(true),
+                    labels: None,
+                    assignee_id: None,
+                    text_i18n: None,
+                    estimate_minutes: None,
+                    due_date: None,
+                    description: None,
+                    custom: None,
+                },
+            )
+            .await
+            .expect("failed update todo");
+        let app = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+
+        let uri = format!("/todos/calendar?from={}", day(-1));
+        let req = build_todo_req_with_empty(&uri, Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let calendar: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        let texts = |todos: &serde_json::Value| -> Vec<String> {
+            todos
+                .as_array()
+                .map(|todos| {
+                    todos
+                        .iter()
+                        .map(|todo| todo["text"].as_str().unwrap().to_string())
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let days = calendar["days"].as_array().unwrap();
+        assert_eq!(days.len(), 7);
+        assert_eq!(calendar["to"], day(5).to_string());
+        assert_eq!(days[0]["date"], day(-1).to_string());
+        assert_eq!(texts(&days[0]["todos"]), vec!["done late"]);
+        assert_eq!(texts(&days[1]["todos"]), vec!["today"]);
+        assert_eq!(texts(&days[1]["overdue"]), vec!["late"]);
+        assert_eq!(texts(&days[3]["todos"]), vec!["soon"]);
+        assert!(days[2].get("overdue").is_none());
+
+        // ranges without today carry nothing over
+        let uri = format!(
+            "/todos/calendar?from={}&to={}&filter=completed:false",
+            day(-1),
+            day(-1)
+        );
+        let req = build_todo_req_with_empty(&uri, Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let calendar: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(texts(&calendar["days"][0]["todos"]), vec!["late"]);
+
+        let uri = format!("/todos/calendar?from={}&to={}", day(0), day(42));
+        let req = build_todo_req_with_empty(&uri, Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn should_digest_todos_due_soon() {
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let bearer = register_and_login(&app, "dora").await;
+        let other = register_and_login(&app, "otto").await;
+        let today = chrono::Utc::now().date_naive();
+        let week_end = repositories::digest::week_end(today);
+        for (text, due, bearer) in [
+            ("late", today - chrono::Duration::days(3), &bearer),
+            ("now", today, &bearer),
+            ("sunday", week_end, &bearer),
+            ("next week", week_end + chrono::Duration::days(1), &bearer),
+            ("not mine", today, &other),
+        ] {
+            let body = format!(r#"{{"text": "{}", "due_date": "{}"}}"#, text, due);
+            let mut req = build_todo_req_with_json("/todos", Method::POST, body);
+            req.headers_mut()
+                .insert(header::AUTHORIZATION, bearer.parse().unwrap());
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(res.status(), StatusCode::CREATED);
+        }
+
+        let mut req = build_todo_req_with_empty("/me/digest", Method::GET);
+        req.headers_mut()
+            .insert(header::AUTHORIZATION, bearer.parse().unwrap());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let digest: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        let texts = |group: &str| -> Vec<String> {
+            digest[group]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|todo| todo["text"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(digest["date"], today.to_string());
+        assert_eq!(texts("overdue"), vec!["late"]);
+        assert_eq!(digest["overdue"][0]["workspace_id"], 1);
+        // on a Sunday the week ends today
+        if week_end == today {
+            assert_eq!(texts("today"), vec!["now", "sunday"]);
+            assert!(texts("this_week").is_empty());
+        } else {
+            assert_eq!(texts("today"), vec!["now"]);
+            assert_eq!(texts("this_week"), vec!["sunday"]);
+        }
+
+        let req = build_todo_req_with_empty("/me/digest", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn should_move_cards_across_board() {
+        let repository = TodoRepositoryForMemory::new();
+        for text in ["plan", "build", "ship"] {
+            repository
+                .create(CreateTodo::new(text.to_string()))
+                .await
+                .expect("failed create todo");
+        }
+        let app = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let columns = |board: &serde_json::Value| -> Vec<(String, i64, Vec<i64>)> {
+            board["columns"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|column| {
+                    let ids = column["todos"]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|todo| todo["id"].as_i64().unwrap())
+                        .collect();
+                    (
+                        column["name"].as_str().unwrap().to_string(),
+                        column["count"].as_i64().unwrap(),
+                        ids,
+                    )
+                })
+                .collect()
+        };
+
+        let req = build_todo_req_with_empty("/board", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let board: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(
+            columns(&board),
+            vec![
+                ("open".to_string(), 3, vec![1, 2, 3]),
+                ("done".to_string(), 0, vec![]),
+            ]
+        );
+
+        let req = build_todo_req_with_json(
+            "/workspaces/1/board/move",
+            Method::POST,
+            r#"{"todo_id": 2, "column": "done", "position": 0}"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let req = build_todo_req_with_json(
+            "/board/move",
+            Method::POST,
+            r#"{"todo_id": 3, "column": "open", "position": 0}"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        let board: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(
+            columns(&board),
+            vec![
+                ("open".to_string(), 2, vec![3, 1]),
+                ("done".to_string(), 1, vec![2]),
+            ]
+        );
+        let req = build_todo_req_with_empty("/todos/2", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert!(res_to_todo(res).await.completed);
+
+        let req = build_todo_req_with_empty("/board?filter=completed:false", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let board: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(columns(&board)[1].1, 0);
+
+        for (body, status) in [
+            (
+                r#"{"todo_id": 9, "column": "done", "position": 0}"#,
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                r#"{"todo_id": 1, "column": "doing", "position": 0}"#,
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+        ] {
+            let req = build_todo_req_with_json("/board/move", Method::POST, body.to_string());
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(res.status(), status);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_manage_todos_from_telegram() {
+        let mut config = Config::default();
+        config.telegram.webhook_secret = Some("secret".to_string());
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            config,
+        );
+        let bearer = register_and_login(&app, "tom").await;
+        let update = |text: &str, secret: &str| {
+            let body = serde_json::json!({
+                "update_id": 1,
+                "message": { "message_id": 1, "chat": { "id": 42, "type": "private" }, "text": text },
+            });
+            let mut req =
+                build_todo_req_with_json("/telegram/webhook", Method::POST, body.to_string());
+            req.headers_mut()
+                .insert("x-telegram-bot-api-secret-token", secret.parse().unwrap());
+            req
+        };
+        let send = |text: &str| {
+            let req = update(text, "secret");
+            let app = app.clone();
+            async move {
+                let res = app.oneshot(req).await.unwrap();
+                assert_eq!(res.status(), StatusCode::OK);
+                let reply: serde_json::Value =
+                    serde_json::from_str(&res_to_string(res).await).unwrap();
+                assert_eq!(reply["method"], "sendMessage");
+                assert_eq!(reply["chat_id"], 42);
+                reply["text"].as_str().unwrap().to_string()
+            }
+        };
+
+        assert!(send("/list").await.starts_with("Link this chat first"));
+        let mut req = build_todo_req_with_empty("/telegram/codes", Method::POST);
+        req.headers_mut()
+            .insert(header::AUTHORIZATION, bearer.parse().unwrap());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let code: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        let command = code["command"].as_str().unwrap().to_string();
+
+        assert!(send(&command)
+            .await
+            .starts_with("This chat now acts for you"));
+        assert!(send(&command).await.starts_with("That code is unknown"));
+        assert_eq!(send("/add buy milk").await, "Added #1: buy milk");
+        assert_eq!(send("/add@todo_bot walk dog").await, "Added #2: walk dog");
+        assert_eq!(send("/list").await, "#2 walk dog\n#1 buy milk");
+        assert_eq!(send("/done #1").await, "Completed #1: buy milk");
+        assert_eq!(send("/done 9").await, "There is no todo #9");
+        assert_eq!(send("/list").await, "#2 walk dog");
+        assert_eq!(send("/unlink").await, "This chat no longer acts for you");
+        assert!(send("/list").await.starts_with("Link this chat first"));
+
+        let res = app.clone().oneshot(update("/list", "wrong")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn should_file_mail_to_plus_addresses_as_todos() {
+        use hmac::{Hmac, Mac};
+
+        let mut config = Config::default();
+        config.email.address = Some("todo@in.example.com".to_string());
+        config.email.mailgun_signing_key = Some("key".to_string());
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            config,
+        );
+        let bearer = register_and_login(&app, "ellen").await;
+        let mut req = build_todo_req_with_empty("/email/address", Method::POST);
+        req.headers_mut()
+            .insert(header::AUTHORIZATION, bearer.parse().unwrap());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let address: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        let address = address["address"].as_str().unwrap().to_string();
+        assert!(address.starts_with("todo+") && address.ends_with("@in.example.com"));
+
+        let mail = |recipient: &str, subject: &str, key: &str| {
+            let timestamp = chrono::Utc::now().timestamp().to_string();
+            let mut mac = Hmac::<sha2::Sha256>::new_from_slice(key.as_bytes()).unwrap();
+            mac.update(format!("{}tok", timestamp).as_bytes());
+            let signature: String = mac
+                .finalize()
+                .into_bytes()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            let body = serde_urlencoded::to_string([
+                ("recipient", recipient),
+                ("sender", "ellen@example.com"),
+                ("subject", subject),
+                ("body-plain", "from the shop"),
+                ("timestamp", &timestamp),
+                ("token", "tok"),
+                ("signature", &signature),
+            ])
+            .unwrap();
+            Request::builder()
+                .uri("/email/inbound")
+                .method(Method::POST)
+                .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let res = app
+            .clone()
+            .oneshot(mail(&address, "  buy   milk ", "key"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let todo = res_to_todo(res).await;
+        assert_eq!(todo.text, "buy milk");
+        assert_eq!(todo.description.as_deref(), Some("from the shop"));
+        assert!(todo.owner_id.is_some());
+
+        for (recipient, subject, key, status) in [
+            (
+                address.as_str(),
+                "forged",
+                "other",
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                "todo+unknown@in.example.com",
+                "lost",
+                "key",
+                StatusCode::NOT_ACCEPTABLE,
+            ),
+            (address.as_str(), " ", "key", StatusCode::NOT_ACCEPTABLE),
+        ] {
+            let res = app
+                .clone()
+                .oneshot(mail(recipient, subject, key))
+                .await
+                .unwrap();
+            assert_eq!(res.status(), status);
+        }
+
+        let mut req = build_todo_req_with_empty("/email/address", Method::DELETE);
+        req.headers_mut()
+            .insert(header::AUTHORIZATION, bearer.parse().unwrap());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        let res = app
+            .oneshot(mail(&address, "too late", "key"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_ACCEPTABLE);
+    }
+
+    #[tokio::test]
+    async fn should_add_todos_with_slack_command() {
+        let mut config = Config::default();
+        config.slack.signing_secret = Some("secret".to_string());
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            config,
+        );
+        let bearer = register_and_login(&app, "sally").await;
+        let command = |text: &str, sent_at: chrono::DateTime<chrono::Utc>| {
+            let body = format!("team_id=T0&user_id=U1&command=%2Ftodo&text={}", text);
+            let mut req = Request::builder()
+                .uri("/slack/commands")
+                .method(Method::POST)
+                .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(Body::from(body.clone()))
+                .unwrap();
+            let headers = slack::signed_headers("secret", body.as_bytes(), sent_at);
+            req.headers_mut().extend(headers);
+            req
+        };
+        let reply = |res: Response| async {
+            let reply: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+            assert_eq!(reply["response_type"], "ephemeral");
+            reply["text"].as_str().unwrap().to_string()
+        };
+        let now = chrono::Utc::now();
+
+        let res = app
+            .clone()
+            .oneshot(command("add+buy+milk", now))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(reply(res).await.starts_with("Link your Slack account"));
+
+        let mut req = build_todo_req_with_json(
+            "/slack/link",
+            Method::PUT,
+            r#"{"webhook_url": "https://hooks.slack.com/services/T0/B0/x", "team_id": "T0", "slack_user_id": "U1"}"#
+                .to_string(),
+        );
+        req.headers_mut()
+            .insert(header::AUTHORIZATION, bearer.parse().unwrap());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let mut req = build_todo_req_with_json(
+            "/slack/link",
+            Method::PUT,
+            r#"{"webhook_url": "https://example.com/hook"}"#.to_string(),
+        );
+        req.headers_mut()
+            .insert(header::AUTHORIZATION, bearer.parse().unwrap());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let res = app
+            .clone()
+            .oneshot(command("add+buy+milk", now))
+            .await
+            .unwrap();
+        assert_eq!(reply(res).await, "Added #1: buy milk");
+        let res = app.clone().oneshot(command("list", now)).await.unwrap();
+        assert_eq!(reply(res).await, "Usage: /todo add <text>");
+        let stale = now - chrono::Duration::minutes(10);
+        let res = app
+            .clone()
+            .oneshot(command("add+again", stale))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let mut req = build_todo_req_with_empty("/todos/1", Method::GET);
+        req.headers_mut()
+            .insert(header::AUTHORIZATION, bearer.parse().unwrap());
+        let res = app.clone().oneshot(req).await.unwrap();
+        let todo = res_to_todo(res).await;
+        assert_eq!(todo.text, "buy milk");
+        assert!(todo.owner_id.is_some());
+
+        let mut req = build_todo_req_with_empty("/slack/link", Method::DELETE);
+        req.headers_mut()
+            .insert(header::AUTHORIZATION, bearer.parse().unwrap());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        let req = build_todo_req_with_empty("/slack/link", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn should_render_descriptions_on_request() {
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let req = build_todo_req_with_json(
+            "/todos",
+            Method::POST,
+            r#"{"text": "plan", "description": "**soon** <script>x()</script>"}"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let get = |uri: &str| {
+            let req = build_todo_req_with_empty(uri, Method::GET);
+            let app = app.clone();
+            async move {
+                let res = app.oneshot(req).await.unwrap();
+                let status = res.status();
+                let body: serde_json::Value =
+                    serde_json::from_str(&res_to_string(res).await).unwrap();
+                (status, body)
+            }
+        };
+
+        let (_, todo) = get("/todos/1").await;
+        assert_eq!(todo["description"], "**soon** <script>x()</script>");
+        assert!(todo.get("description_html").is_none());
+        let (_, todo) = get("/todos/1?render=html").await;
+        assert_eq!(todo["description_html"], "<p><strong>soon</strong> </p>\n");
+        let (_, todos) = get("/todos?render=html&fields=id,description_html").await;
+        assert_eq!(
+            todos,
+            serde_json::json!([{ "id": 1, "description_html": "<p><strong>soon</strong> </p>\n" }])
+        );
+        let (status, _) = get("/todos/1?render=pdf").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let req = build_todo_req_with_json(
+            "/todos/1",
+            Method::PATCH,
+            format!(r#"{{"description": "{}"}}"#, "x".repeat(10001)),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let mut req = build_todo_req_with_json(
+            "/todos/1",
+            Method::PATCH,
+            r#"{"description": null}"#.to_string(),
+        );
+        req.headers_mut().insert(
+            header::CONTENT_TYPE,
+            "application/merge-patch+json".parse().unwrap(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res_to_todo(res).await.description, None);
+    }
+
+    #[tokio::test]
+    async fn should_address_todos_and_labels_by_uuid_when_asked() {
+        let labels = LabelRepositoryForMemory::new();
+        let label = labels
+            .create(CreateLabel::new("home".to_string()))
+            .await
+            .unwrap();
+        let repository = TodoRepositoryForMemory::with_labels(labels.clone());
+        let todo = repository
+            .create(CreateTodo::new("by uuid".to_string()))
+            .await
+            .expect("failed create todo");
+        let mut config = Config::default();
+        config.features.uuid_ids = true;
+        let app = create_app(repository, labels, UserRepositoryForMemory::new(), config);
+        let send = |uri: String, method: Method, body: &str| {
+            let req = build_todo_req_with_json(&uri, method, body.to_string());
+            app.clone().oneshot(req)
+        };
+
+        let res = send("/todos/1".to_string(), Method::GET, "").await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res = send(format!("/todos/{}", todo.uuid), Method::GET, "")
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(body["uuid"], todo.uuid.to_string());
+        assert_eq!(
+            body["_links"]["self"]["href"],
+            format!("/todos/{}", todo.uuid)
+        );
+
+        let res = send(
+            format!("/todos/{}/items", todo.uuid),
+            Method::POST,
+            r#"{"text": "step"}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let res = send(
+            format!("/labels/{}", label.uuid),
+            Method::PATCH,
+            r#"{"name": "house"}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = send(format!("/todos/{}", label.uuid), Method::GET, "")
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res = send("/todos/one".to_string(), Method::GET, "")
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn should_check_and_filter_custom_fields() {
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let send = |uri: &str, method: Method, body: &str| {
+            let req = build_todo_req_with_json(uri, method, body.to_string());
+            let app = app.clone();
+            async move {
+                let res = app.oneshot(req).await.unwrap();
+                let status = res.status();
+                let body: serde_json::Value =
+                    serde_json::from_str(&res_to_string(res).await).unwrap();
+                (status, body)
+            }
+        };
+
+        let (status, fields) = send(
+            "/custom-fields",
+            Method::PUT,
+            r#"{"fields": [
+                {"name": "sprint", "type": "number", "required": true},
+                {"name": "size", "type": "enum", "values": ["s", "m", "l"]}
+            ]}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            fields["fields"][1]["values"],
+            serde_json::json!(["s", "m", "l"])
+        );
+        let (status, _) = send(
+            "/custom-fields",
+            Method::PUT,
+            r#"{"fields": [{"name": "size", "type": "enum"}]}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, error) = send("/todos", Method::POST, r#"{"text": "no sprint"}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            error["message"],
+            "Validation error: [custom: [sprint] is required]"
+        );
+        let (status, _) = send(
+            "/todos",
+            Method::POST,
+            r#"{"text": "huge", "custom": {"sprint": 12, "size": "xl"}}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, todo) = send(
+            "/todos",
+            Method::POST,
+            r#"{"text": "first", "custom": {"sprint": 12, "size": "s"}}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(
+            todo["custom"],
+            serde_json::json!({ "sprint": 12, "size": "s" })
+        );
+        send(
+            "/todos",
+            Method::POST,
+            r#"{"text": "second", "custom": {"sprint": 13}}"#,
+        )
+        .await;
+
+        let (status, todo) = send("/todos/1", Method::PATCH, r#"{"custom": {"size": null}}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(todo["custom"], serde_json::json!({ "sprint": 12 }));
+        let (status, _) = send("/todos/1", Method::PATCH, r#"{"custom": {"sprint": null}}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (_, todos) = send("/todos?custom.sprint=12&fields=text", Method::GET, "").await;
+        assert_eq!(todos, serde_json::json!([{ "text": "first" }]));
+        let (status, _) = send("/todos?custom.sprint=soon", Method::GET, "").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = send("/todos?custom.team=a", Method::GET, "").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn should_chart_remaining_estimates() {
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let req = build_todo_req_with_json(
+            "/todos",
+            Method::POST,
+            r#"{"text": "estimated", "estimate_minutes": 90}"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res_to_todo(res).await.estimate_minutes, Some(90));
+        let req = build_todo_req_with_json(
+            "/todos/1",
+            Method::PATCH,
+            r#"{"estimate_minutes": 0}"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let req = build_todo_req_with_json(
+            "/todos/1",
+            Method::PATCH,
+            r#"{"estimate_minutes": 40}"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let today = chrono::Utc::now().date_naive();
+        let uri = format!(
+            "/workspaces/1/burndown?from={}&to={}",
+            today - chrono::Duration::days(1),
+            today
+        );
+        let req = build_todo_req_with_empty(&uri, Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let days: Vec<BurndownDay> = serde_json::from_str(&res_to_string(res).await).unwrap();
+        let remaining: Vec<i64> = days.iter().map(|day| day.remaining_minutes).collect();
+        assert_eq!(remaining, vec![0, 40]);
+
+        let req = build_todo_req_with_empty("/burndown", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let days: Vec<BurndownDay> = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(days.len(), 14);
+        assert_eq!(days[13].day, today);
+
+        for query in [
+            "from=2023-05-02&to=2023-05-01",
+            "from=2022-01-01&to=2023-05-01",
+        ] {
+            let req = build_todo_req_with_empty(&format!("/burndown?{}", query), Method::GET);
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_manage_checklist_items() {
+        let repository = TodoRepositoryForMemory::new();
+        repository
+            .create(CreateTodo::new("trip".to_string()))
+            .await
+            .expect("failed create todo");
+        let app = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+
+        for text in ["pack", "book"] {
+            let req = build_todo_req_with_json(
+                "/todos/1/items",
+                Method::POST,
+                format!(r#"{{"text": "{}"}}"#, text),
+            );
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(res.status(), StatusCode::CREATED);
+        }
+        let req = build_todo_req_with_json(
+            "/todos/1/items/2",
+            Method::PATCH,
+            r#"{"completed": true, "position": 0}"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let req = build_todo_req_with_empty("/todos/1/items", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let items: Vec<ChecklistItem> = serde_json::from_str(&res_to_string(res).await).unwrap();
+        let order: Vec<(&str, bool)> = items
+            .iter()
+            .map(|item| (item.text.as_str(), item.completed))
+            .collect();
+        assert_eq!(order, vec![("book", true), ("pack", false)]);
+
+        let req = build_todo_req_with_empty("/todos/1", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let todo = res_to_todo(res).await;
+        assert_eq!(todo.progress, Progress { done: 1, total: 2 });
+
+        let req = build_todo_req_with_empty("/todos/1/clone?checklist=true", Method::POST);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let copy = res_to_todo(res).await;
+        assert_eq!(copy.progress, Progress { done: 0, total: 2 });
+
+        let req = build_todo_req_with_empty("/todos/1/items/1", Method::DELETE);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        let req = build_todo_req_with_empty("/todos/1/items/1", Method::DELETE);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let req = build_todo_req_with_json(
+            "/todos/1/items",
+            Method::POST,
+            r#"{"text": ""}"#.to_string(),
+        );
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn should_delete_todo() {
+        let repository = TodoRepositoryForMemory::new();
+        repository
+            .create(CreateTodo::new("should_delete_todo".to_string()))
+            .await
+            .expect("failed create todo");
+
+        let req = build_todo_req_with_empty("/todos/1", Method::DELETE);
+        let res = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_revert_todo() {
+        let repository = TodoRepositoryForMemory::new();
+        let expected = repository
+            .create(CreateTodo::new("before_should_revert_todo".to_string()))
+            .await
+            .expect("failed create todo");
+        let req = build_todo_req_with_json(
+            "/todos/1",
+            Method::PATCH,
+            r#"{"text": "should_revert_todo"}"#.to_string(),
+        );
+        let app = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        app.clone().oneshot(req).await.unwrap();
+
+        let req = build_todo_req_with_empty("/todos/1/revisions/1/revert", Method::POST);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let todo = res_to_todo(res).await;
+        assert_eq!(expected, todo);
+
+        let req = build_todo_req_with_empty("/todos/1/revisions/9/revert", Method::POST);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_snooze_todos_out_of_default_listings() {
+        let repository = TodoRepositoryForMemory::new();
+        for text in ["now", "later"] {
+            repository
+                .create(CreateTodo::new(text.to_string()))
+                .await
+                .expect("failed create todo");
+        }
+        let app = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let snooze = |body: &str| {
+            build_todo_req_with_json("/todos/2/snooze", Method::POST, body.to_string())
+        };
+        let texts =
+            |todos: Vec<Todo>| -> Vec<String> { todos.into_iter().map(|todo| todo.text).collect() };
+
+        for body in [
+            r#"{}"#,
+            r#"{"minutes": 0}"#,
+            r#"{"until": "2001-01-01T00:00:00Z"}"#,
+            r#"{"until": "2999-01-01T00:00:00Z", "minutes": 5}"#,
+        ] {
+            let res = app.clone().oneshot(snooze(body)).await.unwrap();
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{}", body);
+        }
+
+        let res = app
+            .clone()
+            .oneshot(snooze(r#"{"minutes": 90}"#))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let todo = res_to_todo(res).await;
+        assert!(todo.is_snoozed(chrono::Utc::now() + chrono::Duration::minutes(89)));
+
+        let req = build_todo_req_with_empty("/todos", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let todos: Vec<Todo> = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(texts(todos), vec!["now"]);
+        let req = build_todo_req_with_empty("/todos?include_snoozed=true", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let todos: Vec<Todo> = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(texts(todos), vec!["later", "now"]);
+        let req = build_todo_req_with_empty("/todos/2", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res_to_todo(res).await, todo);
+
+        let req = build_todo_req_with_empty("/todos/2/snooze", Method::DELETE);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res_to_todo(res).await.snoozed_until, None);
+        let req = build_todo_req_with_empty("/todos", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let todos: Vec<Todo> = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(texts(todos), vec!["later", "now"]);
+
+        let res = app
+            .oneshot(build_todo_req_with_json(
+                "/todos/9/snooze",
+                Method::POST,
+                r#"{"minutes": 5}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn should_return_error_body_when_todo_not_found() {
+        let req = build_todo_req_with_empty("/todos/1", Method::GET);
+        let res = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+        let body: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(body["status"], 404);
+        assert_eq!(body["message"], "NotFound, id is 1");
+    }
+
+    #[tokio::test]
+    async fn should_return_not_found_when_updating_missing_todo() {
+        let req = build_todo_req_with_json(
+            "/todos/1",
+            Method::PATCH,
+            r#"{"completed": true}"#.to_string(),
+        );
+        let res = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_return_error_body_when_validation_failed() {
+        let req = build_todo_req_with_json("/todos", Method::POST, r#"{"text": ""}"#.to_string());
+        let res = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+
+        let body: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(body["status"], 400);
+        assert!(body["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Validation error"));
+    }
+
+    #[tokio::test]
+    async fn should_localize_error_messages() {
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let mut req =
+            build_todo_req_with_json("/todos", Method::POST, r#"{"text": ""}"#.to_string());
+        req.headers_mut()
+            .insert(header::ACCEPT_LANGUAGE, "ja-JP,en;q=0.8".parse().unwrap());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.headers()[header::CONTENT_LANGUAGE], "ja");
+        let body: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(body["message"], "入力エラー: [text: 空にできません]");
+
+        let mut req = build_todo_req_with_empty("/todos/1", Method::GET);
+        req.headers_mut()
+            .insert(header::ACCEPT_LANGUAGE, "en".parse().unwrap());
+        let res = app.oneshot(req).await.unwrap();
+        let body: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(body["message"], "NotFound, id is 1");
+    }
+
+    #[tokio::test]
+    async fn should_pick_todo_text_by_accept_language() {
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let req = build_todo_req_with_json(
+            "/todos",
+            Method::POST,
+            r#"{"text": "buy milk", "text_i18n": {"ja": "牛乳を買う"}}"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+
+        for (accept_language, expected) in [
+            (None, "buy milk"),
+            (Some("ja-JP,en;q=0.5"), "牛乳を買う"),
+            (Some("fr"), "buy milk"),
+        ] {
+            let mut req = build_todo_req_with_empty("/todos/1", Method::GET);
+            if let Some(value) = accept_language {
+                req.headers_mut()
+                    .insert(header::ACCEPT_LANGUAGE, value.parse().unwrap());
+            }
+            let res = app.clone().oneshot(req).await.unwrap();
+            let todo: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+            assert_eq!(todo["text"], expected);
+            assert_eq!(todo["text_i18n"]["ja"], "牛乳を買う");
+        }
+
+        let req = build_todo_req_with_json(
+            "/todos/1",
+            Method::PATCH,
+            r#"{"text_i18n": {"ja": ""}}"#.to_string(),
+        );
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn should_point_at_malformed_json() {
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+
+        for (body, path) in [
+            (r#"{"text": "a", "labels": [1, "two"]}"#, "labels[1]"),
+            (r#"{"labels": []}"#, "."),
+            (r#"{"text": "a""#, "."),
+        ] {
+            let req = build_todo_req_with_json("/todos", Method::POST, body.to_string());
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+
+            let body: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+            assert_eq!(body["status"], 422);
+            assert_eq!(body["details"]["path"], path);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_reject_unknown_fields_in_strict_mode() {
+        let body = r#"{"text": "typo", "compleded": true}"#;
+        let req = build_todo_req_with_json("/todos", Method::POST, body.to_string());
+        let res = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+
+        let mut config = Config::default();
+        config.http.strict_json = true;
+        let req = build_todo_req_with_json("/todos", Method::POST, body.to_string());
+        let res = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            config,
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+        let body: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(body["details"]["path"], "compleded");
+    }
+
+    #[tokio::test]
+    async fn should_rate_limit_by_client_ip() {
+        let mut config = Config::default();
+        config.rate_limit.burst = 1;
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            config,
+        );
+        let req_from = |ip: [u8; 4]| {
+            let mut req = build_todo_req_with_empty("/todos", Method::GET);
+            req.extensions_mut()
+                .insert(ConnectInfo(SocketAddr::from((ip, 4000))));
+            req
+        };
+
+        let res = app.clone().oneshot(req_from([10, 0, 0, 1])).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+
+        let res = app.clone().oneshot(req_from([10, 0, 0, 1])).await.unwrap();
+        assert_eq!(StatusCode::TOO_MANY_REQUESTS, res.status());
+        assert!(res.headers().contains_key(header::RETRY_AFTER));
+
+        let res = app.oneshot(req_from([10, 0, 0, 2])).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_rate_limit_clients_behind_trusted_proxies() {
+        let mut config = Config::default();
+        config.rate_limit.burst = 1;
+        config.http.trusted_proxies = vec!["10.0.0.0/8".parse().unwrap()];
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            config,
+        );
+        let req_via = |peer: [u8; 4], forwarded_for: &str| {
+            let mut req = build_todo_req_with_empty("/todos", Method::GET);
+            req.extensions_mut()
+                .insert(ConnectInfo(SocketAddr::from((peer, 4000))));
+            req.headers_mut()
+                .insert("x-forwarded-for", forwarded_for.parse().unwrap());
+            req
+        };
+
+        let res = app
+            .clone()
+            .oneshot(req_via([10, 0, 0, 1], "1.1.1.1"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let res = app
+            .clone()
+            .oneshot(req_via([10, 0, 0, 1], "1.1.1.1"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::TOO_MANY_REQUESTS, res.status());
+        let res = app
+            .clone()
+            .oneshot(req_via([10, 0, 0, 1], "2.2.2.2"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+
+        // an untrusted peer cannot pick a fresh address for itself
+        let res = app
+            .clone()
+            .oneshot(req_via([3, 3, 3, 3], "4.4.4.4"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let res = app.oneshot(req_via([3, 3, 3, 3], "5.5.5.5")).await.unwrap();
+        assert_eq!(StatusCode::TOO_MANY_REQUESTS, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_reject_oversized_body() {
+        let mut config = Config::default();
+        config.http.max_body_bytes = 16;
+        let req = build_todo_req_with_json(
+            "/todos",
+            Method::POST,
+            r#"{"text": "should_reject_oversized_body"}"#.to_string(),
+        );
+        let res = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            config,
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::PAYLOAD_TOO_LARGE, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_compress_responses() {
+        let repository = TodoRepositoryForMemory::new();
+        for i in 0..20 {
+            repository
+                .create(CreateTodo::new(format!("should_compress_responses {}", i)))
+                .await
+                .expect("failed create todo");
+        }
+        let mut req = build_todo_req_with_empty("/todos", Method::GET);
+        req.headers_mut()
+            .insert(header::ACCEPT_ENCODING, "gzip".parse().unwrap());
+        let res = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!(res.headers()[header::CONTENT_ENCODING], "gzip");
+    }
+
+    #[tokio::test]
+    async fn should_set_security_headers() {
+        let mut config = Config::default();
+        config.security_headers.frame_options = String::new();
+        config.security_headers.content_security_policy = "default-src 'none'".to_string();
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            config,
+        );
+
+        for (path, method) in [("/app", Method::GET), ("/todos", Method::PUT)] {
+            let res = app
+                .clone()
+                .oneshot(build_todo_req_with_empty(path, method))
+                .await
+                .unwrap();
+            let headers = res.headers();
+            assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+            assert_eq!(
+                headers[header::REFERRER_POLICY],
+                "strict-origin-when-cross-origin"
+            );
+            assert!(headers.contains_key(header::STRICT_TRANSPORT_SECURITY));
+            assert_eq!(
+                headers[header::CONTENT_SECURITY_POLICY],
+                "default-src 'none'"
+            );
+            assert!(!headers.contains_key(header::X_FRAME_OPTIONS));
+        }
+    }
+
+    #[tokio::test]
+    async fn should_tag_responses_with_request_id() {
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let res = app
+            .clone()
+            .oneshot(build_todo_req_with_empty("/todos/1", Method::GET))
+            .await
+            .unwrap();
+        let generated = res.headers()["x-request-id"].to_str().unwrap();
+        assert_eq!(generated.len(), 32);
+
+        let mut req = build_todo_req_with_empty("/todos", Method::GET);
+        req.headers_mut()
+            .insert("x-request-id", "from-proxy".parse().unwrap());
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.headers()["x-request-id"], "from-proxy");
+    }
+
+    #[tokio::test]
+    async fn should_serve_graphql() {
+        let repository = TodoRepositoryForMemory::new();
+        repository
+            .create(CreateTodo::new("should_serve_graphql".to_string()))
+            .await
+            .expect("failed create todo");
+        let req = build_todo_req_with_json(
+            "/graphql",
+            Method::POST,
+            r#"{"query": "{ todo(id: 1) { text completed } }"}"#.to_string(),
+        );
+        let res = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        let body: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(body["data"]["todo"]["text"], "should_serve_graphql");
+        assert_eq!(body["data"]["todo"]["completed"], false);
+    }
+
+    #[tokio::test]
+    async fn should_return_hello_world() {
+        let repository = TodoRepositoryForMemory::new();
+        let req = Request::builder().uri("/").body(Body::empty()).unwrap();
+        let res = create_app(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let body = String::from_utf8(bytes.to_vec()).unwrap();
+        assert_eq!(body, "Hello, world!!");
+    }
+
+    #[tokio::test]
+    async fn should_created_label() {
+        let expected = Label {
+            color: "#1e90ff".to_string(),
+            icon: Some("🏠".to_string()),
+            ..Label::new(1, "should_created_label".to_string())
+        };
+        let req = build_todo_req_with_json(
+            "/labels",
+            Method::POST,
+            r##"{"name": "should_created_label", "color": "#1e90ff", "icon": "🏠"}"##.to_string(),
+        );
+        let res = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let body = res_to_string(res).await;
+        let label: Label = serde_json::from_str(&body)
+            .unwrap_or_else(|_| panic!("connot convert Label instance. body: {}", body));
+        assert!(!label.uuid.is_nil());
+        assert_eq!(
+            Label {
+                uuid: label.uuid,
+                ..expected
+            },
+            label
+        );
+    }
+
+    #[tokio::test]
+    async fn should_conflict_on_duplicate_label_names() {
+        let labels = LabelRepositoryForMemory::new();
+        for name in ["work", "home"] {
+            labels
+                .create(CreateLabel::new(name.to_string()))
+                .await
+                .expect("failed create label");
+        }
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            labels,
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+
+        let req =
+            build_todo_req_with_json("/labels", Method::POST, r#"{"name": "work"}"#.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        let body: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(body["message"], "Duplicate data, id is 1");
+        assert_eq!(body["details"]["id"], 1);
+        assert_eq!(body["details"]["name"], "work");
+
+        let req = build_todo_req_with_json(
+            "/labels/2",
+            Method::PATCH,
+            r#"{"name": "work"}"#.to_string(),
+        );
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        let body: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(body["details"]["name"], "work");
+    }
+
+    #[tokio::test]
+    async fn should_update_label_color_and_icon() {
+        let labels = LabelRepositoryForMemory::new();
+        let label = labels
+            .create(CreateLabel::new("home".to_string()))
+            .await
+            .expect("failed create label");
+        let repository = TodoRepositoryForMemory::with_labels(labels.clone());
+        repository
+            .create(CreateTodo {
+                labels: vec![label.id],
+                ..CreateTodo::new("laundry".to_string())
+            })
+            .await
+            .expect("failed create todo");
+        let app = create_app(
+            repository,
+            labels,
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+
+        let req = build_todo_req_with_json(
+            "/labels/1",
+            Method::PATCH,
+            r##"{"color": "#f80", "icon": "🧺"}"##.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let req = build_todo_req_with_empty("/todos/1?include=labels", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let todo: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(todo["labels"][0]["color"], "#f80");
+        assert_eq!(todo["labels"][0]["icon"], "🧺");
+
+        let req = build_todo_req_with_json(
+            "/labels/1",
+            Method::PATCH,
+            r#"{"color": "orange"}"#.to_string(),
+        );
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn should_suggest_labels_for_typeahead() {
+        let labels = LabelRepositoryForMemory::new();
+        for name in ["work", "workshop", "groceries"] {
+            labels
+                .create(CreateLabel::new(name.to_string()))
+                .await
+                .expect("failed create label");
+        }
+        let repository = TodoRepositoryForMemory::with_labels(labels.clone());
+        repository
+            .create(CreateTodo {
+                labels: vec![2],
+                ..CreateTodo::new("book a room".to_string())
+            })
+            .await
+            .expect("failed create todo");
+        let app = create_app(
+            repository,
+            labels,
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let suggest = |uri: &str| build_todo_req_with_empty(uri, Method::GET);
+
+        let res = app
+            .clone()
+            .oneshot(suggest("/labels/suggest?q=Wo"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let suggested: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(suggested[0]["name"], "workshop");
+        assert_eq!(suggested[0]["todos"], 1);
+        assert_eq!(suggested[1]["name"], "work");
+        assert_eq!(suggested.as_array().unwrap().len(), 2);
+
+        let res = app
+            .clone()
+            .oneshot(suggest("/labels/suggest?q=grocries&limit=1"))
+            .await
+            .unwrap();
+        let suggested: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(suggested[0]["name"], "groceries");
+
+        let res = app
+            .oneshot(suggest("/labels/suggest?q=w&limit=0"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn should_report_label_usage_and_clean_up_unused_labels() {
+        let labels = LabelRepositoryForMemory::new();
+        for name in ["home", "work"] {
+            labels
+                .create(CreateLabel::new(name.to_string()))
+                .await
+                .expect("failed create label");
+        }
+        let repository = TodoRepositoryForMemory::with_labels(labels.clone());
+        repository
+            .create(CreateTodo {
+                labels: vec![1],
+                ..CreateTodo::new("laundry".to_string())
+            })
+            .await
+            .expect("failed create todo");
+        let home = labels.find(1).await.unwrap();
+        let app = create_app(
+            repository,
+            labels,
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+
+        let req = build_todo_req_with_empty("/labels/stats", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let stats: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(stats[0]["name"], "home");
+        assert_eq!(stats[0]["todos"], 1);
+        assert_eq!(stats[1]["name"], "work");
+        assert_eq!(stats[1]["todos"], 0);
+
+        let req = build_todo_req_with_empty("/labels/cleanup", Method::POST);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res_to_string(res).await, r#"{"deleted":[2]}"#);
+
+        let req = build_todo_req_with_empty("/labels", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        let all: Vec<Label> = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(all, vec![home]);
+    }
+
+    #[tokio::test]
+    async fn should_merge_labels() {
+        let labels = LabelRepositoryForMemory::new();
+        for name in ["work", "Work"] {
+            labels
+                .create(CreateLabel::new(name.to_string()))
+                .await
+                .expect("failed create label");
+        }
+        let repository = TodoRepositoryForMemory::with_labels(labels.clone());
+        repository
+            .create(CreateTodo {
+                labels: vec![2],
+                ..CreateTodo::new("report".to_string())
+            })
+            .await
+            .expect("failed create todo");
+        let app = create_app(
+            repository,
+            labels,
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+
+        let req = build_todo_req_with_json(
+            "/labels/2/merge",
+            Method::POST,
+            r#"{"target_id": 2}"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let req = build_todo_req_with_json(
+            "/labels/2/merge",
+            Method::POST,
+            r#"{"target_id": 1}"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res_to_string(res).await, r#"{"target_id":1,"todos":[1]}"#);
+
+        let req = build_todo_req_with_empty("/todos/1?include=labels", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let todo: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(todo["labels"][0]["name"], "work");
+
+        let req = build_todo_req_with_json(
+            "/labels/2/merge",
+            Method::POST,
+            r#"{"target_id": 1}"#.to_string(),
+        );
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn should_get_all_labels() {
+        let label_repository = LabelRepositoryForMemory::new();
+        let expected = label_repository
+            .create(CreateLabel::new("should_get_all_labels".to_string()))
+            .await
+            .expect("failed create label");
+        let req = build_todo_req_with_empty("/labels", Method::GET);
+        let res = create_app(
+            TodoRepositoryForMemory::new(),
+            label_repository,
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        let body = res_to_string(res).await;
+        let labels: Vec<Label> = serde_json::from_str(&body)
+            .unwrap_or_else(|_| panic!("connot convert Label instance. body: {}", body));
+        assert_eq!(vec![expected], labels);
+    }
+
+    #[tokio::test]
+    async fn should_delete_label() {
+        let label_repository = LabelRepositoryForMemory::new();
+        label_repository
+            .create(CreateLabel::new("should_delete_label".to_string()))
+            .await
+            .expect("failed create label");
+        let req = build_todo_req_with_empty("/labels/1", Method::DELETE);
+        let res = create_app(
+            TodoRepositoryForMemory::new(),
+            label_repository,
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_mount_todo_routes_alone() {
+        let repository = TodoRepositoryForMemory::new();
+        repository
+            .create(CreateTodo::new(
+                "should_mount_todo_routes_alone".to_string(),
+            ))
+            .await
+            .expect("failed create todo");
+        let app = todo_routes::<
+            TodoRepositoryForMemory,
+            LabelRepositoryForMemory,
+            UserRepositoryForMemory,
+        >()
+        .with_state(AppState::new(
+            repository,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        ));
+
+        let req = build_todo_req_with_empty("/todos/1", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+
+        let req = build_todo_req_with_empty("/labels", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_register_login_and_change_password() {
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let register = |password: &str| {
+            build_todo_req_with_json(
+                "/users",
+                Method::POST,
+                format!(r#"{{ "username": "alice", "password": "{}" }}"#, password),
+            )
+        };
+
+        let res = app.clone().oneshot(register("short")).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        let res = app
+            .clone()
+            .oneshot(register("s3cure-enough"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let body = res_to_string(res).await;
+        assert!(!body.contains("password"), "body: {}", body);
+        let res = app
+            .clone()
+            .oneshot(register("s3cure-enough"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::CONFLICT, res.status());
+
+        let login = |password: &str| {
+            build_todo_req_with_json(
+                "/auth/login",
+                Method::POST,
+                format!(r#"{{ "username": "alice", "password": "{}" }}"#, password),
+            )
+        };
+        let res = app.clone().oneshot(login("wrong-pass1")).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+        let res = app.clone().oneshot(login("s3cure-enough")).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let token: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        let bearer = format!("Bearer {}", token["access_token"].as_str().unwrap());
+
+        let res = app
+            .clone()
+            .oneshot(build_todo_req_with_empty("/me", Method::GET))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+        let mut req = build_todo_req_with_empty("/me", Method::GET);
+        req.headers_mut()
+            .insert(header::AUTHORIZATION, bearer.parse().unwrap());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let body = res_to_string(res).await;
+        assert!(body.contains(r#""username":"alice""#), "body: {}", body);
+        assert!(!body.contains("password"), "body: {}", body);
+
+        let change_password = |current: &str| {
+            let mut req = build_todo_req_with_json(
+                "/me/password",
+                Method::PATCH,
+                format!(
+                    r#"{{ "current_password": "{}", "new_password": "an0ther-secret" }}"#,
+                    current
+                ),
+            );
+            req.headers_mut()
+                .insert(header::AUTHORIZATION, bearer.parse().unwrap());
+            req
+        };
+        let res = app
+            .clone()
+            .oneshot(change_password("wrong-pass1"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::FORBIDDEN, res.status());
+        let res = app
+            .clone()
+            .oneshot(change_password("s3cure-enough"))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+
+        let res = app.clone().oneshot(login("s3cure-enough")).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+        let res = app.oneshot(login("an0ther-secret")).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_authenticate_with_session_cookie() {
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let credentials = r#"{ "username": "bob", "password": "s3cure-enough", "session": true }"#;
+        let req = build_todo_req_with_json("/users", Method::POST, credentials.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+
+        let req = build_todo_req_with_json("/auth/login", Method::POST, credentials.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let set_cookie = res.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(set_cookie.contains("HttpOnly"), "{}", set_cookie);
+        assert!(set_cookie.contains("SameSite=Strict"), "{}", set_cookie);
+        let cookie = set_cookie.split(';').next().unwrap().to_string();
+
+        let with_cookie = |path: &str, method: Method| {
+            let mut req = build_todo_req_with_empty(path, method);
+            req.headers_mut()
+                .insert(header::COOKIE, cookie.parse().unwrap());
+            req
+        };
+        let res = app
+            .clone()
+            .oneshot(with_cookie("/me", Method::GET))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+
+        let res = app
+            .clone()
+            .oneshot(with_cookie("/auth/logout", Method::POST))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::FORBIDDEN, res.status());
+        let res = app
+            .clone()
+            .oneshot(with_cookie("/auth/csrf", Method::GET))
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        let mut logout = with_cookie("/auth/logout", Method::POST);
+        logout.headers_mut().insert(
+            "x-csrf-token",
+            body["token"].as_str().unwrap().parse().unwrap(),
+        );
+        let res = app.clone().oneshot(logout).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        assert!(res.headers()[header::SET_COOKIE]
+            .to_str()
+            .unwrap()
+            .contains("Max-Age=0"));
+        let res = app.oneshot(with_cookie("/me", Method::GET)).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_require_csrf_token_in_session_forms() {
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let credentials = r#"{ "username": "bob", "password": "s3cure-enough", "session": true }"#;
+        let req = build_todo_req_with_json("/users", Method::POST, credentials.to_string());
+        app.clone().oneshot(req).await.unwrap();
+        let req = build_todo_req_with_json("/auth/login", Method::POST, credentials.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        let set_cookie = res.headers()[header::SET_COOKIE].to_str().unwrap();
+        let cookie = set_cookie.split(';').next().unwrap().to_string();
+
+        let mut req = build_todo_req_with_empty("/app", Method::GET);
+        req.headers_mut()
+            .insert(header::COOKIE, cookie.parse().unwrap());
+        let page = res_to_string(app.clone().oneshot(req).await.unwrap()).await;
+        let token = page
+            .split(r#"name="csrf_token" value=""#)
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .unwrap()
+            .to_string();
+
+        let post = |form: String| {
+            Request::builder()
+                .uri("/app/todos")
+                .method(Method::POST)
+                .header(header::COOKIE, &cookie)
+                .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(Body::from(form))
+                .unwrap()
+        };
+        let res = app
+            .clone()
+            .oneshot(post("text=forged".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::FORBIDDEN, res.status());
+        let res = app
+            .clone()
+            .oneshot(post("csrf_token=wrong&text=forged".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::FORBIDDEN, res.status());
+        let res = app
+            .clone()
+            .oneshot(post(format!("csrf_token={}&text=mine", token)))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::SEE_OTHER, res.status());
+
+        // without the cookie there is nothing to forge
+        let req = build_todo_req_with_json("/todos", Method::POST, r#"{"text":"api"}"#.to_string());
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_rotate_refresh_tokens() {
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let credentials = r#"{ "username": "carol", "password": "s3cure-enough" }"#;
+        let req = build_todo_req_with_json("/users", Method::POST, credentials.to_string());
+        app.clone().oneshot(req).await.unwrap();
+        let req = build_todo_req_with_json("/auth/login", Method::POST, credentials.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        let token: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        let first = token["refresh_token"].as_str().unwrap().to_string();
+
+        let refresh = |refresh_token: &str| {
+            build_todo_req_with_json(
+                "/auth/refresh",
+                Method::POST,
+                format!(r#"{{ "refresh_token": "{}" }}"#, refresh_token),
+            )
+        };
+        let res = app.clone().oneshot(refresh(&first)).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let token: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert!(token["access_token"].is_string());
+        let second = token["refresh_token"].as_str().unwrap().to_string();
+        assert_ne!(first, second);
+
+        // replaying the first token revokes the second one too
+        let res = app.clone().oneshot(refresh(&first)).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+        let res = app.clone().oneshot(refresh(&second)).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+
+        let req = build_todo_req_with_json("/auth/login", Method::POST, credentials.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        let token: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        let third = token["refresh_token"].as_str().unwrap().to_string();
+        let req = build_todo_req_with_json(
+            "/auth/logout",
+            Method::POST,
+            format!(r#"{{ "refresh_token": "{}" }}"#, third),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let res = app.oneshot(refresh(&third)).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_start_oauth_flow() {
+        let mut config = Config::default();
+        config.auth.github = Some(config::OAuthClientConfig {
+            client_id: "client".to_string(),
+            client_secret: "secret".to_string(),
+        });
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            config,
+        );
+
+        let req = build_todo_req_with_empty("/auth/oauth/github", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::FOUND, res.status());
+        let location = res.headers()[header::LOCATION].to_str().unwrap();
+        assert!(location.starts_with("https://github.com/login/oauth/authorize?"));
+        let state_cookie = res.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(state_cookie.starts_with("todo_oauth_state="));
+
+        // a callback without the browser's state cookie is refused
+        let req = build_todo_req_with_empty(
+            "/auth/oauth/github/callback?code=abc&state=forged",
+            Method::GET,
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+
+        let req = build_todo_req_with_empty("/auth/oauth/google", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_lock_out_repeated_login_failures() {
+        let mut config = Config::default();
+        config.auth.lockout.account_failures = 2;
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            config,
+        );
+        let req = build_todo_req_with_json(
+            "/users",
+            Method::POST,
+            r#"{ "username": "erin", "password": "s3cure-enough" }"#.to_string(),
+        );
+        app.clone().oneshot(req).await.unwrap();
+        let login = |password: &str| {
+            build_todo_req_with_json(
+                "/auth/login",
+                Method::POST,
+                format!(r#"{{ "username": "erin", "password": "{}" }}"#, password),
+            )
+        };
+
+        for _ in 0..2 {
+            let res = app.clone().oneshot(login("wrong-pass1")).await.unwrap();
+            assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+        }
+        // locked even with the right password
+        let res = app.oneshot(login("s3cure-enough")).await.unwrap();
+        assert_eq!(StatusCode::TOO_MANY_REQUESTS, res.status());
+        assert_eq!(res.headers()[header::RETRY_AFTER], "30");
+    }
+
+    /// Registers `username` and returns an `Authorization` header value.
+    async fn register_and_login(app: &Router, username: &str) -> String {
+        let credentials = format!(
+            r#"{{ "username": "{}", "password": "s3cure-enough" }}"#,
+            username
+        );
+        let req = build_todo_req_with_json("/users", Method::POST, credentials.clone());
+        app.clone().oneshot(req).await.unwrap();
+        let req = build_todo_req_with_json("/auth/login", Method::POST, credentials);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let token: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        format!("Bearer {}", token["access_token"].as_str().unwrap())
+    }
+
+    #[tokio::test]
+    async fn should_list_todos_of_saved_view() {
+        let labels = LabelRepositoryForMemory::new();
+        let app = create_app(
+            TodoRepositoryForMemory::with_labels(labels.clone()),
+            labels,
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let owner = register_and_login(&app, "olivia").await;
+        let other = register_and_login(&app, "oscar").await;
+        let send = |method: Method, uri: &str, body: Option<&str>, bearer: &str| {
+            let mut req = match body {
+                Some(body) => build_todo_req_with_json(uri, method, body.to_string()),
+                None => build_todo_req_with_empty(uri, method),
+            };
+            req.headers_mut()
+                .insert(header::AUTHORIZATION, bearer.parse().unwrap());
+            app.clone().oneshot(req)
+        };
+        send(
+            Method::POST,
+            "/labels",
+            Some(r#"{ "name": "work" }"#),
+            &owner,
+        )
+        .await
+        .unwrap();
+        for body in [
+            r#"{ "text": "report", "labels": [1] }"#,
+            r#"{ "text": "groceries" }"#,
+            r#"{ "text": "slides", "labels": [1] }"#,
+        ] {
+            let res = send(Method::POST, "/todos", Some(body), &owner)
+                .await
+                .unwrap();
+            assert_eq!(StatusCode::CREATED, res.status());
+        }
+        send(
+            Method::PATCH,
+            "/todos/3",
+            Some(r#"{ "completed": true }"#),
+            &owner,
+        )
+        .await
+        .unwrap();
+
+        let res = send(
+            Method::POST,
+            "/views",
+            Some(r#"{ "name": "soon", "filter": "due<7d" }"#),
+            &owner,
+        )
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        let res = send(
+            Method::POST,
+            "/views",
+            Some(r#"{ "name": "open work", "filter": "completed:false label:work" }"#),
+            &owner,
+        )
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+
+        let res = send(Method::GET, "/views/1/todos", None, &owner)
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let todos: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        let texts: Vec<&str> = todos
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|todo| todo["text"].as_str().unwrap())
+            .collect();
+        assert_eq!(texts, vec!["report"]);
+
+        let res = send(Method::GET, "/views/1/todos?limit=1", None, &owner)
+            .await
+            .unwrap();
+        assert_eq!(res.headers()["x-total-count"], "1");
+        let page: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(page["_links"]["self"]["href"], "/views/1/todos?limit=1");
+
+        let res = send(Method::GET, "/views/1/todos", None, &other)
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        let res = send(Method::DELETE, "/views/1", None, &owner)
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let res = send(Method::GET, "/views", None, &owner).await.unwrap();
+        assert_eq!(res_to_string(res).await, "[]");
+    }
+
+    #[tokio::test]
+    async fn should_scope_todos_by_workspace() {
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let owner = register_and_login(&app, "olivia").await;
+        let viewer = register_and_login(&app, "victor").await;
+        let authorized = |mut req: Request<Body>, bearer: &str| {
+            req.headers_mut()
+                .insert(header::AUTHORIZATION, bearer.parse().unwrap());
+            req
+        };
+        let create_todo = || {
+            build_todo_req_with_json(
+                "/workspaces/2/todos",
+                Method::POST,
+                r#"{ "text": "team todo" }"#.to_string(),
+            )
+        };
+
+        let req = build_todo_req_with_json(
+            "/workspaces",
+            Method::POST,
+            r#"{ "name": "team" }"#.to_string(),
+        );
+        let res = app.clone().oneshot(authorized(req, &owner)).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let res = app.clone().oneshot(create_todo()).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+        let res = app
+            .clone()
+            .oneshot(authorized(create_todo(), &owner))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let todo = res_to_todo(res).await;
+
+        let req = build_todo_req_with_empty("/todos", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res_to_string(res).await, "[]");
+        let req = build_todo_req_with_empty(&format!("/todos/{}", todo.id), Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        let mut req = build_todo_req_with_empty(&format!("/todos/{}", todo.id), Method::GET);
+        req.headers_mut()
+            .insert("x-workspace-id", "2".parse().unwrap());
+        let res = app.clone().oneshot(authorized(req, &owner)).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+
+        let list = || build_todo_req_with_empty("/workspaces/2/todos", Method::GET);
+        let res = app
+            .clone()
+            .oneshot(authorized(list(), &viewer))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        let req = build_todo_req_with_json(
+            "/workspaces/2/members",
+            Method::POST,
+            r#"{ "user_id": 2, "role": "viewer" }"#.to_string(),
+        );
+        let res = app.clone().oneshot(authorized(req, &owner)).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let res = app
+            .clone()
+            .oneshot(authorized(list(), &viewer))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let res = app
+            .oneshot(authorized(create_todo(), &viewer))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::FORBIDDEN, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_assign_todos_within_workspace() {
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let owner = register_and_login(&app, "olivia").await;
+        let assignee = register_and_login(&app, "victor").await;
+        let bystander = register_and_login(&app, "mallory").await;
+        let send = |path: &str, method: Method, body: &str, bearer: &str| {
+            let mut req = build_todo_req_with_json(path, method, body.to_string());
+            req.headers_mut()
+                .insert(header::AUTHORIZATION, bearer.parse().unwrap());
+            app.clone().oneshot(req)
+        };
+        send("/workspaces", Method::POST, r#"{ "name": "team" }"#, &owner)
+            .await
+            .unwrap();
+        for user_id in [2, 3] {
+            let member = format!(r#"{{ "user_id": {}, "role": "member" }}"#, user_id);
+            send("/workspaces/2/members", Method::POST, &member, &owner)
+                .await
+                .unwrap();
+        }
+
+        let body = r#"{ "text": "review", "assignee_id": 4 }"#;
+        let res = send("/workspaces/2/todos", Method::POST, body, &owner)
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        let body = r#"{ "text": "review", "assignee_id": 2 }"#;
+        let res = send("/workspaces/2/todos", Method::POST, body, &owner)
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let todo = res_to_todo(res).await;
+        assert_eq!((todo.owner_id, todo.assignee_id), (Some(1), Some(2)));
+
+        let mine = |bearer: &str| {
+            let mut req = build_todo_req_with_empty("/workspaces/2/todos?assignee=me", Method::GET);
+            req.headers_mut()
+                .insert(header::AUTHORIZATION, bearer.parse().unwrap());
+            app.clone().oneshot(req)
+        };
+        let res = mine(&assignee).await.unwrap();
+        let todos: Vec<Todo> = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(todos.len(), 1);
+        let res = mine(&owner).await.unwrap();
+        assert_eq!(res_to_string(res).await, "[]");
+
+        let path = format!("/workspaces/2/todos/{}", todo.id);
+        let complete = r#"{ "completed": true }"#;
+        let res = send(&path, Method::PATCH, complete, &bystander)
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let res = send(
+            "/workspaces/2",
+            Method::PATCH,
+            r#"{ "assignees_only": true }"#,
+            &owner,
+        )
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let res = send(&path, Method::PATCH, complete, &bystander)
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::FORBIDDEN, res.status());
+        let res = send(&path, Method::PATCH, complete, &assignee)
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_update_notification_settings() {
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let bearer = register_and_login(&app, "nora").await;
+        let authorized = |mut req: Request<Body>| {
+            req.headers_mut()
+                .insert(header::AUTHORIZATION, bearer.parse().unwrap());
+            req
+        };
+
+        let req = build_todo_req_with_empty("/me/notifications", Method::GET);
+        let res = app.clone().oneshot(authorized(req)).await.unwrap();
+        let settings: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(settings["assigned"]["email"], true);
+
+        let req = build_todo_req_with_json(
+            "/me/notifications",
+            Method::PATCH,
+            r#"{ "assigned": { "email": false, "webhook": true } }"#.to_string(),
+        );
+        let res = app.clone().oneshot(authorized(req)).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+
+        let req = build_todo_req_with_empty("/me/notifications", Method::GET);
+        let res = app.clone().oneshot(authorized(req)).await.unwrap();
+        let settings: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(
+            settings["assigned"],
+            serde_json::json!({ "email": false, "webhook": true, "websocket": true, "slack": false })
+        );
+        assert_eq!(settings["due_soon"]["email"], true);
+
+        let req = build_todo_req_with_empty("/me/notifications", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+    }
+    #[tokio::test]
+    async fn should_manage_users_as_admin() {
+        let users = UserRepositoryForMemory::new();
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            users.clone(),
+            Config::default(),
+        );
+        let admin = register_and_login(&app, "ada").await;
+        let bob = register_and_login(&app, "bob").await;
+        register_and_login(&app, "carol").await;
+        cli::grant_admin(&users, "ada").await.unwrap();
+        let authorized = |mut req: Request<Body>, bearer: &str| {
+            req.headers_mut()
+                .insert(header::AUTHORIZATION, bearer.parse().unwrap());
+            req
+        };
+
+        let req = build_todo_req_with_empty("/admin/users", Method::GET);
+        let res = app.clone().oneshot(authorized(req, &bob)).await.unwrap();
+        assert_eq!(StatusCode::FORBIDDEN, res.status());
+
+        let req = build_todo_req_with_empty("/admin/users?q=O&limit=1", Method::GET);
+        let res = app.clone().oneshot(authorized(req, &admin)).await.unwrap();
+        let page: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(page["items"][0]["username"], "carol");
+        assert_eq!(page["items"][0]["role"], "user");
+        let req = build_todo_req_with_empty(
+            &format!(
+                "/admin/users?q=O&limit=1&after={}",
+                page["next_cursor"].as_str().unwrap()
+            ),
+            Method::GET,
+        );
+        let res = app.clone().oneshot(authorized(req, &admin)).await.unwrap();
+        let page: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(page["items"][0]["username"], "bob");
+        assert_eq!(page["next_cursor"], serde_json::Value::Null);
+
+        let req = build_todo_req_with_json(
+            "/todos",
+            Method::POST,
+            r#"{ "text": "bob's todo" }"#.to_string(),
+        );
+        let res = app.clone().oneshot(authorized(req, &bob)).await.unwrap();
+        let todo = res_to_todo(res).await;
+        assert_eq!(todo.owner_id, Some(2));
+
+        let req = build_todo_req_with_json(
+            "/admin/users/2",
+            Method::PATCH,
+            r#"{ "disabled": true }"#.to_string(),
+        );
+        let res = app.clone().oneshot(authorized(req, &admin)).await.unwrap();
+        let account: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert!(account["disabled_at"].is_string());
+        let req = build_todo_req_with_json(
+            "/auth/login",
+            Method::POST,
+            r#"{ "username": "bob", "password": "s3cure-enough" }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::FORBIDDEN, res.status());
+
+        let req = build_todo_req_with_empty("/admin/users/1", Method::DELETE);
+        let res = app.clone().oneshot(authorized(req, &admin)).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        let req = build_todo_req_with_empty("/admin/users/2?transfer_to=9", Method::DELETE);
+        let res = app.clone().oneshot(authorized(req, &admin)).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        let req = build_todo_req_with_empty("/admin/users/2?transfer_to=3", Method::DELETE);
+        let res = app.clone().oneshot(authorized(req, &admin)).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+
+        let req = build_todo_req_with_empty(&format!("/todos/{}", todo.id), Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res_to_todo(res).await.owner_id, Some(3));
+        let req = build_todo_req_with_empty("/admin/users?disabled=true", Method::GET);
+        let res = app.oneshot(authorized(req, &admin)).await.unwrap();
+        let page: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(page["items"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn should_export_data_and_schedule_deletion() {
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let bearer = register_and_login(&app, "erin").await;
+        let authorized = |mut req: Request<Body>| {
+            req.headers_mut()
+                .insert(header::AUTHORIZATION, bearer.parse().unwrap());
+            req
+        };
+        let req = build_todo_req_with_json(
+            "/todos",
+            Method::POST,
+            r#"{ "text": "erin's todo" }"#.to_string(),
+        );
+        app.clone().oneshot(authorized(req)).await.unwrap();
+
+        let req = build_todo_req_with_empty("/me/export", Method::POST);
+        let res = app.clone().oneshot(authorized(req)).await.unwrap();
+        assert_eq!(StatusCode::ACCEPTED, res.status());
+        let location = res.headers()[header::LOCATION]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let mut status = serde_json::Value::Null;
+        for _ in 0..100 {
+            let req = build_todo_req_with_empty(&location, Method::GET);
+            let res = app.clone().oneshot(authorized(req)).await.unwrap();
+            let export: serde_json::Value =
+                serde_json::from_str(&res_to_string(res).await).unwrap();
+            status = export["status"].clone();
+            if status != "pending" {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(status, "ready");
+
+        let req = build_todo_req_with_empty(&format!("{}/archive", location), Method::GET);
+        let res = app.clone().oneshot(authorized(req)).await.unwrap();
+        assert!(res.headers()[header::CONTENT_DISPOSITION]
+            .to_str()
+            .unwrap()
+            .starts_with("attachment"));
+        let archive: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(archive["account"]["username"], "erin");
+        assert_eq!(archive["todos"][0]["text"], "erin's todo");
+        assert_eq!(archive["todos"][0]["workspace_id"], 1);
+        assert_eq!(archive["activity"][0]["action"], "export_requested");
+        let req = build_todo_req_with_empty(&format!("{}/archive", location), Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+
+        let req = build_todo_req_with_empty("/me", Method::DELETE);
+        let res = app.clone().oneshot(authorized(req)).await.unwrap();
+        assert_eq!(StatusCode::ACCEPTED, res.status());
+        let scheduled = res_to_string(res).await;
+        let req = build_todo_req_with_empty("/me", Method::DELETE);
+        let res = app.clone().oneshot(authorized(req)).await.unwrap();
+        assert_eq!(res_to_string(res).await, scheduled);
+        let req = build_todo_req_with_empty("/me/deletion", Method::DELETE);
+        let res = app.clone().oneshot(authorized(req)).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let req = build_todo_req_with_empty("/me/deletion", Method::GET);
+        let res = app.oneshot(authorized(req)).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_enforce_quotas() {
+        let mut config = Config::default();
+        config.quota.max_open_todos = Some(1);
+        config.quota.max_labels = Some(1);
+        let app = create_app(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            config,
+        );
+        let bearer = register_and_login(&app, "ada").await;
+        let authorized = |mut req: Request<Body>| {
+            req.headers_mut()
+                .insert(header::AUTHORIZATION, bearer.parse().unwrap());
+            req
+        };
+        let create = || {
+            build_todo_req_with_json(
+                "/todos",
+                Method::POST,
+                r#"{"text": "should_enforce_quotas"}"#.to_string(),
+            )
+        };
+
+        let res = app.clone().oneshot(authorized(create())).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let res = app.clone().oneshot(authorized(create())).await.unwrap();
+        assert_eq!(StatusCode::PAYMENT_REQUIRED, res.status());
+        let body: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(body["details"]["quota"], "open_todos");
+        assert_eq!(body["details"]["used"], 1);
+        assert_eq!(body["details"]["limit"], 1);
+        // anonymous todos count against nobody
+        let res = app.clone().oneshot(create()).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+
+        let req = build_todo_req_with_json(
+            "/todos/1",
+            Method::PATCH,
+            r#"{"completed": true}"#.to_string(),
+        );
+        app.clone().oneshot(authorized(req)).await.unwrap();
+        let res = app.clone().oneshot(authorized(create())).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let req = build_todo_req_with_json(
+            "/todos/1",
+            Method::PATCH,
+            r#"{"completed": false}"#.to_string(),
+        );
+        let res = app.clone().oneshot(authorized(req)).await.unwrap();
+        assert_eq!(StatusCode::PAYMENT_REQUIRED, res.status());
+
+        let label = |name: &str| {
+            build_todo_req_with_json(
+                "/labels",
+                Method::POST,
+                format!(r#"{{"name": "{}"}}"#, name),
+            )
+        };
+        let res = app
+            .clone()
+            .oneshot(authorized(label("home")))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let res = app
+            .clone()
+            .oneshot(authorized(label("work")))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::PAYMENT_REQUIRED, res.status());
+
+        let req = build_todo_req_with_empty("/me/usage", Method::GET);
+        let res = app.oneshot(authorized(req)).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let usage: serde_json::Value = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(usage["open_todos"]["used"], 1);
+        assert_eq!(usage["open_todos"]["limit"], 1);
+        assert_eq!(usage["workspaces"][0]["workspace_id"], 1);
+        assert_eq!(usage["workspaces"][0]["labels"]["used"], 1);
+    }
+
+    #[tokio::test]
+    async fn should_publish_domain_events() {
+        let events = Events::default();
+        let mut received = events.subscribe();
+        async fn next(received: &mut Receiver<WorkspaceEvent>) -> DomainEvent {
+            let event = tokio::time::timeout(Duration::from_secs(5), received.recv())
+                .await
+                .expect("no event within 5s")
+                .unwrap();
+            assert_eq!(event.workspace_id, 1);
+            event.event
+        }
+        let app = create_app_with_events(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+            events,
+        );
+
+        let req = build_todo_req_with_json(
+            "/todos",
+            Method::POST,
+            r#"{"text": "should_publish_domain_events"}"#.to_string(),
+        );
+        let todo = res_to_todo(app.clone().oneshot(req).await.unwrap()).await;
+        assert_eq!(next(&mut received).await, DomainEvent::TodoCreated(todo));
+        let req = build_todo_req_with_json(
+            "/todos/1",
+            Method::PATCH,
+            r#"{"completed": true}"#.to_string(),
+        );
+        let completed = res_to_todo(app.clone().oneshot(req).await.unwrap()).await;
+        assert_eq!(
+            next(&mut received).await,
+            DomainEvent::TodoUpdated(completed.clone())
+        );
+        assert_eq!(
+            next(&mut received).await,
+            DomainEvent::TodoCompleted(completed)
+        );
+
+        let req =
+            build_todo_req_with_json("/labels", Method::POST, r#"{"name": "home"}"#.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        let label: Label = serde_json::from_str(&res_to_string(res).await).unwrap();
+        assert_eq!(next(&mut received).await, DomainEvent::LabelCreated(label));
+        let req = build_todo_req_with_empty("/labels/1", Method::DELETE);
+        app.oneshot(req).await.unwrap();
+        assert_eq!(next(&mut received).await, DomainEvent::LabelDeleted(1));
+    }
+}
@@ -1,222 +1,19 @@
-mod handlers;
-mod repositories;
-
-use crate::repositories::{
-    label::LabelRepositoryForDb,
-    todo::{TodoRepository, TodoRepositoryForDb},
-};
-use axum::{
-    extract::Extension,
-    routing::{delete, get, post},
-    Router,
-};
-use handlers::{
-    label::{all_label, create_label, delete_label},
-    todo::{all_todo, create_todo, delete_todo, find_todo, update_todo},
-};
-use repositories::label::LabelRepository;
-use std::net::SocketAddr;
-use std::{env, sync::Arc};
+use clap::Parser;
+use my_todo::{cli::Cli, config::Config};
 
 use dotenv::dotenv;
-use hyper::header::CONTENT_TYPE;
-use sqlx::PgPool;
-use tower_http::cors::{Any, CorsLayer, Origin};
 
 #[tokio::main]
-async fn main() {
-    // logging
-    let log_level = env::var("RUST_LOG").unwrap_or("info".to_string());
-    env::set_var("RUST_LOG", log_level);
-    tracing_subscriber::fmt::init();
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
     dotenv().ok();
+    let config = Config::load().unwrap_or_else(|e| {
+        eprintln!("invalid configuration: {}", e);
+        std::process::exit(1);
+    });
 
-    let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
-    tracing::debug!("start connect database...");
-    let pool = PgPool::connect(database_url)
-        .await
-        .expect(&format!("fail connect database, url is [{}]", database_url));
-
-    let app = create_app(
-        TodoRepositoryForDb::new(pool.clone()),
-        LabelRepositoryForDb::new(pool.clone()),
-    );
-    let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
-    tracing::debug!("listening on {}", addr);
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
-}
-
-fn create_app<Todo: TodoRepository, Label: LabelRepository>(
-    todo_repository: Todo,
-    label_repository: Label,
-) -> Router {
-    Router::new()
-        .route("/", get(root))
-        .route("/todos", post(create_todo::<Todo>).get(all_todo::<Todo>))
-        .route(
-            "/todos/:id",
-            get(find_todo::<Todo>)
-                .delete(delete_todo::<Todo>)
-                .patch(update_todo::<Todo>),
-        )
-        .route(
-            "/labels",
-            post(create_label::<Label>).get(all_label::<Label>),
-        )
-        .route("/labels/:id", delete(delete_label::<Label>))
-        .layer(Extension(Arc::new(todo_repository)))
-        .layer(Extension(Arc::new(label_repository)))
-        .layer(
-            CorsLayer::new()
-                .allow_origin(Origin::exact("http://localhost:3001".parse().unwrap()))
-                .allow_methods(Any)
-                .allow_headers(vec![CONTENT_TYPE]),
-        )
-}
-
-async fn root() -> &'static str {
-    "Hello, World!"
-}
-
-#[cfg(test)]
-mod test {
-    use super::*;
-    use crate::repositories::todo::{CreateTodo, Todo, TodoRepositoryForMemory};
-    use axum::response::Response;
-    use axum::{body::Body, http::Request};
-
-    use hyper::{header, Method, StatusCode};
-    use tower::ServiceExt;
-
-    fn build_todo_req_with_json(path: &str, method: Method, json_body: String) -> Request<Body> {
-        Request::builder()
-            .uri(path)
-            .method(method)
-            .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
-            .body(Body::from(json_body))
-            .unwrap()
-    }
-
-    fn build_todo_req_with_empty(path: &str, method: Method) -> Request<Body> {
-        Request::builder()
-            .uri(path)
-            .method(method)
-            .body(Body::empty())
-            .unwrap()
-    }
-
-    async fn res_to_string(res: Response) -> String {
-        let b = res.into_body();
-        let bytes = hyper::body::to_bytes(b).await.unwrap();
-        String::from_utf8(bytes.to_vec()).unwrap()
-    }
-
-    async fn res_to_todo(res: Response) -> Todo {
-        let body = res_to_string(res).await;
-        let todo: Todo = serde_json::from_str(&body).expect(&format!("body: {}", body));
-        todo
-    }
-
-    async fn should_created_todo() {
-        let expected = Todo::new(1, "should_created_todo".to_string());
-
-        let repository = TodoRepositoryForMemory::new();
-        let req = build_todo_req_with_json(
-            "/todos",
-            Method::POST,
-            r#"{"text": "should_created_todo" }"#.to_string(),
-        );
-
-        let res = create_app(repository).oneshot(req).await.unwrap();
-        let todo = res_to_todo(res).await;
-        assert_eq!(expected, todo);
-    }
-
-    async fn should_find_todo() {
-        // 期待値作成
-        let expected = Todo::new(1, "should_find_todo".to_string());
-        // repo作成
-        let repository = TodoRepositoryForMemory::new();
-        // repoから、Todoを作成
-        repository
-            .create(CreateTodo::new("should_find_todo".to_string()))
-            .await
-            .expect("failed create todo");
-        // リクエストを作成
-        let req = build_todo_req_with_empty("/todos/1", Method::GET);
-        // レスポンスを作成
-        let res = create_app(repository).oneshot(req).await.unwrap();
-        // レスポンスから、todoを生成
-        let todo = res_to_todo(res).await;
-        // expected
-        assert_eq!(expected, todo);
-    }
-
-    #[tokio::test]
-    async fn should_get_all_todos() {
-        let expected = Todo::new(1, "should_get_all_todos".to_string());
-        let repository = TodoRepositoryForMemory::new();
-        repository
-            .create(CreateTodo::new("should_get_all_todos".to_string()))
-            .await
-            .expect("failed create todo");
-        let req = build_todo_req_with_empty("/todos", Method::GET);
-        let res = create_app(repository).oneshot(req).await.unwrap();
-        let body = res_to_string(res).await;
-        let todo: Vec<Todo> = serde_json::from_str(&body)
-            .expect(&format!("connot convert TOdo instance. boy: {}", body));
-        assert_eq!(vec![expected], todo)
-    }
-
-    #[tokio::test]
-    async fn should_update_todo() {
-        let expected = Todo::new(1, "should_update_todo".to_string());
-        let repository = TodoRepositoryForMemory::new();
-        repository
-            .create(CreateTodo::new("before_should_update_todo".to_string()))
-            .await
-            .expect("failed create todo");
-
-        let req = build_todo_req_with_json(
-            "/todos/1",
-            Method::PATCH,
-            r#"{
-              "id": 1,
-              "text": "should_update_todo",
-              "completed": false
-            }"#
-            .to_string(),
-        );
-        let res = create_app(repository).oneshot(req).await.unwrap();
-        let todo = res_to_todo(res).await;
-
-        assert_eq!(expected, todo);
-    }
-
-    #[tokio::test]
-    async fn should_delete_todo() {
-        let repository = TodoRepositoryForMemory::new();
-        repository
-            .create(CreateTodo::new("should_delete_todo".to_string()))
-            .await
-            .expect("failed create todo");
-
-        let req = build_todo_req_with_empty("/todos/1", Method::DELETE);
-        let res = create_app(repository).oneshot(req).await.unwrap();
-
-        assert_eq!(StatusCode::NO_CONTENT, res.status());
-    }
+    let _telemetry = my_todo::telemetry::init(&config)?;
+    my_todo::middleware::panic::log_panics();
 
-    #[tokio::test]
-    async fn should_return_hello_world() {
-        let repository = TodoRepositoryForMemory::new();
-        let req = Request::builder().uri("/").body(Body::empty()).unwrap();
-        let res = create_app(repository).oneshot(req).await.unwrap();
-        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
-        let body = String::from_utf8(bytes.to_vec()).unwrap();
-        assert_eq!(body, "Hello, world!!");
-    }
+    my_todo::cli::run(cli, config).await
 }
@@ -0,0 +1,13 @@
+pub mod access_log;
+pub mod cache_control;
+pub mod csrf;
+pub mod etag;
+pub mod forwarded;
+pub mod localize;
+pub mod negotiate;
+pub mod overload;
+pub mod panic;
+pub mod rate_limit;
+pub mod request_id;
+pub mod security_headers;
+pub mod workspace;
@@ -0,0 +1,119 @@
+use std::{
+    sync::{Arc, OnceLock},
+    time::Instant,
+};
+
+use axum::{extract::MatchedPath, http::Request, middleware::Next, response::Response};
+use tracing::Instrument;
+
+use super::request_id::RequestId;
+use crate::telemetry;
+
+/// What the access log line learns only inside the app: the route that
+/// matched and who was signed in. `access_log` adds it to the extensions of
+/// every request; `record_route` and the `AuthUser` extractor fill it in.
+#[derive(Debug, Clone, Default)]
+pub struct LogFields(Arc<Fields>);
+
+#[derive(Debug, Default)]
+struct Fields {
+    route: OnceLock<String>,
+    user_id: OnceLock<i32>,
+}
+
+impl LogFields {
+    pub fn record_route(&self, route: &str) {
+        let _ = self.0.route.set(route.to_string());
+    }
+
+    pub fn record_user(&self, id: i32) {
+        let _ = self.0.user_id.set(id);
+    }
+
+    /// The matched route pattern, e.g. `/todos/:id`, rather than the path,
+    /// so lines group by endpoint. `-` when no route matched.
+    fn route(&self) -> &str {
+        self.0.route.get().map_or("-", String::as_str)
+    }
+
+    fn user_id(&self) -> Option<i32> {
+        self.0.user_id.get().copied()
+    }
+}
+
+/// Logs one line per request with its status and latency, and runs it in a
+/// `request` span so everything logged while serving it carries the
+/// request id. The span continues the caller's trace and is exported as
+/// `<method> <route>`. Must sit inside `request_id`.
+pub async fn access_log<B>(mut req: Request<B>, next: Next<B>) -> Response {
+    let fields = LogFields::default();
+    req.extensions_mut().insert(fields.clone());
+    let request_id = req.extensions().get::<RequestId>().cloned();
+    let span = tracing::info_span!(
+        "request",
+        request_id = request_id.as_ref().map(|id| id.0.as_str()),
+        method = %req.method(),
+        path = req.uri().path(),
+        otel.kind = "server",
+        otel.name = tracing::field::Empty,
+    );
+    telemetry::continue_trace(&span, req.headers());
+    let method = req.method().clone();
+
+    let start = Instant::now();
+    let res = next.run(req).instrument(span.clone()).await;
+    span.record("otel.name", format!("{} {}", method, fields.route()));
+    span.in_scope(|| {
+        tracing::info!(
+            target: "access",
+            route = fields.route(),
+            status = res.status().as_u16(),
+            latency_ms = start.elapsed().as_millis() as u64,
+            user_id = fields.user_id(),
+            "request finished"
+        )
+    });
+    res
+}
+
+/// Hands the route pattern to `access_log`, which sits outside the router
+/// and never sees `MatchedPath` itself.
+pub async fn record_route<B>(req: Request<B>, next: Next<B>) -> Response {
+    if let (Some(route), Some(fields)) = (
+        req.extensions().get::<MatchedPath>(),
+        req.extensions().get::<LogFields>(),
+    ) {
+        fields.record_route(route.as_str());
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{body::Body, middleware::from_fn, routing::get, Extension, Router};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn should_record_route_and_user() {
+        let fields = LogFields::default();
+        let app = Router::new()
+            .route(
+                "/todos/:id",
+                get(|Extension(fields): Extension<LogFields>| async move {
+                    fields.record_user(7);
+                }),
+            )
+            .layer(from_fn(record_route));
+        let req = Request::builder()
+            .uri("/todos/1")
+            .extension(fields.clone())
+            .body(Body::empty())
+            .unwrap();
+
+        app.oneshot(req).await.unwrap();
+        assert_eq!(fields.route(), "/todos/:id");
+        assert_eq!(fields.user_id(), Some(7));
+        assert_eq!(LogFields::default().route(), "-");
+    }
+}
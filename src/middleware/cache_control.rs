@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{MatchedPath, State},
+    http::{header::CACHE_CONTROL, HeaderValue, Method, Request},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::config::CacheControlConfig;
+
+/// The `Cache-Control` values `cache_control` adds, by route class; `None`
+/// where the configured value is empty.
+#[derive(Debug, Clone, Default)]
+pub struct CacheControl(Arc<Policies>);
+
+#[derive(Debug, Default)]
+struct Policies {
+    mutations: Option<HeaderValue>,
+    lists: Option<HeaderValue>,
+    items: Option<HeaderValue>,
+}
+
+impl CacheControl {
+    /// Values were checked when the config was loaded.
+    pub fn from_config(config: &CacheControlConfig) -> anyhow::Result<Self> {
+        if !config.enabled {
+            return Ok(Self::default());
+        }
+        let value = |value: &str| -> anyhow::Result<_> {
+            Ok((!value.is_empty()).then(|| value.parse()).transpose()?)
+        };
+        Ok(Self(Arc::new(Policies {
+            mutations: value(&config.mutations)?,
+            lists: value(&config.lists)?,
+            items: value(&config.items)?,
+        })))
+    }
+
+    /// Successful reads of a route ending in a parameter, e.g.
+    /// `/todos/:id`, are items and other successful reads lists; anything
+    /// else must not be stored.
+    fn policy(&self, method: &Method, route: &str, success: bool) -> Option<&HeaderValue> {
+        let read = method == Method::GET || method == Method::HEAD;
+        if !(read && success) {
+            return self.0.mutations.as_ref();
+        }
+        match route.rsplit('/').next() {
+            Some(last) if last.starts_with(':') => self.0.items.as_ref(),
+            _ => self.0.lists.as_ref(),
+        }
+    }
+}
+
+/// Adds the configured `Cache-Control` to answers from routes that did not
+/// set one. Static files and other fallback answers are left alone.
+pub async fn cache_control<B>(
+    State(policies): State<CacheControl>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let method = req.method().clone();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|route| route.as_str().to_string());
+    let mut res = next.run(req).await;
+    let Some(route) = route else {
+        return res;
+    };
+    let success = res.status().is_success() || res.status().as_u16() == 304;
+    if let Some(value) = policies.policy(&method, &route, success) {
+        if !res.headers().contains_key(CACHE_CONTROL) {
+            res.headers_mut().insert(CACHE_CONTROL, value.clone());
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn should_pick_policy_by_route_class() {
+        let policies = CacheControl::from_config(&Config::default().cache_control).unwrap();
+        let policy = |method: Method, route: &str, success: bool| {
+            policies
+                .policy(&method, route, success)
+                .map(|value| value.to_str().unwrap().to_string())
+        };
+
+        assert_eq!(
+            policy(Method::GET, "/todos", true).as_deref(),
+            Some("private, max-age=5, must-revalidate")
+        );
+        assert_eq!(
+            policy(Method::GET, "/todos/:id", true),
+            Some(Config::default().cache_control.items)
+        );
+        assert_eq!(
+            policy(Method::GET, "/todos/:id/revisions", true),
+            Some(Config::default().cache_control.lists)
+        );
+        assert_eq!(
+            policy(Method::POST, "/todos", true).as_deref(),
+            Some("no-store")
+        );
+        assert_eq!(
+            policy(Method::GET, "/todos/:id", false).as_deref(),
+            Some("no-store")
+        );
+
+        let disabled = CacheControl::from_config(&CacheControlConfig {
+            enabled: false,
+            ..Config::default().cache_control
+        })
+        .unwrap();
+        assert_eq!(disabled.policy(&Method::POST, "/todos", true), None);
+    }
+}
@@ -0,0 +1,86 @@
+use axum::{
+    body::{boxed, Empty, Full},
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_NONE_MATCH},
+        HeaderValue, Method, Request, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+use crate::handlers::error::ApiError;
+
+/// A weak tag over a response body: equal bodies, equal tags. Weak because
+/// the body is hashed before `negotiate` re-encodes or compression squeezes
+/// it, so one tag stands for every encoding of the same todos.
+fn weak_etag(body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    let hex: String = digest[..16].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("W/\"{}\"", hex)
+}
+
+/// Whether an `If-None-Match` list names `tag`, comparing weakly.
+fn none_match_hits(header: &HeaderValue, tag: &str) -> bool {
+    let strip = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let tag = strip(tag);
+    header.to_str().is_ok_and(|list| {
+        list.split(',')
+            .any(|candidate| candidate.trim() == "*" || strip(candidate) == tag)
+    })
+}
+
+/// Tags successful `GET` answers with a weak `ETag` and answers `304 Not
+/// Modified`, without a body, when `If-None-Match` already names it. The
+/// handler still runs; what polling clients save is the transfer.
+pub async fn etag<B>(req: Request<B>, next: Next<B>) -> Response {
+    if req.method() != Method::GET {
+        return next.run(req).await;
+    }
+    let if_none_match = req.headers().get(IF_NONE_MATCH).cloned();
+    let res = next.run(req).await;
+    if res.status() != StatusCode::OK {
+        return res;
+    }
+
+    let (mut parts, body) = res.into_parts();
+    let bytes = match hyper::body::to_bytes(body).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::error!("reading a response body for its ETag failed: {}", e);
+            return ApiError::internal("Unexpected Error").into_response();
+        }
+    };
+    let tag = weak_etag(&bytes);
+    parts.headers.insert(
+        ETAG,
+        HeaderValue::from_str(&tag).expect("ETags are header safe"),
+    );
+    if if_none_match.is_some_and(|header| none_match_hits(&header, &tag)) {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(CONTENT_TYPE);
+        parts.headers.remove(CONTENT_LENGTH);
+        return Response::from_parts(parts, boxed(Empty::new()));
+    }
+    Response::from_parts(parts, boxed(Full::from(bytes)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_match_etags_weakly() {
+        let tag = weak_etag(b"[]");
+        assert_eq!(tag, weak_etag(b"[]"));
+        assert_ne!(tag, weak_etag(b"[1]"));
+        assert!(tag.starts_with("W/\"") && tag.len() == 36);
+
+        let header = |value: &str| HeaderValue::from_str(value).unwrap();
+        assert!(none_match_hits(&header(&tag), &tag));
+        assert!(none_match_hits(&header(&tag[2..]), &tag));
+        assert!(none_match_hits(&header(&format!("\"x\", {}", tag)), &tag));
+        assert!(none_match_hits(&header("*"), &tag));
+        assert!(!none_match_hits(&header("W/\"x\""), &tag));
+    }
+}
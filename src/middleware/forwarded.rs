@@ -0,0 +1,170 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{header::HOST, HeaderMap, HeaderName, Request},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::config::IpNet;
+
+static X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+static X_FORWARDED_PROTO: HeaderName = HeaderName::from_static("x-forwarded-proto");
+
+/// Who sent a request, seen through any trusted proxies. `forwarded` adds it
+/// to the extensions of requests that came over a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientInfo {
+    pub ip: IpAddr,
+    pub https: bool,
+}
+
+impl ClientInfo {
+    /// `path` as an absolute URL on the host the client asked for, or as it
+    /// is without a `Host` header.
+    pub fn url(&self, headers: &HeaderMap, path: &str) -> String {
+        match headers.get(HOST).and_then(|host| host.to_str().ok()) {
+            Some(host) => {
+                let scheme = if self.https { "https" } else { "http" };
+                format!("{}://{}{}", scheme, host, path)
+            }
+            None => path.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    proxies: Arc<Vec<IpNet>>,
+    /// Whether connections to this server are TLS.
+    https: bool,
+}
+
+impl TrustedProxies {
+    pub fn new(proxies: Vec<IpNet>, https: bool) -> Self {
+        Self {
+            proxies: Arc::new(proxies),
+            https,
+        }
+    }
+
+    fn trusts(&self, ip: IpAddr) -> bool {
+        self.proxies.iter().any(|net| net.contains(ip))
+    }
+
+    /// The client behind `peer`. `X-Forwarded-For` is read from the right,
+    /// where the nearest proxy appended, up to the first address that is not
+    /// a trusted proxy; anything further left could have been made up by
+    /// the client. The scheme is the last one a trusted proxy reported.
+    fn client(&self, peer: IpAddr, headers: &HeaderMap) -> ClientInfo {
+        if !self.trusts(peer) {
+            return ClientInfo {
+                ip: peer,
+                https: self.https,
+            };
+        }
+
+        let mut ip = peer;
+        for hop in list(headers, &X_FORWARDED_FOR).into_iter().rev() {
+            let Ok(hop) = hop.parse() else {
+                break;
+            };
+            ip = hop;
+            if !self.trusts(hop) {
+                break;
+            }
+        }
+        let https = match list(headers, &X_FORWARDED_PROTO).last() {
+            Some(proto) => proto.eq_ignore_ascii_case("https"),
+            None => self.https,
+        };
+        ClientInfo { ip, https }
+    }
+}
+
+/// The comma-separated values of every `name` header, in order.
+fn list<'a>(headers: &'a HeaderMap, name: &HeaderName) -> Vec<&'a str> {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+/// Records the `ClientInfo` that rate limiting, login lockouts and logs go
+/// by. Must sit outside of them.
+pub async fn forwarded<B>(
+    State(proxies): State<TrustedProxies>,
+    mut req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if let Some(ConnectInfo(peer)) = req.extensions().get::<ConnectInfo<SocketAddr>>().copied() {
+        let client = proxies.client(peer.ip(), req.headers());
+        req.extensions_mut().insert(client);
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn should_believe_only_trusted_proxies() {
+        let proxies = TrustedProxies::new(vec!["10.0.0.0/8".parse().unwrap()], false);
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        let forwarded = headers(&[
+            ("x-forwarded-for", "1.1.1.1, 2.2.2.2"),
+            ("x-forwarded-for", "10.0.0.2"),
+            ("x-forwarded-proto", "http, https"),
+        ]);
+
+        // 1.1.1.1 may be forged by 2.2.2.2, which the proxies saw connect
+        let client = proxies.client(ip("10.0.0.1"), &forwarded);
+        assert_eq!(client.ip, ip("2.2.2.2"));
+        assert!(client.https);
+
+        let client = proxies.client(ip("3.3.3.3"), &forwarded);
+        assert_eq!(
+            client,
+            ClientInfo {
+                ip: ip("3.3.3.3"),
+                https: false
+            }
+        );
+
+        let client = proxies.client(ip("10.0.0.1"), &headers(&[]));
+        assert_eq!(client.ip, ip("10.0.0.1"));
+        let garbage = headers(&[("x-forwarded-for", "unknown, 10.0.0.3")]);
+        assert_eq!(proxies.client(ip("10.0.0.1"), &garbage).ip, ip("10.0.0.3"));
+    }
+
+    #[test]
+    fn should_build_urls_with_forwarded_scheme() {
+        let client = ClientInfo {
+            ip: [127, 0, 0, 1].into(),
+            https: true,
+        };
+        let url = client.url(&headers(&[("host", "todo.example.com")]), "/me/exports/1");
+        assert_eq!(url, "https://todo.example.com/me/exports/1");
+        assert_eq!(
+            client.url(&HeaderMap::new(), "/me/exports/1"),
+            "/me/exports/1"
+        );
+    }
+}
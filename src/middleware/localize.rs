@@ -0,0 +1,39 @@
+use axum::{
+    http::{
+        header::{CONTENT_LANGUAGE, CONTENT_LENGTH, VARY},
+        HeaderValue, Request,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use crate::{handlers::error::ErrorBody, i18n::Locale};
+
+/// Renders error bodies again in the language `Accept-Language` prefers.
+/// Must sit inside `negotiate`, which only re-encodes what is JSON by then.
+pub async fn localize<B>(req: Request<B>, next: Next<B>) -> Response {
+    let locale = Locale::preferred(req.headers());
+    let mut res = next.run(req).await;
+    res.headers_mut()
+        .append(VARY, HeaderValue::from_static("accept-language"));
+    let Some(body) = res.extensions().get::<ErrorBody>() else {
+        return res;
+    };
+    if locale == Locale::En {
+        return res;
+    }
+
+    let body = ErrorBody {
+        message: locale.translate(&body.message),
+        ..body.clone()
+    };
+    let (mut parts, _) = res.into_parts();
+    let (_, translated) = Json(&body).into_response().into_parts();
+    parts
+        .headers
+        .insert(CONTENT_LANGUAGE, HeaderValue::from_static(locale.tag()));
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.extensions.insert(body);
+    Response::from_parts(parts, translated)
+}
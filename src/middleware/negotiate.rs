@@ -0,0 +1,196 @@
+use axum::{
+    body::{boxed, Full},
+    http::{
+        header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE, VARY},
+        HeaderMap, HeaderValue, Request,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+
+use crate::handlers::error::ApiError;
+
+/// The body encodings the API speaks besides JSON. Handlers work with JSON
+/// only: `negotiate` converts their responses and `parse_json` their
+/// request bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyFormat {
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+impl BodyFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            BodyFormat::Json => "application/json",
+            BodyFormat::MessagePack => "application/msgpack",
+            BodyFormat::Cbor => "application/cbor",
+        }
+    }
+
+    fn from_essence(essence: &str) -> Option<Self> {
+        match essence {
+            "application/json" => Some(BodyFormat::Json),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(BodyFormat::MessagePack)
+            }
+            "application/cbor" => Some(BodyFormat::Cbor),
+            _ => None,
+        }
+    }
+
+    /// The binary format a `Content-Type` header names; JSON is left to
+    /// the callers, which also accept `+json` types.
+    pub fn binary_content_type(headers: &HeaderMap) -> Option<Self> {
+        let essence = headers
+            .get(CONTENT_TYPE)?
+            .to_str()
+            .ok()?
+            .split(';')
+            .next()?
+            .trim()
+            .to_ascii_lowercase();
+        Self::from_essence(&essence).filter(|format| *format != BodyFormat::Json)
+    }
+
+    /// The format `Accept` ranks highest, earlier ranges winning ties.
+    /// Wildcards and headers naming nothing we speak mean JSON.
+    pub fn preferred(headers: &HeaderMap) -> Self {
+        let mut best = (BodyFormat::Json, 0.0);
+        let ranges = headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','));
+        for range in ranges {
+            let mut params = range.split(';');
+            let essence = params
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_ascii_lowercase();
+            let q = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if let Some(format) = Self::from_essence(&essence) {
+                if q > best.1 {
+                    best = (format, q);
+                }
+            }
+        }
+        best.0
+    }
+
+    pub fn encode(self, value: &Value) -> anyhow::Result<Vec<u8>> {
+        Ok(match self {
+            BodyFormat::Json => serde_json::to_vec(value)?,
+            BodyFormat::MessagePack => rmp_serde::to_vec_named(value)?,
+            BodyFormat::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(value, &mut bytes)?;
+                bytes
+            }
+        })
+    }
+
+    pub fn decode(self, bytes: &[u8]) -> Result<Value, String> {
+        match self {
+            BodyFormat::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+            BodyFormat::MessagePack => rmp_serde::from_slice(bytes).map_err(|e| e.to_string()),
+            BodyFormat::Cbor => ciborium::from_reader(bytes).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// Re-encodes JSON responses in the format the request's `Accept` prefers.
+pub async fn negotiate<B>(req: Request<B>, next: Next<B>) -> Response {
+    let format = BodyFormat::preferred(req.headers());
+    let mut res = next.run(req).await;
+    res.headers_mut()
+        .append(VARY, HeaderValue::from_static("accept"));
+    let is_json = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes() == b"application/json");
+    if format == BodyFormat::Json || !is_json {
+        return res;
+    }
+
+    let (mut parts, body) = res.into_parts();
+    let encoded = async {
+        let bytes = hyper::body::to_bytes(body)
+            .await
+            .map_err(|e| anyhow::anyhow!("reading response body: {}", e))?;
+        let value: Value = serde_json::from_slice(&bytes)?;
+        format.encode(&value)
+    };
+    match encoded.await {
+        Ok(bytes) => {
+            parts.headers.insert(
+                CONTENT_TYPE,
+                HeaderValue::from_static(format.content_type()),
+            );
+            parts.headers.remove(CONTENT_LENGTH);
+            Response::from_parts(parts, boxed(Full::from(bytes)))
+        }
+        Err(e) => {
+            tracing::error!("re-encoding a response failed: {:?}", e);
+            ApiError::internal("Unexpected Error").into_response()
+        }
+    }
+}
+
+/// Request bodies in a binary format, as the JSON `parse_json` reads.
+pub fn binary_to_json(format: BodyFormat, bytes: &[u8]) -> Result<Vec<u8>, ApiError> {
+    let value = format.decode(bytes).map_err(|e| {
+        ApiError::new(
+            axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+            format!("Body parse error: [{}]", e),
+        )
+    })?;
+    serde_json::to_vec(&value).map_err(|e| anyhow::Error::from(e).into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn accept(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn should_pick_preferred_format() {
+        assert_eq!(BodyFormat::preferred(&HeaderMap::new()), BodyFormat::Json);
+        assert_eq!(BodyFormat::preferred(&accept("*/*")), BodyFormat::Json);
+        assert_eq!(
+            BodyFormat::preferred(&accept("application/msgpack")),
+            BodyFormat::MessagePack
+        );
+        assert_eq!(
+            BodyFormat::preferred(&accept(
+                "application/json;q=0.5, application/cbor, application/msgpack"
+            )),
+            BodyFormat::Cbor
+        );
+        assert_eq!(
+            BodyFormat::preferred(&accept("application/cbor;q=0.2, application/json")),
+            BodyFormat::Json
+        );
+    }
+
+    #[test]
+    fn should_round_trip_binary_formats() {
+        let value = json!({"id": 1, "text": "milk", "labels": [], "assignee_id": null});
+        for format in [BodyFormat::MessagePack, BodyFormat::Cbor] {
+            let bytes = format.encode(&value).unwrap();
+            assert_eq!(format.decode(&bytes).unwrap(), value);
+        }
+    }
+}
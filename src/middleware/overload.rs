@@ -0,0 +1,30 @@
+use axum::{http::StatusCode, BoxError};
+use tower::{load_shed::error::Overloaded, timeout::error::Elapsed};
+
+use crate::handlers::error::ApiError;
+
+/// Turns errors from the timeout and load-shed layers into JSON responses.
+pub async fn handle_overload_error(error: BoxError) -> ApiError {
+    if error.is::<Elapsed>() {
+        ApiError::new(StatusCode::REQUEST_TIMEOUT, "Request Timeout")
+    } else if error.is::<Overloaded>() {
+        ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "Service Unavailable")
+    } else {
+        tracing::error!("unhandled middleware error: {}", error);
+        ApiError::internal("Unexpected Error")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn should_map_middleware_errors() {
+        let error = handle_overload_error(Box::new(Elapsed::new())).await;
+        assert_eq!(error.status(), StatusCode::REQUEST_TIMEOUT);
+
+        let error = handle_overload_error(Box::new(Overloaded::new())).await;
+        assert_eq!(error.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
@@ -0,0 +1,81 @@
+use std::{any::Any, backtrace::Backtrace};
+
+use axum::response::{IntoResponse, Response};
+
+use super::request_id::RequestId;
+use crate::handlers::error::ApiError;
+
+/// For `CatchPanicLayer`: answers a panicking handler with the usual 500
+/// body instead of dropping the connection. The payload goes along as the
+/// error's cause, so error reports name it.
+pub fn handle_panic(payload: Box<dyn Any + Send + 'static>) -> Response {
+    let message = match (
+        payload.downcast_ref::<&str>(),
+        payload.downcast_ref::<String>(),
+    ) {
+        (Some(message), _) => message.to_string(),
+        (None, Some(message)) => message.clone(),
+        (None, None) => "non-string panic payload".to_string(),
+    };
+    ApiError::internal("Unexpected Error")
+        .with_cause(format!("panic: {}", message))
+        .into_response()
+}
+
+/// Logs every panic at error level with its full backtrace, and the id of
+/// the request it happened in, in place of the default hook's output.
+pub fn log_panics() {
+    std::panic::set_hook(Box::new(|info| {
+        let request_id = RequestId::current();
+        tracing::error!(
+            request_id = request_id.as_ref().map(|id| id.0.as_str()),
+            backtrace = %Backtrace::force_capture(),
+            "{}",
+            info
+        );
+    }));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+        middleware::from_fn,
+        routing::get,
+        Router,
+    };
+    use tower::ServiceExt;
+    use tower_http::catch_panic::CatchPanicLayer;
+
+    use crate::{handlers::error::ErrorCause, middleware::request_id::request_id};
+
+    #[tokio::test]
+    async fn should_answer_panics_with_error_body() {
+        let app = Router::new()
+            .route("/", get(|| async { panic!("boom") as &str }))
+            .layer(CatchPanicLayer::custom(handle_panic))
+            .layer(from_fn(request_id));
+        let req = Request::builder()
+            .uri("/")
+            .header("x-request-id", "req-1")
+            .body(Body::empty())
+            .unwrap();
+
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let cause = res.extensions().get::<ErrorCause>();
+        assert_eq!(cause.unwrap().0, "panic: boom");
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "status": 500,
+                "message": "Unexpected Error",
+                "request_id": "req-1"
+            })
+        );
+    }
+}
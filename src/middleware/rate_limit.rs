@@ -0,0 +1,272 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    async_trait,
+    extract::State,
+    http::{header::RETRY_AFTER, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use super::forwarded::ClientInfo;
+use crate::{config::RateLimitConfig, handlers::error::ApiError};
+
+/// Token bucket parameters: `burst` tokens at most, refilled at
+/// `requests` per `window`.
+#[derive(Debug, Clone, Copy)]
+pub struct Quota {
+    pub burst: u32,
+    pub requests: u32,
+    pub window: Duration,
+}
+
+impl Quota {
+    fn refill_per_sec(&self) -> f64 {
+        self.requests as f64 / self.window.as_secs_f64()
+    }
+}
+
+impl From<&RateLimitConfig> for Quota {
+    fn from(config: &RateLimitConfig) -> Self {
+        Self {
+            burst: config.burst,
+            requests: config.requests,
+            window: Duration::from_secs(config.window_secs),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Allowed,
+    Limited { retry_after: Duration },
+}
+
+#[async_trait]
+pub trait RateLimitStore: Send + Sync + 'static {
+    async fn hit(&self, key: &str, quota: &Quota) -> anyhow::Result<Decision>;
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+const MAX_MEMORY_KEYS: usize = 10_000;
+
+#[derive(Debug, Default)]
+pub struct MemoryRateLimitStore {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl MemoryRateLimitStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn hit_at(&self, key: &str, quota: &Quota, now: Instant) -> Decision {
+        let mut buckets = self.buckets.lock().unwrap();
+        let rate = quota.refill_per_sec();
+        if buckets.len() >= MAX_MEMORY_KEYS {
+            // buckets that refilled completely carry no information
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate
+                    < quota.burst as f64
+            });
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: quota.burst as f64,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(quota.burst as f64);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Decision::Allowed
+        } else {
+            Decision::Limited {
+                retry_after: Duration::from_secs_f64((1.0 - bucket.tokens) / rate),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn hit(&self, key: &str, quota: &Quota) -> anyhow::Result<Decision> {
+        Ok(self.hit_at(key, quota, Instant::now()))
+    }
+}
+
+#[cfg(feature = "redis")]
+pub use self::redis_store::RedisRateLimitStore;
+
+#[cfg(feature = "redis")]
+mod redis_store {
+    use super::*;
+    use redis::{aio::ConnectionManager, Script};
+    use tokio::sync::OnceCell;
+
+    /// Same token bucket as the memory store, evaluated atomically in Redis
+    /// so every instance shares one budget per client.
+    const TOKEN_BUCKET: &str = r#"
+        local capacity = tonumber(ARGV[1])
+        local rate = tonumber(ARGV[2])
+        local now = tonumber(ARGV[3])
+        local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+        local tokens = tonumber(bucket[1]) or capacity
+        local ts = tonumber(bucket[2]) or now
+        tokens = math.min(capacity, tokens + (now - ts) * rate)
+        local retry = 0
+        if tokens >= 1 then
+            tokens = tokens - 1
+        else
+            retry = math.ceil((1 - tokens) / rate)
+        end
+        redis.call('HSET', KEYS[1], 'tokens', tokens, 'ts', now)
+        redis.call('PEXPIRE', KEYS[1], math.ceil(capacity / rate))
+        return retry
+    "#;
+
+    pub struct RedisRateLimitStore {
+        client: redis::Client,
+        connection: OnceCell<ConnectionManager>,
+        script: Script,
+    }
+
+    impl RedisRateLimitStore {
+        pub fn new(url: &str) -> anyhow::Result<Self> {
+            Ok(Self {
+                client: redis::Client::open(url)?,
+                connection: OnceCell::new(),
+                script: Script::new(TOKEN_BUCKET),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl RateLimitStore for RedisRateLimitStore {
+        async fn hit(&self, key: &str, quota: &Quota) -> anyhow::Result<Decision> {
+            let mut connection = self
+                .connection
+                .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+                .await?
+                .clone();
+            let now_ms = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_millis() as u64;
+            let retry_ms: u64 = self
+                .script
+                .key(format!("rate_limit:{}", key))
+                .arg(quota.burst)
+                .arg(quota.refill_per_sec() / 1000.0)
+                .arg(now_ms)
+                .invoke_async(&mut connection)
+                .await?;
+
+            Ok(match retry_ms {
+                0 => Decision::Allowed,
+                ms => Decision::Limited {
+                    retry_after: Duration::from_millis(ms),
+                },
+            })
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimiter {
+    store: Arc<dyn RateLimitStore>,
+    quota: Quota,
+}
+
+impl RateLimiter {
+    pub fn new(store: Arc<dyn RateLimitStore>, quota: Quota) -> Self {
+        Self { store, quota }
+    }
+
+    pub fn from_config(config: &RateLimitConfig) -> anyhow::Result<Self> {
+        let store: Arc<dyn RateLimitStore> = match &config.redis_url {
+            #[cfg(feature = "redis")]
+            Some(url) => Arc::new(RedisRateLimitStore::new(url)?),
+            #[cfg(not(feature = "redis"))]
+            Some(_) => anyhow::bail!("rate_limit.redis_url needs the `redis` feature"),
+            None => Arc::new(MemoryRateLimitStore::new()),
+        };
+
+        Ok(Self::new(store, Quota::from(config)))
+    }
+}
+
+/// Rejects clients that ran out of tokens with 429 and `Retry-After`.
+/// Requests without a `ClientInfo` (e.g. in-process `oneshot` calls) are
+/// not limited because they cannot be attributed to a client.
+pub async fn rate_limit<B>(
+    State(limiter): State<RateLimiter>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let Some(client) = req.extensions().get::<ClientInfo>().copied() else {
+        return next.run(req).await;
+    };
+    let key = client.ip.to_string();
+
+    match limiter.store.hit(&key, &limiter.quota).await {
+        Ok(Decision::Allowed) => next.run(req).await,
+        Ok(Decision::Limited { retry_after }) => {
+            tracing::debug!("rate limited {}", key);
+            let secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+            let mut res =
+                ApiError::new(StatusCode::TOO_MANY_REQUESTS, "Too Many Requests").into_response();
+            res.headers_mut().insert(RETRY_AFTER, secs.into());
+            res
+        }
+        Err(e) => {
+            // an unavailable limiter backend must not take the API down
+            tracing::warn!("rate limit store failed: {:?}", e);
+            next.run(req).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_refill_tokens_over_time() {
+        let store = MemoryRateLimitStore::new();
+        let quota = Quota {
+            burst: 2,
+            requests: 1,
+            window: Duration::from_secs(10),
+        };
+        let start = Instant::now();
+
+        assert_eq!(store.hit_at("a", &quota, start), Decision::Allowed);
+        assert_eq!(store.hit_at("a", &quota, start), Decision::Allowed);
+        assert_eq!(
+            store.hit_at("a", &quota, start),
+            Decision::Limited {
+                retry_after: Duration::from_secs(10)
+            }
+        );
+        // other clients have their own bucket
+        assert_eq!(store.hit_at("b", &quota, start), Decision::Allowed);
+
+        let later = start + Duration::from_secs(10);
+        assert_eq!(store.hit_at("a", &quota, later), Decision::Allowed);
+        assert!(matches!(
+            store.hit_at("a", &quota, later),
+            Decision::Limited { .. }
+        ));
+    }
+}
@@ -0,0 +1,75 @@
+use axum::{
+    http::{header::HeaderName, HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+use rand::{rngs::OsRng, RngCore};
+
+pub static REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Names one request in responses, logs and error reports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+tokio::task_local! {
+    static CURRENT: RequestId;
+}
+
+impl RequestId {
+    /// The id of the request being served, for code that is not handed it.
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+
+    fn generate() -> Self {
+        let mut bytes = [0; 16];
+        OsRng.fill_bytes(&mut bytes);
+        Self(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+
+    /// An id a proxy in front already gave the request, if it is short and
+    /// printable enough to echo.
+    fn given(value: &HeaderValue) -> Option<Self> {
+        let id = value.to_str().ok()?;
+        (!id.is_empty()
+            && id.len() <= MAX_REQUEST_ID_LEN
+            && id.bytes().all(|b| b.is_ascii_graphic()))
+        .then(|| Self(id.to_string()))
+    }
+}
+
+/// Adds a `RequestId` extension and answers with it in `X-Request-Id`,
+/// keeping the one a proxy sent. Must wrap everything that reports it.
+pub async fn request_id<B>(mut req: Request<B>, next: Next<B>) -> Response {
+    let id = req
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(RequestId::given)
+        .unwrap_or_else(RequestId::generate);
+    let value = HeaderValue::from_str(&id.0).expect("request ids are header safe");
+    req.extensions_mut().insert(id.clone());
+
+    let mut res = CURRENT.scope(id, next.run(req)).await;
+    res.headers_mut().insert(REQUEST_ID_HEADER.clone(), value);
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_keep_only_sane_request_ids() {
+        let given = |value: &str| RequestId::given(&HeaderValue::from_str(value).unwrap());
+        assert_eq!(given("abc-123"), Some(RequestId("abc-123".to_string())));
+        assert_eq!(given(""), None);
+        assert_eq!(given("has space"), None);
+        assert_eq!(given(&"a".repeat(MAX_REQUEST_ID_LEN + 1)), None);
+
+        let generated = RequestId::generate();
+        assert_eq!(generated.0.len(), 32);
+        assert_ne!(generated, RequestId::generate());
+    }
+}
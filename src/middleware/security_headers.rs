@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::{
+        header::{
+            CONTENT_SECURITY_POLICY, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY,
+            X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+        },
+        HeaderName, HeaderValue, Request,
+    },
+    middleware::Next,
+    response::Response,
+};
+
+use crate::config::SecurityHeadersConfig;
+
+/// The headers `security_headers` adds, as configured.
+#[derive(Debug, Clone, Default)]
+pub struct SecurityHeaders(Arc<Vec<(HeaderName, HeaderValue)>>);
+
+impl SecurityHeaders {
+    /// Leaves out the headers configured as empty; values were checked when
+    /// the config was loaded.
+    pub fn from_config(config: &SecurityHeadersConfig) -> anyhow::Result<Self> {
+        if !config.enabled {
+            return Ok(Self::default());
+        }
+        let headers = [
+            (X_CONTENT_TYPE_OPTIONS, &config.content_type_options),
+            (X_FRAME_OPTIONS, &config.frame_options),
+            (REFERRER_POLICY, &config.referrer_policy),
+            (STRICT_TRANSPORT_SECURITY, &config.strict_transport_security),
+            (CONTENT_SECURITY_POLICY, &config.content_security_policy),
+        ]
+        .into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(name, value)| Ok((name, value.parse()?)))
+        .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self(Arc::new(headers)))
+    }
+}
+
+/// Adds the configured headers to responses that did not set them, so a
+/// route may still send a policy of its own.
+pub async fn security_headers<B>(
+    State(headers): State<SecurityHeaders>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let mut res = next.run(req).await;
+    for (name, value) in headers.0.iter() {
+        if !res.headers().contains_key(name) {
+            res.headers_mut().insert(name.clone(), value.clone());
+        }
+    }
+    res
+}
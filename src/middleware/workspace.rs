@@ -0,0 +1,86 @@
+use axum::{
+    http::{header::HeaderName, uri::PathAndQuery, Request, Uri},
+    middleware::Next,
+    response::Response,
+};
+
+/// Names the workspace of a request that has no `/workspaces/:id` prefix.
+pub static WORKSPACE_HEADER: HeaderName = HeaderName::from_static("x-workspace-id");
+
+/// Resources that may be addressed under `/workspaces/:id`.
+const SCOPED_RESOURCES: &[&str] = &[
+    "todos",
+    "labels",
+    "views",
+    "custom-fields",
+    "burndown",
+    "board",
+    "slack",
+    "telegram",
+    "email",
+];
+
+/// The workspace taken from the path by `workspace_prefix`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkspacePath(pub i32);
+
+/// Serves `/workspaces/:id/todos/...`, `/workspaces/:id/labels/...`,
+/// `/workspaces/:id/views/...`, `/workspaces/:id/burndown` and
+/// `/workspaces/:id/board/...` by the unprefixed routes, leaving the id
+/// behind as a `WorkspacePath` extension.
+/// Must wrap the whole router so the rewrite happens before routing.
+pub async fn workspace_prefix<B>(mut req: Request<B>, next: Next<B>) -> Response {
+    if let Some((workspace_id, rest)) = split_prefix(req.uri().path()) {
+        let path_and_query = match req.uri().query() {
+            Some(query) => format!("{}?{}", rest, query),
+            None => rest.to_string(),
+        };
+        if let Ok(path_and_query) = path_and_query.parse::<PathAndQuery>() {
+            let mut parts = req.uri().clone().into_parts();
+            parts.path_and_query = Some(path_and_query);
+            if let Ok(uri) = Uri::from_parts(parts) {
+                *req.uri_mut() = uri;
+                req.extensions_mut().insert(WorkspacePath(workspace_id));
+            }
+        }
+    }
+    next.run(req).await
+}
+
+fn split_prefix(path: &str) -> Option<(i32, &str)> {
+    let rest = path.strip_prefix("/workspaces/")?;
+    let (id, rest) = rest.split_at(rest.find('/')?);
+    let resource = rest[1..].split('/').next()?;
+    if !SCOPED_RESOURCES.contains(&resource) || !id.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((id.parse().ok()?, rest))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_split_only_scoped_resources() {
+        assert_eq!(split_prefix("/workspaces/2/todos"), Some((2, "/todos")));
+        assert_eq!(
+            split_prefix("/workspaces/2/todos/5/revisions"),
+            Some((2, "/todos/5/revisions"))
+        );
+        assert_eq!(split_prefix("/workspaces/3/labels"), Some((3, "/labels")));
+        assert_eq!(
+            split_prefix("/workspaces/3/burndown"),
+            Some((3, "/burndown"))
+        );
+        assert_eq!(
+            split_prefix("/workspaces/3/board/move"),
+            Some((3, "/board/move"))
+        );
+        assert_eq!(split_prefix("/workspaces/2/members"), None);
+        assert_eq!(split_prefix("/workspaces/2/todosx"), None);
+        assert_eq!(split_prefix("/workspaces/+2/todos"), None);
+        assert_eq!(split_prefix("/workspaces"), None);
+        assert_eq!(split_prefix("/todos"), None);
+    }
+}
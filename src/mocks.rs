@@ -0,0 +1,123 @@
+//! `mockall` doubles of the repository traits for handler tests that need
+//! neither Postgres nor the in-memory repositories.
+//!
+//! Handlers reach the store through `in_workspace` (and `with_filter` or
+//! `acting_as`), so expect those to return the mock that answers the call.
+//! The outbox relay polls `claim_outbox` as soon as the app is built.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use mockall::mock;
+use uuid::Uuid;
+
+use crate::{
+    outbox::OutboxEntry,
+    repositories::{
+        board::Positions,
+        burndown::BurndownDay,
+        checklist::{ChecklistItem, CreateChecklistItem, UpdateChecklistItem},
+        label::{CreateLabel, Label, LabelRepository, LabelStats, UpdateLabel},
+        search::Search,
+        todo::{
+            CreateTodo, OwnedTodos, PageCursor, Todo, TodoChange, TodoFilter, TodoPage, TodoReader,
+            TodoRevision, TodoScope, TodoWriter, UpdateTodo,
+        },
+    },
+};
+
+mock! {
+    pub TodoRepository {}
+
+    impl Clone for TodoRepository {
+        fn clone(&self) -> Self;
+    }
+
+    impl TodoScope for TodoRepository {
+        fn in_workspace(&self, workspace_id: i32) -> Self;
+    }
+
+    #[axum::async_trait]
+    impl TodoReader for TodoRepository {
+        async fn find(&self, id: i32) -> anyhow::Result<Todo>;
+        fn with_filter(&self, filter: TodoFilter) -> Self;
+        async fn all(&self) -> anyhow::Result<Vec<Todo>>;
+        async fn count(&self) -> anyhow::Result<i64>;
+        async fn exists(&self, id: i32) -> anyhow::Result<bool>;
+        async fn id_of(&self, uuid: Uuid) -> anyhow::Result<Option<i32>>;
+        async fn find_open_duplicate(&self, text: &str) -> anyhow::Result<Option<Todo>>;
+        async fn page(&self, cursor: PageCursor, limit: u32) -> anyhow::Result<TodoPage>;
+        async fn revisions(&self, id: i32) -> anyhow::Result<Vec<TodoRevision>>;
+        async fn count_open_owned(&self, owner_id: i32) -> anyhow::Result<i64>;
+        async fn owned(&self, owner_id: i32) -> anyhow::Result<Vec<(i32, Todo)>>;
+        async fn label_stats(&self) -> anyhow::Result<Vec<LabelStats>>;
+        async fn suggest_labels(&self, search: &Search, limit: u32) -> anyhow::Result<Vec<LabelStats>>;
+        async fn checklist(&self, id: i32) -> anyhow::Result<Vec<ChecklistItem>>;
+        async fn burndown(&self, from: NaiveDate, to: NaiveDate) -> anyhow::Result<Vec<BurndownDay>>;
+        async fn board_positions(&self) -> anyhow::Result<Positions>;
+        async fn due_by(&self, user_id: i32, by: NaiveDate) -> anyhow::Result<Vec<(i32, Todo)>>;
+    }
+
+    #[axum::async_trait]
+    impl TodoWriter for TodoRepository {
+        async fn create(&self, payload: CreateTodo) -> anyhow::Result<Todo>;
+        async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<Todo>;
+        async fn update_if(
+            &self,
+            id: i32,
+            expected: &Todo,
+            payload: UpdateTodo,
+        ) -> anyhow::Result<Todo>;
+        async fn delete(&self, id: i32) -> anyhow::Result<()>;
+        async fn revert(&self, id: i32, rev: i32) -> anyhow::Result<Todo>;
+        async fn delete_unused_labels(&self) -> anyhow::Result<Vec<i32>>;
+        async fn merge_labels(&self, source: i32, target: i32) -> anyhow::Result<Vec<Todo>>;
+        async fn snooze(&self, id: i32, until: Option<DateTime<Utc>>) -> anyhow::Result<Todo>;
+        async fn unsnooze_due(&self) -> anyhow::Result<Vec<(i32, i32)>>;
+        async fn add_item(
+            &self,
+            id: i32,
+            payload: CreateChecklistItem,
+        ) -> anyhow::Result<ChecklistItem>;
+        async fn update_item(
+            &self,
+            id: i32,
+            item_id: i32,
+            payload: UpdateChecklistItem,
+        ) -> anyhow::Result<ChecklistItem>;
+        async fn delete_item(&self, id: i32, item_id: i32) -> anyhow::Result<()>;
+        async fn move_on_board(
+            &self,
+            id: i32,
+            completed: bool,
+            position: u32,
+        ) -> anyhow::Result<Todo>;
+        async fn release_owned(
+            &self,
+            owner_id: i32,
+            release: OwnedTodos,
+        ) -> anyhow::Result<Vec<(i32, i32)>>;
+        async fn apply(&self, changes: Vec<TodoChange>) -> anyhow::Result<Vec<Option<Todo>>>;
+        fn acting_as(&self, user_id: Option<i32>) -> Self;
+        async fn claim_outbox(&self, limit: u32) -> anyhow::Result<Vec<OutboxEntry>>;
+        async fn mark_sent(&self, ids: &[i64]) -> anyhow::Result<()>;
+    }
+}
+
+mock! {
+    pub LabelRepository {}
+
+    impl Clone for LabelRepository {
+        fn clone(&self) -> Self;
+    }
+
+    #[axum::async_trait]
+    impl LabelRepository for LabelRepository {
+        fn in_workspace(&self, workspace_id: i32) -> Self;
+        async fn find(&self, id: i32) -> anyhow::Result<Label>;
+        async fn id_of(&self, uuid: Uuid) -> anyhow::Result<Option<i32>>;
+        async fn create(&self, payload: CreateLabel) -> anyhow::Result<Label>;
+        async fn update(&self, id: i32, payload: UpdateLabel) -> anyhow::Result<Label>;
+        async fn all(&self) -> anyhow::Result<Vec<Label>>;
+        async fn count(&self) -> anyhow::Result<i64>;
+        async fn delete(&self, id: i32) -> anyhow::Result<()>;
+    }
+}
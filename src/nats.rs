@@ -0,0 +1,184 @@
+use axum::async_trait;
+use serde::Deserialize;
+use tokio_stream::StreamExt;
+use validator::Validate;
+
+use async_nats::Client;
+
+use crate::{
+    config::NatsConfig,
+    events::{Subscriber, WorkspaceEvent},
+    handlers::{error::ApiError, validation_error, workspace::WorkspaceScope},
+    repositories::{
+        label::LabelRepository,
+        todo::{CreateTodo, Todo, TodoRepository},
+        user::UserRepository,
+        workspace::DEFAULT_WORKSPACE_ID,
+    },
+    state::AppState,
+};
+
+/// Connects to `config.url`; `None` when NATS is not configured.
+pub async fn connect(config: &NatsConfig) -> anyhow::Result<Option<Client>> {
+    match &config.url {
+        Some(url) => Ok(Some(async_nats::connect(url.as_str()).await?)),
+        None => Ok(None),
+    }
+}
+
+/// Publishes every event it handles as JSON to
+/// `<subject_prefix>.events.<type>`. With the postgres event backend every
+/// instance hears every event, so enable it on one instance only.
+pub struct NatsPublisher {
+    client: Client,
+    prefix: String,
+}
+
+impl NatsPublisher {
+    pub fn new(client: Client, config: &NatsConfig) -> Self {
+        Self {
+            client,
+            prefix: config.subject_prefix.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl Subscriber for NatsPublisher {
+    fn name(&self) -> &'static str {
+        "nats"
+    }
+
+    async fn handle(&self, event: &WorkspaceEvent) -> anyhow::Result<()> {
+        let subject = format!("{}.events.{}", self.prefix, event.event.kind());
+        self.client
+            .publish(subject, serde_json::to_vec(event)?.into())
+            .await?;
+        Ok(())
+    }
+}
+
+/// Body of a message on `<subject_prefix>.create`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CreateMessage {
+    #[serde(default = "default_workspace")]
+    pub workspace_id: i32,
+    #[serde(flatten)]
+    pub todo: CreateTodo,
+}
+
+fn default_workspace() -> i32 {
+    DEFAULT_WORKSPACE_ID
+}
+
+/// Creates a todo for every message on `<subject_prefix>.create`. Messages
+/// sent as requests are answered with the todo, or with the error body the
+/// REST API would send.
+pub async fn serve_creates<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    client: Client,
+    config: &NatsConfig,
+    state: AppState<T, L, U>,
+) -> anyhow::Result<()> {
+    let mut messages = client
+        .subscribe(format!("{}.create", config.subject_prefix))
+        .await?;
+    tokio::spawn(async move {
+        while let Some(message) = messages.next().await {
+            let reply = match create_todo(&state, &message.payload).await {
+                Ok(todo) => serde_json::to_vec(&todo),
+                Err(e) => {
+                    tracing::warn!("creating a todo from nats failed: {:?}", e);
+                    serde_json::to_vec(&e.body())
+                }
+            };
+            let Some(subject) = message.reply else {
+                continue;
+            };
+            let result = match reply {
+                Ok(reply) => client
+                    .publish(subject, reply.into())
+                    .await
+                    .map_err(anyhow::Error::from),
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = result {
+                tracing::warn!("answering a nats create failed: {:?}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Messages come from trusted services, so the todo belongs to nobody and
+/// membership is not checked; assignees still have to belong to the
+/// workspace.
+pub async fn create_todo<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    state: &AppState<T, L, U>,
+    payload: &[u8],
+) -> Result<Todo, ApiError> {
+    let message: CreateMessage = serde_json::from_slice(payload)
+        .map_err(|e| ApiError::bad_request(format!("Invalid message: {}", e)))?;
+    message.todo.validate().map_err(validation_error)?;
+    if message.workspace_id != DEFAULT_WORKSPACE_ID {
+        state
+            .user_repository
+            .workspace(message.workspace_id)
+            .await?;
+    }
+
+    let scope = WorkspaceScope {
+        id: message.workspace_id,
+        user: None,
+        role: None,
+        uuid_ids: state.config.features.uuid_ids,
+    };
+    if let Some(assignee_id) = message.todo.assignee_id {
+        scope.check_assignee(state, assignee_id).await?;
+    }
+    scope.check_custom(state, &message.todo.custom).await?;
+    let todo = scope.todos(state).create(message.todo).await?;
+
+    Ok(todo)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        config::Config,
+        repositories::{
+            label::LabelRepositoryForMemory,
+            todo::{TodoReader, TodoRepositoryForMemory},
+            user::UserRepositoryForMemory,
+        },
+    };
+    use axum::http::StatusCode;
+
+    #[tokio::test]
+    async fn should_create_todos_from_messages() {
+        let state = AppState::new(
+            TodoRepositoryForMemory::new(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+
+        let todo = create_todo(&state, br#"{"text": "from nats"}"#)
+            .await
+            .unwrap();
+        assert_eq!(todo.text, "from nats");
+        assert_eq!(todo.owner_id, None);
+        assert_eq!(state.todo_repository.find(todo.id).await.unwrap(), todo);
+
+        let err = create_todo(&state, br#"{"text": ""}"#).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+        let err = create_todo(&state, br#"{"workspace_id": 99, "text": "lost"}"#)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::NOT_FOUND);
+        let err = create_todo(&state, br#"{"text": "x", "assignee_id": 5}"#)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+    }
+}
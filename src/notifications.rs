@@ -0,0 +1,280 @@
+use std::sync::Arc;
+
+use axum::async_trait;
+use serde::Serialize;
+use tracing::Instrument;
+
+use crate::{
+    events::{DomainEvent, Subscriber, WorkspaceEvent},
+    repositories::{
+        notification::{Channels, NotificationSettings},
+        todo::Todo,
+        user::UserRepository,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    DueSoon,
+    Assigned,
+    Commented,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    Email,
+    Webhook,
+    Websocket,
+    Slack,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Notification {
+    pub kind: NotificationKind,
+    /// The recipient.
+    pub user_id: i32,
+    /// Where the todo lives.
+    pub workspace_id: i32,
+    pub todo: Todo,
+}
+
+impl Notification {
+    /// One line for chat channels.
+    pub fn summary(&self) -> String {
+        let todo = &self.todo;
+        match self.kind {
+            NotificationKind::DueSoon => match todo.due_date {
+                Some(due) => format!("Due {}: #{} {}", due, todo.id, todo.text),
+                None => format!("Due soon: #{} {}", todo.id, todo.text),
+            },
+            NotificationKind::Assigned => format!("Assigned to you: #{} {}", todo.id, todo.text),
+            NotificationKind::Commented => format!("New comment on #{} {}", todo.id, todo.text),
+        }
+    }
+}
+
+/// Sends a notification over one channel.
+#[async_trait]
+pub trait Delivery: Send + Sync + 'static {
+    async fn deliver(&self, channel: Channel, notification: &Notification) -> anyhow::Result<()>;
+}
+
+/// Writes notifications to the `notifications` tracing target, standing in
+/// for channels that have no sender configured.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogDelivery;
+
+#[async_trait]
+impl Delivery for LogDelivery {
+    async fn deliver(&self, channel: Channel, notification: &Notification) -> anyhow::Result<()> {
+        tracing::info!(
+            target: "notifications",
+            ?channel,
+            kind = ?notification.kind,
+            user_id = notification.user_id,
+            todo_id = notification.todo.id,
+            "notification"
+        );
+        Ok(())
+    }
+}
+
+/// Routes notifications to the channels their recipient enabled in
+/// `NotificationSettings`.
+#[derive(Clone)]
+pub struct Notifier {
+    delivery: Arc<dyn Delivery>,
+}
+
+impl Notifier {
+    pub fn new(delivery: Arc<dyn Delivery>) -> Self {
+        Self { delivery }
+    }
+
+    pub fn channels(settings: &NotificationSettings, kind: NotificationKind) -> Vec<Channel> {
+        let channels: Channels = match kind {
+            NotificationKind::DueSoon => settings.due_soon,
+            NotificationKind::Assigned => settings.assigned,
+            NotificationKind::Commented => settings.commented,
+        };
+        [
+            (channels.email, Channel::Email),
+            (channels.webhook, Channel::Webhook),
+            (channels.websocket, Channel::Websocket),
+            (channels.slack, Channel::Slack),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, channel)| enabled.then_some(channel))
+        .collect()
+    }
+
+    /// Delivers on every enabled channel; failures are logged, not returned.
+    pub async fn dispatch(&self, settings: &NotificationSettings, notification: &Notification) {
+        for channel in Self::channels(settings, notification.kind) {
+            let span = tracing::info_span!("deliver", ?channel, kind = ?notification.kind);
+            let result = self
+                .delivery
+                .deliver(channel, notification)
+                .instrument(span)
+                .await;
+            if let Err(e) = result {
+                tracing::warn!("{:?} notification failed: {:?}", channel, e);
+            }
+        }
+    }
+}
+
+impl Default for Notifier {
+    fn default() -> Self {
+        Self::new(Arc::new(LogDelivery))
+    }
+}
+
+/// Notifies new assignees, unless they assigned themselves.
+pub struct AssignmentNotifier<U> {
+    notifier: Notifier,
+    users: Arc<U>,
+}
+
+impl<U: UserRepository> AssignmentNotifier<U> {
+    pub fn new(notifier: Notifier, users: Arc<U>) -> Self {
+        Self { notifier, users }
+    }
+}
+
+#[async_trait]
+impl<U: UserRepository> Subscriber for AssignmentNotifier<U> {
+    fn name(&self) -> &'static str {
+        "assignment notifications"
+    }
+
+    async fn handle(&self, event: &WorkspaceEvent) -> anyhow::Result<()> {
+        let DomainEvent::TodoAssigned { todo, by } = &event.event else {
+            return Ok(());
+        };
+        let Some(assignee_id) = todo.assignee_id else {
+            return Ok(());
+        };
+        if *by == Some(assignee_id) {
+            return Ok(());
+        }
+        let settings = self.users.notification_settings(assignee_id).await?;
+        self.notifier
+            .dispatch(
+                &settings,
+                &Notification {
+                    kind: NotificationKind::Assigned,
+                    user_id: assignee_id,
+                    workspace_id: event.workspace_id,
+                    todo: todo.clone(),
+                },
+            )
+            .await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        notification::UpdateNotificationSettings, user::UserRepositoryForMemory,
+    };
+    use std::sync::Mutex;
+
+    #[derive(Debug, Clone, Default)]
+    struct MemoryDelivery {
+        delivered: Arc<Mutex<Vec<(Channel, Notification)>>>,
+    }
+
+    impl MemoryDelivery {
+        fn delivered(&self) -> Vec<(Channel, Notification)> {
+            self.delivered.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl Delivery for MemoryDelivery {
+        async fn deliver(
+            &self,
+            channel: Channel,
+            notification: &Notification,
+        ) -> anyhow::Result<()> {
+            self.delivered
+                .lock()
+                .unwrap()
+                .push((channel, notification.clone()));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn should_deliver_on_enabled_channels_only() {
+        let delivery = MemoryDelivery::default();
+        let notifier = Notifier::new(Arc::new(delivery.clone()));
+        let settings: UpdateNotificationSettings =
+            serde_json::from_str(r#"{ "assigned": { "email": false } }"#).unwrap();
+        let notification = Notification {
+            kind: NotificationKind::Assigned,
+            user_id: 2,
+            workspace_id: 1,
+            todo: Todo::new(1, "review".to_string()),
+        };
+
+        notifier
+            .dispatch(
+                &settings.apply(NotificationSettings::default()),
+                &notification,
+            )
+            .await;
+        assert_eq!(
+            delivery.delivered(),
+            vec![(Channel::Websocket, notification)]
+        );
+    }
+
+    #[tokio::test]
+    async fn should_notify_assignees_but_not_self_assigners() {
+        let delivery = MemoryDelivery::default();
+        let users = UserRepositoryForMemory::new();
+        let assignee = users
+            .create("bob".to_string(), "hash".to_string())
+            .await
+            .unwrap();
+        let subscriber =
+            AssignmentNotifier::new(Notifier::new(Arc::new(delivery.clone())), Arc::new(users));
+        let todo = Todo {
+            assignee_id: Some(assignee.id),
+            ..Todo::new(1, "review".to_string())
+        };
+        let assigned = |by| WorkspaceEvent {
+            workspace_id: 1,
+            event: DomainEvent::TodoAssigned {
+                todo: todo.clone(),
+                by,
+            },
+        };
+
+        subscriber
+            .handle(&assigned(Some(assignee.id)))
+            .await
+            .unwrap();
+        assert_eq!(delivery.delivered(), vec![]);
+        subscriber.handle(&assigned(None)).await.unwrap();
+        let channels: Vec<Channel> = delivery
+            .delivered()
+            .into_iter()
+            .map(|(channel, notification)| {
+                assert_eq!(notification.user_id, assignee.id);
+                assert_eq!(notification.workspace_id, 1);
+                channel
+            })
+            .collect();
+        assert_eq!(
+            channels,
+            Notifier::channels(&NotificationSettings::default(), NotificationKind::Assigned)
+        );
+    }
+}
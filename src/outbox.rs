@@ -0,0 +1,108 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::sync::Notify;
+
+use crate::{
+    events::{Events, WorkspaceEvent},
+    repositories::todo::TodoWriter,
+};
+
+/// Claimed entries reappear for other relays after this long unless marked
+/// sent, so a relay that crashed mid-batch loses nothing.
+pub const LEASE_SECS: i64 = 30;
+const BATCH_SIZE: u32 = 100;
+/// Also catches entries written by other instances and by CLI commands.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// An event written in the same transaction as the change it describes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutboxEntry {
+    pub id: i64,
+    pub event: WorkspaceEvent,
+}
+
+/// Wakes the relay that moves outbox entries onto the event bus. Delivery is
+/// at least once: an entry published just before a crash is published
+/// again.
+#[derive(Debug, Clone, Default)]
+pub struct Outbox {
+    wake: Arc<Notify>,
+}
+
+impl Outbox {
+    /// Tells the relay that new entries are waiting.
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+
+    pub fn relay<T: TodoWriter>(&self, todos: Arc<T>, events: Events) {
+        let wake = self.wake.clone();
+        tokio::spawn(async move {
+            loop {
+                match relay_batch(&*todos, &events).await {
+                    // a full batch suggests more are waiting
+                    Ok(sent) if sent == BATCH_SIZE as usize => continue,
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!("outbox relay failed: {:?}", e),
+                }
+                tokio::select! {
+                    _ = wake.notified() => {}
+                    _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                }
+            }
+        });
+    }
+}
+
+/// Publishes one batch of unsent entries in order and returns how many went
+/// out. Entries after a failed publish stay claimed until the lease runs out.
+pub async fn relay_batch<T: TodoWriter>(todos: &T, events: &Events) -> anyhow::Result<usize> {
+    let entries = todos.claim_outbox(BATCH_SIZE).await?;
+    let mut sent = Vec::with_capacity(entries.len());
+    let mut failure = None;
+    for entry in entries {
+        if let Err(e) = events.deliver(entry.event).await {
+            failure = Some(e);
+            break;
+        }
+        sent.push(entry.id);
+    }
+    if !sent.is_empty() {
+        todos.mark_sent(&sent).await?;
+    }
+
+    match failure {
+        Some(e) => Err(e),
+        None => Ok(sent.len()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        events::DomainEvent,
+        repositories::todo::{CreateTodo, TodoRepositoryForMemory},
+    };
+
+    #[tokio::test]
+    async fn should_publish_and_mark_sent() {
+        let todos = TodoRepositoryForMemory::new();
+        let events = Events::default();
+        let mut received = events.subscribe();
+        let todo = todos
+            .create(CreateTodo::new("relayed".to_string()))
+            .await
+            .unwrap();
+
+        assert_eq!(relay_batch(&todos, &events).await.unwrap(), 1);
+        assert_eq!(
+            received.try_recv().unwrap(),
+            WorkspaceEvent {
+                workspace_id: 1,
+                event: DomainEvent::TodoCreated(todo),
+            }
+        );
+        assert_eq!(relay_batch(&todos, &events).await.unwrap(), 0);
+    }
+}
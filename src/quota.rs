@@ -0,0 +1,178 @@
+use std::fmt;
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    config::QuotaConfig,
+    repositories::{
+        label::LabelRepository,
+        todo::{Todo, TodoReader},
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaKind {
+    OpenTodos,
+    Labels,
+}
+
+impl fmt::Display for QuotaKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            QuotaKind::OpenTodos => "open todos",
+            QuotaKind::Labels => "labels",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Usage {
+    pub used: i64,
+    /// `None` when unlimited.
+    pub limit: Option<u64>,
+}
+
+impl Usage {
+    /// Fails when one more would go past the limit.
+    fn check(self, quota: QuotaKind) -> Result<(), QuotaExceeded> {
+        match self.limit {
+            Some(limit) if self.used >= limit as i64 => Err(QuotaExceeded {
+                quota,
+                used: self.used,
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Answered with 402 and itself as the error details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Error)]
+#[error("quota exceeded: {used} of {limit} {quota} used")]
+pub struct QuotaExceeded {
+    pub quota: QuotaKind,
+    pub used: i64,
+    pub limit: u64,
+}
+
+/// The limits of `QuotaConfig`, checked before anything is stored that
+/// would count against them.
+#[derive(Debug, Clone, Default)]
+pub struct Quotas {
+    config: QuotaConfig,
+}
+
+impl Quotas {
+    pub fn new(config: QuotaConfig) -> Self {
+        Self { config }
+    }
+
+    pub async fn open_todos<T: TodoReader>(
+        &self,
+        todos: &T,
+        owner_id: i32,
+    ) -> anyhow::Result<Usage> {
+        Ok(Usage {
+            used: todos.count_open_owned(owner_id).await?,
+            limit: self.config.max_open_todos,
+        })
+    }
+
+    /// Usage of the workspace `labels` is scoped to.
+    pub async fn labels<L: LabelRepository>(&self, labels: &L) -> anyhow::Result<Usage> {
+        Ok(Usage {
+            used: labels.count().await?,
+            limit: self.config.max_labels,
+        })
+    }
+
+    /// Anonymous todos count against nobody.
+    pub async fn check_open_todos<T: TodoReader>(
+        &self,
+        todos: &T,
+        owner_id: Option<i32>,
+    ) -> anyhow::Result<()> {
+        match (owner_id, self.config.max_open_todos) {
+            (Some(owner_id), Some(_)) => Ok(self
+                .open_todos(todos, owner_id)
+                .await?
+                .check(QuotaKind::OpenTodos)?),
+            _ => Ok(()),
+        }
+    }
+
+    /// Setting a completed todo's `completed` to false counts like creating
+    /// it.
+    pub async fn check_reopen<T: TodoReader>(
+        &self,
+        todos: &T,
+        todo: &Todo,
+        completed: Option<bool>,
+    ) -> anyhow::Result<()> {
+        if todo.completed && completed == Some(false) {
+            self.check_open_todos(todos, todo.owner_id).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn check_labels<L: LabelRepository>(&self, labels: &L) -> anyhow::Result<()> {
+        if self.config.max_labels.is_none() {
+            return Ok(());
+        }
+        Ok(self.labels(labels).await?.check(QuotaKind::Labels)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        label::{CreateLabel, LabelRepositoryForMemory},
+        todo::{CreateTodo, TodoRepositoryForMemory, TodoWriter},
+    };
+
+    #[tokio::test]
+    async fn should_refuse_past_the_limit() {
+        let quotas = Quotas::new(QuotaConfig {
+            max_open_todos: Some(1),
+            max_labels: None,
+        });
+        let todos = TodoRepositoryForMemory::new();
+        quotas.check_open_todos(&todos, Some(7)).await.unwrap();
+        todos
+            .create(CreateTodo {
+                owner_id: Some(7),
+                ..CreateTodo::new("first".to_string())
+            })
+            .await
+            .unwrap();
+
+        let err = quotas.check_open_todos(&todos, Some(7)).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<QuotaExceeded>(),
+            Some(&QuotaExceeded {
+                quota: QuotaKind::OpenTodos,
+                used: 1,
+                limit: 1,
+            })
+        );
+        quotas.check_open_todos(&todos, None).await.unwrap();
+        quotas.check_open_todos(&todos, Some(8)).await.unwrap();
+
+        let labels = LabelRepositoryForMemory::new();
+        labels
+            .create(CreateLabel::new("home".to_string()))
+            .await
+            .unwrap();
+        quotas.check_labels(&labels).await.unwrap();
+        assert_eq!(
+            quotas.labels(&labels).await.unwrap(),
+            Usage {
+                used: 1,
+                limit: None,
+            }
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use std::{net::IpAddr, sync::Arc};
+
+use axum::{
+    extract::State,
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{
+    config::ReportingConfig,
+    handlers::error::{ErrorBody, ErrorCause},
+    middleware::{forwarded::ClientInfo, request_id::RequestId},
+};
+
+/// The request an error happened in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestContext {
+    pub request_id: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub client_ip: Option<IpAddr>,
+}
+
+impl RequestContext {
+    fn of<B>(req: &Request<B>) -> Self {
+        Self {
+            request_id: req
+                .extensions()
+                .get::<RequestId>()
+                .map(|RequestId(id)| id.clone()),
+            method: req.method().to_string(),
+            path: req.uri().path().to_string(),
+            client_ip: req.extensions().get::<ClientInfo>().map(|client| client.ip),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorReport {
+    pub message: String,
+    /// Unset for panics outside of requests.
+    pub status: Option<StatusCode>,
+    pub request: Option<RequestContext>,
+}
+
+/// Sends errors somewhere people will look at them. Called on the request
+/// path, so implementations hand reports off rather than wait on them.
+pub trait ErrorReporter: Send + Sync + 'static {
+    fn report(&self, report: &ErrorReport);
+}
+
+pub type Reporter = Arc<dyn ErrorReporter>;
+
+tokio::task_local! {
+    static REQUEST: RequestContext;
+}
+
+/// The reporter `config` asks for, `None` without a `SENTRY_DSN`. Sentry is
+/// process-wide, and so are the panics it is handed from then on.
+pub fn from_config(config: &ReportingConfig) -> anyhow::Result<Option<Reporter>> {
+    match &config.sentry_dsn {
+        #[cfg(feature = "sentry")]
+        Some(dsn) => {
+            let reporter: Reporter = Arc::new(SentryReporter::new(dsn, config)?);
+            report_panics(reporter.clone());
+            Ok(Some(reporter))
+        }
+        #[cfg(not(feature = "sentry"))]
+        Some(_) => anyhow::bail!("reporting.sentry_dsn needs the `sentry` feature"),
+        None => Ok(None),
+    }
+}
+
+/// Reports panics outside of requests before the previous hook sees them.
+/// Handler panics become 500 answers, which `report_errors` reports along
+/// with the request.
+pub fn report_panics(reporter: Reporter) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if REQUEST.try_with(|_| ()).is_err() {
+            reporter.report(&ErrorReport {
+                message: format!("panic: {}", info),
+                status: None,
+                request: None,
+            });
+        }
+        previous(info);
+    }));
+}
+
+/// Reports every 5xx answer. Errors turned into a generic message keep
+/// their real cause for the report in an `ErrorCause` extension.
+pub async fn report_errors<B>(
+    State(reporter): State<Reporter>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let context = RequestContext::of(&req);
+    let res = REQUEST.scope(context.clone(), next.run(req)).await;
+    if res.status().is_server_error() {
+        let extensions = res.extensions();
+        let message = match (
+            extensions.get::<ErrorCause>(),
+            extensions.get::<ErrorBody>(),
+        ) {
+            (Some(ErrorCause(cause)), _) => cause.clone(),
+            (None, Some(body)) => body.message.clone(),
+            (None, None) => res.status().to_string(),
+        };
+        reporter.report(&ErrorReport {
+            message,
+            status: Some(res.status()),
+            request: Some(context),
+        });
+    }
+    res
+}
+
+#[cfg(feature = "sentry")]
+pub use self::sentry_reporter::SentryReporter;
+
+#[cfg(feature = "sentry")]
+mod sentry_reporter {
+    use super::*;
+    use sentry::{ClientInitGuard, ClientOptions, Level};
+
+    pub struct SentryReporter {
+        /// Flushes pending events when the reporter goes.
+        _guard: ClientInitGuard,
+    }
+
+    impl SentryReporter {
+        pub fn new(dsn: &str, config: &ReportingConfig) -> anyhow::Result<Self> {
+            let guard = sentry::init(ClientOptions {
+                dsn: Some(dsn.parse()?),
+                environment: config.environment.clone().map(Into::into),
+                release: sentry::release_name!(),
+                ..Default::default()
+            });
+            Ok(Self { _guard: guard })
+        }
+    }
+
+    impl ErrorReporter for SentryReporter {
+        fn report(&self, report: &ErrorReport) {
+            sentry::with_scope(
+                |scope| {
+                    if let Some(status) = report.status {
+                        scope.set_tag("http.status_code", status.as_u16());
+                    }
+                    if let Some(request) = &report.request {
+                        if let Some(id) = &request.request_id {
+                            scope.set_tag("request_id", id);
+                        }
+                        scope.set_tag("http.method", &request.method);
+                        scope.set_extra("path", request.path.clone().into());
+                        if let Some(ip) = request.client_ip {
+                            scope.set_extra("client_ip", ip.to_string().into());
+                        }
+                    }
+                },
+                || sentry::capture_message(&report.message, Level::Error),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use axum::{
+        body::Body,
+        middleware::{from_fn, from_fn_with_state},
+        routing::get,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{
+        handlers::error::ApiError,
+        middleware::request_id::{request_id, REQUEST_ID_HEADER},
+        repositories::RepositoryError,
+    };
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<ErrorReport>>);
+
+    impl ErrorReporter for Recorder {
+        fn report(&self, report: &ErrorReport) {
+            self.0.lock().unwrap().push(report.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn should_report_server_errors_with_cause() {
+        let recorder = Arc::new(Recorder::default());
+        let app = Router::new()
+            .route(
+                "/broken",
+                get(|| async {
+                    Err::<(), ApiError>(
+                        anyhow::Error::from(RepositoryError::Unexpected("boom".into())).into(),
+                    )
+                }),
+            )
+            .route(
+                "/missing",
+                get(|| async { Err::<(), ApiError>(ApiError::not_found("gone")) }),
+            )
+            .layer(from_fn_with_state(
+                recorder.clone() as Reporter,
+                report_errors,
+            ))
+            .layer(from_fn(request_id));
+        let get = |uri: &str| {
+            Request::builder()
+                .uri(uri)
+                .header(&REQUEST_ID_HEADER, "req-1")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let res = app.clone().oneshot(get("/missing")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res = app.oneshot(get("/broken")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let reports = recorder.0.lock().unwrap();
+        assert_eq!(
+            *reports,
+            vec![ErrorReport {
+                message: "Unexpected Error: [boom]".to_string(),
+                status: Some(StatusCode::INTERNAL_SERVER_ERROR),
+                request: Some(RequestContext {
+                    request_id: Some("req-1".to_string()),
+                    method: "GET".to_string(),
+                    path: "/broken".to_string(),
+                    client_ip: None,
+                }),
+            }]
+        );
+    }
+}
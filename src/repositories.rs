@@ -1,14 +1,45 @@
+pub mod account;
+pub mod audit;
+pub mod board;
+pub mod burndown;
+pub mod cached;
+pub mod checklist;
+pub mod crud;
+pub mod custom_field;
+pub mod data_export;
+pub mod digest;
+pub mod email;
+pub mod event_store;
+pub mod evented;
+pub mod filter;
 pub mod label;
+#[cfg(feature = "mongodb")]
+pub mod mongo;
+pub mod notification;
+#[cfg(feature = "redis")]
+pub mod redis_store;
+pub mod search;
+pub mod slack;
+pub mod split;
+pub mod telegram;
 pub mod todo;
+pub mod user;
+pub mod view;
+pub mod workspace;
 
 use thiserror::Error;
 
 #[derive(Debug, Error)]
-enum RepositoryError {
+pub enum RepositoryError {
     #[error("Unexpected Error: [{0}]")]
     Unexpected(String),
     #[error("NotFound, id is {0}")]
     NotFound(i32),
     #[error("Duplicate data, id is {0}")]
     Duplicate(i32),
+    #[error("Conflict, id {0} was modified concurrently")]
+    Conflict(i32),
+    /// Labels a new todo names that the workspace does not have, by id.
+    #[error("Unknown labels, ids are {0:?}")]
+    MissingLabels(Vec<i32>),
 }
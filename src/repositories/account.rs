@@ -0,0 +1,114 @@
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use super::user::User;
+
+/// Site-wide role, unrelated to the roles members hold in a workspace.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UserRole {
+    #[default]
+    User,
+    /// Manages every account through `/admin/users`.
+    Admin,
+}
+
+impl UserRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UserRole::User => "user",
+            UserRole::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for UserRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for UserRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user" => Ok(UserRole::User),
+            "admin" => Ok(UserRole::Admin),
+            _ => Err(format!("unknown user role {:?}", s)),
+        }
+    }
+}
+
+/// A user as administrators see it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Account {
+    #[serde(flatten)]
+    pub user: User,
+    pub role: UserRole,
+    pub disabled_at: Option<DateTime<Utc>>,
+    /// When the deletion the user asked for goes through, unless cancelled.
+    pub delete_after: Option<DateTime<Utc>>,
+}
+
+impl Account {
+    pub fn is_disabled(&self) -> bool {
+        self.disabled_at.is_some()
+    }
+}
+
+/// Narrows `accounts`; every given member must match.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct AccountFilter {
+    /// Case-insensitive substring of the username or email.
+    pub q: Option<String>,
+    pub role: Option<UserRole>,
+    pub disabled: Option<bool>,
+}
+
+impl AccountFilter {
+    pub(super) fn matches(&self, account: &Account) -> bool {
+        let user = &account.user;
+        self.q.as_deref().is_none_or(|q| {
+            let q = q.to_lowercase();
+            user.username.to_lowercase().contains(&q)
+                || user
+                    .email
+                    .as_deref()
+                    .is_some_and(|email| email.to_lowercase().contains(&q))
+        }) && self.role.is_none_or(|role| account.role == role)
+            && self
+                .disabled
+                .is_none_or(|disabled| account.is_disabled() == disabled)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AccountPage {
+    pub items: Vec<Account>,
+    /// Id of the last item when more accounts follow.
+    pub next: Option<i32>,
+}
+
+impl AccountPage {
+    pub(super) fn from_rows(mut items: Vec<Account>, limit: u32) -> Self {
+        let next = if items.len() > limit as usize {
+            items.truncate(limit as usize);
+            items.last().map(|account| account.user.id)
+        } else {
+            None
+        };
+        Self { items, next }
+    }
+}
+
+/// Body of `PATCH /admin/users/:id`; absent members keep their value.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Validate)]
+pub struct UpdateAccount {
+    pub role: Option<UserRole>,
+    /// Disabling also ends every session and refresh token of the account.
+    pub disabled: Option<bool>,
+}
@@ -0,0 +1,74 @@
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    ExportRequested,
+    DeletionScheduled,
+    DeletionCancelled,
+    AccountDeleted,
+}
+
+impl AuditAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditAction::ExportRequested => "export_requested",
+            AuditAction::DeletionScheduled => "deletion_scheduled",
+            AuditAction::DeletionCancelled => "deletion_cancelled",
+            AuditAction::AccountDeleted => "account_deleted",
+        }
+    }
+}
+
+impl fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "export_requested" => Ok(AuditAction::ExportRequested),
+            "deletion_scheduled" => Ok(AuditAction::DeletionScheduled),
+            "deletion_cancelled" => Ok(AuditAction::DeletionCancelled),
+            "account_deleted" => Ok(AuditAction::AccountDeleted),
+            _ => Err(format!("unknown audit action {:?}", s)),
+        }
+    }
+}
+
+/// One line of the audit log; entries outlive the account they are about.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub id: i32,
+    /// The account the action concerns.
+    pub user_id: i32,
+    /// Who acted, when not the user themselves; `None` for the system.
+    pub actor_id: Option<i32>,
+    pub action: AuditAction,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NewAuditEntry {
+    pub user_id: i32,
+    pub actor_id: Option<i32>,
+    pub action: AuditAction,
+}
+
+impl NewAuditEntry {
+    /// An action the user took on their own account.
+    pub fn by_user(user_id: i32, action: AuditAction) -> Self {
+        Self {
+            user_id,
+            actor_id: Some(user_id),
+            action,
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+
+use super::todo::Todo;
+
+/// Where todos sit within their board column, by todo id; counts from 0
+/// without gaps once a column has been rearranged.
+pub type Positions = HashMap<i32, i32>;
+
+/// Puts `todos` in board order: open ones first, then each column by
+/// position, with todos never moved after the placed ones, oldest first.
+pub fn sort(todos: &mut [Todo], positions: &Positions) {
+    todos.sort_by_key(|todo| {
+        let position = positions.get(&todo.id);
+        (
+            todo.completed,
+            position.is_none(),
+            position.copied(),
+            todo.id,
+        )
+    });
+}
+
+/// The ids of a column in their new order once todo `id` is put at
+/// `position`, moving those from there on down; last when past the end.
+/// `column` holds the column's todos with their current position.
+pub fn reorder(mut column: Vec<(i32, Option<i32>)>, id: i32, position: u32) -> Vec<i32> {
+    column.retain(|(other, _)| *other != id);
+    column.sort_by_key(|(id, position)| (position.is_none(), *position, *id));
+    let mut ids: Vec<i32> = column.into_iter().map(|(id, _)| id).collect();
+    ids.insert((position as usize).min(ids.len()), id);
+    ids
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_reorder_column() {
+        let column = vec![(1, None), (2, Some(1)), (3, Some(0)), (4, None)];
+        assert_eq!(reorder(column.clone(), 4, 0), vec![4, 3, 2, 1]);
+        assert_eq!(reorder(column.clone(), 3, 2), vec![2, 1, 3, 4]);
+        assert_eq!(reorder(column, 5, 9), vec![3, 2, 1, 4, 5]);
+
+        let mut todos: Vec<Todo> = (1..=4)
+            .map(|id| Todo {
+                completed: id == 1,
+                ..Todo::new(id, format!("todo {}", id))
+            })
+            .collect();
+        sort(&mut todos, &Positions::from([(3, 0), (1, 0)]));
+        let ids: Vec<i32> = todos.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![3, 2, 4, 1]);
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use super::todo::TodoRevision;
+
+/// The estimated minutes left on open todos at the end of `day`, in UTC.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow)]
+pub struct BurndownDay {
+    pub day: NaiveDate,
+    pub remaining_minutes: i64,
+}
+
+/// What a revision leaves to do: its estimate while open, nothing once
+/// completed or without one.
+fn remaining(revision: &TodoRevision) -> i64 {
+    match (revision.completed, revision.estimate_minutes) {
+        (false, Some(minutes)) => minutes as i64,
+        _ => 0,
+    }
+}
+
+/// Every day from `from` to `to` from the revisions of a workspace's todos,
+/// as the database computes it: each todo counts with the last revision it
+/// had by the end of the day.
+pub fn from_revisions(
+    revisions: &[TodoRevision],
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Vec<BurndownDay> {
+    let mut histories: HashMap<i32, Vec<&TodoRevision>> = HashMap::new();
+    for revision in revisions {
+        histories
+            .entry(revision.todo_id)
+            .or_default()
+            .push(revision);
+    }
+    for history in histories.values_mut() {
+        history.sort_by_key(|revision| revision.rev);
+    }
+    from.iter_days()
+        .take_while(|day| *day <= to)
+        .map(|day| {
+            let end =
+                Utc.from_utc_datetime(&(day + Duration::days(1)).and_hms_opt(0, 0, 0).unwrap());
+            let remaining_minutes = histories
+                .values()
+                .filter_map(|history| {
+                    history
+                        .iter()
+                        .take_while(|revision| revision.created_at < end)
+                        .last()
+                })
+                .map(|revision| remaining(revision))
+                .sum();
+            BurndownDay {
+                day,
+                remaining_minutes,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn revision(
+        todo_id: i32,
+        rev: i32,
+        at: &str,
+        completed: bool,
+        estimate: Option<i32>,
+    ) -> TodoRevision {
+        TodoRevision {
+            todo_id,
+            rev,
+            text: "todo".to_string(),
+            completed,
+            estimate_minutes: estimate,
+            created_at: at.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn should_burn_down_by_day() {
+        let revisions = [
+            revision(1, 1, "2023-05-01T09:00:00Z", false, Some(60)),
+            revision(2, 1, "2023-05-01T10:00:00Z", false, Some(30)),
+            revision(2, 2, "2023-05-02T23:59:00Z", false, Some(45)),
+            revision(1, 2, "2023-05-03T08:00:00Z", true, Some(60)),
+            revision(3, 1, "2023-05-03T09:00:00Z", false, None),
+            revision(1, 3, "2023-05-05T08:00:00Z", false, Some(60)),
+        ];
+        let day = |day: &str| day.parse::<NaiveDate>().unwrap();
+        let burndown: Vec<(NaiveDate, i64)> =
+            from_revisions(&revisions, day("2023-04-30"), day("2023-05-05"))
+                .into_iter()
+                .map(|day| (day.day, day.remaining_minutes))
+                .collect();
+        assert_eq!(
+            burndown,
+            vec![
+                (day("2023-04-30"), 0),
+                (day("2023-05-01"), 90),
+                (day("2023-05-02"), 105),
+                (day("2023-05-03"), 45),
+                (day("2023-05-04"), 45),
+                (day("2023-05-05"), 105),
+            ]
+        );
+        assert!(from_revisions(&revisions, day("2023-05-02"), day("2023-05-01")).is_empty());
+    }
+}
@@ -0,0 +1,370 @@
+use std::sync::Arc;
+
+use axum::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{de::DeserializeOwned, Serialize};
+use uuid::Uuid;
+
+use super::{
+    board::Positions,
+    burndown::BurndownDay,
+    checklist::{ChecklistItem, CreateChecklistItem, UpdateChecklistItem},
+    label::LabelStats,
+    search::Search,
+    todo::{
+        CreateTodo, OwnedTodos, PageCursor, Todo, TodoChange, TodoFilter, TodoPage, TodoReader,
+        TodoRepository, TodoRevision, TodoScope, TodoWriter, UpdateTodo,
+    },
+    workspace::DEFAULT_WORKSPACE_ID,
+};
+use crate::{cache::Cache, outbox::OutboxEntry};
+
+fn all_key(workspace_id: i32) -> String {
+    format!("todos:{}:all", workspace_id)
+}
+
+fn todo_key(workspace_id: i32, id: i32) -> String {
+    format!("todos:{}:{}", workspace_id, id)
+}
+
+/// Cache-aside decorator for `find` and `all`; every mutation drops the
+/// entries it affects. Cache failures are logged and the inner repository
+/// answers instead.
+#[derive(Clone)]
+pub struct CachedTodoRepository<T> {
+    inner: T,
+    cache: Arc<dyn Cache>,
+    workspace_id: i32,
+    /// Filtered listings bypass the cache.
+    filter: TodoFilter,
+}
+
+impl<T: TodoRepository> CachedTodoRepository<T> {
+    pub fn new(inner: T, cache: Arc<dyn Cache>) -> Self {
+        Self {
+            inner,
+            cache,
+            workspace_id: DEFAULT_WORKSPACE_ID,
+            filter: TodoFilter::default(),
+        }
+    }
+
+    async fn read<V: Serialize + DeserializeOwned>(&self, key: &str) -> Option<V> {
+        match self.cache.get(key).await {
+            Ok(value) => value.and_then(|value| serde_json::from_str(&value).ok()),
+            Err(e) => {
+                tracing::warn!("cache read failed: {:?}", e);
+                None
+            }
+        }
+    }
+
+    async fn write<V: Serialize>(&self, key: &str, value: &V) {
+        let result = match serde_json::to_string(value) {
+            Ok(value) => self.cache.set(key, value).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            tracing::warn!("cache write failed: {:?}", e);
+        }
+    }
+
+    async fn invalidate(&self, id: Option<i32>) {
+        let mut keys = vec![all_key(self.workspace_id)];
+        keys.extend(id.map(|id| todo_key(self.workspace_id, id)));
+        if let Err(e) = self.cache.remove(&keys).await {
+            tracing::warn!("cache invalidation failed: {:?}", e);
+        }
+    }
+}
+
+impl<T: TodoRepository> TodoScope for CachedTodoRepository<T> {
+    fn in_workspace(&self, workspace_id: i32) -> Self {
+        Self {
+            inner: self.inner.in_workspace(workspace_id),
+            cache: self.cache.clone(),
+            workspace_id,
+            filter: self.filter.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl<T: TodoRepository> TodoReader for CachedTodoRepository<T> {
+    fn with_filter(&self, filter: TodoFilter) -> Self {
+        Self {
+            inner: self.inner.with_filter(filter.clone()),
+            cache: self.cache.clone(),
+            workspace_id: self.workspace_id,
+            filter,
+        }
+    }
+    async fn find(&self, id: i32) -> anyhow::Result<Todo> {
+        let key = todo_key(self.workspace_id, id);
+        if let Some(todo) = self.read(&key).await {
+            return Ok(todo);
+        }
+        let todo = self.inner.find(id).await?;
+        self.write(&key, &todo).await;
+        Ok(todo)
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Todo>> {
+        // snoozes run out without a write, so what they hide is left out
+        // of the cached listing on the way out
+        let unfiltered = TodoFilter {
+            hide_snoozed: false,
+            ..self.filter.clone()
+        };
+        if unfiltered != TodoFilter::default() {
+            return self.inner.all().await;
+        }
+        let key = all_key(self.workspace_id);
+        let todos = match self.read::<Vec<Todo>>(&key).await {
+            Some(todos) => todos,
+            None => {
+                let todos = self.inner.with_filter(unfiltered).all().await?;
+                self.write(&key, &todos).await;
+                todos
+            }
+        };
+        Ok(todos
+            .into_iter()
+            .filter(|todo| self.filter.matches(todo))
+            .collect())
+    }
+    async fn count(&self) -> anyhow::Result<i64> {
+        self.inner.count().await
+    }
+    async fn exists(&self, id: i32) -> anyhow::Result<bool> {
+        self.inner.exists(id).await
+    }
+    async fn id_of(&self, uuid: Uuid) -> anyhow::Result<Option<i32>> {
+        self.inner.id_of(uuid).await
+    }
+    async fn find_open_duplicate(&self, text: &str) -> anyhow::Result<Option<Todo>> {
+        self.inner.find_open_duplicate(text).await
+    }
+    async fn page(&self, cursor: PageCursor, limit: u32) -> anyhow::Result<TodoPage> {
+        self.inner.page(cursor, limit).await
+    }
+    async fn revisions(&self, id: i32) -> anyhow::Result<Vec<TodoRevision>> {
+        self.inner.revisions(id).await
+    }
+    async fn count_open_owned(&self, owner_id: i32) -> anyhow::Result<i64> {
+        self.inner.count_open_owned(owner_id).await
+    }
+    async fn owned(&self, owner_id: i32) -> anyhow::Result<Vec<(i32, Todo)>> {
+        self.inner.owned(owner_id).await
+    }
+    async fn label_stats(&self) -> anyhow::Result<Vec<LabelStats>> {
+        self.inner.label_stats().await
+    }
+    async fn suggest_labels(&self, search: &Search, limit: u32) -> anyhow::Result<Vec<LabelStats>> {
+        self.inner.suggest_labels(search, limit).await
+    }
+    async fn checklist(&self, id: i32) -> anyhow::Result<Vec<ChecklistItem>> {
+        self.inner.checklist(id).await
+    }
+    async fn burndown(&self, from: NaiveDate, to: NaiveDate) -> anyhow::Result<Vec<BurndownDay>> {
+        self.inner.burndown(from, to).await
+    }
+    async fn board_positions(&self) -> anyhow::Result<Positions> {
+        self.inner.board_positions().await
+    }
+    async fn due_by(&self, user_id: i32, by: NaiveDate) -> anyhow::Result<Vec<(i32, Todo)>> {
+        self.inner.due_by(user_id, by).await
+    }
+}
+
+#[async_trait]
+impl<T: TodoRepository> TodoWriter for CachedTodoRepository<T> {
+    async fn create(&self, payload: CreateTodo) -> anyhow::Result<Todo> {
+        let todo = self.inner.create(payload).await?;
+        self.invalidate(None).await;
+        Ok(todo)
+    }
+    async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<Todo> {
+        let result = self.inner.update(id, payload).await;
+        self.invalidate(Some(id)).await;
+        result
+    }
+    async fn update_if(
+        &self,
+        id: i32,
+        expected: &Todo,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<Todo> {
+        let result = self.inner.update_if(id, expected, payload).await;
+        self.invalidate(Some(id)).await;
+        result
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let result = self.inner.delete(id).await;
+        self.invalidate(Some(id)).await;
+        result
+    }
+    async fn revert(&self, id: i32, rev: i32) -> anyhow::Result<Todo> {
+        let result = self.inner.revert(id, rev).await;
+        self.invalidate(Some(id)).await;
+        result
+    }
+    /// No todo carried them, so nothing cached changes.
+    async fn delete_unused_labels(&self) -> anyhow::Result<Vec<i32>> {
+        self.inner.delete_unused_labels().await
+    }
+    async fn merge_labels(&self, source: i32, target: i32) -> anyhow::Result<Vec<Todo>> {
+        let result = self.inner.merge_labels(source, target).await;
+        let mut keys = vec![all_key(self.workspace_id)];
+        if let Ok(moved) = &result {
+            keys.extend(
+                moved
+                    .iter()
+                    .map(|todo| todo_key(self.workspace_id, todo.id)),
+            );
+        }
+        if let Err(e) = self.cache.remove(&keys).await {
+            tracing::warn!("cache invalidation failed: {:?}", e);
+        }
+        result
+    }
+    async fn apply(&self, changes: Vec<TodoChange>) -> anyhow::Result<Vec<Option<Todo>>> {
+        let mut keys = vec![all_key(self.workspace_id)];
+        keys.extend(changes.iter().filter_map(|change| match change {
+            TodoChange::Create(_) => None,
+            TodoChange::Update(id, _) | TodoChange::Delete(id) => {
+                Some(todo_key(self.workspace_id, *id))
+            }
+        }));
+        let result = self.inner.apply(changes).await;
+        if let Err(e) = self.cache.remove(&keys).await {
+            tracing::warn!("cache invalidation failed: {:?}", e);
+        }
+        result
+    }
+    async fn snooze(&self, id: i32, until: Option<DateTime<Utc>>) -> anyhow::Result<Todo> {
+        let result = self.inner.snooze(id, until).await;
+        self.invalidate(Some(id)).await;
+        result
+    }
+    async fn unsnooze_due(&self) -> anyhow::Result<Vec<(i32, i32)>> {
+        let woken = self.inner.unsnooze_due().await?;
+        let mut keys: Vec<String> = woken
+            .iter()
+            .flat_map(|(workspace_id, id)| [all_key(*workspace_id), todo_key(*workspace_id, *id)])
+            .collect();
+        keys.sort();
+        keys.dedup();
+        if !keys.is_empty() {
+            if let Err(e) = self.cache.remove(&keys).await {
+                tracing::warn!("cache invalidation failed: {:?}", e);
+            }
+        }
+        Ok(woken)
+    }
+    async fn add_item(
+        &self,
+        id: i32,
+        payload: CreateChecklistItem,
+    ) -> anyhow::Result<ChecklistItem> {
+        let result = self.inner.add_item(id, payload).await;
+        self.invalidate(Some(id)).await;
+        result
+    }
+    async fn update_item(
+        &self,
+        id: i32,
+        item_id: i32,
+        payload: UpdateChecklistItem,
+    ) -> anyhow::Result<ChecklistItem> {
+        let result = self.inner.update_item(id, item_id, payload).await;
+        self.invalidate(Some(id)).await;
+        result
+    }
+    async fn delete_item(&self, id: i32, item_id: i32) -> anyhow::Result<()> {
+        let result = self.inner.delete_item(id, item_id).await;
+        self.invalidate(Some(id)).await;
+        result
+    }
+    async fn move_on_board(&self, id: i32, completed: bool, position: u32) -> anyhow::Result<Todo> {
+        let result = self.inner.move_on_board(id, completed, position).await;
+        self.invalidate(Some(id)).await;
+        result
+    }
+    async fn release_owned(
+        &self,
+        owner_id: i32,
+        release: OwnedTodos,
+    ) -> anyhow::Result<Vec<(i32, i32)>> {
+        let released = self.inner.release_owned(owner_id, release).await?;
+        let mut keys: Vec<String> = released
+            .iter()
+            .flat_map(|(workspace_id, id)| [all_key(*workspace_id), todo_key(*workspace_id, *id)])
+            .collect();
+        keys.sort();
+        keys.dedup();
+        if !keys.is_empty() {
+            if let Err(e) = self.cache.remove(&keys).await {
+                tracing::warn!("cache invalidation failed: {:?}", e);
+            }
+        }
+        Ok(released)
+    }
+    fn acting_as(&self, user_id: Option<i32>) -> Self {
+        Self {
+            inner: self.inner.acting_as(user_id),
+            cache: self.cache.clone(),
+            workspace_id: self.workspace_id,
+            filter: self.filter.clone(),
+        }
+    }
+    async fn claim_outbox(&self, limit: u32) -> anyhow::Result<Vec<OutboxEntry>> {
+        self.inner.claim_outbox(limit).await
+    }
+    async fn mark_sent(&self, ids: &[i64]) -> anyhow::Result<()> {
+        self.inner.mark_sent(ids).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{cache::MemoryCache, repositories::todo::TodoRepositoryForMemory};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn should_serve_reads_from_cache_until_mutated() {
+        let inner = TodoRepositoryForMemory::new();
+        let cache = Arc::new(MemoryCache::new(Duration::from_secs(60), 100));
+        let repository = CachedTodoRepository::new(inner.clone(), cache);
+
+        let todo = repository
+            .create(CreateTodo::new("cached".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(repository.find(todo.id).await.unwrap(), todo);
+        assert_eq!(repository.all().await.unwrap(), vec![todo.clone()]);
+
+        // changes behind the decorator's back stay invisible until the TTL
+        let payload = UpdateTodo {
+            text: Some("changed".to_string()),
+            completed: None,
+            labels: None,
+            assignee_id: None,
+            text_i18n: None,
+            estimate_minutes: None,
+            due_date: None,
+            description: None,
+            custom: None,
+        };
+        inner.update(todo.id, payload.clone()).await.unwrap();
+        assert_eq!(repository.find(todo.id).await.unwrap().text, "cached");
+
+        repository.update(todo.id, payload).await.unwrap();
+        assert_eq!(repository.find(todo.id).await.unwrap().text, "changed");
+        assert_eq!(repository.all().await.unwrap()[0].text, "changed");
+
+        repository.delete(todo.id).await.unwrap();
+        assert!(repository.find(todo.id).await.is_err());
+        assert!(repository.all().await.unwrap().is_empty());
+    }
+}
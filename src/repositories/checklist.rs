@@ -0,0 +1,168 @@
+use async_graphql::SimpleObject;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::Validate;
+
+use super::RepositoryError;
+
+/// One step of a todo's checklist. `position` counts from 0 in list order,
+/// without gaps.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow)]
+pub struct ChecklistItem {
+    pub id: i32,
+    pub text: String,
+    pub completed: bool,
+    pub position: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+pub struct CreateChecklistItem {
+    #[validate(length(min = 1, message = "can not be empty"))]
+    #[validate(length(max = 100, message = "can not be over 100"))]
+    pub text: String,
+    /// Where the item goes, moving those from there on down; last when
+    /// left out or past the end.
+    #[serde(default)]
+    pub position: Option<u32>,
+}
+
+impl CreateChecklistItem {
+    pub fn new(text: String) -> Self {
+        Self {
+            text,
+            position: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+pub struct UpdateChecklistItem {
+    #[validate(length(min = 1, message = "can not be empty"))]
+    #[validate(length(max = 100, message = "can not be over 100"))]
+    pub text: Option<String>,
+    pub completed: Option<bool>,
+    /// Moves the item, shifting the ones in between.
+    pub position: Option<u32>,
+}
+
+/// How much of a todo's checklist is done.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, SimpleObject)]
+pub struct Progress {
+    pub done: i64,
+    pub total: i64,
+}
+
+impl Progress {
+    pub fn of(items: &[ChecklistItem]) -> Self {
+        Self {
+            done: items.iter().filter(|item| item.completed).count() as i64,
+            total: items.len() as i64,
+        }
+    }
+
+    /// No checklist at all.
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+}
+
+/// Puts `item` at `position` of a checklist held in order, as the memory
+/// and event stores keep it, and returns it as placed.
+pub fn insert(
+    items: &mut Vec<ChecklistItem>,
+    item: ChecklistItem,
+    position: Option<u32>,
+) -> ChecklistItem {
+    let at = position.map_or(items.len(), |position| (position as usize).min(items.len()));
+    items.insert(at, item);
+    renumber(items);
+    items[at].clone()
+}
+
+/// Applies `payload` to item `id` and returns it as it is afterwards.
+pub fn update(
+    items: &mut Vec<ChecklistItem>,
+    id: i32,
+    payload: UpdateChecklistItem,
+) -> Result<ChecklistItem, RepositoryError> {
+    let at = items
+        .iter()
+        .position(|item| item.id == id)
+        .ok_or(RepositoryError::NotFound(id))?;
+    let mut item = items.remove(at);
+    if let Some(text) = payload.text {
+        item.text = text;
+    }
+    if let Some(completed) = payload.completed {
+        item.completed = completed;
+    }
+    Ok(insert(
+        items,
+        item,
+        Some(payload.position.unwrap_or(at as u32)),
+    ))
+}
+
+pub fn remove(items: &mut Vec<ChecklistItem>, id: i32) -> Result<(), RepositoryError> {
+    let at = items
+        .iter()
+        .position(|item| item.id == id)
+        .ok_or(RepositoryError::NotFound(id))?;
+    items.remove(at);
+    renumber(items);
+    Ok(())
+}
+
+fn renumber(items: &mut [ChecklistItem]) {
+    for (position, item) in items.iter_mut().enumerate() {
+        item.position = position as i32;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn item(id: i32, text: &str) -> ChecklistItem {
+        ChecklistItem {
+            id,
+            text: text.to_string(),
+            completed: false,
+            position: 0,
+        }
+    }
+
+    #[test]
+    fn should_keep_checklist_in_order() {
+        let mut items = vec![];
+        insert(&mut items, item(1, "pack"), None);
+        insert(&mut items, item(2, "go"), Some(9));
+        let first = insert(&mut items, item(3, "book"), Some(0));
+        assert_eq!(first.position, 0);
+        let order = |items: &[ChecklistItem]| -> Vec<(i32, i32)> {
+            items.iter().map(|item| (item.id, item.position)).collect()
+        };
+        assert_eq!(order(&items), vec![(3, 0), (1, 1), (2, 2)]);
+
+        let moved = update(
+            &mut items,
+            3,
+            UpdateChecklistItem {
+                text: None,
+                completed: Some(true),
+                position: Some(2),
+            },
+        )
+        .unwrap();
+        assert_eq!((moved.position, moved.completed), (2, true));
+        assert_eq!(order(&items), vec![(1, 0), (2, 1), (3, 2)]);
+        assert_eq!(Progress::of(&items), Progress { done: 1, total: 3 });
+
+        remove(&mut items, 1).unwrap();
+        assert_eq!(order(&items), vec![(2, 0), (3, 1)]);
+        assert!(matches!(
+            remove(&mut items, 1),
+            Err(RepositoryError::NotFound(1))
+        ));
+    }
+}
@@ -0,0 +1,476 @@
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use axum::async_trait;
+use sqlx::{
+    postgres::{PgArguments, PgRow},
+    query::QueryAs,
+    types::Json,
+    FromRow, PgPool, Postgres,
+};
+
+use super::{
+    filter::SqlValue,
+    label::{CreateLabel, Label, LabelRepository, UpdateLabel},
+    todo::{CreateTodo, Todo, TodoRepository, UpdateTodo},
+    workspace::DEFAULT_WORKSPACE_ID,
+    RepositoryError,
+};
+
+/// What every workspace resource offers, for code that needs no more; `E`
+/// is stored, `C` creates one and `U` changes one.
+#[async_trait]
+pub trait CrudRepository<E, C, U>: Clone + std::marker::Send + std::marker::Sync + 'static {
+    /// The same store, restricted to one workspace's entities.
+    fn in_workspace(&self, workspace_id: i32) -> Self;
+    async fn find(&self, id: i32) -> anyhow::Result<E>;
+    /// Every entity of the workspace, by id.
+    async fn all(&self) -> anyhow::Result<Vec<E>>;
+    async fn create(&self, payload: C) -> anyhow::Result<E>;
+    async fn update(&self, id: i32, payload: U) -> anyhow::Result<E>;
+    async fn delete(&self, id: i32) -> anyhow::Result<()>;
+}
+
+/// What `CrudRepositoryForMemory` and `CrudRepositoryForDb` keep.
+pub trait Entity: Clone + Send + Sync + 'static {
+    fn id(&self) -> i32;
+}
+
+/// A payload that makes an entity once its id is known.
+pub trait NewEntity<E>: Send + 'static {
+    fn into_entity(self, id: i32) -> E;
+}
+
+/// A payload that changes an entity in place.
+pub trait EntityChange<E>: Send + 'static {
+    fn apply_to(self, entity: &mut E);
+}
+
+/// Where `CrudRepositoryForDb` keeps an entity: a table with a serial `id`
+/// and a `workspace_id`, whose rows read back as the entity.
+pub trait Table: Entity + for<'r> FromRow<'r, PgRow> + Unpin {
+    const TABLE: &'static str;
+}
+
+/// The columns a payload writes, with their values; an update leaves the
+/// others alone.
+pub trait Columns: Send + 'static {
+    fn columns(self) -> Vec<(&'static str, SqlValue)>;
+}
+
+#[async_trait]
+impl<T: TodoRepository> CrudRepository<Todo, CreateTodo, UpdateTodo> for T {
+    fn in_workspace(&self, workspace_id: i32) -> Self {
+        super::todo::TodoScope::in_workspace(self, workspace_id)
+    }
+    async fn find(&self, id: i32) -> anyhow::Result<Todo> {
+        super::todo::TodoReader::find(self, id).await
+    }
+    /// Newest first, as todos are listed.
+    async fn all(&self) -> anyhow::Result<Vec<Todo>> {
+        super::todo::TodoReader::all(self).await
+    }
+    async fn create(&self, payload: CreateTodo) -> anyhow::Result<Todo> {
+        super::todo::TodoWriter::create(self, payload).await
+    }
+    async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<Todo> {
+        super::todo::TodoWriter::update(self, id, payload).await
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        super::todo::TodoWriter::delete(self, id).await
+    }
+}
+
+#[async_trait]
+impl<L: LabelRepository> CrudRepository<Label, CreateLabel, UpdateLabel> for L {
+    fn in_workspace(&self, workspace_id: i32) -> Self {
+        LabelRepository::in_workspace(self, workspace_id)
+    }
+    async fn find(&self, id: i32) -> anyhow::Result<Label> {
+        LabelRepository::find(self, id).await
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Label>> {
+        LabelRepository::all(self).await
+    }
+    async fn create(&self, payload: CreateLabel) -> anyhow::Result<Label> {
+        LabelRepository::create(self, payload).await
+    }
+    async fn update(&self, id: i32, payload: UpdateLabel) -> anyhow::Result<Label> {
+        LabelRepository::update(self, id, payload).await
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        LabelRepository::delete(self, id).await
+    }
+}
+
+/// Entities by id, with the workspace each belongs to.
+type EntityDatas<E> = HashMap<i32, (i32, E)>;
+
+/// Any `Entity` in memory, for resources that need nothing more.
+#[derive(Debug)]
+pub struct CrudRepositoryForMemory<E> {
+    store: Arc<RwLock<EntityDatas<E>>>,
+    next_id: Arc<AtomicI32>,
+    workspace_id: i32,
+}
+
+impl<E> CrudRepositoryForMemory<E> {
+    pub fn new() -> Self {
+        Self {
+            store: Arc::default(),
+            next_id: Arc::default(),
+            workspace_id: DEFAULT_WORKSPACE_ID,
+        }
+    }
+}
+
+impl<E> Default for CrudRepositoryForMemory<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> Clone for CrudRepositoryForMemory<E> {
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+            next_id: self.next_id.clone(),
+            workspace_id: self.workspace_id,
+        }
+    }
+}
+
+#[async_trait]
+impl<E, C, U> CrudRepository<E, C, U> for CrudRepositoryForMemory<E>
+where
+    E: Entity,
+    C: NewEntity<E>,
+    U: EntityChange<E>,
+{
+    fn in_workspace(&self, workspace_id: i32) -> Self {
+        Self {
+            workspace_id,
+            ..self.clone()
+        }
+    }
+    async fn find(&self, id: i32) -> anyhow::Result<E> {
+        let store = self.store.read().unwrap();
+        match store.get(&id) {
+            Some((workspace_id, entity)) if *workspace_id == self.workspace_id => {
+                Ok(entity.clone())
+            }
+            _ => Err(RepositoryError::NotFound(id).into()),
+        }
+    }
+    async fn all(&self) -> anyhow::Result<Vec<E>> {
+        let store = self.store.read().unwrap();
+        let mut all: Vec<E> = store
+            .values()
+            .filter(|(workspace_id, _)| *workspace_id == self.workspace_id)
+            .map(|(_, entity)| entity.clone())
+            .collect();
+        all.sort_by_key(|entity| entity.id());
+        Ok(all)
+    }
+    async fn create(&self, payload: C) -> anyhow::Result<E> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let entity = payload.into_entity(id);
+        self.store
+            .write()
+            .unwrap()
+            .insert(id, (self.workspace_id, entity.clone()));
+        Ok(entity)
+    }
+    async fn update(&self, id: i32, payload: U) -> anyhow::Result<E> {
+        let mut store = self.store.write().unwrap();
+        match store.get_mut(&id) {
+            Some((workspace_id, entity)) if *workspace_id == self.workspace_id => {
+                payload.apply_to(entity);
+                Ok(entity.clone())
+            }
+            _ => Err(RepositoryError::NotFound(id).into()),
+        }
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let mut store = self.store.write().unwrap();
+        match store.get(&id) {
+            Some((workspace_id, _)) if *workspace_id == self.workspace_id => {
+                store.remove(&id);
+                Ok(())
+            }
+            _ => Err(RepositoryError::NotFound(id).into()),
+        }
+    }
+}
+
+/// Any `Table` in Postgres, for resources that need nothing more.
+#[derive(Debug)]
+pub struct CrudRepositoryForDb<E> {
+    pool: PgPool,
+    workspace_id: i32,
+    entity: PhantomData<fn() -> E>,
+}
+
+impl<E> CrudRepositoryForDb<E> {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            workspace_id: DEFAULT_WORKSPACE_ID,
+            entity: PhantomData,
+        }
+    }
+}
+
+impl<E> Clone for CrudRepositoryForDb<E> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            workspace_id: self.workspace_id,
+            entity: PhantomData,
+        }
+    }
+}
+
+/// Binds `values` in order, after what `query` binds already.
+fn bind_values<'q, E>(
+    query: QueryAs<'q, Postgres, E, PgArguments>,
+    values: Vec<SqlValue>,
+) -> QueryAs<'q, Postgres, E, PgArguments> {
+    values.into_iter().fold(query, |query, value| match value {
+        SqlValue::Bool(value) => query.bind(value),
+        SqlValue::Int(value) => query.bind(value),
+        SqlValue::Text(value) => query.bind(value),
+        SqlValue::Date(value) => query.bind(value),
+        SqlValue::Json(value) => query.bind(Json(value)),
+    })
+}
+
+#[async_trait]
+impl<E, C, U> CrudRepository<E, C, U> for CrudRepositoryForDb<E>
+where
+    E: Table,
+    C: Columns,
+    U: Columns,
+{
+    fn in_workspace(&self, workspace_id: i32) -> Self {
+        Self {
+            workspace_id,
+            ..self.clone()
+        }
+    }
+    async fn find(&self, id: i32) -> anyhow::Result<E> {
+        let query = format!("select * from {} where id=$1 and workspace_id=$2", E::TABLE);
+        sqlx::query_as::<_, E>(&query)
+            .bind(id)
+            .bind(self.workspace_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| RepositoryError::NotFound(id).into())
+    }
+    async fn all(&self) -> anyhow::Result<Vec<E>> {
+        let query = format!(
+            "select * from {} where workspace_id=$1 order by id",
+            E::TABLE
+        );
+        Ok(sqlx::query_as::<_, E>(&query)
+            .bind(self.workspace_id)
+            .fetch_all(&self.pool)
+            .await?)
+    }
+    async fn create(&self, payload: C) -> anyhow::Result<E> {
+        let (names, values): (Vec<_>, Vec<_>) = payload.columns().into_iter().unzip();
+        let params: Vec<String> = (2..names.len() + 2).map(|n| format!("${}", n)).collect();
+        let query = format!(
+            "insert into {} (workspace_id{}) values ($1{}) returning *",
+            E::TABLE,
+            names
+                .iter()
+                .map(|name| format!(", {}", name))
+                .collect::<String>(),
+            params
+                .iter()
+                .map(|param| format!(", {}", param))
+                .collect::<String>(),
+        );
+        let query = sqlx::query_as::<_, E>(&query).bind(self.workspace_id);
+        Ok(bind_values(query, values).fetch_one(&self.pool).await?)
+    }
+    async fn update(&self, id: i32, payload: U) -> anyhow::Result<E> {
+        let (names, values): (Vec<_>, Vec<_>) = payload.columns().into_iter().unzip();
+        if names.is_empty() {
+            return CrudRepository::<E, C, U>::find(self, id).await;
+        }
+        let sets: Vec<String> = names
+            .iter()
+            .enumerate()
+            .map(|(n, name)| format!("{}=${}", name, n + 3))
+            .collect();
+        let query = format!(
+            "update {} set {} where id=$1 and workspace_id=$2 returning *",
+            E::TABLE,
+            sets.join(", ")
+        );
+        let query = sqlx::query_as::<_, E>(&query)
+            .bind(id)
+            .bind(self.workspace_id);
+        bind_values(query, values)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| RepositoryError::NotFound(id).into())
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let query = format!("delete from {} where id=$1 and workspace_id=$2", E::TABLE);
+        let deleted = sqlx::query(&query)
+            .bind(id)
+            .bind(self.workspace_id)
+            .execute(&self.pool)
+            .await?;
+        if deleted.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{repositories::label::LabelRepositoryForMemory, test_db::TestDb};
+
+    #[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+    struct Note {
+        id: i32,
+        text: String,
+    }
+
+    struct CreateNote(&'static str);
+
+    struct UpdateNote(Option<&'static str>);
+
+    impl Entity for Note {
+        fn id(&self) -> i32 {
+            self.id
+        }
+    }
+
+    impl Table for Note {
+        const TABLE: &'static str = "crud_notes";
+    }
+
+    impl NewEntity<Note> for CreateNote {
+        fn into_entity(self, id: i32) -> Note {
+            Note {
+                id,
+                text: self.0.to_string(),
+            }
+        }
+    }
+
+    impl EntityChange<Note> for UpdateNote {
+        fn apply_to(self, note: &mut Note) {
+            if let Some(text) = self.0 {
+                note.text = text.to_string();
+            }
+        }
+    }
+
+    impl Columns for CreateNote {
+        fn columns(self) -> Vec<(&'static str, SqlValue)> {
+            vec![("text", SqlValue::Text(self.0.to_string()))]
+        }
+    }
+
+    impl Columns for UpdateNote {
+        fn columns(self) -> Vec<(&'static str, SqlValue)> {
+            self.0
+                .map(|text| ("text", SqlValue::Text(text.to_string())))
+                .into_iter()
+                .collect()
+        }
+    }
+
+    /// What any `CrudRepository` of notes must do.
+    async fn note_scenario<R: CrudRepository<Note, CreateNote, UpdateNote>>(notes: R) {
+        let first = notes.create(CreateNote("first")).await.unwrap();
+        let second = notes.create(CreateNote("second")).await.unwrap();
+        assert_eq!(notes.find(first.id).await.unwrap(), first);
+        assert_eq!(
+            notes.all().await.unwrap(),
+            vec![first.clone(), second.clone()]
+        );
+
+        let updated = notes
+            .update(first.id, UpdateNote(Some("changed")))
+            .await
+            .unwrap();
+        assert_eq!(updated.text, "changed");
+        assert_eq!(
+            notes.update(first.id, UpdateNote(None)).await.unwrap(),
+            updated
+        );
+
+        let elsewhere = notes.in_workspace(2);
+        assert!(elsewhere.all().await.unwrap().is_empty());
+        let missing = elsewhere.find(first.id).await.unwrap_err();
+        assert!(matches!(
+            missing.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(_))
+        ));
+        assert!(elsewhere.delete(first.id).await.is_err());
+
+        notes.delete(first.id).await.unwrap();
+        assert_eq!(notes.all().await.unwrap(), vec![second]);
+        assert!(notes.update(first.id, UpdateNote(None)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn should_keep_entities_in_memory() {
+        note_scenario(CrudRepositoryForMemory::<Note>::new()).await;
+    }
+
+    #[tokio::test]
+    async fn should_serve_labels_as_crud_repository() {
+        async fn rename<R: CrudRepository<Label, CreateLabel, UpdateLabel>>(
+            labels: R,
+            id: i32,
+        ) -> Label {
+            let payload = UpdateLabel {
+                name: Some("chores".to_string()),
+                ..UpdateLabel::default()
+            };
+            labels.update(id, payload).await.unwrap()
+        }
+        let labels = LabelRepositoryForMemory::new();
+        let home = LabelRepository::create(&labels, CreateLabel::new("home".to_string()))
+            .await
+            .unwrap();
+
+        assert_eq!(rename(labels, home.id).await.name, "chores");
+    }
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        let db = TestDb::new().await;
+        sqlx::query(
+            r#"
+            create table crud_notes (
+                id serial primary key,
+                workspace_id integer not null,
+                text text not null
+            )
+        "#,
+        )
+        .execute(&db.pool)
+        .await
+        .unwrap();
+
+        note_scenario(CrudRepositoryForDb::<Note>::new(db.pool.clone())).await;
+    }
+}
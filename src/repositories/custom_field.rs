@@ -0,0 +1,237 @@
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use validator::{Validate, ValidationError};
+
+/// Fields a workspace may define at most.
+const MAX_FIELDS: usize = 50;
+
+/// Characters a `string` value holds at most.
+const MAX_TEXT: usize = 1000;
+
+/// A field the todos of a workspace carry under `custom`, so a team can
+/// track e.g. a sprint or a customer without a schema change.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct CustomField {
+    /// Lowercase letters, digits and underscores, as in `?custom.<name>=`.
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: FieldKind,
+    /// Todos must have a value for it.
+    #[serde(default)]
+    pub required: bool,
+    /// What an `enum` field may hold; other kinds take none.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub values: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldKind {
+    String,
+    Number,
+    Boolean,
+    /// A `YYYY-MM-DD` string.
+    Date,
+    /// One of the field's `values`.
+    Enum,
+}
+
+/// Every custom field of a workspace, in the order clients show them.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default, Validate)]
+pub struct CustomFields {
+    #[validate(custom = "validate_fields")]
+    pub fields: Vec<CustomField>,
+}
+
+fn validate_fields(fields: &[CustomField]) -> Result<(), ValidationError> {
+    let mut names = HashSet::new();
+    let problem = if fields.len() > MAX_FIELDS {
+        Some(format!("can not be over {}", MAX_FIELDS))
+    } else {
+        fields.iter().find_map(|field| {
+            let valid_name = !field.name.is_empty()
+                && field.name.len() <= 40
+                && field
+                    .name
+                    .bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_');
+            if !valid_name {
+                Some(format!("invalid name [{}]", field.name))
+            } else if !names.insert(field.name.as_str()) {
+                Some(format!("[{}] is defined twice", field.name))
+            } else if (field.kind == FieldKind::Enum) == field.values.is_empty() {
+                Some(format!(
+                    "[{}] needs values if and only if it is an enum",
+                    field.name
+                ))
+            } else {
+                None
+            }
+        })
+    };
+    match problem {
+        Some(problem) => {
+            let mut error = ValidationError::new("fields");
+            error.message = Some(problem.into());
+            Err(error)
+        }
+        None => Ok(()),
+    }
+}
+
+impl CustomField {
+    /// Why `value` does not fit the field, if it does not.
+    fn check(&self, value: &Value) -> Result<(), String> {
+        let fits = match (self.kind, value) {
+            (FieldKind::String, Value::String(text)) => text.chars().count() <= MAX_TEXT,
+            (FieldKind::Number, Value::Number(_)) | (FieldKind::Boolean, Value::Bool(_)) => true,
+            (FieldKind::Date, Value::String(text)) => text.parse::<NaiveDate>().is_ok(),
+            (FieldKind::Enum, Value::String(text)) => self.values.contains(text),
+            _ => false,
+        };
+        if fits {
+            return Ok(());
+        }
+        Err(match self.kind {
+            FieldKind::String => format!("[{}] must be a string of up to {}", self.name, MAX_TEXT),
+            FieldKind::Number => format!("[{}] must be a number", self.name),
+            FieldKind::Boolean => format!("[{}] must be true or false", self.name),
+            FieldKind::Date => format!("[{}] must be a date such as 2023-06-01", self.name),
+            FieldKind::Enum => format!(
+                "[{}] must be one of [{}]",
+                self.name,
+                self.values.join(", ")
+            ),
+        })
+    }
+
+    /// The value `?custom.<name>=<text>` looks for.
+    pub fn parse(&self, text: &str) -> Result<Value, String> {
+        let value = match self.kind {
+            FieldKind::Number => text
+                .parse::<serde_json::Number>()
+                .map(Value::Number)
+                .map_err(|_| format!("[{}] must be a number", self.name))?,
+            FieldKind::Boolean => text
+                .parse::<bool>()
+                .map(Value::Bool)
+                .map_err(|_| format!("[{}] must be true or false", self.name))?,
+            FieldKind::String | FieldKind::Date | FieldKind::Enum => {
+                Value::String(text.to_string())
+            }
+        };
+        self.check(&value)?;
+        Ok(value)
+    }
+}
+
+impl CustomFields {
+    pub fn get(&self, name: &str) -> Option<&CustomField> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// Why a todo can not hold `values`: a field is not defined, a value
+    /// does not fit its field, or a required field is missing.
+    pub fn check(&self, values: &BTreeMap<String, Value>) -> Result<(), String> {
+        for (name, value) in values {
+            self.get(name)
+                .ok_or_else(|| format!("unknown field [{}]", name))?
+                .check(value)?;
+        }
+        match self
+            .fields
+            .iter()
+            .find(|field| field.required && !values.contains_key(&field.name))
+        {
+            Some(field) => Err(format!("[{}] is required", field.name)),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn fields() -> CustomFields {
+        serde_json::from_value(json!({
+            "fields": [
+                { "name": "sprint", "type": "number", "required": true },
+                { "name": "size", "type": "enum", "values": ["s", "m", "l"] },
+                { "name": "review_on", "type": "date" },
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn should_validate_definitions() {
+        assert!(fields().validate().is_ok());
+        let invalid = |fields: Value| {
+            serde_json::from_value::<CustomFields>(json!({ "fields": fields }))
+                .unwrap()
+                .validate()
+                .is_err()
+        };
+        assert!(invalid(json!([{ "name": "Sprint", "type": "number" }])));
+        assert!(invalid(json!([
+            { "name": "sprint", "type": "number" },
+            { "name": "sprint", "type": "string" },
+        ])));
+        assert!(invalid(json!([{ "name": "size", "type": "enum" }])));
+        assert!(invalid(
+            json!([{ "name": "sprint", "type": "number", "values": ["1"] }])
+        ));
+    }
+
+    #[test]
+    fn should_check_values_against_definitions() {
+        let fields = fields();
+        let values = |values: Value| serde_json::from_value(values).unwrap();
+
+        assert!(fields
+            .check(&values(
+                json!({ "sprint": 12, "size": "m", "review_on": "2023-06-01" })
+            ))
+            .is_ok());
+        assert_eq!(
+            fields.check(&values(json!({ "size": "m" }))).unwrap_err(),
+            "[sprint] is required"
+        );
+        assert_eq!(
+            fields
+                .check(&values(json!({ "sprint": 12, "size": "xl" })))
+                .unwrap_err(),
+            "[size] must be one of [s, m, l]"
+        );
+        assert_eq!(
+            fields
+                .check(&values(json!({ "sprint": "12" })))
+                .unwrap_err(),
+            "[sprint] must be a number"
+        );
+        assert_eq!(
+            fields
+                .check(&values(json!({ "sprint": 12, "team": "a" })))
+                .unwrap_err(),
+            "unknown field [team]"
+        );
+    }
+
+    #[test]
+    fn should_parse_query_values_by_type() {
+        let fields = fields();
+        assert_eq!(
+            fields.get("sprint").unwrap().parse("12").unwrap(),
+            json!(12)
+        );
+        assert!(fields.get("sprint").unwrap().parse("twelve").is_err());
+        assert_eq!(fields.get("size").unwrap().parse("s").unwrap(), json!("s"));
+        assert!(fields.get("review_on").unwrap().parse("soon").is_err());
+    }
+}
@@ -0,0 +1,89 @@
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use super::todo::Todo;
+
+/// A todo of a digest, with the workspace it lives in.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct DigestTodo {
+    pub workspace_id: i32,
+    #[serde(flatten)]
+    pub todo: Todo,
+}
+
+/// The open todos a user has due soon, in all workspaces, soonest first
+/// within each group.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Digest {
+    /// The day, in UTC, the groups are counted from.
+    pub date: NaiveDate,
+    pub overdue: Vec<DigestTodo>,
+    pub today: Vec<DigestTodo>,
+    /// Due after today up to the Sunday ending the week.
+    pub this_week: Vec<DigestTodo>,
+}
+
+/// The last day a digest for `today` reaches: the Sunday of its week.
+pub fn week_end(today: NaiveDate) -> NaiveDate {
+    today + Duration::days(6 - today.weekday().num_days_from_monday() as i64)
+}
+
+impl Digest {
+    /// Sorts `todos`, as `TodoReader::due_by` returns them, into their
+    /// groups; those due past `week_end(today)` are left out.
+    pub fn of(date: NaiveDate, todos: Vec<(i32, Todo)>) -> Self {
+        let mut digest = Self {
+            date,
+            overdue: vec![],
+            today: vec![],
+            this_week: vec![],
+        };
+        for (workspace_id, todo) in todos {
+            let group = match todo.due_date {
+                Some(due) if due < date => &mut digest.overdue,
+                Some(due) if due == date => &mut digest.today,
+                Some(due) if due <= week_end(date) => &mut digest.this_week,
+                _ => continue,
+            };
+            group.push(DigestTodo { workspace_id, todo });
+        }
+        digest
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_group_by_due_day() {
+        let day = |day: &str| day.parse::<NaiveDate>().unwrap();
+        // a Wednesday
+        let today = day("2023-05-24");
+        assert_eq!(week_end(today), day("2023-05-28"));
+        assert_eq!(week_end(day("2023-05-28")), day("2023-05-28"));
+
+        let todos = [
+            (1, "2023-05-01"),
+            (2, "2023-05-24"),
+            (3, "2023-05-28"),
+            (4, "2023-05-29"),
+        ]
+        .into_iter()
+        .map(|(id, due)| {
+            let todo = Todo {
+                due_date: Some(day(due)),
+                ..Todo::new(id, format!("todo {}", id))
+            };
+            (2, todo)
+        })
+        .collect();
+        let digest = Digest::of(today, todos);
+        let ids =
+            |group: &[DigestTodo]| -> Vec<i32> { group.iter().map(|todo| todo.todo.id).collect() };
+        assert_eq!(ids(&digest.overdue), vec![1]);
+        assert_eq!(ids(&digest.today), vec![2]);
+        assert_eq!(ids(&digest.this_week), vec![3]);
+        assert_eq!(digest.today[0].workspace_id, 2);
+    }
+}
@@ -0,0 +1,19 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Mail to a user's plus-address becomes their todos in one workspace.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow)]
+pub struct EmailInbox {
+    pub workspace_id: i32,
+    pub user_id: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The token of a plus-address; only its hash is stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewEmailInbox {
+    pub token_hash: String,
+    pub workspace_id: i32,
+    pub user_id: i32,
+}
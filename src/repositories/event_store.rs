@@ -0,0 +1,1556 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use axum::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use sqlx::{types::Json, Executor, FromRow, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::{
+    board::{self, Positions},
+    burndown::{self, BurndownDay},
+    checklist::{self, ChecklistItem, CreateChecklistItem, Progress, UpdateChecklistItem},
+    label::{Label, LabelRow, LabelStats},
+    search::Search,
+    todo::{
+        check_labels, check_new_labels, claim_outbox, mark_sent, merge_custom, normalize_text,
+        place_on_board, record_events, select_board_positions, CreateTodo, OwnedTodos, PageCursor,
+        Todo, TodoChange, TodoFilter, TodoPage, TodoReader, TodoRevision, TodoScope, TodoWriter,
+        UpdateTodo,
+    },
+    workspace::DEFAULT_WORKSPACE_ID,
+    RepositoryError,
+};
+use crate::{database::DbUuid, events::DomainEvent, outbox::OutboxEntry};
+
+/// A stream is snapshotted whenever its length reaches a multiple of this.
+const SNAPSHOT_EVERY: i32 = 20;
+
+/// One change of a todo as stored in its stream; never updated or removed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TodoEvent {
+    Created {
+        text: String,
+        labels: Vec<i32>,
+        owner_id: Option<i32>,
+        assignee_id: Option<i32>,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        text_i18n: BTreeMap<String, String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        estimate_minutes: Option<i32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        due_date: Option<NaiveDate>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        custom: BTreeMap<String, Value>,
+    },
+    /// Holds the members `update` was given; the others kept their value.
+    Changed {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        completed: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        labels: Option<Vec<i32>>,
+        /// `Some(None)` unassigned the todo.
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            deserialize_with = "present"
+        )]
+        assignee_id: Option<Option<i32>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        text_i18n: Option<BTreeMap<String, String>>,
+        /// `Some(None)` dropped the estimate.
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            deserialize_with = "present"
+        )]
+        estimate_minutes: Option<Option<i32>>,
+        /// `Some(None)` dropped the due date.
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            deserialize_with = "present"
+        )]
+        due_date: Option<Option<NaiveDate>>,
+        /// `Some(None)` dropped the description.
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            deserialize_with = "present"
+        )]
+        description: Option<Option<String>>,
+        /// The custom fields set; `None` values were removed.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        custom: Option<BTreeMap<String, Option<Value>>>,
+    },
+    OwnerChanged {
+        owner_id: i32,
+    },
+    /// `None` woke the todo.
+    Snoozed {
+        until: Option<DateTime<Utc>>,
+    },
+    /// Checklist item `id`, put at `position` or last.
+    ItemAdded {
+        id: i32,
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        position: Option<u32>,
+    },
+    /// Holds the members `update_item` was given, like `Changed`.
+    ItemChanged {
+        id: i32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        completed: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        position: Option<u32>,
+    },
+    ItemRemoved {
+        id: i32,
+    },
+    Deleted,
+}
+
+/// Keeps a `null` apart from an absent member.
+fn present<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+    deserializer: D,
+) -> Result<Option<T>, D::Error> {
+    T::deserialize(deserializer).map(Some)
+}
+
+/// A stream folded up to some event, as kept in its snapshot.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TodoState {
+    pub text: String,
+    pub completed: bool,
+    /// Sorted, without duplicates.
+    pub labels: Vec<i32>,
+    pub owner_id: Option<i32>,
+    pub assignee_id: Option<i32>,
+    #[serde(default)]
+    pub text_i18n: BTreeMap<String, String>,
+    #[serde(default)]
+    pub snoozed_until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub estimate_minutes: Option<i32>,
+    #[serde(default)]
+    pub due_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub custom: BTreeMap<String, Value>,
+    /// The checklist, in order.
+    #[serde(default)]
+    pub items: Vec<ChecklistItem>,
+    /// Item ids are never reused within a stream.
+    #[serde(default)]
+    pub last_item_id: i32,
+    pub deleted: bool,
+}
+
+impl TodoState {
+    pub fn apply(mut self, event: &TodoEvent) -> Self {
+        match event {
+            TodoEvent::Created {
+                text,
+                labels,
+                owner_id,
+                assignee_id,
+                text_i18n,
+                estimate_minutes,
+                due_date,
+                description,
+                custom,
+            } => {
+                self = Self {
+                    text: text.clone(),
+                    labels: normalized(labels),
+                    owner_id: *owner_id,
+                    assignee_id: *assignee_id,
+                    text_i18n: text_i18n.clone(),
+                    estimate_minutes: *estimate_minutes,
+                    due_date: *due_date,
+                    description: description.clone(),
+                    custom: custom.clone(),
+                    ..Self::default()
+                };
+            }
+            TodoEvent::Changed {
+                text,
+                completed,
+                labels,
+                assignee_id,
+                text_i18n,
+                estimate_minutes,
+                due_date,
+                description,
+                custom,
+            } => {
+                if let Some(text) = text {
+                    self.text = text.clone();
+                }
+                if let Some(completed) = completed {
+                    self.completed = *completed;
+                }
+                if let Some(labels) = labels {
+                    self.labels = normalized(labels);
+                }
+                if let Some(assignee_id) = assignee_id {
+                    self.assignee_id = *assignee_id;
+                }
+                if let Some(text_i18n) = text_i18n {
+                    self.text_i18n = text_i18n.clone();
+                }
+                if let Some(estimate_minutes) = estimate_minutes {
+                    self.estimate_minutes = *estimate_minutes;
+                }
+                if let Some(due_date) = due_date {
+                    self.due_date = *due_date;
+                }
+                if let Some(description) = description {
+                    self.description = description.clone();
+                }
+                if let Some(custom) = custom {
+                    merge_custom(&mut self.custom, custom);
+                }
+            }
+            TodoEvent::OwnerChanged { owner_id } => self.owner_id = Some(*owner_id),
+            TodoEvent::Snoozed { until } => self.snoozed_until = *until,
+            TodoEvent::ItemAdded { id, text, position } => {
+                self.last_item_id = self.last_item_id.max(*id);
+                let item = ChecklistItem {
+                    id: *id,
+                    text: text.clone(),
+                    completed: false,
+                    position: 0,
+                };
+                checklist::insert(&mut self.items, item, *position);
+            }
+            // the item was checked before the event was appended
+            TodoEvent::ItemChanged {
+                id,
+                text,
+                completed,
+                position,
+            } => {
+                let change = UpdateChecklistItem {
+                    text: text.clone(),
+                    completed: *completed,
+                    position: *position,
+                };
+                let _ = checklist::update(&mut self.items, *id, change);
+            }
+            TodoEvent::ItemRemoved { id } => {
+                let _ = checklist::remove(&mut self.items, *id);
+            }
+            TodoEvent::Deleted => self.deleted = true,
+        }
+        self
+    }
+
+    /// Every state the todo was created or updated into, oldest first, as
+    /// `revisions` reports them.
+    pub fn history(todo_id: i32, events: &[(TodoEvent, DateTime<Utc>)]) -> Vec<TodoRevision> {
+        let mut state = Self::default();
+        let mut revisions = vec![];
+        for (event, created_at) in events {
+            state = state.apply(event);
+            if matches!(event, TodoEvent::Created { .. } | TodoEvent::Changed { .. }) {
+                revisions.push(TodoRevision {
+                    todo_id,
+                    rev: revisions.len() as i32 + 1,
+                    text: state.text.clone(),
+                    completed: state.completed,
+                    estimate_minutes: state.estimate_minutes,
+                    created_at: *created_at,
+                });
+            }
+        }
+        revisions
+    }
+}
+
+fn normalized(labels: &[i32]) -> Vec<i32> {
+    let mut labels = labels.to_vec();
+    labels.sort_unstable();
+    labels.dedup();
+    labels
+}
+
+#[derive(Debug, Clone)]
+struct Stream {
+    id: i32,
+    uuid: Uuid,
+    workspace_id: i32,
+    /// Number of events so far.
+    seq: i32,
+    state: TodoState,
+}
+
+#[derive(Debug, FromRow)]
+struct StreamRow {
+    id: i32,
+    uuid: DbUuid,
+    workspace_id: i32,
+    snapshot: Option<Json<TodoState>>,
+    snapshot_seq: i32,
+    /// The events after the snapshot.
+    events: Json<Vec<TodoEvent>>,
+}
+
+impl From<StreamRow> for Stream {
+    fn from(row: StreamRow) -> Self {
+        let state = row.events.0.iter().fold(
+            row.snapshot.map(|snapshot| snapshot.0).unwrap_or_default(),
+            TodoState::apply,
+        );
+        Self {
+            id: row.id,
+            uuid: row.uuid.0,
+            workspace_id: row.workspace_id,
+            seq: row.snapshot_seq + row.events.0.len() as i32,
+            state,
+        }
+    }
+}
+
+/// Keeps every change of a todo as an event in the todo's stream and
+/// rebuilds the todo by folding them, so its history is complete by
+/// construction. Listings fold every stream of the workspace and the
+/// account-wide lookups every stream there is; snapshots keep each fold
+/// short.
+#[derive(Debug, Clone)]
+pub struct TodoRepositoryForEventStore {
+    pool: PgPool,
+    workspace_id: i32,
+    filter: TodoFilter,
+    actor: Option<i32>,
+}
+
+impl TodoRepositoryForEventStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            workspace_id: DEFAULT_WORKSPACE_ID,
+            filter: TodoFilter::default(),
+            actor: None,
+        }
+    }
+
+    async fn update_checked(
+        &self,
+        id: i32,
+        expected: Option<&Todo>,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<Todo> {
+        let mut tx = self.pool.begin().await?;
+        let todo = self.change(&mut tx, id, expected, payload).await?;
+        tx.commit().await?;
+
+        Ok(todo)
+    }
+
+    /// Creates a stream for a new todo as part of `tx`.
+    async fn insert(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        payload: CreateTodo,
+    ) -> anyhow::Result<Todo> {
+        check_new_labels(&mut *tx, self.workspace_id, &payload.labels).await?;
+        let uuid = Uuid::now_v7();
+        let (id,) = sqlx::query_as::<_, (i32,)>(
+            r#"
+            insert into todo_streams (workspace_id, uuid) values ($1, $2) returning id
+        "#,
+        )
+        .bind(self.workspace_id)
+        .bind(DbUuid(uuid))
+        .fetch_one(&mut *tx)
+        .await?;
+        let stream = Stream {
+            id,
+            uuid,
+            workspace_id: self.workspace_id,
+            seq: 0,
+            state: TodoState::default(),
+        };
+        let event = TodoEvent::Created {
+            text: payload.text,
+            labels: payload.labels,
+            owner_id: payload.owner_id,
+            assignee_id: payload.assignee_id,
+            text_i18n: payload.text_i18n,
+            estimate_minutes: payload.estimate_minutes,
+            due_date: payload.due_date,
+            description: payload.description,
+            custom: payload.custom,
+        };
+        let stream = append(&mut *tx, stream, &[event]).await?;
+        let todo = resolve_one(&mut *tx, stream).await?;
+        record_events(
+            &mut *tx,
+            self.workspace_id,
+            DomainEvent::created(&todo, self.actor),
+        )
+        .await?;
+        Ok(todo)
+    }
+
+    /// Appends the change of todo `id` as part of `tx`, unless it no longer
+    /// equals `expected`.
+    async fn change(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: i32,
+        expected: Option<&Todo>,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<Todo> {
+        let stream = lock_stream(&mut *tx, self.workspace_id, id).await?;
+        let before = resolve_one(&mut *tx, stream.clone()).await?;
+        if expected.is_some_and(|expected| before != *expected) {
+            return Err(RepositoryError::Conflict(id).into());
+        }
+        if let Some(labels) = &payload.labels {
+            check_labels(&mut *tx, self.workspace_id, labels).await?;
+        }
+        let event = TodoEvent::Changed {
+            text: payload.text,
+            completed: payload.completed,
+            labels: payload.labels,
+            assignee_id: payload.assignee_id,
+            text_i18n: payload.text_i18n,
+            estimate_minutes: payload.estimate_minutes,
+            due_date: payload.due_date,
+            description: payload.description,
+            custom: payload.custom,
+        };
+        let stream = append(&mut *tx, stream, &[event]).await?;
+        let todo = resolve_one(&mut *tx, stream).await?;
+        record_events(
+            &mut *tx,
+            self.workspace_id,
+            DomainEvent::changed(&before, &todo, self.actor),
+        )
+        .await?;
+
+        Ok(todo)
+    }
+
+    /// Ends the stream of todo `id` as part of `tx`.
+    async fn remove(&self, tx: &mut Transaction<'_, Postgres>, id: i32) -> anyhow::Result<()> {
+        let stream = lock_stream(&mut *tx, self.workspace_id, id).await?;
+        append(&mut *tx, stream, &[TodoEvent::Deleted]).await?;
+        record_events(
+            &mut *tx,
+            self.workspace_id,
+            vec![DomainEvent::TodoDeleted(id)],
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Appends the checklist event `event` makes of the locked stream, or
+    /// fails with what it returns, recording the todo as changed; returns
+    /// the stream afterwards.
+    async fn change_checklist(
+        &self,
+        id: i32,
+        event: impl FnOnce(&TodoState) -> Result<TodoEvent, RepositoryError> + Send,
+    ) -> anyhow::Result<Stream> {
+        let mut tx = self.pool.begin().await?;
+        let stream = lock_stream(&mut tx, self.workspace_id, id).await?;
+        let before = resolve_one(&mut tx, stream.clone()).await?;
+        let event = event(&stream.state)?;
+        let stream = append(&mut tx, stream, &[event]).await?;
+        let todo = resolve_one(&mut tx, stream.clone()).await?;
+        record_events(
+            &mut tx,
+            self.workspace_id,
+            DomainEvent::changed(&before, &todo, self.actor),
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(stream)
+    }
+}
+
+/// Item `item_id` of a checklist, or NotFound.
+fn item(state: &TodoState, item_id: i32) -> Result<ChecklistItem, RepositoryError> {
+    state
+        .items
+        .iter()
+        .find(|item| item.id == item_id)
+        .cloned()
+        .ok_or(RepositoryError::NotFound(item_id))
+}
+
+/// Streams that are not deleted, narrowed to `workspace_id` and `id` when
+/// given.
+async fn load_streams<'e, E: Executor<'e, Database = Postgres>>(
+    executor: E,
+    workspace_id: Option<i32>,
+    id: Option<i32>,
+) -> anyhow::Result<Vec<Stream>> {
+    let rows = sqlx::query_as::<_, StreamRow>(
+        r#"
+        select todo_streams.id, todo_streams.uuid, todo_streams.workspace_id, todo_streams.snapshot,
+            todo_streams.snapshot_seq,
+            coalesce(
+                json_agg(todo_events.event order by todo_events.seq)
+                    filter (where todo_events.seq is not null),
+                '[]'
+            ) as events
+        from todo_streams
+        left join todo_events on todo_events.todo_id = todo_streams.id
+            and todo_events.seq > todo_streams.snapshot_seq
+        where not todo_streams.deleted
+            and ($1::integer is null or todo_streams.workspace_id = $1)
+            and ($2::integer is null or todo_streams.id = $2)
+        group by todo_streams.id
+        order by todo_streams.id
+    "#,
+    )
+    .bind(workspace_id)
+    .bind(id)
+    .fetch_all(executor)
+    .await?;
+
+    Ok(rows.into_iter().map(Stream::from).collect())
+}
+
+/// Locks the stream for the rest of `tx`, then loads it.
+async fn lock_stream(
+    tx: &mut Transaction<'_, Postgres>,
+    workspace_id: i32,
+    id: i32,
+) -> anyhow::Result<Stream> {
+    sqlx::query(
+        r#"
+        select id from todo_streams
+        where id=$1 and workspace_id=$2 and not deleted for update
+    "#,
+    )
+    .bind(id)
+    .bind(workspace_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(RepositoryError::NotFound(id))?;
+    let stream = load_streams(&mut *tx, Some(workspace_id), Some(id))
+        .await?
+        .pop()
+        .ok_or(RepositoryError::NotFound(id))?;
+
+    Ok(stream)
+}
+
+/// Appends `events` and returns the stream as it is afterwards, snapshotting
+/// it when its length passes a multiple of `SNAPSHOT_EVERY`.
+async fn append(
+    tx: &mut Transaction<'_, Postgres>,
+    mut stream: Stream,
+    events: &[TodoEvent],
+) -> anyhow::Result<Stream> {
+    let mut snapshot = false;
+    for event in events {
+        stream.seq += 1;
+        stream.state = stream.state.apply(event);
+        snapshot |= stream.seq % SNAPSHOT_EVERY == 0;
+        sqlx::query(
+            r#"
+            insert into todo_events (todo_id, seq, event) values ($1, $2, $3)
+        "#,
+        )
+        .bind(stream.id)
+        .bind(stream.seq)
+        .bind(Json(event))
+        .execute(&mut *tx)
+        .await?;
+    }
+    if snapshot || stream.state.deleted {
+        sqlx::query(
+            r#"
+            update todo_streams set snapshot=$2, snapshot_seq=$3, deleted=$4 where id=$1
+        "#,
+        )
+        .bind(stream.id)
+        .bind(Json(&stream.state))
+        .bind(stream.seq)
+        .bind(stream.state.deleted)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    Ok(stream)
+}
+
+/// Turns streams into todos. Labels deleted since and users that are gone no
+/// longer show, as the foreign keys see to for `TodoRepositoryForDb`.
+async fn resolve<'e, E: Executor<'e, Database = Postgres>>(
+    executor: E,
+    streams: Vec<Stream>,
+) -> anyhow::Result<Vec<Todo>> {
+    let mut label_ids: Vec<i32> = vec![];
+    let mut user_ids: Vec<i32> = vec![];
+    for stream in &streams {
+        label_ids.extend(&stream.state.labels);
+        user_ids.extend(stream.state.owner_id);
+        user_ids.extend(stream.state.assignee_id);
+    }
+    let (labels, users) = sqlx::query_as::<_, (Json<Vec<Label>>, Vec<i32>)>(
+        r#"
+        select
+            coalesce(
+                (select json_agg(json_build_object(
+                        'id', id, 'uuid', uuid, 'name', name, 'color', color, 'icon', icon
+                    ))
+                    from labels where id = any($1)),
+                '[]'
+            ),
+            coalesce((select array_agg(id) from users where id = any($2)), '{}')
+    "#,
+    )
+    .bind(label_ids)
+    .bind(user_ids)
+    .fetch_one(executor)
+    .await?;
+    let labels: HashMap<i32, Label> = labels
+        .0
+        .into_iter()
+        .map(|label| (label.id, label))
+        .collect();
+    let users: HashSet<i32> = users.into_iter().collect();
+    let known = |user_id: Option<i32>| user_id.filter(|id| users.contains(id));
+
+    Ok(streams
+        .into_iter()
+        .map(|stream| Todo {
+            id: stream.id,
+            uuid: stream.uuid,
+            labels: stream
+                .state
+                .labels
+                .iter()
+                .filter_map(|id| labels.get(id).cloned())
+                .collect(),
+            owner_id: known(stream.state.owner_id),
+            assignee_id: known(stream.state.assignee_id),
+            completed: stream.state.completed,
+            text: stream.state.text,
+            text_i18n: stream.state.text_i18n,
+            snoozed_until: stream.state.snoozed_until,
+            estimate_minutes: stream.state.estimate_minutes,
+            due_date: stream.state.due_date,
+            description: stream.state.description,
+            custom: stream.state.custom,
+            progress: Progress::of(&stream.state.items),
+        })
+        .collect())
+}
+
+async fn resolve_one<'e, E: Executor<'e, Database = Postgres>>(
+    executor: E,
+    stream: Stream,
+) -> anyhow::Result<Todo> {
+    let todo = resolve(executor, vec![stream])
+        .await?
+        .pop()
+        .expect("one todo per stream");
+    Ok(todo)
+}
+
+impl TodoScope for TodoRepositoryForEventStore {
+    fn in_workspace(&self, workspace_id: i32) -> Self {
+        Self {
+            workspace_id,
+            ..self.clone()
+        }
+    }
+}
+
+#[async_trait]
+impl TodoReader for TodoRepositoryForEventStore {
+    fn with_filter(&self, filter: TodoFilter) -> Self {
+        Self {
+            filter,
+            ..self.clone()
+        }
+    }
+    #[tracing::instrument(skip_all)]
+    async fn find(&self, id: i32) -> anyhow::Result<Todo> {
+        let stream = load_streams(&self.pool, Some(self.workspace_id), Some(id))
+            .await?
+            .pop()
+            .ok_or(RepositoryError::NotFound(id))?;
+        resolve_one(&self.pool, stream).await
+    }
+    #[tracing::instrument(skip_all)]
+    async fn all(&self) -> anyhow::Result<Vec<Todo>> {
+        let streams = load_streams(&self.pool, Some(self.workspace_id), None).await?;
+        let mut todos: Vec<Todo> = resolve(&self.pool, streams)
+            .await?
+            .into_iter()
+            .filter(|todo| self.filter.matches(todo))
+            .collect();
+        self.filter.sort(&mut todos);
+        Ok(todos)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn count(&self) -> anyhow::Result<i64> {
+        Ok(self.all().await?.len() as i64)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn exists(&self, id: i32) -> anyhow::Result<bool> {
+        let (exists,) = sqlx::query_as::<_, (bool,)>(
+            r#"
+            select exists(
+                select 1 from todo_streams where id=$1 and workspace_id=$2 and not deleted
+            )
+        "#,
+        )
+        .bind(id)
+        .bind(self.workspace_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(exists)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn id_of(&self, uuid: Uuid) -> anyhow::Result<Option<i32>> {
+        let id = sqlx::query_as::<_, (i32,)>(
+            r#"
+            select id from todo_streams where uuid=$1 and workspace_id=$2 and not deleted
+        "#,
+        )
+        .bind(DbUuid(uuid))
+        .bind(self.workspace_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(id.map(|(id,)| id))
+    }
+    #[tracing::instrument(skip_all)]
+    async fn find_open_duplicate(&self, text: &str) -> anyhow::Result<Option<Todo>> {
+        let text = normalize_text(text);
+        let todos = self.with_filter(TodoFilter::default()).all().await?;
+        Ok(todos
+            .into_iter()
+            .rev()
+            .find(|todo| !todo.completed && normalize_text(&todo.text) == text))
+    }
+    #[tracing::instrument(skip_all)]
+    async fn page(&self, cursor: PageCursor, limit: u32) -> anyhow::Result<TodoPage> {
+        Ok(TodoPage::slice(self.all().await?, cursor, limit))
+    }
+    #[tracing::instrument(skip_all)]
+    async fn revisions(&self, id: i32) -> anyhow::Result<Vec<TodoRevision>> {
+        self.find(id).await?;
+        let events = sqlx::query_as::<_, (Json<TodoEvent>, DateTime<Utc>)>(
+            r#"
+            select event, created_at from todo_events where todo_id=$1 order by seq
+        "#,
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|(event, created_at)| (event.0, created_at))
+        .collect::<Vec<_>>();
+
+        Ok(TodoState::history(id, &events))
+    }
+    #[tracing::instrument(skip_all)]
+    async fn count_open_owned(&self, owner_id: i32) -> anyhow::Result<i64> {
+        let count = load_streams(&self.pool, None, None)
+            .await?
+            .iter()
+            .filter(|stream| stream.state.owner_id == Some(owner_id) && !stream.state.completed)
+            .count();
+        Ok(count as i64)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn owned(&self, owner_id: i32) -> anyhow::Result<Vec<(i32, Todo)>> {
+        let streams: Vec<Stream> = load_streams(&self.pool, None, None)
+            .await?
+            .into_iter()
+            .filter(|stream| stream.state.owner_id == Some(owner_id))
+            .collect();
+        let workspaces: Vec<i32> = streams.iter().map(|stream| stream.workspace_id).collect();
+        let todos = resolve(&self.pool, streams).await?;
+
+        Ok(workspaces.into_iter().zip(todos).collect())
+    }
+    /// Counted from the folded streams; the labels they name live in no
+    /// table to aggregate.
+    #[tracing::instrument(skip_all)]
+    async fn label_stats(&self) -> anyhow::Result<Vec<LabelStats>> {
+        let mut counts: HashMap<i32, i64> = HashMap::new();
+        for stream in load_streams(&self.pool, Some(self.workspace_id), None).await? {
+            for label in stream.state.labels {
+                *counts.entry(label).or_default() += 1;
+            }
+        }
+        let labels = sqlx::query_as::<_, LabelRow>(
+            r#"
+            select * from labels where workspace_id=$1 order by id
+        "#,
+        )
+        .bind(self.workspace_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(labels
+            .into_iter()
+            .map(Label::from)
+            .map(|label| LabelStats {
+                todos: counts.get(&label.id).copied().unwrap_or_default(),
+                label,
+            })
+            .collect())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn suggest_labels(&self, search: &Search, limit: u32) -> anyhow::Result<Vec<LabelStats>> {
+        Ok(LabelStats::suggest(
+            self.label_stats().await?,
+            search,
+            limit,
+        ))
+    }
+    #[tracing::instrument(skip_all)]
+    async fn checklist(&self, id: i32) -> anyhow::Result<Vec<ChecklistItem>> {
+        let stream = load_streams(&self.pool, Some(self.workspace_id), Some(id))
+            .await?
+            .pop()
+            .ok_or(RepositoryError::NotFound(id))?;
+        Ok(stream.state.items)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn burndown(&self, from: NaiveDate, to: NaiveDate) -> anyhow::Result<Vec<BurndownDay>> {
+        let rows = sqlx::query_as::<_, (i32, Json<TodoEvent>, DateTime<Utc>)>(
+            r#"
+            select todo_events.todo_id, todo_events.event, todo_events.created_at
+            from todo_events
+            join todo_streams on todo_streams.id = todo_events.todo_id
+            where todo_streams.workspace_id=$1 and not todo_streams.deleted
+            order by todo_events.todo_id, todo_events.seq
+        "#,
+        )
+        .bind(self.workspace_id)
+        .fetch_all(&self.pool)
+        .await?;
+        let mut streams: BTreeMap<i32, Vec<(TodoEvent, DateTime<Utc>)>> = BTreeMap::new();
+        for (id, event, created_at) in rows {
+            streams.entry(id).or_default().push((event.0, created_at));
+        }
+        let revisions: Vec<TodoRevision> = streams
+            .iter()
+            .flat_map(|(id, events)| TodoState::history(*id, events))
+            .collect();
+
+        Ok(burndown::from_revisions(&revisions, from, to))
+    }
+    /// Kept in the table `TodoRepositoryForDb` uses; where a todo sits on
+    /// the board is no part of its history.
+    #[tracing::instrument(skip_all)]
+    async fn board_positions(&self) -> anyhow::Result<Positions> {
+        select_board_positions(&self.pool, self.workspace_id).await
+    }
+    #[tracing::instrument(skip_all)]
+    async fn due_by(&self, user_id: i32, by: NaiveDate) -> anyhow::Result<Vec<(i32, Todo)>> {
+        let mut streams: Vec<Stream> = load_streams(&self.pool, None, None)
+            .await?
+            .into_iter()
+            .filter(|stream| {
+                let state = &stream.state;
+                !state.completed
+                    && state.due_date.is_some_and(|due| due <= by)
+                    && state.assignee_id.or(state.owner_id) == Some(user_id)
+            })
+            .collect();
+        streams.sort_by_key(|stream| (stream.state.due_date, stream.id));
+        let workspaces: Vec<i32> = streams.iter().map(|stream| stream.workspace_id).collect();
+        let todos = resolve(&self.pool, streams).await?;
+
+        Ok(workspaces.into_iter().zip(todos).collect())
+    }
+}
+
+#[async_trait]
+impl TodoWriter for TodoRepositoryForEventStore {
+    #[tracing::instrument(skip_all)]
+    async fn create(&self, payload: CreateTodo) -> anyhow::Result<Todo> {
+        let mut tx = self.pool.begin().await?;
+        let todo = self.insert(&mut tx, payload).await?;
+        tx.commit().await?;
+
+        Ok(todo)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<Todo> {
+        self.update_checked(id, None, payload).await
+    }
+    #[tracing::instrument(skip_all)]
+    async fn update_if(
+        &self,
+        id: i32,
+        expected: &Todo,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<Todo> {
+        self.update_checked(id, Some(expected), payload).await
+    }
+    #[tracing::instrument(skip_all)]
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        self.remove(&mut tx, id).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn revert(&self, id: i32, rev: i32) -> anyhow::Result<Todo> {
+        let revision = self
+            .revisions(id)
+            .await?
+            .into_iter()
+            .find(|revision| revision.rev == rev)
+            .ok_or(RepositoryError::NotFound(rev))?;
+
+        self.update(
+            id,
+            UpdateTodo {
+                text: Some(revision.text),
+                completed: Some(revision.completed),
+                labels: None,
+                assignee_id: None,
+                text_i18n: None,
+                estimate_minutes: Some(revision.estimate_minutes),
+                due_date: None,
+                description: None,
+                custom: None,
+            },
+        )
+        .await
+    }
+    #[tracing::instrument(skip_all)]
+    async fn delete_unused_labels(&self) -> anyhow::Result<Vec<i32>> {
+        let mut tx = self.pool.begin().await?;
+        let used: HashSet<i32> = load_streams(&mut tx, Some(self.workspace_id), None)
+            .await?
+            .into_iter()
+            .flat_map(|stream| stream.state.labels)
+            .collect();
+        let mut deleted: Vec<i32> = sqlx::query_as::<_, (i32,)>(
+            r#"
+            delete from labels where workspace_id=$1 and id <> all($2) returning id
+        "#,
+        )
+        .bind(self.workspace_id)
+        .bind(used.into_iter().collect::<Vec<_>>())
+        .fetch_all(&mut tx)
+        .await?
+        .into_iter()
+        .map(|(id,)| id)
+        .collect();
+        tx.commit().await?;
+        deleted.sort_unstable();
+
+        Ok(deleted)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn merge_labels(&self, source: i32, target: i32) -> anyhow::Result<Vec<Todo>> {
+        let mut tx = self.pool.begin().await?;
+        check_labels(&mut tx, self.workspace_id, &[source, target]).await?;
+        let ids: Vec<i32> = load_streams(&mut tx, Some(self.workspace_id), None)
+            .await?
+            .into_iter()
+            .filter(|stream| stream.state.labels.contains(&source))
+            .map(|stream| stream.id)
+            .collect();
+        let mut moved = Vec::new();
+        for id in ids {
+            let stream = lock_stream(&mut tx, self.workspace_id, id).await?;
+            let before = resolve_one(&mut tx, stream.clone()).await?;
+            let labels = stream
+                .state
+                .labels
+                .iter()
+                .map(|label| if *label == source { target } else { *label })
+                .collect();
+            let event = TodoEvent::Changed {
+                text: None,
+                completed: None,
+                labels: Some(labels),
+                assignee_id: None,
+                text_i18n: None,
+                estimate_minutes: None,
+                due_date: None,
+                description: None,
+                custom: None,
+            };
+            let stream = append(&mut tx, stream, &[event]).await?;
+            let todo = resolve_one(&mut tx, stream).await?;
+            record_events(
+                &mut tx,
+                self.workspace_id,
+                DomainEvent::changed(&before, &todo, self.actor),
+            )
+            .await?;
+            moved.push(todo);
+        }
+        sqlx::query(
+            r#"
+            delete from labels where id=$1 and workspace_id=$2
+        "#,
+        )
+        .bind(source)
+        .bind(self.workspace_id)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(moved)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn snooze(&self, id: i32, until: Option<DateTime<Utc>>) -> anyhow::Result<Todo> {
+        let mut tx = self.pool.begin().await?;
+        let stream = lock_stream(&mut tx, self.workspace_id, id).await?;
+        let before = resolve_one(&mut tx, stream.clone()).await?;
+        let stream = append(&mut tx, stream, &[TodoEvent::Snoozed { until }]).await?;
+        let todo = resolve_one(&mut tx, stream).await?;
+        record_events(
+            &mut tx,
+            self.workspace_id,
+            DomainEvent::changed(&before, &todo, self.actor),
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(todo)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn unsnooze_due(&self) -> anyhow::Result<Vec<(i32, i32)>> {
+        let now = Utc::now();
+        let due = |stream: &Stream| stream.state.snoozed_until.is_some_and(|until| until <= now);
+        let mut tx = self.pool.begin().await?;
+        let mut snoozed: Vec<(i32, i32)> = load_streams(&mut tx, None, None)
+            .await?
+            .into_iter()
+            .filter(due)
+            .map(|stream| (stream.workspace_id, stream.id))
+            .collect();
+        snoozed.sort();
+        let mut woken = Vec::with_capacity(snoozed.len());
+        for (workspace_id, id) in snoozed {
+            let stream = lock_stream(&mut tx, workspace_id, id).await?;
+            // snoozed again since it was listed
+            if !due(&stream) {
+                continue;
+            }
+            let stream = append(&mut tx, stream, &[TodoEvent::Snoozed { until: None }]).await?;
+            let event = DomainEvent::TodoUpdated(resolve_one(&mut tx, stream).await?);
+            record_events(&mut tx, workspace_id, vec![event]).await?;
+            woken.push((workspace_id, id));
+        }
+        tx.commit().await?;
+
+        Ok(woken)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn add_item(
+        &self,
+        id: i32,
+        payload: CreateChecklistItem,
+    ) -> anyhow::Result<ChecklistItem> {
+        let mut item_id = 0;
+        let stream = self
+            .change_checklist(id, |state| {
+                item_id = state.last_item_id + 1;
+                Ok(TodoEvent::ItemAdded {
+                    id: item_id,
+                    text: payload.text,
+                    position: payload.position,
+                })
+            })
+            .await?;
+        Ok(item(&stream.state, item_id)?)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn update_item(
+        &self,
+        id: i32,
+        item_id: i32,
+        payload: UpdateChecklistItem,
+    ) -> anyhow::Result<ChecklistItem> {
+        let stream = self
+            .change_checklist(id, |state| {
+                item(state, item_id)?;
+                Ok(TodoEvent::ItemChanged {
+                    id: item_id,
+                    text: payload.text,
+                    completed: payload.completed,
+                    position: payload.position,
+                })
+            })
+            .await?;
+        Ok(item(&stream.state, item_id)?)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn delete_item(&self, id: i32, item_id: i32) -> anyhow::Result<()> {
+        self.change_checklist(id, |state| {
+            item(state, item_id)?;
+            Ok(TodoEvent::ItemRemoved { id: item_id })
+        })
+        .await?;
+        Ok(())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn move_on_board(&self, id: i32, completed: bool, position: u32) -> anyhow::Result<Todo> {
+        let mut tx = self.pool.begin().await?;
+        let stream = lock_stream(&mut tx, self.workspace_id, id).await?;
+        let mut todo = resolve_one(&mut tx, stream.clone()).await?;
+        if todo.completed != completed {
+            let event = TodoEvent::Changed {
+                text: None,
+                completed: Some(completed),
+                labels: None,
+                assignee_id: None,
+                text_i18n: None,
+                estimate_minutes: None,
+                due_date: None,
+                description: None,
+                custom: None,
+            };
+            let stream = append(&mut tx, stream, &[event]).await?;
+            let after = resolve_one(&mut tx, stream).await?;
+            record_events(
+                &mut tx,
+                self.workspace_id,
+                DomainEvent::changed(&todo, &after, self.actor),
+            )
+            .await?;
+            todo = after;
+        }
+        let positions = select_board_positions(&mut tx, self.workspace_id).await?;
+        let column = load_streams(&mut tx, Some(self.workspace_id), None)
+            .await?
+            .into_iter()
+            .filter(|stream| stream.state.completed == completed)
+            .map(|stream| (stream.id, positions.get(&stream.id).copied()))
+            .collect();
+        place_on_board(
+            &mut tx,
+            self.workspace_id,
+            &board::reorder(column, id, position),
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(todo)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn release_owned(
+        &self,
+        owner_id: i32,
+        release: OwnedTodos,
+    ) -> anyhow::Result<Vec<(i32, i32)>> {
+        let mut tx = self.pool.begin().await?;
+        let mut owned: Vec<(i32, i32)> = load_streams(&mut tx, None, None)
+            .await?
+            .into_iter()
+            .filter(|stream| stream.state.owner_id == Some(owner_id))
+            .map(|stream| (stream.workspace_id, stream.id))
+            .collect();
+        owned.sort();
+        let mut released = Vec::with_capacity(owned.len());
+        for (workspace_id, id) in owned {
+            let stream = lock_stream(&mut tx, workspace_id, id).await?;
+            // changed hands since it was listed
+            if stream.state.owner_id != Some(owner_id) {
+                continue;
+            }
+            let event = match release {
+                OwnedTodos::Delete => {
+                    append(&mut tx, stream, &[TodoEvent::Deleted]).await?;
+                    DomainEvent::TodoDeleted(id)
+                }
+                OwnedTodos::TransferTo(new_owner) => {
+                    let changed = TodoEvent::OwnerChanged {
+                        owner_id: new_owner,
+                    };
+                    let stream = append(&mut tx, stream, &[changed]).await?;
+                    DomainEvent::TodoUpdated(resolve_one(&mut tx, stream).await?)
+                }
+            };
+            record_events(&mut tx, workspace_id, vec![event]).await?;
+            released.push((workspace_id, id));
+        }
+        tx.commit().await?;
+
+        Ok(released)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn apply(&self, changes: Vec<TodoChange>) -> anyhow::Result<Vec<Option<Todo>>> {
+        let mut tx = self.pool.begin().await?;
+        let mut changed = Vec::with_capacity(changes.len());
+        for change in changes {
+            changed.push(match change {
+                TodoChange::Create(payload) => Some(self.insert(&mut tx, payload).await?),
+                TodoChange::Update(id, payload) => {
+                    Some(self.change(&mut tx, id, None, payload).await?)
+                }
+                TodoChange::Delete(id) => {
+                    self.remove(&mut tx, id).await?;
+                    None
+                }
+            });
+        }
+        tx.commit().await?;
+
+        Ok(changed)
+    }
+    fn acting_as(&self, user_id: Option<i32>) -> Self {
+        Self {
+            actor: user_id,
+            ..self.clone()
+        }
+    }
+    #[tracing::instrument(skip_all)]
+    async fn claim_outbox(&self, limit: u32) -> anyhow::Result<Vec<OutboxEntry>> {
+        claim_outbox(&self.pool, limit).await
+    }
+    #[tracing::instrument(skip_all)]
+    async fn mark_sent(&self, ids: &[i64]) -> anyhow::Result<()> {
+        mark_sent(&self.pool, ids).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        repositories::label::{CreateLabel, LabelRepository, LabelRepositoryForDb},
+        test_db::TestDb,
+    };
+
+    #[test]
+    fn should_fold_events() {
+        let until: DateTime<Utc> = "2030-01-01T09:00:00Z".parse().unwrap();
+        let due: NaiveDate = "2030-01-31".parse().unwrap();
+        let events = [
+            TodoEvent::Created {
+                text: "write".to_string(),
+                labels: vec![3, 1, 3],
+                owner_id: Some(7),
+                assignee_id: Some(7),
+                text_i18n: BTreeMap::from([("ja".to_string(), "書く".to_string())]),
+                estimate_minutes: Some(30),
+                due_date: Some(due),
+                description: Some("notes".to_string()),
+                custom: BTreeMap::from([
+                    ("sprint".to_string(), Value::from(12)),
+                    ("size".to_string(), Value::from("m")),
+                ]),
+            },
+            TodoEvent::Changed {
+                text: None,
+                completed: Some(true),
+                labels: None,
+                assignee_id: Some(None),
+                text_i18n: None,
+                estimate_minutes: Some(Some(45)),
+                due_date: None,
+                description: None,
+                custom: Some(BTreeMap::from([
+                    ("sprint".to_string(), Some(Value::from(13))),
+                    ("size".to_string(), None),
+                ])),
+            },
+            TodoEvent::OwnerChanged { owner_id: 8 },
+            TodoEvent::Snoozed { until: Some(until) },
+            TodoEvent::ItemAdded {
+                id: 1,
+                text: "outline".to_string(),
+                position: None,
+            },
+            TodoEvent::ItemAdded {
+                id: 2,
+                text: "draft".to_string(),
+                position: None,
+            },
+            TodoEvent::ItemChanged {
+                id: 1,
+                text: None,
+                completed: Some(true),
+                position: Some(1),
+            },
+            TodoEvent::ItemRemoved { id: 2 },
+        ];
+        let state = events.iter().fold(TodoState::default(), TodoState::apply);
+        assert_eq!(
+            state,
+            TodoState {
+                text: "write".to_string(),
+                completed: true,
+                labels: vec![1, 3],
+                owner_id: Some(8),
+                assignee_id: None,
+                text_i18n: BTreeMap::from([("ja".to_string(), "書く".to_string())]),
+                snoozed_until: Some(until),
+                estimate_minutes: Some(45),
+                due_date: Some(due),
+                description: Some("notes".to_string()),
+                custom: BTreeMap::from([("sprint".to_string(), Value::from(13))]),
+                items: vec![ChecklistItem {
+                    id: 1,
+                    text: "outline".to_string(),
+                    completed: true,
+                    position: 0,
+                }],
+                last_item_id: 2,
+                deleted: false,
+            }
+        );
+
+        let now = Utc::now();
+        let history = TodoState::history(1, &events.map(|event| (event, now)));
+        assert_eq!(
+            history.len(),
+            2,
+            "owner changes, snoozes and checklists are no revisions"
+        );
+        assert!(history[1].completed);
+        assert_eq!(history[0].estimate_minutes, Some(30));
+    }
+
+    #[test]
+    fn should_keep_unassigning_apart_from_absent() {
+        let unassigned = TodoEvent::Changed {
+            text: None,
+            completed: None,
+            labels: None,
+            assignee_id: Some(None),
+            text_i18n: None,
+            estimate_minutes: None,
+            due_date: None,
+            description: None,
+            custom: None,
+        };
+        let json = serde_json::to_string(&unassigned).unwrap();
+        assert_eq!(json, r#"{"type":"changed","assignee_id":null}"#);
+        assert_eq!(
+            serde_json::from_str::<TodoEvent>(&json).unwrap(),
+            unassigned
+        );
+        assert_eq!(
+            serde_json::from_str::<TodoEvent>(r#"{"type":"changed"}"#).unwrap(),
+            TodoEvent::Changed {
+                text: None,
+                completed: None,
+                labels: None,
+                assignee_id: None,
+                text_i18n: None,
+                estimate_minutes: None,
+                due_date: None,
+                description: None,
+                custom: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn event_store_scenario() {
+        let db = TestDb::new().await;
+        let pool = db.pool.clone();
+        let label = LabelRepositoryForDb::new(pool.clone())
+            .create(CreateLabel::new("event store label".to_string()))
+            .await
+            .unwrap();
+        let repository = TodoRepositoryForEventStore::new(pool.clone());
+
+        let created = repository
+            .create(CreateTodo {
+                labels: vec![label.id],
+                ..CreateTodo::new("streamed".to_string())
+            })
+            .await
+            .unwrap();
+        assert_eq!(created.labels, vec![label.clone()]);
+        assert_eq!(
+            repository.id_of(created.uuid).await.unwrap(),
+            Some(created.id)
+        );
+        let unused = LabelRepositoryForDb::new(pool.clone())
+            .create(CreateLabel::new("unused".to_string()))
+            .await
+            .unwrap();
+        let stats = repository.label_stats().await.unwrap();
+        let counts: Vec<(i32, i64)> = stats
+            .iter()
+            .map(|stats| (stats.label.id, stats.todos))
+            .collect();
+        assert_eq!(counts, vec![(label.id, 1), (unused.id, 0)]);
+        assert_eq!(
+            repository.delete_unused_labels().await.unwrap(),
+            vec![unused.id]
+        );
+        let typo = LabelRepositoryForDb::new(pool.clone())
+            .create(CreateLabel::new("Event store label".to_string()))
+            .await
+            .unwrap();
+        let other = repository
+            .create(CreateTodo {
+                labels: vec![typo.id],
+                ..CreateTodo::new("other".to_string())
+            })
+            .await
+            .unwrap();
+        let moved = repository.merge_labels(typo.id, label.id).await.unwrap();
+        assert_eq!(moved.len(), 1);
+        assert_eq!(moved[0].id, other.id);
+        assert_eq!(moved[0].labels, vec![label.clone()]);
+        let later = Utc::now() + chrono::Duration::hours(1);
+        let snoozed = repository.snooze(other.id, Some(later)).await.unwrap();
+        assert!(snoozed.is_snoozed(Utc::now()));
+        let awake = repository.with_filter(TodoFilter {
+            hide_snoozed: true,
+            ..TodoFilter::default()
+        });
+        assert!(!awake.all().await.unwrap().contains(&snoozed));
+        let earlier = Utc::now() - chrono::Duration::seconds(1);
+        repository.snooze(other.id, Some(earlier)).await.unwrap();
+        assert!(repository
+            .unsnooze_due()
+            .await
+            .unwrap()
+            .contains(&(DEFAULT_WORKSPACE_ID, other.id)));
+        assert_eq!(repository.find(other.id).await.unwrap().snoozed_until, None);
+        let first = repository
+            .add_item(other.id, CreateChecklistItem::new("first".to_string()))
+            .await
+            .unwrap();
+        repository.delete_item(other.id, first.id).await.unwrap();
+        let second = repository
+            .add_item(other.id, CreateChecklistItem::new("second".to_string()))
+            .await
+            .unwrap();
+        assert_eq!((second.id, second.position), (2, 0), "ids are not reused");
+        repository
+            .update_item(
+                other.id,
+                second.id,
+                UpdateChecklistItem {
+                    text: Some("done".to_string()),
+                    completed: Some(true),
+                    position: None,
+                },
+            )
+            .await
+            .unwrap();
+        let progress = repository.find(other.id).await.unwrap().progress;
+        assert_eq!(progress, Progress { done: 1, total: 1 });
+        assert_eq!(
+            repository.checklist(other.id).await.unwrap()[0].text,
+            "done"
+        );
+        let finished = repository.move_on_board(other.id, true, 0).await.unwrap();
+        assert!(finished.completed);
+        assert_eq!(repository.find(other.id).await.unwrap(), finished);
+        assert_eq!(
+            repository.board_positions().await.unwrap().get(&other.id),
+            Some(&0)
+        );
+        assert_eq!(repository.find(created.id).await.unwrap(), created);
+        assert!(repository.all().await.unwrap().contains(&created));
+        assert!(repository.exists(created.id).await.unwrap());
+        assert!(!repository
+            .in_workspace(created.id + 1000)
+            .exists(created.id)
+            .await
+            .unwrap());
+
+        // enough changes to be folded from a snapshot
+        for i in 0..SNAPSHOT_EVERY {
+            repository
+                .update(
+                    created.id,
+                    UpdateTodo {
+                        text: Some(format!("change {}", i)),
+                        completed: Some(i % 2 == 0),
+                        labels: None,
+                        assignee_id: None,
+                        text_i18n: None,
+                        estimate_minutes: None,
+                        due_date: None,
+                        description: None,
+                        custom: None,
+                    },
+                )
+                .await
+                .unwrap();
+        }
+        let (snapshot_seq,) =
+            sqlx::query_as::<_, (i32,)>("select snapshot_seq from todo_streams where id=$1")
+                .bind(created.id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(snapshot_seq, SNAPSHOT_EVERY);
+        let todo = repository.find(created.id).await.unwrap();
+        assert_eq!(todo.text, format!("change {}", SNAPSHOT_EVERY - 1));
+        assert!(!todo.completed);
+        assert_eq!(todo.labels, vec![label.clone()]);
+
+        let revisions = repository.revisions(created.id).await.unwrap();
+        assert_eq!(revisions.len() as i32, SNAPSHOT_EVERY + 1);
+        let reverted = repository.revert(created.id, 1).await.unwrap();
+        assert_eq!(reverted.text, "streamed");
+        let err = repository
+            .update_if(
+                created.id,
+                &todo,
+                UpdateTodo {
+                    text: Some("stale".to_string()),
+                    completed: None,
+                    labels: None,
+                    assignee_id: None,
+                    text_i18n: None,
+                    estimate_minutes: None,
+                    due_date: None,
+                    description: None,
+                    custom: None,
+                },
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(RepositoryError::Conflict(_))
+        ));
+
+        LabelRepositoryForDb::new(pool.clone())
+            .delete(label.id)
+            .await
+            .unwrap();
+        assert!(repository.find(created.id).await.unwrap().labels.is_empty());
+
+        repository.delete(created.id).await.unwrap();
+        assert!(repository.find(created.id).await.is_err());
+        assert!(!repository.exists(created.id).await.unwrap());
+        let (events,) =
+            sqlx::query_as::<_, (i64,)>("select count(*) from todo_events where todo_id=$1")
+                .bind(created.id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(events as i32, SNAPSHOT_EVERY + 3, "nothing is dropped");
+    }
+}
@@ -0,0 +1,227 @@
+use axum::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use uuid::Uuid;
+
+use super::{
+    board::Positions,
+    burndown::BurndownDay,
+    checklist::{ChecklistItem, CreateChecklistItem, UpdateChecklistItem},
+    label::LabelStats,
+    search::Search,
+    todo::{
+        CreateTodo, OwnedTodos, PageCursor, Todo, TodoChange, TodoFilter, TodoPage, TodoReader,
+        TodoRepository, TodoRevision, TodoScope, TodoWriter, UpdateTodo,
+    },
+};
+use crate::outbox::{Outbox, OutboxEntry};
+
+/// Gets the events of every change out as soon as it succeeds, whatever the
+/// store and whoever made the change. Stores record events in the outbox
+/// along with the change, so a crash between the two loses nothing; this
+/// wakes the relay that publishes them instead of waiting for its next poll.
+#[derive(Debug, Clone)]
+pub struct EventedRepository<T> {
+    inner: T,
+    outbox: Outbox,
+}
+
+impl<T: TodoRepository> EventedRepository<T> {
+    pub fn new(inner: T, outbox: Outbox) -> Self {
+        Self { inner, outbox }
+    }
+
+    /// Passes `result` on, waking the relay when the change went through.
+    fn woken<R>(&self, result: anyhow::Result<R>) -> anyhow::Result<R> {
+        if result.is_ok() {
+            self.outbox.wake();
+        }
+        result
+    }
+}
+
+impl<T: TodoRepository> TodoScope for EventedRepository<T> {
+    fn in_workspace(&self, workspace_id: i32) -> Self {
+        Self {
+            inner: self.inner.in_workspace(workspace_id),
+            outbox: self.outbox.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl<T: TodoRepository> TodoReader for EventedRepository<T> {
+    async fn find(&self, id: i32) -> anyhow::Result<Todo> {
+        self.inner.find(id).await
+    }
+    fn with_filter(&self, filter: TodoFilter) -> Self {
+        Self {
+            inner: self.inner.with_filter(filter),
+            outbox: self.outbox.clone(),
+        }
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Todo>> {
+        self.inner.all().await
+    }
+    async fn count(&self) -> anyhow::Result<i64> {
+        self.inner.count().await
+    }
+    async fn exists(&self, id: i32) -> anyhow::Result<bool> {
+        self.inner.exists(id).await
+    }
+    async fn id_of(&self, uuid: Uuid) -> anyhow::Result<Option<i32>> {
+        self.inner.id_of(uuid).await
+    }
+    async fn find_open_duplicate(&self, text: &str) -> anyhow::Result<Option<Todo>> {
+        self.inner.find_open_duplicate(text).await
+    }
+    async fn page(&self, cursor: PageCursor, limit: u32) -> anyhow::Result<TodoPage> {
+        self.inner.page(cursor, limit).await
+    }
+    async fn revisions(&self, id: i32) -> anyhow::Result<Vec<TodoRevision>> {
+        self.inner.revisions(id).await
+    }
+    async fn count_open_owned(&self, owner_id: i32) -> anyhow::Result<i64> {
+        self.inner.count_open_owned(owner_id).await
+    }
+    async fn owned(&self, owner_id: i32) -> anyhow::Result<Vec<(i32, Todo)>> {
+        self.inner.owned(owner_id).await
+    }
+    async fn label_stats(&self) -> anyhow::Result<Vec<LabelStats>> {
+        self.inner.label_stats().await
+    }
+    async fn suggest_labels(&self, search: &Search, limit: u32) -> anyhow::Result<Vec<LabelStats>> {
+        self.inner.suggest_labels(search, limit).await
+    }
+    async fn checklist(&self, id: i32) -> anyhow::Result<Vec<ChecklistItem>> {
+        self.inner.checklist(id).await
+    }
+    async fn burndown(&self, from: NaiveDate, to: NaiveDate) -> anyhow::Result<Vec<BurndownDay>> {
+        self.inner.burndown(from, to).await
+    }
+    async fn board_positions(&self) -> anyhow::Result<Positions> {
+        self.inner.board_positions().await
+    }
+    async fn due_by(&self, user_id: i32, by: NaiveDate) -> anyhow::Result<Vec<(i32, Todo)>> {
+        self.inner.due_by(user_id, by).await
+    }
+}
+
+#[async_trait]
+impl<T: TodoRepository> TodoWriter for EventedRepository<T> {
+    async fn create(&self, payload: CreateTodo) -> anyhow::Result<Todo> {
+        self.woken(self.inner.create(payload).await)
+    }
+    async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<Todo> {
+        self.woken(self.inner.update(id, payload).await)
+    }
+    async fn update_if(
+        &self,
+        id: i32,
+        expected: &Todo,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<Todo> {
+        self.woken(self.inner.update_if(id, expected, payload).await)
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        self.woken(self.inner.delete(id).await)
+    }
+    async fn revert(&self, id: i32, rev: i32) -> anyhow::Result<Todo> {
+        self.woken(self.inner.revert(id, rev).await)
+    }
+    async fn delete_unused_labels(&self) -> anyhow::Result<Vec<i32>> {
+        self.woken(self.inner.delete_unused_labels().await)
+    }
+    async fn merge_labels(&self, source: i32, target: i32) -> anyhow::Result<Vec<Todo>> {
+        self.woken(self.inner.merge_labels(source, target).await)
+    }
+    async fn apply(&self, changes: Vec<TodoChange>) -> anyhow::Result<Vec<Option<Todo>>> {
+        self.woken(self.inner.apply(changes).await)
+    }
+    async fn snooze(&self, id: i32, until: Option<DateTime<Utc>>) -> anyhow::Result<Todo> {
+        self.woken(self.inner.snooze(id, until).await)
+    }
+    async fn unsnooze_due(&self) -> anyhow::Result<Vec<(i32, i32)>> {
+        self.woken(self.inner.unsnooze_due().await)
+    }
+    async fn add_item(
+        &self,
+        id: i32,
+        payload: CreateChecklistItem,
+    ) -> anyhow::Result<ChecklistItem> {
+        self.woken(self.inner.add_item(id, payload).await)
+    }
+    async fn update_item(
+        &self,
+        id: i32,
+        item_id: i32,
+        payload: UpdateChecklistItem,
+    ) -> anyhow::Result<ChecklistItem> {
+        self.woken(self.inner.update_item(id, item_id, payload).await)
+    }
+    async fn delete_item(&self, id: i32, item_id: i32) -> anyhow::Result<()> {
+        self.woken(self.inner.delete_item(id, item_id).await)
+    }
+    async fn move_on_board(&self, id: i32, completed: bool, position: u32) -> anyhow::Result<Todo> {
+        self.woken(self.inner.move_on_board(id, completed, position).await)
+    }
+    async fn release_owned(
+        &self,
+        owner_id: i32,
+        release: OwnedTodos,
+    ) -> anyhow::Result<Vec<(i32, i32)>> {
+        self.woken(self.inner.release_owned(owner_id, release).await)
+    }
+    fn acting_as(&self, user_id: Option<i32>) -> Self {
+        Self {
+            inner: self.inner.acting_as(user_id),
+            outbox: self.outbox.clone(),
+        }
+    }
+    /// The relay's own calls, which must not wake it again.
+    async fn claim_outbox(&self, limit: u32) -> anyhow::Result<Vec<OutboxEntry>> {
+        self.inner.claim_outbox(limit).await
+    }
+    async fn mark_sent(&self, ids: &[i64]) -> anyhow::Result<()> {
+        self.inner.mark_sent(ids).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, time::Duration};
+
+    use super::*;
+    use crate::{
+        events::{DomainEvent, Events},
+        repositories::todo::TodoRepositoryForMemory,
+    };
+
+    #[tokio::test]
+    async fn should_publish_events_once_changes_succeed() {
+        let outbox = Outbox::default();
+        let todos = EventedRepository::new(TodoRepositoryForMemory::new(), outbox.clone());
+        let events = Events::default();
+        let mut received = events.subscribe();
+        outbox.relay(Arc::new(todos.clone()), events);
+        // the relay's first round, before anything changed
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let todo = todos
+            .create(CreateTodo::new("publish me".to_string()))
+            .await
+            .unwrap();
+        let event = tokio::time::timeout(Duration::from_secs(1), received.recv())
+            .await
+            .expect("not woken")
+            .unwrap();
+        assert_eq!(event.event, DomainEvent::TodoCreated(todo.clone()));
+
+        assert!(todos.delete(todo.id + 1).await.is_err());
+        todos.delete(todo.id).await.unwrap();
+        let event = tokio::time::timeout(Duration::from_secs(1), received.recv())
+            .await
+            .expect("not woken")
+            .unwrap();
+        assert_eq!(event.event, DomainEvent::TodoDeleted(todo.id));
+    }
+}
@@ -0,0 +1,653 @@
+use std::{cmp::Ordering, str::FromStr};
+
+use chrono::NaiveDate;
+use serde_json::Value;
+use thiserror::Error;
+
+use super::todo::{Todo, TodoFilter};
+use crate::database::Redact;
+
+const FIELDS: &str = "completed, label, assignee or due";
+
+/// A filter expression such as
+/// `completed:false AND (label:home OR assignee:me)`. Terms are
+/// `field:value`, with values holding spaces in double quotes, e.g.
+/// `label:"next week"`. `due` also compares, as in `due<2023-06-01`.
+/// Terms side by side must all hold, as if joined by
+/// `AND`; `OR` binds looser than `AND`, `NOT` tighter, and parentheses
+/// group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterExpr {
+    All(Vec<FilterExpr>),
+    Any(Vec<FilterExpr>),
+    Not(Box<FilterExpr>),
+    Term(Term),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term {
+    Completed(bool),
+    /// A label of this name is attached.
+    Label(String),
+    Assignee(Assignee),
+    /// The due date compares so; todos without one never match.
+    Due(Comparison, NaiveDate),
+    /// The custom field of this name holds this value; only built from
+    /// `?custom.<name>=`, which knows the field's type.
+    Custom(String, Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    On,
+    Before,
+    OnOrBefore,
+    After,
+    OnOrAfter,
+}
+
+impl Comparison {
+    fn from_operator(operator: &str) -> Option<Self> {
+        Some(match operator {
+            ":" => Comparison::On,
+            "<" => Comparison::Before,
+            "<=" => Comparison::OnOrBefore,
+            ">" => Comparison::After,
+            ">=" => Comparison::OnOrAfter,
+            _ => return None,
+        })
+    }
+
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::On => ordering.is_eq(),
+            Comparison::Before => ordering.is_lt(),
+            Comparison::OnOrBefore => ordering.is_le(),
+            Comparison::After => ordering.is_gt(),
+            Comparison::OnOrAfter => ordering.is_ge(),
+        }
+    }
+
+    fn sql(self) -> &'static str {
+        match self {
+            Comparison::On => "=",
+            Comparison::Before => "<",
+            Comparison::OnOrBefore => "<=",
+            Comparison::After => ">",
+            Comparison::OnOrAfter => ">=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assignee {
+    /// Whoever the expression is used by; see `FilterExpr::resolve`.
+    Me,
+    Id(i32),
+}
+
+/// Offsets count characters from the start of the expression.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FilterError {
+    #[error("expected `field:value` at `{0}`")]
+    Syntax(String),
+    #[error("missing closing quote after `{0}`")]
+    UnclosedQuote(String),
+    #[error("unknown field `{0}`: use {}", FIELDS)]
+    UnknownField(String),
+    #[error("`{field}` does not support `{operator}`")]
+    Operator { field: String, operator: String },
+    #[error("invalid {field} [{value}]")]
+    InvalidValue { field: &'static str, value: String },
+    #[error("unexpected `{token}` at {at}")]
+    Unexpected { token: String, at: usize },
+    #[error("unexpected end after `{0}`")]
+    UnexpectedEnd(String),
+    #[error("`(` at {0} is never closed")]
+    UnclosedParen(usize),
+    #[error("`assignee:me` needs a signed in user")]
+    SignInRequired,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Term(Term),
+}
+
+impl Token {
+    fn shown(&self) -> String {
+        match self {
+            Token::Open => "(".to_string(),
+            Token::Close => ")".to_string(),
+            Token::And => "AND".to_string(),
+            Token::Or => "OR".to_string(),
+            Token::Not => "NOT".to_string(),
+            Token::Term(_) => "term".to_string(),
+        }
+    }
+}
+
+impl FromStr for FilterExpr {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        if tokens.is_empty() {
+            return Ok(FilterExpr::All(Vec::new()));
+        }
+        let mut parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+        };
+        let expr = parser.any()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(expr),
+            Some((token, at)) => Err(FilterError::Unexpected {
+                token: token.shown(),
+                at: *at,
+            }),
+        }
+    }
+}
+
+/// Splits `input` into tokens, each with the offset it starts at.
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, FilterError> {
+    let mut tokens = Vec::new();
+    let mut rest = input;
+    loop {
+        rest = rest.trim_start();
+        let at = input[..input.len() - rest.len()].chars().count();
+        if rest.is_empty() {
+            return Ok(tokens);
+        }
+        // a parenthesis is a word of its own
+        let word_end = rest
+            .find(|c: char| c.is_whitespace() || c == '(' || c == ')')
+            .unwrap_or(rest.len())
+            .max(1);
+        let symbol = match &rest[..word_end] {
+            "(" => Some(Token::Open),
+            ")" => Some(Token::Close),
+            "AND" => Some(Token::And),
+            "OR" => Some(Token::Or),
+            "NOT" => Some(Token::Not),
+            _ => None,
+        };
+        if let Some(symbol) = symbol {
+            tokens.push((symbol, at));
+            rest = &rest[word_end..];
+            continue;
+        }
+        let (term, tail) = term(rest)?;
+        tokens.push((Token::Term(term), at));
+        rest = tail;
+    }
+}
+
+/// Splits the first `field:value` term off `input`.
+fn term(input: &str) -> Result<(Term, &str), FilterError> {
+    let field_end = input
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(input.len());
+    let (field, rest) = input.split_at(field_end);
+    let operator_end = rest
+        .find(|c: char| !matches!(c, ':' | '<' | '>' | '='))
+        .unwrap_or(rest.len());
+    let (operator, rest) = rest.split_at(operator_end);
+    if field.is_empty() || operator.is_empty() {
+        let shown = input
+            .split(|c: char| c.is_whitespace() || c == ')')
+            .next()
+            .unwrap_or(input);
+        return Err(FilterError::Syntax(shown.to_string()));
+    }
+
+    let (value, rest) = if let Some(quoted) = rest.strip_prefix('"') {
+        let end = quoted
+            .find('"')
+            .ok_or_else(|| FilterError::UnclosedQuote(format!("{}{}", field, operator)))?;
+        (&quoted[..end], &quoted[end + 1..])
+    } else {
+        let value_end = rest
+            .find(|c: char| c.is_whitespace() || c == ')')
+            .unwrap_or(rest.len());
+        match rest.split_at(value_end) {
+            ("", _) => return Err(FilterError::Syntax(format!("{}{}", field, operator))),
+            split => split,
+        }
+    };
+
+    if !["completed", "label", "assignee", "due"].contains(&field) {
+        return Err(FilterError::UnknownField(field.to_string()));
+    }
+    let comparison = Comparison::from_operator(operator)
+        .filter(|comparison| field == "due" || *comparison == Comparison::On)
+        .ok_or_else(|| FilterError::Operator {
+            field: field.to_string(),
+            operator: operator.to_string(),
+        })?;
+    let invalid = |field| FilterError::InvalidValue {
+        field,
+        value: value.to_string(),
+    };
+    let term = match field {
+        "completed" => Term::Completed(value.parse().map_err(|_| invalid("completed"))?),
+        "due" => Term::Due(comparison, value.parse().map_err(|_| invalid("due"))?),
+        "label" => Term::Label(value.to_string()),
+        _ => Term::Assignee(match value {
+            "me" => Assignee::Me,
+            id => Assignee::Id(id.parse().map_err(|_| invalid("assignee"))?),
+        }),
+    };
+    Ok((term, rest))
+}
+
+/// Recursive descent over the tokens, loosest binding first.
+struct Parser<'a> {
+    tokens: &'a [(Token, usize)],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Result<(Token, usize), FilterError> {
+        let token = self.tokens.get(self.pos).cloned().ok_or_else(|| {
+            let last = &self.tokens[self.pos - 1].0;
+            FilterError::UnexpectedEnd(last.shown())
+        })?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn any(&mut self) -> Result<FilterExpr, FilterError> {
+        let mut alternatives = vec![self.all()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            alternatives.push(self.all()?);
+        }
+        Ok(flatten(alternatives, FilterExpr::Any))
+    }
+
+    fn all(&mut self) -> Result<FilterExpr, FilterError> {
+        let mut conditions = vec![self.unary()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.pos += 1,
+                Some(Token::Open | Token::Not | Token::Term(_)) => {}
+                _ => break,
+            }
+            conditions.push(self.unary()?);
+        }
+        Ok(flatten(conditions, FilterExpr::All))
+    }
+
+    fn unary(&mut self) -> Result<FilterExpr, FilterError> {
+        match self.next()? {
+            (Token::Not, _) => Ok(FilterExpr::Not(Box::new(self.unary()?))),
+            (Token::Term(term), _) => Ok(FilterExpr::Term(term)),
+            (Token::Open, at) => {
+                let expr = self.any()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(FilterError::UnclosedParen(at));
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            (token, at) => Err(FilterError::Unexpected {
+                token: token.shown(),
+                at,
+            }),
+        }
+    }
+}
+
+/// A lone expression stands for itself rather than a group of one.
+fn flatten(mut exprs: Vec<FilterExpr>, group: fn(Vec<FilterExpr>) -> FilterExpr) -> FilterExpr {
+    match exprs.len() {
+        1 => exprs.remove(0),
+        _ => group(exprs),
+    }
+}
+
+/// A value `FilterExpr::to_sql` binds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SqlValue {
+    Bool(bool),
+    Int(i32),
+    Text(String),
+    Date(NaiveDate),
+    Json(Value),
+}
+
+impl Redact for SqlValue {
+    fn redacted(&self) -> String {
+        match self {
+            SqlValue::Bool(value) => value.redacted(),
+            SqlValue::Int(value) => value.redacted(),
+            SqlValue::Text(value) => value.redacted(),
+            SqlValue::Date(value) => value.redacted(),
+            SqlValue::Json(_) => "<redacted>".to_string(),
+        }
+    }
+}
+
+impl FilterExpr {
+    /// Replaces `assignee:me` with `user_id`.
+    pub fn resolve(self, user_id: Option<i32>) -> Result<Self, FilterError> {
+        let resolve_all = |exprs: Vec<FilterExpr>| {
+            exprs
+                .into_iter()
+                .map(|expr| expr.resolve(user_id))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(match self {
+            FilterExpr::All(exprs) => FilterExpr::All(resolve_all(exprs)?),
+            FilterExpr::Any(exprs) => FilterExpr::Any(resolve_all(exprs)?),
+            FilterExpr::Not(expr) => FilterExpr::Not(Box::new(expr.resolve(user_id)?)),
+            FilterExpr::Term(Term::Assignee(Assignee::Me)) => FilterExpr::Term(Term::Assignee(
+                Assignee::Id(user_id.ok_or(FilterError::SignInRequired)?),
+            )),
+            term => term,
+        })
+    }
+
+    /// The repository filter for `user_id`, whom `assignee:me` stands for.
+    pub fn to_filter(self, user_id: Option<i32>) -> Result<TodoFilter, FilterError> {
+        Ok(TodoFilter {
+            expr: Some(self.resolve(user_id)?),
+            ..TodoFilter::default()
+        })
+    }
+
+    /// An unresolved `assignee:me` matches nothing.
+    pub fn matches(&self, todo: &Todo) -> bool {
+        match self {
+            FilterExpr::All(exprs) => exprs.iter().all(|expr| expr.matches(todo)),
+            FilterExpr::Any(exprs) => exprs.iter().any(|expr| expr.matches(todo)),
+            FilterExpr::Not(expr) => !expr.matches(todo),
+            FilterExpr::Term(Term::Completed(completed)) => todo.completed == *completed,
+            FilterExpr::Term(Term::Label(name)) => {
+                todo.labels.iter().any(|label| &label.name == name)
+            }
+            FilterExpr::Term(Term::Assignee(Assignee::Id(id))) => todo.assignee_id == Some(*id),
+            FilterExpr::Term(Term::Assignee(Assignee::Me)) => false,
+            FilterExpr::Term(Term::Due(comparison, date)) => todo
+                .due_date
+                .is_some_and(|due| comparison.holds(due.cmp(date))),
+            FilterExpr::Term(Term::Custom(name, value)) => todo.custom.get(name) == Some(value),
+        }
+    }
+
+    /// A condition on `todos` reading parameters from `$first` on, and the
+    /// values to bind to them in order. Like `matches`, an unresolved
+    /// `assignee:me` holds for no todo.
+    pub fn to_sql(&self, first: usize) -> (String, Vec<SqlValue>) {
+        let mut values = Vec::new();
+        let sql = self.write_sql(first, &mut values);
+        (sql, values)
+    }
+
+    fn write_sql(&self, first: usize, values: &mut Vec<SqlValue>) -> String {
+        let (exprs, join) = match self {
+            FilterExpr::All(exprs) => (exprs, " and "),
+            FilterExpr::Any(exprs) => (exprs, " or "),
+            FilterExpr::Not(expr) => return format!("not {}", expr.write_sql(first, values)),
+            FilterExpr::Term(term) => {
+                let mut param = |value| {
+                    values.push(value);
+                    format!("${}", first + values.len() - 1)
+                };
+                return match term {
+                    Term::Completed(completed) => {
+                        format!("(todos.completed = {})", param(SqlValue::Bool(*completed)))
+                    }
+                    Term::Label(name) => format!(
+                        r#"exists (
+                            select 1 from todo_labels
+                            join labels on labels.id = todo_labels.label_id
+                            where todo_labels.todo_id = todos.id and labels.name = {}
+                        )"#,
+                        param(SqlValue::Text(name.clone()))
+                    ),
+                    Term::Assignee(Assignee::Id(id)) => {
+                        format!("(todos.assignee_id = {})", param(SqlValue::Int(*id)))
+                    }
+                    Term::Assignee(Assignee::Me) => "false".to_string(),
+                    // false rather than null, so `NOT` holds for todos
+                    // without a due date as `matches` says
+                    Term::Due(comparison, date) => format!(
+                        "coalesce(todos.due_date {} {}, false)",
+                        comparison.sql(),
+                        param(SqlValue::Date(*date))
+                    ),
+                    Term::Custom(name, value) => format!(
+                        "coalesce(todos.custom -> {} = {}, false)",
+                        param(SqlValue::Text(name.clone())),
+                        param(SqlValue::Json(value.clone()))
+                    ),
+                };
+            }
+        };
+        if exprs.is_empty() {
+            return matches!(self, FilterExpr::All(_)).to_string();
+        }
+        let conditions: Vec<String> = exprs
+            .iter()
+            .map(|expr| expr.write_sql(first, values))
+            .collect();
+        format!("({})", conditions.join(join))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::repositories::label::Label;
+
+    fn label(name: &str) -> FilterExpr {
+        FilterExpr::Term(Term::Label(name.to_string()))
+    }
+
+    #[test]
+    fn should_parse_filter_expressions() {
+        let expr: FilterExpr = r#"completed:false label:work  label:"next week" assignee:me"#
+            .parse()
+            .unwrap();
+        assert_eq!(
+            expr,
+            FilterExpr::All(vec![
+                FilterExpr::Term(Term::Completed(false)),
+                label("work"),
+                label("next week"),
+                FilterExpr::Term(Term::Assignee(Assignee::Me)),
+            ])
+        );
+        assert_eq!(
+            expr.clone().resolve(Some(3)).unwrap(),
+            FilterExpr::All(vec![
+                FilterExpr::Term(Term::Completed(false)),
+                label("work"),
+                label("next week"),
+                FilterExpr::Term(Term::Assignee(Assignee::Id(3))),
+            ])
+        );
+        assert_eq!(expr.resolve(None), Err(FilterError::SignInRequired));
+        assert_eq!("".parse(), Ok(FilterExpr::All(Vec::new())));
+    }
+
+    #[test]
+    fn should_bind_and_tighter_than_or() {
+        let expr: FilterExpr = "completed:false AND (label:home OR label:work) OR NOT label:later"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            expr,
+            FilterExpr::Any(vec![
+                FilterExpr::All(vec![
+                    FilterExpr::Term(Term::Completed(false)),
+                    FilterExpr::Any(vec![label("home"), label("work")]),
+                ]),
+                FilterExpr::Not(Box::new(label("later"))),
+            ])
+        );
+
+        let todo = |completed: bool, labels: &[&str]| Todo {
+            completed,
+            labels: labels
+                .iter()
+                .enumerate()
+                .map(|(id, name)| Label::new(id as i32, name.to_string()))
+                .collect(),
+            ..Todo::new(1, "todo".to_string())
+        };
+        assert!(expr.matches(&todo(false, &["work", "later"])));
+        assert!(expr.matches(&todo(true, &[])));
+        assert!(!expr.matches(&todo(true, &["home", "later"])));
+    }
+
+    #[test]
+    fn should_compile_to_sql() {
+        let expr: FilterExpr = "NOT completed:true (label:home OR assignee:7)"
+            .parse()
+            .unwrap();
+        let (sql, values) = expr.to_sql(3);
+
+        assert!(sql.starts_with("(not (todos.completed = $3) and (exists ("));
+        assert!(sql.contains("labels.name = $4"));
+        assert!(sql.ends_with(" or (todos.assignee_id = $5)))"));
+        assert_eq!(
+            values,
+            vec![
+                SqlValue::Bool(true),
+                SqlValue::Text("home".to_string()),
+                SqlValue::Int(7),
+            ]
+        );
+        assert_eq!(FilterExpr::All(Vec::new()).to_sql(1).0, "true");
+    }
+
+    #[test]
+    fn should_compare_due_dates() {
+        let expr: FilterExpr = "due>=2023-06-01 due<2023-06-08".parse().unwrap();
+        let due = |date: Option<&str>| Todo {
+            due_date: date.map(|date| date.parse().unwrap()),
+            ..Todo::new(1, "todo".to_string())
+        };
+        assert!(expr.matches(&due(Some("2023-06-01"))));
+        assert!(!expr.matches(&due(Some("2023-06-08"))));
+        assert!(!expr.matches(&due(None)));
+        let not: FilterExpr = "NOT due:2023-06-01".parse().unwrap();
+        assert!(not.matches(&due(None)));
+
+        let (sql, values) = expr.to_sql(2);
+        assert_eq!(
+            sql,
+            "(coalesce(todos.due_date >= $2, false) and coalesce(todos.due_date < $3, false))"
+        );
+        assert_eq!(
+            values,
+            vec![
+                SqlValue::Date("2023-06-01".parse().unwrap()),
+                SqlValue::Date("2023-06-08".parse().unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_match_custom_values() {
+        let expr = FilterExpr::Term(Term::Custom("sprint".to_string(), Value::from(12)));
+        let sprint = |value: Value| Todo {
+            custom: BTreeMap::from([("sprint".to_string(), value)]),
+            ..Todo::new(1, "todo".to_string())
+        };
+        assert!(expr.matches(&sprint(Value::from(12))));
+        assert!(!expr.matches(&sprint(Value::from("12"))));
+        assert!(!expr.matches(&Todo::new(1, "todo".to_string())));
+
+        let (sql, values) = expr.to_sql(2);
+        assert_eq!(sql, "coalesce(todos.custom -> $2 = $3, false)");
+        assert_eq!(
+            values,
+            vec![
+                SqlValue::Text("sprint".to_string()),
+                SqlValue::Json(Value::from(12)),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_reject_malformed_expressions() {
+        let error = |expr: &str| expr.parse::<FilterExpr>().unwrap_err();
+
+        assert_eq!(error("work"), FilterError::Syntax("work".to_string()));
+        assert_eq!(error("label:"), FilterError::Syntax("label:".to_string()));
+        assert_eq!(
+            error(r#"label:"next week"#),
+            FilterError::UnclosedQuote("label:".to_string())
+        );
+        assert_eq!(
+            error("priority:1"),
+            FilterError::UnknownField("priority".to_string())
+        );
+        assert_eq!(
+            error("due<7d"),
+            FilterError::InvalidValue {
+                field: "due",
+                value: "7d".to_string(),
+            }
+        );
+        assert_eq!(
+            error("due=<2023-06-01"),
+            FilterError::Operator {
+                field: "due".to_string(),
+                operator: "=<".to_string(),
+            }
+        );
+        assert_eq!(
+            error("completed>=true"),
+            FilterError::Operator {
+                field: "completed".to_string(),
+                operator: ">=".to_string(),
+            }
+        );
+        assert_eq!(
+            error("completed:maybe"),
+            FilterError::InvalidValue {
+                field: "completed",
+                value: "maybe".to_string(),
+            }
+        );
+        assert_eq!(
+            error("label:a OR"),
+            FilterError::UnexpectedEnd("OR".to_string())
+        );
+        assert_eq!(
+            error("label:a ) label:b"),
+            FilterError::Unexpected {
+                token: ")".to_string(),
+                at: 8,
+            }
+        );
+        assert_eq!(
+            error("label:a AND (label:b OR label:c"),
+            FilterError::UnclosedParen(12)
+        );
+        assert_eq!(
+            error("OR label:a"),
+            FilterError::Unexpected {
+                token: "OR".to_string(),
+                at: 0,
+            }
+        );
+    }
+}
@@ -1,93 +1,614 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+};
+
+use async_graphql::SimpleObject;
 use axum::async_trait;
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, PgPool};
+use tokio::sync::Notify;
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
 
-use super::RepositoryError;
+use super::{search::Search, workspace::DEFAULT_WORKSPACE_ID, RepositoryError};
+use crate::database::DbUuid;
 
 #[async_trait]
 pub trait LabelRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
-    async fn create(&self, text: String) -> anyhow::Result<Label>;
+    /// The same store, restricted to one workspace's labels.
+    fn in_workspace(&self, workspace_id: i32) -> Self;
+    async fn find(&self, id: i32) -> anyhow::Result<Label>;
+    /// The id of the workspace's label whose `uuid` this is, if there is one.
+    async fn id_of(&self, uuid: Uuid) -> anyhow::Result<Option<i32>>;
+    /// Fails with `Duplicate`, naming the label that has it, when the name is
+    /// taken in the workspace.
+    async fn create(&self, payload: CreateLabel) -> anyhow::Result<Label>;
+    async fn update(&self, id: i32, payload: UpdateLabel) -> anyhow::Result<Label>;
     async fn all(&self) -> anyhow::Result<Vec<Label>>;
+    async fn count(&self) -> anyhow::Result<i64>;
     async fn delete(&self, id: i32) -> anyhow::Result<()>;
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow)]
+/// What labels get without a color of their own.
+pub const DEFAULT_LABEL_COLOR: &str = "#808080";
+
+fn default_color() -> String {
+    DEFAULT_LABEL_COLOR.to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, SimpleObject)]
 pub struct Label {
     pub id: i32,
-    pub text: String,
+    /// The id paths take under `features.uuid_ids`; nil until stored.
+    #[serde(default, skip_serializing_if = "Uuid::is_nil")]
+    pub uuid: Uuid,
+    pub name: String,
+    /// `#rgb` or `#rrggbb`, for the chip the label is shown as.
+    #[serde(default = "default_color")]
+    pub color: String,
+    /// A short name or emoji shown beside the label.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+impl Label {
+    pub fn new(id: i32, name: String) -> Self {
+        Self {
+            id,
+            uuid: Uuid::nil(),
+            name,
+            color: default_color(),
+            icon: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+pub struct CreateLabel {
+    pub name: String,
+    #[serde(default = "default_color")]
+    #[validate(custom = "validate_color")]
+    pub color: String,
+    #[serde(default)]
+    #[validate(length(min = 1, message = "can not be empty"))]
+    #[validate(length(max = 32, message = "can not be over 32"))]
+    pub icon: Option<String>,
+}
+
+impl CreateLabel {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            color: default_color(),
+            icon: None,
+        }
+    }
+}
+
+/// Fields left out stay as they are; an empty `icon` removes it.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
 pub struct UpdateLabel {
-    pub id: i32,
-    pub text: String,
+    pub name: Option<String>,
+    #[validate(custom = "validate_color")]
+    pub color: Option<String>,
+    #[validate(length(max = 32, message = "can not be over 32"))]
+    pub icon: Option<String>,
+}
+
+/// A label with how many todos carry it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct LabelStats {
+    #[serde(flatten)]
+    pub label: Label,
+    pub todos: i64,
+}
+
+impl LabelStats {
+    /// Typeahead order: labels whose name starts with the query come first,
+    /// then the most used, then by name.
+    pub(super) fn suggest(mut stats: Vec<Self>, search: &Search, limit: u32) -> Vec<Self> {
+        let prefix = search.text.to_lowercase();
+        let starts = |stats: &Self| stats.label.name.to_lowercase().starts_with(&prefix);
+        stats.retain(|stats| starts(stats) || search.matches(&stats.label.name));
+        stats.sort_by(|a, b| {
+            starts(b)
+                .cmp(&starts(a))
+                .then(b.todos.cmp(&a.todos))
+                .then_with(|| a.label.name.cmp(&b.label.name))
+        });
+        stats.truncate(limit as usize);
+        stats
+    }
+}
+
+/// `text` as a `like` pattern matching what starts with it.
+pub(super) fn like_prefix(text: &str) -> String {
+    let mut pattern = String::with_capacity(text.len() + 1);
+    for c in text.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+#[derive(FromRow)]
+pub(super) struct LabelRow {
+    id: i32,
+    uuid: DbUuid,
+    name: String,
+    color: String,
+    icon: Option<String>,
+}
+
+impl From<LabelRow> for Label {
+    fn from(row: LabelRow) -> Self {
+        Self {
+            id: row.id,
+            uuid: row.uuid.0,
+            name: row.name,
+            color: row.color,
+            icon: row.icon,
+        }
+    }
+}
+
+#[derive(FromRow)]
+pub(super) struct LabelStatsRow {
+    id: i32,
+    uuid: DbUuid,
+    name: String,
+    color: String,
+    icon: Option<String>,
+    todos: i64,
+}
+
+impl From<LabelStatsRow> for LabelStats {
+    fn from(row: LabelStatsRow) -> Self {
+        Self {
+            label: Label {
+                id: row.id,
+                uuid: row.uuid.0,
+                name: row.name,
+                color: row.color,
+                icon: row.icon,
+            },
+            todos: row.todos,
+        }
+    }
+}
+
+/// Where `POST /labels/:id/merge` moves the label's todos.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+pub struct MergeLabel {
+    pub target_id: i32,
+}
+
+fn validate_color(color: &str) -> Result<(), ValidationError> {
+    let digits = color.strip_prefix('#').unwrap_or_default();
+    if matches!(digits.len(), 3 | 6) && digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(())
+    } else {
+        let mut error = ValidationError::new("color");
+        error.message = Some("must be a hex color such as #1e90ff".into());
+        Err(error)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredLabel {
+    workspace_id: i32,
+    label: Label,
+}
+
+type LabelDatas = HashMap<i32, StoredLabel>;
+
+/// Every workspace's labels, as `TodoRepositoryForMemory` saves them.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct LabelSnapshot {
+    next_id: i32,
+    labels: LabelDatas,
+}
+
+#[derive(Debug, Clone)]
+pub struct LabelRepositoryForMemory {
+    store: Arc<RwLock<LabelDatas>>,
+    next_id: Arc<AtomicI32>,
+    /// Notified whenever the store is about to change.
+    changes: Arc<Notify>,
+    workspace_id: i32,
+}
+
+impl Default for LabelRepositoryForMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LabelRepositoryForMemory {
+    pub fn new() -> Self {
+        LabelRepositoryForMemory {
+            store: Arc::default(),
+            next_id: Arc::default(),
+            changes: Arc::default(),
+            workspace_id: DEFAULT_WORKSPACE_ID,
+        }
+    }
+
+    fn scoped(&self, id: i32) -> impl Fn(&&StoredLabel) -> bool + '_ {
+        move |stored| stored.workspace_id == self.workspace_id && stored.label.id == id
+    }
+
+    fn write_store_ref(&self) -> RwLockWriteGuard<'_, LabelDatas> {
+        let store = self.store.write().unwrap();
+        self.changes.notify_one();
+        store
+    }
+
+    fn read_store_ref(&self) -> RwLockReadGuard<'_, LabelDatas> {
+        self.store.read().unwrap()
+    }
+
+    pub(crate) fn changes(&self) -> Arc<Notify> {
+        self.changes.clone()
+    }
+
+    pub(crate) fn snapshot(&self) -> LabelSnapshot {
+        LabelSnapshot {
+            next_id: self.next_id.load(Ordering::SeqCst),
+            labels: self.read_store_ref().clone(),
+        }
+    }
+
+    /// Replaces every workspace's labels with those of `snapshot`.
+    pub(crate) fn restore(&self, snapshot: LabelSnapshot) {
+        *self.store.write().unwrap() = snapshot.labels;
+        self.next_id.store(snapshot.next_id, Ordering::SeqCst);
+    }
+
+    /// Labels for `ids` in id order; fails on the first id that is unknown.
+    pub(crate) fn find_many(&self, ids: &[i32]) -> Result<Vec<Label>, RepositoryError> {
+        let store = self.read_store_ref();
+        let mut labels = ids
+            .iter()
+            .map(|id| {
+                store
+                    .get(id)
+                    .filter(self.scoped(*id))
+                    .map(|stored| stored.label.clone())
+                    .ok_or(RepositoryError::NotFound(*id))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        labels.sort_by_key(|label| label.id);
+        labels.dedup();
+        Ok(labels)
+    }
+
+    /// The ids of `ids` the workspace has no label for, ascending.
+    pub(crate) fn missing(&self, ids: &[i32]) -> Vec<i32> {
+        let store = self.read_store_ref();
+        let mut missing: Vec<i32> = ids
+            .iter()
+            .copied()
+            .filter(|id| {
+                !store
+                    .get(id)
+                    .is_some_and(|stored| self.scoped(*id)(&stored))
+            })
+            .collect();
+        missing.sort_unstable();
+        missing.dedup();
+        missing
+    }
+
+    /// Deletes the workspace's labels not in `keep`, returning their ids.
+    pub(crate) fn delete_except(&self, keep: &HashSet<i32>) -> Vec<i32> {
+        let mut store = self.write_store_ref();
+        let mut deleted: Vec<i32> = store
+            .values()
+            .filter(|stored| {
+                stored.workspace_id == self.workspace_id && !keep.contains(&stored.label.id)
+            })
+            .map(|stored| stored.label.id)
+            .collect();
+        deleted.sort_unstable();
+        for id in &deleted {
+            store.remove(id);
+        }
+        deleted
+    }
+
+    pub(crate) fn remove(&self, id: i32) -> Result<(), RepositoryError> {
+        let mut store = self.write_store_ref();
+        store
+            .get(&id)
+            .filter(self.scoped(id))
+            .ok_or(RepositoryError::NotFound(id))?;
+        store.remove(&id);
+        Ok(())
+    }
+
+    pub(crate) fn get(&self, id: i32) -> Option<Label> {
+        self.read_store_ref()
+            .get(&id)
+            .filter(self.scoped(id))
+            .map(|stored| stored.label.clone())
+    }
+}
+
+#[async_trait]
+impl LabelRepository for LabelRepositoryForMemory {
+    fn in_workspace(&self, workspace_id: i32) -> Self {
+        Self {
+            workspace_id,
+            ..self.clone()
+        }
+    }
+    async fn find(&self, id: i32) -> anyhow::Result<Label> {
+        Ok(self.get(id).ok_or(RepositoryError::NotFound(id))?)
+    }
+    async fn id_of(&self, uuid: Uuid) -> anyhow::Result<Option<i32>> {
+        Ok(self
+            .read_store_ref()
+            .values()
+            .find(|stored| stored.workspace_id == self.workspace_id && stored.label.uuid == uuid)
+            .map(|stored| stored.label.id))
+    }
+    async fn create(&self, payload: CreateLabel) -> anyhow::Result<Label> {
+        let mut store = self.write_store_ref();
+        if let Some(stored) = store.values().find(|stored| {
+            stored.workspace_id == self.workspace_id && stored.label.name == payload.name
+        }) {
+            return Err(RepositoryError::Duplicate(stored.label.id).into());
+        }
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let label = Label {
+            id,
+            uuid: Uuid::now_v7(),
+            name: payload.name,
+            color: payload.color,
+            icon: payload.icon,
+        };
+        store.insert(
+            id,
+            StoredLabel {
+                workspace_id: self.workspace_id,
+                label: label.clone(),
+            },
+        );
+        Ok(label)
+    }
+    async fn update(&self, id: i32, payload: UpdateLabel) -> anyhow::Result<Label> {
+        let mut store = self.write_store_ref();
+        store
+            .get(&id)
+            .filter(self.scoped(id))
+            .ok_or(RepositoryError::NotFound(id))?;
+        if let Some(name) = &payload.name {
+            if let Some(stored) = store.values().find(|stored| {
+                stored.workspace_id == self.workspace_id
+                    && stored.label.name == *name
+                    && stored.label.id != id
+            }) {
+                return Err(RepositoryError::Duplicate(stored.label.id).into());
+            }
+        }
+        let label = &mut store.get_mut(&id).unwrap().label;
+        if let Some(name) = payload.name {
+            label.name = name;
+        }
+        if let Some(color) = payload.color {
+            label.color = color;
+        }
+        if let Some(icon) = payload.icon {
+            label.icon = Some(icon).filter(|icon| !icon.is_empty());
+        }
+        Ok(label.clone())
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Label>> {
+        let store = self.read_store_ref();
+        let mut labels: Vec<Label> = store
+            .values()
+            .filter(|stored| stored.workspace_id == self.workspace_id)
+            .map(|stored| stored.label.clone())
+            .collect();
+        labels.sort_by_key(|label| label.id);
+        Ok(labels)
+    }
+    async fn count(&self) -> anyhow::Result<i64> {
+        let store = self.read_store_ref();
+        let count = store
+            .values()
+            .filter(|stored| stored.workspace_id == self.workspace_id)
+            .count();
+        Ok(count as i64)
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        Ok(self.remove(id)?)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct LabelRepositoryForDb {
     pool: PgPool,
+    workspace_id: i32,
 }
 
 impl LabelRepositoryForDb {
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            workspace_id: DEFAULT_WORKSPACE_ID,
+        }
+    }
+
+    /// The id of the workspace's label called `name`; 0 if it went away
+    /// since.
+    async fn named(&self, name: &str) -> anyhow::Result<i32> {
+        let label = sqlx::query_as::<_, (i32,)>(
+            r#"
+            select id from labels where name = $1 and workspace_id = $2
+            "#,
+        )
+        .bind(name)
+        .bind(self.workspace_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(label.map_or(0, |(id,)| id))
     }
 }
 
 #[async_trait]
 impl LabelRepository for LabelRepositoryForDb {
-    async fn create(&self, name: String) -> anyhow::Result<Label> {
-        let optional_label = sqlx::query_as::<_, Label>(
+    fn in_workspace(&self, workspace_id: i32) -> Self {
+        Self {
+            workspace_id,
+            ..self.clone()
+        }
+    }
+    #[tracing::instrument(skip_all)]
+    async fn find(&self, id: i32) -> anyhow::Result<Label> {
+        let label = sqlx::query_as::<_, LabelRow>(
             r#"
-        select * from labels where name = $1
-        "#,
+            select * from labels where id = $1 and workspace_id = $2
+            "#,
+        )
+        .bind(id)
+        .bind(self.workspace_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(label.into())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn id_of(&self, uuid: Uuid) -> anyhow::Result<Option<i32>> {
+        let id = sqlx::query_as::<_, (i32,)>(
+            r#"
+            select id from labels where uuid = $1 and workspace_id = $2
+            "#,
         )
-        .bind(name.clone())
+        .bind(DbUuid(uuid))
+        .bind(self.workspace_id)
         .fetch_optional(&self.pool)
         .await?;
 
-        if let Some(label) = optional_label {
-            return Err(RepositoryError::Unexpected(label.id.to_string()).into());
-        }
-
-        let label = sqlx::query_as::<_, Label>(
+        Ok(id.map(|(id,)| id))
+    }
+    #[tracing::instrument(skip_all)]
+    async fn create(&self, payload: CreateLabel) -> anyhow::Result<Label> {
+        let label = sqlx::query_as::<_, LabelRow>(
             r#"
-            insert into labels ( name )
-            values ( $1 )
+            insert into labels ( name, workspace_id, color, icon, uuid )
+            values ( $1, $2, $3, $4, $5 )
+            on conflict (workspace_id, name) do nothing
             returning *
             "#,
         )
-        .bind(name.clone())
-        .fetch_one(&self.pool)
+        .bind(&payload.name)
+        .bind(self.workspace_id)
+        .bind(payload.color)
+        .bind(payload.icon)
+        .bind(DbUuid(Uuid::now_v7()))
+        .fetch_optional(&self.pool)
         .await?;
 
-        Ok(label)
+        match label {
+            Some(label) => Ok(label.into()),
+            None => Err(RepositoryError::Duplicate(self.named(&payload.name).await?).into()),
+        }
+    }
+    #[tracing::instrument(skip_all)]
+    async fn update(&self, id: i32, payload: UpdateLabel) -> anyhow::Result<Label> {
+        let name = payload.name.clone();
+        let label = sqlx::query_as::<_, LabelRow>(
+            r#"
+            update labels set
+                name = coalesce($3, name),
+                color = coalesce($4, color),
+                icon = case when $5::text is null then icon else nullif($5, '') end
+            where id = $1 and workspace_id = $2
+            returning *
+            "#,
+        )
+        .bind(id)
+        .bind(self.workspace_id)
+        .bind(payload.name)
+        .bind(payload.color)
+        .bind(payload.icon)
+        .fetch_optional(&self.pool)
+        .await;
+        let label = match (label, name) {
+            // unique_violation: another label has the name
+            (Err(sqlx::Error::Database(e)), Some(name)) if e.code().as_deref() == Some("23505") => {
+                return Err(RepositoryError::Duplicate(self.named(&name).await?).into());
+            }
+            (label, _) => label?.ok_or(RepositoryError::NotFound(id))?,
+        };
+
+        Ok(label.into())
     }
+    #[tracing::instrument(skip_all)]
     async fn all(&self) -> anyhow::Result<Vec<Label>> {
-        let labels = sqlx::query_as::<_, Label>(
+        let labels = sqlx::query_as::<_, LabelRow>(
             r#"
             select * from labels
+            where workspace_id = $1
             order by labels.id asc;
             "#,
         )
+        .bind(self.workspace_id)
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(labels)
+        Ok(labels.into_iter().map(Label::from).collect())
     }
+    #[tracing::instrument(skip_all)]
+    async fn count(&self) -> anyhow::Result<i64> {
+        let (count,) = sqlx::query_as::<_, (i64,)>(
+            r#"
+            select count(*) from labels where workspace_id=$1
+        "#,
+        )
+        .bind(self.workspace_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count)
+    }
+    #[tracing::instrument(skip_all)]
     async fn delete(&self, id: i32) -> anyhow::Result<()> {
-        sqlx::query(
+        let mut tx = self.pool.begin().await?;
+        let deleted = sqlx::query(
             r#"
-          delete from labels where id=$1
+          delete from labels where id=$1 and workspace_id=$2
           "#,
         )
         .bind(id)
-        .execute(&self.pool)
+        .bind(self.workspace_id)
+        .execute(&mut tx)
         .await
-        .map_err(|e| match e {
-            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
-            _ => RepositoryError::Unexpected(e.to_string()),
-        })?;
+        .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+        if deleted.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+        // the foreign key is deferred, so the links can go after the label
+        sqlx::query(
+            r#"
+          delete from todo_labels where label_id=$1
+          "#,
+        )
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
 
         Ok(())
     }
@@ -96,31 +617,236 @@ impl LabelRepository for LabelRepositoryForDb {
 #[cfg(test)]
 mod test {
     use super::*;
-    use dotenv::dotenv;
-    use sqlx::PgPool;
-    use std::env;
+    use crate::test_db::TestDb;
 
     #[tokio::test]
-    async fn crud_scenario() {
-        dotenv().ok();
+    async fn should_not_reuse_id_after_delete() {
+        let repository = LabelRepositoryForMemory::new();
+        let first = repository
+            .create(CreateLabel::new("first".to_string()))
+            .await
+            .unwrap();
+        let second = repository
+            .create(CreateLabel::new("second".to_string()))
+            .await
+            .unwrap();
+        repository.delete(first.id).await.unwrap();
 
-        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let third = repository
+            .create(CreateLabel::new("third".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(third.id, 3);
+        assert_eq!(repository.all().await.unwrap(), vec![second, third]);
+    }
 
-        let pool = PgPool::connect(database_url)
+    #[tokio::test]
+    async fn should_keep_workspaces_apart() {
+        let repository = LabelRepositoryForMemory::new();
+        let other = repository.in_workspace(2);
+        let shared = repository
+            .create(CreateLabel::new("shared".to_string()))
+            .await
+            .unwrap();
+        let private = other
+            .create(CreateLabel::new("shared".to_string()))
             .await
-            .expect("failed connect database");
+            .unwrap();
+
+        assert_eq!(other.all().await.unwrap(), vec![private.clone()]);
+        assert_eq!(other.get(shared.id), None);
+        assert!(other.delete(shared.id).await.is_err());
+        assert_eq!(repository.all().await.unwrap(), vec![shared]);
+    }
+
+    #[tokio::test]
+    async fn should_update_label_attributes() {
+        let repository = LabelRepositoryForMemory::new();
+        let home = repository
+            .create(CreateLabel {
+                icon: Some("🏠".to_string()),
+                ..CreateLabel::new("home".to_string())
+            })
+            .await
+            .unwrap();
+        let work = repository
+            .create(CreateLabel::new("work".to_string()))
+            .await
+            .unwrap();
+
+        let recolored = repository
+            .update(
+                home.id,
+                UpdateLabel {
+                    color: Some("#f80".to_string()),
+                    ..UpdateLabel::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            recolored,
+            Label {
+                color: "#f80".to_string(),
+                ..home.clone()
+            }
+        );
+        let plain = repository
+            .update(
+                home.id,
+                UpdateLabel {
+                    icon: Some(String::new()),
+                    ..UpdateLabel::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(plain.icon, None);
+
+        let renamed = repository
+            .update(
+                work.id,
+                UpdateLabel {
+                    name: Some("home".to_string()),
+                    ..UpdateLabel::default()
+                },
+            )
+            .await;
+        assert!(matches!(
+            renamed.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Duplicate(id)) if *id == home.id
+        ));
+        assert!(repository
+            .in_workspace(2)
+            .update(home.id, UpdateLabel::default())
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn should_validate_colors() {
+        let label = |color: &str| CreateLabel {
+            color: color.to_string(),
+            ..CreateLabel::new("home".to_string())
+        };
+        assert!(label("#1e90ff").validate().is_ok());
+        assert!(label("#FFF").validate().is_ok());
+        for invalid in ["1e90ff", "#1e90f", "#ggg", "red", ""] {
+            assert!(label(invalid).validate().is_err(), "{}", invalid);
+        }
+        let icon = CreateLabel {
+            icon: Some(String::new()),
+            ..CreateLabel::new("home".to_string())
+        };
+        assert!(icon.validate().is_err());
+    }
+
+    #[test]
+    fn should_suggest_prefix_matches_then_most_used() {
+        let stats: Vec<LabelStats> = [("work", 1), ("Workout", 5), ("groceries", 9), ("home", 9)]
+            .into_iter()
+            .enumerate()
+            .map(|(i, (name, todos))| LabelStats {
+                label: Label::new(i as i32 + 1, name.to_string()),
+                todos,
+            })
+            .collect();
+        let search = |text: &str| Search {
+            text: text.to_string(),
+            threshold: 0.3,
+        };
+        let names = |stats: Vec<LabelStats>| -> Vec<String> {
+            stats.into_iter().map(|stats| stats.label.name).collect()
+        };
+
+        assert_eq!(
+            names(LabelStats::suggest(stats.clone(), &search("wor"), 10)),
+            ["Workout", "work"]
+        );
+        assert_eq!(
+            names(LabelStats::suggest(stats.clone(), &search("grocries"), 10)),
+            ["groceries"]
+        );
+        assert_eq!(
+            names(LabelStats::suggest(stats, &search(""), 2)),
+            ["groceries", "home"]
+        );
+
+        assert_eq!(like_prefix("50%_off\\"), "50\\%\\_off\\\\%");
+    }
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        let db = TestDb::new().await;
+        let pool = db.pool.clone();
 
         let repository = LabelRepositoryForDb::new(pool.clone());
         let label_text = "test_label";
 
-        let created = repository.create(label_text.to_string()).await.unwrap();
+        let created = repository
+            .create(CreateLabel {
+                icon: Some("🏠".to_string()),
+                ..CreateLabel::new(label_text.to_string())
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(created.name, label_text.to_string());
+        assert_eq!(created.color, DEFAULT_LABEL_COLOR);
+        assert_eq!(created.icon.as_deref(), Some("🏠"));
+        assert!(!created.uuid.is_nil());
+        assert_eq!(
+            repository.id_of(created.uuid).await.unwrap(),
+            Some(created.id)
+        );
 
-        assert_eq!(created.text, label_text.to_string());
+        let updated = repository
+            .update(
+                created.id,
+                UpdateLabel {
+                    color: Some("#1e90ff".to_string()),
+                    icon: Some(String::new()),
+                    ..UpdateLabel::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            (updated.color.as_str(), updated.icon.as_deref()),
+            ("#1e90ff", None)
+        );
+        assert_eq!(repository.find(created.id).await.unwrap(), updated);
+
+        let duplicate = repository
+            .create(CreateLabel::new(label_text.to_string()))
+            .await;
+        assert!(matches!(
+            duplicate.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Duplicate(id)) if *id == created.id
+        ));
+        let other = repository
+            .create(CreateLabel::new("other_label".to_string()))
+            .await
+            .unwrap();
+        let renamed = repository
+            .update(
+                other.id,
+                UpdateLabel {
+                    name: Some(label_text.to_string()),
+                    ..UpdateLabel::default()
+                },
+            )
+            .await;
+        assert!(matches!(
+            renamed.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Duplicate(id)) if *id == created.id
+        ));
+        repository.delete(other.id).await.unwrap();
 
         let all = repository.all().await.unwrap();
 
         let label = all.last().unwrap();
-        assert_eq!(label.text, created.text);
+        assert_eq!(label.name, created.name);
 
         repository.delete(label.id).await.unwrap();
     }
@@ -0,0 +1,1230 @@
+//! Todos and labels in MongoDB, for deployments whose data lives there.
+//! A todo is one document of the `todos` collection holding its checklist,
+//! board position and revisions, with its labels referenced by id from the
+//! `labels` collection. Changes and the outbox entries of their events are
+//! written in one transaction, which needs a replica set; a single-node one
+//! will do.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use axum::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use mongodb::{
+    bson::{self, doc, Document},
+    error::{Error, ErrorKind, WriteFailure, TRANSIENT_TRANSACTION_ERROR},
+    options::{FindOneAndUpdateOptions, FindOptions, IndexOptions, ReturnDocument},
+    Client, ClientSession, Collection, Cursor, Database, IndexModel, SessionCursor,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use super::{
+    board::{self, Positions},
+    burndown::{self, BurndownDay},
+    checklist::{self, ChecklistItem, CreateChecklistItem, Progress, UpdateChecklistItem},
+    label::{CreateLabel, Label, LabelRepository, LabelStats, UpdateLabel},
+    search::Search,
+    todo::{
+        merge_custom, normalize_text, CreateTodo, OwnedTodos, PageCursor, Todo, TodoChange,
+        TodoFilter, TodoPage, TodoReader, TodoRevision, TodoScope, TodoWriter, UpdateTodo,
+    },
+    workspace::DEFAULT_WORKSPACE_ID,
+    RepositoryError,
+};
+use crate::{
+    events::{DomainEvent, WorkspaceEvent},
+    outbox::{OutboxEntry, LEASE_SECS},
+};
+
+/// Where the url names no database.
+const DEFAULT_DATABASE: &str = "my_todo";
+
+/// Connects to the database `url` names and makes sure the indexes the
+/// repositories rely on exist.
+pub async fn connect(url: &str) -> anyhow::Result<Database> {
+    let client = Client::with_uri_str(url).await?;
+    let db = client
+        .default_database()
+        .unwrap_or_else(|| client.database(DEFAULT_DATABASE));
+    let index = |keys: Document, unique: bool| {
+        IndexModel::builder()
+            .keys(keys)
+            .options(IndexOptions::builder().unique(unique).build())
+            .build()
+    };
+    db.collection::<TodoDocument>("todos")
+        .create_indexes(
+            [
+                index(doc! { "workspace_id": 1, "uuid": 1 }, true),
+                index(doc! { "owner_id": 1 }, false),
+            ],
+            None,
+        )
+        .await?;
+    db.collection::<LabelDocument>("labels")
+        .create_indexes(
+            [
+                index(doc! { "workspace_id": 1, "name": 1 }, true),
+                index(doc! { "workspace_id": 1, "uuid": 1 }, true),
+            ],
+            None,
+        )
+        .await?;
+    Ok(db)
+}
+
+/// The next value of counter `name`, counting from 1. Like a sequence in
+/// SQL, values taken by a transaction that fails are not handed out again.
+async fn next_id(db: &Database, name: &str) -> anyhow::Result<i64> {
+    let options = FindOneAndUpdateOptions::builder()
+        .upsert(true)
+        .return_document(ReturnDocument::After)
+        .build();
+    let counter = db
+        .collection::<Document>("counters")
+        .find_one_and_update(
+            doc! { "_id": name },
+            doc! { "$inc": { "seq": 1_i64 } },
+            options,
+        )
+        .await?
+        .ok_or_else(|| RepositoryError::Unexpected(format!("counter {} not created", name)))?;
+    Ok(counter.get_i64("seq")?)
+}
+
+async fn collect<T: DeserializeOwned + Unpin + Send + Sync>(
+    mut cursor: Cursor<T>,
+) -> anyhow::Result<Vec<T>> {
+    let mut documents = Vec::new();
+    while cursor.advance().await? {
+        documents.push(cursor.deserialize_current()?);
+    }
+    Ok(documents)
+}
+
+async fn collect_in<T: DeserializeOwned + Unpin + Send + Sync>(
+    mut cursor: SessionCursor<T>,
+    session: &mut ClientSession,
+) -> anyhow::Result<Vec<T>> {
+    let mut documents = Vec::new();
+    while cursor.advance(session).await? {
+        documents.push(cursor.deserialize_current()?);
+    }
+    Ok(documents)
+}
+
+fn by_id() -> FindOptions {
+    FindOptions::builder().sort(doc! { "_id": 1 }).build()
+}
+
+/// Starts a transaction; dropping the session before committing aborts it.
+async fn begin(db: &Database) -> anyhow::Result<ClientSession> {
+    let client = db.collection::<Document>("todos").client().clone();
+    let mut session = client.start_session(None).await?;
+    session.start_transaction(None).await?;
+    Ok(session)
+}
+
+/// Another transaction wrote the same document first.
+fn is_write_conflict(error: &Error) -> bool {
+    error.contains_label(TRANSIENT_TRANSACTION_ERROR)
+}
+
+fn is_duplicate_key(error: &Error) -> bool {
+    matches!(
+        &*error.kind,
+        ErrorKind::Write(WriteFailure::WriteError(e)) if e.code == 11000
+    )
+}
+
+/// A todo as the `todos` collection holds it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TodoDocument {
+    #[serde(rename = "_id")]
+    id: i32,
+    workspace_id: i32,
+    uuid: String,
+    text: String,
+    completed: bool,
+    /// Ids into the `labels` collection, ascending; deleting a label pulls
+    /// its id out.
+    labels: Vec<i32>,
+    owner_id: Option<i32>,
+    assignee_id: Option<i32>,
+    #[serde(default)]
+    text_i18n: BTreeMap<String, String>,
+    snoozed_until: Option<DateTime<Utc>>,
+    estimate_minutes: Option<i32>,
+    due_date: Option<NaiveDate>,
+    description: Option<String>,
+    #[serde(default)]
+    custom: BTreeMap<String, Value>,
+    /// The checklist, in order.
+    #[serde(default)]
+    items: Vec<ChecklistItem>,
+    /// Where the todo sits in its board column, once moved there.
+    position: Option<i32>,
+    #[serde(default)]
+    revisions: Vec<TodoRevision>,
+}
+
+impl TodoDocument {
+    fn new(id: i32, workspace_id: i32, payload: CreateTodo) -> Self {
+        let mut document = Self {
+            id,
+            workspace_id,
+            uuid: Uuid::now_v7().to_string(),
+            text: payload.text,
+            completed: false,
+            labels: normalized(&payload.labels),
+            owner_id: payload.owner_id,
+            assignee_id: payload.assignee_id,
+            text_i18n: payload.text_i18n,
+            snoozed_until: None,
+            estimate_minutes: payload.estimate_minutes,
+            due_date: payload.due_date,
+            description: payload.description,
+            custom: payload.custom,
+            items: vec![],
+            position: None,
+            revisions: vec![],
+        };
+        document.push_revision();
+        document
+    }
+
+    /// The todo, with the labels of `labels` it refers to.
+    fn todo(&self, labels: &HashMap<i32, Label>) -> Todo {
+        Todo {
+            id: self.id,
+            uuid: self.uuid.parse().unwrap_or_default(),
+            text: self.text.clone(),
+            completed: self.completed,
+            labels: self
+                .labels
+                .iter()
+                .filter_map(|id| labels.get(id).cloned())
+                .collect(),
+            owner_id: self.owner_id,
+            assignee_id: self.assignee_id,
+            text_i18n: self.text_i18n.clone(),
+            snoozed_until: self.snoozed_until,
+            estimate_minutes: self.estimate_minutes,
+            due_date: self.due_date,
+            description: self.description.clone(),
+            custom: self.custom.clone(),
+            progress: Progress::of(&self.items),
+        }
+    }
+
+    /// Applies `payload`, recording a revision; its label ids are checked
+    /// by the caller.
+    fn update(&mut self, payload: UpdateTodo) {
+        if let Some(text) = payload.text {
+            self.text = text;
+        }
+        if let Some(completed) = payload.completed {
+            self.completed = completed;
+        }
+        if let Some(labels) = payload.labels {
+            self.labels = normalized(&labels);
+        }
+        if let Some(assignee_id) = payload.assignee_id {
+            self.assignee_id = assignee_id;
+        }
+        if let Some(text_i18n) = payload.text_i18n {
+            self.text_i18n = text_i18n;
+        }
+        if let Some(estimate_minutes) = payload.estimate_minutes {
+            self.estimate_minutes = estimate_minutes;
+        }
+        if let Some(due_date) = payload.due_date {
+            self.due_date = due_date;
+        }
+        if let Some(description) = payload.description {
+            self.description = description;
+        }
+        if let Some(changes) = &payload.custom {
+            merge_custom(&mut self.custom, changes);
+        }
+        self.push_revision();
+    }
+
+    fn push_revision(&mut self) {
+        self.revisions.push(TodoRevision {
+            todo_id: self.id,
+            rev: self.revisions.len() as i32 + 1,
+            text: self.text.clone(),
+            completed: self.completed,
+            estimate_minutes: self.estimate_minutes,
+            created_at: Utc::now(),
+        });
+    }
+}
+
+fn normalized(ids: &[i32]) -> Vec<i32> {
+    let mut ids = ids.to_vec();
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
+
+/// Fails with NotFound on the first of `ids` not in `labels`.
+fn check_labels(labels: &HashMap<i32, Label>, ids: &[i32]) -> Result<(), RepositoryError> {
+    match ids.iter().find(|id| !labels.contains_key(id)) {
+        Some(id) => Err(RepositoryError::NotFound(*id)),
+        None => Ok(()),
+    }
+}
+
+/// Fails with MissingLabels, naming every one of `ids` not in `labels`.
+fn check_new_labels(labels: &HashMap<i32, Label>, ids: &[i32]) -> Result<(), RepositoryError> {
+    let missing: Vec<i32> = normalized(ids)
+        .into_iter()
+        .filter(|id| !labels.contains_key(id))
+        .collect();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(RepositoryError::MissingLabels(missing))
+    }
+}
+
+/// A label as the `labels` collection holds it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct LabelDocument {
+    #[serde(rename = "_id")]
+    id: i32,
+    workspace_id: i32,
+    uuid: String,
+    name: String,
+    color: String,
+    icon: Option<String>,
+}
+
+impl LabelDocument {
+    fn label(self) -> Label {
+        Label {
+            id: self.id,
+            uuid: self.uuid.parse().unwrap_or_default(),
+            name: self.name,
+            color: self.color,
+            icon: self.icon,
+        }
+    }
+}
+
+/// An event waiting in the `outbox` collection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct OutboxDocument {
+    #[serde(rename = "_id")]
+    id: i64,
+    workspace_id: i32,
+    event: DomainEvent,
+    /// Until when the relay that claimed it may send it alone.
+    claimed_until: Option<bson::DateTime>,
+}
+
+/// The labels of every document `filter` matches, by id.
+async fn label_map(db: &Database, filter: Document) -> anyhow::Result<HashMap<i32, Label>> {
+    let documents = collect(
+        db.collection::<LabelDocument>("labels")
+            .find(filter, None)
+            .await?,
+    )
+    .await?;
+    Ok(documents
+        .into_iter()
+        .map(|document| (document.id, document.label()))
+        .collect())
+}
+
+#[derive(Debug, Clone)]
+pub struct TodoRepositoryForMongo {
+    db: Database,
+    workspace_id: i32,
+    filter: TodoFilter,
+    actor: Option<i32>,
+}
+
+impl TodoRepositoryForMongo {
+    pub fn new(db: Database) -> Self {
+        Self {
+            db,
+            workspace_id: DEFAULT_WORKSPACE_ID,
+            filter: TodoFilter::default(),
+            actor: None,
+        }
+    }
+
+    fn todos(&self) -> Collection<TodoDocument> {
+        self.db.collection("todos")
+    }
+
+    fn outbox(&self) -> Collection<OutboxDocument> {
+        self.db.collection("outbox")
+    }
+
+    fn scoped(&self, id: i32) -> Document {
+        doc! { "_id": id, "workspace_id": self.workspace_id }
+    }
+
+    fn in_scope(&self) -> Document {
+        doc! { "workspace_id": self.workspace_id }
+    }
+
+    /// The workspace's labels by id.
+    async fn labels(&self) -> anyhow::Result<HashMap<i32, Label>> {
+        label_map(&self.db, self.in_scope()).await
+    }
+
+    /// The labels of every workspace by id, for todos of any of them.
+    async fn every_label(&self) -> anyhow::Result<HashMap<i32, Label>> {
+        label_map(&self.db, doc! {}).await
+    }
+
+    /// The workspace's todo documents, oldest first.
+    async fn documents(&self) -> anyhow::Result<Vec<TodoDocument>> {
+        collect(self.todos().find(self.in_scope(), by_id()).await?).await
+    }
+
+    async fn load(&self, session: &mut ClientSession, id: i32) -> anyhow::Result<TodoDocument> {
+        Ok(self
+            .todos()
+            .find_one_with_session(self.scoped(id), None, session)
+            .await?
+            .ok_or(RepositoryError::NotFound(id))?)
+    }
+
+    /// Writes `document` back, failing with Conflict when another
+    /// transaction changed it since it was loaded.
+    async fn save(
+        &self,
+        session: &mut ClientSession,
+        document: &TodoDocument,
+    ) -> anyhow::Result<()> {
+        match self
+            .todos()
+            .replace_one_with_session(doc! { "_id": document.id }, document, None, session)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) if is_write_conflict(&e) => Err(RepositoryError::Conflict(document.id).into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn record(
+        &self,
+        session: &mut ClientSession,
+        workspace_id: i32,
+        events: Vec<DomainEvent>,
+    ) -> anyhow::Result<()> {
+        for event in events {
+            let entry = OutboxDocument {
+                id: next_id(&self.db, "outbox").await?,
+                workspace_id,
+                event,
+                claimed_until: None,
+            };
+            self.outbox()
+                .insert_one_with_session(entry, None, session)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn insert(
+        &self,
+        session: &mut ClientSession,
+        labels: &HashMap<i32, Label>,
+        payload: CreateTodo,
+    ) -> anyhow::Result<(Todo, Vec<DomainEvent>)> {
+        check_new_labels(labels, &payload.labels)?;
+        let id = next_id(&self.db, "todos").await? as i32;
+        let document = TodoDocument::new(id, self.workspace_id, payload);
+        self.todos()
+            .insert_one_with_session(&document, None, session)
+            .await?;
+        let todo = document.todo(labels);
+        let events = DomainEvent::created(&todo, self.actor);
+        Ok((todo, events))
+    }
+
+    async fn replace(
+        &self,
+        session: &mut ClientSession,
+        labels: &HashMap<i32, Label>,
+        id: i32,
+        expected: Option<&Todo>,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<(Todo, Vec<DomainEvent>)> {
+        if let Some(ids) = &payload.labels {
+            check_labels(labels, ids)?;
+        }
+        let mut document = self.load(session, id).await?;
+        let before = document.todo(labels);
+        if expected.is_some_and(|expected| before != *expected) {
+            return Err(RepositoryError::Conflict(id).into());
+        }
+        document.update(payload);
+        self.save(session, &document).await?;
+        let after = document.todo(labels);
+        let events = DomainEvent::changed(&before, &after, self.actor);
+        Ok((after, events))
+    }
+
+    async fn remove(
+        &self,
+        session: &mut ClientSession,
+        id: i32,
+    ) -> anyhow::Result<Vec<DomainEvent>> {
+        let deleted = self
+            .todos()
+            .delete_one_with_session(self.scoped(id), None, session)
+            .await?;
+        if deleted.deleted_count == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+        Ok(vec![DomainEvent::TodoDeleted(id)])
+    }
+
+    async fn update_checked(
+        &self,
+        id: i32,
+        expected: Option<&Todo>,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<Todo> {
+        let labels = self.labels().await?;
+        let mut session = begin(&self.db).await?;
+        let (todo, events) = self
+            .replace(&mut session, &labels, id, expected, payload)
+            .await?;
+        self.record(&mut session, self.workspace_id, events).await?;
+        session.commit_transaction().await?;
+        Ok(todo)
+    }
+
+    /// Lets `change` rework the checklist of todo `id`, recording the todo
+    /// as changed.
+    async fn change_checklist<R: Send>(
+        &self,
+        id: i32,
+        change: impl FnOnce(&mut Vec<ChecklistItem>) -> Result<R, RepositoryError> + Send,
+    ) -> anyhow::Result<R> {
+        let labels = self.labels().await?;
+        let mut session = begin(&self.db).await?;
+        let mut document = self.load(&mut session, id).await?;
+        let before = document.todo(&labels);
+        let result = change(&mut document.items)?;
+        self.save(&mut session, &document).await?;
+        let events = DomainEvent::changed(&before, &document.todo(&labels), self.actor);
+        self.record(&mut session, self.workspace_id, events).await?;
+        session.commit_transaction().await?;
+        Ok(result)
+    }
+}
+
+impl TodoScope for TodoRepositoryForMongo {
+    fn in_workspace(&self, workspace_id: i32) -> Self {
+        Self {
+            workspace_id,
+            ..self.clone()
+        }
+    }
+}
+
+#[async_trait]
+impl TodoReader for TodoRepositoryForMongo {
+    async fn find(&self, id: i32) -> anyhow::Result<Todo> {
+        let document = self
+            .todos()
+            .find_one(self.scoped(id), None)
+            .await?
+            .ok_or(RepositoryError::NotFound(id))?;
+        Ok(document.todo(&self.labels().await?))
+    }
+    fn with_filter(&self, filter: TodoFilter) -> Self {
+        Self {
+            filter,
+            ..self.clone()
+        }
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Todo>> {
+        let labels = self.labels().await?;
+        let mut todos: Vec<Todo> = self
+            .documents()
+            .await?
+            .iter()
+            .map(|document| document.todo(&labels))
+            .filter(|todo| self.filter.matches(todo))
+            .collect();
+        self.filter.sort(&mut todos);
+        Ok(todos)
+    }
+    async fn count(&self) -> anyhow::Result<i64> {
+        if self.filter == TodoFilter::default() {
+            return Ok(self.todos().count_documents(self.in_scope(), None).await? as i64);
+        }
+        Ok(self.all().await?.len() as i64)
+    }
+    async fn exists(&self, id: i32) -> anyhow::Result<bool> {
+        Ok(self.todos().count_documents(self.scoped(id), None).await? > 0)
+    }
+    async fn id_of(&self, uuid: Uuid) -> anyhow::Result<Option<i32>> {
+        let filter = doc! { "workspace_id": self.workspace_id, "uuid": uuid.to_string() };
+        let document = self.todos().find_one(filter, None).await?;
+        Ok(document.map(|document| document.id))
+    }
+    async fn find_open_duplicate(&self, text: &str) -> anyhow::Result<Option<Todo>> {
+        let text = normalize_text(text);
+        let filter = doc! { "workspace_id": self.workspace_id, "completed": false };
+        let open = collect(self.todos().find(filter, by_id()).await?).await?;
+        match open
+            .into_iter()
+            .find(|document| normalize_text(&document.text) == text)
+        {
+            Some(document) => Ok(Some(document.todo(&self.labels().await?))),
+            None => Ok(None),
+        }
+    }
+    async fn page(&self, cursor: PageCursor, limit: u32) -> anyhow::Result<TodoPage> {
+        Ok(TodoPage::slice(self.all().await?, cursor, limit))
+    }
+    async fn revisions(&self, id: i32) -> anyhow::Result<Vec<TodoRevision>> {
+        let document = self
+            .todos()
+            .find_one(self.scoped(id), None)
+            .await?
+            .ok_or(RepositoryError::NotFound(id))?;
+        Ok(document.revisions)
+    }
+    async fn count_open_owned(&self, owner_id: i32) -> anyhow::Result<i64> {
+        let filter = doc! { "owner_id": owner_id, "completed": false };
+        Ok(self.todos().count_documents(filter, None).await? as i64)
+    }
+    async fn owned(&self, owner_id: i32) -> anyhow::Result<Vec<(i32, Todo)>> {
+        let owned = collect(
+            self.todos()
+                .find(doc! { "owner_id": owner_id }, by_id())
+                .await?,
+        )
+        .await?;
+        let labels = self.every_label().await?;
+        Ok(owned
+            .iter()
+            .map(|document| (document.workspace_id, document.todo(&labels)))
+            .collect())
+    }
+    async fn label_stats(&self) -> anyhow::Result<Vec<LabelStats>> {
+        let mut counts: HashMap<i32, i64> = HashMap::new();
+        for document in self.documents().await? {
+            for id in document.labels {
+                *counts.entry(id).or_default() += 1;
+            }
+        }
+        let mut labels: Vec<Label> = self.labels().await?.into_values().collect();
+        labels.sort_by_key(|label| label.id);
+        Ok(labels
+            .into_iter()
+            .map(|label| LabelStats {
+                todos: counts.get(&label.id).copied().unwrap_or_default(),
+                label,
+            })
+            .collect())
+    }
+    async fn suggest_labels(&self, search: &Search, limit: u32) -> anyhow::Result<Vec<LabelStats>> {
+        Ok(LabelStats::suggest(
+            self.label_stats().await?,
+            search,
+            limit,
+        ))
+    }
+    async fn checklist(&self, id: i32) -> anyhow::Result<Vec<ChecklistItem>> {
+        let document = self
+            .todos()
+            .find_one(self.scoped(id), None)
+            .await?
+            .ok_or(RepositoryError::NotFound(id))?;
+        Ok(document.items)
+    }
+    async fn burndown(&self, from: NaiveDate, to: NaiveDate) -> anyhow::Result<Vec<BurndownDay>> {
+        let revisions: Vec<TodoRevision> = self
+            .documents()
+            .await?
+            .into_iter()
+            .flat_map(|document| document.revisions)
+            .collect();
+        Ok(burndown::from_revisions(&revisions, from, to))
+    }
+    async fn board_positions(&self) -> anyhow::Result<Positions> {
+        Ok(self
+            .documents()
+            .await?
+            .iter()
+            .filter_map(|document| Some((document.id, document.position?)))
+            .collect())
+    }
+    async fn due_by(&self, user_id: i32, by: NaiveDate) -> anyhow::Result<Vec<(i32, Todo)>> {
+        let filter = doc! {
+            "completed": false,
+            "due_date": { "$ne": null },
+            "$or": [
+                { "assignee_id": user_id },
+                { "assignee_id": null, "owner_id": user_id },
+            ],
+        };
+        let open = collect(self.todos().find(filter, by_id()).await?).await?;
+        let labels = self.every_label().await?;
+        let mut due: Vec<(i32, Todo)> = open
+            .iter()
+            .filter(|document| document.due_date.is_some_and(|due| due <= by))
+            .map(|document| (document.workspace_id, document.todo(&labels)))
+            .collect();
+        due.sort_by_key(|(_, todo)| (todo.due_date, todo.id));
+        Ok(due)
+    }
+}
+
+#[async_trait]
+impl TodoWriter for TodoRepositoryForMongo {
+    async fn create(&self, payload: CreateTodo) -> anyhow::Result<Todo> {
+        let labels = self.labels().await?;
+        let mut session = begin(&self.db).await?;
+        let (todo, events) = self.insert(&mut session, &labels, payload).await?;
+        self.record(&mut session, self.workspace_id, events).await?;
+        session.commit_transaction().await?;
+        Ok(todo)
+    }
+    async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<Todo> {
+        self.update_checked(id, None, payload).await
+    }
+    async fn update_if(
+        &self,
+        id: i32,
+        expected: &Todo,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<Todo> {
+        self.update_checked(id, Some(expected), payload).await
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let mut session = begin(&self.db).await?;
+        let events = self.remove(&mut session, id).await?;
+        self.record(&mut session, self.workspace_id, events).await?;
+        session.commit_transaction().await?;
+        Ok(())
+    }
+    async fn revert(&self, id: i32, rev: i32) -> anyhow::Result<Todo> {
+        let revision = self
+            .revisions(id)
+            .await?
+            .into_iter()
+            .find(|revision| revision.rev == rev)
+            .ok_or(RepositoryError::NotFound(rev))?;
+        self.update(
+            id,
+            UpdateTodo {
+                text: Some(revision.text),
+                completed: Some(revision.completed),
+                estimate_minutes: Some(revision.estimate_minutes),
+                ..UpdateTodo::default()
+            },
+        )
+        .await
+    }
+    async fn delete_unused_labels(&self) -> anyhow::Result<Vec<i32>> {
+        let mut session = begin(&self.db).await?;
+        let documents = collect_in(
+            self.todos()
+                .find_with_session(self.in_scope(), None, &mut session)
+                .await?,
+            &mut session,
+        )
+        .await?;
+        let used: HashSet<i32> = documents
+            .into_iter()
+            .flat_map(|document| document.labels)
+            .collect();
+        let mut unused: Vec<i32> = self
+            .labels()
+            .await?
+            .into_keys()
+            .filter(|id| !used.contains(id))
+            .collect();
+        unused.sort_unstable();
+        self.db
+            .collection::<LabelDocument>("labels")
+            .delete_many_with_session(
+                doc! { "workspace_id": self.workspace_id, "_id": { "$in": unused.clone() } },
+                None,
+                &mut session,
+            )
+            .await?;
+        session.commit_transaction().await?;
+        Ok(unused)
+    }
+    async fn merge_labels(&self, source: i32, target: i32) -> anyhow::Result<Vec<Todo>> {
+        let labels = self.labels().await?;
+        check_labels(&labels, &[source, target])?;
+        let mut session = begin(&self.db).await?;
+        let filter = doc! { "workspace_id": self.workspace_id, "labels": source };
+        let carrying = collect_in(
+            self.todos()
+                .find_with_session(filter, by_id(), &mut session)
+                .await?,
+            &mut session,
+        )
+        .await?;
+        let mut moved = Vec::with_capacity(carrying.len());
+        for mut document in carrying {
+            let before = document.todo(&labels);
+            let ids: Vec<i32> = document
+                .labels
+                .iter()
+                .map(|&id| if id == source { target } else { id })
+                .collect();
+            document.labels = normalized(&ids);
+            self.save(&mut session, &document).await?;
+            let after = document.todo(&labels);
+            let events = DomainEvent::changed(&before, &after, self.actor);
+            self.record(&mut session, self.workspace_id, events).await?;
+            moved.push(after);
+        }
+        self.db
+            .collection::<LabelDocument>("labels")
+            .delete_one_with_session(self.scoped(source), None, &mut session)
+            .await?;
+        session.commit_transaction().await?;
+        Ok(moved)
+    }
+    async fn snooze(&self, id: i32, until: Option<DateTime<Utc>>) -> anyhow::Result<Todo> {
+        let labels = self.labels().await?;
+        let mut session = begin(&self.db).await?;
+        let mut document = self.load(&mut session, id).await?;
+        let before = document.todo(&labels);
+        document.snoozed_until = until;
+        self.save(&mut session, &document).await?;
+        let after = document.todo(&labels);
+        let events = DomainEvent::changed(&before, &after, self.actor);
+        self.record(&mut session, self.workspace_id, events).await?;
+        session.commit_transaction().await?;
+        Ok(after)
+    }
+    async fn unsnooze_due(&self) -> anyhow::Result<Vec<(i32, i32)>> {
+        let now = Utc::now();
+        let labels = self.every_label().await?;
+        let mut session = begin(&self.db).await?;
+        let snoozed = collect_in(
+            self.todos()
+                .find_with_session(
+                    doc! { "snoozed_until": { "$ne": null } },
+                    None,
+                    &mut session,
+                )
+                .await?,
+            &mut session,
+        )
+        .await?;
+        let mut due: Vec<TodoDocument> = snoozed
+            .into_iter()
+            .filter(|document| document.snoozed_until.is_some_and(|until| until <= now))
+            .collect();
+        due.sort_by_key(|document| (document.workspace_id, document.id));
+        let mut woken = Vec::with_capacity(due.len());
+        for mut document in due {
+            document.snoozed_until = None;
+            self.save(&mut session, &document).await?;
+            let event = DomainEvent::TodoUpdated(document.todo(&labels));
+            self.record(&mut session, document.workspace_id, vec![event])
+                .await?;
+            woken.push((document.workspace_id, document.id));
+        }
+        session.commit_transaction().await?;
+        Ok(woken)
+    }
+    async fn add_item(
+        &self,
+        id: i32,
+        payload: CreateChecklistItem,
+    ) -> anyhow::Result<ChecklistItem> {
+        let item = ChecklistItem {
+            id: next_id(&self.db, "checklist_items").await? as i32,
+            text: payload.text,
+            completed: false,
+            position: 0,
+        };
+        self.change_checklist(id, |items| {
+            Ok(checklist::insert(items, item, payload.position))
+        })
+        .await
+    }
+    async fn update_item(
+        &self,
+        id: i32,
+        item_id: i32,
+        payload: UpdateChecklistItem,
+    ) -> anyhow::Result<ChecklistItem> {
+        self.change_checklist(id, |items| checklist::update(items, item_id, payload))
+            .await
+    }
+    async fn delete_item(&self, id: i32, item_id: i32) -> anyhow::Result<()> {
+        self.change_checklist(id, |items| checklist::remove(items, item_id))
+            .await
+    }
+    async fn move_on_board(&self, id: i32, completed: bool, position: u32) -> anyhow::Result<Todo> {
+        let labels = self.labels().await?;
+        let mut session = begin(&self.db).await?;
+        let mut document = self.load(&mut session, id).await?;
+        if document.completed != completed {
+            let before = document.todo(&labels);
+            document.completed = completed;
+            document.push_revision();
+            self.save(&mut session, &document).await?;
+            let events = DomainEvent::changed(&before, &document.todo(&labels), self.actor);
+            self.record(&mut session, self.workspace_id, events).await?;
+        }
+        let filter = doc! { "workspace_id": self.workspace_id, "completed": completed };
+        let column = collect_in(
+            self.todos()
+                .find_with_session(filter, None, &mut session)
+                .await?,
+            &mut session,
+        )
+        .await?
+        .into_iter()
+        .map(|other| (other.id, other.position))
+        .collect();
+        for (id, position) in board::reorder(column, id, position).into_iter().zip(0..) {
+            self.todos()
+                .update_one_with_session(
+                    doc! { "_id": id },
+                    doc! { "$set": { "position": position } },
+                    None,
+                    &mut session,
+                )
+                .await?;
+        }
+        session.commit_transaction().await?;
+        Ok(document.todo(&labels))
+    }
+    async fn release_owned(
+        &self,
+        owner_id: i32,
+        release: OwnedTodos,
+    ) -> anyhow::Result<Vec<(i32, i32)>> {
+        let labels = self.every_label().await?;
+        let mut session = begin(&self.db).await?;
+        let mut owned = collect_in(
+            self.todos()
+                .find_with_session(doc! { "owner_id": owner_id }, None, &mut session)
+                .await?,
+            &mut session,
+        )
+        .await?;
+        owned.sort_by_key(|document| (document.workspace_id, document.id));
+        let mut released = Vec::with_capacity(owned.len());
+        for mut document in owned {
+            let event = match release {
+                OwnedTodos::Delete => {
+                    self.todos()
+                        .delete_one_with_session(doc! { "_id": document.id }, None, &mut session)
+                        .await?;
+                    DomainEvent::TodoDeleted(document.id)
+                }
+                OwnedTodos::TransferTo(new_owner) => {
+                    document.owner_id = Some(new_owner);
+                    self.save(&mut session, &document).await?;
+                    DomainEvent::TodoUpdated(document.todo(&labels))
+                }
+            };
+            self.record(&mut session, document.workspace_id, vec![event])
+                .await?;
+            released.push((document.workspace_id, document.id));
+        }
+        session.commit_transaction().await?;
+        Ok(released)
+    }
+    async fn apply(&self, changes: Vec<TodoChange>) -> anyhow::Result<Vec<Option<Todo>>> {
+        let labels = self.labels().await?;
+        let mut session = begin(&self.db).await?;
+        let mut changed = Vec::with_capacity(changes.len());
+        let mut events = Vec::new();
+        for change in changes {
+            let (todo, more) = match change {
+                TodoChange::Create(payload) => {
+                    let (todo, events) = self.insert(&mut session, &labels, payload).await?;
+                    (Some(todo), events)
+                }
+                TodoChange::Update(id, payload) => {
+                    let (todo, events) = self
+                        .replace(&mut session, &labels, id, None, payload)
+                        .await?;
+                    (Some(todo), events)
+                }
+                TodoChange::Delete(id) => (None, self.remove(&mut session, id).await?),
+            };
+            changed.push(todo);
+            events.extend(more);
+        }
+        self.record(&mut session, self.workspace_id, events).await?;
+        session.commit_transaction().await?;
+        Ok(changed)
+    }
+    fn acting_as(&self, user_id: Option<i32>) -> Self {
+        Self {
+            actor: user_id,
+            ..self.clone()
+        }
+    }
+    async fn claim_outbox(&self, limit: u32) -> anyhow::Result<Vec<OutboxEntry>> {
+        let now = Utc::now();
+        let options = FindOneAndUpdateOptions::builder()
+            .sort(doc! { "_id": 1 })
+            .build();
+        let lease =
+            bson::DateTime::from_millis((now + Duration::seconds(LEASE_SECS)).timestamp_millis());
+        let mut claimed = Vec::new();
+        // one at a time, so no two relays get the same entry
+        while claimed.len() < limit as usize {
+            let unclaimed = doc! {
+                "$or": [
+                    { "claimed_until": null },
+                    { "claimed_until": { "$lt": bson::DateTime::from_millis(now.timestamp_millis()) } },
+                ],
+            };
+            let Some(entry) = self
+                .outbox()
+                .find_one_and_update(
+                    unclaimed,
+                    doc! { "$set": { "claimed_until": lease } },
+                    options.clone(),
+                )
+                .await?
+            else {
+                break;
+            };
+            claimed.push(OutboxEntry {
+                id: entry.id,
+                event: WorkspaceEvent {
+                    workspace_id: entry.workspace_id,
+                    event: entry.event,
+                },
+            });
+        }
+        Ok(claimed)
+    }
+    async fn mark_sent(&self, ids: &[i64]) -> anyhow::Result<()> {
+        self.outbox()
+            .delete_many(doc! { "_id": { "$in": ids.to_vec() } }, None)
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LabelRepositoryForMongo {
+    db: Database,
+    workspace_id: i32,
+}
+
+impl LabelRepositoryForMongo {
+    pub fn new(db: Database) -> Self {
+        Self {
+            db,
+            workspace_id: DEFAULT_WORKSPACE_ID,
+        }
+    }
+
+    fn labels(&self) -> Collection<LabelDocument> {
+        self.db.collection("labels")
+    }
+
+    fn scoped(&self, id: i32) -> Document {
+        doc! { "_id": id, "workspace_id": self.workspace_id }
+    }
+
+    async fn named(&self, name: &str) -> anyhow::Result<Option<LabelDocument>> {
+        let filter = doc! { "workspace_id": self.workspace_id, "name": name };
+        Ok(self.labels().find_one(filter, None).await?)
+    }
+
+    /// Turns the unique index refusing a taken name into Duplicate.
+    async fn duplicate_or(&self, name: &str, error: Error) -> anyhow::Error {
+        if is_duplicate_key(&error) {
+            if let Ok(Some(taken)) = self.named(name).await {
+                return RepositoryError::Duplicate(taken.id).into();
+            }
+        }
+        error.into()
+    }
+}
+
+#[async_trait]
+impl LabelRepository for LabelRepositoryForMongo {
+    fn in_workspace(&self, workspace_id: i32) -> Self {
+        Self {
+            workspace_id,
+            ..self.clone()
+        }
+    }
+    async fn find(&self, id: i32) -> anyhow::Result<Label> {
+        let document = self
+            .labels()
+            .find_one(self.scoped(id), None)
+            .await?
+            .ok_or(RepositoryError::NotFound(id))?;
+        Ok(document.label())
+    }
+    async fn id_of(&self, uuid: Uuid) -> anyhow::Result<Option<i32>> {
+        let filter = doc! { "workspace_id": self.workspace_id, "uuid": uuid.to_string() };
+        let document = self.labels().find_one(filter, None).await?;
+        Ok(document.map(|document| document.id))
+    }
+    async fn create(&self, payload: CreateLabel) -> anyhow::Result<Label> {
+        if let Some(taken) = self.named(&payload.name).await? {
+            return Err(RepositoryError::Duplicate(taken.id).into());
+        }
+        let document = LabelDocument {
+            id: next_id(&self.db, "labels").await? as i32,
+            workspace_id: self.workspace_id,
+            uuid: Uuid::now_v7().to_string(),
+            name: payload.name,
+            color: payload.color,
+            icon: payload.icon,
+        };
+        if let Err(e) = self.labels().insert_one(&document, None).await {
+            return Err(self.duplicate_or(&document.name, e).await);
+        }
+        Ok(document.label())
+    }
+    async fn update(&self, id: i32, payload: UpdateLabel) -> anyhow::Result<Label> {
+        let mut document = self
+            .labels()
+            .find_one(self.scoped(id), None)
+            .await?
+            .ok_or(RepositoryError::NotFound(id))?;
+        if let Some(name) = payload.name {
+            if let Some(taken) = self.named(&name).await? {
+                if taken.id != id {
+                    return Err(RepositoryError::Duplicate(taken.id).into());
+                }
+            }
+            document.name = name;
+        }
+        if let Some(color) = payload.color {
+            document.color = color;
+        }
+        if let Some(icon) = payload.icon {
+            document.icon = Some(icon).filter(|icon| !icon.is_empty());
+        }
+        if let Err(e) = self
+            .labels()
+            .replace_one(self.scoped(id), &document, None)
+            .await
+        {
+            return Err(self.duplicate_or(&document.name, e).await);
+        }
+        Ok(document.label())
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Label>> {
+        let filter = doc! { "workspace_id": self.workspace_id };
+        let documents = collect(self.labels().find(filter, by_id()).await?).await?;
+        Ok(documents
+            .into_iter()
+            .map(|document| document.label())
+            .collect())
+    }
+    async fn count(&self) -> anyhow::Result<i64> {
+        let filter = doc! { "workspace_id": self.workspace_id };
+        Ok(self.labels().count_documents(filter, None).await? as i64)
+    }
+    /// Pulls the label off the todos carrying it in the same transaction.
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let mut session = begin(&self.db).await?;
+        let deleted = self
+            .labels()
+            .delete_one_with_session(self.scoped(id), None, &mut session)
+            .await?;
+        if deleted.deleted_count == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+        self.db
+            .collection::<TodoDocument>("todos")
+            .update_many_with_session(
+                doc! { "workspace_id": self.workspace_id, "labels": id },
+                doc! { "$pull": { "labels": id } },
+                None,
+                &mut session,
+            )
+            .await?;
+        session.commit_transaction().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_keep_todos_as_documents() {
+        let mut document = TodoDocument::new(
+            7,
+            2,
+            CreateTodo {
+                labels: vec![3, 1, 3, 9],
+                owner_id: Some(5),
+                ..CreateTodo::new("pack".to_string())
+            },
+        );
+        assert_eq!(document.labels, vec![1, 3, 9]);
+        let stored: TodoDocument =
+            bson::from_document(bson::to_document(&document).unwrap()).unwrap();
+        assert_eq!(stored, document);
+
+        document.update(UpdateTodo {
+            completed: Some(true),
+            estimate_minutes: Some(Some(30)),
+            ..UpdateTodo::default()
+        });
+        document.items.push(ChecklistItem {
+            id: 1,
+            text: "bag".to_string(),
+            completed: true,
+            position: 0,
+        });
+        let revs: Vec<(i32, bool)> = document
+            .revisions
+            .iter()
+            .map(|revision| (revision.rev, revision.completed))
+            .collect();
+        assert_eq!(revs, vec![(1, false), (2, true)]);
+
+        // label 9 was deleted since, and drops out like a dangling join
+        let labels = HashMap::from([
+            (1, Label::new(1, "home".to_string())),
+            (3, Label::new(3, "work".to_string())),
+        ]);
+        let todo = document.todo(&labels);
+        assert_eq!(todo.id, 7);
+        assert!(!todo.uuid.is_nil());
+        assert_eq!(todo.labels, vec![labels[&1].clone(), labels[&3].clone()]);
+        assert_eq!(todo.progress, Progress { done: 1, total: 1 });
+        assert!(todo.completed);
+    }
+
+    #[test]
+    fn should_name_unknown_labels() {
+        let labels = HashMap::from([(1, Label::new(1, "home".to_string()))]);
+        assert!(check_labels(&labels, &[1]).is_ok());
+        assert!(matches!(
+            check_labels(&labels, &[1, 4, 2]),
+            Err(RepositoryError::NotFound(4))
+        ));
+        assert!(matches!(
+            check_new_labels(&labels, &[4, 1, 2, 4]),
+            Err(RepositoryError::MissingLabels(missing)) if missing == vec![2, 4]
+        ));
+    }
+}
@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+/// Where a user wants to hear about each kind of event. Stored as JSON, so
+/// kinds and channels added later fall back to their defaults.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct NotificationSettings {
+    pub due_soon: Channels,
+    pub assigned: Channels,
+    pub commented: Channels,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            due_soon: Channels::EMAIL_AND_WEBSOCKET,
+            assigned: Channels::EMAIL_AND_WEBSOCKET,
+            commented: Channels::WEBSOCKET,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Channels {
+    pub email: bool,
+    pub webhook: bool,
+    pub websocket: bool,
+    /// Posted to the user's Slack webhook for the workspace, when linked.
+    pub slack: bool,
+}
+
+impl Channels {
+    const EMAIL_AND_WEBSOCKET: Self = Self {
+        email: true,
+        webhook: false,
+        websocket: true,
+        slack: false,
+    };
+    const WEBSOCKET: Self = Self {
+        email: false,
+        webhook: false,
+        websocket: true,
+        slack: false,
+    };
+}
+
+/// Body of `PATCH /me/notifications`; absent members keep their value.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Validate)]
+pub struct UpdateNotificationSettings {
+    pub due_soon: Option<UpdateChannels>,
+    pub assigned: Option<UpdateChannels>,
+    pub commented: Option<UpdateChannels>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UpdateChannels {
+    pub email: Option<bool>,
+    pub webhook: Option<bool>,
+    pub websocket: Option<bool>,
+    pub slack: Option<bool>,
+}
+
+impl UpdateNotificationSettings {
+    pub fn apply(&self, settings: NotificationSettings) -> NotificationSettings {
+        let apply = |channels: Channels, update: Option<UpdateChannels>| match update {
+            Some(update) => Channels {
+                email: update.email.unwrap_or(channels.email),
+                webhook: update.webhook.unwrap_or(channels.webhook),
+                websocket: update.websocket.unwrap_or(channels.websocket),
+                slack: update.slack.unwrap_or(channels.slack),
+            },
+            None => channels,
+        };
+        NotificationSettings {
+            due_soon: apply(settings.due_soon, self.due_soon),
+            assigned: apply(settings.assigned, self.assigned),
+            commented: apply(settings.commented, self.commented),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_keep_absent_members_when_applying() {
+        let update: UpdateNotificationSettings =
+            serde_json::from_str(r#"{ "assigned": { "email": false, "webhook": true } }"#).unwrap();
+        let settings = update.apply(NotificationSettings::default());
+
+        assert_eq!(
+            settings.assigned,
+            Channels {
+                email: false,
+                webhook: true,
+                websocket: true,
+                slack: false,
+            }
+        );
+        assert_eq!(settings.due_soon, NotificationSettings::default().due_soon);
+
+        let stored: NotificationSettings =
+            serde_json::from_str(r#"{ "assigned": { "email": false } }"#).unwrap();
+        assert_eq!(stored.assigned, Channels::default());
+        assert_eq!(stored.commented, Channels::WEBSOCKET);
+    }
+}
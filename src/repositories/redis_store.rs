@@ -0,0 +1,1086 @@
+//! Todos in Redis, for demos and deployments that can afford to lose them.
+//! Each todo is a hash under `store:todo:{id}` whose fields hold JSON, and
+//! each workspace lists its todos in a sorted set scored by id, which pages
+//! the way the newest-first listing does. Ids come from INCR, so like a
+//! sequence they may skip. Labels stay with the label repository the todos
+//! are given; a label deleted there drops off the todos naming it.
+//!
+//! Writes WATCH the todos they read and go through in one MULTI/EXEC, failing
+//! with Conflict when another write changed one of them first.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+
+use axum::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use redis::{
+    aio::{Connection, ConnectionManager},
+    AsyncCommands, Client, Pipeline, Script,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tokio::sync::OnceCell;
+use uuid::Uuid;
+
+use super::{
+    board::{self, Positions},
+    burndown::{self, BurndownDay},
+    checklist::{self, ChecklistItem, CreateChecklistItem, Progress, UpdateChecklistItem},
+    label::{Label, LabelRepository, LabelStats},
+    search::Search,
+    todo::{
+        merge_custom, normalize_text, CreateTodo, OwnedTodos, PageCursor, Todo, TodoChange,
+        TodoFilter, TodoPage, TodoReader, TodoRevision, TodoScope, TodoWriter, UpdateTodo,
+    },
+    workspace::DEFAULT_WORKSPACE_ID,
+    RepositoryError,
+};
+use crate::{
+    events::{DomainEvent, WorkspaceEvent},
+    outbox::{OutboxEntry, LEASE_SECS},
+};
+
+/// Every todo of every workspace, for lookups by owner or assignee.
+const ALL_TODOS: &str = "store:todos";
+const TODO_IDS: &str = "store:todo_ids";
+const ITEM_IDS: &str = "store:checklist_item_ids";
+/// Outbox ids in order; the entries and their claims are hashes by id.
+const OUTBOX: &str = "store:outbox";
+const OUTBOX_ENTRIES: &str = "store:outbox:entries";
+const OUTBOX_CLAIMS: &str = "store:outbox:claims";
+const OUTBOX_IDS: &str = "store:outbox_ids";
+
+/// Claims up to ARGV[3] unclaimed or expired entries, oldest first, until
+/// ARGV[1] + ARGV[2] milliseconds; a script, so no two relays get the same.
+const CLAIM_OUTBOX: &str = r#"
+local claimed = {}
+local now = tonumber(ARGV[1])
+for _, id in ipairs(redis.call('ZRANGE', KEYS[1], 0, -1)) do
+    if #claimed >= 2 * tonumber(ARGV[3]) then
+        break
+    end
+    local claimed_until = tonumber(redis.call('HGET', KEYS[3], id) or '0')
+    if claimed_until < now then
+        redis.call('HSET', KEYS[3], id, now + tonumber(ARGV[2]))
+        table.insert(claimed, id)
+        table.insert(claimed, redis.call('HGET', KEYS[2], id))
+    end
+end
+return claimed
+"#;
+
+fn todo_key(id: i32) -> String {
+    format!("store:todo:{}", id)
+}
+
+fn workspace_key(workspace_id: i32) -> String {
+    format!("store:todos:{}", workspace_id)
+}
+
+fn uuid_key(workspace_id: i32) -> String {
+    format!("store:todo_uuids:{}", workspace_id)
+}
+
+/// A todo as its hash holds it, one JSON value per field.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TodoHash {
+    id: i32,
+    workspace_id: i32,
+    uuid: Uuid,
+    text: String,
+    completed: bool,
+    /// Label ids, ascending.
+    labels: Vec<i32>,
+    owner_id: Option<i32>,
+    assignee_id: Option<i32>,
+    text_i18n: BTreeMap<String, String>,
+    snoozed_until: Option<DateTime<Utc>>,
+    estimate_minutes: Option<i32>,
+    due_date: Option<NaiveDate>,
+    description: Option<String>,
+    custom: BTreeMap<String, Value>,
+    /// The checklist, in order.
+    items: Vec<ChecklistItem>,
+    /// Where the todo sits in its board column, once moved there.
+    position: Option<i32>,
+    revisions: Vec<TodoRevision>,
+}
+
+impl TodoHash {
+    fn new(id: i32, workspace_id: i32, payload: CreateTodo) -> Self {
+        let mut hash = Self {
+            id,
+            workspace_id,
+            uuid: Uuid::now_v7(),
+            text: payload.text,
+            completed: false,
+            labels: normalized(&payload.labels),
+            owner_id: payload.owner_id,
+            assignee_id: payload.assignee_id,
+            text_i18n: payload.text_i18n,
+            snoozed_until: None,
+            estimate_minutes: payload.estimate_minutes,
+            due_date: payload.due_date,
+            description: payload.description,
+            custom: payload.custom,
+            items: vec![],
+            position: None,
+            revisions: vec![],
+        };
+        hash.push_revision();
+        hash
+    }
+
+    fn fields(&self) -> anyhow::Result<Vec<(String, String)>> {
+        let Value::Object(fields) = serde_json::to_value(self)? else {
+            anyhow::bail!("todo {} is not a map", self.id);
+        };
+        Ok(fields
+            .into_iter()
+            .map(|(field, value)| (field, value.to_string()))
+            .collect())
+    }
+
+    /// The todo HGETALL returned, None when there was no hash.
+    fn from_fields(fields: HashMap<String, String>) -> anyhow::Result<Option<Self>> {
+        if fields.is_empty() {
+            return Ok(None);
+        }
+        let mut values = Map::new();
+        for (field, value) in fields {
+            values.insert(field, serde_json::from_str(&value)?);
+        }
+        Ok(Some(serde_json::from_value(Value::Object(values))?))
+    }
+
+    /// The todo, with the labels of `labels` it refers to.
+    fn todo(&self, labels: &HashMap<i32, Label>) -> Todo {
+        Todo {
+            id: self.id,
+            uuid: self.uuid,
+            text: self.text.clone(),
+            completed: self.completed,
+            labels: self
+                .labels
+                .iter()
+                .filter_map(|id| labels.get(id).cloned())
+                .collect(),
+            owner_id: self.owner_id,
+            assignee_id: self.assignee_id,
+            text_i18n: self.text_i18n.clone(),
+            snoozed_until: self.snoozed_until,
+            estimate_minutes: self.estimate_minutes,
+            due_date: self.due_date,
+            description: self.description.clone(),
+            custom: self.custom.clone(),
+            progress: Progress::of(&self.items),
+        }
+    }
+
+    /// Applies `payload`, recording a revision; its label ids are checked
+    /// by the caller.
+    fn update(&mut self, payload: UpdateTodo) {
+        if let Some(text) = payload.text {
+            self.text = text;
+        }
+        if let Some(completed) = payload.completed {
+            self.completed = completed;
+        }
+        if let Some(labels) = payload.labels {
+            self.labels = normalized(&labels);
+        }
+        if let Some(assignee_id) = payload.assignee_id {
+            self.assignee_id = assignee_id;
+        }
+        if let Some(text_i18n) = payload.text_i18n {
+            self.text_i18n = text_i18n;
+        }
+        if let Some(estimate_minutes) = payload.estimate_minutes {
+            self.estimate_minutes = estimate_minutes;
+        }
+        if let Some(due_date) = payload.due_date {
+            self.due_date = due_date;
+        }
+        if let Some(description) = payload.description {
+            self.description = description;
+        }
+        if let Some(changes) = &payload.custom {
+            merge_custom(&mut self.custom, changes);
+        }
+        self.push_revision();
+    }
+
+    fn push_revision(&mut self) {
+        self.revisions.push(TodoRevision {
+            todo_id: self.id,
+            rev: self.revisions.len() as i32 + 1,
+            text: self.text.clone(),
+            completed: self.completed,
+            estimate_minutes: self.estimate_minutes,
+            created_at: Utc::now(),
+        });
+    }
+}
+
+fn normalized(ids: &[i32]) -> Vec<i32> {
+    let mut ids = ids.to_vec();
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
+
+/// Fails with NotFound on the first of `ids` not in `labels`.
+fn check_labels(labels: &HashMap<i32, Label>, ids: &[i32]) -> Result<(), RepositoryError> {
+    match ids.iter().find(|id| !labels.contains_key(id)) {
+        Some(id) => Err(RepositoryError::NotFound(*id)),
+        None => Ok(()),
+    }
+}
+
+/// Fails with MissingLabels, naming every one of `ids` not in `labels`.
+fn check_new_labels(labels: &HashMap<i32, Label>, ids: &[i32]) -> Result<(), RepositoryError> {
+    let missing: Vec<i32> = normalized(ids)
+        .into_iter()
+        .filter(|id| !labels.contains_key(id))
+        .collect();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(RepositoryError::MissingLabels(missing))
+    }
+}
+
+/// The todos of `ids` in that order, leaving out those deleted meanwhile.
+async fn load_many(con: &mut ConnectionManager, ids: &[i32]) -> anyhow::Result<Vec<TodoHash>> {
+    if ids.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut pipe = redis::pipe();
+    for id in ids {
+        pipe.hgetall(todo_key(*id));
+    }
+    let fields: Vec<HashMap<String, String>> = pipe.query_async(con).await?;
+    let mut hashes = Vec::with_capacity(fields.len());
+    for fields in fields {
+        hashes.extend(TodoHash::from_fields(fields)?);
+    }
+    Ok(hashes)
+}
+
+/// Writes under way on a connection of their own. Todos read through it are
+/// WATCHed and writes are queued, to go through together on `commit` unless
+/// one of the watched todos changed meanwhile.
+struct Transaction {
+    con: Connection,
+    pipe: Pipeline,
+    queued: bool,
+    /// Todos as the transaction left them so far; None once deleted.
+    todos: HashMap<i32, Option<TodoHash>>,
+    watched: Vec<i32>,
+}
+
+impl Transaction {
+    async fn begin(client: &Client) -> anyhow::Result<Self> {
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        Ok(Self {
+            con: client.get_async_connection().await?,
+            pipe,
+            queued: false,
+            todos: HashMap::new(),
+            watched: vec![],
+        })
+    }
+
+    /// Todo `id` of the workspace, as this transaction sees it.
+    async fn load(&mut self, workspace_id: i32, id: i32) -> anyhow::Result<TodoHash> {
+        if !self.todos.contains_key(&id) {
+            let key = todo_key(id);
+            redis::cmd("WATCH")
+                .arg(&key)
+                .query_async::<_, ()>(&mut self.con)
+                .await?;
+            self.watched.push(id);
+            let fields = self.con.hgetall(&key).await?;
+            self.todos.insert(id, TodoHash::from_fields(fields)?);
+        }
+        match &self.todos[&id] {
+            Some(hash) if hash.workspace_id == workspace_id => Ok(hash.clone()),
+            _ => Err(RepositoryError::NotFound(id).into()),
+        }
+    }
+
+    /// The todos listed under `key` that have not been deleted, ascending.
+    async fn load_listed(&mut self, key: &str) -> anyhow::Result<Vec<TodoHash>> {
+        let ids: BTreeSet<i32> = self.con.zrange(key, 0, -1).await?;
+        let mut hashes = Vec::with_capacity(ids.len());
+        for id in ids {
+            let workspace_id = match self.todos.get(&id) {
+                Some(Some(hash)) => hash.workspace_id,
+                Some(None) => continue,
+                None => {
+                    let fields = self.con.hgetall(todo_key(id)).await?;
+                    match TodoHash::from_fields(fields)? {
+                        Some(hash) => hash.workspace_id,
+                        None => continue,
+                    }
+                }
+            };
+            hashes.push(self.load(workspace_id, id).await?);
+        }
+        Ok(hashes)
+    }
+
+    async fn next_id(&mut self, counter: &str) -> anyhow::Result<i64> {
+        Ok(self.con.incr(counter, 1).await?)
+    }
+
+    fn save(&mut self, hash: &TodoHash) -> anyhow::Result<()> {
+        self.pipe
+            .hset_multiple(todo_key(hash.id), &hash.fields()?)
+            .ignore()
+            .zadd(workspace_key(hash.workspace_id), hash.id, hash.id)
+            .ignore()
+            .zadd(ALL_TODOS, hash.id, hash.id)
+            .ignore()
+            .hset(uuid_key(hash.workspace_id), hash.uuid.to_string(), hash.id)
+            .ignore();
+        self.queued = true;
+        self.todos.insert(hash.id, Some(hash.clone()));
+        Ok(())
+    }
+
+    fn remove(&mut self, hash: &TodoHash) {
+        self.pipe
+            .del(todo_key(hash.id))
+            .ignore()
+            .zrem(workspace_key(hash.workspace_id), hash.id)
+            .ignore()
+            .zrem(ALL_TODOS, hash.id)
+            .ignore()
+            .hdel(uuid_key(hash.workspace_id), hash.uuid.to_string())
+            .ignore();
+        self.queued = true;
+        self.todos.insert(hash.id, None);
+    }
+
+    /// Queues the outbox entries of `events`.
+    async fn record(&mut self, workspace_id: i32, events: Vec<DomainEvent>) -> anyhow::Result<()> {
+        for event in events {
+            let id = self.next_id(OUTBOX_IDS).await?;
+            let entry = serde_json::to_string(&WorkspaceEvent {
+                workspace_id,
+                event,
+            })?;
+            self.pipe
+                .hset(OUTBOX_ENTRIES, id, entry)
+                .ignore()
+                .zadd(OUTBOX, id, id)
+                .ignore();
+            self.queued = true;
+        }
+        Ok(())
+    }
+
+    async fn commit(mut self) -> anyhow::Result<()> {
+        if !self.queued {
+            redis::cmd("UNWATCH")
+                .query_async::<_, ()>(&mut self.con)
+                .await?;
+            return Ok(());
+        }
+        let done: Option<()> = self.pipe.query_async(&mut self.con).await?;
+        match (done, self.watched.first()) {
+            (Some(()), _) => Ok(()),
+            (None, Some(id)) => Err(RepositoryError::Conflict(*id).into()),
+            (None, None) => {
+                Err(RepositoryError::Unexpected("transaction aborted".to_string()).into())
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TodoRepositoryForRedis<L> {
+    client: Client,
+    /// Shared by reads; each write opens a connection for its WATCH.
+    connection: Arc<OnceCell<ConnectionManager>>,
+    labels: L,
+    workspace_id: i32,
+    filter: TodoFilter,
+    actor: Option<i32>,
+}
+
+impl<L: LabelRepository> TodoRepositoryForRedis<L> {
+    /// Connects on first use.
+    pub fn new(url: &str, labels: L) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: Client::open(url)?,
+            connection: Arc::new(OnceCell::new()),
+            labels,
+            workspace_id: DEFAULT_WORKSPACE_ID,
+            filter: TodoFilter::default(),
+            actor: None,
+        })
+    }
+
+    async fn connection(&self) -> anyhow::Result<ConnectionManager> {
+        Ok(self
+            .connection
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await?
+            .clone())
+    }
+
+    /// The workspace's labels by id.
+    async fn label_map(&self) -> anyhow::Result<HashMap<i32, Label>> {
+        self.labels_of([self.workspace_id]).await
+    }
+
+    /// The labels of each of `workspace_ids` by id.
+    async fn labels_of(
+        &self,
+        workspace_ids: impl IntoIterator<Item = i32>,
+    ) -> anyhow::Result<HashMap<i32, Label>> {
+        let mut labels = HashMap::new();
+        for workspace_id in workspace_ids.into_iter().collect::<BTreeSet<_>>() {
+            for label in self.labels.in_workspace(workspace_id).all().await? {
+                labels.insert(label.id, label);
+            }
+        }
+        Ok(labels)
+    }
+
+    /// The todos listed under `key`, ascending.
+    async fn listed(&self, key: &str) -> anyhow::Result<Vec<TodoHash>> {
+        let mut con = self.connection().await?;
+        let ids: Vec<i32> = con.zrange(key, 0, -1).await?;
+        load_many(&mut con, &ids).await
+    }
+
+    /// The workspace's todos, ascending.
+    async fn hashes(&self) -> anyhow::Result<Vec<TodoHash>> {
+        self.listed(&workspace_key(self.workspace_id)).await
+    }
+
+    async fn hash(&self, id: i32) -> anyhow::Result<TodoHash> {
+        let fields = self.connection().await?.hgetall(todo_key(id)).await?;
+        match TodoHash::from_fields(fields)? {
+            Some(hash) if hash.workspace_id == self.workspace_id => Ok(hash),
+            _ => Err(RepositoryError::NotFound(id).into()),
+        }
+    }
+
+    /// Todos of any workspace, with their workspace.
+    async fn with_workspaces(&self, hashes: &[TodoHash]) -> anyhow::Result<Vec<(i32, Todo)>> {
+        let labels = self
+            .labels_of(hashes.iter().map(|hash| hash.workspace_id))
+            .await?;
+        Ok(hashes
+            .iter()
+            .map(|hash| (hash.workspace_id, hash.todo(&labels)))
+            .collect())
+    }
+
+    async fn insert(
+        &self,
+        tx: &mut Transaction,
+        labels: &HashMap<i32, Label>,
+        payload: CreateTodo,
+    ) -> anyhow::Result<(Todo, Vec<DomainEvent>)> {
+        check_new_labels(labels, &payload.labels)?;
+        let id = tx.next_id(TODO_IDS).await? as i32;
+        let hash = TodoHash::new(id, self.workspace_id, payload);
+        tx.save(&hash)?;
+        let todo = hash.todo(labels);
+        let events = DomainEvent::created(&todo, self.actor);
+        Ok((todo, events))
+    }
+
+    async fn replace(
+        &self,
+        tx: &mut Transaction,
+        labels: &HashMap<i32, Label>,
+        id: i32,
+        expected: Option<&Todo>,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<(Todo, Vec<DomainEvent>)> {
+        if let Some(ids) = &payload.labels {
+            check_labels(labels, ids)?;
+        }
+        let mut hash = tx.load(self.workspace_id, id).await?;
+        let before = hash.todo(labels);
+        if expected.is_some_and(|expected| before != *expected) {
+            return Err(RepositoryError::Conflict(id).into());
+        }
+        hash.update(payload);
+        tx.save(&hash)?;
+        let after = hash.todo(labels);
+        let events = DomainEvent::changed(&before, &after, self.actor);
+        Ok((after, events))
+    }
+
+    async fn remove(&self, tx: &mut Transaction, id: i32) -> anyhow::Result<Vec<DomainEvent>> {
+        let hash = tx.load(self.workspace_id, id).await?;
+        tx.remove(&hash);
+        Ok(vec![DomainEvent::TodoDeleted(id)])
+    }
+
+    async fn update_checked(
+        &self,
+        id: i32,
+        expected: Option<&Todo>,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<Todo> {
+        let labels = self.label_map().await?;
+        let mut tx = Transaction::begin(&self.client).await?;
+        let (todo, events) = self
+            .replace(&mut tx, &labels, id, expected, payload)
+            .await?;
+        tx.record(self.workspace_id, events).await?;
+        tx.commit().await?;
+        Ok(todo)
+    }
+
+    /// Lets `change` rework todo `id`, recording it as changed.
+    async fn change<R: Send>(
+        &self,
+        id: i32,
+        change: impl FnOnce(&mut TodoHash) -> Result<R, RepositoryError> + Send,
+    ) -> anyhow::Result<(Todo, R)> {
+        let labels = self.label_map().await?;
+        let mut tx = Transaction::begin(&self.client).await?;
+        let mut hash = tx.load(self.workspace_id, id).await?;
+        let before = hash.todo(&labels);
+        let result = change(&mut hash)?;
+        tx.save(&hash)?;
+        let after = hash.todo(&labels);
+        let events = DomainEvent::changed(&before, &after, self.actor);
+        tx.record(self.workspace_id, events).await?;
+        tx.commit().await?;
+        Ok((after, result))
+    }
+}
+
+impl<L: LabelRepository> TodoScope for TodoRepositoryForRedis<L> {
+    fn in_workspace(&self, workspace_id: i32) -> Self {
+        Self {
+            workspace_id,
+            ..self.clone()
+        }
+    }
+}
+
+#[async_trait]
+impl<L: LabelRepository> TodoReader for TodoRepositoryForRedis<L> {
+    async fn find(&self, id: i32) -> anyhow::Result<Todo> {
+        let hash = self.hash(id).await?;
+        Ok(hash.todo(&self.label_map().await?))
+    }
+    fn with_filter(&self, filter: TodoFilter) -> Self {
+        Self {
+            filter,
+            ..self.clone()
+        }
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Todo>> {
+        let labels = self.label_map().await?;
+        let mut todos: Vec<Todo> = self
+            .hashes()
+            .await?
+            .iter()
+            .map(|hash| hash.todo(&labels))
+            .filter(|todo| self.filter.matches(todo))
+            .collect();
+        self.filter.sort(&mut todos);
+        Ok(todos)
+    }
+    async fn count(&self) -> anyhow::Result<i64> {
+        if self.filter == TodoFilter::default() {
+            let key = workspace_key(self.workspace_id);
+            return Ok(self.connection().await?.zcard(key).await?);
+        }
+        Ok(self.all().await?.len() as i64)
+    }
+    async fn exists(&self, id: i32) -> anyhow::Result<bool> {
+        let key = workspace_key(self.workspace_id);
+        let score: Option<f64> = self.connection().await?.zscore(key, id).await?;
+        Ok(score.is_some())
+    }
+    async fn id_of(&self, uuid: Uuid) -> anyhow::Result<Option<i32>> {
+        let key = uuid_key(self.workspace_id);
+        Ok(self.connection().await?.hget(key, uuid.to_string()).await?)
+    }
+    async fn find_open_duplicate(&self, text: &str) -> anyhow::Result<Option<Todo>> {
+        let text = normalize_text(text);
+        match self
+            .hashes()
+            .await?
+            .into_iter()
+            .find(|hash| !hash.completed && normalize_text(&hash.text) == text)
+        {
+            Some(hash) => Ok(Some(hash.todo(&self.label_map().await?))),
+            None => Ok(None),
+        }
+    }
+    /// Straight off the workspace's sorted set unless a filter applies.
+    async fn page(&self, cursor: PageCursor, limit: u32) -> anyhow::Result<TodoPage> {
+        if self.filter != TodoFilter::default() {
+            return Ok(TodoPage::slice(self.all().await?, cursor, limit));
+        }
+        let mut con = self.connection().await?;
+        let key = workspace_key(self.workspace_id);
+        let fetch = limit as isize + 1;
+        let ids: Vec<i32> = match cursor {
+            PageCursor::After(after) => {
+                let max = after.map_or("+inf".to_string(), |id| format!("({}", id));
+                con.zrevrangebyscore_limit(key, max, "-inf", 0, fetch)
+                    .await?
+            }
+            PageCursor::Before(before) => {
+                let min = before.map_or("-inf".to_string(), |id| format!("({}", id));
+                con.zrangebyscore_limit(key, min, "+inf", 0, fetch).await?
+            }
+        };
+        let labels = self.label_map().await?;
+        let todos = load_many(&mut con, &ids)
+            .await?
+            .iter()
+            .map(|hash| hash.todo(&labels))
+            .collect();
+        Ok(TodoPage::from_rows(todos, cursor, limit))
+    }
+    async fn revisions(&self, id: i32) -> anyhow::Result<Vec<TodoRevision>> {
+        Ok(self.hash(id).await?.revisions)
+    }
+    async fn count_open_owned(&self, owner_id: i32) -> anyhow::Result<i64> {
+        let open = self
+            .listed(ALL_TODOS)
+            .await?
+            .into_iter()
+            .filter(|hash| hash.owner_id == Some(owner_id) && !hash.completed)
+            .count();
+        Ok(open as i64)
+    }
+    async fn owned(&self, owner_id: i32) -> anyhow::Result<Vec<(i32, Todo)>> {
+        let owned: Vec<TodoHash> = self
+            .listed(ALL_TODOS)
+            .await?
+            .into_iter()
+            .filter(|hash| hash.owner_id == Some(owner_id))
+            .collect();
+        self.with_workspaces(&owned).await
+    }
+    async fn label_stats(&self) -> anyhow::Result<Vec<LabelStats>> {
+        let mut counts: HashMap<i32, i64> = HashMap::new();
+        for hash in self.hashes().await? {
+            for id in hash.labels {
+                *counts.entry(id).or_default() += 1;
+            }
+        }
+        let mut labels: Vec<Label> = self.label_map().await?.into_values().collect();
+        labels.sort_by_key(|label| label.id);
+        Ok(labels
+            .into_iter()
+            .map(|label| LabelStats {
+                todos: counts.get(&label.id).copied().unwrap_or_default(),
+                label,
+            })
+            .collect())
+    }
+    async fn suggest_labels(&self, search: &Search, limit: u32) -> anyhow::Result<Vec<LabelStats>> {
+        Ok(LabelStats::suggest(
+            self.label_stats().await?,
+            search,
+            limit,
+        ))
+    }
+    async fn checklist(&self, id: i32) -> anyhow::Result<Vec<ChecklistItem>> {
+        Ok(self.hash(id).await?.items)
+    }
+    async fn burndown(&self, from: NaiveDate, to: NaiveDate) -> anyhow::Result<Vec<BurndownDay>> {
+        let revisions: Vec<TodoRevision> = self
+            .hashes()
+            .await?
+            .into_iter()
+            .flat_map(|hash| hash.revisions)
+            .collect();
+        Ok(burndown::from_revisions(&revisions, from, to))
+    }
+    async fn board_positions(&self) -> anyhow::Result<Positions> {
+        Ok(self
+            .hashes()
+            .await?
+            .iter()
+            .filter_map(|hash| Some((hash.id, hash.position?)))
+            .collect())
+    }
+    async fn due_by(&self, user_id: i32, by: NaiveDate) -> anyhow::Result<Vec<(i32, Todo)>> {
+        let mut due: Vec<TodoHash> = self
+            .listed(ALL_TODOS)
+            .await?
+            .into_iter()
+            .filter(|hash| {
+                !hash.completed
+                    && hash.due_date.is_some_and(|due| due <= by)
+                    && hash.assignee_id.or(hash.owner_id) == Some(user_id)
+            })
+            .collect();
+        due.sort_by_key(|hash| (hash.due_date, hash.id));
+        self.with_workspaces(&due).await
+    }
+}
+
+#[async_trait]
+impl<L: LabelRepository> TodoWriter for TodoRepositoryForRedis<L> {
+    async fn create(&self, payload: CreateTodo) -> anyhow::Result<Todo> {
+        let labels = self.label_map().await?;
+        let mut tx = Transaction::begin(&self.client).await?;
+        let (todo, events) = self.insert(&mut tx, &labels, payload).await?;
+        tx.record(self.workspace_id, events).await?;
+        tx.commit().await?;
+        Ok(todo)
+    }
+    async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<Todo> {
+        self.update_checked(id, None, payload).await
+    }
+    async fn update_if(
+        &self,
+        id: i32,
+        expected: &Todo,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<Todo> {
+        self.update_checked(id, Some(expected), payload).await
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let mut tx = Transaction::begin(&self.client).await?;
+        let events = self.remove(&mut tx, id).await?;
+        tx.record(self.workspace_id, events).await?;
+        tx.commit().await
+    }
+    async fn revert(&self, id: i32, rev: i32) -> anyhow::Result<Todo> {
+        let revision = self
+            .revisions(id)
+            .await?
+            .into_iter()
+            .find(|revision| revision.rev == rev)
+            .ok_or(RepositoryError::NotFound(rev))?;
+        self.update(
+            id,
+            UpdateTodo {
+                text: Some(revision.text),
+                completed: Some(revision.completed),
+                estimate_minutes: Some(revision.estimate_minutes),
+                ..UpdateTodo::default()
+            },
+        )
+        .await
+    }
+    /// Deleted through the label repository, so a todo labelled meanwhile
+    /// may lose the label again.
+    async fn delete_unused_labels(&self) -> anyhow::Result<Vec<i32>> {
+        let used: HashSet<i32> = self
+            .hashes()
+            .await?
+            .into_iter()
+            .flat_map(|hash| hash.labels)
+            .collect();
+        let mut unused: Vec<i32> = self
+            .label_map()
+            .await?
+            .into_keys()
+            .filter(|id| !used.contains(id))
+            .collect();
+        unused.sort_unstable();
+        let labels = self.labels.in_workspace(self.workspace_id);
+        for id in &unused {
+            labels.delete(*id).await?;
+        }
+        Ok(unused)
+    }
+    async fn merge_labels(&self, source: i32, target: i32) -> anyhow::Result<Vec<Todo>> {
+        let labels = self.label_map().await?;
+        check_labels(&labels, &[source, target])?;
+        let mut tx = Transaction::begin(&self.client).await?;
+        let mut moved = Vec::new();
+        for mut hash in tx.load_listed(&workspace_key(self.workspace_id)).await? {
+            if !hash.labels.contains(&source) {
+                continue;
+            }
+            let before = hash.todo(&labels);
+            let ids: Vec<i32> = hash
+                .labels
+                .iter()
+                .map(|&id| if id == source { target } else { id })
+                .collect();
+            hash.labels = normalized(&ids);
+            tx.save(&hash)?;
+            let after = hash.todo(&labels);
+            let events = DomainEvent::changed(&before, &after, self.actor);
+            tx.record(self.workspace_id, events).await?;
+            moved.push(after);
+        }
+        tx.commit().await?;
+        self.labels
+            .in_workspace(self.workspace_id)
+            .delete(source)
+            .await?;
+        Ok(moved)
+    }
+    async fn snooze(&self, id: i32, until: Option<DateTime<Utc>>) -> anyhow::Result<Todo> {
+        let (todo, ()) = self
+            .change(id, |hash| {
+                hash.snoozed_until = until;
+                Ok(())
+            })
+            .await?;
+        Ok(todo)
+    }
+    async fn unsnooze_due(&self) -> anyhow::Result<Vec<(i32, i32)>> {
+        let now = Utc::now();
+        let mut tx = Transaction::begin(&self.client).await?;
+        let due: Vec<TodoHash> = tx
+            .load_listed(ALL_TODOS)
+            .await?
+            .into_iter()
+            .filter(|hash| hash.snoozed_until.is_some_and(|until| until <= now))
+            .collect();
+        let labels = self
+            .labels_of(due.iter().map(|hash| hash.workspace_id))
+            .await?;
+        let mut woken = Vec::with_capacity(due.len());
+        for mut hash in due {
+            hash.snoozed_until = None;
+            tx.save(&hash)?;
+            let event = DomainEvent::TodoUpdated(hash.todo(&labels));
+            tx.record(hash.workspace_id, vec![event]).await?;
+            woken.push((hash.workspace_id, hash.id));
+        }
+        woken.sort_unstable();
+        tx.commit().await?;
+        Ok(woken)
+    }
+    async fn add_item(
+        &self,
+        id: i32,
+        payload: CreateChecklistItem,
+    ) -> anyhow::Result<ChecklistItem> {
+        let mut con = self.connection().await?;
+        let item = ChecklistItem {
+            id: con.incr(ITEM_IDS, 1).await?,
+            text: payload.text,
+            completed: false,
+            position: 0,
+        };
+        let (_, item) = self
+            .change(id, |hash| {
+                Ok(checklist::insert(&mut hash.items, item, payload.position))
+            })
+            .await?;
+        Ok(item)
+    }
+    async fn update_item(
+        &self,
+        id: i32,
+        item_id: i32,
+        payload: UpdateChecklistItem,
+    ) -> anyhow::Result<ChecklistItem> {
+        let (_, item) = self
+            .change(id, |hash| {
+                checklist::update(&mut hash.items, item_id, payload)
+            })
+            .await?;
+        Ok(item)
+    }
+    async fn delete_item(&self, id: i32, item_id: i32) -> anyhow::Result<()> {
+        self.change(id, |hash| checklist::remove(&mut hash.items, item_id))
+            .await?;
+        Ok(())
+    }
+    async fn move_on_board(&self, id: i32, completed: bool, position: u32) -> anyhow::Result<Todo> {
+        let labels = self.label_map().await?;
+        let mut tx = Transaction::begin(&self.client).await?;
+        let mut hash = tx.load(self.workspace_id, id).await?;
+        if hash.completed != completed {
+            let before = hash.todo(&labels);
+            hash.completed = completed;
+            hash.push_revision();
+            tx.save(&hash)?;
+            let events = DomainEvent::changed(&before, &hash.todo(&labels), self.actor);
+            tx.record(self.workspace_id, events).await?;
+        }
+        let column: Vec<TodoHash> = tx
+            .load_listed(&workspace_key(self.workspace_id))
+            .await?
+            .into_iter()
+            .filter(|other| other.completed == completed)
+            .collect();
+        let mut by_id: HashMap<i32, TodoHash> = column
+            .iter()
+            .map(|other| (other.id, other.clone()))
+            .collect();
+        let order = board::reorder(
+            column
+                .iter()
+                .map(|other| (other.id, other.position))
+                .collect(),
+            id,
+            position,
+        );
+        for (id, position) in order.into_iter().zip(0..) {
+            if let Some(mut other) = by_id.remove(&id) {
+                other.position = Some(position);
+                tx.save(&other)?;
+            }
+        }
+        tx.commit().await?;
+        Ok(hash.todo(&labels))
+    }
+    async fn release_owned(
+        &self,
+        owner_id: i32,
+        release: OwnedTodos,
+    ) -> anyhow::Result<Vec<(i32, i32)>> {
+        let mut tx = Transaction::begin(&self.client).await?;
+        let mut owned: Vec<TodoHash> = tx
+            .load_listed(ALL_TODOS)
+            .await?
+            .into_iter()
+            .filter(|hash| hash.owner_id == Some(owner_id))
+            .collect();
+        owned.sort_by_key(|hash| (hash.workspace_id, hash.id));
+        let labels = self
+            .labels_of(owned.iter().map(|hash| hash.workspace_id))
+            .await?;
+        let mut released = Vec::with_capacity(owned.len());
+        for mut hash in owned {
+            let event = match release {
+                OwnedTodos::Delete => {
+                    tx.remove(&hash);
+                    DomainEvent::TodoDeleted(hash.id)
+                }
+                OwnedTodos::TransferTo(new_owner) => {
+                    hash.owner_id = Some(new_owner);
+                    tx.save(&hash)?;
+                    DomainEvent::TodoUpdated(hash.todo(&labels))
+                }
+            };
+            tx.record(hash.workspace_id, vec![event]).await?;
+            released.push((hash.workspace_id, hash.id));
+        }
+        tx.commit().await?;
+        Ok(released)
+    }
+    async fn apply(&self, changes: Vec<TodoChange>) -> anyhow::Result<Vec<Option<Todo>>> {
+        let labels = self.label_map().await?;
+        let mut tx = Transaction::begin(&self.client).await?;
+        let mut changed = Vec::with_capacity(changes.len());
+        let mut events = Vec::new();
+        for change in changes {
+            let (todo, more) = match change {
+                TodoChange::Create(payload) => {
+                    let (todo, events) = self.insert(&mut tx, &labels, payload).await?;
+                    (Some(todo), events)
+                }
+                TodoChange::Update(id, payload) => {
+                    let (todo, events) = self.replace(&mut tx, &labels, id, None, payload).await?;
+                    (Some(todo), events)
+                }
+                TodoChange::Delete(id) => (None, self.remove(&mut tx, id).await?),
+            };
+            changed.push(todo);
+            events.extend(more);
+        }
+        tx.record(self.workspace_id, events).await?;
+        tx.commit().await?;
+        Ok(changed)
+    }
+    fn acting_as(&self, user_id: Option<i32>) -> Self {
+        Self {
+            actor: user_id,
+            ..self.clone()
+        }
+    }
+    async fn claim_outbox(&self, limit: u32) -> anyhow::Result<Vec<OutboxEntry>> {
+        let claimed: Vec<(i64, String)> = Script::new(CLAIM_OUTBOX)
+            .key(OUTBOX)
+            .key(OUTBOX_ENTRIES)
+            .key(OUTBOX_CLAIMS)
+            .arg(Utc::now().timestamp_millis())
+            .arg(Duration::seconds(LEASE_SECS).num_milliseconds())
+            .arg(limit)
+            .invoke_async(&mut self.connection().await?)
+            .await?;
+        claimed
+            .into_iter()
+            .map(|(id, entry)| {
+                Ok(OutboxEntry {
+                    id,
+                    event: serde_json::from_str(&entry)?,
+                })
+            })
+            .collect()
+    }
+    async fn mark_sent(&self, ids: &[i64]) -> anyhow::Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        redis::pipe()
+            .atomic()
+            .zrem(OUTBOX, ids)
+            .ignore()
+            .hdel(OUTBOX_ENTRIES, ids)
+            .ignore()
+            .hdel(OUTBOX_CLAIMS, ids)
+            .ignore()
+            .query_async::<_, ()>(&mut self.connection().await?)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_keep_todos_as_hashes() {
+        let mut hash = TodoHash::new(
+            7,
+            2,
+            CreateTodo {
+                labels: vec![3, 1, 3, 9],
+                owner_id: Some(5),
+                ..CreateTodo::new("pack".to_string())
+            },
+        );
+        assert_eq!(hash.labels, vec![1, 3, 9]);
+        let fields: HashMap<String, String> = hash.fields().unwrap().into_iter().collect();
+        assert_eq!(fields["text"], r#""pack""#);
+        assert_eq!(fields["owner_id"], "5");
+        assert_eq!(TodoHash::from_fields(fields).unwrap(), Some(hash.clone()));
+        assert_eq!(TodoHash::from_fields(HashMap::new()).unwrap(), None);
+
+        hash.update(UpdateTodo {
+            completed: Some(true),
+            ..UpdateTodo::default()
+        });
+        hash.items.push(ChecklistItem {
+            id: 1,
+            text: "bag".to_string(),
+            completed: true,
+            position: 0,
+        });
+        assert_eq!(hash.revisions.len(), 2);
+
+        // label 9 was deleted since, and drops out like a dangling join
+        let labels = HashMap::from([
+            (1, Label::new(1, "home".to_string())),
+            (3, Label::new(3, "work".to_string())),
+        ]);
+        let todo = hash.todo(&labels);
+        assert_eq!(todo.uuid, hash.uuid);
+        assert_eq!(todo.labels, vec![labels[&1].clone(), labels[&3].clone()]);
+        assert_eq!(todo.progress, Progress { done: 1, total: 1 });
+    }
+}
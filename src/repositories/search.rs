@@ -0,0 +1,159 @@
+use std::{collections::HashSet, ops::Range};
+
+/// Fuzzy search over todo text, forgiving typos: `grocries` finds
+/// `Buy groceries`. Todos rank by how close their best run of whole words
+/// comes to `text`, as Postgres' `pg_trgm` computes it with
+/// `strict_word_similarity`, and those below `threshold` are left out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Search {
+    pub text: String,
+    /// Between 0 and 1; 1 only lets exact words through.
+    pub threshold: f32,
+}
+
+impl Search {
+    /// How well `text` matches, from 0 to 1.
+    pub fn score(&self, text: &str) -> f32 {
+        strict_word_similarity(&self.text, text)
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        self.score(text) >= self.threshold
+    }
+
+    /// `text` with the run of words that matched best between `<mark>` and
+    /// `</mark>`, as `ts_headline` marks what matched a text search.
+    pub fn highlight(&self, text: &str) -> String {
+        match best_extent(&self.text, text) {
+            Some((_, range)) => format!(
+                "{}{}{}{}{}",
+                &text[..range.start],
+                HIGHLIGHT_START,
+                &text[range.clone()],
+                HIGHLIGHT_END,
+                &text[range.end..]
+            ),
+            None => text.to_string(),
+        }
+    }
+}
+
+const HIGHLIGHT_START: &str = "<mark>";
+const HIGHLIGHT_END: &str = "</mark>";
+
+/// Where the runs of letters and digits in `text`, the words `pg_trgm`
+/// sees, are.
+fn words(text: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (at, c) in text.char_indices() {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(at),
+            (false, Some(from)) => {
+                words.push(from..at);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    words.extend(start.map(|from| from..text.len()));
+    words
+}
+
+/// Every three characters of `word` padded with two spaces in front and one
+/// behind, so `cat` gives `  c`, ` ca`, `cat` and `at `.
+fn trigrams(word: &str) -> HashSet<[char; 3]> {
+    let padded: Vec<char> = format!("  {} ", word).chars().collect();
+    padded
+        .windows(3)
+        .map(|window| [window[0], window[1], window[2]])
+        .collect()
+}
+
+fn similarity(a: &HashSet<[char; 3]>, b: &HashSet<[char; 3]>) -> f32 {
+    let shared = a.intersection(b).count();
+    let all = a.len() + b.len() - shared;
+    if all == 0 {
+        0.0
+    } else {
+        shared as f32 / all as f32
+    }
+}
+
+/// The greatest similarity between the trigrams of `query` and those of
+/// any run of consecutive words in `text`.
+pub fn strict_word_similarity(query: &str, text: &str) -> f32 {
+    best_extent(query, text).map_or(0.0, |(similarity, _)| similarity)
+}
+
+/// The shortest, first run of words in `text` most similar to `query`, and
+/// how similar; `None` when no word shares a trigram with it.
+fn best_extent(query: &str, text: &str) -> Option<(f32, Range<usize>)> {
+    let query: HashSet<_> = words(query)
+        .into_iter()
+        .flat_map(|word| trigrams(&query[word].to_lowercase()))
+        .collect();
+    let spans = words(text);
+    let words: Vec<_> = spans
+        .iter()
+        .map(|span| trigrams(&text[span.clone()].to_lowercase()))
+        .collect();
+    let mut best: Option<(f32, Range<usize>)> = None;
+    for start in 0..words.len() {
+        let mut extent = HashSet::new();
+        for end in start..words.len() {
+            extent.extend(&words[end]);
+            let score = similarity(&query, &extent);
+            if score > best.as_ref().map_or(0.0, |(best, _)| *best) {
+                best = Some((score, spans[start].start..spans[end].end));
+            }
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_score_like_pg_trgm() {
+        assert_eq!(trigrams("cat").len(), 4);
+        assert_eq!(strict_word_similarity("groceries", "Buy GROCERIES!"), 1.0);
+        // 7 of the 12 trigrams of either word are shared
+        assert_eq!(
+            strict_word_similarity("grocries", "buy groceries"),
+            7.0 / 12.0
+        );
+        assert_eq!(strict_word_similarity("milk", "buy bread"), 0.0);
+        assert_eq!(strict_word_similarity("", "buy bread"), 0.0);
+
+        let search = Search {
+            text: "grocries".to_string(),
+            threshold: 0.5,
+        };
+        assert!(search.matches("Buy groceries and milk"));
+        assert!(!search.matches("Call the grocer"));
+    }
+
+    #[test]
+    fn should_highlight_best_matching_words() {
+        let search = |text: &str| Search {
+            text: text.to_string(),
+            threshold: 0.5,
+        };
+        assert_eq!(
+            search("grocries").highlight("Buy groceries, milk"),
+            "Buy <mark>groceries</mark>, milk"
+        );
+        assert_eq!(
+            search("milk eggs").highlight("buy milk & eggs!"),
+            "buy <mark>milk & eggs</mark>!"
+        );
+        assert_eq!(
+            search("tax").highlight("日本語 taxes"),
+            "日本語 <mark>taxes</mark>"
+        );
+        assert_eq!(search("milk").highlight("buy bread"), "buy bread");
+    }
+}
@@ -0,0 +1,66 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::{Validate, ValidationError};
+
+/// Where Slack notifications of a user's todos in a workspace go, and which
+/// Slack user may add todos there with the `/todo` command.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow)]
+pub struct SlackLink {
+    pub workspace_id: i32,
+    pub user_id: i32,
+    /// An incoming webhook of the user's Slack app.
+    pub webhook_url: String,
+    pub team_id: Option<String>,
+    pub slack_user_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Body of `PUT /slack/link`. Without `team_id` and `slack_user_id` the
+/// link only receives notifications.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+pub struct SetSlackLink {
+    #[validate(length(max = 500, message = "can not be over 500"))]
+    #[validate(custom = "validate_webhook_url")]
+    pub webhook_url: String,
+    #[serde(default)]
+    #[validate(length(min = 1, max = 50, message = "must be 1 to 50 characters"))]
+    pub team_id: Option<String>,
+    #[serde(default)]
+    #[validate(length(min = 1, max = 50, message = "must be 1 to 50 characters"))]
+    pub slack_user_id: Option<String>,
+}
+
+pub const WEBHOOK_URL_PREFIX: &str = "https://hooks.slack.com/";
+
+/// Only Slack's own hosts, so a link can not make the server post elsewhere.
+fn validate_webhook_url(url: &str) -> Result<(), ValidationError> {
+    if url.starts_with(WEBHOOK_URL_PREFIX) {
+        return Ok(());
+    }
+    let mut error = ValidationError::new("webhook_url");
+    error.message = Some(format!("must start with {}", WEBHOOK_URL_PREFIX).into());
+    Err(error)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_accept_slack_webhooks_only() {
+        let link = |url: &str| SetSlackLink {
+            webhook_url: url.to_string(),
+            team_id: None,
+            slack_user_id: None,
+        };
+
+        assert!(link("https://hooks.slack.com/services/T0/B0/x")
+            .validate()
+            .is_ok());
+        let errors = link("https://example.com/hooks.slack.com/")
+            .validate()
+            .unwrap_err();
+        assert!(errors.field_errors().contains_key("webhook_url"));
+    }
+}
@@ -0,0 +1,22 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// A Telegram chat whose commands act for a user in one workspace.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow)]
+pub struct TelegramLink {
+    pub chat_id: i64,
+    pub workspace_id: i32,
+    pub user_id: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A one-time code linking whichever chat sends it first; only its hash is
+/// stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewTelegramCode {
+    pub code_hash: String,
+    pub workspace_id: i32,
+    pub user_id: i32,
+    pub expires_at: DateTime<Utc>,
+}
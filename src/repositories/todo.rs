@@ -1,291 +1,3996 @@
 use std::{
-    collections::HashMap,
-    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    collections::{BTreeMap, HashMap, HashSet},
+    io,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
 };
 
 use anyhow::Context;
+use async_graphql::SimpleObject;
 use axum::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool};
-use validator::Validate;
+use serde_json::Value;
+use sqlx::{
+    postgres::PgArguments, query::QueryAs, types::Json, Executor, FromRow, PgPool, Postgres,
+    Transaction,
+};
+use tokio::sync::Notify;
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+use super::{
+    board::{self, Positions},
+    burndown::{self, BurndownDay},
+    checklist::{self, ChecklistItem, CreateChecklistItem, Progress, UpdateChecklistItem},
+    filter::{FilterExpr, SqlValue},
+    label::{
+        like_prefix, Label, LabelRepository, LabelRepositoryForMemory, LabelSnapshot, LabelStats,
+        LabelStatsRow,
+    },
+    search::Search,
+    workspace::DEFAULT_WORKSPACE_ID,
+    RepositoryError,
+};
+use crate::{
+    database::{DbUuid, Redact, SlowQueryLog},
+    events::{DomainEvent, WorkspaceEvent},
+    outbox::{OutboxEntry, LEASE_SECS},
+};
 
-use super::RepositoryError;
+/// What readers and writers share: scoping to a workspace.
+pub trait TodoScope: Clone + std::marker::Send + std::marker::Sync + 'static {
+    /// The same store, restricted to one workspace's todos.
+    fn in_workspace(&self, workspace_id: i32) -> Self;
+}
 
+/// The queries; may be served by a read replica or a cache that lags
+/// behind `TodoWriter`.
 #[async_trait]
-pub trait TodoRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
-    async fn create(&self, payload: CreateTodo) -> anyhow::Result<Todo>;
+pub trait TodoReader: TodoScope {
     async fn find(&self, id: i32) -> anyhow::Result<Todo>;
+    /// The same store, with listings narrowed by `filter`.
+    fn with_filter(&self, filter: TodoFilter) -> Self;
     async fn all(&self) -> anyhow::Result<Vec<Todo>>;
+    async fn count(&self) -> anyhow::Result<i64>;
+    async fn exists(&self, id: i32) -> anyhow::Result<bool>;
+    /// The id of the workspace's todo whose `uuid` this is, if there is one.
+    async fn id_of(&self, uuid: Uuid) -> anyhow::Result<Option<i32>>;
+    /// The oldest incomplete todo whose text equals `text` once both are
+    /// normalized with `normalize_text`; ignores the filter.
+    async fn find_open_duplicate(&self, text: &str) -> anyhow::Result<Option<Todo>>;
+    /// Up to `limit` todos next to `cursor`, newest first, fetching one row
+    /// past `limit` to tell whether another page exists.
+    async fn page(&self, cursor: PageCursor, limit: u32) -> anyhow::Result<TodoPage>;
+    async fn revisions(&self, id: i32) -> anyhow::Result<Vec<TodoRevision>>;
+    /// Incomplete todos `owner_id` created, in all workspaces.
+    async fn count_open_owned(&self, owner_id: i32) -> anyhow::Result<i64>;
+    /// Every todo `owner_id` created, in all workspaces, with the workspace
+    /// it lives in; oldest first.
+    async fn owned(&self, owner_id: i32) -> anyhow::Result<Vec<(i32, Todo)>>;
+    /// Every label of the workspace with how many of its todos carry it, by
+    /// id; ignores the filter.
+    async fn label_stats(&self) -> anyhow::Result<Vec<LabelStats>>;
+    /// Up to `limit` labels whose name starts with the query or matches it
+    /// fuzzily, in the order `LabelStats::suggest` gives.
+    async fn suggest_labels(&self, search: &Search, limit: u32) -> anyhow::Result<Vec<LabelStats>>;
+    /// The checklist of todo `id`, in order.
+    async fn checklist(&self, id: i32) -> anyhow::Result<Vec<ChecklistItem>>;
+    /// Estimated minutes left on the workspace's open todos at the end of
+    /// each day from `from` to `to`, by their revisions; ignores the filter.
+    /// Deleted todos take their history with them.
+    async fn burndown(&self, from: NaiveDate, to: NaiveDate) -> anyhow::Result<Vec<BurndownDay>>;
+    /// Where the workspace's todos sit within their board column, for
+    /// `board::sort`; ignores the filter.
+    async fn board_positions(&self) -> anyhow::Result<Positions>;
+    /// Open todos assigned to `user_id`, or created by them and assigned to
+    /// no one, due on or before `by`, in all workspaces, with the workspace
+    /// each lives in; soonest first, then by id.
+    async fn due_by(&self, user_id: i32, by: NaiveDate) -> anyhow::Result<Vec<(i32, Todo)>>;
+}
+
+/// The changes, together with the outbox they record their events in.
+#[async_trait]
+pub trait TodoWriter: TodoScope {
+    async fn create(&self, payload: CreateTodo) -> anyhow::Result<Todo>;
     async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<Todo>;
+    /// Like `update`, but fails with `Conflict` unless the todo still equals
+    /// `expected`; check and write happen atomically.
+    async fn update_if(
+        &self,
+        id: i32,
+        expected: &Todo,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<Todo>;
     async fn delete(&self, id: i32) -> anyhow::Result<()>;
+    async fn revert(&self, id: i32, rev: i32) -> anyhow::Result<Todo>;
+    /// Deletes the workspace's labels no todo carries, returning their ids.
+    /// Todos hold the links, so the check and the delete happen here.
+    async fn delete_unused_labels(&self) -> anyhow::Result<Vec<i32>>;
+    /// Moves every todo carrying label `source` over to `target` and deletes
+    /// `source`, all at once; returns the todos moved, as they are now.
+    async fn merge_labels(&self, source: i32, target: i32) -> anyhow::Result<Vec<Todo>>;
+    /// Hides the todo from listings that leave snoozed todos out until
+    /// `until`; `None` wakes it.
+    async fn snooze(&self, id: i32, until: Option<DateTime<Utc>>) -> anyhow::Result<Todo>;
+    /// Wakes the todos of every workspace whose snooze is over, returning
+    /// the workspace and id of each.
+    async fn unsnooze_due(&self) -> anyhow::Result<Vec<(i32, i32)>>;
+    /// Adds an item to the checklist of todo `id`. Checklist changes count
+    /// as changes of the todo, whose `progress` they move.
+    async fn add_item(
+        &self,
+        id: i32,
+        payload: CreateChecklistItem,
+    ) -> anyhow::Result<ChecklistItem>;
+    async fn update_item(
+        &self,
+        id: i32,
+        item_id: i32,
+        payload: UpdateChecklistItem,
+    ) -> anyhow::Result<ChecklistItem>;
+    async fn delete_item(&self, id: i32, item_id: i32) -> anyhow::Result<()>;
+    /// Puts todo `id` at `position` of the board column of todos whose
+    /// `completed` is given, as `board::reorder` does, completing or
+    /// reopening it on the way; all at once.
+    async fn move_on_board(&self, id: i32, completed: bool, position: u32) -> anyhow::Result<Todo>;
+    /// Deletes or hands over every todo `owner_id` created, in all
+    /// workspaces, returning the (workspace id, todo id) of each one touched.
+    async fn release_owned(
+        &self,
+        owner_id: i32,
+        release: OwnedTodos,
+    ) -> anyhow::Result<Vec<(i32, i32)>>;
+    /// Makes `changes` in order, all in one transaction: when one fails,
+    /// none of them stay. Returns each todo as its change left it, `None`
+    /// for deleted ones.
+    async fn apply(&self, changes: Vec<TodoChange>) -> anyhow::Result<Vec<Option<Todo>>>;
+    /// Changes made through the returned repository name `user_id` as the
+    /// one who made them in the events they record.
+    fn acting_as(&self, user_id: Option<i32>) -> Self;
+    /// Up to `limit` unsent events of every workspace, oldest first, hidden
+    /// from further claims for `LEASE_SECS`.
+    async fn claim_outbox(&self, limit: u32) -> anyhow::Result<Vec<OutboxEntry>>;
+    async fn mark_sent(&self, ids: &[i64]) -> anyhow::Result<()>;
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow)]
+/// Both capabilities, as the app state holds them; see
+/// `SplitTodoRepository` for serving them from different stores.
+pub trait TodoRepository: TodoReader + TodoWriter {}
+
+impl<T: TodoReader + TodoWriter> TodoRepository for T {}
+
+/// What becomes of a deleted user's todos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OwnedTodos {
+    Delete,
+    TransferTo(i32),
+}
+
+/// One of the changes `TodoWriter::apply` makes together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TodoChange {
+    Create(CreateTodo),
+    Update(i32, UpdateTodo),
+    Delete(i32),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, SimpleObject)]
 pub struct Todo {
-    id: i32,
-    text: String,
-    completed: bool,
+    pub id: i32,
+    /// The id paths take under `features.uuid_ids`; nil until stored.
+    #[serde(default, skip_serializing_if = "Uuid::is_nil")]
+    pub uuid: Uuid,
+    pub text: String,
+    pub completed: bool,
+    pub labels: Vec<Label>,
+    /// The user who created the todo; unset for anonymous ones.
+    pub owner_id: Option<i32>,
+    pub assignee_id: Option<i32>,
+    /// `text` in other languages, keyed by language tag such as `ja` or
+    /// `en-US`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub text_i18n: BTreeMap<String, String>,
+    /// Left out of listings that hide snoozed todos until then.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snoozed_until: Option<DateTime<Utc>>,
+    /// How long the todo is expected to take.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate_minutes: Option<i32>,
+    /// The day the todo should be done by.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due_date: Option<NaiveDate>,
+    /// Long-form notes in Markdown, next to the short `text`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Values of the workspace's custom fields, keyed by field name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub custom: BTreeMap<String, Value>,
+    /// How far the checklist got; left out without one.
+    #[serde(default, skip_serializing_if = "Progress::is_empty")]
+    pub progress: Progress,
+}
+
+/// Narrows listings (`all`, `page` and `count`); lookups by id ignore it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TodoFilter {
+    pub assignee_id: Option<i32>,
+    /// A filter expression, with `assignee:me` resolved.
+    pub expr: Option<FilterExpr>,
+    /// Keeps todos whose text matches and lists the best matches first.
+    pub search: Option<Search>,
+    /// Leaves out todos snoozed until later.
+    pub hide_snoozed: bool,
+}
+
+impl TodoFilter {
+    pub(super) fn matches(&self, todo: &Todo) -> bool {
+        self.assignee_id
+            .is_none_or(|assignee_id| todo.assignee_id == Some(assignee_id))
+            && self.expr.as_ref().is_none_or(|expr| expr.matches(todo))
+            && self
+                .search
+                .as_ref()
+                .is_none_or(|search| search.matches(&todo.text))
+            && !(self.hide_snoozed && todo.is_snoozed(Utc::now()))
+    }
+
+    /// Puts matching `todos` in listing order: newest first, or best
+    /// matches first when searching.
+    pub(super) fn sort(&self, todos: &mut [Todo]) {
+        match &self.search {
+            Some(search) => todos.sort_by(|a, b| {
+                search
+                    .score(&b.text)
+                    .total_cmp(&search.score(&a.text))
+                    .then(b.id.cmp(&a.id))
+            }),
+            None => todos.sort_by_key(|todo| std::cmp::Reverse(todo.id)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TodoPage {
+    pub items: Vec<Todo>,
+    /// Id of the last item when older todos follow.
+    pub next: Option<i32>,
+    /// Id of the first item when newer todos come before it.
+    pub prev: Option<i32>,
+}
+
+/// Where a page of todos sits in the newest-first listing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageCursor {
+    /// Todos with an id below the given one; the first page without one.
+    After(Option<i32>),
+    /// Todos with an id above the given one; the last page without one.
+    Before(Option<i32>),
+}
+
+impl Default for PageCursor {
+    fn default() -> Self {
+        Self::After(None)
+    }
+}
+
+impl TodoPage {
+    /// `rows` were fetched in the cursor's direction: newest first after a
+    /// cursor, oldest first before one.
+    pub(super) fn from_rows(mut items: Vec<Todo>, cursor: PageCursor, limit: u32) -> Self {
+        let more = items.len() > limit as usize;
+        items.truncate(limit as usize);
+        let (next, prev) = match cursor {
+            PageCursor::After(after) => {
+                let next = more.then(|| items.last().map(|todo| todo.id)).flatten();
+                let prev = after.and(items.first().map(|todo| todo.id));
+                (next, prev)
+            }
+            PageCursor::Before(before) => {
+                items.reverse();
+                let next = before.and(items.last().map(|todo| todo.id));
+                let prev = more.then(|| items.first().map(|todo| todo.id)).flatten();
+                (next, prev)
+            }
+        };
+        Self { items, next, prev }
+    }
+
+    /// One page out of every matching todo, in listing order. Pages start
+    /// next to the cursor todo, or by id when it left the listing.
+    pub(super) fn slice(todos: Vec<Todo>, cursor: PageCursor, limit: u32) -> Self {
+        let position = |id: i32| todos.iter().position(|todo| todo.id == id);
+        let rows = match cursor {
+            PageCursor::After(after) => {
+                let skip = after.map_or(0, |after| {
+                    position(after).map_or_else(
+                        || todos.iter().take_while(|todo| todo.id >= after).count(),
+                        |at| at + 1,
+                    )
+                });
+                todos
+                    .into_iter()
+                    .skip(skip)
+                    .take(limit as usize + 1)
+                    .collect()
+            }
+            PageCursor::Before(before) => {
+                let keep = before.map_or(todos.len(), |before| {
+                    position(before)
+                        .unwrap_or_else(|| todos.iter().take_while(|todo| todo.id > before).count())
+                });
+                let mut todos = todos;
+                todos.truncate(keep);
+                todos.into_iter().rev().take(limit as usize + 1).collect()
+            }
+        };
+        Self::from_rows(rows, cursor, limit)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow)]
+pub struct TodoRevision {
+    pub todo_id: i32,
+    pub rev: i32,
+    pub text: String,
+    pub completed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate_minutes: Option<i32>,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
 pub struct CreateTodo {
     #[validate(length(min = 1, message = "can not be empty"))]
     #[validate(length(max = 100, message = "can not be over 100"))]
-    text: String,
+    pub text: String,
+    /// Ids of the labels to attach.
+    #[serde(default)]
+    pub labels: Vec<i32>,
+    #[serde(default)]
+    pub assignee_id: Option<i32>,
+    #[serde(default)]
+    #[validate(custom = "validate_text_i18n")]
+    pub text_i18n: BTreeMap<String, String>,
+    #[serde(default)]
+    #[validate(range(min = 1, max = 100000, message = "must be between 1 and 100000"))]
+    pub estimate_minutes: Option<i32>,
+    #[serde(default)]
+    pub due_date: Option<NaiveDate>,
+    /// Markdown; clients may have it rendered with `?render=html`.
+    #[serde(default)]
+    #[validate(length(max = 10000, message = "can not be over 10000"))]
+    pub description: Option<String>,
+    /// Values of the workspace's custom fields, checked against their
+    /// definitions by the handlers.
+    #[serde(default)]
+    pub custom: BTreeMap<String, Value>,
+    /// Set by the server from the caller, never read from the body.
+    #[serde(skip)]
+    pub owner_id: Option<i32>,
+}
+
+/// `text` lowercased, trimmed and with runs of whitespace collapsed to one
+/// space, so `Buy  milk ` and `buy milk` count as the same todo.
+pub fn normalize_text(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
 }
 
-#[cfg(test)]
 impl CreateTodo {
     pub fn new(text: String) -> Self {
-        Self { text }
+        Self {
+            text,
+            labels: vec![],
+            assignee_id: None,
+            text_i18n: BTreeMap::new(),
+            estimate_minutes: None,
+            due_date: None,
+            description: None,
+            custom: BTreeMap::new(),
+            owner_id: None,
+        }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq, Validate)]
 pub struct UpdateTodo {
     #[validate(length(min = 1, message = "can not be empty"))]
     #[validate(length(max = 100, message = "can not be over 100"))]
-    text: Option<String>,
-    completed: Option<bool>,
+    pub text: Option<String>,
+    pub completed: Option<bool>,
+    /// Replaces every attached label when given.
+    #[serde(default)]
+    pub labels: Option<Vec<i32>>,
+    /// `Some(None)` unassigns; a JSON `null` counts as absent.
+    #[serde(default)]
+    pub assignee_id: Option<Option<i32>>,
+    /// Replaces every translation when given.
+    #[serde(default)]
+    #[validate(custom = "validate_text_i18n")]
+    pub text_i18n: Option<BTreeMap<String, String>>,
+    /// `Some(None)` drops the estimate, like `assignee_id`.
+    #[serde(default)]
+    #[validate(range(min = 1, max = 100000, message = "must be between 1 and 100000"))]
+    pub estimate_minutes: Option<Option<i32>>,
+    /// `Some(None)` drops the due date.
+    #[serde(default)]
+    pub due_date: Option<Option<NaiveDate>>,
+    /// `Some(None)` drops the description.
+    #[serde(default)]
+    #[validate(length(max = 10000, message = "can not be over 10000"))]
+    pub description: Option<Option<String>>,
+    /// Sets the custom fields given; a `null` value removes one, and the
+    /// others keep their value.
+    #[serde(default)]
+    pub custom: Option<BTreeMap<String, Option<Value>>>,
+}
+
+impl UpdateTodo {
+    /// The custom values of a todo holding `current` once this applies.
+    pub fn merged_custom(&self, current: &BTreeMap<String, Value>) -> BTreeMap<String, Value> {
+        let mut custom = current.clone();
+        if let Some(changes) = &self.custom {
+            merge_custom(&mut custom, changes);
+        }
+        custom
+    }
+}
+
+/// Sets the `Some` values of `changes` and removes the `None` ones.
+pub fn merge_custom(
+    custom: &mut BTreeMap<String, Value>,
+    changes: &BTreeMap<String, Option<Value>>,
+) {
+    for (name, value) in changes {
+        match value {
+            Some(value) => custom.insert(name.clone(), value.clone()),
+            None => custom.remove(name),
+        };
+    }
+}
+
+/// Translations follow the rules of `text`, under a tag of letters, digits
+/// and hyphens.
+fn validate_text_i18n(texts: &BTreeMap<String, String>) -> Result<(), ValidationError> {
+    for (tag, text) in texts {
+        let valid_tag = !tag.is_empty()
+            && tag.len() <= 35
+            && tag
+                .split('-')
+                .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()));
+        let problem = if !valid_tag {
+            format!("invalid language tag [{}]", tag)
+        } else if text.is_empty() {
+            format!("[{}] can not be empty", tag)
+        } else if text.chars().count() > 100 {
+            format!("[{}] can not be over 100", tag)
+        } else {
+            continue;
+        };
+        let mut error = ValidationError::new("text_i18n");
+        error.message = Some(problem.into());
+        return Err(error);
+    }
+
+    Ok(())
 }
 
 impl Todo {
     pub fn new(id: i32, text: String) -> Self {
         Self {
             id,
+            uuid: Uuid::nil(),
             text,
             completed: false,
+            labels: vec![],
+            owner_id: None,
+            assignee_id: None,
+            text_i18n: BTreeMap::new(),
+            snoozed_until: None,
+            estimate_minutes: None,
+            due_date: None,
+            description: None,
+            custom: BTreeMap::new(),
+            progress: Progress::default(),
+        }
+    }
+
+    /// Whether the todo is still snoozed at `now`.
+    pub fn is_snoozed(&self, now: DateTime<Utc>) -> bool {
+        self.snoozed_until.is_some_and(|until| until > now)
+    }
+}
+
+impl TodoRevision {
+    fn of(todo: &Todo, rev: i32) -> Self {
+        Self {
+            todo_id: todo.id,
+            rev,
+            text: todo.text.clone(),
+            completed: todo.completed,
+            estimate_minutes: todo.estimate_minutes,
+            created_at: Utc::now(),
         }
     }
 }
 
 type TodoDatas = HashMap<i32, Todo>;
+type TodoRevisionDatas = HashMap<i32, Vec<TodoRevision>>;
+
+#[derive(Debug, Default)]
+struct MemoryOutbox {
+    next_id: i64,
+    /// Unsent entries with the end of their lease once claimed.
+    entries: Vec<(OutboxEntry, Option<DateTime<Utc>>)>,
+}
+
+/// Everything `TodoRepositoryForMemory` saves to its file. Unsent outbox
+/// entries are left out; their events are lost when the process stops first.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct MemorySnapshot {
+    next_id: i32,
+    todos: TodoDatas,
+    revisions: TodoRevisionDatas,
+    next_item_id: i32,
+    items: HashMap<i32, Vec<ChecklistItem>>,
+    positions: Positions,
+    workspaces: HashMap<i32, i32>,
+    labels: LabelSnapshot,
+}
+
+/// A burst of changes is saved in one write this long after the first.
+const FLUSH_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
 
 #[derive(Debug, Clone)]
 pub struct TodoRepositoryForMemory {
     store: Arc<RwLock<TodoDatas>>,
+    next_id: Arc<AtomicI32>,
+    revisions: Arc<RwLock<TodoRevisionDatas>>,
+    /// Checklists by todo id.
+    items: Arc<RwLock<HashMap<i32, Vec<ChecklistItem>>>>,
+    next_item_id: Arc<AtomicI32>,
+    positions: Arc<RwLock<Positions>>,
+    /// Todo id to the workspace it was created in.
+    workspaces: Arc<RwLock<HashMap<i32, i32>>>,
+    labels: LabelRepositoryForMemory,
+    outbox: Arc<Mutex<MemoryOutbox>>,
+    /// Notified whenever the todos or their labels are about to change.
+    changes: Arc<Notify>,
+    /// Where `flush` saves to; held while saving so saves do not overlap.
+    file: Option<Arc<tokio::sync::Mutex<PathBuf>>>,
+    workspace_id: i32,
+    filter: TodoFilter,
+    actor: Option<i32>,
+}
+
+impl Default for TodoRepositoryForMemory {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TodoRepositoryForMemory {
     pub fn new() -> Self {
+        Self::with_labels(LabelRepositoryForMemory::new())
+    }
+
+    /// Resolve label ids against `labels`, the store the app serves under `/labels`.
+    pub fn with_labels(labels: LabelRepositoryForMemory) -> Self {
         TodoRepositoryForMemory {
             store: Arc::default(),
+            next_id: Arc::default(),
+            revisions: Arc::default(),
+            items: Arc::default(),
+            next_item_id: Arc::default(),
+            positions: Arc::default(),
+            workspaces: Arc::default(),
+            changes: labels.changes(),
+            labels,
+            outbox: Arc::default(),
+            file: None,
+            workspace_id: DEFAULT_WORKSPACE_ID,
+            filter: TodoFilter::default(),
+            actor: None,
         }
     }
 
-    fn write_store_ref(&self) -> RwLockWriteGuard<TodoDatas> {
-        self.store.write().unwrap()
+    /// Keeps the todos and labels in the JSON file at `path`, loading what
+    /// it holds now. A missing file is created by the first `flush`.
+    pub fn persist_to(mut self, path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        match std::fs::read(&path) {
+            Ok(json) => {
+                let snapshot = serde_json::from_slice(&json)
+                    .with_context(|| format!("cannot load todos from {}", path.display()))?;
+                self.restore(snapshot);
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(e).with_context(|| format!("cannot read {}", path.display()));
+            }
+        }
+        self.file = Some(Arc::new(tokio::sync::Mutex::new(path)));
+        Ok(self)
     }
 
-    fn read_store_ref(&self) -> RwLockReadGuard<TodoDatas> {
-        self.store.read().unwrap()
+    /// Writes everything to the file given to `persist_to`, if any. The old
+    /// contents stay in place until the new ones are complete.
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let path = file.lock().await;
+        let json = serde_json::to_vec(&self.snapshot())?;
+        let partial = path.with_extension("partial");
+        tokio::fs::write(&partial, json)
+            .await
+            .with_context(|| format!("cannot write {}", partial.display()))?;
+        tokio::fs::rename(&partial, &*path)
+            .await
+            .with_context(|| format!("cannot replace {}", path.display()))?;
+        Ok(())
     }
-}
 
-#[async_trait]
-impl TodoRepository for TodoRepositoryForMemory {
-    async fn create(&self, payload: CreateTodo) -> anyhow::Result<Todo> {
-        let mut store = self.write_store_ref();
-        let id = (store.len() + 1) as i32;
-        let todo = Todo::new(id, payload.text.clone());
-        store.insert(id, todo.clone());
-        Ok(todo)
+    /// Runs `flush` shortly after every change for as long as the process
+    /// does.
+    pub fn flush_on_change(&self) {
+        let todos = self.clone();
+        tokio::spawn(async move {
+            loop {
+                todos.changes.notified().await;
+                tokio::time::sleep(FLUSH_DELAY).await;
+                if let Err(e) = todos.flush().await {
+                    tracing::warn!("saving todos failed: {:?}", e);
+                }
+            }
+        });
     }
-    async fn find(&self, id: i32) -> anyhow::Result<Todo> {
+
+    fn snapshot(&self) -> MemorySnapshot {
+        // held so no todo changes halfway through
         let store = self.read_store_ref();
-        let todo = store
-            .get(&id)
-            .map(|todo| todo.clone())
-            .ok_or(RepositoryError::NotFound(id))?;
+        MemorySnapshot {
+            next_id: self.next_id.load(Ordering::SeqCst),
+            todos: store.clone(),
+            revisions: self.revisions.read().unwrap().clone(),
+            next_item_id: self.next_item_id.load(Ordering::SeqCst),
+            items: self.items.read().unwrap().clone(),
+            positions: self.positions.read().unwrap().clone(),
+            workspaces: self.workspaces.read().unwrap().clone(),
+            labels: self.labels.snapshot(),
+        }
+    }
 
-        Ok(todo)
+    fn restore(&self, snapshot: MemorySnapshot) {
+        *self.store.write().unwrap() = snapshot.todos;
+        self.next_id.store(snapshot.next_id, Ordering::SeqCst);
+        *self.revisions.write().unwrap() = snapshot.revisions;
+        self.next_item_id
+            .store(snapshot.next_item_id, Ordering::SeqCst);
+        *self.items.write().unwrap() = snapshot.items;
+        *self.positions.write().unwrap() = snapshot.positions;
+        *self.workspaces.write().unwrap() = snapshot.workspaces;
+        self.labels.restore(snapshot.labels);
     }
-    async fn all(&self) -> anyhow::Result<Vec<Todo>> {
-        let store = self.read_store_ref();
-        Ok(Vec::from_iter(store.values().map(|todo| todo.clone())))
+
+    fn owns(&self, id: i32) -> bool {
+        self.workspaces.read().unwrap().get(&id) == Some(&self.workspace_id)
     }
-    async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<Todo> {
+
+    fn owned(&self, id: i32) -> Result<(), RepositoryError> {
+        self.owns(id)
+            .then_some(())
+            .ok_or(RepositoryError::NotFound(id))
+    }
+
+    fn update_checked(
+        &self,
+        id: i32,
+        expected: Option<&Todo>,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<Todo> {
         let mut store = self.write_store_ref();
+        let (todo, events) = self.replace(&mut store, id, expected, payload)?;
+        self.record(self.workspace_id, events);
+        Ok(todo)
+    }
+
+    /// Adds a todo to `store`, the locked store; returns it with the events
+    /// to record.
+    fn insert(
+        &self,
+        store: &mut TodoDatas,
+        payload: CreateTodo,
+    ) -> anyhow::Result<(Todo, Vec<DomainEvent>)> {
+        let missing = self.labels.missing(&payload.labels);
+        if !missing.is_empty() {
+            return Err(RepositoryError::MissingLabels(missing).into());
+        }
+        let labels = self.labels.find_many(&payload.labels)?;
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let todo = Todo {
+            labels,
+            owner_id: payload.owner_id,
+            assignee_id: payload.assignee_id,
+            text_i18n: payload.text_i18n.clone(),
+            estimate_minutes: payload.estimate_minutes,
+            due_date: payload.due_date,
+            description: payload.description.clone(),
+            custom: payload.custom.clone(),
+            uuid: Uuid::now_v7(),
+            ..Todo::new(id, payload.text.clone())
+        };
+        store.insert(id, todo.clone());
+        self.workspaces
+            .write()
+            .unwrap()
+            .insert(id, self.workspace_id);
+        self.push_revision(&todo);
+        let events = DomainEvent::created(&todo, self.actor);
+        Ok((todo, events))
+    }
+
+    /// Updates todo `id` of `store`, the locked store, unless it no longer
+    /// equals `expected`; returns it with the events to record.
+    fn replace(
+        &self,
+        store: &mut TodoDatas,
+        id: i32,
+        expected: Option<&Todo>,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<(Todo, Vec<DomainEvent>)> {
+        let labels = payload
+            .labels
+            .map(|ids| self.labels.find_many(&ids))
+            .transpose()?;
+        self.owned(id)?;
         let todo = store.get(&id).context(RepositoryError::NotFound(id))?;
+        let before = self.current(todo.clone());
+        if expected.is_some_and(|expected| before != *expected) {
+            return Err(RepositoryError::Conflict(id).into());
+        }
+        let mut custom = todo.custom.clone();
+        if let Some(changes) = &payload.custom {
+            merge_custom(&mut custom, changes);
+        }
         let text = payload.text.unwrap_or(todo.text.clone());
         let completed = payload.completed.unwrap_or(todo.completed);
+        let labels = labels.unwrap_or_else(|| before.labels.clone());
+        let assignee_id = payload.assignee_id.unwrap_or(todo.assignee_id);
+        let text_i18n = payload.text_i18n.unwrap_or(todo.text_i18n.clone());
+        let estimate_minutes = payload.estimate_minutes.unwrap_or(todo.estimate_minutes);
+        let due_date = payload.due_date.unwrap_or(todo.due_date);
+        let description = payload
+            .description
+            .unwrap_or_else(|| todo.description.clone());
 
         let todo = Todo {
             id,
+            uuid: todo.uuid,
             text,
             completed,
+            labels,
+            owner_id: todo.owner_id,
+            assignee_id,
+            text_i18n,
+            snoozed_until: todo.snoozed_until,
+            estimate_minutes,
+            due_date,
+            description,
+            custom,
+            progress: before.progress,
         };
         store.insert(id, todo.clone());
-        Ok(todo)
+        self.push_revision(&todo);
+        let events = DomainEvent::changed(&before, &todo, self.actor);
+        Ok((todo, events))
     }
-    async fn delete(&self, id: i32) -> anyhow::Result<()> {
-        let mut store = self.write_store_ref();
+
+    /// Deletes todo `id` of `store`, the locked store, with what hangs off
+    /// it; returns the events to record.
+    fn remove(&self, store: &mut TodoDatas, id: i32) -> anyhow::Result<Vec<DomainEvent>> {
+        self.owned(id)?;
         store.remove(&id).ok_or(RepositoryError::NotFound(id))?;
-        Ok(())
+        self.revisions.write().unwrap().remove(&id);
+        self.items.write().unwrap().remove(&id);
+        self.positions.write().unwrap().remove(&id);
+        self.workspaces.write().unwrap().remove(&id);
+        Ok(vec![DomainEvent::TodoDeleted(id)])
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct TodoRepositoryForDb {
-    pool: PgPool,
+    /// Called with the store locked, so the events land together with the
+    /// change.
+    fn record(&self, workspace_id: i32, events: Vec<DomainEvent>) {
+        let mut outbox = self.outbox.lock().unwrap();
+        for event in events {
+            outbox.next_id += 1;
+            let entry = OutboxEntry {
+                id: outbox.next_id,
+                event: WorkspaceEvent {
+                    workspace_id,
+                    event,
+                },
+            };
+            outbox.entries.push((entry, None));
+        }
+    }
+
+    /// Labels as they are now: deleted ones dropped and renamed or recolored
+    /// ones refreshed, as the join does in SQL; and the checklist's progress.
+    fn current(&self, mut todo: Todo) -> Todo {
+        todo.labels = todo
+            .labels
+            .iter()
+            .filter_map(|label| self.labels.get(label.id))
+            .collect();
+        todo.progress = self
+            .items
+            .read()
+            .unwrap()
+            .get(&todo.id)
+            .map(|items| Progress::of(items))
+            .unwrap_or_default();
+        todo
+    }
+
+    /// Lets `change` rework the checklist of todo `id`, recording the todo
+    /// as changed.
+    fn change_checklist<R>(
+        &self,
+        id: i32,
+        change: impl FnOnce(&mut Vec<ChecklistItem>) -> Result<R, RepositoryError>,
+    ) -> anyhow::Result<R> {
+        self.owned(id)?;
+        // held so the events land in the order of the changes
+        let store = self.write_store_ref();
+        let todo = store.get(&id).context(RepositoryError::NotFound(id))?;
+        let before = self.current(todo.clone());
+        let result = change(self.items.write().unwrap().entry(id).or_default())?;
+        let after = self.current(todo.clone());
+        self.record(
+            self.workspace_id,
+            DomainEvent::changed(&before, &after, self.actor),
+        );
+        Ok(result)
+    }
+
+    fn push_revision(&self, todo: &Todo) {
+        let mut revisions = self.revisions.write().unwrap();
+        let history = revisions.entry(todo.id).or_default();
+        let rev = history.len() as i32 + 1;
+        history.push(TodoRevision::of(todo, rev));
+    }
+
+    fn write_store_ref(&self) -> RwLockWriteGuard<'_, TodoDatas> {
+        let store = self.store.write().unwrap();
+        self.changes.notify_one();
+        store
+    }
+
+    fn read_store_ref(&self) -> RwLockReadGuard<'_, TodoDatas> {
+        self.store.read().unwrap()
+    }
 }
 
-impl TodoRepositoryForDb {
-    pub fn new(pool: PgPool) -> Self {
-        TodoRepositoryForDb { pool }
+impl TodoScope for TodoRepositoryForMemory {
+    fn in_workspace(&self, workspace_id: i32) -> Self {
+        Self {
+            labels: self.labels.in_workspace(workspace_id),
+            workspace_id,
+            ..self.clone()
+        }
     }
 }
 
 #[async_trait]
-impl TodoRepository for TodoRepositoryForDb {
-    async fn create(&self, payload: CreateTodo) -> anyhow::Result<Todo> {
-        let todo = sqlx::query_as::<_, Todo>(
-            r#"
-          insert into todos (text, completed)
-          values ($1, false)
-          returning *
-        "#,
-        )
-        .bind(payload.text.clone())
-        .fetch_one(&self.pool)
-        .await?;
-
-        Ok(todo)
+impl TodoReader for TodoRepositoryForMemory {
+    fn with_filter(&self, filter: TodoFilter) -> Self {
+        Self {
+            filter,
+            ..self.clone()
+        }
     }
     async fn find(&self, id: i32) -> anyhow::Result<Todo> {
-        let todo = sqlx::query_as::<_, Todo>(
-            r#"
-            select * from todos where id=$1
-        "#,
-        )
-        .bind(id)
-        .fetch_one(&self.pool)
-        .await
-        .map_err(|e| match e {
-            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
-            _ => RepositoryError::Unexpected(e.to_string()),
-        })?;
+        self.owned(id)?;
+        let store = self.read_store_ref();
+        let todo = store
+            .get(&id)
+            .cloned()
+            .ok_or(RepositoryError::NotFound(id))?;
 
-        Ok(todo)
+        Ok(self.current(todo))
     }
     async fn all(&self) -> anyhow::Result<Vec<Todo>> {
-        let todos = sqlx::query_as::<_, Todo>(
-            r#"
-            select * from todos
-            order by id desc;
-        "#,
-        )
-        .fetch_all(&self.pool)
-        .await?;
-
+        let store = self.read_store_ref();
+        let mut todos: Vec<Todo> = store
+            .values()
+            .filter(|todo| self.owns(todo.id) && self.filter.matches(todo))
+            .cloned()
+            .map(|todo| self.current(todo))
+            .collect();
+        self.filter.sort(&mut todos);
         Ok(todos)
     }
-    async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<Todo> {
-        let old_todo = self.find(id).await?;
-        let todo = sqlx::query_as::<_, Todo>(
-            r#"
-            update todos set text=$1, completed=$2
-            where id=$3
-            returning *
-        "#,
-        )
-        .bind(payload.text.clone())
-        .bind(payload.completed.unwrap_or(old_todo.completed))
-        .bind(id)
-        .fetch_one(&self.pool)
-        .await
-        .map_err(|e| match e {
-            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
-            _ => RepositoryError::Unexpected(e.to_string()),
-        })?;
+    async fn count(&self) -> anyhow::Result<i64> {
+        let store = self.read_store_ref();
+        let count = store
+            .values()
+            .filter(|todo| self.owns(todo.id) && self.filter.matches(todo))
+            .count();
+        Ok(count as i64)
+    }
+    async fn exists(&self, id: i32) -> anyhow::Result<bool> {
+        Ok(self.read_store_ref().contains_key(&id) && self.owns(id))
+    }
+    async fn id_of(&self, uuid: Uuid) -> anyhow::Result<Option<i32>> {
+        Ok(self
+            .read_store_ref()
+            .values()
+            .find(|todo| todo.uuid == uuid && self.owns(todo.id))
+            .map(|todo| todo.id))
+    }
+    async fn find_open_duplicate(&self, text: &str) -> anyhow::Result<Option<Todo>> {
+        let text = normalize_text(text);
+        let store = self.read_store_ref();
+        let duplicate = store
+            .values()
+            .filter(|todo| {
+                self.owns(todo.id) && !todo.completed && normalize_text(&todo.text) == text
+            })
+            .min_by_key(|todo| todo.id)
+            .cloned();
+        Ok(duplicate.map(|todo| self.current(todo)))
+    }
+    async fn page(&self, cursor: PageCursor, limit: u32) -> anyhow::Result<TodoPage> {
+        Ok(TodoPage::slice(self.all().await?, cursor, limit))
+    }
+    async fn revisions(&self, id: i32) -> anyhow::Result<Vec<TodoRevision>> {
+        self.owned(id)?;
+        let store = self.read_store_ref();
+        store.get(&id).ok_or(RepositoryError::NotFound(id))?;
+        let revisions = self.revisions.read().unwrap();
+        Ok(revisions.get(&id).cloned().unwrap_or_default())
+    }
+    async fn count_open_owned(&self, owner_id: i32) -> anyhow::Result<i64> {
+        let store = self.read_store_ref();
+        let count = store
+            .values()
+            .filter(|todo| todo.owner_id == Some(owner_id) && !todo.completed)
+            .count();
+        Ok(count as i64)
+    }
+    async fn owned(&self, owner_id: i32) -> anyhow::Result<Vec<(i32, Todo)>> {
+        let store = self.read_store_ref();
+        let workspaces = self.workspaces.read().unwrap();
+        let mut owned: Vec<(i32, Todo)> = store
+            .values()
+            .filter(|todo| todo.owner_id == Some(owner_id))
+            .filter_map(|todo| {
+                let workspace_id = *workspaces.get(&todo.id)?;
+                Some((workspace_id, self.current(todo.clone())))
+            })
+            .collect();
+        owned.sort_by_key(|(_, todo)| todo.id);
+        Ok(owned)
+    }
+    async fn label_stats(&self) -> anyhow::Result<Vec<LabelStats>> {
+        let labels = self.labels.all().await?;
+        let store = self.read_store_ref();
+        let mut counts: HashMap<i32, i64> = HashMap::new();
+        for todo in store.values().filter(|todo| self.owns(todo.id)) {
+            for label in &todo.labels {
+                *counts.entry(label.id).or_default() += 1;
+            }
+        }
+        Ok(labels
+            .into_iter()
+            .map(|label| LabelStats {
+                todos: counts.get(&label.id).copied().unwrap_or_default(),
+                label,
+            })
+            .collect())
+    }
+    async fn suggest_labels(&self, search: &Search, limit: u32) -> anyhow::Result<Vec<LabelStats>> {
+        Ok(LabelStats::suggest(
+            self.label_stats().await?,
+            search,
+            limit,
+        ))
+    }
+    async fn checklist(&self, id: i32) -> anyhow::Result<Vec<ChecklistItem>> {
+        self.owned(id)?;
+        let store = self.read_store_ref();
+        store.get(&id).ok_or(RepositoryError::NotFound(id))?;
+        let items = self.items.read().unwrap();
+        Ok(items.get(&id).cloned().unwrap_or_default())
+    }
+    async fn burndown(&self, from: NaiveDate, to: NaiveDate) -> anyhow::Result<Vec<BurndownDay>> {
+        let revisions: Vec<TodoRevision> = self
+            .revisions
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(id, _)| self.owns(**id))
+            .flat_map(|(_, history)| history.iter().cloned())
+            .collect();
+        Ok(burndown::from_revisions(&revisions, from, to))
+    }
+    async fn board_positions(&self) -> anyhow::Result<Positions> {
+        let positions = self.positions.read().unwrap();
+        Ok(positions
+            .iter()
+            .filter(|(id, _)| self.owns(**id))
+            .map(|(id, position)| (*id, *position))
+            .collect())
+    }
+    async fn due_by(&self, user_id: i32, by: NaiveDate) -> anyhow::Result<Vec<(i32, Todo)>> {
+        let store = self.read_store_ref();
+        let workspaces = self.workspaces.read().unwrap();
+        let mut due: Vec<(i32, Todo)> = store
+            .values()
+            .filter(|todo| !todo.completed && todo.due_date.is_some_and(|due| due <= by))
+            .filter(|todo| todo.assignee_id.or(todo.owner_id) == Some(user_id))
+            .filter_map(|todo| {
+                let workspace_id = *workspaces.get(&todo.id)?;
+                Some((workspace_id, self.current(todo.clone())))
+            })
+            .collect();
+        due.sort_by_key(|(_, todo)| (todo.due_date, todo.id));
+        Ok(due)
+    }
+}
 
+#[async_trait]
+impl TodoWriter for TodoRepositoryForMemory {
+    async fn create(&self, payload: CreateTodo) -> anyhow::Result<Todo> {
+        let mut store = self.write_store_ref();
+        let (todo, events) = self.insert(&mut store, payload)?;
+        self.record(self.workspace_id, events);
         Ok(todo)
     }
+    async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<Todo> {
+        self.update_checked(id, None, payload)
+    }
+    async fn update_if(
+        &self,
+        id: i32,
+        expected: &Todo,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<Todo> {
+        self.update_checked(id, Some(expected), payload)
+    }
     async fn delete(&self, id: i32) -> anyhow::Result<()> {
-        sqlx::query(
-            r#"
-            delete from todos where id=$1
-        "#,
-        )
-        .bind(id)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| match e {
-            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
-            _ => RepositoryError::Unexpected(e.to_string()),
-        })?;
-
+        let mut store = self.write_store_ref();
+        let events = self.remove(&mut store, id)?;
+        self.record(self.workspace_id, events);
         Ok(())
     }
-}
-
-#[cfg(test)]
-mod test {
-    use std::env;
-
-    use super::*;
-    use dotenv::dotenv;
-
-    #[tokio::test]
-    async fn todo_curd_scenario() {
-        let text = "todo text".to_string();
-        let id = 1;
-        let expected = Todo::new(id, text.clone());
+    async fn revert(&self, id: i32, rev: i32) -> anyhow::Result<Todo> {
+        self.owned(id)?;
+        let revision = self
+            .revisions
+            .read()
+            .unwrap()
+            .get(&id)
+            .and_then(|history| history.iter().find(|revision| revision.rev == rev))
+            .cloned()
+            .ok_or(RepositoryError::NotFound(rev))?;
+
+        self.update(
+            id,
+            UpdateTodo {
+                text: Some(revision.text),
+                completed: Some(revision.completed),
+                labels: None,
+                assignee_id: None,
+                text_i18n: None,
+                estimate_minutes: Some(revision.estimate_minutes),
+                due_date: None,
+                description: None,
+                custom: None,
+            },
+        )
+        .await
+    }
+    async fn delete_unused_labels(&self) -> anyhow::Result<Vec<i32>> {
+        // held so no todo picks up a label on its way out
+        let store = self.read_store_ref();
+        let used: HashSet<i32> = store
+            .values()
+            .filter(|todo| self.owns(todo.id))
+            .flat_map(|todo| todo.labels.iter().map(|label| label.id))
+            .collect();
+        Ok(self.labels.delete_except(&used))
+    }
+    async fn merge_labels(&self, source: i32, target: i32) -> anyhow::Result<Vec<Todo>> {
+        let mut store = self.write_store_ref();
+        self.labels.find_many(&[source, target])?;
+        let mut ids: Vec<i32> = store
+            .values()
+            .filter(|todo| self.owns(todo.id) && todo.labels.iter().any(|label| label.id == source))
+            .map(|todo| todo.id)
+            .collect();
+        ids.sort_unstable();
+        let mut moved = Vec::new();
+        for id in ids {
+            let Some(todo) = store.get_mut(&id) else {
+                continue;
+            };
+            let before = self.current(todo.clone());
+            let labels: Vec<i32> = before
+                .labels
+                .iter()
+                .map(|label| if label.id == source { target } else { label.id })
+                .collect();
+            todo.labels = self.labels.find_many(&labels)?;
+            todo.labels.dedup_by_key(|label| label.id);
+            let after = self.current(todo.clone());
+            self.record(
+                self.workspace_id,
+                DomainEvent::changed(&before, &after, self.actor),
+            );
+            moved.push(after);
+        }
+        self.labels.remove(source)?;
+        Ok(moved)
+    }
+    async fn snooze(&self, id: i32, until: Option<DateTime<Utc>>) -> anyhow::Result<Todo> {
+        self.owned(id)?;
+        let mut store = self.write_store_ref();
+        let todo = store.get_mut(&id).context(RepositoryError::NotFound(id))?;
+        let before = self.current(todo.clone());
+        todo.snoozed_until = until;
+        let after = self.current(todo.clone());
+        self.record(
+            self.workspace_id,
+            DomainEvent::changed(&before, &after, self.actor),
+        );
+        Ok(after)
+    }
+    async fn unsnooze_due(&self) -> anyhow::Result<Vec<(i32, i32)>> {
+        let now = Utc::now();
+        let mut store = self.write_store_ref();
+        let workspaces = self.workspaces.read().unwrap();
+        let mut woken: Vec<(i32, i32)> = store
+            .values()
+            .filter(|todo| todo.snoozed_until.is_some_and(|until| until <= now))
+            .filter_map(|todo| workspaces.get(&todo.id).map(|ws| (*ws, todo.id)))
+            .collect();
+        woken.sort();
+        for &(workspace_id, id) in &woken {
+            let Some(todo) = store.get_mut(&id) else {
+                continue;
+            };
+            todo.snoozed_until = None;
+            let event = DomainEvent::TodoUpdated(self.current(todo.clone()));
+            self.record(workspace_id, vec![event]);
+        }
+        Ok(woken)
+    }
+    async fn add_item(
+        &self,
+        id: i32,
+        payload: CreateChecklistItem,
+    ) -> anyhow::Result<ChecklistItem> {
+        self.change_checklist(id, |items| {
+            let item = ChecklistItem {
+                id: self.next_item_id.fetch_add(1, Ordering::SeqCst) + 1,
+                text: payload.text,
+                completed: false,
+                position: 0,
+            };
+            Ok(checklist::insert(items, item, payload.position))
+        })
+    }
+    async fn update_item(
+        &self,
+        id: i32,
+        item_id: i32,
+        payload: UpdateChecklistItem,
+    ) -> anyhow::Result<ChecklistItem> {
+        self.change_checklist(id, |items| checklist::update(items, item_id, payload))
+    }
+    async fn delete_item(&self, id: i32, item_id: i32) -> anyhow::Result<()> {
+        self.change_checklist(id, |items| checklist::remove(items, item_id))
+    }
+    async fn move_on_board(&self, id: i32, completed: bool, position: u32) -> anyhow::Result<Todo> {
+        self.owned(id)?;
+        let mut store = self.write_store_ref();
+        let todo = store.get_mut(&id).context(RepositoryError::NotFound(id))?;
+        if todo.completed != completed {
+            let before = self.current(todo.clone());
+            todo.completed = completed;
+            let after = todo.clone();
+            self.push_revision(&after);
+            self.record(
+                self.workspace_id,
+                DomainEvent::changed(&before, &self.current(after), self.actor),
+            );
+        }
+        let todo = self.current(store[&id].clone());
+        let mut positions = self.positions.write().unwrap();
+        let column = store
+            .values()
+            .filter(|other| self.owns(other.id) && other.completed == completed)
+            .map(|other| (other.id, positions.get(&other.id).copied()))
+            .collect();
+        positions.extend(board::reorder(column, id, position).into_iter().zip(0..));
+        Ok(todo)
+    }
+    async fn release_owned(
+        &self,
+        owner_id: i32,
+        release: OwnedTodos,
+    ) -> anyhow::Result<Vec<(i32, i32)>> {
+        let mut store = self.write_store_ref();
+        let mut workspaces = self.workspaces.write().unwrap();
+        let mut released: Vec<(i32, i32)> = store
+            .values()
+            .filter(|todo| todo.owner_id == Some(owner_id))
+            .filter_map(|todo| workspaces.get(&todo.id).map(|ws| (*ws, todo.id)))
+            .collect();
+        released.sort();
+        for &(workspace_id, id) in &released {
+            let event = match release {
+                OwnedTodos::Delete => {
+                    store.remove(&id);
+                    workspaces.remove(&id);
+                    self.revisions.write().unwrap().remove(&id);
+                    self.items.write().unwrap().remove(&id);
+                    self.positions.write().unwrap().remove(&id);
+                    DomainEvent::TodoDeleted(id)
+                }
+                OwnedTodos::TransferTo(new_owner) => {
+                    let Some(todo) = store.get_mut(&id) else {
+                        continue;
+                    };
+                    todo.owner_id = Some(new_owner);
+                    DomainEvent::TodoUpdated(self.current(todo.clone()))
+                }
+            };
+            self.record(workspace_id, vec![event]);
+        }
+        Ok(released)
+    }
+    async fn apply(&self, changes: Vec<TodoChange>) -> anyhow::Result<Vec<Option<Todo>>> {
+        let mut store = self.write_store_ref();
+        // put back when a change fails; the events wait for the last one
+        let saved = (
+            store.clone(),
+            self.revisions.read().unwrap().clone(),
+            self.items.read().unwrap().clone(),
+            self.positions.read().unwrap().clone(),
+            self.workspaces.read().unwrap().clone(),
+        );
+        let mut changed = Vec::with_capacity(changes.len());
+        let mut events = Vec::new();
+        for change in changes {
+            let result = match change {
+                TodoChange::Create(payload) => self
+                    .insert(&mut store, payload)
+                    .map(|(todo, events)| (Some(todo), events)),
+                TodoChange::Update(id, payload) => self
+                    .replace(&mut store, id, None, payload)
+                    .map(|(todo, events)| (Some(todo), events)),
+                TodoChange::Delete(id) => self.remove(&mut store, id).map(|events| (None, events)),
+            };
+            match result {
+                Ok((todo, more)) => {
+                    changed.push(todo);
+                    events.extend(more);
+                }
+                Err(e) => {
+                    let (todos, revisions, items, positions, workspaces) = saved;
+                    *store = todos;
+                    *self.revisions.write().unwrap() = revisions;
+                    *self.items.write().unwrap() = items;
+                    *self.positions.write().unwrap() = positions;
+                    *self.workspaces.write().unwrap() = workspaces;
+                    return Err(e);
+                }
+            }
+        }
+        self.record(self.workspace_id, events);
+        Ok(changed)
+    }
+    fn acting_as(&self, user_id: Option<i32>) -> Self {
+        Self {
+            actor: user_id,
+            ..self.clone()
+        }
+    }
+    async fn claim_outbox(&self, limit: u32) -> anyhow::Result<Vec<OutboxEntry>> {
+        let now = Utc::now();
+        let mut outbox = self.outbox.lock().unwrap();
+        let claimed = outbox
+            .entries
+            .iter_mut()
+            .filter(|(_, claimed_until)| claimed_until.is_none_or(|until| until < now))
+            .take(limit as usize)
+            .map(|(entry, claimed_until)| {
+                *claimed_until = Some(now + Duration::seconds(LEASE_SECS));
+                entry.clone()
+            })
+            .collect();
+        Ok(claimed)
+    }
+    async fn mark_sent(&self, ids: &[i64]) -> anyhow::Result<()> {
+        let mut outbox = self.outbox.lock().unwrap();
+        outbox.entries.retain(|(entry, _)| !ids.contains(&entry.id));
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TodoRepositoryForDb {
+    pool: PgPool,
+    workspace_id: i32,
+    filter: TodoFilter,
+    actor: Option<i32>,
+    slow_queries: SlowQueryLog,
+    list_cap: Option<u32>,
+}
+
+impl TodoRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        TodoRepositoryForDb {
+            pool,
+            workspace_id: DEFAULT_WORKSPACE_ID,
+            filter: TodoFilter::default(),
+            actor: None,
+            slow_queries: SlowQueryLog::default(),
+            list_cap: None,
+        }
+    }
+
+    pub fn with_slow_query_log(self, slow_queries: SlowQueryLog) -> Self {
+        Self {
+            slow_queries,
+            ..self
+        }
+    }
+
+    /// Makes `all` return at most `cap` todos, the first in listing order;
+    /// `0` lifts the cap.
+    pub fn with_list_cap(self, cap: u32) -> Self {
+        Self {
+            list_cap: (cap > 0).then_some(cap),
+            ..self
+        }
+    }
+
+    /// Inserts a todo as part of `tx`.
+    async fn insert(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        payload: CreateTodo,
+    ) -> anyhow::Result<Todo> {
+        self.slow_queries
+            .time(
+                "labels.check",
+                &[&payload.labels],
+                check_new_labels(&mut *tx, self.workspace_id, &payload.labels),
+            )
+            .await?;
+        let (id,) = self
+            .slow_queries
+            .time(
+                "todos.create",
+                &[
+                    &payload.text,
+                    &self.workspace_id,
+                    &payload.owner_id,
+                    &payload.assignee_id,
+                    &Json(&payload.text_i18n),
+                ],
+                sqlx::query_as::<_, (i32,)>(
+                    r#"
+                    insert into todos
+                        (text, completed, workspace_id, owner_id, assignee_id, text_i18n,
+                        estimate_minutes, due_date, description, custom, uuid)
+                    values ($1, false, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                    returning id
+                "#,
+                )
+                .bind(payload.text.clone())
+                .bind(self.workspace_id)
+                .bind(payload.owner_id)
+                .bind(payload.assignee_id)
+                .bind(Json(&payload.text_i18n))
+                .bind(payload.estimate_minutes)
+                .bind(payload.due_date)
+                .bind(payload.description.clone())
+                .bind(Json(&payload.custom))
+                .bind(DbUuid(Uuid::now_v7()))
+                .fetch_one(&mut *tx),
+            )
+            .await?;
+        self.slow_queries
+            .time(
+                "todos.replace_labels",
+                &[&id, &payload.labels],
+                replace_labels(&mut *tx, self.workspace_id, id, &payload.labels),
+            )
+            .await?;
+        let todo = self
+            .slow_queries
+            .time(
+                "todos.find",
+                &[&self.workspace_id, &id],
+                select_todo(&mut *tx, self.workspace_id, id),
+            )
+            .await?;
+        self.slow_queries
+            .time(
+                "todo_revisions.insert",
+                &[&id],
+                insert_revision(&mut *tx, &todo),
+            )
+            .await?;
+        self.slow_queries
+            .time(
+                "outbox.insert",
+                &[&self.workspace_id],
+                record_events(
+                    &mut *tx,
+                    self.workspace_id,
+                    DomainEvent::created(&todo, self.actor),
+                ),
+            )
+            .await?;
+
+        Ok(todo)
+    }
+
+    /// Deletes todo `id` as part of `tx`.
+    async fn remove(&self, tx: &mut Transaction<'_, Postgres>, id: i32) -> anyhow::Result<()> {
+        self.slow_queries
+            .time(
+                "todos.delete_labels",
+                &[&id, &self.workspace_id],
+                sqlx::query(
+                    r#"
+                    delete from todo_labels where todo_id in
+                        (select id from todos where id=$1 and workspace_id=$2)
+                "#,
+                )
+                .bind(id)
+                .bind(self.workspace_id)
+                .execute(&mut *tx),
+            )
+            .await?;
+        let deleted = self
+            .slow_queries
+            .time(
+                "todos.delete",
+                &[&id, &self.workspace_id],
+                sqlx::query(
+                    r#"
+                    delete from todos where id=$1 and workspace_id=$2
+                "#,
+                )
+                .bind(id)
+                .bind(self.workspace_id)
+                .execute(&mut *tx),
+            )
+            .await
+            .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+        if deleted.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+        self.slow_queries
+            .time(
+                "outbox.insert",
+                &[&self.workspace_id],
+                record_events(
+                    &mut *tx,
+                    self.workspace_id,
+                    vec![DomainEvent::TodoDeleted(id)],
+                ),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// The `where` conditions of every listing: the workspace, then the
+    /// `TodoFilter`, reading parameters from `$first` on; and the values of
+    /// the filter expression, for `bind_filter`. A search takes the two
+    /// parameters after the assignee.
+    fn filter_clause(&self, first: usize) -> (String, Vec<SqlValue>) {
+        let mut next = first + 2;
+        let search = match &self.filter.search {
+            Some(_) => {
+                next += 2;
+                format!(
+                    "strict_word_similarity(${}, todos.text) >= ${}",
+                    first + 2,
+                    first + 3
+                )
+            }
+            None => "true".to_string(),
+        };
+        let (expr, values) = match &self.filter.expr {
+            Some(expr) => expr.to_sql(next),
+            None => ("true".to_string(), Vec::new()),
+        };
+        let snoozed = if self.filter.hide_snoozed {
+            "(todos.snoozed_until is null or todos.snoozed_until <= now())"
+        } else {
+            "true"
+        };
+        let clause = format!(
+            r#"todos.workspace_id = ${w}
+            and (${a}::integer is null or todos.assignee_id = ${a})
+            and {search}
+            and {expr}
+            and {snoozed}"#,
+            w = first,
+            a = first + 1,
+            search = search,
+            expr = expr,
+            snoozed = snoozed
+        );
+        (clause, values)
+    }
+
+    /// What listings are ordered by, most significant first, for rows of
+    /// `table` and a `filter_clause` reading from `$first`: how well the
+    /// text matches when searching, then the id.
+    fn order_columns(&self, table: &str, first: usize) -> String {
+        match &self.filter.search {
+            Some(_) => format!(
+                "strict_word_similarity(${}, {t}.text), {t}.id",
+                first + 2,
+                t = table
+            ),
+            None => format!("{}.id", table),
+        }
+    }
+
+    /// Binds what `filter_clause` reads, in its order.
+    fn bind_filter<'q, O>(
+        &self,
+        query: QueryAs<'q, Postgres, O, PgArguments>,
+        values: &[SqlValue],
+    ) -> QueryAs<'q, Postgres, O, PgArguments> {
+        let mut query = query.bind(self.workspace_id).bind(self.filter.assignee_id);
+        if let Some(search) = &self.filter.search {
+            query = query.bind(search.text.clone()).bind(search.threshold);
+        }
+        values.iter().fold(query, |query, value| match value {
+            SqlValue::Bool(value) => query.bind(*value),
+            SqlValue::Int(value) => query.bind(*value),
+            SqlValue::Text(value) => query.bind(value.clone()),
+            SqlValue::Date(value) => query.bind(*value),
+            SqlValue::Json(value) => query.bind(Json(value.clone())),
+        })
+    }
+
+    /// What `bind_filter` binds, for the slow query log.
+    fn filter_binds<'a>(&'a self, values: &'a [SqlValue]) -> Vec<&'a dyn Redact> {
+        let mut binds: Vec<&dyn Redact> = vec![&self.workspace_id, &self.filter.assignee_id];
+        if let Some(search) = &self.filter.search {
+            binds.extend([&search.text as &dyn Redact, &search.threshold]);
+        }
+        binds.extend(values.iter().map(|value| value as &dyn Redact));
+        binds
+    }
+}
+
+/// Todos joined with their labels, aggregated so a list costs one query
+/// instead of one per todo. Callers append the filter and `group by`.
+const SELECT_TODOS_WITH_LABELS: &str = r#"
+    select todos.id, todos.uuid, todos.workspace_id, todos.text, todos.completed, todos.owner_id,
+        todos.assignee_id, todos.text_i18n, todos.snoozed_until, todos.estimate_minutes, todos.due_date,
+        todos.description, todos.custom,
+        (select count(*) from checklist_items
+            where checklist_items.todo_id = todos.id and checklist_items.completed) as items_done,
+        (select count(*) from checklist_items
+            where checklist_items.todo_id = todos.id) as items_total,
+        coalesce(
+            json_agg(
+                json_build_object(
+                    'id', labels.id, 'uuid', labels.uuid, 'name', labels.name,
+                    'color', labels.color, 'icon', labels.icon
+                )
+                order by labels.id
+            )
+                filter (where labels.id is not null),
+            '[]'
+        ) as labels
+    from todos
+    left join todo_labels on todo_labels.todo_id = todos.id
+    left join labels on labels.id = todo_labels.label_id
+"#;
+
+#[derive(Debug, FromRow)]
+struct TodoWithLabelsRow {
+    id: i32,
+    uuid: DbUuid,
+    workspace_id: i32,
+    text: String,
+    completed: bool,
+    labels: Json<Vec<Label>>,
+    owner_id: Option<i32>,
+    assignee_id: Option<i32>,
+    text_i18n: Json<BTreeMap<String, String>>,
+    snoozed_until: Option<DateTime<Utc>>,
+    estimate_minutes: Option<i32>,
+    due_date: Option<NaiveDate>,
+    description: Option<String>,
+    custom: Json<BTreeMap<String, Value>>,
+    items_done: i64,
+    items_total: i64,
+}
+
+impl From<TodoWithLabelsRow> for Todo {
+    fn from(row: TodoWithLabelsRow) -> Self {
+        Self {
+            id: row.id,
+            uuid: row.uuid.0,
+            text: row.text,
+            completed: row.completed,
+            labels: row.labels.0,
+            owner_id: row.owner_id,
+            assignee_id: row.assignee_id,
+            text_i18n: row.text_i18n.0,
+            snoozed_until: row.snoozed_until,
+            estimate_minutes: row.estimate_minutes,
+            due_date: row.due_date,
+            description: row.description,
+            custom: row.custom.0,
+            progress: Progress {
+                done: row.items_done,
+                total: row.items_total,
+            },
+        }
+    }
+}
+
+async fn select_todo<'e, E: Executor<'e, Database = Postgres>>(
+    executor: E,
+    workspace_id: i32,
+    id: i32,
+) -> anyhow::Result<Todo> {
+    let query = format!(
+        "{} where todos.id=$1 and todos.workspace_id=$2 group by todos.id",
+        SELECT_TODOS_WITH_LABELS
+    );
+    let row = sqlx::query_as::<_, TodoWithLabelsRow>(&query)
+        .bind(id)
+        .bind(workspace_id)
+        .fetch_one(executor)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id).into(),
+            // kept as is so an unreachable replica can be told apart
+            e => anyhow::Error::from(e),
+        })?;
+
+    Ok(row.into())
+}
+
+/// The todos of `ids` found in the workspace, by id.
+async fn select_todos<'e, E: Executor<'e, Database = Postgres>>(
+    executor: E,
+    workspace_id: i32,
+    ids: &[i32],
+) -> anyhow::Result<Vec<Todo>> {
+    let query = format!(
+        "{} where todos.id = any($1) and todos.workspace_id=$2 group by todos.id order by todos.id",
+        SELECT_TODOS_WITH_LABELS
+    );
+    let rows = sqlx::query_as::<_, TodoWithLabelsRow>(&query)
+        .bind(ids)
+        .bind(workspace_id)
+        .fetch_all(executor)
+        .await?;
+
+    Ok(rows.into_iter().map(Todo::from).collect())
+}
+
+impl TodoScope for TodoRepositoryForDb {
+    fn in_workspace(&self, workspace_id: i32) -> Self {
+        Self {
+            workspace_id,
+            ..self.clone()
+        }
+    }
+}
+
+#[async_trait]
+impl TodoReader for TodoRepositoryForDb {
+    fn with_filter(&self, filter: TodoFilter) -> Self {
+        Self {
+            filter,
+            ..self.clone()
+        }
+    }
+    #[tracing::instrument(skip_all)]
+    async fn find(&self, id: i32) -> anyhow::Result<Todo> {
+        self.slow_queries
+            .time(
+                "todos.find",
+                &[&self.workspace_id, &id],
+                select_todo(&self.pool, self.workspace_id, id),
+            )
+            .await
+    }
+    #[tracing::instrument(skip_all)]
+    async fn all(&self) -> anyhow::Result<Vec<Todo>> {
+        let (clause, values) = self.filter_clause(1);
+        let query = format!(
+            r#"
+            {}
+            where {}
+            group by todos.id
+            order by ({}) desc
+            limit {}
+        "#,
+            SELECT_TODOS_WITH_LABELS,
+            clause,
+            self.order_columns("todos", 1),
+            self.list_cap
+                .map_or_else(|| "all".to_string(), |cap| cap.to_string())
+        );
+        let rows = self
+            .slow_queries
+            .time(
+                "todos.all",
+                &self.filter_binds(&values),
+                self.bind_filter(sqlx::query_as::<_, TodoWithLabelsRow>(&query), &values)
+                    .fetch_all(&self.pool),
+            )
+            .await?;
+
+        Ok(rows.into_iter().map(Todo::from).collect())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn count(&self) -> anyhow::Result<i64> {
+        let (clause, values) = self.filter_clause(1);
+        let query = format!("select count(*) from todos where {}", clause);
+        let (count,) = self
+            .slow_queries
+            .time(
+                "todos.count",
+                &self.filter_binds(&values),
+                self.bind_filter(sqlx::query_as::<_, (i64,)>(&query), &values)
+                    .fetch_one(&self.pool),
+            )
+            .await?;
+
+        Ok(count)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn exists(&self, id: i32) -> anyhow::Result<bool> {
+        let (exists,) = self
+            .slow_queries
+            .time(
+                "todos.exists",
+                &[&id, &self.workspace_id],
+                sqlx::query_as::<_, (bool,)>(
+                    r#"
+                    select exists(select 1 from todos where id=$1 and workspace_id=$2)
+                "#,
+                )
+                .bind(id)
+                .bind(self.workspace_id)
+                .fetch_one(&self.pool),
+            )
+            .await?;
+
+        Ok(exists)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn id_of(&self, uuid: Uuid) -> anyhow::Result<Option<i32>> {
+        let id = self
+            .slow_queries
+            .time(
+                "todos.id_of",
+                &[&DbUuid(uuid), &self.workspace_id],
+                sqlx::query_as::<_, (i32,)>(
+                    r#"
+                    select id from todos where uuid=$1 and workspace_id=$2
+                "#,
+                )
+                .bind(DbUuid(uuid))
+                .bind(self.workspace_id)
+                .fetch_optional(&self.pool),
+            )
+            .await?;
+
+        Ok(id.map(|(id,)| id))
+    }
+    #[tracing::instrument(skip_all)]
+    async fn find_open_duplicate(&self, text: &str) -> anyhow::Result<Option<Todo>> {
+        let text = normalize_text(text);
+        let id = self
+            .slow_queries
+            .time(
+                "todos.find_open_duplicate",
+                &[&self.workspace_id, &text],
+                sqlx::query_as::<_, (i32,)>(
+                    r#"
+                    select id from todos
+                    where workspace_id = $1 and not completed
+                        and lower(regexp_replace(btrim(text), '\s+', ' ', 'g')) = $2
+                    order by id
+                    limit 1
+                "#,
+                )
+                .bind(self.workspace_id)
+                .bind(&text)
+                .fetch_optional(&self.pool),
+            )
+            .await?;
+        match id {
+            Some((id,)) => Ok(Some(self.find(id).await?)),
+            None => Ok(None),
+        }
+    }
+    #[tracing::instrument(skip_all)]
+    async fn page(&self, cursor: PageCursor, limit: u32) -> anyhow::Result<TodoPage> {
+        let (bound, compare, order) = match cursor {
+            PageCursor::After(after) => (after, "<", "desc"),
+            PageCursor::Before(before) => (before, ">", "asc"),
+        };
+        let (clause, values) = self.filter_clause(3);
+        // ranked listings continue from wherever the cursor todo ranks
+        let bound_key = match self.filter.search {
+            Some(_) => format!(
+                "(select {} from todos as anchor where anchor.id = $1)",
+                self.order_columns("anchor", 3)
+            ),
+            None => "$1".to_string(),
+        };
+        let query = format!(
+            r#"
+            {}
+            where ($1::integer is null or ({}) {} {}) and {}
+            group by todos.id
+            order by ({}) {}
+            limit $2
+        "#,
+            SELECT_TODOS_WITH_LABELS,
+            self.order_columns("todos", 3),
+            compare,
+            bound_key,
+            clause,
+            self.order_columns("todos", 3),
+            order
+        );
+        let fetch = limit as i64 + 1;
+        let mut binds: Vec<&dyn Redact> = vec![&bound, &fetch];
+        binds.extend(self.filter_binds(&values));
+        let rows = self
+            .slow_queries
+            .time(
+                "todos.page",
+                &binds,
+                self.bind_filter(
+                    sqlx::query_as::<_, TodoWithLabelsRow>(&query)
+                        .bind(bound)
+                        .bind(fetch),
+                    &values,
+                )
+                .fetch_all(&self.pool),
+            )
+            .await?;
+
+        Ok(TodoPage::from_rows(
+            rows.into_iter().map(Todo::from).collect(),
+            cursor,
+            limit,
+        ))
+    }
+    #[tracing::instrument(skip_all)]
+    async fn checklist(&self, id: i32) -> anyhow::Result<Vec<ChecklistItem>> {
+        self.find(id).await?;
+        self.slow_queries
+            .time("checklist_items.all", &[&id], select_items(&self.pool, id))
+            .await
+    }
+    #[tracing::instrument(skip_all)]
+    async fn revisions(&self, id: i32) -> anyhow::Result<Vec<TodoRevision>> {
+        self.find(id).await?;
+        let revisions = self
+            .slow_queries
+            .time(
+                "todos.revisions",
+                &[&id],
+                sqlx::query_as::<_, TodoRevision>(
+                    r#"
+                    select todo_id, rev, text, completed, estimate_minutes, created_at from todo_revisions
+                    where todo_id=$1
+                    order by rev asc
+                "#,
+                )
+                .bind(id)
+                .fetch_all(&self.pool),
+            )
+            .await?;
+
+        Ok(revisions)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn count_open_owned(&self, owner_id: i32) -> anyhow::Result<i64> {
+        let (count,) = self
+            .slow_queries
+            .time(
+                "todos.count_open_owned",
+                &[&owner_id],
+                sqlx::query_as::<_, (i64,)>(
+                    r#"
+                    select count(*) from todos where owner_id=$1 and not completed
+                "#,
+                )
+                .bind(owner_id)
+                .fetch_one(&self.pool),
+            )
+            .await?;
+
+        Ok(count)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn owned(&self, owner_id: i32) -> anyhow::Result<Vec<(i32, Todo)>> {
+        let query = format!(
+            r#"
+            {}
+            where todos.owner_id=$1
+            group by todos.id
+            order by todos.id
+        "#,
+            SELECT_TODOS_WITH_LABELS
+        );
+        let rows = self
+            .slow_queries
+            .time(
+                "todos.owned",
+                &[&owner_id],
+                sqlx::query_as::<_, TodoWithLabelsRow>(&query)
+                    .bind(owner_id)
+                    .fetch_all(&self.pool),
+            )
+            .await?;
+        let workspaces: HashMap<i32, i32> = self
+            .slow_queries
+            .time(
+                "todos.owned_workspaces",
+                &[&owner_id],
+                sqlx::query_as::<_, (i32, i32)>(
+                    r#"
+                    select id, workspace_id from todos where owner_id=$1
+                "#,
+                )
+                .bind(owner_id)
+                .fetch_all(&self.pool),
+            )
+            .await?
+            .into_iter()
+            .collect();
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| Some((*workspaces.get(&row.id)?, Todo::from(row))))
+            .collect())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn label_stats(&self) -> anyhow::Result<Vec<LabelStats>> {
+        let rows = self
+            .slow_queries
+            .time(
+                "labels.stats",
+                &[&self.workspace_id],
+                sqlx::query_as::<_, LabelStatsRow>(
+                    r#"
+                    select labels.id, labels.uuid, labels.name, labels.color, labels.icon,
+                        count(todo_labels.todo_id) as todos
+                    from labels
+                    left join todo_labels on todo_labels.label_id = labels.id
+                    where labels.workspace_id = $1
+                    group by labels.id
+                    order by labels.id
+                "#,
+                )
+                .bind(self.workspace_id)
+                .fetch_all(&self.pool),
+            )
+            .await?;
+
+        Ok(rows.into_iter().map(LabelStats::from).collect())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn suggest_labels(&self, search: &Search, limit: u32) -> anyhow::Result<Vec<LabelStats>> {
+        let prefix = like_prefix(&search.text.to_lowercase());
+        let rows = self
+            .slow_queries
+            .time(
+                "labels.suggest",
+                &[&self.workspace_id, &search.text, &search.threshold, &limit],
+                sqlx::query_as::<_, LabelStatsRow>(
+                    r#"
+                    select labels.id, labels.uuid, labels.name, labels.color, labels.icon,
+                        count(todo_labels.todo_id) as todos
+                    from labels
+                    left join todo_labels on todo_labels.label_id = labels.id
+                    where labels.workspace_id = $1
+                        and (lower(labels.name) like $2
+                            or strict_word_similarity($3, labels.name) >= $4)
+                    group by labels.id
+                    order by lower(labels.name) like $2 desc, todos desc, labels.name
+                    limit $5
+                "#,
+                )
+                .bind(self.workspace_id)
+                .bind(prefix)
+                .bind(&search.text)
+                .bind(search.threshold)
+                .bind(limit as i64)
+                .fetch_all(&self.pool),
+            )
+            .await?;
+
+        Ok(rows.into_iter().map(LabelStats::from).collect())
+    }
+    /// Each revision moves the total by how much it changed what its todo
+    /// leaves to do; the days of the range add nothing but make sure every
+    /// one of them gets a row.
+    #[tracing::instrument(skip_all)]
+    async fn burndown(&self, from: NaiveDate, to: NaiveDate) -> anyhow::Result<Vec<BurndownDay>> {
+        let days = self
+            .slow_queries
+            .time(
+                "todos.burndown",
+                &[&self.workspace_id, &from, &to],
+                sqlx::query_as::<_, BurndownDay>(
+                    r#"
+                    with remaining as (
+                        select todo_revisions.todo_id, todo_revisions.rev,
+                            (todo_revisions.created_at at time zone 'UTC')::date as day,
+                            case when todo_revisions.completed then 0
+                                else coalesce(todo_revisions.estimate_minutes, 0) end as minutes
+                        from todo_revisions
+                        join todos on todos.id = todo_revisions.todo_id
+                        where todos.workspace_id = $1
+                    ),
+                    changes as (
+                        select day,
+                            minutes - coalesce(
+                                lag(minutes) over (partition by todo_id order by rev), 0
+                            ) as delta
+                        from remaining
+                        union all
+                        select day::date, 0
+                        from generate_series($2::date, $3::date, '1 day'::interval) as day
+                    ),
+                    totals as (
+                        select day, sum(sum(delta)) over (order by day) as remaining_minutes
+                        from changes
+                        where day <= $3
+                        group by day
+                    )
+                    select day, remaining_minutes::bigint as remaining_minutes from totals
+                    where day >= $2
+                    order by day
+                "#,
+                )
+                .bind(self.workspace_id)
+                .bind(from)
+                .bind(to)
+                .fetch_all(&self.pool),
+            )
+            .await?;
+
+        Ok(days)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn board_positions(&self) -> anyhow::Result<Positions> {
+        self.slow_queries
+            .time(
+                "board_positions.all",
+                &[&self.workspace_id],
+                select_board_positions(&self.pool, self.workspace_id),
+            )
+            .await
+    }
+    #[tracing::instrument(skip_all)]
+    async fn due_by(&self, user_id: i32, by: NaiveDate) -> anyhow::Result<Vec<(i32, Todo)>> {
+        let query = format!(
+            r#"
+            {}
+            where not todos.completed and todos.due_date <= $2
+                and coalesce(todos.assignee_id, todos.owner_id) = $1
+            group by todos.id
+            order by todos.due_date, todos.id
+        "#,
+            SELECT_TODOS_WITH_LABELS
+        );
+        let rows = self
+            .slow_queries
+            .time(
+                "todos.due_by",
+                &[&user_id, &by],
+                sqlx::query_as::<_, TodoWithLabelsRow>(&query)
+                    .bind(user_id)
+                    .bind(by)
+                    .fetch_all(&self.pool),
+            )
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.workspace_id, Todo::from(row)))
+            .collect())
+    }
+}
+
+#[async_trait]
+impl TodoWriter for TodoRepositoryForDb {
+    #[tracing::instrument(skip_all)]
+    async fn create(&self, payload: CreateTodo) -> anyhow::Result<Todo> {
+        let mut tx = self.pool.begin().await?;
+        let todo = self.insert(&mut tx, payload).await?;
+        tx.commit().await?;
+
+        Ok(todo)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<Todo> {
+        let mut tx = self.pool.begin().await?;
+        let todo = self
+            .slow_queries
+            .time(
+                "todos.update",
+                &[&self.workspace_id, &id],
+                update_locked(&mut tx, self.workspace_id, id, None, payload, self.actor),
+            )
+            .await?;
+        tx.commit().await?;
+
+        Ok(todo)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn update_if(
+        &self,
+        id: i32,
+        expected: &Todo,
+        payload: UpdateTodo,
+    ) -> anyhow::Result<Todo> {
+        let mut tx = self.pool.begin().await?;
+        let todo = self
+            .slow_queries
+            .time(
+                "todos.update",
+                &[&self.workspace_id, &id],
+                update_locked(
+                    &mut tx,
+                    self.workspace_id,
+                    id,
+                    Some(expected),
+                    payload,
+                    self.actor,
+                ),
+            )
+            .await?;
+        tx.commit().await?;
+
+        Ok(todo)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        self.remove(&mut tx, id).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn revert(&self, id: i32, rev: i32) -> anyhow::Result<Todo> {
+        self.find(id).await?;
+        let revision = self
+            .slow_queries
+            .time(
+                "todos.revision",
+                &[&id, &rev],
+                sqlx::query_as::<_, TodoRevision>(
+                    r#"
+                    select todo_id, rev, text, completed, estimate_minutes, created_at from todo_revisions
+                    where todo_id=$1 and rev=$2
+                "#,
+                )
+                .bind(id)
+                .bind(rev)
+                .fetch_one(&self.pool),
+            )
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::RowNotFound => RepositoryError::NotFound(rev),
+                _ => RepositoryError::Unexpected(e.to_string()),
+            })?;
+
+        self.update(
+            id,
+            UpdateTodo {
+                text: Some(revision.text),
+                completed: Some(revision.completed),
+                labels: None,
+                assignee_id: None,
+                text_i18n: None,
+                estimate_minutes: Some(revision.estimate_minutes),
+                due_date: None,
+                description: None,
+                custom: None,
+            },
+        )
+        .await
+    }
+    #[tracing::instrument(skip_all)]
+    async fn delete_unused_labels(&self) -> anyhow::Result<Vec<i32>> {
+        let mut deleted: Vec<i32> = self
+            .slow_queries
+            .time(
+                "labels.delete_unused",
+                &[&self.workspace_id],
+                sqlx::query_as::<_, (i32,)>(
+                    r#"
+                    delete from labels
+                    where workspace_id = $1
+                        and not exists (
+                            select 1 from todo_labels where todo_labels.label_id = labels.id
+                        )
+                    returning id
+                "#,
+                )
+                .bind(self.workspace_id)
+                .fetch_all(&self.pool),
+            )
+            .await?
+            .into_iter()
+            .map(|(id,)| id)
+            .collect();
+        deleted.sort_unstable();
+
+        Ok(deleted)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn merge_labels(&self, source: i32, target: i32) -> anyhow::Result<Vec<Todo>> {
+        let mut tx = self.pool.begin().await?;
+        check_labels(&mut tx, self.workspace_id, &[source, target]).await?;
+        let ids: Vec<i32> = sqlx::query_as::<_, (i32,)>(
+            r#"
+            select distinct todo_id from todo_labels where label_id=$1 order by todo_id
+        "#,
+        )
+        .bind(source)
+        .fetch_all(&mut tx)
+        .await?
+        .into_iter()
+        .map(|(id,)| id)
+        .collect();
+        let before = select_todos(&mut tx, self.workspace_id, &ids).await?;
+        self.slow_queries
+            .time(
+                "labels.merge",
+                &[&source, &target],
+                sqlx::query(
+                    r#"
+                    update todo_labels set label_id=$2
+                    where label_id=$1
+                        and todo_id not in (select todo_id from todo_labels where label_id=$2)
+                "#,
+                )
+                .bind(source)
+                .bind(target)
+                .execute(&mut tx),
+            )
+            .await?;
+        // todos that carried both keep the target only
+        sqlx::query(
+            r#"
+            delete from todo_labels where label_id=$1
+        "#,
+        )
+        .bind(source)
+        .execute(&mut tx)
+        .await?;
+        sqlx::query(
+            r#"
+            delete from labels where id=$1 and workspace_id=$2
+        "#,
+        )
+        .bind(source)
+        .bind(self.workspace_id)
+        .execute(&mut tx)
+        .await?;
+        let moved = select_todos(&mut tx, self.workspace_id, &ids).await?;
+        let events = before
+            .iter()
+            .zip(&moved)
+            .flat_map(|(before, after)| DomainEvent::changed(before, after, self.actor))
+            .collect();
+        record_events(&mut tx, self.workspace_id, events).await?;
+        tx.commit().await?;
+
+        Ok(moved)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn snooze(&self, id: i32, until: Option<DateTime<Utc>>) -> anyhow::Result<Todo> {
+        let mut tx = self.pool.begin().await?;
+        let todo = self
+            .slow_queries
+            .time(
+                "todos.snooze",
+                &[&self.workspace_id, &id],
+                snooze_locked(&mut tx, self.workspace_id, id, until, self.actor),
+            )
+            .await?;
+        tx.commit().await?;
+
+        Ok(todo)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn unsnooze_due(&self) -> anyhow::Result<Vec<(i32, i32)>> {
+        let mut tx = self.pool.begin().await?;
+        let mut woken = self
+            .slow_queries
+            .time(
+                "todos.unsnooze_due",
+                &[],
+                sqlx::query_as::<_, (i32, i32)>(
+                    r#"
+                    update todos set snoozed_until = null where snoozed_until <= now()
+                    returning workspace_id, id
+                "#,
+                )
+                .fetch_all(&mut tx),
+            )
+            .await?;
+        woken.sort();
+        for &(workspace_id, id) in &woken {
+            let todo = self
+                .slow_queries
+                .time(
+                    "todos.find",
+                    &[&workspace_id, &id],
+                    select_todo(&mut tx, workspace_id, id),
+                )
+                .await?;
+            self.slow_queries
+                .time(
+                    "outbox.insert",
+                    &[&workspace_id],
+                    record_events(&mut tx, workspace_id, vec![DomainEvent::TodoUpdated(todo)]),
+                )
+                .await?;
+        }
+        tx.commit().await?;
+
+        Ok(woken)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn add_item(
+        &self,
+        id: i32,
+        payload: CreateChecklistItem,
+    ) -> anyhow::Result<ChecklistItem> {
+        let mut tx = self.pool.begin().await?;
+        let change = |items: &mut Vec<ChecklistItem>| {
+            let item = ChecklistItem {
+                id: 0,
+                text: payload.text,
+                completed: false,
+                position: 0,
+            };
+            Ok(Some(checklist::insert(items, item, payload.position)))
+        };
+        let item = self
+            .slow_queries
+            .time(
+                "checklist_items.add",
+                &[&self.workspace_id, &id],
+                change_checklist(&mut tx, self.workspace_id, id, self.actor, change),
+            )
+            .await?;
+        tx.commit().await?;
+
+        Ok(item.expect("the added item"))
+    }
+    #[tracing::instrument(skip_all)]
+    async fn update_item(
+        &self,
+        id: i32,
+        item_id: i32,
+        payload: UpdateChecklistItem,
+    ) -> anyhow::Result<ChecklistItem> {
+        let mut tx = self.pool.begin().await?;
+        let change =
+            |items: &mut Vec<ChecklistItem>| checklist::update(items, item_id, payload).map(Some);
+        let item = self
+            .slow_queries
+            .time(
+                "checklist_items.update",
+                &[&self.workspace_id, &id, &item_id],
+                change_checklist(&mut tx, self.workspace_id, id, self.actor, change),
+            )
+            .await?;
+        tx.commit().await?;
+
+        Ok(item.expect("the updated item"))
+    }
+    #[tracing::instrument(skip_all)]
+    async fn delete_item(&self, id: i32, item_id: i32) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let change =
+            |items: &mut Vec<ChecklistItem>| checklist::remove(items, item_id).map(|()| None);
+        self.slow_queries
+            .time(
+                "checklist_items.delete",
+                &[&self.workspace_id, &id, &item_id],
+                change_checklist(&mut tx, self.workspace_id, id, self.actor, change),
+            )
+            .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn move_on_board(&self, id: i32, completed: bool, position: u32) -> anyhow::Result<Todo> {
+        let mut tx = self.pool.begin().await?;
+        let todo = self
+            .slow_queries
+            .time(
+                "todos.move_on_board",
+                &[&self.workspace_id, &id, &completed, &position],
+                move_locked(
+                    &mut tx,
+                    self.workspace_id,
+                    id,
+                    completed,
+                    position,
+                    self.actor,
+                ),
+            )
+            .await?;
+        tx.commit().await?;
+
+        Ok(todo)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn release_owned(
+        &self,
+        owner_id: i32,
+        release: OwnedTodos,
+    ) -> anyhow::Result<Vec<(i32, i32)>> {
+        let mut tx = self.pool.begin().await?;
+        let mut released = match release {
+            OwnedTodos::Delete => {
+                self.slow_queries
+                    .time(
+                        "todos.delete_owned_labels",
+                        &[&owner_id],
+                        sqlx::query(
+                            r#"
+                            delete from todo_labels where todo_id in
+                                (select id from todos where owner_id=$1)
+                        "#,
+                        )
+                        .bind(owner_id)
+                        .execute(&mut tx),
+                    )
+                    .await?;
+                self.slow_queries
+                    .time(
+                        "todos.delete_owned",
+                        &[&owner_id],
+                        sqlx::query_as::<_, (i32, i32)>(
+                            r#"
+                            delete from todos where owner_id=$1
+                            returning workspace_id, id
+                        "#,
+                        )
+                        .bind(owner_id)
+                        .fetch_all(&mut tx),
+                    )
+                    .await?
+            }
+            OwnedTodos::TransferTo(new_owner) => {
+                self.slow_queries
+                    .time(
+                        "todos.transfer_owned",
+                        &[&owner_id, &new_owner],
+                        sqlx::query_as::<_, (i32, i32)>(
+                            r#"
+                            update todos set owner_id=$2 where owner_id=$1
+                            returning workspace_id, id
+                        "#,
+                        )
+                        .bind(owner_id)
+                        .bind(new_owner)
+                        .fetch_all(&mut tx),
+                    )
+                    .await?
+            }
+        };
+        released.sort();
+        for &(workspace_id, id) in &released {
+            let event = match release {
+                OwnedTodos::Delete => DomainEvent::TodoDeleted(id),
+                OwnedTodos::TransferTo(_) => DomainEvent::TodoUpdated(
+                    self.slow_queries
+                        .time(
+                            "todos.find",
+                            &[&workspace_id, &id],
+                            select_todo(&mut tx, workspace_id, id),
+                        )
+                        .await?,
+                ),
+            };
+            self.slow_queries
+                .time(
+                    "outbox.insert",
+                    &[&workspace_id],
+                    record_events(&mut tx, workspace_id, vec![event]),
+                )
+                .await?;
+        }
+        tx.commit().await?;
+
+        Ok(released)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn apply(&self, changes: Vec<TodoChange>) -> anyhow::Result<Vec<Option<Todo>>> {
+        let mut tx = self.pool.begin().await?;
+        let mut changed = Vec::with_capacity(changes.len());
+        for change in changes {
+            changed.push(match change {
+                TodoChange::Create(payload) => Some(self.insert(&mut tx, payload).await?),
+                TodoChange::Update(id, payload) => Some(
+                    self.slow_queries
+                        .time(
+                            "todos.update",
+                            &[&self.workspace_id, &id],
+                            update_locked(
+                                &mut tx,
+                                self.workspace_id,
+                                id,
+                                None,
+                                payload,
+                                self.actor,
+                            ),
+                        )
+                        .await?,
+                ),
+                TodoChange::Delete(id) => {
+                    self.remove(&mut tx, id).await?;
+                    None
+                }
+            });
+        }
+        tx.commit().await?;
+
+        Ok(changed)
+    }
+    fn acting_as(&self, user_id: Option<i32>) -> Self {
+        Self {
+            actor: user_id,
+            ..self.clone()
+        }
+    }
+    #[tracing::instrument(skip_all)]
+    async fn claim_outbox(&self, limit: u32) -> anyhow::Result<Vec<OutboxEntry>> {
+        self.slow_queries
+            .time("outbox.claim", &[&limit], claim_outbox(&self.pool, limit))
+            .await
+    }
+    #[tracing::instrument(skip_all)]
+    async fn mark_sent(&self, ids: &[i64]) -> anyhow::Result<()> {
+        self.slow_queries
+            .time("outbox.mark_sent", &[&ids], mark_sent(&self.pool, ids))
+            .await
+    }
+}
+
+pub(super) async fn claim_outbox(pool: &PgPool, limit: u32) -> anyhow::Result<Vec<OutboxEntry>> {
+    let mut rows = sqlx::query_as::<_, (i64, i32, Json<DomainEvent>)>(
+        r#"
+        update outbox set claimed_until = now() + $2 * interval '1 second'
+        where id in (
+            select id from outbox
+            where sent_at is null and (claimed_until is null or claimed_until < now())
+            order by id
+            limit $1
+            for update skip locked
+        )
+        returning id, workspace_id, event
+    "#,
+    )
+    .bind(limit as i64)
+    .bind(LEASE_SECS as f64)
+    .fetch_all(pool)
+    .await?;
+    rows.sort_by_key(|(id, _, _)| *id);
+
+    Ok(rows
+        .into_iter()
+        .map(|(id, workspace_id, event)| OutboxEntry {
+            id,
+            event: WorkspaceEvent {
+                workspace_id,
+                event: event.0,
+            },
+        })
+        .collect())
+}
+
+pub(super) async fn mark_sent(pool: &PgPool, ids: &[i64]) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+        update outbox set sent_at = now() where id = any($1)
+    "#,
+    )
+    .bind(ids)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub(super) async fn record_events(
+    tx: &mut Transaction<'_, Postgres>,
+    workspace_id: i32,
+    events: Vec<DomainEvent>,
+) -> anyhow::Result<()> {
+    for event in events {
+        sqlx::query(
+            r#"
+            insert into outbox (workspace_id, event) values ($1, $2)
+        "#,
+        )
+        .bind(workspace_id)
+        .bind(Json(event))
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Locks the todo row for the rest of `tx`, then applies `payload` when the
+/// todo still matches `expected` (if given).
+async fn update_locked(
+    tx: &mut Transaction<'_, Postgres>,
+    workspace_id: i32,
+    id: i32,
+    expected: Option<&Todo>,
+    payload: UpdateTodo,
+    by: Option<i32>,
+) -> anyhow::Result<Todo> {
+    let (old_text, old_completed, old_assignee, Json(old_text_i18n)) =
+        sqlx::query_as::<_, (String, bool, Option<i32>, Json<BTreeMap<String, String>>)>(
+            r#"
+        select text, completed, assignee_id, text_i18n from todos
+        where id=$1 and workspace_id=$2 for update
+    "#,
+        )
+        .bind(id)
+        .bind(workspace_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+    let before = select_todo(&mut *tx, workspace_id, id).await?;
+    if expected.is_some_and(|expected| before != *expected) {
+        return Err(RepositoryError::Conflict(id).into());
+    }
+    let custom = payload.merged_custom(&before.custom);
+    sqlx::query(
+        r#"
+        update todos set text=$1, completed=$2, assignee_id=$3, text_i18n=$4,
+            estimate_minutes=$5, due_date=$6, description=$7, custom=$8
+        where id=$9
+    "#,
+    )
+    .bind(payload.text.unwrap_or(old_text))
+    .bind(payload.completed.unwrap_or(old_completed))
+    .bind(payload.assignee_id.unwrap_or(old_assignee))
+    .bind(Json(payload.text_i18n.unwrap_or(old_text_i18n)))
+    .bind(payload.estimate_minutes.unwrap_or(before.estimate_minutes))
+    .bind(payload.due_date.unwrap_or(before.due_date))
+    .bind(
+        payload
+            .description
+            .unwrap_or_else(|| before.description.clone()),
+    )
+    .bind(Json(custom))
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+    if let Some(labels) = &payload.labels {
+        replace_labels(tx, workspace_id, id, labels).await?;
+    }
+    let todo = select_todo(&mut *tx, workspace_id, id).await?;
+    insert_revision(tx, &todo).await?;
+    record_events(tx, workspace_id, DomainEvent::changed(&before, &todo, by)).await?;
+
+    Ok(todo)
+}
+
+/// Locks the todo row for the rest of `tx`.
+async fn lock_todo(
+    tx: &mut Transaction<'_, Postgres>,
+    workspace_id: i32,
+    id: i32,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+        select id from todos where id=$1 and workspace_id=$2 for update
+    "#,
+    )
+    .bind(id)
+    .bind(workspace_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(RepositoryError::NotFound(id))?;
+
+    Ok(())
+}
+
+/// Locks the todo row for the rest of `tx` and sets when it wakes.
+async fn snooze_locked(
+    tx: &mut Transaction<'_, Postgres>,
+    workspace_id: i32,
+    id: i32,
+    until: Option<DateTime<Utc>>,
+    by: Option<i32>,
+) -> anyhow::Result<Todo> {
+    lock_todo(tx, workspace_id, id).await?;
+    let before = select_todo(&mut *tx, workspace_id, id).await?;
+    sqlx::query(
+        r#"
+        update todos set snoozed_until=$1 where id=$2
+    "#,
+    )
+    .bind(until)
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+    let todo = select_todo(&mut *tx, workspace_id, id).await?;
+    record_events(tx, workspace_id, DomainEvent::changed(&before, &todo, by)).await?;
+
+    Ok(todo)
+}
+
+/// Locks the todo row for the rest of `tx`, moves it to the other board
+/// column when `completed` says so, then renumbers its column.
+async fn move_locked(
+    tx: &mut Transaction<'_, Postgres>,
+    workspace_id: i32,
+    id: i32,
+    completed: bool,
+    position: u32,
+    by: Option<i32>,
+) -> anyhow::Result<Todo> {
+    lock_todo(tx, workspace_id, id).await?;
+    let mut todo = select_todo(&mut *tx, workspace_id, id).await?;
+    if todo.completed != completed {
+        let payload = UpdateTodo {
+            text: None,
+            completed: Some(completed),
+            labels: None,
+            assignee_id: None,
+            text_i18n: None,
+            estimate_minutes: None,
+            due_date: None,
+            description: None,
+            custom: None,
+        };
+        todo = update_locked(tx, workspace_id, id, None, payload, by).await?;
+    }
+    let column = sqlx::query_as::<_, (i32, Option<i32>)>(
+        r#"
+        select todos.id, board_positions.position from todos
+        left join board_positions on board_positions.todo_id = todos.id
+        where todos.workspace_id=$1 and todos.completed=$2
+    "#,
+    )
+    .bind(workspace_id)
+    .bind(completed)
+    .fetch_all(&mut *tx)
+    .await?;
+    place_on_board(tx, workspace_id, &board::reorder(column, id, position)).await?;
+
+    Ok(todo)
+}
+
+pub(super) async fn select_board_positions<'e, E: Executor<'e, Database = Postgres>>(
+    executor: E,
+    workspace_id: i32,
+) -> anyhow::Result<Positions> {
+    let positions = sqlx::query_as::<_, (i32, i32)>(
+        r#"
+        select todo_id, position from board_positions where workspace_id=$1
+    "#,
+    )
+    .bind(workspace_id)
+    .fetch_all(executor)
+    .await?;
+
+    Ok(positions.into_iter().collect())
+}
+
+/// Numbers a board column from 0 in the order of `ids`.
+pub(super) async fn place_on_board(
+    tx: &mut Transaction<'_, Postgres>,
+    workspace_id: i32,
+    ids: &[i32],
+) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+        insert into board_positions (todo_id, workspace_id, position)
+        select placed.id, $1, (placed.n - 1)::integer
+        from unnest($2::integer[]) with ordinality as placed(id, n)
+        on conflict (todo_id) do update set position = excluded.position
+    "#,
+    )
+    .bind(workspace_id)
+    .bind(ids)
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+async fn select_items<'e, E: Executor<'e, Database = Postgres>>(
+    executor: E,
+    todo_id: i32,
+) -> anyhow::Result<Vec<ChecklistItem>> {
+    let items = sqlx::query_as::<_, ChecklistItem>(
+        r#"
+        select id, text, completed, position from checklist_items
+        where todo_id=$1 order by position, id
+    "#,
+    )
+    .bind(todo_id)
+    .fetch_all(executor)
+    .await?;
+
+    Ok(items)
+}
+
+/// Locks the todo row for the rest of `tx`, lets `change` rework its
+/// checklist as `checklist` does in memory and writes back what changed,
+/// recording the todo as changed. New items come with id 0; the item
+/// `change` returns is answered as saved.
+async fn change_checklist(
+    tx: &mut Transaction<'_, Postgres>,
+    workspace_id: i32,
+    id: i32,
+    by: Option<i32>,
+    change: impl FnOnce(&mut Vec<ChecklistItem>) -> Result<Option<ChecklistItem>, RepositoryError>,
+) -> anyhow::Result<Option<ChecklistItem>> {
+    lock_todo(tx, workspace_id, id).await?;
+    let before = select_todo(&mut *tx, workspace_id, id).await?;
+    let saved = select_items(&mut *tx, id).await?;
+    let mut items = saved.clone();
+    let changed = change(&mut items)?;
+
+    let kept: Vec<i32> = items.iter().map(|item| item.id).collect();
+    sqlx::query(
+        r#"
+        delete from checklist_items where todo_id=$1 and id <> all($2)
+    "#,
+    )
+    .bind(id)
+    .bind(kept)
+    .execute(&mut *tx)
+    .await?;
+    for item in items.iter_mut().filter(|item| !saved.contains(item)) {
+        if item.id == 0 {
+            let (item_id,) = sqlx::query_as::<_, (i32,)>(
+                r#"
+                insert into checklist_items (todo_id, text, completed, position)
+                values ($1, $2, $3, $4)
+                returning id
+            "#,
+            )
+            .bind(id)
+            .bind(item.text.clone())
+            .bind(item.completed)
+            .bind(item.position)
+            .fetch_one(&mut *tx)
+            .await?;
+            item.id = item_id;
+        } else {
+            sqlx::query(
+                r#"
+                update checklist_items set text=$2, completed=$3, position=$4 where id=$1
+            "#,
+            )
+            .bind(item.id)
+            .bind(item.text.clone())
+            .bind(item.completed)
+            .bind(item.position)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    let todo = select_todo(&mut *tx, workspace_id, id).await?;
+    record_events(tx, workspace_id, DomainEvent::changed(&before, &todo, by)).await?;
+
+    Ok(changed.map(|changed| items[changed.position as usize].clone()))
+}
+
+/// Fails with NotFound on a label id that is unknown in the workspace.
+pub(super) async fn check_labels(
+    tx: &mut Transaction<'_, Postgres>,
+    workspace_id: i32,
+    labels: &[i32],
+) -> anyhow::Result<()> {
+    if let Some(missing) = missing_labels(tx, workspace_id, labels).await?.first() {
+        return Err(RepositoryError::NotFound(*missing).into());
+    }
+
+    Ok(())
+}
+
+/// Fails with MissingLabels, naming each of `labels` unknown in the
+/// workspace, before a todo is created with them.
+pub(super) async fn check_new_labels(
+    tx: &mut Transaction<'_, Postgres>,
+    workspace_id: i32,
+    labels: &[i32],
+) -> anyhow::Result<()> {
+    let missing = missing_labels(tx, workspace_id, labels).await?;
+    if !missing.is_empty() {
+        return Err(RepositoryError::MissingLabels(missing).into());
+    }
+
+    Ok(())
+}
+
+/// The ids of `labels` unknown in the workspace, ascending.
+async fn missing_labels(
+    tx: &mut Transaction<'_, Postgres>,
+    workspace_id: i32,
+    labels: &[i32],
+) -> anyhow::Result<Vec<i32>> {
+    let known: Vec<(i32,)> = sqlx::query_as(
+        r#"
+        select id from labels where id = any($1) and workspace_id = $2
+    "#,
+    )
+    .bind(labels)
+    .bind(workspace_id)
+    .fetch_all(&mut *tx)
+    .await?;
+    let mut missing: Vec<i32> = labels
+        .iter()
+        .copied()
+        .filter(|id| !known.contains(&(*id,)))
+        .collect();
+    missing.sort_unstable();
+    missing.dedup();
+
+    Ok(missing)
+}
+
+/// Attach exactly `labels` to the todo, failing with NotFound on an id that is
+/// unknown in the workspace.
+async fn replace_labels(
+    tx: &mut Transaction<'_, Postgres>,
+    workspace_id: i32,
+    todo_id: i32,
+    labels: &[i32],
+) -> anyhow::Result<()> {
+    check_labels(tx, workspace_id, labels).await?;
+
+    sqlx::query(
+        r#"
+        delete from todo_labels where todo_id=$1
+    "#,
+    )
+    .bind(todo_id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        r#"
+        insert into todo_labels (todo_id, label_id)
+        select $1, label_id from unnest($2::integer[]) as label_id
+        group by label_id
+    "#,
+    )
+    .bind(todo_id)
+    .bind(labels)
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+async fn insert_revision(tx: &mut Transaction<'_, Postgres>, todo: &Todo) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+        insert into todo_revisions (todo_id, rev, text, completed, estimate_minutes)
+        select $1, coalesce(max(rev), 0) + 1, $2, $3, $4
+        from todo_revisions where todo_id=$1
+    "#,
+    )
+    .bind(todo.id)
+    .bind(todo.text.clone())
+    .bind(todo.completed)
+    .bind(todo.estimate_minutes)
+    .execute(tx)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        repositories::{
+            filter::Term,
+            label::{CreateLabel, LabelRepository, LabelRepositoryForDb},
+        },
+        test_db::TestDb,
+    };
+
+    #[tokio::test]
+    async fn todo_curd_scenario() {
+        let text = "todo text".to_string();
+        let id = 1;
+
+        // create
+        let repository = TodoRepositoryForMemory::new();
+        let todo = repository
+            .create(CreateTodo::new(text.clone()))
+            .await
+            .expect("failed");
+        assert!(!todo.uuid.is_nil());
+        let expected = Todo {
+            uuid: todo.uuid,
+            ..Todo::new(id, text.clone())
+        };
+        assert_eq!(todo, expected);
+
+        // find
+        let todo = repository.find(id).await.unwrap();
+        assert_eq!(todo, expected);
+
+        // all
+        let todos = repository.all().await.unwrap();
+        assert_eq!(todos, vec![expected.clone()]);
+
+        // count / exists
+        assert_eq!(repository.count().await.unwrap(), 1);
+        assert!(repository.exists(id).await.unwrap());
+        assert!(!repository.exists(id + 1).await.unwrap());
+        assert_eq!(repository.id_of(todo.uuid).await.unwrap(), Some(id));
+        assert_eq!(repository.id_of(Uuid::now_v7()).await.unwrap(), None);
+
+        // update
+        let text = "update todo".to_string();
+        let todo = repository
+            .update(
+                id,
+                UpdateTodo {
+                    text: Some(text.clone()),
+                    completed: None,
+                    labels: None,
+                    assignee_id: None,
+                    text_i18n: None,
+                    estimate_minutes: None,
+                    due_date: None,
+                    description: None,
+                    custom: None,
+                },
+            )
+            .await
+            .unwrap();
+
+        let expected = Todo { text, ..expected };
 
-        // create
-        let repository = TodoRepositoryForMemory::new();
-        let todo = repository
-            .create(CreateTodo { text: text.clone() })
-            .await
-            .expect("failed");
         assert_eq!(todo, expected);
 
-        // find
-        let todo = repository.find(id).await.unwrap();
-        assert_eq!(todo, expected);
+        // revisions
+        let revisions = repository.revisions(id).await.unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(revisions[0].text, "todo text");
+        assert_eq!(revisions[1].rev, 2);
+
+        // revert
+        let todo = repository.revert(id, 1).await.unwrap();
+        assert_eq!(todo.text, "todo text");
+        assert_eq!(repository.revisions(id).await.unwrap().len(), 3);
+
+        // delete
+        repository.delete(id).await.unwrap();
+        let todo = repository.find(id).await;
+        assert!(todo.is_err());
+    }
+
+    #[tokio::test]
+    async fn should_not_reuse_id_after_delete() {
+        let repository = TodoRepositoryForMemory::new();
+        let first = repository
+            .create(CreateTodo::new("first".to_string()))
+            .await
+            .unwrap();
+        let second = repository
+            .create(CreateTodo::new("second".to_string()))
+            .await
+            .unwrap();
+        repository.delete(first.id).await.unwrap();
+
+        let third = repository
+            .create(CreateTodo::new("third".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(third.id, 3);
+
+        // the second todo must survive the create
+        let todo = repository.find(second.id).await.unwrap();
+        assert_eq!(todo.text, "second");
+        assert_eq!(repository.all().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn should_apply_changes_all_or_none() {
+        let repository = TodoRepositoryForMemory::new();
+        let first = repository
+            .create(CreateTodo::new("first".to_string()))
+            .await
+            .unwrap();
+        let sent: Vec<i64> = repository
+            .claim_outbox(10)
+            .await
+            .unwrap()
+            .iter()
+            .map(|entry| entry.id)
+            .collect();
+        repository.mark_sent(&sent).await.unwrap();
+
+        let result = repository
+            .apply(vec![
+                TodoChange::Create(CreateTodo::new("second".to_string())),
+                TodoChange::Delete(first.id),
+                TodoChange::Update(first.id, UpdateTodo::default()),
+            ])
+            .await;
+        assert!(result.is_err());
+        assert_eq!(repository.all().await.unwrap(), vec![first.clone()]);
+        assert_eq!(repository.revisions(first.id).await.unwrap().len(), 1);
+        assert_eq!(repository.claim_outbox(10).await.unwrap(), vec![]);
+
+        let changed = repository
+            .apply(vec![
+                TodoChange::Create(CreateTodo::new("second".to_string())),
+                TodoChange::Update(
+                    first.id,
+                    UpdateTodo {
+                        completed: Some(true),
+                        ..UpdateTodo::default()
+                    },
+                ),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(changed[0].as_ref().unwrap().text, "second");
+        assert!(changed[1].as_ref().unwrap().completed);
+        let kinds: Vec<&str> = repository
+            .claim_outbox(10)
+            .await
+            .unwrap()
+            .iter()
+            .map(|entry| entry.event.event.kind())
+            .collect();
+        assert_eq!(
+            kinds,
+            vec!["todo_created", "todo_updated", "todo_completed"]
+        );
+    }
+
+    #[tokio::test]
+    async fn should_page_todos_newest_first() {
+        let repository = TodoRepositoryForMemory::new();
+        for i in 1..=5 {
+            repository
+                .create(CreateTodo::new(format!("todo {}", i)))
+                .await
+                .unwrap();
+        }
+
+        let first = repository.page(PageCursor::After(None), 2).await.unwrap();
+        let ids: Vec<i32> = first.items.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![5, 4]);
+        assert_eq!((first.next, first.prev), (Some(4), None));
+
+        let last = repository
+            .page(PageCursor::After(Some(2)), 2)
+            .await
+            .unwrap();
+        let ids: Vec<i32> = last.items.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![1]);
+        assert_eq!((last.next, last.prev), (None, Some(1)));
+    }
+
+    #[tokio::test]
+    async fn should_page_backwards_from_the_end() {
+        let repository = TodoRepositoryForMemory::new();
+        for i in 1..=5 {
+            repository
+                .create(CreateTodo::new(format!("todo {}", i)))
+                .await
+                .unwrap();
+        }
+
+        let last = repository.page(PageCursor::Before(None), 2).await.unwrap();
+        let ids: Vec<i32> = last.items.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![2, 1]);
+        assert_eq!((last.next, last.prev), (None, Some(2)));
+
+        let newer = repository
+            .page(PageCursor::Before(Some(2)), 2)
+            .await
+            .unwrap();
+        let ids: Vec<i32> = newer.items.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![4, 3]);
+        assert_eq!((newer.next, newer.prev), (Some(3), Some(4)));
+
+        let first = repository
+            .page(PageCursor::Before(Some(4)), 2)
+            .await
+            .unwrap();
+        let ids: Vec<i32> = first.items.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![5]);
+        assert_eq!((first.next, first.prev), (Some(5), None));
+    }
+
+    #[tokio::test]
+    async fn should_reject_update_if_todo_changed() {
+        let repository = TodoRepositoryForMemory::new();
+        let todo = repository
+            .create(CreateTodo::new("original".to_string()))
+            .await
+            .unwrap();
+        let payload = UpdateTodo {
+            text: None,
+            completed: Some(true),
+            labels: None,
+            assignee_id: None,
+            text_i18n: None,
+            estimate_minutes: None,
+            due_date: None,
+            description: None,
+            custom: None,
+        };
+
+        let updated = repository
+            .update_if(todo.id, &todo, payload.clone())
+            .await
+            .unwrap();
+        let result = repository.update_if(todo.id, &todo, payload).await;
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Conflict(_))
+        ));
+        assert_eq!(repository.find(todo.id).await.unwrap(), updated);
+    }
+
+    #[tokio::test]
+    async fn should_count_labels_and_delete_unused_ones() {
+        let labels = LabelRepositoryForMemory::new();
+        let work = labels
+            .create(CreateLabel::new("work".to_string()))
+            .await
+            .unwrap();
+        let home = labels
+            .create(CreateLabel::new("home".to_string()))
+            .await
+            .unwrap();
+        let elsewhere = labels
+            .in_workspace(2)
+            .create(CreateLabel::new("elsewhere".to_string()))
+            .await
+            .unwrap();
+        let repository = TodoRepositoryForMemory::with_labels(labels.clone());
+        for _ in 0..2 {
+            repository
+                .create(CreateTodo {
+                    labels: vec![work.id],
+                    ..CreateTodo::new("report".to_string())
+                })
+                .await
+                .unwrap();
+        }
+
+        let stats = repository.label_stats().await.unwrap();
+        let counts: Vec<(i32, i64)> = stats
+            .iter()
+            .map(|stats| (stats.label.id, stats.todos))
+            .collect();
+        assert_eq!(counts, vec![(work.id, 2), (home.id, 0)]);
+
+        assert_eq!(
+            repository.delete_unused_labels().await.unwrap(),
+            vec![home.id]
+        );
+        assert_eq!(labels.all().await.unwrap(), vec![work]);
+        assert_eq!(labels.in_workspace(2).all().await.unwrap(), vec![elsewhere]);
+        assert!(repository.delete_unused_labels().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn should_merge_labels() {
+        let labels = LabelRepositoryForMemory::new();
+        let work = labels
+            .create(CreateLabel::new("work".to_string()))
+            .await
+            .unwrap();
+        let typo = labels
+            .create(CreateLabel::new("Work".to_string()))
+            .await
+            .unwrap();
+        let repository = TodoRepositoryForMemory::with_labels(labels.clone());
+        let both = repository
+            .create(CreateTodo {
+                labels: vec![work.id, typo.id],
+                ..CreateTodo::new("report".to_string())
+            })
+            .await
+            .unwrap();
+        let only_typo = repository
+            .create(CreateTodo {
+                labels: vec![typo.id],
+                ..CreateTodo::new("meeting".to_string())
+            })
+            .await
+            .unwrap();
+        repository.claim_outbox(10).await.unwrap();
+
+        let moved = repository.merge_labels(typo.id, work.id).await.unwrap();
+        let ids: Vec<i32> = moved.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![both.id, only_typo.id]);
+        for id in ids {
+            let todo = repository.find(id).await.unwrap();
+            assert_eq!(todo.labels, vec![work.clone()]);
+        }
+        assert_eq!(labels.all().await.unwrap(), vec![work.clone()]);
+        assert_eq!(repository.claim_outbox(10).await.unwrap().len(), 2);
+
+        let missing = repository.merge_labels(typo.id, work.id).await;
+        assert!(matches!(
+            missing.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_filter_listings_by_completion_and_labels() {
+        let labels = LabelRepositoryForMemory::new();
+        let work = labels
+            .create(CreateLabel::new("work".to_string()))
+            .await
+            .unwrap();
+        let home = labels
+            .create(CreateLabel::new("home".to_string()))
+            .await
+            .unwrap();
+        let repository = TodoRepositoryForMemory::with_labels(labels);
+        let report = repository
+            .create(CreateTodo {
+                labels: vec![work.id, home.id],
+                ..CreateTodo::new("report".to_string())
+            })
+            .await
+            .unwrap();
+        repository
+            .create(CreateTodo {
+                labels: vec![work.id],
+                ..CreateTodo::new("slides".to_string())
+            })
+            .await
+            .unwrap();
+        let filter = |expr: &str| TodoFilter {
+            expr: Some(expr.parse().unwrap()),
+            ..TodoFilter::default()
+        };
+
+        let filtered = repository.with_filter(filter("completed:false label:work label:home"));
+        assert_eq!(filtered.all().await.unwrap(), vec![report.clone()]);
+        assert_eq!(filtered.count().await.unwrap(), 1);
+        let either = repository.with_filter(filter("label:home OR NOT label:work"));
+        assert_eq!(either.all().await.unwrap(), vec![report.clone()]);
+        let done = repository.with_filter(filter("completed:true"));
+        assert_eq!(done.count().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn should_rank_search_matches_first() {
+        let repository = TodoRepositoryForMemory::new();
+        for text in [
+            "buy groceries",
+            "call the grocer",
+            "groceries",
+            "groceries list",
+            "grocries",
+        ] {
+            repository
+                .create(CreateTodo::new(text.to_string()))
+                .await
+                .unwrap();
+        }
+        let searched = repository.with_filter(TodoFilter {
+            search: Some(Search {
+                text: "grocries".to_string(),
+                threshold: 0.5,
+            }),
+            ..TodoFilter::default()
+        });
+
+        // the typo itself ranks first, equally good matches newest first
+        let ids: Vec<i32> = searched
+            .all()
+            .await
+            .unwrap()
+            .iter()
+            .map(|todo| todo.id)
+            .collect();
+        assert_eq!(ids, vec![5, 4, 3, 1]);
+        assert_eq!(searched.count().await.unwrap(), 4);
+
+        let first = searched.page(PageCursor::After(None), 2).await.unwrap();
+        let ids: Vec<i32> = first.items.iter().map(|todo| todo.id).collect();
+        assert_eq!((ids, first.next), (vec![5, 4], Some(4)));
+        let second = searched.page(PageCursor::After(Some(4)), 2).await.unwrap();
+        let ids: Vec<i32> = second.items.iter().map(|todo| todo.id).collect();
+        assert_eq!((ids, second.prev), (vec![3, 1], Some(3)));
+        let back = searched.page(PageCursor::Before(Some(3)), 2).await.unwrap();
+        assert_eq!(back.items, first.items);
+    }
+
+    #[tokio::test]
+    async fn should_burn_down_open_estimates() {
+        let repository = TodoRepositoryForMemory::new();
+        for (text, estimate) in [("write", 60), ("review", 30)] {
+            repository
+                .create(CreateTodo {
+                    estimate_minutes: Some(estimate),
+                    ..CreateTodo::new(text.to_string())
+                })
+                .await
+                .unwrap();
+        }
+        repository
+            .in_workspace(2)
+            .create(CreateTodo {
+                estimate_minutes: Some(500),
+                ..CreateTodo::new("elsewhere".to_string())
+            })
+            .await
+            .unwrap();
+        let done = repository
+            .update(
+                1,
+                UpdateTodo {
+                    text: None,
+                    completed: Some(true),
+                    labels: None,
+                    assignee_id: None,
+                    text_i18n: None,
+                    estimate_minutes: None,
+                    due_date: None,
+                    description: None,
+                    custom: None,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(done.estimate_minutes, Some(60));
+
+        let today = Utc::now().date_naive();
+        let burndown = repository
+            .burndown(today - Duration::days(2), today)
+            .await
+            .unwrap();
+        let remaining: Vec<i64> = burndown.iter().map(|day| day.remaining_minutes).collect();
+        assert_eq!(remaining, vec![0, 0, 30]);
+        assert_eq!(burndown[2].day, today);
+    }
+
+    #[tokio::test]
+    async fn should_move_todos_on_board() {
+        let repository = TodoRepositoryForMemory::new();
+        for text in ["plan", "build", "ship"] {
+            repository
+                .create(CreateTodo::new(text.to_string()))
+                .await
+                .unwrap();
+        }
+        let moved = repository.move_on_board(3, false, 0).await.unwrap();
+        assert!(!moved.completed);
+        assert_eq!(
+            repository.board_positions().await.unwrap(),
+            Positions::from([(3, 0), (1, 1), (2, 2)])
+        );
+
+        let done = repository.move_on_board(1, true, 5).await.unwrap();
+        assert!(done.completed);
+        assert_eq!(repository.revisions(1).await.unwrap().len(), 2);
+        let mut todos = repository.all().await.unwrap();
+        board::sort(&mut todos, &repository.board_positions().await.unwrap());
+        let ids: Vec<i32> = todos.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![3, 2, 1]);
+        assert!(repository
+            .in_workspace(2)
+            .board_positions()
+            .await
+            .unwrap()
+            .is_empty());
 
-        // all
-        let todos = repository.all().await.unwrap();
-        assert_eq!(todos, vec![expected.clone()]);
+        repository.delete(3).await.unwrap();
+        assert!(!repository.board_positions().await.unwrap().contains_key(&3));
+        assert!(matches!(
+            repository
+                .move_on_board(3, false, 0)
+                .await
+                .unwrap_err()
+                .downcast_ref(),
+            Some(RepositoryError::NotFound(3))
+        ));
+    }
 
-        // update
-        let text = "update todo".to_string();
+    #[tokio::test]
+    async fn should_keep_checklist_with_progress() {
+        let repository = TodoRepositoryForMemory::new();
+        let todo = repository
+            .create(CreateTodo::new("trip".to_string()))
+            .await
+            .unwrap();
+        let pack = repository
+            .add_item(todo.id, CreateChecklistItem::new("pack".to_string()))
+            .await
+            .unwrap();
+        let book = repository
+            .add_item(
+                todo.id,
+                CreateChecklistItem {
+                    position: Some(0),
+                    ..CreateChecklistItem::new("book".to_string())
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!((book.position, pack.id), (0, 1));
+        repository
+            .update_item(
+                todo.id,
+                pack.id,
+                UpdateChecklistItem {
+                    text: None,
+                    completed: Some(true),
+                    position: None,
+                },
+            )
+            .await
+            .unwrap();
+
+        let progress = repository.find(todo.id).await.unwrap().progress;
+        assert_eq!(progress, Progress { done: 1, total: 2 });
+        let texts: Vec<String> = repository
+            .checklist(todo.id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|item| item.text)
+            .collect();
+        assert_eq!(texts, vec!["book", "pack"]);
+
+        repository.delete_item(todo.id, book.id).await.unwrap();
+        let err = repository.delete_item(todo.id, book.id).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(RepositoryError::NotFound(_))
+        ));
+        assert!(repository.in_workspace(2).checklist(todo.id).await.is_err());
+        let all = repository.all().await.unwrap();
+        assert_eq!(all[0].progress, Progress { done: 1, total: 1 });
+        // every change of the checklist is one of the todo too
+        let outbox = repository.claim_outbox(10).await.unwrap();
+        assert_eq!(outbox.len(), 5);
+
+        repository.delete(todo.id).await.unwrap();
+        assert!(repository.checklist(todo.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn should_hide_snoozed_todos_until_they_wake() {
+        let repository = TodoRepositoryForMemory::new();
         let todo = repository
+            .create(CreateTodo::new("later".to_string()))
+            .await
+            .unwrap();
+        let other = repository
+            .in_workspace(2)
+            .create(CreateTodo::new("elsewhere".to_string()))
+            .await
+            .unwrap();
+        let awake = repository.with_filter(TodoFilter {
+            hide_snoozed: true,
+            ..TodoFilter::default()
+        });
+
+        let snoozed = repository
+            .snooze(todo.id, Some(Utc::now() + Duration::hours(1)))
+            .await
+            .unwrap();
+        assert!(snoozed.is_snoozed(Utc::now()));
+        assert!(awake.all().await.unwrap().is_empty());
+        assert_eq!(awake.count().await.unwrap(), 0);
+        assert_eq!(repository.all().await.unwrap(), vec![snoozed.clone()]);
+        assert_eq!(repository.find(todo.id).await.unwrap(), snoozed);
+        assert!(repository.unsnooze_due().await.unwrap().is_empty());
+
+        // over, but not yet woken: listings show it already
+        let past = Utc::now() - Duration::seconds(1);
+        repository.snooze(todo.id, Some(past)).await.unwrap();
+        repository
+            .in_workspace(2)
+            .snooze(other.id, Some(past))
+            .await
+            .unwrap();
+        assert_eq!(awake.count().await.unwrap(), 1);
+        assert_eq!(
+            repository.unsnooze_due().await.unwrap(),
+            vec![(DEFAULT_WORKSPACE_ID, todo.id), (2, other.id)]
+        );
+        assert_eq!(repository.find(todo.id).await.unwrap(), todo);
+
+        let events: Vec<&str> = repository
+            .claim_outbox(10)
+            .await
+            .unwrap()
+            .iter()
+            .map(|entry| entry.event.event.kind())
+            .collect();
+        assert_eq!(
+            events
+                .iter()
+                .filter(|kind| **kind == "todo_updated")
+                .count(),
+            5
+        );
+        let err = repository.snooze(other.id, None).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(RepositoryError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_find_open_duplicates_by_normalized_text() {
+        let repository = TodoRepositoryForMemory::new();
+        let milk = repository
+            .create(CreateTodo::new("Buy milk".to_string()))
+            .await
+            .unwrap();
+        repository
+            .create(CreateTodo::new("buy  milk".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(normalize_text("  Buy\tMILK "), "buy milk");
+
+        let duplicate = repository.find_open_duplicate(" BUY milk").await.unwrap();
+        assert_eq!(duplicate, Some(milk.clone()));
+        assert_eq!(
+            repository.find_open_duplicate("buy milk!").await.unwrap(),
+            None
+        );
+        let other = repository.in_workspace(2);
+        assert_eq!(other.find_open_duplicate("buy milk").await.unwrap(), None);
+
+        for todo in repository.all().await.unwrap() {
+            repository
+                .update(
+                    todo.id,
+                    UpdateTodo {
+                        text: None,
+                        completed: Some(true),
+                        labels: None,
+                        assignee_id: None,
+                        text_i18n: None,
+                        estimate_minutes: None,
+                        due_date: None,
+                        description: None,
+                        custom: None,
+                    },
+                )
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            repository.find_open_duplicate("buy milk").await.unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn should_filter_listings_by_assignee() {
+        let repository = TodoRepositoryForMemory::new();
+        let mine = repository
+            .create(CreateTodo {
+                assignee_id: Some(7),
+                ..CreateTodo::new("mine".to_string())
+            })
+            .await
+            .unwrap();
+        let other = repository
+            .create(CreateTodo::new("other".to_string()))
+            .await
+            .unwrap();
+        let assigned = repository.with_filter(TodoFilter {
+            assignee_id: Some(7),
+            ..TodoFilter::default()
+        });
+
+        assert_eq!(assigned.all().await.unwrap(), vec![mine.clone()]);
+        assert_eq!(assigned.count().await.unwrap(), 1);
+        // lookups by id are not narrowed
+        assert_eq!(assigned.find(other.id).await.unwrap(), other);
+
+        let unassigned = repository
             .update(
-                id,
+                mine.id,
                 UpdateTodo {
-                    text: Some(text.clone()),
+                    text: None,
                     completed: None,
+                    labels: None,
+                    assignee_id: Some(None),
+                    text_i18n: None,
+                    estimate_minutes: None,
+                    due_date: None,
+                    description: None,
+                    custom: None,
                 },
             )
             .await
             .unwrap();
+        assert_eq!(unassigned.assignee_id, None);
+        assert!(assigned.all().await.unwrap().is_empty());
+    }
 
-        let expected = Todo {
-            id,
-            text,
-            completed: false,
+    #[tokio::test]
+    async fn should_find_todos_due_for_assignee_or_owner() {
+        let repository = TodoRepositoryForMemory::new();
+        let today = Utc::now().date_naive();
+        let due = |text: &str, days: i64, owner_id: i32, assignee_id: Option<i32>| CreateTodo {
+            owner_id: Some(owner_id),
+            assignee_id,
+            due_date: Some(today + Duration::days(days)),
+            ..CreateTodo::new(text.to_string())
         };
+        let mine = repository.create(due("mine", 1, 7, None)).await.unwrap();
+        let handed = repository
+            .in_workspace(2)
+            .create(due("handed to me", 0, 8, Some(7)))
+            .await
+            .unwrap();
+        repository
+            .create(due("handed away", 0, 7, Some(8)))
+            .await
+            .unwrap();
+        repository.create(due("later", 9, 7, None)).await.unwrap();
+        let done = repository.create(due("done", 0, 7, None)).await.unwrap();
+        repository.move_on_board(done.id, true, 0).await.unwrap();
 
-        assert_eq!(todo, expected);
+        let found = repository
+            .due_by(7, today + Duration::days(1))
+            .await
+            .unwrap();
+        assert_eq!(found, vec![(2, handed), (1, mine)]);
+    }
 
-        // delete
-        repository.delete(id).await.unwrap();
-        let todo = repository.find(id).await;
-        assert!(!todo.is_ok());
+    #[tokio::test]
+    async fn should_release_owned_todos_in_every_workspace() {
+        let repository = TodoRepositoryForMemory::new();
+        let owned = |text: &str| CreateTodo {
+            owner_id: Some(7),
+            ..CreateTodo::new(text.to_string())
+        };
+        let here = repository.create(owned("here")).await.unwrap();
+        let team = repository.in_workspace(2);
+        let there = team.create(owned("there")).await.unwrap();
+        let other = repository
+            .create(CreateTodo::new("other".to_string()))
+            .await
+            .unwrap();
+
+        let released = repository
+            .release_owned(7, OwnedTodos::TransferTo(8))
+            .await
+            .unwrap();
+        assert_eq!(released, vec![(1, here.id), (2, there.id)]);
+        assert_eq!(team.find(there.id).await.unwrap().owner_id, Some(8));
+
+        let released = repository
+            .release_owned(8, OwnedTodos::Delete)
+            .await
+            .unwrap();
+        assert_eq!(released.len(), 2);
+        assert!(!team.exists(there.id).await.unwrap());
+        assert_eq!(repository.all().await.unwrap(), vec![other]);
     }
 
     #[tokio::test]
-    async fn crud_scenario() {
-        dotenv().ok();
-        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+    async fn should_record_events_in_the_outbox() {
+        let repository = TodoRepositoryForMemory::new().acting_as(Some(3));
+        let todo = repository
+            .create(CreateTodo {
+                assignee_id: Some(4),
+                ..CreateTodo::new("outbox".to_string())
+            })
+            .await
+            .unwrap();
+        let events = |entries: Vec<OutboxEntry>| -> Vec<DomainEvent> {
+            entries.into_iter().map(|entry| entry.event.event).collect()
+        };
+
+        let claimed = repository.claim_outbox(10).await.unwrap();
+        let ids: Vec<i64> = claimed.iter().map(|entry| entry.id).collect();
+        assert_eq!(
+            events(claimed),
+            DomainEvent::created(&todo, Some(3)),
+            "the creation and the assignment"
+        );
+        assert_eq!(repository.claim_outbox(10).await.unwrap(), vec![]);
+        repository.mark_sent(&ids).await.unwrap();
+
+        let done = repository
+            .update(
+                todo.id,
+                UpdateTodo {
+                    text: None,
+                    completed: Some(true),
+                    labels: None,
+                    assignee_id: None,
+                    text_i18n: None,
+                    estimate_minutes: None,
+                    due_date: None,
+                    description: None,
+                    custom: None,
+                },
+            )
+            .await
+            .unwrap();
+        repository.delete(todo.id).await.unwrap();
+        assert_eq!(
+            events(repository.claim_outbox(10).await.unwrap()),
+            vec![
+                DomainEvent::TodoUpdated(done.clone()),
+                DomainEvent::TodoCompleted(done),
+                DomainEvent::TodoDeleted(todo.id),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn should_resolve_labels_from_label_store() {
+        let labels = LabelRepositoryForMemory::new();
+        let home = labels
+            .create(CreateLabel::new("home".to_string()))
+            .await
+            .unwrap();
+        let work = labels
+            .create(CreateLabel::new("work".to_string()))
+            .await
+            .unwrap();
+        let repository = TodoRepositoryForMemory::with_labels(labels.clone());
+
+        let todo = repository
+            .create(CreateTodo {
+                labels: vec![work.id, home.id],
+                ..CreateTodo::new("labelled".to_string())
+            })
+            .await
+            .unwrap();
+        assert_eq!(todo.labels, vec![home.clone(), work.clone()]);
+
+        let result = repository
+            .create(CreateTodo {
+                labels: vec![99],
+                ..CreateTodo::new("unknown label".to_string())
+            })
+            .await;
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::MissingLabels(missing)) if *missing == vec![99]
+        ));
+
+        labels.delete(home.id).await.unwrap();
+        assert_eq!(repository.find(todo.id).await.unwrap().labels, vec![work]);
+    }
+
+    #[tokio::test]
+    async fn should_persist_todos_and_labels_to_file() {
+        let path = std::env::temp_dir().join(format!("my-todo-{}.json", Uuid::now_v7()));
+        let labels = LabelRepositoryForMemory::new();
+        let repository = TodoRepositoryForMemory::with_labels(labels.clone())
+            .persist_to(&path)
+            .unwrap();
+        let home = labels
+            .create(CreateLabel::new("home".to_string()))
+            .await
+            .unwrap();
+        let todo = repository
+            .create(CreateTodo {
+                labels: vec![home.id],
+                ..CreateTodo::new("saved".to_string())
+            })
+            .await
+            .unwrap();
+        repository
+            .add_item(todo.id, CreateChecklistItem::new("step".to_string()))
+            .await
+            .unwrap();
+        repository.flush().await.unwrap();
 
-        let pool = PgPool::connect(database_url)
+        let labels = LabelRepositoryForMemory::new();
+        let reloaded = TodoRepositoryForMemory::with_labels(labels.clone())
+            .persist_to(&path)
+            .unwrap();
+        let found = reloaded.find(todo.id).await.unwrap();
+        assert_eq!(found.labels, vec![home.clone()]);
+        assert_eq!(found.progress, Progress { done: 0, total: 1 });
+        assert_eq!(labels.find(home.id).await.unwrap(), home);
+        assert!(reloaded.in_workspace(2).find(todo.id).await.is_err());
+        // ids carry on where they left off
+        let next = reloaded
+            .create(CreateTodo::new("after reload".to_string()))
             .await
-            .expect("failed connect database");
+            .unwrap();
+        assert_eq!(next.id, todo.id + 1);
+
+        // changes are saved shortly without asking
+        reloaded.flush_on_change();
+        reloaded.delete(todo.id).await.unwrap();
+        let mut saved = false;
+        for _ in 0..50 {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            let again = TodoRepositoryForMemory::new().persist_to(&path).unwrap();
+            if !again.exists(todo.id).await.unwrap() {
+                saved = true;
+                break;
+            }
+        }
+        assert!(saved);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        let db = TestDb::new().await;
+        let pool = db.pool.clone();
 
         let repository = TodoRepositoryForDb::new(pool.clone());
+        let label_repository = LabelRepositoryForDb::new(pool.clone());
         let todo_text = "[crud_scenario] text";
+        let label = label_repository
+            .create(CreateLabel::new(format!(
+                "[todo crud_scenario] {}",
+                Utc::now().timestamp_nanos_opt().unwrap()
+            )))
+            .await
+            .unwrap();
 
         // create
         let created = repository
@@ -294,17 +3999,52 @@ mod test {
             .unwrap();
         assert_eq!(created.text, todo_text);
         assert!(!created.completed);
+        let refused = repository
+            .create(CreateTodo {
+                labels: vec![label.id, label.id + 1000],
+                ..CreateTodo::new(todo_text.to_string())
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            refused.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::MissingLabels(missing)) if *missing == vec![label.id + 1000]
+        ));
 
         // find
         let finded = repository.find(created.id).await.unwrap();
 
         assert_eq!(finded, created);
 
+        // find_open_duplicate
+        let duplicate = repository
+            .find_open_duplicate("  [CRUD_SCENARIO]   Text ")
+            .await
+            .unwrap();
+        assert_eq!(duplicate, Some(created.clone()));
+
         // all
         let all = repository.all().await.unwrap();
         let todo = all.first().unwrap();
 
         assert_eq!(created, *todo);
+        let capped = repository.clone().with_list_cap(1).all().await.unwrap();
+        assert_eq!(capped, vec![created.clone()]);
+        assert!(repository.count().await.unwrap() >= 1);
+        assert!(repository.exists(created.id).await.unwrap());
+        assert_eq!(
+            repository.id_of(created.uuid).await.unwrap(),
+            Some(created.id)
+        );
+
+        // page
+        let page = repository.page(PageCursor::After(None), 1).await.unwrap();
+        assert_eq!(page.items, vec![created.clone()]);
+        let older = repository
+            .page(PageCursor::After(Some(created.id)), 100)
+            .await
+            .unwrap();
+        assert!(older.items.iter().all(|todo| todo.id < created.id));
 
         // update
         let updated_text = "[test] updated text";
@@ -314,6 +4054,16 @@ mod test {
                 UpdateTodo {
                     text: Some(updated_text.to_string()),
                     completed: Some(true),
+                    labels: Some(vec![label.id]),
+                    assignee_id: None,
+                    text_i18n: Some(BTreeMap::from([(
+                        "ja".to_string(),
+                        "[test] 更新後".to_string(),
+                    )])),
+                    estimate_minutes: None,
+                    due_date: None,
+                    description: None,
+                    custom: None,
                 },
             )
             .await
@@ -323,10 +4073,326 @@ mod test {
             updated,
             Todo {
                 id: created.id,
+                uuid: created.uuid,
                 text: updated_text.to_string(),
-                completed: true
+                completed: true,
+                labels: vec![label.clone()],
+                owner_id: None,
+                assignee_id: None,
+                text_i18n: BTreeMap::from([("ja".to_string(), "[test] 更新後".to_string())]),
+                snoozed_until: None,
+                estimate_minutes: None,
+                due_date: None,
+                description: None,
+                custom: BTreeMap::new(),
+                progress: Progress::default(),
             }
         );
+        let all = repository.all().await.unwrap();
+        assert_eq!(
+            all.iter().find(|todo| todo.id == created.id),
+            Some(&updated)
+        );
+
+        // filter
+        let expr = FilterExpr::All(vec![
+            FilterExpr::Term(Term::Completed(true)),
+            FilterExpr::Term(Term::Label(label.name.clone())),
+        ]);
+        let labelled = repository.with_filter(TodoFilter {
+            expr: Some(expr.clone()),
+            ..TodoFilter::default()
+        });
+        assert_eq!(labelled.all().await.unwrap(), vec![updated.clone()]);
+        assert_eq!(labelled.count().await.unwrap(), 1);
+        let page = labelled.page(PageCursor::After(None), 10).await.unwrap();
+        assert_eq!(page.items, vec![updated.clone()]);
+        let open = repository.with_filter(TodoFilter {
+            expr: Some(FilterExpr::Not(Box::new(expr))),
+            ..TodoFilter::default()
+        });
+        assert!(open
+            .all()
+            .await
+            .unwrap()
+            .iter()
+            .all(|todo| todo.id != updated.id));
+        let searched = repository.with_filter(TodoFilter {
+            search: Some(Search {
+                text: "updatd".to_string(),
+                threshold: 0.5,
+            }),
+            ..TodoFilter::default()
+        });
+        let best = searched.page(PageCursor::After(None), 1).await.unwrap();
+        assert_eq!(best.items, vec![updated.clone()]);
+        let rest = searched
+            .page(PageCursor::After(Some(updated.id)), 10)
+            .await
+            .unwrap();
+        assert!(rest.items.iter().all(|todo| todo.id != updated.id));
+
+        // snooze, unsnooze_due
+        let later = Utc::now() + Duration::hours(1);
+        let snoozed = repository.snooze(updated.id, Some(later)).await.unwrap();
+        assert!(snoozed.is_snoozed(Utc::now()));
+        let awake = repository.with_filter(TodoFilter {
+            hide_snoozed: true,
+            ..TodoFilter::default()
+        });
+        assert!(!awake.all().await.unwrap().contains(&snoozed));
+        assert!(repository.all().await.unwrap().contains(&snoozed));
+        let earlier = Utc::now() - Duration::seconds(1);
+        repository.snooze(updated.id, Some(earlier)).await.unwrap();
+        assert!(awake
+            .all()
+            .await
+            .unwrap()
+            .iter()
+            .any(|todo| todo.id == updated.id));
+        assert!(repository
+            .unsnooze_due()
+            .await
+            .unwrap()
+            .contains(&(DEFAULT_WORKSPACE_ID, updated.id)));
+        assert_eq!(repository.find(updated.id).await.unwrap(), updated);
+
+        // checklist
+        let first = repository
+            .add_item(created.id, CreateChecklistItem::new("first".to_string()))
+            .await
+            .unwrap();
+        let second = repository
+            .add_item(
+                created.id,
+                CreateChecklistItem {
+                    position: Some(0),
+                    ..CreateChecklistItem::new("second".to_string())
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(second.position, 0);
+        let moved = repository
+            .update_item(
+                created.id,
+                second.id,
+                UpdateChecklistItem {
+                    text: None,
+                    completed: Some(true),
+                    position: Some(1),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!((moved.position, moved.completed), (1, true));
+        let ids: Vec<i32> = repository
+            .checklist(created.id)
+            .await
+            .unwrap()
+            .iter()
+            .map(|item| item.id)
+            .collect();
+        assert_eq!(ids, vec![first.id, second.id]);
+        let progress = repository.find(created.id).await.unwrap().progress;
+        assert_eq!(progress, Progress { done: 1, total: 2 });
+        repository.delete_item(created.id, first.id).await.unwrap();
+        repository.delete_item(created.id, second.id).await.unwrap();
+        assert!(repository
+            .find(created.id)
+            .await
+            .unwrap()
+            .progress
+            .is_empty());
+
+        // update_if
+        let result = repository
+            .update_if(
+                created.id,
+                &created,
+                UpdateTodo {
+                    text: Some("[test] stale".to_string()),
+                    completed: None,
+                    labels: None,
+                    assignee_id: None,
+                    text_i18n: None,
+                    estimate_minutes: None,
+                    due_date: None,
+                    description: None,
+                    custom: None,
+                },
+            )
+            .await;
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Conflict(_))
+        ));
+
+        // revisions
+        let revisions = repository.revisions(created.id).await.unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(revisions[0].text, todo_text);
+        assert_eq!(revisions[1].text, updated_text);
+
+        // revert
+        let reverted = repository.revert(created.id, 1).await.unwrap();
+        assert_eq!(reverted.text, todo_text);
+        assert!(!reverted.completed);
+
+        // burndown
+        repository
+            .update(
+                created.id,
+                UpdateTodo {
+                    text: None,
+                    completed: None,
+                    labels: None,
+                    assignee_id: None,
+                    text_i18n: None,
+                    estimate_minutes: Some(Some(90)),
+                    due_date: None,
+                    description: None,
+                    custom: None,
+                },
+            )
+            .await
+            .unwrap();
+        let today = Utc::now().date_naive();
+        let remaining: Vec<i64> = repository
+            .burndown(today - Duration::days(1), today)
+            .await
+            .unwrap()
+            .iter()
+            .map(|day| day.remaining_minutes)
+            .collect();
+        assert_eq!(remaining, vec![0, 90]);
+
+        // due dates
+        let due: NaiveDate = "2030-01-31".parse().unwrap();
+        let dated = repository
+            .update(
+                created.id,
+                UpdateTodo {
+                    text: None,
+                    completed: None,
+                    labels: None,
+                    assignee_id: None,
+                    text_i18n: None,
+                    estimate_minutes: None,
+                    due_date: Some(Some(due)),
+                    description: Some(Some("by *then*".to_string())),
+                    custom: Some(BTreeMap::from([(
+                        "sprint".to_string(),
+                        Some(Value::from(12)),
+                    )])),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(dated.due_date, Some(due));
+        assert_eq!(dated.description.as_deref(), Some("by *then*"));
+        assert_eq!(dated.custom["sprint"], 12);
+        let filter = "due<=2030-01-31"
+            .parse::<FilterExpr>()
+            .unwrap()
+            .to_filter(None)
+            .unwrap();
+        let ids: Vec<i32> = repository
+            .with_filter(filter)
+            .all()
+            .await
+            .unwrap()
+            .iter()
+            .map(|todo| todo.id)
+            .collect();
+        assert_eq!(ids, vec![created.id]);
+        let filter = FilterExpr::Term(Term::Custom("sprint".to_string(), Value::from(12)))
+            .to_filter(None)
+            .unwrap();
+        let ids: Vec<i32> = repository
+            .with_filter(filter)
+            .all()
+            .await
+            .unwrap()
+            .iter()
+            .map(|todo| todo.id)
+            .collect();
+        assert_eq!(ids, vec![created.id]);
+
+        // board; staying in its column records no change
+        let placed = repository
+            .move_on_board(created.id, false, 3)
+            .await
+            .unwrap();
+        assert_eq!(placed, dated);
+        assert_eq!(
+            repository.board_positions().await.unwrap(),
+            Positions::from([(created.id, 0)])
+        );
+
+        // label_stats, delete_unused_labels
+        let unused = label_repository
+            .create(CreateLabel::new("[todo crud_scenario] unused".to_string()))
+            .await
+            .unwrap();
+        let stats = repository.label_stats().await.unwrap();
+        let counts: Vec<(i32, i64)> = stats
+            .iter()
+            .map(|stats| (stats.label.id, stats.todos))
+            .collect();
+        assert_eq!(counts, vec![(label.id, 1), (unused.id, 0)]);
+        assert_eq!(
+            repository.delete_unused_labels().await.unwrap(),
+            vec![unused.id]
+        );
+        assert_eq!(label_repository.count().await.unwrap(), 1);
+
+        // suggest_labels
+        let search = |text: &str| Search {
+            text: text.to_string(),
+            threshold: 0.5,
+        };
+        let suggested = repository
+            .suggest_labels(&search("[TODO crud"), 10)
+            .await
+            .unwrap();
+        assert_eq!(suggested, stats[..1]);
+        let fuzzy = repository
+            .suggest_labels(&search("scenaro"), 10)
+            .await
+            .unwrap();
+        assert_eq!(fuzzy, stats[..1]);
+        assert!(repository
+            .suggest_labels(&search("%"), 10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // merge_labels
+        let duplicate = label_repository
+            .create(CreateLabel::new(
+                "[todo crud_scenario] duplicate".to_string(),
+            ))
+            .await
+            .unwrap();
+        let moved = repository
+            .merge_labels(label.id, duplicate.id)
+            .await
+            .unwrap();
+        assert_eq!(moved.len(), 1);
+        assert_eq!(moved[0].labels, vec![duplicate.clone()]);
+        assert_eq!(repository.find(created.id).await.unwrap(), moved[0]);
+        let label = duplicate;
+
+        // apply, rolled back as a whole
+        let result = repository
+            .apply(vec![
+                TodoChange::Delete(created.id),
+                TodoChange::Delete(created.id),
+            ])
+            .await;
+        assert!(result.is_err());
+        assert!(repository.exists(created.id).await.unwrap());
 
         // delete
         let result = repository.delete(created.id).await;
@@ -343,5 +4409,23 @@ mod test {
         .unwrap();
 
         assert!(todo_rows.is_empty());
+        assert!(!repository.exists(created.id).await.unwrap());
+        label_repository.delete(label.id).await.unwrap();
+
+        // outbox, written along with every change
+        let recorded: Vec<(String,)> = sqlx::query_as(
+            r#"
+        select event->>'type' from outbox
+        where event->'data'->>'id' = $1
+            or (event->>'type' = 'todo_deleted' and event->>'data' = $1)
+        order by id
+        "#,
+        )
+        .bind(created.id.to_string())
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(recorded.first().unwrap().0, "todo_created");
+        assert_eq!(recorded.last().unwrap().0, "todo_deleted");
     }
 }
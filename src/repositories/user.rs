@@ -0,0 +1,2696 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json, FromRow, PgPool};
+
+use super::{
+    account::{Account, AccountFilter, AccountPage, UpdateAccount, UserRole},
+    audit::{AuditEntry, NewAuditEntry},
+    custom_field::{CustomField, CustomFields},
+    data_export::{DataExport, ExportStatus},
+    email::{EmailInbox, NewEmailInbox},
+    notification::NotificationSettings,
+    slack::{SetSlackLink, SlackLink},
+    telegram::{NewTelegramCode, TelegramLink},
+    view::{CreateView, SavedView},
+    workspace::{Member, Membership, Role, UpdateWorkspace, Workspace, DEFAULT_WORKSPACE_ID},
+    RepositoryError,
+};
+
+#[async_trait]
+pub trait UserRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    async fn create(&self, username: String, password_hash: String) -> anyhow::Result<User>;
+    async fn find(&self, id: i32) -> anyhow::Result<User>;
+    async fn credentials(&self, id: i32) -> anyhow::Result<UserCredentials>;
+    /// The account owning a verified email, created without a password when
+    /// there is none yet.
+    async fn find_or_create_by_email(&self, email: &str) -> anyhow::Result<User>;
+    /// `None` for an unknown name, so login can answer without a 404.
+    async fn credentials_by_username(
+        &self,
+        username: &str,
+    ) -> anyhow::Result<Option<UserCredentials>>;
+    async fn update_password(&self, id: i32, password_hash: String) -> anyhow::Result<()>;
+    /// The defaults until the user changes them.
+    async fn notification_settings(&self, id: i32) -> anyhow::Result<NotificationSettings>;
+    async fn set_notification_settings(
+        &self,
+        id: i32,
+        settings: NotificationSettings,
+    ) -> anyhow::Result<()>;
+    async fn create_session(
+        &self,
+        session_id: String,
+        user_id: i32,
+        expires_at: DateTime<Utc>,
+    ) -> anyhow::Result<()>;
+    /// The user of an unexpired session.
+    async fn session_user(&self, session_id: &str) -> anyhow::Result<Option<i32>>;
+    async fn delete_session(&self, session_id: &str) -> anyhow::Result<()>;
+    async fn create_refresh_token(&self, token: NewRefreshToken) -> anyhow::Result<()>;
+    /// Marks the token used and stores `next_hash` in its family, or revokes
+    /// the whole family when the token was used before.
+    async fn rotate_refresh_token(
+        &self,
+        token_hash: &str,
+        next_hash: String,
+        expires_at: DateTime<Utc>,
+    ) -> anyhow::Result<RefreshRotation>;
+    /// Revokes the token's whole family.
+    async fn revoke_refresh_token(&self, token_hash: &str) -> anyhow::Result<()>;
+    /// Creates a workspace owned by `owner_id`.
+    async fn create_workspace(&self, name: String, owner_id: i32) -> anyhow::Result<Workspace>;
+    async fn workspace(&self, id: i32) -> anyhow::Result<Workspace>;
+    async fn update_workspace(
+        &self,
+        id: i32,
+        payload: UpdateWorkspace,
+    ) -> anyhow::Result<Workspace>;
+    /// The fields the workspace's todos carry under `custom`.
+    async fn custom_fields(&self, workspace_id: i32) -> anyhow::Result<CustomFields>;
+    /// Replaces every field definition; values todos already hold are kept.
+    async fn set_custom_fields(
+        &self,
+        workspace_id: i32,
+        fields: CustomFields,
+    ) -> anyhow::Result<CustomFields>;
+    async fn memberships(&self, user_id: i32) -> anyhow::Result<Vec<Membership>>;
+    async fn role(&self, workspace_id: i32, user_id: i32) -> anyhow::Result<Option<Role>>;
+    async fn members(&self, workspace_id: i32) -> anyhow::Result<Vec<Member>>;
+    /// Adds the user or changes their role.
+    async fn set_member(&self, workspace_id: i32, user_id: i32, role: Role) -> anyhow::Result<()>;
+    async fn remove_member(&self, workspace_id: i32, user_id: i32) -> anyhow::Result<()>;
+    async fn account(&self, id: i32) -> anyhow::Result<Account>;
+    /// Accounts with an id below `after` (newest first), fetching one row
+    /// past `limit` to tell whether another page exists.
+    async fn accounts(
+        &self,
+        filter: &AccountFilter,
+        after: Option<i32>,
+        limit: u32,
+    ) -> anyhow::Result<AccountPage>;
+    /// Disabling ends every session and refresh token of the account.
+    async fn update_account(&self, id: i32, payload: UpdateAccount) -> anyhow::Result<Account>;
+    /// Deletes the account with its sessions, tokens, memberships and
+    /// exports; todos are the caller's business.
+    async fn delete(&self, id: i32) -> anyhow::Result<()>;
+    /// Marks the account for deletion at `delete_after`; `None` cancels.
+    async fn schedule_deletion(
+        &self,
+        id: i32,
+        delete_after: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<()>;
+    /// Accounts whose grace period is over.
+    async fn deletions_due(&self) -> anyhow::Result<Vec<i32>>;
+    async fn record_audit(&self, entry: NewAuditEntry) -> anyhow::Result<()>;
+    /// Oldest first.
+    async fn audit_log(&self, user_id: i32) -> anyhow::Result<Vec<AuditEntry>>;
+    async fn create_export(&self, user_id: i32) -> anyhow::Result<DataExport>;
+    /// Stores the archive and marks the export ready, or failed without one.
+    async fn finish_export(
+        &self,
+        id: i32,
+        archive: Option<serde_json::Value>,
+    ) -> anyhow::Result<()>;
+    /// Only the user's own exports are found.
+    async fn data_export(&self, user_id: i32, id: i32) -> anyhow::Result<DataExport>;
+    /// `None` until the export is ready.
+    async fn export_archive(
+        &self,
+        user_id: i32,
+        id: i32,
+    ) -> anyhow::Result<Option<serde_json::Value>>;
+    async fn create_view(
+        &self,
+        workspace_id: i32,
+        user_id: i32,
+        payload: CreateView,
+    ) -> anyhow::Result<SavedView>;
+    /// The user's views of one workspace, oldest first.
+    async fn views(&self, workspace_id: i32, user_id: i32) -> anyhow::Result<Vec<SavedView>>;
+    /// Only the user's own views of the workspace are found.
+    async fn view(&self, workspace_id: i32, user_id: i32, id: i32) -> anyhow::Result<SavedView>;
+    async fn delete_view(&self, workspace_id: i32, user_id: i32, id: i32) -> anyhow::Result<()>;
+    async fn slack_link(
+        &self,
+        workspace_id: i32,
+        user_id: i32,
+    ) -> anyhow::Result<Option<SlackLink>>;
+    /// Creates or replaces the user's link in the workspace; fails with
+    /// `Duplicate` when another link holds the same Slack user.
+    async fn set_slack_link(
+        &self,
+        workspace_id: i32,
+        user_id: i32,
+        payload: SetSlackLink,
+    ) -> anyhow::Result<SlackLink>;
+    async fn delete_slack_link(&self, workspace_id: i32, user_id: i32) -> anyhow::Result<()>;
+    /// The link a slash command of this Slack user acts through.
+    async fn slack_link_by_slack_user(
+        &self,
+        team_id: &str,
+        slack_user_id: &str,
+    ) -> anyhow::Result<Option<SlackLink>>;
+    async fn create_telegram_code(&self, code: NewTelegramCode) -> anyhow::Result<()>;
+    /// Uses up the code and links `chat_id` to its user and workspace in
+    /// place of whatever the chat was linked to; `None` for an unknown or
+    /// expired code.
+    async fn redeem_telegram_code(
+        &self,
+        code_hash: &str,
+        chat_id: i64,
+    ) -> anyhow::Result<Option<TelegramLink>>;
+    async fn telegram_link(&self, chat_id: i64) -> anyhow::Result<Option<TelegramLink>>;
+    /// Unlinking an unlinked chat does nothing.
+    async fn delete_telegram_link(&self, chat_id: i64) -> anyhow::Result<()>;
+    /// Creates the user's inbox in the workspace, or gives the existing one
+    /// the new token so mail to the old address bounces.
+    async fn set_email_inbox(&self, inbox: NewEmailInbox) -> anyhow::Result<EmailInbox>;
+    /// The inbox mail to the token's plus-address goes to.
+    async fn email_inbox(&self, token_hash: &str) -> anyhow::Result<Option<EmailInbox>>;
+    async fn delete_email_inbox(&self, workspace_id: i32, user_id: i32) -> anyhow::Result<()>;
+}
+
+/// Public view of an account; the password hash lives only in
+/// `UserCredentials`, which is never serialized.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow)]
+pub struct User {
+    pub id: i32,
+    pub username: String,
+    /// Verified through an OAuth provider.
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+pub struct UserCredentials {
+    pub id: i32,
+    pub username: String,
+    /// `None` for accounts that only log in through OAuth.
+    pub password_hash: Option<String>,
+    pub disabled: bool,
+}
+
+/// Only the hash of a refresh token is stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewRefreshToken {
+    pub user_id: i32,
+    pub family: String,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshRotation {
+    Rotated {
+        user_id: i32,
+    },
+    /// An already rotated token came back; the family is revoked.
+    Reused {
+        user_id: i32,
+    },
+    /// Unknown, expired or revoked.
+    Invalid,
+}
+
+#[derive(Debug, Clone, FromRow)]
+struct StoredRefreshToken {
+    user_id: i32,
+    family: String,
+    expires_at: DateTime<Utc>,
+    used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+struct StoredUser {
+    user: User,
+    password_hash: Option<String>,
+    notification_settings: NotificationSettings,
+    role: UserRole,
+    disabled_at: Option<DateTime<Utc>>,
+    delete_after: Option<DateTime<Utc>>,
+}
+
+impl StoredUser {
+    fn new(user: User, password_hash: Option<String>) -> Self {
+        Self {
+            user,
+            password_hash,
+            notification_settings: NotificationSettings::default(),
+            role: UserRole::default(),
+            disabled_at: None,
+            delete_after: None,
+        }
+    }
+
+    fn credentials(&self) -> UserCredentials {
+        UserCredentials {
+            id: self.user.id,
+            username: self.user.username.clone(),
+            password_hash: self.password_hash.clone(),
+            disabled: self.disabled_at.is_some(),
+        }
+    }
+
+    fn account(&self) -> Account {
+        Account {
+            user: self.user.clone(),
+            role: self.role,
+            disabled_at: self.disabled_at,
+            delete_after: self.delete_after,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct StoredExport {
+    export: DataExport,
+    archive: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone)]
+struct StoredSession {
+    user_id: i32,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct UserRepositoryForMemory {
+    store: Arc<RwLock<HashMap<i32, StoredUser>>>,
+    next_id: Arc<AtomicI32>,
+    sessions: Arc<RwLock<HashMap<String, StoredSession>>>,
+    refresh_tokens: Arc<RwLock<HashMap<String, StoredRefreshToken>>>,
+    workspaces: Arc<RwLock<HashMap<i32, Workspace>>>,
+    next_workspace_id: Arc<AtomicI32>,
+    members: Arc<RwLock<HashMap<(i32, i32), Role>>>,
+    audit_log: Arc<RwLock<Vec<AuditEntry>>>,
+    exports: Arc<RwLock<HashMap<i32, StoredExport>>>,
+    next_export_id: Arc<AtomicI32>,
+    views: Arc<RwLock<HashMap<i32, SavedView>>>,
+    next_view_id: Arc<AtomicI32>,
+    slack_links: Arc<RwLock<HashMap<(i32, i32), SlackLink>>>,
+    telegram_codes: Arc<RwLock<HashMap<String, NewTelegramCode>>>,
+    telegram_links: Arc<RwLock<HashMap<i64, TelegramLink>>>,
+    email_inboxes: Arc<RwLock<HashMap<String, EmailInbox>>>,
+    custom_fields: Arc<RwLock<HashMap<i32, CustomFields>>>,
+}
+
+impl UserRepositoryForMemory {
+    pub fn new() -> Self {
+        let default = Workspace {
+            id: DEFAULT_WORKSPACE_ID,
+            name: "default".to_string(),
+            assignees_only: false,
+            created_at: Utc::now(),
+        };
+        Self {
+            store: Arc::default(),
+            next_id: Arc::default(),
+            sessions: Arc::default(),
+            refresh_tokens: Arc::default(),
+            workspaces: Arc::new(RwLock::new(HashMap::from([(default.id, default)]))),
+            next_workspace_id: Arc::default(),
+            members: Arc::default(),
+            audit_log: Arc::default(),
+            exports: Arc::default(),
+            next_export_id: Arc::default(),
+            views: Arc::default(),
+            next_view_id: Arc::default(),
+            slack_links: Arc::default(),
+            telegram_codes: Arc::default(),
+            telegram_links: Arc::default(),
+            email_inboxes: Arc::default(),
+            custom_fields: Arc::default(),
+        }
+    }
+}
+
+impl Default for UserRepositoryForMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl UserRepository for UserRepositoryForMemory {
+    async fn create(&self, username: String, password_hash: String) -> anyhow::Result<User> {
+        let mut store = self.store.write().unwrap();
+        if let Some(existing) = store
+            .values()
+            .find(|stored| stored.user.username == username)
+        {
+            return Err(RepositoryError::Duplicate(existing.user.id).into());
+        }
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let user = User {
+            id,
+            username,
+            email: None,
+            created_at: Utc::now(),
+        };
+        store.insert(id, StoredUser::new(user.clone(), Some(password_hash)));
+        Ok(user)
+    }
+    async fn find_or_create_by_email(&self, email: &str) -> anyhow::Result<User> {
+        let mut store = self.store.write().unwrap();
+        if let Some(stored) = store
+            .values()
+            .find(|stored| stored.user.email.as_deref() == Some(email))
+        {
+            return Ok(stored.user.clone());
+        }
+        if let Some(existing) = store.values().find(|stored| stored.user.username == email) {
+            return Err(RepositoryError::Duplicate(existing.user.id).into());
+        }
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let user = User {
+            id,
+            username: email.to_string(),
+            email: Some(email.to_string()),
+            created_at: Utc::now(),
+        };
+        store.insert(id, StoredUser::new(user.clone(), None));
+        Ok(user)
+    }
+    async fn find(&self, id: i32) -> anyhow::Result<User> {
+        let store = self.store.read().unwrap();
+        let stored = store.get(&id).ok_or(RepositoryError::NotFound(id))?;
+        Ok(stored.user.clone())
+    }
+    async fn credentials(&self, id: i32) -> anyhow::Result<UserCredentials> {
+        let store = self.store.read().unwrap();
+        let stored = store.get(&id).ok_or(RepositoryError::NotFound(id))?;
+        Ok(stored.credentials())
+    }
+    async fn credentials_by_username(
+        &self,
+        username: &str,
+    ) -> anyhow::Result<Option<UserCredentials>> {
+        let store = self.store.read().unwrap();
+        Ok(store
+            .values()
+            .find(|stored| stored.user.username == username)
+            .map(StoredUser::credentials))
+    }
+    async fn update_password(&self, id: i32, password_hash: String) -> anyhow::Result<()> {
+        let mut store = self.store.write().unwrap();
+        let stored = store.get_mut(&id).ok_or(RepositoryError::NotFound(id))?;
+        stored.password_hash = Some(password_hash);
+        Ok(())
+    }
+    async fn notification_settings(&self, id: i32) -> anyhow::Result<NotificationSettings> {
+        let store = self.store.read().unwrap();
+        let stored = store.get(&id).ok_or(RepositoryError::NotFound(id))?;
+        Ok(stored.notification_settings)
+    }
+    async fn set_notification_settings(
+        &self,
+        id: i32,
+        settings: NotificationSettings,
+    ) -> anyhow::Result<()> {
+        let mut store = self.store.write().unwrap();
+        let stored = store.get_mut(&id).ok_or(RepositoryError::NotFound(id))?;
+        stored.notification_settings = settings;
+        Ok(())
+    }
+    async fn create_session(
+        &self,
+        session_id: String,
+        user_id: i32,
+        expires_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        let mut sessions = self.sessions.write().unwrap();
+        let now = Utc::now();
+        sessions.retain(|_, session| session.expires_at > now);
+        sessions.insert(
+            session_id,
+            StoredSession {
+                user_id,
+                expires_at,
+            },
+        );
+        Ok(())
+    }
+    async fn session_user(&self, session_id: &str) -> anyhow::Result<Option<i32>> {
+        let sessions = self.sessions.read().unwrap();
+        Ok(sessions
+            .get(session_id)
+            .filter(|session| session.expires_at > Utc::now())
+            .map(|session| session.user_id))
+    }
+    async fn delete_session(&self, session_id: &str) -> anyhow::Result<()> {
+        self.sessions.write().unwrap().remove(session_id);
+        Ok(())
+    }
+    async fn create_refresh_token(&self, token: NewRefreshToken) -> anyhow::Result<()> {
+        let mut tokens = self.refresh_tokens.write().unwrap();
+        let now = Utc::now();
+        tokens.retain(|_, stored| stored.expires_at > now);
+        tokens.insert(
+            token.token_hash,
+            StoredRefreshToken {
+                user_id: token.user_id,
+                family: token.family,
+                expires_at: token.expires_at,
+                used_at: None,
+            },
+        );
+        Ok(())
+    }
+    async fn rotate_refresh_token(
+        &self,
+        token_hash: &str,
+        next_hash: String,
+        expires_at: DateTime<Utc>,
+    ) -> anyhow::Result<RefreshRotation> {
+        let mut tokens = self.refresh_tokens.write().unwrap();
+        let now = Utc::now();
+        let stored = match tokens.get_mut(token_hash) {
+            Some(stored) if stored.expires_at > now => stored,
+            _ => return Ok(RefreshRotation::Invalid),
+        };
+        let user_id = stored.user_id;
+        let family = stored.family.clone();
+        if stored.used_at.is_some() {
+            tokens.retain(|_, stored| stored.family != family);
+            return Ok(RefreshRotation::Reused { user_id });
+        }
+
+        stored.used_at = Some(now);
+        tokens.insert(
+            next_hash,
+            StoredRefreshToken {
+                user_id,
+                family,
+                expires_at,
+                used_at: None,
+            },
+        );
+        Ok(RefreshRotation::Rotated { user_id })
+    }
+    async fn revoke_refresh_token(&self, token_hash: &str) -> anyhow::Result<()> {
+        let mut tokens = self.refresh_tokens.write().unwrap();
+        if let Some(family) = tokens.get(token_hash).map(|stored| stored.family.clone()) {
+            tokens.retain(|_, stored| stored.family != family);
+        }
+        Ok(())
+    }
+    async fn create_workspace(&self, name: String, owner_id: i32) -> anyhow::Result<Workspace> {
+        self.find(owner_id).await?;
+        // ids after the default workspace, which every store starts with
+        let id = DEFAULT_WORKSPACE_ID + self.next_workspace_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let workspace = Workspace {
+            id,
+            name,
+            assignees_only: false,
+            created_at: Utc::now(),
+        };
+        self.workspaces
+            .write()
+            .unwrap()
+            .insert(id, workspace.clone());
+        self.members
+            .write()
+            .unwrap()
+            .insert((id, owner_id), Role::Owner);
+        Ok(workspace)
+    }
+    async fn workspace(&self, id: i32) -> anyhow::Result<Workspace> {
+        let workspaces = self.workspaces.read().unwrap();
+        let workspace = workspaces.get(&id).ok_or(RepositoryError::NotFound(id))?;
+        Ok(workspace.clone())
+    }
+    async fn update_workspace(
+        &self,
+        id: i32,
+        payload: UpdateWorkspace,
+    ) -> anyhow::Result<Workspace> {
+        let mut workspaces = self.workspaces.write().unwrap();
+        let workspace = workspaces
+            .get_mut(&id)
+            .ok_or(RepositoryError::NotFound(id))?;
+        if let Some(name) = payload.name {
+            workspace.name = name;
+        }
+        if let Some(assignees_only) = payload.assignees_only {
+            workspace.assignees_only = assignees_only;
+        }
+        Ok(workspace.clone())
+    }
+    async fn custom_fields(&self, workspace_id: i32) -> anyhow::Result<CustomFields> {
+        self.workspace(workspace_id).await?;
+        let custom_fields = self.custom_fields.read().unwrap();
+        Ok(custom_fields
+            .get(&workspace_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+    async fn set_custom_fields(
+        &self,
+        workspace_id: i32,
+        fields: CustomFields,
+    ) -> anyhow::Result<CustomFields> {
+        self.workspace(workspace_id).await?;
+        self.custom_fields
+            .write()
+            .unwrap()
+            .insert(workspace_id, fields.clone());
+        Ok(fields)
+    }
+    async fn memberships(&self, user_id: i32) -> anyhow::Result<Vec<Membership>> {
+        let workspaces = self.workspaces.read().unwrap();
+        let members = self.members.read().unwrap();
+        let mut memberships: Vec<Membership> = members
+            .iter()
+            .filter(|((_, member), _)| *member == user_id)
+            .filter_map(|((workspace_id, _), role)| {
+                workspaces.get(workspace_id).map(|workspace| Membership {
+                    workspace: workspace.clone(),
+                    role: *role,
+                })
+            })
+            .collect();
+        memberships.sort_by_key(|membership| membership.workspace.id);
+        Ok(memberships)
+    }
+    async fn role(&self, workspace_id: i32, user_id: i32) -> anyhow::Result<Option<Role>> {
+        let members = self.members.read().unwrap();
+        Ok(members.get(&(workspace_id, user_id)).copied())
+    }
+    async fn members(&self, workspace_id: i32) -> anyhow::Result<Vec<Member>> {
+        let members = self.members.read().unwrap();
+        let mut members: Vec<Member> = members
+            .iter()
+            .filter(|((workspace, _), _)| *workspace == workspace_id)
+            .map(|((_, user_id), role)| Member {
+                user_id: *user_id,
+                role: *role,
+            })
+            .collect();
+        members.sort_by_key(|member| member.user_id);
+        Ok(members)
+    }
+    async fn set_member(&self, workspace_id: i32, user_id: i32, role: Role) -> anyhow::Result<()> {
+        self.find(user_id).await?;
+        if !self.workspaces.read().unwrap().contains_key(&workspace_id) {
+            return Err(RepositoryError::NotFound(workspace_id).into());
+        }
+        self.members
+            .write()
+            .unwrap()
+            .insert((workspace_id, user_id), role);
+        Ok(())
+    }
+    async fn remove_member(&self, workspace_id: i32, user_id: i32) -> anyhow::Result<()> {
+        self.members
+            .write()
+            .unwrap()
+            .remove(&(workspace_id, user_id))
+            .ok_or(RepositoryError::NotFound(user_id))?;
+        Ok(())
+    }
+    async fn account(&self, id: i32) -> anyhow::Result<Account> {
+        let store = self.store.read().unwrap();
+        let stored = store.get(&id).ok_or(RepositoryError::NotFound(id))?;
+        Ok(stored.account())
+    }
+    async fn accounts(
+        &self,
+        filter: &AccountFilter,
+        after: Option<i32>,
+        limit: u32,
+    ) -> anyhow::Result<AccountPage> {
+        let store = self.store.read().unwrap();
+        let mut accounts: Vec<Account> = store
+            .values()
+            .map(StoredUser::account)
+            .filter(|account| {
+                after.is_none_or(|after| account.user.id < after) && filter.matches(account)
+            })
+            .collect();
+        accounts.sort_by_key(|account| std::cmp::Reverse(account.user.id));
+        accounts.truncate(limit as usize + 1);
+        Ok(AccountPage::from_rows(accounts, limit))
+    }
+    async fn update_account(&self, id: i32, payload: UpdateAccount) -> anyhow::Result<Account> {
+        let account = {
+            let mut store = self.store.write().unwrap();
+            let stored = store.get_mut(&id).ok_or(RepositoryError::NotFound(id))?;
+            if let Some(role) = payload.role {
+                stored.role = role;
+            }
+            match payload.disabled {
+                Some(true) => {
+                    stored.disabled_at.get_or_insert_with(Utc::now);
+                }
+                Some(false) => stored.disabled_at = None,
+                None => {}
+            }
+            stored.account()
+        };
+        if account.is_disabled() {
+            self.sessions
+                .write()
+                .unwrap()
+                .retain(|_, session| session.user_id != id);
+            self.refresh_tokens
+                .write()
+                .unwrap()
+                .retain(|_, token| token.user_id != id);
+        }
+        Ok(account)
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        self.store
+            .write()
+            .unwrap()
+            .remove(&id)
+            .ok_or(RepositoryError::NotFound(id))?;
+        self.sessions
+            .write()
+            .unwrap()
+            .retain(|_, session| session.user_id != id);
+        self.refresh_tokens
+            .write()
+            .unwrap()
+            .retain(|_, token| token.user_id != id);
+        self.members
+            .write()
+            .unwrap()
+            .retain(|(_, user_id), _| *user_id != id);
+        self.exports
+            .write()
+            .unwrap()
+            .retain(|_, stored| stored.export.user_id != id);
+        self.views
+            .write()
+            .unwrap()
+            .retain(|_, view| view.user_id != id);
+        self.slack_links
+            .write()
+            .unwrap()
+            .retain(|(_, user_id), _| *user_id != id);
+        self.telegram_codes
+            .write()
+            .unwrap()
+            .retain(|_, code| code.user_id != id);
+        self.telegram_links
+            .write()
+            .unwrap()
+            .retain(|_, link| link.user_id != id);
+        self.email_inboxes
+            .write()
+            .unwrap()
+            .retain(|_, inbox| inbox.user_id != id);
+        Ok(())
+    }
+    async fn schedule_deletion(
+        &self,
+        id: i32,
+        delete_after: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<()> {
+        let mut store = self.store.write().unwrap();
+        let stored = store.get_mut(&id).ok_or(RepositoryError::NotFound(id))?;
+        stored.delete_after = delete_after;
+        Ok(())
+    }
+    async fn deletions_due(&self) -> anyhow::Result<Vec<i32>> {
+        let store = self.store.read().unwrap();
+        let now = Utc::now();
+        let mut due: Vec<i32> = store
+            .values()
+            .filter(|stored| stored.delete_after.is_some_and(|at| at <= now))
+            .map(|stored| stored.user.id)
+            .collect();
+        due.sort();
+        Ok(due)
+    }
+    async fn record_audit(&self, entry: NewAuditEntry) -> anyhow::Result<()> {
+        let mut log = self.audit_log.write().unwrap();
+        let id = log.len() as i32 + 1;
+        log.push(AuditEntry {
+            id,
+            user_id: entry.user_id,
+            actor_id: entry.actor_id,
+            action: entry.action,
+            created_at: Utc::now(),
+        });
+        Ok(())
+    }
+    async fn audit_log(&self, user_id: i32) -> anyhow::Result<Vec<AuditEntry>> {
+        let log = self.audit_log.read().unwrap();
+        Ok(log
+            .iter()
+            .filter(|entry| entry.user_id == user_id)
+            .cloned()
+            .collect())
+    }
+    async fn create_export(&self, user_id: i32) -> anyhow::Result<DataExport> {
+        self.find(user_id).await?;
+        let id = self.next_export_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let export = DataExport {
+            id,
+            user_id,
+            status: ExportStatus::Pending,
+            created_at: Utc::now(),
+            completed_at: None,
+        };
+        self.exports.write().unwrap().insert(
+            id,
+            StoredExport {
+                export: export.clone(),
+                archive: None,
+            },
+        );
+        Ok(export)
+    }
+    async fn finish_export(
+        &self,
+        id: i32,
+        archive: Option<serde_json::Value>,
+    ) -> anyhow::Result<()> {
+        let mut exports = self.exports.write().unwrap();
+        let stored = exports.get_mut(&id).ok_or(RepositoryError::NotFound(id))?;
+        stored.export.status = match archive {
+            Some(_) => ExportStatus::Ready,
+            None => ExportStatus::Failed,
+        };
+        stored.export.completed_at = Some(Utc::now());
+        stored.archive = archive;
+        Ok(())
+    }
+    async fn data_export(&self, user_id: i32, id: i32) -> anyhow::Result<DataExport> {
+        let exports = self.exports.read().unwrap();
+        let stored = exports
+            .get(&id)
+            .filter(|stored| stored.export.user_id == user_id)
+            .ok_or(RepositoryError::NotFound(id))?;
+        Ok(stored.export.clone())
+    }
+    async fn export_archive(
+        &self,
+        user_id: i32,
+        id: i32,
+    ) -> anyhow::Result<Option<serde_json::Value>> {
+        let exports = self.exports.read().unwrap();
+        let stored = exports
+            .get(&id)
+            .filter(|stored| stored.export.user_id == user_id)
+            .ok_or(RepositoryError::NotFound(id))?;
+        Ok(stored.archive.clone())
+    }
+    async fn create_view(
+        &self,
+        workspace_id: i32,
+        user_id: i32,
+        payload: CreateView,
+    ) -> anyhow::Result<SavedView> {
+        self.find(user_id).await?;
+        let id = self.next_view_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let view = SavedView {
+            id,
+            workspace_id,
+            user_id,
+            name: payload.name,
+            filter: payload.filter,
+            created_at: Utc::now(),
+        };
+        self.views.write().unwrap().insert(id, view.clone());
+        Ok(view)
+    }
+    async fn views(&self, workspace_id: i32, user_id: i32) -> anyhow::Result<Vec<SavedView>> {
+        let mut views: Vec<SavedView> = self
+            .views
+            .read()
+            .unwrap()
+            .values()
+            .filter(|view| view.workspace_id == workspace_id && view.user_id == user_id)
+            .cloned()
+            .collect();
+        views.sort_by_key(|view| view.id);
+        Ok(views)
+    }
+    async fn view(&self, workspace_id: i32, user_id: i32, id: i32) -> anyhow::Result<SavedView> {
+        let views = self.views.read().unwrap();
+        let view = views
+            .get(&id)
+            .filter(|view| view.workspace_id == workspace_id && view.user_id == user_id)
+            .ok_or(RepositoryError::NotFound(id))?;
+        Ok(view.clone())
+    }
+    async fn delete_view(&self, workspace_id: i32, user_id: i32, id: i32) -> anyhow::Result<()> {
+        self.view(workspace_id, user_id, id).await?;
+        self.views.write().unwrap().remove(&id);
+        Ok(())
+    }
+    async fn slack_link(
+        &self,
+        workspace_id: i32,
+        user_id: i32,
+    ) -> anyhow::Result<Option<SlackLink>> {
+        let links = self.slack_links.read().unwrap();
+        Ok(links.get(&(workspace_id, user_id)).cloned())
+    }
+    async fn set_slack_link(
+        &self,
+        workspace_id: i32,
+        user_id: i32,
+        payload: SetSlackLink,
+    ) -> anyhow::Result<SlackLink> {
+        self.find(user_id).await?;
+        let mut links = self.slack_links.write().unwrap();
+        if let Some(other) = links.values().find(|link| {
+            (link.workspace_id, link.user_id) != (workspace_id, user_id)
+                && payload.slack_user_id.is_some()
+                && (&link.team_id, &link.slack_user_id)
+                    == (&payload.team_id, &payload.slack_user_id)
+        }) {
+            return Err(RepositoryError::Duplicate(other.user_id).into());
+        }
+        let created_at = links
+            .get(&(workspace_id, user_id))
+            .map_or_else(Utc::now, |link| link.created_at);
+        let link = SlackLink {
+            workspace_id,
+            user_id,
+            webhook_url: payload.webhook_url,
+            team_id: payload.team_id,
+            slack_user_id: payload.slack_user_id,
+            created_at,
+        };
+        links.insert((workspace_id, user_id), link.clone());
+        Ok(link)
+    }
+    async fn delete_slack_link(&self, workspace_id: i32, user_id: i32) -> anyhow::Result<()> {
+        self.slack_links
+            .write()
+            .unwrap()
+            .remove(&(workspace_id, user_id))
+            .ok_or(RepositoryError::NotFound(user_id))?;
+        Ok(())
+    }
+    async fn slack_link_by_slack_user(
+        &self,
+        team_id: &str,
+        slack_user_id: &str,
+    ) -> anyhow::Result<Option<SlackLink>> {
+        let links = self.slack_links.read().unwrap();
+        Ok(links
+            .values()
+            .find(|link| {
+                link.team_id.as_deref() == Some(team_id)
+                    && link.slack_user_id.as_deref() == Some(slack_user_id)
+            })
+            .cloned())
+    }
+    async fn create_telegram_code(&self, code: NewTelegramCode) -> anyhow::Result<()> {
+        self.find(code.user_id).await?;
+        self.telegram_codes
+            .write()
+            .unwrap()
+            .insert(code.code_hash.clone(), code);
+        Ok(())
+    }
+    async fn redeem_telegram_code(
+        &self,
+        code_hash: &str,
+        chat_id: i64,
+    ) -> anyhow::Result<Option<TelegramLink>> {
+        let code = self.telegram_codes.write().unwrap().remove(code_hash);
+        let Some(code) = code.filter(|code| code.expires_at > Utc::now()) else {
+            return Ok(None);
+        };
+        let link = TelegramLink {
+            chat_id,
+            workspace_id: code.workspace_id,
+            user_id: code.user_id,
+            created_at: Utc::now(),
+        };
+        self.telegram_links
+            .write()
+            .unwrap()
+            .insert(chat_id, link.clone());
+        Ok(Some(link))
+    }
+    async fn telegram_link(&self, chat_id: i64) -> anyhow::Result<Option<TelegramLink>> {
+        Ok(self.telegram_links.read().unwrap().get(&chat_id).cloned())
+    }
+    async fn delete_telegram_link(&self, chat_id: i64) -> anyhow::Result<()> {
+        self.telegram_links.write().unwrap().remove(&chat_id);
+        Ok(())
+    }
+    async fn set_email_inbox(&self, inbox: NewEmailInbox) -> anyhow::Result<EmailInbox> {
+        self.find(inbox.user_id).await?;
+        let mut inboxes = self.email_inboxes.write().unwrap();
+        let previous = inboxes
+            .iter()
+            .find(|(_, stored)| {
+                (stored.workspace_id, stored.user_id) == (inbox.workspace_id, inbox.user_id)
+            })
+            .map(|(token_hash, stored)| (token_hash.clone(), stored.created_at));
+        let created_at = match previous {
+            Some((token_hash, created_at)) => {
+                inboxes.remove(&token_hash);
+                created_at
+            }
+            None => Utc::now(),
+        };
+        let stored = EmailInbox {
+            workspace_id: inbox.workspace_id,
+            user_id: inbox.user_id,
+            created_at,
+        };
+        inboxes.insert(inbox.token_hash, stored.clone());
+        Ok(stored)
+    }
+    async fn email_inbox(&self, token_hash: &str) -> anyhow::Result<Option<EmailInbox>> {
+        Ok(self.email_inboxes.read().unwrap().get(token_hash).cloned())
+    }
+    async fn delete_email_inbox(&self, workspace_id: i32, user_id: i32) -> anyhow::Result<()> {
+        let mut inboxes = self.email_inboxes.write().unwrap();
+        let count = inboxes.len();
+        inboxes.retain(|_, inbox| (inbox.workspace_id, inbox.user_id) != (workspace_id, user_id));
+        if inboxes.len() == count {
+            return Err(RepositoryError::NotFound(user_id).into());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UserRepositoryForDb {
+    pool: PgPool,
+}
+
+impl UserRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl UserRepository for UserRepositoryForDb {
+    #[tracing::instrument(skip_all)]
+    async fn create(&self, username: String, password_hash: String) -> anyhow::Result<User> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+            insert into users (username, password_hash)
+            values ($1, $2)
+            on conflict (username) do nothing
+            returning id, username, email, created_at
+        "#,
+        )
+        .bind(&username)
+        .bind(password_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match user {
+            Some(user) => Ok(user),
+            None => {
+                let existing = self.credentials_by_username(&username).await?;
+                Err(RepositoryError::Duplicate(existing.map_or(0, |user| user.id)).into())
+            }
+        }
+    }
+    #[tracing::instrument(skip_all)]
+    async fn find(&self, id: i32) -> anyhow::Result<User> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+            select id, username, email, created_at from users where id=$1
+        "#,
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+
+        Ok(user)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn find_or_create_by_email(&self, email: &str) -> anyhow::Result<User> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+            with created as (
+                insert into users (username, email)
+                values ($1, $1)
+                on conflict do nothing
+                returning id, username, email, created_at
+            )
+            select * from created
+            union all
+            select id, username, email, created_at from users where email=$1
+        "#,
+        )
+        .bind(email)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match user {
+            Some(user) => Ok(user),
+            // the address is taken as a username by a password account
+            None => {
+                let existing = self.credentials_by_username(email).await?;
+                Err(RepositoryError::Duplicate(existing.map_or(0, |user| user.id)).into())
+            }
+        }
+    }
+    #[tracing::instrument(skip_all)]
+    async fn credentials(&self, id: i32) -> anyhow::Result<UserCredentials> {
+        let credentials = sqlx::query_as::<_, UserCredentials>(
+            r#"
+            select id, username, password_hash, disabled_at is not null as disabled
+            from users where id=$1
+        "#,
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+            _ => RepositoryError::Unexpected(e.to_string()),
+        })?;
+
+        Ok(credentials)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn credentials_by_username(
+        &self,
+        username: &str,
+    ) -> anyhow::Result<Option<UserCredentials>> {
+        let credentials = sqlx::query_as::<_, UserCredentials>(
+            r#"
+            select id, username, password_hash, disabled_at is not null as disabled
+            from users where username=$1
+        "#,
+        )
+        .bind(username)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(credentials)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn update_password(&self, id: i32, password_hash: String) -> anyhow::Result<()> {
+        let result = sqlx::query(
+            r#"
+            update users set password_hash=$1 where id=$2
+        "#,
+        )
+        .bind(password_hash)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn notification_settings(&self, id: i32) -> anyhow::Result<NotificationSettings> {
+        let (settings,) = sqlx::query_as::<_, (Option<Json<NotificationSettings>>,)>(
+            r#"
+            select notification_settings from users where id=$1
+        "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(settings.map(|settings| settings.0).unwrap_or_default())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn set_notification_settings(
+        &self,
+        id: i32,
+        settings: NotificationSettings,
+    ) -> anyhow::Result<()> {
+        let result = sqlx::query(
+            r#"
+            update users set notification_settings=$1 where id=$2
+        "#,
+        )
+        .bind(Json(settings))
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn create_session(
+        &self,
+        session_id: String,
+        user_id: i32,
+        expires_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("delete from sessions where expires_at <= now()")
+            .execute(&mut tx)
+            .await?;
+        sqlx::query(
+            r#"
+            insert into sessions (id, user_id, expires_at) values ($1, $2, $3)
+        "#,
+        )
+        .bind(session_id)
+        .bind(user_id)
+        .bind(expires_at)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn session_user(&self, session_id: &str) -> anyhow::Result<Option<i32>> {
+        let user_id = sqlx::query_scalar::<_, i32>(
+            r#"
+            select sessions.user_id from sessions
+            join users on users.id = sessions.user_id
+            where sessions.id=$1 and sessions.expires_at > now() and users.disabled_at is null
+        "#,
+        )
+        .bind(session_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user_id)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn delete_session(&self, session_id: &str) -> anyhow::Result<()> {
+        sqlx::query("delete from sessions where id=$1")
+            .bind(session_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn create_refresh_token(&self, token: NewRefreshToken) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("delete from refresh_tokens where expires_at <= now()")
+            .execute(&mut tx)
+            .await?;
+        sqlx::query(
+            r#"
+            insert into refresh_tokens (user_id, family, token_hash, expires_at)
+            values ($1, $2, $3, $4)
+        "#,
+        )
+        .bind(token.user_id)
+        .bind(token.family)
+        .bind(token.token_hash)
+        .bind(token.expires_at)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn rotate_refresh_token(
+        &self,
+        token_hash: &str,
+        next_hash: String,
+        expires_at: DateTime<Utc>,
+    ) -> anyhow::Result<RefreshRotation> {
+        let mut tx = self.pool.begin().await?;
+        let stored = sqlx::query_as::<_, StoredRefreshToken>(
+            r#"
+            select user_id, family, expires_at, used_at from refresh_tokens
+            where token_hash=$1
+            for update
+        "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(&mut tx)
+        .await?;
+        let stored = match stored {
+            Some(stored) if stored.expires_at > Utc::now() => stored,
+            _ => return Ok(RefreshRotation::Invalid),
+        };
+        if stored.used_at.is_some() {
+            sqlx::query("delete from refresh_tokens where family=$1")
+                .bind(&stored.family)
+                .execute(&mut tx)
+                .await?;
+            tx.commit().await?;
+            return Ok(RefreshRotation::Reused {
+                user_id: stored.user_id,
+            });
+        }
+
+        sqlx::query("update refresh_tokens set used_at=now() where token_hash=$1")
+            .bind(token_hash)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query(
+            r#"
+            insert into refresh_tokens (user_id, family, token_hash, expires_at)
+            values ($1, $2, $3, $4)
+        "#,
+        )
+        .bind(stored.user_id)
+        .bind(&stored.family)
+        .bind(next_hash)
+        .bind(expires_at)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(RefreshRotation::Rotated {
+            user_id: stored.user_id,
+        })
+    }
+    #[tracing::instrument(skip_all)]
+    async fn revoke_refresh_token(&self, token_hash: &str) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            delete from refresh_tokens where family in
+                (select family from refresh_tokens where token_hash=$1)
+        "#,
+        )
+        .bind(token_hash)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn create_workspace(&self, name: String, owner_id: i32) -> anyhow::Result<Workspace> {
+        let mut tx = self.pool.begin().await?;
+        let workspace = sqlx::query_as::<_, Workspace>(
+            r#"
+            insert into workspaces (name) values ($1)
+            returning id, name, assignees_only, created_at
+        "#,
+        )
+        .bind(name)
+        .fetch_one(&mut tx)
+        .await?;
+        sqlx::query(
+            r#"
+            insert into workspace_members (workspace_id, user_id, role)
+            select $1, id, $3 from users where id=$2
+        "#,
+        )
+        .bind(workspace.id)
+        .bind(owner_id)
+        .bind(Role::Owner.as_str())
+        .execute(&mut tx)
+        .await?
+        .rows_affected()
+        .eq(&1)
+        .then_some(())
+        .ok_or(RepositoryError::NotFound(owner_id))?;
+        tx.commit().await?;
+
+        Ok(workspace)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn workspace(&self, id: i32) -> anyhow::Result<Workspace> {
+        let workspace = sqlx::query_as::<_, Workspace>(
+            r#"
+            select id, name, assignees_only, created_at from workspaces where id=$1
+        "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(workspace)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn update_workspace(
+        &self,
+        id: i32,
+        payload: UpdateWorkspace,
+    ) -> anyhow::Result<Workspace> {
+        let workspace = sqlx::query_as::<_, Workspace>(
+            r#"
+            update workspaces
+            set name=coalesce($2, name), assignees_only=coalesce($3, assignees_only)
+            where id=$1
+            returning id, name, assignees_only, created_at
+        "#,
+        )
+        .bind(id)
+        .bind(payload.name)
+        .bind(payload.assignees_only)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(workspace)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn custom_fields(&self, workspace_id: i32) -> anyhow::Result<CustomFields> {
+        let Json(fields) = sqlx::query_scalar::<_, Json<Vec<CustomField>>>(
+            r#"
+            select custom_fields from workspaces where id=$1
+        "#,
+        )
+        .bind(workspace_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(workspace_id))?;
+
+        Ok(CustomFields { fields })
+    }
+    #[tracing::instrument(skip_all)]
+    async fn set_custom_fields(
+        &self,
+        workspace_id: i32,
+        fields: CustomFields,
+    ) -> anyhow::Result<CustomFields> {
+        let result = sqlx::query("update workspaces set custom_fields=$2 where id=$1")
+            .bind(workspace_id)
+            .bind(Json(&fields.fields))
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(workspace_id).into());
+        }
+
+        Ok(fields)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn memberships(&self, user_id: i32) -> anyhow::Result<Vec<Membership>> {
+        let rows = sqlx::query_as::<_, MembershipRow>(
+            r#"
+            select workspaces.id, workspaces.name, workspaces.assignees_only,
+                workspaces.created_at, workspace_members.role
+            from workspace_members
+            join workspaces on workspaces.id = workspace_members.workspace_id
+            where workspace_members.user_id=$1
+            order by workspaces.id
+        "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(Membership::try_from).collect()
+    }
+    #[tracing::instrument(skip_all)]
+    async fn role(&self, workspace_id: i32, user_id: i32) -> anyhow::Result<Option<Role>> {
+        let role = sqlx::query_scalar::<_, String>(
+            r#"
+            select role from workspace_members where workspace_id=$1 and user_id=$2
+        "#,
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        role.map(|role| parse_role(&role)).transpose()
+    }
+    #[tracing::instrument(skip_all)]
+    async fn members(&self, workspace_id: i32) -> anyhow::Result<Vec<Member>> {
+        let rows = sqlx::query_as::<_, (i32, String)>(
+            r#"
+            select user_id, role from workspace_members where workspace_id=$1
+            order by user_id
+        "#,
+        )
+        .bind(workspace_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|(user_id, role)| {
+                Ok(Member {
+                    user_id,
+                    role: parse_role(&role)?,
+                })
+            })
+            .collect()
+    }
+    #[tracing::instrument(skip_all)]
+    async fn set_member(&self, workspace_id: i32, user_id: i32, role: Role) -> anyhow::Result<()> {
+        self.find(user_id).await?;
+        sqlx::query(
+            r#"
+            insert into workspace_members (workspace_id, user_id, role)
+            values ($1, $2, $3)
+            on conflict (workspace_id, user_id) do update set role = excluded.role
+        "#,
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .bind(role.as_str())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.code().as_deref() == Some("23503") => {
+                RepositoryError::NotFound(workspace_id)
+            }
+            e => RepositoryError::Unexpected(e.to_string()),
+        })?;
+
+        Ok(())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn remove_member(&self, workspace_id: i32, user_id: i32) -> anyhow::Result<()> {
+        let result = sqlx::query(
+            r#"
+            delete from workspace_members where workspace_id=$1 and user_id=$2
+        "#,
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(user_id).into());
+        }
+
+        Ok(())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn account(&self, id: i32) -> anyhow::Result<Account> {
+        let row = sqlx::query_as::<_, AccountRow>(&format!("{} where id=$1", SELECT_ACCOUNTS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(RepositoryError::NotFound(id))?;
+
+        row.try_into()
+    }
+    #[tracing::instrument(skip_all)]
+    async fn accounts(
+        &self,
+        filter: &AccountFilter,
+        after: Option<i32>,
+        limit: u32,
+    ) -> anyhow::Result<AccountPage> {
+        let query = format!(
+            r#"
+            {}
+            where ($1::integer is null or id < $1)
+                and ($3::text is null or strpos(lower(username), lower($3)) > 0
+                    or strpos(lower(coalesce(email, '')), lower($3)) > 0)
+                and ($4::text is null or role = $4)
+                and ($5::boolean is null or (disabled_at is not null) = $5)
+            order by id desc
+            limit $2
+        "#,
+            SELECT_ACCOUNTS
+        );
+        let rows = sqlx::query_as::<_, AccountRow>(&query)
+            .bind(after)
+            .bind(limit as i64 + 1)
+            .bind(filter.q.as_deref())
+            .bind(filter.role.map(UserRole::as_str))
+            .bind(filter.disabled)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let accounts = rows
+            .into_iter()
+            .map(Account::try_from)
+            .collect::<anyhow::Result<_>>()?;
+        Ok(AccountPage::from_rows(accounts, limit))
+    }
+    #[tracing::instrument(skip_all)]
+    async fn update_account(&self, id: i32, payload: UpdateAccount) -> anyhow::Result<Account> {
+        let mut tx = self.pool.begin().await?;
+        let row = sqlx::query_as::<_, AccountRow>(
+            r#"
+            update users
+            set role=coalesce($2, role),
+                disabled_at=case
+                    when $3::boolean is null then disabled_at
+                    when $3 then coalesce(disabled_at, now())
+                    else null
+                end
+            where id=$1
+            returning id, username, email, created_at, role, disabled_at, delete_after
+        "#,
+        )
+        .bind(id)
+        .bind(payload.role.map(UserRole::as_str))
+        .bind(payload.disabled)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+        if row.disabled_at.is_some() {
+            sqlx::query("delete from sessions where user_id=$1")
+                .bind(id)
+                .execute(&mut tx)
+                .await?;
+            sqlx::query("delete from refresh_tokens where user_id=$1")
+                .bind(id)
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        row.try_into()
+    }
+    #[tracing::instrument(skip_all)]
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        // sessions, refresh tokens, memberships, exports and views go with
+        // the row
+        let result = sqlx::query("delete from users where id=$1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn schedule_deletion(
+        &self,
+        id: i32,
+        delete_after: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<()> {
+        let result = sqlx::query("update users set delete_after=$1 where id=$2")
+            .bind(delete_after)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn deletions_due(&self) -> anyhow::Result<Vec<i32>> {
+        let due = sqlx::query_scalar::<_, i32>(
+            r#"
+            select id from users where delete_after <= now() order by id
+        "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(due)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn record_audit(&self, entry: NewAuditEntry) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            insert into audit_log (user_id, actor_id, action) values ($1, $2, $3)
+        "#,
+        )
+        .bind(entry.user_id)
+        .bind(entry.actor_id)
+        .bind(entry.action.as_str())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn audit_log(&self, user_id: i32) -> anyhow::Result<Vec<AuditEntry>> {
+        let rows = sqlx::query_as::<_, (i32, i32, Option<i32>, String, DateTime<Utc>)>(
+            r#"
+            select id, user_id, actor_id, action, created_at from audit_log
+            where user_id=$1
+            order by id
+        "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|(id, user_id, actor_id, action, created_at)| {
+                Ok(AuditEntry {
+                    id,
+                    user_id,
+                    actor_id,
+                    action: action
+                        .parse()
+                        .map_err(|e: String| RepositoryError::Unexpected(e))?,
+                    created_at,
+                })
+            })
+            .collect()
+    }
+    #[tracing::instrument(skip_all)]
+    async fn create_export(&self, user_id: i32) -> anyhow::Result<DataExport> {
+        let row = sqlx::query_as::<_, ExportRow>(
+            r#"
+            insert into data_exports (user_id, status)
+            select id, $2 from users where id=$1
+            returning id, user_id, status, created_at, completed_at
+        "#,
+        )
+        .bind(user_id)
+        .bind(ExportStatus::Pending.as_str())
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(user_id))?;
+
+        row.try_into()
+    }
+    #[tracing::instrument(skip_all)]
+    async fn finish_export(
+        &self,
+        id: i32,
+        archive: Option<serde_json::Value>,
+    ) -> anyhow::Result<()> {
+        let status = match archive {
+            Some(_) => ExportStatus::Ready,
+            None => ExportStatus::Failed,
+        };
+        let result = sqlx::query(
+            r#"
+            update data_exports set status=$2, archive=$3, completed_at=now()
+            where id=$1
+        "#,
+        )
+        .bind(id)
+        .bind(status.as_str())
+        .bind(archive.map(Json))
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn data_export(&self, user_id: i32, id: i32) -> anyhow::Result<DataExport> {
+        let row = sqlx::query_as::<_, ExportRow>(
+            r#"
+            select id, user_id, status, created_at, completed_at from data_exports
+            where id=$1 and user_id=$2
+        "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        row.try_into()
+    }
+    #[tracing::instrument(skip_all)]
+    async fn export_archive(
+        &self,
+        user_id: i32,
+        id: i32,
+    ) -> anyhow::Result<Option<serde_json::Value>> {
+        let (archive,) = sqlx::query_as::<_, (Option<Json<serde_json::Value>>,)>(
+            r#"
+            select archive from data_exports where id=$1 and user_id=$2
+        "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(archive.map(|archive| archive.0))
+    }
+    #[tracing::instrument(skip_all)]
+    async fn create_view(
+        &self,
+        workspace_id: i32,
+        user_id: i32,
+        payload: CreateView,
+    ) -> anyhow::Result<SavedView> {
+        let view = sqlx::query_as::<_, SavedView>(
+            r#"
+            insert into saved_views (workspace_id, user_id, name, filter)
+            select $1, id, $3, $4 from users where id=$2
+            returning *
+        "#,
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .bind(payload.name)
+        .bind(payload.filter)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(user_id))?;
+
+        Ok(view)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn views(&self, workspace_id: i32, user_id: i32) -> anyhow::Result<Vec<SavedView>> {
+        let views = sqlx::query_as::<_, SavedView>(
+            r#"
+            select * from saved_views where workspace_id=$1 and user_id=$2 order by id
+        "#,
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(views)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn view(&self, workspace_id: i32, user_id: i32, id: i32) -> anyhow::Result<SavedView> {
+        let view = sqlx::query_as::<_, SavedView>(
+            r#"
+            select * from saved_views where id=$1 and workspace_id=$2 and user_id=$3
+        "#,
+        )
+        .bind(id)
+        .bind(workspace_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(view)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn delete_view(&self, workspace_id: i32, user_id: i32, id: i32) -> anyhow::Result<()> {
+        let result =
+            sqlx::query("delete from saved_views where id=$1 and workspace_id=$2 and user_id=$3")
+                .bind(id)
+                .bind(workspace_id)
+                .bind(user_id)
+                .execute(&self.pool)
+                .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn slack_link(
+        &self,
+        workspace_id: i32,
+        user_id: i32,
+    ) -> anyhow::Result<Option<SlackLink>> {
+        let link = sqlx::query_as::<_, SlackLink>(
+            r#"
+            select * from slack_links where workspace_id=$1 and user_id=$2
+        "#,
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(link)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn set_slack_link(
+        &self,
+        workspace_id: i32,
+        user_id: i32,
+        payload: SetSlackLink,
+    ) -> anyhow::Result<SlackLink> {
+        if let (Some(team_id), Some(slack_user_id)) = (&payload.team_id, &payload.slack_user_id) {
+            if let Some(other) = self
+                .slack_link_by_slack_user(team_id, slack_user_id)
+                .await?
+                .filter(|other| (other.workspace_id, other.user_id) != (workspace_id, user_id))
+            {
+                return Err(RepositoryError::Duplicate(other.user_id).into());
+            }
+        }
+        let link = sqlx::query_as::<_, SlackLink>(
+            r#"
+            insert into slack_links (workspace_id, user_id, webhook_url, team_id, slack_user_id)
+            select $1, id, $3, $4, $5 from users where id=$2
+            on conflict (user_id, workspace_id) do update
+            set webhook_url=excluded.webhook_url,
+                team_id=excluded.team_id,
+                slack_user_id=excluded.slack_user_id
+            returning *
+        "#,
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .bind(payload.webhook_url)
+        .bind(payload.team_id)
+        .bind(payload.slack_user_id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(user_id))?;
+
+        Ok(link)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn delete_slack_link(&self, workspace_id: i32, user_id: i32) -> anyhow::Result<()> {
+        let result = sqlx::query("delete from slack_links where workspace_id=$1 and user_id=$2")
+            .bind(workspace_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(user_id).into());
+        }
+
+        Ok(())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn slack_link_by_slack_user(
+        &self,
+        team_id: &str,
+        slack_user_id: &str,
+    ) -> anyhow::Result<Option<SlackLink>> {
+        let link = sqlx::query_as::<_, SlackLink>(
+            r#"
+            select * from slack_links where team_id=$1 and slack_user_id=$2
+        "#,
+        )
+        .bind(team_id)
+        .bind(slack_user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(link)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn create_telegram_code(&self, code: NewTelegramCode) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            insert into telegram_link_codes (code_hash, workspace_id, user_id, expires_at)
+            values ($1, $2, $3, $4)
+        "#,
+        )
+        .bind(code.code_hash)
+        .bind(code.workspace_id)
+        .bind(code.user_id)
+        .bind(code.expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn redeem_telegram_code(
+        &self,
+        code_hash: &str,
+        chat_id: i64,
+    ) -> anyhow::Result<Option<TelegramLink>> {
+        let link = sqlx::query_as::<_, TelegramLink>(
+            r#"
+            with redeemed as (
+                delete from telegram_link_codes where code_hash=$1
+                returning workspace_id, user_id, expires_at
+            )
+            insert into telegram_links (chat_id, workspace_id, user_id)
+            select $2, workspace_id, user_id from redeemed where expires_at > now()
+            on conflict (chat_id) do update
+            set workspace_id=excluded.workspace_id,
+                user_id=excluded.user_id,
+                created_at=now()
+            returning *
+        "#,
+        )
+        .bind(code_hash)
+        .bind(chat_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(link)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn telegram_link(&self, chat_id: i64) -> anyhow::Result<Option<TelegramLink>> {
+        let link = sqlx::query_as::<_, TelegramLink>(
+            r#"
+            select * from telegram_links where chat_id=$1
+        "#,
+        )
+        .bind(chat_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(link)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn delete_telegram_link(&self, chat_id: i64) -> anyhow::Result<()> {
+        sqlx::query("delete from telegram_links where chat_id=$1")
+            .bind(chat_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+    #[tracing::instrument(skip_all)]
+    async fn set_email_inbox(&self, inbox: NewEmailInbox) -> anyhow::Result<EmailInbox> {
+        let inbox = sqlx::query_as::<_, EmailInbox>(
+            r#"
+            insert into email_inboxes (workspace_id, user_id, token_hash)
+            values ($1, $2, $3)
+            on conflict (workspace_id, user_id) do update
+            set token_hash=excluded.token_hash
+            returning workspace_id, user_id, created_at
+        "#,
+        )
+        .bind(inbox.workspace_id)
+        .bind(inbox.user_id)
+        .bind(inbox.token_hash)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(inbox)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn email_inbox(&self, token_hash: &str) -> anyhow::Result<Option<EmailInbox>> {
+        let inbox = sqlx::query_as::<_, EmailInbox>(
+            r#"
+            select workspace_id, user_id, created_at from email_inboxes where token_hash=$1
+        "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(inbox)
+    }
+    #[tracing::instrument(skip_all)]
+    async fn delete_email_inbox(&self, workspace_id: i32, user_id: i32) -> anyhow::Result<()> {
+        let result = sqlx::query("delete from email_inboxes where workspace_id=$1 and user_id=$2")
+            .bind(workspace_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(user_id).into());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct ExportRow {
+    id: i32,
+    user_id: i32,
+    status: String,
+    created_at: DateTime<Utc>,
+    completed_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<ExportRow> for DataExport {
+    type Error = anyhow::Error;
+
+    fn try_from(row: ExportRow) -> anyhow::Result<Self> {
+        Ok(Self {
+            id: row.id,
+            user_id: row.user_id,
+            status: row
+                .status
+                .parse()
+                .map_err(|e: String| RepositoryError::Unexpected(e))?,
+            created_at: row.created_at,
+            completed_at: row.completed_at,
+        })
+    }
+}
+
+const SELECT_ACCOUNTS: &str =
+    "select id, username, email, created_at, role, disabled_at, delete_after from users";
+
+#[derive(Debug, FromRow)]
+struct AccountRow {
+    id: i32,
+    username: String,
+    email: Option<String>,
+    created_at: DateTime<Utc>,
+    role: String,
+    disabled_at: Option<DateTime<Utc>>,
+    delete_after: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<AccountRow> for Account {
+    type Error = anyhow::Error;
+
+    fn try_from(row: AccountRow) -> anyhow::Result<Self> {
+        Ok(Self {
+            user: User {
+                id: row.id,
+                username: row.username,
+                email: row.email,
+                created_at: row.created_at,
+            },
+            role: row
+                .role
+                .parse()
+                .map_err(|e: String| RepositoryError::Unexpected(e))?,
+            disabled_at: row.disabled_at,
+            delete_after: row.delete_after,
+        })
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct MembershipRow {
+    id: i32,
+    name: String,
+    assignees_only: bool,
+    created_at: DateTime<Utc>,
+    role: String,
+}
+
+impl TryFrom<MembershipRow> for Membership {
+    type Error = anyhow::Error;
+
+    fn try_from(row: MembershipRow) -> anyhow::Result<Self> {
+        Ok(Self {
+            workspace: Workspace {
+                id: row.id,
+                name: row.name,
+                assignees_only: row.assignees_only,
+                created_at: row.created_at,
+            },
+            role: parse_role(&row.role)?,
+        })
+    }
+}
+
+fn parse_role(role: &str) -> anyhow::Result<Role> {
+    role.parse()
+        .map_err(|e: String| RepositoryError::Unexpected(e).into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        repositories::{
+            audit::AuditAction,
+            custom_field::FieldKind,
+            todo::{CreateTodo, OwnedTodos, TodoReader, TodoRepositoryForDb, TodoWriter},
+        },
+        test_db::TestDb,
+    };
+
+    #[tokio::test]
+    async fn should_reject_duplicate_usernames() {
+        let repository = UserRepositoryForMemory::new();
+        let alice = repository
+            .create("alice".to_string(), "hash".to_string())
+            .await
+            .unwrap();
+        let result = repository
+            .create("alice".to_string(), "other".to_string())
+            .await;
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Duplicate(id)) if *id == alice.id
+        ));
+
+        repository
+            .update_password(alice.id, "new hash".to_string())
+            .await
+            .unwrap();
+        let credentials = repository.credentials_by_username("alice").await.unwrap();
+        assert_eq!(
+            credentials.unwrap().password_hash.as_deref(),
+            Some("new hash")
+        );
+    }
+
+    #[tokio::test]
+    async fn should_link_accounts_by_email() {
+        let repository = UserRepositoryForMemory::new();
+        let created = repository
+            .find_or_create_by_email("dave@example.com")
+            .await
+            .unwrap();
+        assert_eq!(created.email.as_deref(), Some("dave@example.com"));
+        let credentials = repository.credentials(created.id).await.unwrap();
+        assert_eq!(credentials.password_hash, None);
+
+        let linked = repository
+            .find_or_create_by_email("dave@example.com")
+            .await
+            .unwrap();
+        assert_eq!(linked, created);
+    }
+
+    #[tokio::test]
+    async fn should_expire_sessions() {
+        let repository = UserRepositoryForMemory::new();
+        let later = Utc::now() + chrono::Duration::minutes(5);
+        let earlier = Utc::now() - chrono::Duration::minutes(5);
+        repository
+            .create_session("live".to_string(), 1, later)
+            .await
+            .unwrap();
+        repository
+            .create_session("stale".to_string(), 1, earlier)
+            .await
+            .unwrap();
+
+        assert_eq!(repository.session_user("live").await.unwrap(), Some(1));
+        assert_eq!(repository.session_user("stale").await.unwrap(), None);
+        repository.delete_session("live").await.unwrap();
+        assert_eq!(repository.session_user("live").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn should_revoke_refresh_family_on_reuse() {
+        let repository = UserRepositoryForMemory::new();
+        let expires_at = Utc::now() + chrono::Duration::minutes(5);
+        repository
+            .create_refresh_token(NewRefreshToken {
+                user_id: 1,
+                family: "family".to_string(),
+                token_hash: "first".to_string(),
+                expires_at,
+            })
+            .await
+            .unwrap();
+
+        let rotation = repository
+            .rotate_refresh_token("first", "second".to_string(), expires_at)
+            .await
+            .unwrap();
+        assert_eq!(rotation, RefreshRotation::Rotated { user_id: 1 });
+        let rotation = repository
+            .rotate_refresh_token("first", "third".to_string(), expires_at)
+            .await
+            .unwrap();
+        assert_eq!(rotation, RefreshRotation::Reused { user_id: 1 });
+        // the reuse took the live token down with it
+        let rotation = repository
+            .rotate_refresh_token("second", "fourth".to_string(), expires_at)
+            .await
+            .unwrap();
+        assert_eq!(rotation, RefreshRotation::Invalid);
+    }
+
+    #[tokio::test]
+    async fn should_keep_slack_users_to_one_link() {
+        let repository = UserRepositoryForMemory::new();
+        let alice = repository
+            .create("alice".to_string(), "hash".to_string())
+            .await
+            .unwrap();
+        let bob = repository
+            .create("bob".to_string(), "hash".to_string())
+            .await
+            .unwrap();
+        let link = |slack_user_id: &str| SetSlackLink {
+            webhook_url: "https://hooks.slack.com/services/T0/B0/x".to_string(),
+            team_id: Some("T0".to_string()),
+            slack_user_id: Some(slack_user_id.to_string()),
+        };
+
+        let linked = repository
+            .set_slack_link(DEFAULT_WORKSPACE_ID, alice.id, link("U1"))
+            .await
+            .unwrap();
+        assert_eq!(
+            repository
+                .slack_link_by_slack_user("T0", "U1")
+                .await
+                .unwrap(),
+            Some(linked)
+        );
+        let result = repository
+            .set_slack_link(DEFAULT_WORKSPACE_ID, bob.id, link("U1"))
+            .await;
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Duplicate(id)) if *id == alice.id
+        ));
+        // relinking replaces the user's own link
+        repository
+            .set_slack_link(DEFAULT_WORKSPACE_ID, alice.id, link("U2"))
+            .await
+            .unwrap();
+        assert_eq!(
+            repository
+                .slack_link_by_slack_user("T0", "U1")
+                .await
+                .unwrap(),
+            None
+        );
+
+        repository.delete(alice.id).await.unwrap();
+        assert_eq!(
+            repository
+                .slack_link(DEFAULT_WORKSPACE_ID, alice.id)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn should_redeem_telegram_codes_once() {
+        let repository = UserRepositoryForMemory::new();
+        let user = repository
+            .create("tina".to_string(), "hash".to_string())
+            .await
+            .unwrap();
+        let code = |code_hash: &str, expires_in| NewTelegramCode {
+            code_hash: code_hash.to_string(),
+            workspace_id: DEFAULT_WORKSPACE_ID,
+            user_id: user.id,
+            expires_at: Utc::now() + chrono::Duration::minutes(expires_in),
+        };
+        repository
+            .create_telegram_code(code("live", 5))
+            .await
+            .unwrap();
+        repository
+            .create_telegram_code(code("stale", -1))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            repository.redeem_telegram_code("stale", 7).await.unwrap(),
+            None
+        );
+        let link = repository
+            .redeem_telegram_code("live", 7)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((link.chat_id, link.user_id), (7, user.id));
+        assert_eq!(
+            repository.redeem_telegram_code("live", 8).await.unwrap(),
+            None
+        );
+        assert_eq!(repository.telegram_link(7).await.unwrap(), Some(link));
+
+        repository.delete(user.id).await.unwrap();
+        assert_eq!(repository.telegram_link(7).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn should_keep_custom_fields_per_workspace() {
+        let repository = UserRepositoryForMemory::new();
+        let user = repository
+            .create("eve".to_string(), "hash".to_string())
+            .await
+            .unwrap();
+        let workspace = repository
+            .create_workspace("team".to_string(), user.id)
+            .await
+            .unwrap();
+        let fields = CustomFields {
+            fields: vec![CustomField {
+                name: "size".to_string(),
+                kind: FieldKind::Enum,
+                required: false,
+                values: vec!["s".to_string(), "l".to_string()],
+            }],
+        };
+
+        repository
+            .set_custom_fields(workspace.id, fields.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            repository.custom_fields(workspace.id).await.unwrap(),
+            fields
+        );
+        assert_eq!(
+            repository
+                .custom_fields(DEFAULT_WORKSPACE_ID)
+                .await
+                .unwrap(),
+            CustomFields::default()
+        );
+        assert!(repository
+            .set_custom_fields(workspace.id + 1, fields)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn should_rotate_email_inbox_tokens() {
+        let repository = UserRepositoryForMemory::new();
+        let user = repository
+            .create("eve".to_string(), "hash".to_string())
+            .await
+            .unwrap();
+        let inbox = |token_hash: &str| NewEmailInbox {
+            token_hash: token_hash.to_string(),
+            workspace_id: DEFAULT_WORKSPACE_ID,
+            user_id: user.id,
+        };
+
+        let created = repository.set_email_inbox(inbox("old")).await.unwrap();
+        assert_eq!(
+            repository.email_inbox("old").await.unwrap(),
+            Some(created.clone())
+        );
+        let rotated = repository.set_email_inbox(inbox("new")).await.unwrap();
+        assert_eq!(rotated, created);
+        assert_eq!(repository.email_inbox("old").await.unwrap(), None);
+        assert_eq!(repository.email_inbox("new").await.unwrap(), Some(created));
+
+        repository.delete(user.id).await.unwrap();
+        assert_eq!(repository.email_inbox("new").await.unwrap(), None);
+        assert!(repository
+            .delete_email_inbox(DEFAULT_WORKSPACE_ID, user.id)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        let db = TestDb::new().await;
+        let pool = db.pool.clone();
+        let repository = UserRepositoryForDb::new(pool.clone());
+        let username = format!(
+            "[crud_scenario] {}",
+            Utc::now().timestamp_nanos_opt().unwrap()
+        );
+
+        let user = repository
+            .create(username.clone(), "hash".to_string())
+            .await
+            .unwrap();
+        assert_eq!(repository.find(user.id).await.unwrap(), user);
+        let result = repository
+            .create(username.clone(), "hash".to_string())
+            .await;
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Duplicate(_))
+        ));
+
+        repository
+            .update_password(user.id, "new hash".to_string())
+            .await
+            .unwrap();
+        let credentials = repository.credentials(user.id).await.unwrap();
+        assert_eq!(credentials.password_hash.as_deref(), Some("new hash"));
+        assert!(repository
+            .credentials_by_username("[crud_scenario] nobody")
+            .await
+            .unwrap()
+            .is_none());
+
+        assert_eq!(
+            repository.notification_settings(user.id).await.unwrap(),
+            NotificationSettings::default()
+        );
+        let mut settings = NotificationSettings::default();
+        settings.commented.email = true;
+        repository
+            .set_notification_settings(user.id, settings)
+            .await
+            .unwrap();
+        assert_eq!(
+            repository.notification_settings(user.id).await.unwrap(),
+            settings
+        );
+
+        let email = format!("{}@example.com", Utc::now().timestamp_nanos_opt().unwrap());
+        let created = repository.find_or_create_by_email(&email).await.unwrap();
+        assert_eq!(created.email.as_deref(), Some(email.as_str()));
+        let linked = repository.find_or_create_by_email(&email).await.unwrap();
+        assert_eq!(linked, created);
+        sqlx::query("delete from users where id=$1")
+            .bind(created.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let session_id = format!("{}-session", username);
+        repository
+            .create_session(
+                session_id.clone(),
+                user.id,
+                Utc::now() + chrono::Duration::minutes(5),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            repository.session_user(&session_id).await.unwrap(),
+            Some(user.id)
+        );
+        repository.delete_session(&session_id).await.unwrap();
+        assert_eq!(repository.session_user(&session_id).await.unwrap(), None);
+
+        let expires_at = Utc::now() + chrono::Duration::minutes(5);
+        let first = format!("{}-first", username);
+        let second = format!("{}-second", username);
+        repository
+            .create_refresh_token(NewRefreshToken {
+                user_id: user.id,
+                family: username.clone(),
+                token_hash: first.clone(),
+                expires_at,
+            })
+            .await
+            .unwrap();
+        let rotation = repository
+            .rotate_refresh_token(&first, second.clone(), expires_at)
+            .await
+            .unwrap();
+        assert_eq!(rotation, RefreshRotation::Rotated { user_id: user.id });
+        repository.revoke_refresh_token(&first).await.unwrap();
+        let rotation = repository
+            .rotate_refresh_token(&second, format!("{}-third", username), expires_at)
+            .await
+            .unwrap();
+        assert_eq!(rotation, RefreshRotation::Invalid);
+
+        let account = repository
+            .update_account(
+                user.id,
+                UpdateAccount {
+                    role: Some(UserRole::Admin),
+                    disabled: Some(true),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(account.role, UserRole::Admin);
+        assert!(account.is_disabled());
+        assert!(repository.credentials(user.id).await.unwrap().disabled);
+        let filter = AccountFilter {
+            q: Some(username.to_uppercase()),
+            role: Some(UserRole::Admin),
+            disabled: Some(true),
+        };
+        let page = repository.accounts(&filter, None, 10).await.unwrap();
+        assert_eq!(page.items, vec![account]);
+        let page = repository
+            .accounts(&filter, Some(user.id), 10)
+            .await
+            .unwrap();
+        assert!(page.items.is_empty());
+
+        let todos = TodoRepositoryForDb::new(pool.clone());
+        let todo = todos
+            .create(CreateTodo {
+                owner_id: Some(user.id),
+                ..CreateTodo::new("[crud_scenario] owned".to_string())
+            })
+            .await
+            .unwrap();
+        let released = todos
+            .release_owned(user.id, OwnedTodos::Delete)
+            .await
+            .unwrap();
+        assert_eq!(released, vec![(DEFAULT_WORKSPACE_ID, todo.id)]);
+        assert!(!todos.exists(todo.id).await.unwrap());
+
+        let export = repository.create_export(user.id).await.unwrap();
+        assert_eq!(export.status, ExportStatus::Pending);
+        assert_eq!(
+            repository.export_archive(user.id, export.id).await.unwrap(),
+            None
+        );
+        let archive = serde_json::json!({ "todos": [] });
+        repository
+            .finish_export(export.id, Some(archive.clone()))
+            .await
+            .unwrap();
+        let export = repository.data_export(user.id, export.id).await.unwrap();
+        assert_eq!(export.status, ExportStatus::Ready);
+        assert_eq!(
+            repository.export_archive(user.id, export.id).await.unwrap(),
+            Some(archive)
+        );
+        assert!(repository.data_export(created.id, export.id).await.is_err());
+
+        let view = repository
+            .create_view(
+                DEFAULT_WORKSPACE_ID,
+                user.id,
+                CreateView {
+                    name: "open".to_string(),
+                    filter: "completed:false".to_string(),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            repository
+                .views(DEFAULT_WORKSPACE_ID, user.id)
+                .await
+                .unwrap(),
+            vec![view.clone()]
+        );
+        assert_eq!(
+            repository
+                .view(DEFAULT_WORKSPACE_ID, user.id, view.id)
+                .await
+                .unwrap(),
+            view
+        );
+        assert!(repository
+            .view(DEFAULT_WORKSPACE_ID, created.id, view.id)
+            .await
+            .is_err());
+        repository
+            .delete_view(DEFAULT_WORKSPACE_ID, user.id, view.id)
+            .await
+            .unwrap();
+        assert!(repository
+            .delete_view(DEFAULT_WORKSPACE_ID, user.id, view.id)
+            .await
+            .is_err());
+
+        let link = repository
+            .set_slack_link(
+                DEFAULT_WORKSPACE_ID,
+                user.id,
+                SetSlackLink {
+                    webhook_url: "https://hooks.slack.com/services/T0/B0/x".to_string(),
+                    team_id: Some("T0".to_string()),
+                    slack_user_id: Some(username.clone()),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            repository
+                .slack_link_by_slack_user("T0", &username)
+                .await
+                .unwrap(),
+            Some(link)
+        );
+        repository
+            .delete_slack_link(DEFAULT_WORKSPACE_ID, user.id)
+            .await
+            .unwrap();
+        assert!(repository
+            .delete_slack_link(DEFAULT_WORKSPACE_ID, user.id)
+            .await
+            .is_err());
+
+        let chat_id = Utc::now().timestamp_nanos_opt().unwrap();
+        repository
+            .create_telegram_code(NewTelegramCode {
+                code_hash: username.clone(),
+                workspace_id: DEFAULT_WORKSPACE_ID,
+                user_id: user.id,
+                expires_at: Utc::now() + chrono::Duration::minutes(5),
+            })
+            .await
+            .unwrap();
+        let link = repository
+            .redeem_telegram_code(&username, chat_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(link.user_id, user.id);
+        assert_eq!(
+            repository
+                .redeem_telegram_code(&username, chat_id)
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(repository.telegram_link(chat_id).await.unwrap(), Some(link));
+        repository.delete_telegram_link(chat_id).await.unwrap();
+        assert_eq!(repository.telegram_link(chat_id).await.unwrap(), None);
+
+        let inbox = repository
+            .set_email_inbox(NewEmailInbox {
+                token_hash: username.clone(),
+                workspace_id: DEFAULT_WORKSPACE_ID,
+                user_id: user.id,
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            repository.email_inbox(&username).await.unwrap(),
+            Some(inbox)
+        );
+        repository
+            .delete_email_inbox(DEFAULT_WORKSPACE_ID, user.id)
+            .await
+            .unwrap();
+        assert_eq!(repository.email_inbox(&username).await.unwrap(), None);
+
+        let workspace = repository
+            .create_workspace("custom".to_string(), user.id)
+            .await
+            .unwrap();
+        assert_eq!(
+            repository.custom_fields(workspace.id).await.unwrap(),
+            CustomFields::default()
+        );
+        let fields = CustomFields {
+            fields: vec![CustomField {
+                name: "sprint".to_string(),
+                kind: FieldKind::Number,
+                required: true,
+                values: vec![],
+            }],
+        };
+        repository
+            .set_custom_fields(workspace.id, fields.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            repository.custom_fields(workspace.id).await.unwrap(),
+            fields
+        );
+
+        repository
+            .record_audit(NewAuditEntry::by_user(
+                user.id,
+                AuditAction::DeletionScheduled,
+            ))
+            .await
+            .unwrap();
+        let log = repository.audit_log(user.id).await.unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].action, AuditAction::DeletionScheduled);
+        repository
+            .schedule_deletion(user.id, Some(Utc::now() - chrono::Duration::seconds(1)))
+            .await
+            .unwrap();
+        assert!(repository.deletions_due().await.unwrap().contains(&user.id));
+
+        repository.delete(user.id).await.unwrap();
+        assert!(repository.find(user.id).await.is_err());
+        sqlx::query("delete from audit_log where user_id=$1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
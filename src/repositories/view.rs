@@ -0,0 +1,57 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::{Validate, ValidationError};
+
+use super::filter::FilterExpr;
+
+/// A named filter expression a user keeps to list the matching todos of a
+/// workspace again later, e.g. `completed:false label:work`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow)]
+pub struct SavedView {
+    pub id: i32,
+    pub workspace_id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub filter: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+pub struct CreateView {
+    #[validate(length(min = 1, message = "can not be empty"))]
+    #[validate(length(max = 100, message = "can not be over 100"))]
+    pub name: String,
+    #[validate(length(max = 500, message = "can not be over 500"))]
+    #[validate(custom = "validate_filter")]
+    pub filter: String,
+}
+
+fn validate_filter(filter: &str) -> Result<(), ValidationError> {
+    filter.parse::<FilterExpr>().map(|_| ()).map_err(|e| {
+        let mut error = ValidationError::new("filter");
+        error.message = Some(e.to_string().into());
+        error
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_validate_filter_expression() {
+        let view = |filter: &str| CreateView {
+            name: "work".to_string(),
+            filter: filter.to_string(),
+        };
+
+        assert!(view("completed:false label:work").validate().is_ok());
+        let errors = view("priority:1").validate().unwrap_err();
+        let message = errors.field_errors()["filter"][0].message.clone();
+        assert_eq!(
+            message.unwrap(),
+            "unknown field `priority`: use completed, label, assignee or due"
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use std::net::{SocketAddr, TcpListener};
+
+use anyhow::Context;
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use socket2::{Domain, Socket, Type};
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    task::JoinSet,
+};
+
+use crate::config::TlsConfig;
+
+/// Serves `app` on every address in `addrs`, over HTTPS when `tls` is set,
+/// until one of them fails.
+pub async fn serve(
+    app: Router,
+    addrs: &[SocketAddr],
+    tls: Option<&TlsConfig>,
+) -> anyhow::Result<()> {
+    let rustls = match tls {
+        Some(tls) => {
+            let rustls = RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path).await?;
+            reload_on_sighup(rustls.clone(), tls.clone())?;
+            Some(rustls)
+        }
+        None => None,
+    };
+
+    let mut servers = JoinSet::new();
+    for &addr in addrs {
+        let listener = bind(addr).with_context(|| format!("cannot listen on {}", addr))?;
+        let app = app
+            .clone()
+            .into_make_service_with_connect_info::<SocketAddr>();
+        match &rustls {
+            Some(rustls) => {
+                tracing::debug!("listening on https://{}", addr);
+                let server = axum_server::from_tcp_rustls(listener, rustls.clone());
+                servers.spawn(async move { server.serve(app).await.map_err(anyhow::Error::from) });
+            }
+            None => {
+                tracing::debug!("listening on http://{}", addr);
+                let server = axum::Server::from_tcp(listener)?;
+                servers.spawn(async move { server.serve(app).await.map_err(anyhow::Error::from) });
+            }
+        }
+    }
+    while let Some(result) = servers.join_next().await {
+        result??;
+    }
+
+    Ok(())
+}
+
+/// A listening socket for `addr`. The unspecified IPv6 address `[::]`
+/// takes IPv4 connections too, whatever the system default is.
+fn bind(addr: SocketAddr) -> anyhow::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, None)?;
+    if addr.is_ipv6() && addr.ip().is_unspecified() {
+        socket.set_only_v6(false)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    Ok(socket.into())
+}
+
+/// Reads the certificate and key again on every SIGHUP. New connections
+/// get the new pair; a pair that fails to load leaves the old one in use.
+fn reload_on_sighup(rustls: RustlsConfig, tls: TlsConfig) -> anyhow::Result<()> {
+    let mut hangups = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            match rustls
+                .reload_from_pem_file(&tls.cert_path, &tls.key_path)
+                .await
+            {
+                Ok(()) => tracing::info!("reloaded TLS certificate from {}", tls.cert_path),
+                Err(e) => tracing::warn!("TLS certificate reload failed: {:?}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_bind_ipv6_any_for_both_families() {
+        let listener = bind("[::]:0".parse().unwrap()).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+        std::net::TcpStream::connect(("::1", port)).unwrap();
+    }
+}
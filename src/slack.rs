@@ -0,0 +1,136 @@
+use std::sync::Arc;
+
+use axum::{async_trait, http::HeaderMap};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+
+use crate::{
+    auth::csrf,
+    notifications::{Channel, Delivery, Notification},
+    repositories::user::UserRepository,
+};
+
+/// Requests older than this are refused, so captured ones can not be
+/// replayed later.
+const MAX_REQUEST_AGE_SECS: i64 = 5 * 60;
+
+/// Posts Slack notifications to the recipient's webhook for the todo's
+/// workspace and hands every other channel to `next`. Recipients without a
+/// link get nothing on Slack.
+pub struct SlackDelivery<U> {
+    users: Arc<U>,
+    http: reqwest::Client,
+    next: Arc<dyn Delivery>,
+}
+
+impl<U: UserRepository> SlackDelivery<U> {
+    pub fn new(users: Arc<U>, next: Arc<dyn Delivery>) -> Self {
+        Self {
+            users,
+            http: reqwest::Client::new(),
+            next,
+        }
+    }
+}
+
+#[async_trait]
+impl<U: UserRepository> Delivery for SlackDelivery<U> {
+    async fn deliver(&self, channel: Channel, notification: &Notification) -> anyhow::Result<()> {
+        if channel != Channel::Slack {
+            return self.next.deliver(channel, notification).await;
+        }
+        let Some(link) = self
+            .users
+            .slack_link(notification.workspace_id, notification.user_id)
+            .await?
+        else {
+            return Ok(());
+        };
+        self.http
+            .post(&link.webhook_url)
+            .json(&json!({ "text": notification.summary() }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Whether `body` was signed with `secret` by Slack, within the last five
+/// minutes of `now`, as its `X-Slack-Signature` and
+/// `X-Slack-Request-Timestamp` headers tell.
+pub fn verify_signature(
+    secret: &str,
+    headers: &HeaderMap,
+    body: &[u8],
+    now: DateTime<Utc>,
+) -> bool {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    let (Some(timestamp), Some(given)) = (
+        header("x-slack-request-timestamp"),
+        header("x-slack-signature"),
+    ) else {
+        return false;
+    };
+    let Ok(sent_at) = timestamp.parse::<i64>() else {
+        return false;
+    };
+    if (now.timestamp() - sent_at).abs() > MAX_REQUEST_AGE_SECS {
+        return false;
+    }
+    csrf::matches(&signature(secret, timestamp, body), given)
+}
+
+/// `v0=` and the hex HMAC-SHA256 of `v0:<timestamp>:<body>`.
+fn signature(secret: &str, timestamp: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(format!("v0:{}:", timestamp).as_bytes());
+    mac.update(body);
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("v0={}", hex)
+}
+
+/// The headers Slack would send along `body`.
+#[cfg(test)]
+pub(crate) fn signed_headers(secret: &str, body: &[u8], sent_at: DateTime<Utc>) -> HeaderMap {
+    let timestamp = sent_at.timestamp().to_string();
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "x-slack-signature",
+        signature(secret, &timestamp, body).parse().unwrap(),
+    );
+    headers.insert("x-slack-request-timestamp", timestamp.parse().unwrap());
+    headers
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_verify_slack_signatures() {
+        // the example of Slack's "Verifying requests from Slack" guide
+        let body = b"token=xyzz0WbapA4vBCDEFasx0q6G&team_id=T1DC2JH3J&team_domain=testteamnow&channel_id=G8PSS9T3V&channel_name=foobar&user_id=U2CERLKJA&user_name=roadrunner&command=%2Fwebhook-collect&text=&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2FT1DC2JH3J%2F397700885554%2F96rGlfmibIGlgcZRskXaIFfN&trigger_id=398738663015.47445629121.803a0bc887a14d10d2c447fce8b6703c";
+        assert_eq!(
+            signature("8f742231b10e8888abcd99yyyzzz85a5", "1531420618", body),
+            "v0=a2114d57b48eac39b9ad189dd8316235a7b4a8d21a10bd27519666489c69b503"
+        );
+
+        let now = Utc::now();
+        let headers = signed_headers("secret", body, now);
+        assert!(verify_signature("secret", &headers, body, now));
+        assert!(!verify_signature("other", &headers, body, now));
+        assert!(!verify_signature("secret", &headers, b"text=forged", now));
+        let later = now + chrono::Duration::minutes(6);
+        assert!(!verify_signature("secret", &headers, body, later));
+        assert!(!verify_signature("secret", &HeaderMap::new(), body, now));
+    }
+}
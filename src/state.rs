@@ -0,0 +1,81 @@
+use std::sync::Arc;
+
+use crate::{
+    auth::{lockout::Lockout, oauth::OAuthClients, token::TokenKeys},
+    config::Config,
+    events::Events,
+    notifications::Notifier,
+    outbox::Outbox,
+    quota::Quotas,
+    reporting::{self, Reporter},
+    repositories::{label::LabelRepository, todo::TodoRepository, user::UserRepository},
+};
+
+pub struct AppState<Todo: TodoRepository, Label: LabelRepository, User: UserRepository> {
+    pub todo_repository: Arc<Todo>,
+    pub label_repository: Arc<Label>,
+    pub user_repository: Arc<User>,
+    pub config: Arc<Config>,
+    pub events: Events,
+    /// Woken by `EventedRepository` after every todo change so its events
+    /// go out promptly.
+    pub outbox: Outbox,
+    pub notifier: Notifier,
+    pub quotas: Quotas,
+    pub tokens: Arc<TokenKeys>,
+    pub oauth: Arc<OAuthClients>,
+    /// Unset when `auth.lockout.enabled` is off.
+    pub lockout: Option<Lockout>,
+    /// Unset when no error reporting is configured.
+    pub reporter: Option<Reporter>,
+}
+
+impl<Todo: TodoRepository, Label: LabelRepository, User: UserRepository>
+    AppState<Todo, Label, User>
+{
+    pub fn new(
+        todo_repository: Todo,
+        label_repository: Label,
+        user_repository: User,
+        config: Config,
+    ) -> Self {
+        Self {
+            todo_repository: Arc::new(todo_repository),
+            label_repository: Arc::new(label_repository),
+            user_repository: Arc::new(user_repository),
+            tokens: Arc::new(TokenKeys::from_config(&config.auth)),
+            oauth: Arc::new(OAuthClients::from_config(&config.auth)),
+            lockout: config.auth.lockout.enabled.then(|| {
+                Lockout::from_config(&config.auth.lockout).expect("invalid lockout settings")
+            }),
+            quotas: Quotas::new(config.quota.clone()),
+            reporter: reporting::from_config(&config.reporting)
+                .expect("invalid error reporting settings"),
+            config: Arc::new(config),
+            events: Events::default(),
+            outbox: Outbox::default(),
+            notifier: Notifier::default(),
+        }
+    }
+}
+
+impl<Todo: TodoRepository, Label: LabelRepository, User: UserRepository> Clone
+    for AppState<Todo, Label, User>
+{
+    fn clone(&self) -> Self {
+        Self {
+            todo_repository: self.todo_repository.clone(),
+            label_repository: self.label_repository.clone(),
+            user_repository: self.user_repository.clone(),
+            config: self.config.clone(),
+            events: self.events.clone(),
+            outbox: self.outbox.clone(),
+            notifier: self.notifier.clone(),
+            quotas: self.quotas.clone(),
+            tokens: self.tokens.clone(),
+            oauth: self.oauth.clone(),
+            lockout: self.lockout.clone(),
+            reporter: self.reporter.clone(),
+        }
+    }
+}
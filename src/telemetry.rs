@@ -0,0 +1,184 @@
+use axum::http::HeaderMap;
+use tracing::Span;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+use crate::config::{Config, LogFormat, TelemetryConfig};
+
+/// Flushes spans not yet exported when dropped; keep it for the life of the
+/// process.
+pub struct Telemetry {
+    _private: (),
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        opentelemetry::global::shutdown_tracer_provider();
+    }
+}
+
+/// Installs the global subscriber: log lines on stderr in `log_format`, and
+/// trace export when `telemetry.otlp_endpoint` is set.
+pub fn init(config: &Config) -> anyhow::Result<Telemetry> {
+    let logs = tracing_subscriber::fmt::layer().with_writer(std::io::stderr);
+    let logs = match config.log_format {
+        LogFormat::Pretty => logs.boxed(),
+        // every span the line was logged in goes along, so lines from a
+        // repository call still carry the request id
+        LogFormat::Json => logs
+            .json()
+            .flatten_event(true)
+            .with_current_span(false)
+            .with_span_list(true)
+            .boxed(),
+    };
+    tracing_subscriber::registry()
+        .with(EnvFilter::new(&config.log_level))
+        .with(logs)
+        .with(otlp::layer(&config.telemetry)?)
+        .try_init()?;
+    Ok(Telemetry { _private: () })
+}
+
+/// Makes `span` part of the trace an incoming W3C `traceparent` header
+/// names, if any.
+pub fn continue_trace(span: &Span, headers: &HeaderMap) {
+    #[cfg(feature = "otel")]
+    otlp::continue_trace(span, headers);
+    #[cfg(not(feature = "otel"))]
+    let _ = (span, headers);
+}
+
+/// `traceparent` and friends for an outgoing request, so the service called
+/// continues the current trace. Empty without trace export.
+pub fn trace_headers() -> HeaderMap {
+    #[allow(unused_mut)]
+    let mut headers = HeaderMap::new();
+    #[cfg(feature = "otel")]
+    otlp::inject(&Span::current(), &mut headers);
+    headers
+}
+
+#[cfg(feature = "otel")]
+mod otlp {
+    use std::str::FromStr;
+
+    use axum::http::{HeaderName, HeaderValue};
+    use opentelemetry::{
+        global,
+        propagation::{Extractor, Injector},
+        KeyValue,
+    };
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::{propagation::TraceContextPropagator, runtime, trace, Resource};
+    use tracing::Subscriber;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    use tracing_subscriber::registry::LookupSpan;
+
+    use super::*;
+
+    pub fn layer<S>(config: &TelemetryConfig) -> anyhow::Result<Option<impl Layer<S>>>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let Some(endpoint) = &config.otlp_endpoint else {
+            return Ok(None);
+        };
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let tracer = opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .http()
+                    .with_endpoint(endpoint),
+            )
+            .with_trace_config(trace::config().with_resource(Resource::new([KeyValue::new(
+                "service.name",
+                config.service_name.clone(),
+            )])))
+            .install_batch(runtime::Tokio)?;
+        Ok(Some(tracing_opentelemetry::layer().with_tracer(tracer)))
+    }
+
+    pub fn continue_trace(span: &Span, headers: &HeaderMap) {
+        let parent = global::get_text_map_propagator(|propagator| {
+            propagator.extract(&HeaderExtractor(headers))
+        });
+        span.set_parent(parent);
+    }
+
+    pub fn inject(span: &Span, headers: &mut HeaderMap) {
+        let context = span.context();
+        global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(&context, &mut HeaderInjector(headers))
+        });
+    }
+
+    struct HeaderExtractor<'a>(&'a HeaderMap);
+
+    impl Extractor for HeaderExtractor<'_> {
+        fn get(&self, key: &str) -> Option<&str> {
+            self.0.get(key).and_then(|value| value.to_str().ok())
+        }
+
+        fn keys(&self) -> Vec<&str> {
+            self.0.keys().map(HeaderName::as_str).collect()
+        }
+    }
+
+    struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+    impl Injector for HeaderInjector<'_> {
+        fn set(&mut self, key: &str, value: String) {
+            if let (Ok(name), Ok(value)) =
+                (HeaderName::from_str(key), HeaderValue::from_str(&value))
+            {
+                self.0.insert(name, value);
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "otel"))]
+mod otlp {
+    use tracing_subscriber::layer::Identity;
+
+    use super::*;
+
+    pub fn layer(_config: &TelemetryConfig) -> anyhow::Result<Option<Identity>> {
+        Ok(None)
+    }
+}
+
+#[cfg(all(test, feature = "otel"))]
+mod test {
+    use axum::http::HeaderValue;
+    use opentelemetry::{global, trace::TracerProvider as _};
+    use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::TracerProvider};
+
+    use super::*;
+
+    #[test]
+    fn should_continue_incoming_trace() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = TracerProvider::builder().build();
+        let tracer = provider.tracer("test");
+        let subscriber =
+            tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let mut incoming = HeaderMap::new();
+            incoming.insert(
+                "traceparent",
+                HeaderValue::from_static("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+            );
+            let span = tracing::info_span!("request");
+            continue_trace(&span, &incoming);
+
+            let outgoing = span.in_scope(trace_headers);
+            let traceparent = outgoing["traceparent"].to_str().unwrap();
+            assert!(traceparent.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+            assert!(!traceparent.contains("00f067aa0ba902b7"));
+        });
+    }
+}
@@ -0,0 +1,128 @@
+//! Throwaway databases for tests. Every `TestDb` is a fresh, migrated
+//! database no other test touches: in a Postgres container of its own, or
+//! on the server at `TEST_DATABASE_URL` where Docker is not available.
+
+use std::{
+    env,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use dotenv::dotenv;
+use sqlx::{Connection, Executor, PgConnection, PgPool};
+use testcontainers_modules::{
+    postgres::Postgres,
+    testcontainers::{runners::AsyncRunner, ContainerAsync, ImageExt},
+};
+
+use crate::database;
+
+/// The version the Dockerfile runs.
+const POSTGRES_TAG: &str = "13-alpine";
+
+static NEXT_DATABASE: AtomicU32 = AtomicU32::new(0);
+
+pub struct TestDb {
+    pub pool: PgPool,
+    pub url: String,
+    cleanup: Cleanup,
+}
+
+enum Cleanup {
+    /// Removed with the container when dropped.
+    Container {
+        _container: Box<ContainerAsync<Postgres>>,
+    },
+    Database {
+        name: String,
+        server_url: String,
+    },
+}
+
+impl TestDb {
+    pub async fn new() -> Self {
+        dotenv().ok();
+        let (url, cleanup) = match env::var("TEST_DATABASE_URL") {
+            Ok(server_url) => {
+                let name = format!(
+                    "my_todo_test_{}_{}",
+                    std::process::id(),
+                    NEXT_DATABASE.fetch_add(1, Ordering::Relaxed)
+                );
+                let mut conn = PgConnection::connect(&server_url)
+                    .await
+                    .expect("failed connect database");
+                conn.execute(format!("create database {}", name).as_str())
+                    .await
+                    .expect("failed create database");
+                (
+                    with_database(&server_url, &name),
+                    Cleanup::Database { name, server_url },
+                )
+            }
+            Err(_) => {
+                let container = Postgres::default()
+                    .with_tag(POSTGRES_TAG)
+                    .start()
+                    .await
+                    .expect("failed start postgres; is Docker running? or set TEST_DATABASE_URL");
+                let url = format!(
+                    "postgres://postgres:postgres@{}:{}/postgres",
+                    container.get_host().await.unwrap(),
+                    container.get_host_port_ipv4(5432).await.unwrap()
+                );
+                (
+                    url,
+                    Cleanup::Container {
+                        _container: Box::new(container),
+                    },
+                )
+            }
+        };
+
+        let pool = PgPool::connect(&url)
+            .await
+            .expect("failed connect database");
+        database::migrate(&pool)
+            .await
+            .expect("failed migrate database");
+        Self { pool, url, cleanup }
+    }
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        let Cleanup::Database { name, server_url } = &self.cleanup else {
+            return;
+        };
+        let drop_database = format!("drop database if exists {} with (force)", name);
+        let server_url = server_url.clone();
+        // the test's runtime may be a single thread that is busy dropping us
+        let dropped = std::thread::spawn(move || {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?
+                .block_on(async {
+                    let mut conn = PgConnection::connect(&server_url).await?;
+                    conn.execute(drop_database.as_str()).await?;
+                    anyhow::Ok(())
+                })
+        })
+        .join();
+        if !matches!(dropped, Ok(Ok(()))) {
+            eprintln!("failed to drop test database {}", name);
+        }
+    }
+}
+
+/// `url` pointing at database `name` of the same server.
+fn with_database(url: &str, name: &str) -> String {
+    let (url, params) = url.split_once('?').unwrap_or((url, ""));
+    let server = match url.rsplit_once('/') {
+        Some((server, _)) if !server.ends_with('/') => server,
+        _ => url,
+    };
+    match params {
+        "" => format!("{}/{}", server, name),
+        params => format!("{}/{}?{}", server, name, params),
+    }
+}
@@ -0,0 +1,399 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Form, Router,
+};
+use maud::{html, Markup, DOCTYPE};
+use serde::Deserialize;
+use serde_json::json;
+use validator::Validate;
+
+use self::fragments::fragment_routes;
+use crate::{
+    auth::csrf::{CsrfToken, CSRF_FIELD, CSRF_HEADER},
+    handlers::{error::ApiError, path_id::PathId, validation_error, workspace::WorkspaceScope},
+    repositories::{
+        label::LabelRepository,
+        todo::{CreateTodo, Todo, TodoRepository, UpdateTodo},
+        user::UserRepository,
+    },
+    state::AppState,
+};
+
+mod fragments;
+
+/// Files under `/app/static`, built into the binary so embedding the app
+/// needs nothing on disk.
+const ASSETS: &[(&str, &str, &str)] = &[("app.css", "text/css", include_str!("web/app.css"))];
+
+/// Server-rendered pages over the same repositories as the JSON API. They
+/// act in the workspace and as the user `WorkspaceScope` resolves, which in
+/// a browser means the default workspace and the session cookie's user.
+/// The forms carry the session's CSRF token, which the `csrf` middleware
+/// checks.
+pub fn web_routes<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+) -> Router<AppState<T, L, U>> {
+    Router::new()
+        .route("/app", get(todo_page::<T, L, U>))
+        .route("/app/todos", post(create_todo::<T, L, U>))
+        .route("/app/todos/:id/toggle", post(toggle_todo::<T, L, U>))
+        .route("/app/todos/:id/delete", post(delete_todo::<T, L, U>))
+        .route("/app/static/:file", get(asset))
+        .merge(fragment_routes::<T, L, U>())
+}
+
+/// Form body of `POST /app/todos`.
+#[derive(Debug, Deserialize)]
+pub struct NewTodo {
+    text: String,
+}
+
+impl NewTodo {
+    fn payload(&self) -> Result<CreateTodo, ApiError> {
+        let payload = CreateTodo::new(self.text.trim().to_string());
+        payload.validate().map_err(validation_error)?;
+        Ok(payload)
+    }
+}
+
+/// Errors as a page instead of the API's JSON body.
+pub struct PageError(ApiError);
+
+impl From<ApiError> for PageError {
+    fn from(error: ApiError) -> Self {
+        Self(error)
+    }
+}
+
+impl From<anyhow::Error> for PageError {
+    fn from(error: anyhow::Error) -> Self {
+        Self(error.into())
+    }
+}
+
+impl IntoResponse for PageError {
+    fn into_response(self) -> Response {
+        let body = self.0.body();
+        let page = layout(
+            "Error",
+            None,
+            html! {
+                p.error { (body.message) }
+                p { a href="/app" { "Back to the list" } }
+            },
+        );
+        (self.0.status(), page).into_response()
+    }
+}
+
+async fn todo_page<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    CsrfToken(csrf): CsrfToken,
+) -> Result<Markup, PageError> {
+    let todos = scope.todos(&state).all().await?;
+    Ok(list(&scope, &todos, csrf.as_deref(), None))
+}
+
+async fn create_todo<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    CsrfToken(csrf): CsrfToken,
+    Form(form): Form<NewTodo>,
+) -> Result<Response, PageError> {
+    let payload = match form.payload() {
+        Ok(payload) => payload,
+        Err(e) => {
+            let todos = scope.todos(&state).all().await?;
+            let page = list(&scope, &todos, csrf.as_deref(), Some(&e));
+            return Ok((StatusCode::UNPROCESSABLE_ENTITY, page).into_response());
+        }
+    };
+    create(&state, &scope, payload).await?;
+
+    Ok(Redirect::to("/app").into_response())
+}
+
+async fn toggle_todo<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path(id): Path<PathId>,
+) -> Result<Redirect, PageError> {
+    let id = scope.todo_id(&state, id).await?;
+    toggle(&state, &scope, id).await?;
+    Ok(Redirect::to("/app"))
+}
+
+async fn delete_todo<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path(id): Path<PathId>,
+) -> Result<Redirect, PageError> {
+    let id = scope.todo_id(&state, id).await?;
+    delete(&state, &scope, id).await?;
+    Ok(Redirect::to("/app"))
+}
+
+async fn create<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    state: &AppState<T, L, U>,
+    scope: &WorkspaceScope,
+    mut payload: CreateTodo,
+) -> Result<Todo, ApiError> {
+    payload.owner_id = scope.user.map(|user| user.id);
+    let todos = scope.todos(state);
+    state
+        .quotas
+        .check_open_todos(&todos, payload.owner_id)
+        .await?;
+    let todo = todos.create(payload).await?;
+    Ok(todo)
+}
+
+/// Completes an open todo or reopens a completed one.
+async fn toggle<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    state: &AppState<T, L, U>,
+    scope: &WorkspaceScope,
+    id: i32,
+) -> Result<Todo, ApiError> {
+    let todos = scope.todos(state);
+    let current = todos.find(id).await?;
+    scope.check_may_change(state, &current).await?;
+    let completed = Some(!current.completed);
+    state
+        .quotas
+        .check_reopen(&todos, &current, completed)
+        .await?;
+    let todo = todos
+        .update(
+            id,
+            UpdateTodo {
+                text: None,
+                completed,
+                labels: None,
+                assignee_id: None,
+                text_i18n: None,
+                estimate_minutes: None,
+                due_date: None,
+                description: None,
+                custom: None,
+            },
+        )
+        .await?;
+    Ok(todo)
+}
+
+async fn delete<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    state: &AppState<T, L, U>,
+    scope: &WorkspaceScope,
+    id: i32,
+) -> Result<(), ApiError> {
+    let todos = scope.todos(state);
+    scope
+        .check_may_change(state, &todos.find(id).await?)
+        .await?;
+    todos.delete(id).await?;
+    Ok(())
+}
+
+async fn asset(Path(file): Path<String>) -> Response {
+    match ASSETS.iter().find(|(name, _, _)| *name == file) {
+        Some((_, content_type, body)) => (
+            [
+                (header::CONTENT_TYPE, *content_type),
+                (header::CACHE_CONTROL, "public, max-age=3600"),
+            ],
+            *body,
+        )
+            .into_response(),
+        None => ApiError::not_found(format!("No asset {}", file)).into_response(),
+    }
+}
+
+fn list(
+    scope: &WorkspaceScope,
+    todos: &[Todo],
+    csrf: Option<&str>,
+    error: Option<&ApiError>,
+) -> Markup {
+    layout(
+        "Todos",
+        csrf,
+        html! {
+            form.new method="post" action="/app/todos"
+                hx-post="/app/fragments/todos" hx-target="#todos" hx-swap="afterbegin" {
+                (csrf_field(csrf))
+                input type="text" name="text" maxlength="100" placeholder="What needs doing?" required autofocus;
+                button type="submit" { "Add" }
+            }
+            @if let Some(error) = error {
+                p.error { (error.body().message) }
+            }
+            (list_section(scope, todos, csrf))
+        },
+    )
+}
+
+/// What `/app/fragments/todos` answers.
+fn list_section(scope: &WorkspaceScope, todos: &[Todo], csrf: Option<&str>) -> Markup {
+    html! {
+        section #todo-list {
+            @if todos.is_empty() {
+                p { "Nothing to do." }
+            }
+            ul.todos #todos {
+                @for todo in todos {
+                    (row(scope, todo, csrf))
+                }
+            }
+        }
+    }
+}
+
+/// One todo. The forms work as they are; with htmx loaded their `hx-*`
+/// attributes swap in the fragment answers instead of reloading the page.
+fn row(scope: &WorkspaceScope, todo: &Todo, csrf: Option<&str>) -> Markup {
+    let id = scope.path_id(todo.id, todo.uuid);
+    html! {
+        li id={ "todo-" (todo.id) } class=[todo.completed.then_some("completed")] {
+            form method="post" action={ "/app/todos/" (id) "/toggle" }
+                hx-post={ "/app/fragments/todos/" (id) "/toggle" }
+                hx-target="closest li" hx-swap="outerHTML" {
+                (csrf_field(csrf))
+                button type="submit" title="Toggle" {
+                    @if todo.completed { "Reopen" } @else { "Done" }
+                }
+            }
+            span.text { (todo.text) }
+            @for label in &todo.labels {
+                span.label { (label.name) }
+            }
+            form method="post" action={ "/app/todos/" (id) "/delete" }
+                hx-delete={ "/app/fragments/todos/" (id) }
+                hx-target="closest li" hx-swap="outerHTML" {
+                (csrf_field(csrf))
+                button type="submit" { "Delete" }
+            }
+        }
+    }
+}
+
+/// The hidden field that lets a plain form post pass the `csrf` middleware.
+fn csrf_field(csrf: Option<&str>) -> Markup {
+    html! {
+        @if let Some(token) = csrf {
+            input type="hidden" name=(CSRF_FIELD) value=(token);
+        }
+    }
+}
+
+/// htmx sends the token as a header too, since `hx-delete` sends no body.
+fn layout(title: &str, csrf: Option<&str>, content: Markup) -> Markup {
+    html! {
+        (DOCTYPE)
+        html lang="en" {
+            head {
+                meta charset="utf-8";
+                meta name="viewport" content="width=device-width, initial-scale=1";
+                title { (title) " - my-todo" }
+                link rel="stylesheet" href="/app/static/app.css";
+            }
+            body hx-headers=[csrf.map(|token| json!({ CSRF_HEADER.as_str(): token }).to_string())] {
+                h1 { (title) }
+                (content)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        config::Config,
+        create_app,
+        repositories::{
+            label::LabelRepositoryForMemory,
+            todo::{TodoReader, TodoRepositoryForMemory},
+            user::UserRepositoryForMemory,
+        },
+    };
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    fn app(todos: TodoRepositoryForMemory) -> Router {
+        create_app(
+            todos,
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        )
+    }
+
+    async fn send(app: Router, method: &str, uri: &str, form: &str) -> (StatusCode, String) {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(form.to_string()))
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        let status = res.status();
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        (status, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn should_manage_todos_through_pages() {
+        let todos = TodoRepositoryForMemory::new();
+
+        let (status, _) = send(
+            app(todos.clone()),
+            "POST",
+            "/app/todos",
+            "text=buy+%3Cmilk%3E",
+        )
+        .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let (status, page) = send(app(todos.clone()), "GET", "/app", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(page.contains("buy &lt;milk&gt;"), "{}", page);
+
+        let (status, _) = send(app(todos.clone()), "POST", "/app/todos/1/toggle", "").await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        assert!(todos.find(1).await.unwrap().completed);
+
+        let (status, page) = send(app(todos.clone()), "POST", "/app/todos", "text=+").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(page.contains("can not be empty"), "{}", page);
+
+        let (status, _) = send(app(todos.clone()), "POST", "/app/todos/1/delete", "").await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        assert!(!todos.exists(1).await.unwrap());
+        let (status, page) = send(app(todos), "POST", "/app/todos/1/delete", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(page.contains("<!DOCTYPE html>"));
+    }
+
+    #[tokio::test]
+    async fn should_serve_static_assets() {
+        let (status, css) = send(
+            app(TodoRepositoryForMemory::new()),
+            "GET",
+            "/app/static/app.css",
+            "",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(css.contains("ul.todos"));
+        let (status, _) = send(
+            app(TodoRepositoryForMemory::new()),
+            "GET",
+            "/app/static/nope.js",
+            "",
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,224 @@
+//! Pieces of the `/app` pages for htmx: the list section and single rows.
+//! Changes answer with the changed row (or nothing, for a delete) and name
+//! what happened in `HX-Trigger`, e.g. `{"todoCreated":{"id":3}}`, so other
+//! parts of a page can refresh themselves.
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Form, Router,
+};
+use maud::{html, Markup};
+use serde_json::json;
+
+use super::{create, delete, list_section, row, toggle, NewTodo};
+use crate::{
+    auth::csrf::CsrfToken,
+    handlers::{error::ApiError, path_id::PathId, workspace::WorkspaceScope},
+    repositories::{label::LabelRepository, todo::TodoRepository, user::UserRepository},
+    state::AppState,
+};
+
+const HX_TRIGGER: HeaderName = HeaderName::from_static("hx-trigger");
+
+pub fn fragment_routes<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+) -> Router<AppState<T, L, U>> {
+    Router::new()
+        .route(
+            "/app/fragments/todos",
+            get(list_fragment::<T, L, U>).post(create_fragment::<T, L, U>),
+        )
+        .route(
+            "/app/fragments/todos/:id",
+            get(row_fragment::<T, L, U>).delete(delete_fragment::<T, L, U>),
+        )
+        .route(
+            "/app/fragments/todos/:id/toggle",
+            post(toggle_fragment::<T, L, U>),
+        )
+}
+
+/// Errors as a fragment; htmx leaves the page alone on error statuses, so
+/// pages pick these up through `htmx:responseError`.
+pub struct FragmentError(ApiError);
+
+impl From<ApiError> for FragmentError {
+    fn from(error: ApiError) -> Self {
+        Self(error)
+    }
+}
+
+impl From<anyhow::Error> for FragmentError {
+    fn from(error: anyhow::Error) -> Self {
+        Self(error.into())
+    }
+}
+
+impl IntoResponse for FragmentError {
+    fn into_response(self) -> Response {
+        let body = self.0.body();
+        let status = match self.0.status() {
+            // the form's value, not the request, was at fault
+            StatusCode::BAD_REQUEST => StatusCode::UNPROCESSABLE_ENTITY,
+            status => status,
+        };
+        (status, html! { p.error { (body.message) } }).into_response()
+    }
+}
+
+/// `markup` with an `HX-Trigger` naming `event` for todo `id`.
+fn triggering(event: &str, id: i32, markup: Markup) -> Response {
+    let trigger = json!({ event: { "id": id } }).to_string();
+    let trigger = HeaderValue::from_str(&trigger).expect("json is a valid header value");
+    ([(HX_TRIGGER, trigger)], markup).into_response()
+}
+
+async fn list_fragment<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    CsrfToken(csrf): CsrfToken,
+) -> Result<Markup, FragmentError> {
+    Ok(list_section(
+        &scope,
+        &scope.todos(&state).all().await?,
+        csrf.as_deref(),
+    ))
+}
+
+async fn row_fragment<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path(id): Path<PathId>,
+    CsrfToken(csrf): CsrfToken,
+) -> Result<Markup, FragmentError> {
+    let id = scope.todo_id(&state, id).await?;
+    Ok(row(
+        &scope,
+        &scope.todos(&state).find(id).await?,
+        csrf.as_deref(),
+    ))
+}
+
+async fn create_fragment<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    CsrfToken(csrf): CsrfToken,
+    Form(form): Form<NewTodo>,
+) -> Result<Response, FragmentError> {
+    let todo = create(&state, &scope, form.payload()?).await?;
+    Ok(triggering(
+        "todoCreated",
+        todo.id,
+        row(&scope, &todo, csrf.as_deref()),
+    ))
+}
+
+async fn toggle_fragment<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path(id): Path<PathId>,
+    CsrfToken(csrf): CsrfToken,
+) -> Result<Response, FragmentError> {
+    let id = scope.todo_id(&state, id).await?;
+    let todo = toggle(&state, &scope, id).await?;
+    Ok(triggering(
+        "todoUpdated",
+        todo.id,
+        row(&scope, &todo, csrf.as_deref()),
+    ))
+}
+
+async fn delete_fragment<T: TodoRepository, L: LabelRepository, U: UserRepository>(
+    State(state): State<AppState<T, L, U>>,
+    scope: WorkspaceScope,
+    Path(id): Path<PathId>,
+) -> Result<Response, FragmentError> {
+    let id = scope.todo_id(&state, id).await?;
+    delete(&state, &scope, id).await?;
+    // an empty 200 makes htmx swap the row out; 204 would leave it
+    Ok(triggering("todoDeleted", id, html! {}))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        config::Config,
+        create_app,
+        repositories::{
+            label::LabelRepositoryForMemory,
+            todo::{TodoReader, TodoRepositoryForMemory},
+            user::UserRepositoryForMemory,
+        },
+    };
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    async fn send(
+        todos: &TodoRepositoryForMemory,
+        method: &str,
+        uri: &str,
+        form: &str,
+    ) -> (StatusCode, Option<String>, String) {
+        let app = create_app(
+            todos.clone(),
+            LabelRepositoryForMemory::new(),
+            UserRepositoryForMemory::new(),
+            Config::default(),
+        );
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/x-www-form-urlencoded")
+            .header("hx-request", "true")
+            .body(Body::from(form.to_string()))
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        let status = res.status();
+        let trigger = res
+            .headers()
+            .get(HX_TRIGGER)
+            .map(|value| value.to_str().unwrap().to_string());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        (status, trigger, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn should_answer_mutations_with_rows_and_triggers() {
+        let todos = TodoRepositoryForMemory::new();
+
+        let (status, trigger, body) =
+            send(&todos, "POST", "/app/fragments/todos", "text=milk").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(trigger.as_deref(), Some(r#"{"todoCreated":{"id":1}}"#));
+        assert!(body.starts_with(r#"<li id="todo-1">"#), "{}", body);
+
+        let (_, trigger, body) = send(&todos, "POST", "/app/fragments/todos/1/toggle", "").await;
+        assert_eq!(trigger.as_deref(), Some(r#"{"todoUpdated":{"id":1}}"#));
+        assert!(
+            body.starts_with(r#"<li id="todo-1" class="completed">"#),
+            "{}",
+            body
+        );
+
+        let (_, trigger, body) = send(&todos, "GET", "/app/fragments/todos", "").await;
+        assert_eq!(trigger, None);
+        assert!(body.starts_with(r#"<section id="todo-list">"#), "{}", body);
+        assert!(!body.contains("<html"));
+
+        let (status, trigger, body) = send(&todos, "DELETE", "/app/fragments/todos/1", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(trigger.as_deref(), Some(r#"{"todoDeleted":{"id":1}}"#));
+        assert!(body.is_empty());
+        assert!(!todos.exists(1).await.unwrap());
+
+        let (status, _, body) = send(&todos, "GET", "/app/fragments/todos/1", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(body.starts_with(r#"<p class="error">"#), "{}", body);
+        let (status, trigger, _) = send(&todos, "POST", "/app/fragments/todos", "text=").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(trigger, None);
+    }
+}
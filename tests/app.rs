@@ -0,0 +1,85 @@
+use axum::{body::Body, http::Request};
+use hyper::{Method, StatusCode};
+use my_todo::{
+    config::Config,
+    create_app,
+    repositories::{
+        label::LabelRepositoryForMemory,
+        todo::{CreateTodo, Todo, TodoRepositoryForMemory, TodoWriter},
+        user::UserRepositoryForMemory,
+    },
+};
+use tower::ServiceExt;
+
+#[tokio::test]
+async fn should_embed_app_with_memory_repositories() {
+    let repository = TodoRepositoryForMemory::new();
+    let created = repository
+        .create(CreateTodo::new("embedded".to_string()))
+        .await
+        .expect("failed create todo");
+
+    let req = Request::builder()
+        .uri("/todos/1")
+        .method(Method::GET)
+        .body(Body::empty())
+        .unwrap();
+    let res = create_app(
+        repository,
+        LabelRepositoryForMemory::new(),
+        UserRepositoryForMemory::new(),
+        Config::default(),
+    )
+    .oneshot(req)
+    .await
+    .unwrap();
+    assert_eq!(StatusCode::OK, res.status());
+
+    let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    let todo: Todo = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(created, todo);
+}
+
+#[cfg(feature = "test-util")]
+#[tokio::test]
+async fn should_embed_app_with_mock_repositories() {
+    use my_todo::{MockLabelRepository, MockTodoRepository};
+
+    let mut repository = MockTodoRepository::new();
+    repository
+        .expect_claim_outbox()
+        .returning(|_| Ok(Vec::new()));
+    repository.expect_in_workspace().returning(|workspace_id| {
+        assert_eq!(workspace_id, 1);
+        let mut scoped = MockTodoRepository::new();
+        scoped.expect_acting_as().returning(|_| {
+            let mut acting = MockTodoRepository::new();
+            acting
+                .expect_find()
+                .withf(|id| *id == 7)
+                .returning(|id| Ok(Todo::new(id, "mocked".to_string())));
+            acting
+        });
+        scoped
+    });
+
+    let req = Request::builder()
+        .uri("/todos/7")
+        .method(Method::GET)
+        .body(Body::empty())
+        .unwrap();
+    let res = create_app(
+        repository,
+        MockLabelRepository::new(),
+        UserRepositoryForMemory::new(),
+        Config::default(),
+    )
+    .oneshot(req)
+    .await
+    .unwrap();
+    assert_eq!(StatusCode::OK, res.status());
+
+    let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    let todo: Todo = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(Todo::new(7, "mocked".to_string()), todo);
+}